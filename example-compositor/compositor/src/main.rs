@@ -107,6 +107,7 @@ impl webrender::Compositor for DirectCompositeInterface {
         id: webrender::NativeSurfaceId,
         transform: CompositorSurfaceTransform,
         clip_rect: DeviceIntRect,
+        _clip_radius: webrender::ClipRadius,
         _image_rendering: ImageRendering,
     ) {
         compositor::add_surface(
@@ -128,7 +129,7 @@ impl webrender::Compositor for DirectCompositeInterface {
         &mut self,
         _device: &mut Device,
         _id: webrender::NativeSurfaceId,
-        _: bool,
+        _: webrender::CompositeSurfaceFormat,
     ) {
         todo!()
     }
@@ -432,6 +433,7 @@ fn main() {
     let compositor_config = if enable_compositor {
         webrender::CompositorConfig::Native {
             compositor: Box::new(DirectCompositeInterface::new(window)),
+            max_tiles_per_frame: None,
         }
     } else {
         webrender::CompositorConfig::Draw {
@@ -0,0 +1,71 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Compares the hashers that can back the resource and glyph caches (see the
+//! `std_resource_hasher` feature) on large sets of image keys.
+//!
+//! Run with `cargo bench --bench resource_hash`.
+
+use std::collections::HashMap;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, BuildHasherDefault};
+use std::time::{Duration, Instant};
+
+use api::{IdNamespace, ImageKey};
+use fxhash::FxHasher;
+
+const KEY_COUNT: u32 = 500_000;
+const ITERATIONS: u32 = 10;
+
+/// Image keys as typically allocated: a handful of namespaces, each handing
+/// out sequential ids.
+fn sequential_keys() -> Vec<ImageKey> {
+    (0 .. KEY_COUNT)
+        .map(|i| ImageKey::new(IdNamespace(i % 4), i / 4))
+        .collect()
+}
+
+/// Keys whose ids only differ in their high bits, which FxHash mixes poorly.
+fn strided_keys() -> Vec<ImageKey> {
+    (0 .. KEY_COUNT)
+        .map(|i| ImageKey::new(IdNamespace(1), i.reverse_bits()))
+        .collect()
+}
+
+fn run<S: BuildHasher + Default>(keys: &[ImageKey]) -> Duration {
+    let start = Instant::now();
+
+    for _ in 0 .. ITERATIONS {
+        let mut map: HashMap<ImageKey, u32, S> = HashMap::default();
+        for (i, key) in keys.iter().enumerate() {
+            map.insert(*key, i as u32);
+        }
+
+        let mut sum = 0u64;
+        for key in keys {
+            sum += *map.get(key).unwrap() as u64;
+        }
+        assert_eq!(sum, (keys.len() as u64 - 1) * keys.len() as u64 / 2);
+    }
+
+    start.elapsed() / ITERATIONS
+}
+
+fn main() {
+    for (name, keys) in [
+        ("sequential", sequential_keys()),
+        ("strided", strided_keys()),
+    ] {
+        let fx = run::<BuildHasherDefault<FxHasher>>(&keys);
+        let sip = run::<RandomState>(&keys);
+
+        println!(
+            "{:>10} ({} keys): fxhash {:>8.2}ms, siphash {:>8.2}ms",
+            name,
+            keys.len(),
+            fx.as_secs_f64() * 1000.0,
+            sip.as_secs_f64() * 1000.0,
+        );
+    }
+}
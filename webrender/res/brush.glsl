@@ -89,6 +89,7 @@ void text_shader_main(
 #define BRUSH_FLAG_TEXEL_RECT                 512
 #define BRUSH_FLAG_FORCE_AA                  1024
 #define BRUSH_FLAG_NORMALIZED_UVS            2048
+#define BRUSH_FLAG_SAMPLER_WRAP              4096
 
 #define INVALID_SEGMENT_INDEX                   0xffff
 
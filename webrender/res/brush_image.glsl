@@ -21,7 +21,8 @@ flat varying highp vec4 v_uv_bounds;
 // sampling artifacts.
 flat varying highp vec4 v_uv_sample_bounds;
 
-// Flag to allow perspective interpolation of UV.
+// Flag to allow perspective interpolation of UV, and flag to let the
+// sampler wrap the UVs rather than clamping them to the image bounds.
 // Packed in to vector to work around bug 1630356.
 flat varying mediump vec2 v_perspective;
 
@@ -169,6 +170,7 @@ void brush_vs(
 
     float perspective_interpolate = (brush_flags & BRUSH_FLAG_PERSPECTIVE_INTERPOLATION) != 0 ? 1.0 : 0.0;
     v_perspective.x = perspective_interpolate;
+    v_perspective.y = (brush_flags & BRUSH_FLAG_SAMPLER_WRAP) != 0 ? 1.0 : 0.0;
 
     if ((brush_flags & BRUSH_FLAG_NORMALIZED_UVS) != 0) {
         uv0 *= texture_size;
@@ -353,8 +355,12 @@ Fragment brush_fs() {
     float perspective_divisor = mix(gl_FragCoord.w, 1.0, v_perspective.x);
     vec2 repeated_uv = compute_repeated_uvs(perspective_divisor);
 
-    // Clamp the uvs to avoid sampling artifacts.
-    vec2 uv = clamp(repeated_uv, v_uv_sample_bounds.xy, v_uv_sample_bounds.zw);
+    // Clamp the uvs to avoid sampling artifacts, unless the sampler is
+    // responsible for wrapping them.
+    vec2 uv = repeated_uv;
+    if (v_perspective.y == 0.0) {
+        uv = clamp(uv, v_uv_sample_bounds.xy, v_uv_sample_bounds.zw);
+    }
 
     vec4 texel = TEX_SAMPLE(sColor0, uv);
 
@@ -395,6 +401,12 @@ void swgl_drawSpanRGBA8() {
         }
     #endif
 
+    // The span commits clamp to the sample bounds, so leave sampler
+    // wrapping to the fragment shader.
+    if (v_perspective.y != 0.0) {
+        return;
+    }
+
     float perspective_divisor = mix(swgl_forceScalar(gl_FragCoord.w), 1.0, v_perspective.x);
 
     #ifdef WR_FEATURE_REPETITION
@@ -41,6 +41,17 @@ uniform mediump vec2 uTextureSize;
 #endif
 #endif
 
+#ifndef WR_FEATURE_FAST_PATH
+flat varying mediump float vSaturation;
+// 1.0 if the output color is un-premultiplied.
+flat varying mediump float vStraightAlpha;
+// Offset of the fragment from the vignette center, the vignette inner and
+// outer radii, and the premultiplied vignette color.
+varying highp vec2 vVignettePos;
+flat varying highp vec2 vVignetteRadii;
+flat varying mediump vec4 vVignetteColor;
+#endif
+
 #ifdef WR_VERTEX_SHADER
 // CPU side data is in CompositeInstance (gpu_types.rs) and is
 // converted to GPU data using desc::COMPOSITE (renderer.rs) by
@@ -50,6 +61,9 @@ PER_INSTANCE attribute vec4 aDeviceClipRect;
 PER_INSTANCE attribute vec4 aColor;
 PER_INSTANCE attribute vec4 aParams;
 PER_INSTANCE attribute vec2 aFlip;
+PER_INSTANCE attribute float aZ;
+PER_INSTANCE attribute vec4 aVignette;
+PER_INSTANCE attribute vec4 aVignetteColor;
 
 #ifdef WR_FEATURE_YUV
 // YUV treats these as a UV clip rect (clamp)
@@ -155,7 +169,19 @@ void main(void) {
 #endif
 #endif
 
-    gl_Position = uTransform * vec4(clipped_world_pos, 0.0, 1.0);
+#ifndef WR_FEATURE_FAST_PATH
+    vSaturation = aParams.x;
+#ifdef WR_FEATURE_YUV
+    vStraightAlpha = 0.0;
+#else
+    vStraightAlpha = aParams.z;
+#endif
+    vVignettePos = clipped_world_pos - aVignette.xy;
+    vVignetteRadii = aVignette.zw;
+    vVignetteColor = aVignetteColor;
+#endif
+
+    gl_Position = uTransform * vec4(clipped_world_pos, aZ, 1.0);
 }
 #endif
 
@@ -188,11 +214,46 @@ void main(void) {
     vec4 color = vColor * texel;
 #endif
 #endif
+
+#ifndef WR_FEATURE_FAST_PATH
+    // Luminance scales with alpha, so the saturation can be applied directly
+    // to the premultiplied color.
+    if (vSaturation != 1.0) {
+        float luminance = dot(color.rgb, vec3(0.2126, 0.7152, 0.0722));
+        color.rgb = mix(vec3(luminance), color.rgb, vSaturation);
+    }
+
+    // The vignette is applied last. Scaling its color by the alpha of the
+    // content keeps the result premultiplied and leaves the alpha unchanged,
+    // so blending vignetted tiles gives the same result as vignetting the
+    // blended tiles.
+    if (vVignetteColor.a != 0.0) {
+        float amount = clamp(
+            (length(vVignettePos) - vVignetteRadii.x) / max(vVignetteRadii.y - vVignetteRadii.x, 0.001),
+            0.0,
+            1.0
+        );
+        color = color * (1.0 - amount * vVignetteColor.a) + vVignetteColor * (amount * color.a);
+    }
+
+    if (vStraightAlpha != 0.0) {
+        color.rgb = color.a > 0.0 ? color.rgb / color.a : vec3(0.0);
+    }
+#endif
+
     write_output(color);
 }
 
 #ifdef SWGL_DRAW_SPAN
 void swgl_drawSpanRGBA8() {
+#ifndef WR_FEATURE_FAST_PATH
+    // Saturation, vignettes and straight alpha aren't supported by the span
+    // commits, use the fragment shader.
+    if (vSaturation != 1.0 || vVignetteColor.a != 0.0 || vStraightAlpha != 0.0) {
+        return;
+    }
+#endif
+
 #ifdef WR_FEATURE_YUV
     if (vYuvFormat.x == YUV_FORMAT_PLANAR) {
         swgl_commitTextureLinearYUV(sColor0, vUV_y, vUVBounds_y,
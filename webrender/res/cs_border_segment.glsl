@@ -374,7 +374,8 @@ void main(void) {
             // +---+---+---+---+
             // |xxx|   |   |xxx|
             // +---+---+---+---+
-            float pos = is_vertical ? vPos.y : vPos.x;
+            // The dash phase offset is in z, and wraps around the period.
+            float pos = mod((is_vertical ? vPos.y : vPos.x) + vClipParams1.z, 4.0 * half_dash);
             bool in_dash = pos < half_dash || pos > 3.0 * half_dash;
             if (!in_dash) {
                 d = 1.;
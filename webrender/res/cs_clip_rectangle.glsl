@@ -17,7 +17,7 @@ flat varying highp vec3 vClipPlane_TR;
 flat varying highp vec3 vClipPlane_BL;
 flat varying highp vec3 vClipPlane_BR;
 #endif
-// Clip mode. Packed in to a vector to work around bug 1630356.
+// x = clip mode, y = AA quality. Packed in to a vector to work around bug 1630356.
 flat varying mediump vec2 vClipMode;
 
 #ifdef WR_VERTEX_SHADER
@@ -25,6 +25,7 @@ flat varying mediump vec2 vClipMode;
 PER_INSTANCE in vec2 aClipLocalPos;
 PER_INSTANCE in vec4 aClipLocalRect;
 PER_INSTANCE in float aClipMode;
+PER_INSTANCE in float aClipAaQuality;
 PER_INSTANCE in vec4 aClipRect_TL;
 PER_INSTANCE in vec4 aClipRadii_TL;
 PER_INSTANCE in vec4 aClipRect_TR;
@@ -100,6 +101,7 @@ void main(void) {
     );
 
     vClipMode.x = clip.rect.mode;
+    vClipMode.y = aClipAaQuality;
     vLocalPos = vi.local_pos;
 
 #ifdef WR_FEATURE_FAST_PATH
@@ -167,14 +169,11 @@ float sd_rounded_box(in vec2 pos, in vec2 box_size, in float radius) {
 }
 #endif
 
-void main(void) {
-    vec2 local_pos = vLocalPos.xy / vLocalPos.w;
-    float aa_range = compute_aa_range(local_pos);
-
+float clip_distance(vec2 local_pos) {
 #ifdef WR_FEATURE_FAST_PATH
-    float dist = sd_rounded_box(local_pos, vClipParams.xy, vClipParams.z);
+    return sd_rounded_box(local_pos, vClipParams.xy, vClipParams.z);
 #else
-    float dist = distance_to_rounded_rect(
+    return distance_to_rounded_rect(
         local_pos,
         vClipPlane_TL,
         vClipCenter_Radius_TL,
@@ -187,9 +186,26 @@ void main(void) {
         vTransformBounds
     );
 #endif
+}
+
+void main(void) {
+    vec2 local_pos = vLocalPos.xy / vLocalPos.w;
+    float aa_range = compute_clip_aa_range(local_pos);
 
     // Compute AA for the given dist and range.
-    float alpha = distance_aa(aa_range, dist);
+    float alpha;
+    if (vClipMode.y != 0.0) {
+        // High quality AA averages four samples within the pixel.
+        vec2 dx = dFdx(local_pos);
+        vec2 dy = dFdy(local_pos);
+        alpha = 0.0;
+        for (int i = 0; i < 4; i++) {
+            vec2 sample_pos = local_pos + clip_aa_sample_offset(i, dx, dy);
+            alpha += 0.25 * distance_aa(2.0 * aa_range, clip_distance(sample_pos));
+        }
+    } else {
+        alpha = distance_aa(aa_range, clip_distance(local_pos));
+    }
 
     // Select alpha or inverse alpha depending on clip in/out.
     float final_alpha = mix(alpha, 1.0 - alpha, vClipMode.x);
@@ -221,8 +237,8 @@ void main(void) {
 // both ways of operating - the uniform radius fast-path and the varying radius
 // slow-path.
 void swgl_drawSpanR8() {
-    // Perspective is not supported.
-    if (swgl_interpStep(vLocalPos).w != 0.0) {
+    // Perspective and high quality AA are not supported.
+    if (swgl_interpStep(vLocalPos).w != 0.0 || vClipMode.y != 0.0) {
         return;
     }
 
@@ -248,9 +264,12 @@ void swgl_drawSpanR8() {
     // essentially the scale of how much local-space maps to an AA pixel. We
     // need to know the inverse, how much local-space we traverse per AA pixel
     // pixel step. We then scale this to represent the amount of span steps
-    // traversed per AA pixel step.
-    float aa_range = compute_aa_range(local_pos);
-    float aa_margin = inversesqrt(aa_range * aa_range * step_scale);
+    // traversed per AA pixel step. The AA region extends half the edge AA
+    // feather from the edge, to which we add half a pixel since intersections
+    // are computed at pixel centers.
+    float aa_range = compute_clip_aa_range(local_pos);
+    float pixel_steps = inversesqrt(aa_range * aa_range * step_scale) / uEdgeAaFeather;
+    float aa_margin = (0.5 * uEdgeAaFeather + 0.5) * pixel_steps;
 
     // We need to know the bounds of the aligned rectangle portion of the rrect
     // in local-space. If we're using the fast-path, this is specified as the
@@ -20,6 +20,7 @@ flat varying highp vec4 vClipPlane_A;
 flat varying highp vec4 vClipPlane_B;
 flat varying highp vec4 vClipPlane_C;
 #endif
+// x = clip mode, y = AA quality.
 flat varying highp vec2 vClipMode;
 
 #ifdef WR_VERTEX_SHADER
@@ -82,6 +83,7 @@ void pattern_vertex(PrimitiveInfo prim_info) {
 #endif
 
     vClipMode.x = clip.mode;
+    vClipMode.y = float(aClipData.w);
 
 #ifdef WR_FEATURE_FAST_PATH
     // If the radii are all uniform, we can use a much simpler 2d
@@ -151,19 +153,16 @@ float sd_rounded_box(in vec2 pos, in vec2 box_size, in float radius) {
 }
 #endif
 
-vec4 pattern_fragment(vec4 _base_color) {
-    vec2 clip_local_pos = vClipLocalPos.xy / vClipLocalPos.w;
-    float aa_range = compute_aa_range(clip_local_pos);
-
+float clip_distance(vec2 clip_local_pos) {
 #ifdef WR_FEATURE_FAST_PATH
-    float dist = sd_rounded_box(clip_local_pos, v_clip_params.xy, v_clip_params.z);
+    return sd_rounded_box(clip_local_pos, v_clip_params.xy, v_clip_params.z);
 #else
     vec3 plane_tl = vec3(vClipPlane_A.x, vClipPlane_A.y, vClipPlane_A.z);
     vec3 plane_tr = vec3(vClipPlane_A.w, vClipPlane_B.x, vClipPlane_B.y);
     vec3 plane_br = vec3(vClipPlane_B.z, vClipPlane_B.w, vClipPlane_C.x);
     vec3 plane_bl = vec3(vClipPlane_C.y, vClipPlane_C.z, vClipPlane_C.w);
 
-    float dist = distance_to_rounded_rect(
+    return distance_to_rounded_rect(
         clip_local_pos,
         plane_tl,
         vClipCenter_Radius_TL,
@@ -176,9 +175,26 @@ vec4 pattern_fragment(vec4 _base_color) {
         vTransformBounds
     );
 #endif
+}
+
+vec4 pattern_fragment(vec4 _base_color) {
+    vec2 clip_local_pos = vClipLocalPos.xy / vClipLocalPos.w;
+    float aa_range = compute_clip_aa_range(clip_local_pos);
 
     // Compute AA for the given dist and range.
-    float alpha = distance_aa(aa_range, dist);
+    float alpha;
+    if (vClipMode.y != 0.0) {
+        // High quality AA averages four samples within the pixel.
+        vec2 dx = dFdx(clip_local_pos);
+        vec2 dy = dFdy(clip_local_pos);
+        alpha = 0.0;
+        for (int i = 0; i < 4; i++) {
+            vec2 sample_pos = clip_local_pos + clip_aa_sample_offset(i, dx, dy);
+            alpha += 0.25 * distance_aa(2.0 * aa_range, clip_distance(sample_pos));
+        }
+    } else {
+        alpha = distance_aa(aa_range, clip_distance(clip_local_pos));
+    }
 
     // Select alpha or inverse alpha depending on clip in/out.
     float final_alpha = mix(alpha, 1.0 - alpha, vClipMode.x);
@@ -115,10 +115,14 @@ uniform bool u_mali_workaround_dummy;
 // fwidth is not defined in ESSL 1, but that's okay because we don't need
 // it for any ESSL 1 shader variants.
 #if __VERSION__ != 100
+    // Width of the anti-aliased edge in device pixels. See
+    // `WebRenderOptions::edge_aa_feather`.
+    uniform float uEdgeAaFeather;
+
     /// Find the appropriate half range to apply the AA approximation over.
     /// This range represents a coefficient to go from one CSS pixel to half a device pixel.
     vec2 compute_aa_range_xy(vec2 position) {
-        return fwidth(position);
+        return fwidth(position) * uEdgeAaFeather;
     }
 
     float compute_aa_range(vec2 position) {
@@ -145,13 +149,47 @@ uniform bool u_mali_workaround_dummy;
         #ifdef SWGL
             // SWGL uses an approximation for fwidth() such that it returns equal x and y.
             // Thus, sqrt(2)/length(w) = sqrt(2)/sqrt(x*x + x*x) = recip(x).
-            return recip(fwidth(position).x);
+            return recip(fwidth(position).x * uEdgeAaFeather);
         #else
             // sqrt(2)/length(w) = inversesqrt(0.5 * dot(w, w))
             vec2 w = fwidth(position);
-            return inversesqrt(0.5 * dot(w, w));
+            return inversesqrt(0.5 * dot(w, w)) / uEdgeAaFeather;
         #endif
     }
+
+    /// Like compute_aa_range(), but invariant under rotation, for clip masks.
+    ///
+    /// fwidth() sums the absolute derivatives of each axis, which widens the
+    /// AA range as a clip is rotated towards 45 degrees, so the edges of a
+    /// rotated clip don't get the same AA as axis-aligned ones. The length of
+    /// the local step per device pixel is the same whatever the rotation, and
+    /// matches compute_aa_range() exactly for axis-aligned transforms.
+    float compute_clip_aa_range(vec2 position) {
+        #ifdef SWGL
+            // SWGL's fwidth() approximation is already uniform, and the clip
+            // span shaders need to agree with this.
+            return compute_aa_range(position);
+        #else
+            vec2 dx = dFdx(position);
+            vec2 dy = dFdy(position);
+            return inversesqrt(0.5 * (dot(dx, dx) + dot(dy, dy))) / uEdgeAaFeather;
+        #endif
+    }
+
+    /// Returns the offset from the pixel center of one of the four samples
+    /// taken by high quality clip mask AA (see `ClipAaQuality::High`), given
+    /// the derivatives of the local position. The samples lie on a rotated
+    /// grid within the pixel, and are each filtered over half a pixel.
+    vec2 clip_aa_sample_offset(int index, vec2 dx, vec2 dy) {
+        if (index == 0) {
+            return -0.125 * dx - 0.375 * dy;
+        } else if (index == 1) {
+            return 0.375 * dx - 0.125 * dy;
+        } else if (index == 2) {
+            return 0.125 * dx + 0.375 * dy;
+        }
+        return -0.375 * dx + 0.125 * dy;
+    }
 #endif
 
     /// Return the blending coefficient for distance antialiasing.
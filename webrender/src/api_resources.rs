@@ -5,10 +5,13 @@
 use crate::api::{BlobImageKey, ImageDescriptor, DirtyRect, TileSize, DebugFlags};
 use crate::api::{BlobImageHandler, AsyncBlobImageRasterizer, BlobImageData, BlobImageParams};
 use crate::api::{BlobImageRequest, BlobImageDescriptor, FontTemplate};
+use crate::api::{BlobImageError, BlobImageResources, BlobImageResult, BlobTilePool};
+use crate::api::{FontInstanceKey, FontKey, IdNamespace};
 use crate::api::units::*;
 use glyph_rasterizer::{SharedFontResources, BaseFontInstance};
 use crate::render_api::{ResourceUpdate, TransactionMsg, AddFont};
 use crate::image_tiling::*;
+use crate::internal_types::FastHashMap;
 use crate::profiler;
 
 use std::collections::HashMap;
@@ -303,3 +306,306 @@ impl ApiResources {
         (Some(handler.create_blob_rasterizer()), blob_request_params)
     }
 }
+
+fn request_namespace(params: &BlobImageParams) -> IdNamespace {
+    (params.request.key.0).0
+}
+
+/// A blob image handler that forwards each blob to the handler registered for
+/// the namespace of its key, or to the default handler if there is none.
+///
+/// If neither exists, the blob is not rasterized: its requests fail with
+/// `BlobImageError::Other` and the resource cache skips them like any other
+/// blob that failed to rasterize.
+pub struct NamespacedBlobImageHandler {
+    default: Option<Box<dyn BlobImageHandler>>,
+    handlers: FastHashMap<IdNamespace, Box<dyn BlobImageHandler>>,
+}
+
+impl NamespacedBlobImageHandler {
+    /// Combine a default handler with per-namespace handlers. The default
+    /// handler is returned as is if there are no per-namespace handlers.
+    pub fn combine(
+        default: Option<Box<dyn BlobImageHandler>>,
+        handlers: FastHashMap<IdNamespace, Box<dyn BlobImageHandler>>,
+    ) -> Option<Box<dyn BlobImageHandler>> {
+        if handlers.is_empty() {
+            return default;
+        }
+
+        Some(Box::new(NamespacedBlobImageHandler {
+            default,
+            handlers,
+        }))
+    }
+
+    fn handler_for(&mut self, key: BlobImageKey) -> Option<&mut Box<dyn BlobImageHandler>> {
+        let namespace = (key.0).0;
+        if self.handlers.contains_key(&namespace) {
+            self.handlers.get_mut(&namespace)
+        } else {
+            self.default.as_mut()
+        }
+    }
+
+    fn all_handlers(&mut self) -> impl Iterator<Item = &mut Box<dyn BlobImageHandler>> {
+        self.default.iter_mut().chain(self.handlers.values_mut())
+    }
+}
+
+impl BlobImageHandler for NamespacedBlobImageHandler {
+    fn create_blob_rasterizer(&mut self) -> Box<dyn AsyncBlobImageRasterizer> {
+        Box::new(NamespacedBlobRasterizer {
+            default: self.default
+                .as_mut()
+                .map(|handler| handler.create_blob_rasterizer()),
+            rasterizers: self.handlers
+                .iter_mut()
+                .map(|(namespace, handler)| (*namespace, handler.create_blob_rasterizer()))
+                .collect(),
+        })
+    }
+
+    fn create_similar(&self) -> Box<dyn BlobImageHandler> {
+        Box::new(NamespacedBlobImageHandler {
+            default: self.default
+                .as_ref()
+                .map(|handler| handler.create_similar()),
+            handlers: self.handlers
+                .iter()
+                .map(|(namespace, handler)| (*namespace, handler.create_similar()))
+                .collect(),
+        })
+    }
+
+    fn prepare_resources(
+        &mut self,
+        services: &dyn BlobImageResources,
+        requests: &[BlobImageParams],
+    ) {
+        let NamespacedBlobImageHandler { ref mut default, ref mut handlers } = *self;
+
+        for (namespace, handler) in handlers.iter_mut() {
+            let handler_requests: Vec<BlobImageParams> = requests
+                .iter()
+                .filter(|params| request_namespace(params) == *namespace)
+                .cloned()
+                .collect();
+            if !handler_requests.is_empty() {
+                handler.prepare_resources(services, &handler_requests);
+            }
+        }
+
+        if let Some(handler) = default {
+            let default_requests: Vec<BlobImageParams> = requests
+                .iter()
+                .filter(|params| !handlers.contains_key(&request_namespace(params)))
+                .cloned()
+                .collect();
+            if !default_requests.is_empty() {
+                handler.prepare_resources(services, &default_requests);
+            }
+        }
+    }
+
+    fn add(
+        &mut self,
+        key: BlobImageKey,
+        data: Arc<BlobImageData>,
+        visible_rect: &DeviceIntRect,
+        tile_size: TileSize,
+    ) {
+        match self.handler_for(key) {
+            Some(handler) => handler.add(key, data, visible_rect, tile_size),
+            None => warn!("No blob image handler for {:?}", key),
+        }
+    }
+
+    fn update(
+        &mut self,
+        key: BlobImageKey,
+        data: Arc<BlobImageData>,
+        visible_rect: &DeviceIntRect,
+        dirty_rect: &BlobDirtyRect,
+    ) {
+        match self.handler_for(key) {
+            Some(handler) => handler.update(key, data, visible_rect, dirty_rect),
+            None => warn!("No blob image handler for {:?}", key),
+        }
+    }
+
+    fn delete(&mut self, key: BlobImageKey) {
+        if let Some(handler) = self.handler_for(key) {
+            handler.delete(key);
+        }
+    }
+
+    fn delete_font(&mut self, key: FontKey) {
+        for handler in self.all_handlers() {
+            handler.delete_font(key);
+        }
+    }
+
+    fn delete_font_instance(&mut self, key: FontInstanceKey) {
+        for handler in self.all_handlers() {
+            handler.delete_font_instance(key);
+        }
+    }
+
+    fn clear_namespace(&mut self, namespace: IdNamespace) {
+        for handler in self.all_handlers() {
+            handler.clear_namespace(namespace);
+        }
+    }
+
+    fn enable_multithreading(&mut self, enable: bool) {
+        for handler in self.all_handlers() {
+            handler.enable_multithreading(enable);
+        }
+    }
+}
+
+/// Splits rasterization requests between the rasterizers of the handlers
+/// of a `NamespacedBlobImageHandler`.
+struct NamespacedBlobRasterizer {
+    default: Option<Box<dyn AsyncBlobImageRasterizer>>,
+    rasterizers: FastHashMap<IdNamespace, Box<dyn AsyncBlobImageRasterizer>>,
+}
+
+impl AsyncBlobImageRasterizer for NamespacedBlobRasterizer {
+    fn rasterize(
+        &mut self,
+        requests: &[BlobImageParams],
+        low_priority: bool,
+        tile_pool: &mut BlobTilePool,
+    ) -> Vec<(BlobImageRequest, BlobImageResult)> {
+        let mut results = Vec::with_capacity(requests.len());
+
+        for (namespace, rasterizer) in self.rasterizers.iter_mut() {
+            let rasterizer_requests: Vec<BlobImageParams> = requests
+                .iter()
+                .filter(|params| request_namespace(params) == *namespace)
+                .cloned()
+                .collect();
+            if !rasterizer_requests.is_empty() {
+                results.extend(rasterizer.rasterize(&rasterizer_requests, low_priority, tile_pool));
+            }
+        }
+
+        let default_requests: Vec<BlobImageParams> = requests
+            .iter()
+            .filter(|params| !self.rasterizers.contains_key(&request_namespace(params)))
+            .cloned()
+            .collect();
+
+        match self.default {
+            Some(ref mut rasterizer) => {
+                if !default_requests.is_empty() {
+                    results.extend(rasterizer.rasterize(&default_requests, low_priority, tile_pool));
+                }
+            }
+            None => {
+                results.extend(default_requests.iter().map(|params| {
+                    let error = format!("No blob image handler for {:?}", params.request.key);
+                    (params.request, Err(BlobImageError::Other(error)))
+                }));
+            }
+        }
+
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{ImageFormat, ImageKey, RasterizedBlobImage};
+
+    /// A handler whose rasterizer fills every tile with a fixed byte.
+    struct FillHandler(u8);
+
+    struct FillRasterizer(u8);
+
+    impl AsyncBlobImageRasterizer for FillRasterizer {
+        fn rasterize(
+            &mut self,
+            requests: &[BlobImageParams],
+            _low_priority: bool,
+            _tile_pool: &mut BlobTilePool,
+        ) -> Vec<(BlobImageRequest, BlobImageResult)> {
+            requests.iter().map(|params| {
+                let rasterized_rect = params.descriptor.rect.cast_unit();
+                let data = vec![self.0; (rasterized_rect.area() * 4) as usize];
+                (params.request, Ok(RasterizedBlobImage { rasterized_rect, data: Arc::new(data) }))
+            }).collect()
+        }
+    }
+
+    impl BlobImageHandler for FillHandler {
+        fn create_blob_rasterizer(&mut self) -> Box<dyn AsyncBlobImageRasterizer> {
+            Box::new(FillRasterizer(self.0))
+        }
+        fn create_similar(&self) -> Box<dyn BlobImageHandler> {
+            Box::new(FillHandler(self.0))
+        }
+        fn prepare_resources(&mut self, _: &dyn BlobImageResources, _: &[BlobImageParams]) {}
+        fn add(&mut self, _: BlobImageKey, _: Arc<BlobImageData>, _: &DeviceIntRect, _: TileSize) {}
+        fn update(&mut self, _: BlobImageKey, _: Arc<BlobImageData>, _: &DeviceIntRect, _: &BlobDirtyRect) {}
+        fn delete(&mut self, _: BlobImageKey) {}
+        fn delete_font(&mut self, _: FontKey) {}
+        fn delete_font_instance(&mut self, _: FontInstanceKey) {}
+        fn clear_namespace(&mut self, _: IdNamespace) {}
+        fn enable_multithreading(&mut self, _: bool) {}
+    }
+
+    fn params(namespace: u32) -> BlobImageParams {
+        BlobImageParams {
+            request: BlobImageRequest {
+                key: BlobImageKey(ImageKey::new(IdNamespace(namespace), 1)),
+                tile: TileOffset::zero(),
+            },
+            descriptor: BlobImageDescriptor {
+                rect: LayoutIntRect::from_size(LayoutIntSize::new(4, 4)),
+                format: ImageFormat::BGRA8,
+            },
+            dirty_rect: DirtyRect::All,
+        }
+    }
+
+    #[test]
+    fn blob_requests_are_routed_by_namespace() {
+        let mut handlers: FastHashMap<IdNamespace, Box<dyn BlobImageHandler>> = FastHashMap::default();
+        handlers.insert(IdNamespace(1), Box::new(FillHandler(1)));
+        handlers.insert(IdNamespace(2), Box::new(FillHandler(2)));
+
+        let mut handler = NamespacedBlobImageHandler::combine(None, handlers).unwrap();
+        let requests = [params(1), params(2), params(3)];
+        let results = handler
+            .create_similar()
+            .create_blob_rasterizer()
+            .rasterize(&requests, false, &mut BlobTilePool::new());
+        assert_eq!(results.len(), requests.len());
+
+        for (request, result) in results {
+            match (request.key.0).0 {
+                IdNamespace(1) => assert!(result.unwrap().data.iter().all(|&b| b == 1)),
+                IdNamespace(2) => assert!(result.unwrap().data.iter().all(|&b| b == 2)),
+                // No handler and no default handler for this namespace.
+                _ => assert!(result.is_err()),
+            }
+        }
+
+        // With a default handler, unmatched namespaces go to it instead.
+        let mut handlers: FastHashMap<IdNamespace, Box<dyn BlobImageHandler>> = FastHashMap::default();
+        handlers.insert(IdNamespace(1), Box::new(FillHandler(1)));
+        handler = NamespacedBlobImageHandler::combine(Some(Box::new(FillHandler(9))), handlers).unwrap();
+        let results = handler
+            .create_blob_rasterizer()
+            .rasterize(&requests, false, &mut BlobTilePool::new());
+
+        for (request, result) in results {
+            let expected = if (request.key.0).0 == IdNamespace(1) { 1 } else { 9 };
+            assert!(result.unwrap().data.iter().all(|&b| b == expected));
+        }
+    }
+}
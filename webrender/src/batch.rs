@@ -2,7 +2,7 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use api::{AlphaType, ClipMode, ImageBufferKind};
+use api::{AlphaType, ClipMode, ImageBufferKind, ImageWrapMode};
 use api::{FontInstanceFlags, YuvColorSpace, YuvFormat, ColorDepth, ColorRange, PremultipliedColorF};
 use api::units::*;
 use crate::clip::{ClipNodeFlags, ClipNodeRange, ClipItemKind, ClipStore};
@@ -31,7 +31,7 @@ use crate::render_task::{RenderTaskAddress, RenderTaskKind, SubPass};
 use crate::renderer::{BlendMode, GpuBufferBuilder, ShaderColorMode};
 use crate::renderer::MAX_VERTEX_TEXTURE_WIDTH;
 use crate::resource_cache::{GlyphFetchResult, ImageProperties};
-use crate::space::SpaceMapper;
+use crate::space::{SpaceMapper, snap_rect_to_device_pixels};
 use crate::visibility::{PrimitiveVisibilityFlags, VisibilityState};
 use smallvec::SmallVec;
 use std::{f32, i32, usize};
@@ -57,6 +57,9 @@ const CLIP_RECTANGLE_AREA_THRESHOLD: f32 = (CLIP_RECTANGLE_TILE_SIZE * CLIP_RECT
 pub enum BrushBatchKind {
     Solid,
     Image(ImageBufferKind),
+    /// An image drawn as a single quad, relying on the texture sampler's
+    /// wrap mode for repetition.
+    WrappedImage(ImageBufferKind, ImageWrapMode),
     Blend,
     MixBlend {
         task_id: RenderTaskId,
@@ -629,6 +632,8 @@ pub struct AlphaBatchBuilder {
     pub opaque_batch_list: OpaqueBatchList,
     pub render_task_id: RenderTaskId,
     render_task_address: RenderTaskAddress,
+    /// The id handed out to every primitive after the depth id budget ran out.
+    last_z_id: ZBufferId,
 }
 
 impl AlphaBatchBuilder {
@@ -636,6 +641,7 @@ impl AlphaBatchBuilder {
         screen_size: DeviceIntSize,
         break_advanced_blend_batches: bool,
         lookback_count: usize,
+        max_depth_ids: i32,
         render_task_id: RenderTaskId,
         render_task_address: RenderTaskAddress,
         memory: &FrameMemory,
@@ -649,6 +655,7 @@ impl AlphaBatchBuilder {
             opaque_batch_list: OpaqueBatchList::new(batch_area_threshold, lookback_count, memory),
             render_task_id,
             render_task_address,
+            last_z_id: ZBufferId(max_depth_ids - 1),
         }
     }
 
@@ -701,10 +708,13 @@ impl AlphaBatchBuilder {
         z_id: ZBufferId,
     ) -> &mut FrameVec<PrimitiveInstanceData> {
         match key.blend_mode {
-            BlendMode::None => {
+            // Opaque batches are drawn front to back and rely on each primitive
+            // having its own depth id, which isn't true once the budget is used up.
+            BlendMode::None if z_id != self.last_z_id => {
                 self.opaque_batch_list
                     .set_params_and_get_batch(key, features, bounding_rect)
             }
+            BlendMode::None |
             BlendMode::Alpha |
             BlendMode::PremultipliedAlpha |
             BlendMode::PremultipliedDestOut |
@@ -953,12 +963,27 @@ impl BatchBuilder {
 
         let z_id = z_generator.next();
 
-        let prim_rect = ctx.data_stores.get_local_prim_rect(
+        let mut prim_rect = ctx.data_stores.get_local_prim_rect(
             prim_instance,
             &ctx.prim_store.pictures,
             ctx.surfaces,
         );
 
+        if ctx.data_stores.prim_snaps_to_device_pixels(prim_instance) {
+            let device_pixel_scale = match render_tasks[self.batcher.render_task_id].kind {
+                RenderTaskKind::Picture(ref pic_task) => pic_task.device_pixel_scale,
+                _ => ctx.global_device_pixel_scale,
+            };
+
+            prim_rect = snap_rect_to_device_pixels(
+                &prim_rect,
+                prim_spatial_node_index,
+                root_spatial_node_index,
+                device_pixel_scale,
+                ctx.spatial_tree,
+            );
+        }
+
         let mut batch_features = BatchFeatures::empty();
         if ctx.data_stores.prim_may_need_repetition(prim_instance) {
             batch_features |= BatchFeatures::REPETITION;
@@ -2553,8 +2578,13 @@ impl BatchBuilder {
                         }
                     };
 
+                    let batch_kind = match image_instance.wrap_mode {
+                        ImageWrapMode::Clamp => BrushBatchKind::Image(texture_source.image_buffer_kind()),
+                        wrap_mode => BrushBatchKind::WrappedImage(texture_source.image_buffer_kind(), wrap_mode),
+                    };
+
                     let batch_params = BrushBatchParameters::shared(
-                        BrushBatchKind::Image(texture_source.image_buffer_kind()),
+                        batch_kind,
                         TextureSet::prim_textured(texture_source),
                         prim_user_data,
                         uv_rect_address.as_int(),
@@ -2583,10 +2613,13 @@ impl BatchBuilder {
                         batch_params.prim_user_data,
                     );
 
-                    let brush_flags = match image_instance.normalized_uvs {
+                    let mut brush_flags = match image_instance.normalized_uvs {
                         true => brush_flags | BrushFlags::NORMALIZED_UVS,
                         false => brush_flags,
                     };
+                    if image_instance.wrap_mode != ImageWrapMode::Clamp {
+                        brush_flags |= BrushFlags::SAMPLER_WRAP;
+                    }
 
                     self.add_segmented_prim_to_batch(
                         segments,
@@ -3878,10 +3911,12 @@ impl ClipBatcher {
                 }
                 ClipItemKind::RoundedRectangle { rect, ref radius, mode, .. } => {
                     let batch_list = self.get_batch_list(is_first_clip);
+                    let mut clip_data = ClipData::rounded_rect(rect.size(), radius, mode);
+                    clip_data.set_aa_quality(ctx.clip_aa_quality);
                     let instance = ClipMaskInstanceRect {
                         common,
                         local_pos: rect.min,
-                        clip_data: ClipData::rounded_rect(rect.size(), radius, mode),
+                        clip_data,
                     };
                     if clip_instance.flags.contains(ClipNodeFlags::USE_FAST_PATH) {
                         batch_list.fast_rectangles.push(instance);
@@ -2,7 +2,7 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use api::{BorderRadius, BorderSide, BorderStyle, ColorF, ColorU};
+use api::{BorderRadius, BorderSide, BorderStyle, ColorF, ColorU, PropertyBinding};
 use api::{NormalBorder as ApiNormalBorder, RepeatMode};
 use api::units::*;
 use crate::clip::ClipNodeId;
@@ -11,10 +11,10 @@ use euclid::vec2;
 use crate::scene_building::SceneBuilder;
 use crate::spatial_tree::SpatialNodeIndex;
 use crate::gpu_types::{BorderInstance, BorderSegment, BrushFlags};
-use crate::prim_store::{BorderSegmentInfo, BrushSegment, NinePatchDescriptor};
-use crate::prim_store::borders::{NormalBorderPrim, NormalBorderData};
+use crate::prim_store::{BorderSegmentInfo, BrushSegment, FloatKey, NinePatchDescriptor};
+use crate::prim_store::borders::{NormalBorderPrim, NormalBorderData, dash_phase_key};
 use crate::util::{lerp, RectHelpers};
-use crate::internal_types::LayoutPrimitiveInfo;
+use crate::internal_types::{FastHashMap, LayoutPrimitiveInfo};
 use crate::segment::EdgeAaSegmentMask;
 
 // Using 2048 as the maximum radius in device space before which we
@@ -29,6 +29,12 @@ pub const MAX_BORDER_RESOLUTION: u32 = 2048;
 /// memory with unreasonable inputs. It would be better to address this by not building
 /// a list of per-dot information in the first place.
 pub const MAX_DASH_COUNT: u32 = 2048;
+/// Number of distinct dash phases per dash period that dashed edges are
+/// cached at.
+const DASH_PHASE_STEPS: u32 = 64;
+/// Maximum number of tessellated dashed / dotted corners retained by a
+/// `BorderCornerCache` before the least recently used ones are evicted.
+pub const MAX_CACHED_BORDER_CORNERS: usize = 256;
 
 // TODO(gw): Perhaps there is a better way to store
 //           the border cache key than duplicating
@@ -163,6 +169,30 @@ pub struct BorderSegmentCacheKey {
     pub h_adjacent_corner_radius: LayoutSizeAu,
     pub v_adjacent_corner_outer: LayoutPointAu,
     pub v_adjacent_corner_radius: LayoutSizeAu,
+    /// Offset of the dash pattern, as a fraction of the dash period. Always
+    /// zero for segments that aren't dashed edges.
+    pub dash_phase: FloatKey,
+}
+
+impl BorderSegmentCacheKey {
+    /// Returns the key of this segment with its dash pattern offset by
+    /// `dash_phase`. Only dashed edges depend on the phase, so the other
+    /// segments keep sharing their cached render tasks.
+    pub fn with_dash_phase(&self, dash_phase: f32) -> Self {
+        let mut key = self.clone();
+        let is_edge = matches!(
+            self.segment,
+            BorderSegment::Left | BorderSegment::Top | BorderSegment::Right | BorderSegment::Bottom
+        );
+        if is_edge && self.side0.style == BorderStyle::Dashed {
+            // Quantize the phase so that an animated phase reuses a bounded
+            // set of cached render tasks instead of creating one per frame.
+            let steps = DASH_PHASE_STEPS as f32;
+            let phase = (dash_phase.rem_euclid(1.0) * steps).round() / steps;
+            key.dash_phase = phase.rem_euclid(1.0).into();
+        }
+        key
+    }
 }
 
 pub fn ensure_no_corner_overlap(
@@ -220,6 +250,7 @@ impl<'a> SceneBuilder<'a> {
         info: &LayoutPrimitiveInfo,
         border: &ApiNormalBorder,
         widths: LayoutSideOffsets,
+        dash_phase: PropertyBinding<f32>,
         spatial_node_index: SpatialNodeIndex,
         clip_node_id: ClipNodeId,
     ) {
@@ -234,6 +265,7 @@ impl<'a> SceneBuilder<'a> {
             NormalBorderPrim {
                 border: border.into(),
                 widths: widths.to_au(),
+                dash_phase: dash_phase_key(dash_phase),
             },
         );
     }
@@ -304,6 +336,165 @@ fn compute_outer_and_clip_sign(
     (outer, clip_sign)
 }
 
+/// Identifies the tessellation of a dashed or dotted corner. The generated
+/// dash / dot clips only depend on the device radius, widths, style and
+/// which corner is being drawn.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "capture", derive(Serialize))]
+struct BorderCornerKey {
+    radius: (FloatKey, FloatKey),
+    widths: (FloatKey, FloatKey),
+    style: BorderStyle,
+    segment: BorderSegment,
+}
+
+/// The clip kind and parameters of one instance of a tessellated corner.
+/// The remaining instance fields come from the segment being built.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "capture", derive(Serialize))]
+struct CornerClip {
+    clip_flags: i32,
+    clip_params: [f32; 8],
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "capture", derive(Serialize))]
+struct CachedCorner {
+    /// None if the corner can't be drawn as dashes or dots, in which
+    /// case a solid corner is used instead.
+    clips: Option<Vec<CornerClip>>,
+    last_used: u64,
+}
+
+/// Retains the tessellation of dashed and dotted corners. Segments with an
+/// identical `BorderSegmentCacheKey` already share a render task through the
+/// render task cache, so this only comes into play for corners whose segment
+/// keys differ in ways that don't affect the tessellation (colors, adjacent
+/// corners, dash phase), e.g. many rounded buttons of different colors. The
+/// cache holds at most `max_entries` corners, and evicts the least recently
+/// used one when full.
+#[derive(Debug)]
+#[cfg_attr(feature = "capture", derive(Serialize))]
+pub struct BorderCornerCache {
+    entries: FastHashMap<BorderCornerKey, CachedCorner>,
+    max_entries: usize,
+    current_use: u64,
+    /// Number of corners that were tessellated rather than found in the cache.
+    tessellated_count: usize,
+}
+
+impl Default for BorderCornerCache {
+    fn default() -> Self {
+        BorderCornerCache::new(MAX_CACHED_BORDER_CORNERS)
+    }
+}
+
+impl BorderCornerCache {
+    /// Create a cache retaining up to `max_entries` corners. A cache with no
+    /// entries tessellates every corner it is asked for.
+    pub fn new(max_entries: usize) -> Self {
+        BorderCornerCache {
+            entries: FastHashMap::default(),
+            max_entries,
+            current_use: 0,
+            tessellated_count: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn tessellated_count(&self) -> usize {
+        self.tessellated_count
+    }
+
+    /// Write the instances for a dashed or dotted corner, reusing a previous
+    /// tessellation with the same parameters if there is one. Returns an
+    /// error if the corner should be drawn as a solid corner instead.
+    fn write_corner_instances(
+        &mut self,
+        style: BorderStyle,
+        corner_radius: DeviceSize,
+        widths: DeviceSize,
+        segment: BorderSegment,
+        base_instance: &BorderInstance,
+        instances: &mut Vec<BorderInstance>,
+    ) -> Result<(), ()> {
+        let write_instances = match style {
+            BorderStyle::Dashed => write_dashed_corner_instances,
+            BorderStyle::Dotted => write_dotted_corner_instances,
+            _ => return Err(()),
+        };
+
+        self.current_use += 1;
+
+        let key = BorderCornerKey {
+            radius: (corner_radius.width.into(), corner_radius.height.into()),
+            widths: (widths.width.into(), widths.height.into()),
+            style,
+            segment,
+        };
+
+        if let Some(entry) = self.entries.get_mut(&key) {
+            entry.last_used = self.current_use;
+            let clips = entry.clips.as_ref().ok_or(())?;
+            instances.extend(clips.iter().map(|clip| BorderInstance {
+                flags: base_instance.flags | clip.clip_flags,
+                clip_params: clip.clip_params,
+                .. *base_instance
+            }));
+            return Ok(());
+        }
+
+        self.tessellated_count += 1;
+
+        let first_instance = instances.len();
+        let result = write_instances(
+            corner_radius,
+            widths,
+            segment,
+            base_instance,
+            instances,
+        );
+
+        if self.max_entries == 0 {
+            return result;
+        }
+
+        if self.entries.len() >= self.max_entries {
+            let oldest = self.entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+
+        let clips = result.ok().map(|_| {
+            instances[first_instance ..]
+                .iter()
+                .map(|instance| CornerClip {
+                    clip_flags: instance.flags & !base_instance.flags,
+                    clip_params: instance.clip_params,
+                })
+                .collect()
+        });
+
+        self.entries.insert(key, CachedCorner {
+            clips,
+            last_used: self.current_use,
+        });
+
+        result
+    }
+}
+
 fn write_dashed_corner_instances(
     corner_radius: DeviceSize,
     widths: DeviceSize,
@@ -916,6 +1107,8 @@ fn add_segment(
     h_adjacent_corner_radius: DeviceSize,
     v_adjacent_corner_outer: DevicePoint,
     v_adjacent_corner_radius: DeviceSize,
+    dash_phase: f32,
+    corner_cache: &mut BorderCornerCache,
 ) {
     let base_flags = (segment as i32) |
                      ((style0 as i32) << 8) |
@@ -947,27 +1140,14 @@ fn add_segment(
                 warn!("TODO: Handle a corner with dotted / dashed transition.");
             }
 
-            let dashed_or_dotted_corner = match style0 {
-                BorderStyle::Dashed => {
-                    write_dashed_corner_instances(
-                        radius,
-                        widths,
-                        segment,
-                        &base_instance,
-                        instances,
-                    )
-                }
-                BorderStyle::Dotted => {
-                    write_dotted_corner_instances(
-                        radius,
-                        widths,
-                        segment,
-                        &base_instance,
-                        instances,
-                    )
-                }
-                _ => Err(()),
-            };
+            let dashed_or_dotted_corner = corner_cache.write_corner_instances(
+                style0,
+                radius,
+                widths,
+                segment,
+                &base_instance,
+                instances,
+            );
 
             if dashed_or_dotted_corner.is_err() {
                 let clip_params = [
@@ -996,18 +1176,20 @@ fn add_segment(
 
             match style0 {
                 BorderStyle::Dashed => {
-                    let (x, y) = if is_vertical {
+                    // The task covers a single dash period, so the phase
+                    // offset is a fraction of its length.
+                    let (x, y, offset) = if is_vertical {
                         let half_dash_size = task_rect.height() * 0.25;
-                        (0., half_dash_size)
+                        (0., half_dash_size, dash_phase * task_rect.height())
                     } else {
                         let half_dash_size = task_rect.width() * 0.25;
-                        (half_dash_size, 0.)
+                        (half_dash_size, 0., dash_phase * task_rect.width())
                     };
 
                     instances.push(BorderInstance {
                         flags: base_flags | ((BorderClipKind::DashEdge as i32) << 24),
                         clip_params: [
-                            x, y, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
+                            x, y, offset, 0.0, 0.0, 0.0, 0.0, 0.0,
                         ],
                         ..base_instance
                     });
@@ -1172,6 +1354,7 @@ fn add_corner_segment(
             h_adjacent_corner_radius: h_corner_radius.to_au(),
             v_adjacent_corner_outer: (v_corner_outer - image_rect.min).to_point().to_au(),
             v_adjacent_corner_radius: v_corner_radius.to_au(),
+            dash_phase: FloatKey::from(0.0f32),
         },
     });
 }
@@ -1236,6 +1419,7 @@ fn add_edge_segment(
             h_adjacent_corner_radius: LayoutSizeAu::zero(),
             v_adjacent_corner_outer: LayoutPointAu::zero(),
             v_adjacent_corner_radius: LayoutSizeAu::zero(),
+            dash_phase: FloatKey::from(0.0f32),
         },
     });
 }
@@ -1247,6 +1431,7 @@ pub fn build_border_instances(
     cache_size: DeviceIntSize,
     border: &ApiNormalBorder,
     scale: LayoutToDeviceScale,
+    corner_cache: &mut BorderCornerCache,
 ) -> Vec<BorderInstance> {
     let mut instances = Vec::new();
 
@@ -1298,6 +1483,8 @@ pub fn build_border_instances(
         h_corner_radius,
         v_corner_outer,
         v_corner_radius,
+        cache_key.dash_phase.into(),
+        corner_cache,
     );
 
     instances
@@ -1476,3 +1663,103 @@ impl NinePatchDescriptor {
         segments
     }
 }
+
+#[cfg(test)]
+fn build_dotted_corner_for_testing(
+    radius: f32,
+    color: ColorF,
+    corner_cache: &mut BorderCornerCache,
+) -> (BorderSegmentCacheKey, Vec<BorderInstance>) {
+    let side = BorderSide {
+        color,
+        style: BorderStyle::Dotted,
+    };
+    let border = ApiNormalBorder {
+        left: side,
+        right: side,
+        top: side,
+        bottom: side,
+        radius: BorderRadius::uniform(radius),
+        do_aa: true,
+    };
+    let side_au = BorderSideAu::from(side);
+    let cache_key = BorderSegmentCacheKey {
+        size: LayoutSize::new(radius, radius).to_au(),
+        radius: LayoutSize::new(radius, radius).to_au(),
+        side0: side_au.clone(),
+        side1: side_au,
+        segment: BorderSegment::TopLeft,
+        do_aa: true,
+        h_adjacent_corner_outer: LayoutPointAu::zero(),
+        h_adjacent_corner_radius: LayoutSizeAu::zero(),
+        v_adjacent_corner_outer: LayoutPointAu::zero(),
+        v_adjacent_corner_radius: LayoutSizeAu::zero(),
+        dash_phase: FloatKey::from(0.0f32),
+    };
+
+    let instances = build_border_instances(
+        &cache_key,
+        DeviceIntSize::new(radius as i32, radius as i32),
+        &border,
+        LayoutToDeviceScale::new(1.0),
+        corner_cache,
+    );
+
+    (cache_key, instances)
+}
+
+#[test]
+fn test_border_corner_cache() {
+    use crate::internal_types::FastHashSet;
+
+    const BORDER_COUNT: usize = 1000;
+
+    // 1000 rounded dotted borders that only differ by color. Their segment
+    // keys differ, so the render task cache can't share them, but they all
+    // have the same corner tessellation.
+    let color = |i: usize| -> ColorF { ColorU::new((i % 256) as u8, (i / 256) as u8, 0, 255).into() };
+
+    let mut uncached = BorderCornerCache::new(0);
+    let mut cached = BorderCornerCache::default();
+    let mut segment_keys = FastHashSet::default();
+
+    for i in 0 .. BORDER_COUNT {
+        let (key, expected) = build_dotted_corner_for_testing(200.0, color(i), &mut uncached);
+        let (_, instances) = build_dotted_corner_for_testing(200.0, color(i), &mut cached);
+        assert!(expected.len() > 1);
+        assert_eq!(instances.len(), expected.len());
+        for (instance, expected) in instances.iter().zip(expected.iter()) {
+            assert_eq!(instance.flags, expected.flags);
+            assert_eq!(instance.clip_params, expected.clip_params);
+            assert_eq!(instance.color0, expected.color0);
+        }
+        segment_keys.insert(key);
+    }
+
+    assert_eq!(segment_keys.len(), BORDER_COUNT);
+    assert_eq!(uncached.tessellated_count(), BORDER_COUNT);
+    assert!(uncached.is_empty());
+    assert_eq!(cached.tessellated_count(), 1);
+    assert_eq!(cached.len(), 1);
+}
+
+#[test]
+fn test_border_corner_cache_eviction() {
+    let mut cache = BorderCornerCache::new(2);
+
+    build_dotted_corner_for_testing(100.0, ColorF::BLACK, &mut cache);
+    build_dotted_corner_for_testing(200.0, ColorF::BLACK, &mut cache);
+    // Touch the first corner so that the second one is the least recently used.
+    build_dotted_corner_for_testing(100.0, ColorF::BLACK, &mut cache);
+    assert_eq!(cache.tessellated_count(), 2);
+
+    build_dotted_corner_for_testing(300.0, ColorF::BLACK, &mut cache);
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache.tessellated_count(), 3);
+
+    build_dotted_corner_for_testing(100.0, ColorF::BLACK, &mut cache);
+    assert_eq!(cache.tessellated_count(), 3);
+    build_dotted_corner_for_testing(200.0, ColorF::BLACK, &mut cache);
+    assert_eq!(cache.tessellated_count(), 4);
+    assert_eq!(cache.len(), 2);
+}
@@ -336,6 +336,23 @@ impl ClipTree {
         }
     }
 
+    /// The number of clips on the longest path from a leaf to the root, i.e.
+    /// the most clips in the clip-chain of any primitive in the tree.
+    pub fn max_leaf_depth(&self) -> usize {
+        // Nodes are always added after their parent, so the depth of each
+        // node can be found in a single pass.
+        let mut depths = vec![0; self.nodes.len()];
+        for (index, node) in self.nodes.iter().enumerate().skip(1) {
+            depths[index] = depths[node.parent.0 as usize] + 1;
+        }
+
+        self.leaves
+            .iter()
+            .map(|leaf| depths[leaf.node_id.0 as usize])
+            .max()
+            .unwrap_or(0)
+    }
+
     /// Find the lowest common ancestor of two clip tree nodes. This is useful
     /// to identify shared clips between primitives attached to different clip-leaves.
     pub fn find_lowest_common_ancestor(
@@ -50,7 +50,7 @@ pub enum NativeSurfaceOperationDetails {
     },
     CreateExternalSurface {
         id: NativeSurfaceId,
-        is_opaque: bool,
+        format: CompositeSurfaceFormat,
     },
     CreateBackdropSurface {
         id: NativeSurfaceId,
@@ -99,9 +99,16 @@ pub enum CompositeTileSurface {
 }
 
 /// The surface format for a tile being composited.
+#[cfg_attr(feature = "capture", derive(Serialize))]
+#[cfg_attr(feature = "replay", derive(Deserialize))]
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum CompositeSurfaceFormat {
+    /// A picture cache tile, color, or an opaque RGBA external surface.
     Rgba,
+    /// An RGBA external surface with premultiplied alpha, which is blended
+    /// with the content beneath it. External surfaces with straight alpha
+    /// are never promoted, since native compositors don't support them.
+    RgbaPremultiplied,
     Yuv,
 }
 
@@ -150,13 +157,22 @@ pub struct CompositeTile {
     pub z_id: ZBufferId,
     pub kind: TileKind,
     pub transform_index: CompositorTransformIndex,
+    /// Opacity applied when compositing this tile (e.g. for a fading slice).
+    pub opacity: f32,
+    /// The picture cache tile this was added for, None for compositor surfaces.
+    pub tile_id: Option<TileId>,
 }
 
 pub fn tile_kind(surface: &CompositeTileSurface, is_opaque: bool) -> TileKind {
     match surface {
-        // Color tiles are, by definition, opaque. We might support non-opaque color
-        // tiles if we ever find pages that have a lot of these.
-        CompositeTileSurface::Color { .. } => TileKind::Opaque,
+        // Color tiles are opaque, unless they are composited with an opacity.
+        CompositeTileSurface::Color { .. } => {
+            if is_opaque {
+                TileKind::Opaque
+            } else {
+                TileKind::Alpha
+            }
+        }
         // Clear tiles have a special bucket
         CompositeTileSurface::Clear => TileKind::Clear,
         CompositeTileSurface::Texture { .. }
@@ -181,6 +197,7 @@ pub enum ExternalSurfaceDependency {
     },
     Rgb {
         image_dependency: ImageDependency,
+        format: CompositeSurfaceFormat,
     },
 }
 
@@ -204,6 +221,8 @@ pub struct ExternalSurfaceDescriptor {
     /// If the native surface needs to be updated, this will contain the size
     /// of the native surface as Some(size). If not dirty, this is None.
     pub update_params: Option<DeviceIntSize>,
+    /// Rounded corners applied by the native compositor to `clip_rect`.
+    pub clip_radius: ClipRadius,
 }
 
 impl ExternalSurfaceDescriptor {
@@ -269,6 +288,7 @@ pub enum ResolvedExternalSurfaceColorData {
     Rgb {
         image_dependency: ImageDependency,
         plane: ExternalPlaneDescriptor,
+        format: CompositeSurfaceFormat,
     },
 }
 
@@ -309,6 +329,14 @@ pub enum CompositorConfig {
     Native {
         /// A client provided interface to a native / OS compositor.
         compositor: Box<dyn Compositor>,
+        /// If set, the maximum number of tiles that may be bound for update in a
+        /// single frame. The budget is split evenly between picture cache slices.
+        /// When the tile grid of a slice would exceed its share, adjacent tiles are
+        /// coalesced by doubling the tile size in each dimension, merging each 2x2
+        /// block of tiles into a single larger tile, until the grid fits. Since at
+        /// most every tile in the grid is updated per frame, this bounds the
+        /// number of surface updates the compositor receives.
+        max_tiles_per_frame: Option<usize>,
     }
 }
 
@@ -366,6 +394,8 @@ pub enum CompositorKind {
     Native {
         /// The capabilities of the underlying platform.
         capabilities: CompositorCapabilities,
+        /// The maximum number of tiles updated per frame, if limited.
+        max_tiles_per_frame: Option<usize>,
     },
 }
 
@@ -387,6 +417,13 @@ impl CompositorKind {
         }
     }
 
+    pub fn get_max_tiles_per_frame(&self) -> Option<usize> {
+        match self {
+            CompositorKind::Draw { .. } => None,
+            CompositorKind::Native { max_tiles_per_frame, .. } => *max_tiles_per_frame,
+        }
+    }
+
     pub fn should_redraw_on_invalidation(&self) -> bool {
         match self {
             CompositorKind::Draw { max_partial_present_rects, .. } => {
@@ -445,6 +482,10 @@ pub struct CompositeSurfaceDescriptor {
     // image dependency.
     pub image_dependencies: [ImageDependency; 3],
     pub image_rendering: ImageRendering,
+    // Rounded corners applied to clip_rect by the native compositor.
+    pub clip_radius: ClipRadius,
+    // Opacity applied to the tiles of this surface when compositing.
+    pub opacity: f32,
     // List of the surface information for each tile added to this virtual surface
     pub tile_descriptors: Vec<CompositeTileDescriptor>,
 }
@@ -528,6 +569,36 @@ pub struct CompositorTransform {
     local_to_device: ScaleOffset,
 }
 
+/// What a surface in `CompositeSurfaceInfo` contains.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CompositeSurfaceContent {
+    /// A picture cache tile.
+    Tile,
+    /// A solid color tile.
+    Color,
+    /// A tile that clears what is under it.
+    Clear,
+    /// A primitive promoted to a compositor surface, such as a video.
+    External,
+}
+
+/// Describes a surface of the last composited frame, for embedders that want
+/// to know how it was composited. See `RenderResults::composite_surfaces`.
+#[derive(Clone, Debug)]
+pub struct CompositeSurfaceInfo {
+    pub content: CompositeSurfaceContent,
+    /// Whether the native compositor composited this surface, rather than
+    /// WR drawing it into the framebuffer.
+    pub is_native: bool,
+    /// The visible device rect of the surface.
+    pub device_rect: DeviceRect,
+    /// The transform from the surface's local space to device space.
+    pub transform: ScaleOffset,
+    /// The picture cache tile, for `Renderer::read_picture_cache_tile`. Only
+    /// set with the draw compositor.
+    pub tile_id: Option<TileId>,
+}
+
 /// The list of tiles to be drawn this frame
 #[cfg_attr(feature = "capture", derive(Serialize))]
 #[cfg_attr(feature = "replay", derive(Deserialize))]
@@ -656,6 +727,58 @@ impl CompositeState {
         transform.raster_to_device
     }
 
+    /// Describe each surface that is composited this frame.
+    pub fn surface_infos(&self) -> Vec<CompositeSurfaceInfo> {
+        match self.compositor_kind {
+            CompositorKind::Draw { .. } => {
+                self.tiles
+                    .iter()
+                    .filter_map(|tile| {
+                        let content = match tile.surface {
+                            CompositeTileSurface::Texture { .. } => CompositeSurfaceContent::Tile,
+                            CompositeTileSurface::Color { .. } => CompositeSurfaceContent::Color,
+                            CompositeTileSurface::Clear => CompositeSurfaceContent::Clear,
+                            CompositeTileSurface::ExternalSurface { .. } => CompositeSurfaceContent::External,
+                        };
+
+                        let device_rect = self
+                            .get_device_rect(&tile.local_rect, tile.transform_index)
+                            .intersection(&tile.device_clip_rect)?
+                            .intersection(&self.get_device_rect(&tile.local_valid_rect, tile.transform_index))?;
+
+                        Some(CompositeSurfaceInfo {
+                            content,
+                            is_native: false,
+                            device_rect,
+                            transform: self.get_device_transform(tile.transform_index),
+                            tile_id: tile.tile_id,
+                        })
+                    })
+                    .collect()
+            }
+            CompositorKind::Native { .. } => {
+                self.descriptor.surfaces
+                    .iter()
+                    .map(|surface| {
+                        let content = if surface.image_dependencies[0] == ImageDependency::INVALID {
+                            CompositeSurfaceContent::Tile
+                        } else {
+                            CompositeSurfaceContent::External
+                        };
+
+                        CompositeSurfaceInfo {
+                            content,
+                            is_native: surface.surface_id.is_some(),
+                            device_rect: surface.clip_rect,
+                            transform: surface.transform,
+                            tile_id: None,
+                        }
+                    })
+                    .collect()
+            }
+        }
+    }
+
     /// Register an occluder during picture cache updates that can be
     /// used during frame building to occlude tiles.
     pub fn register_occluder(
@@ -748,6 +871,8 @@ impl CompositeState {
             device_clip_rect: clip_rect,
             z_id: external_surface.z_id,
             transform_index: external_surface.transform_index,
+            opacity: 1.0,
+            tile_id: None,
         };
 
         // Add a surface descriptor for each compositor surface. For the Draw
@@ -760,6 +885,8 @@ impl CompositeState {
                 transform: self.get_compositor_transform(external_surface.transform_index),
                 image_dependencies: image_dependencies,
                 image_rendering: external_surface.image_rendering,
+                clip_radius: external_surface.clip_radius,
+                opacity: 1.0,
                 tile_descriptors: Vec::new(),
             }
         );
@@ -798,6 +925,8 @@ impl CompositeState {
                     transform: slice_transform,
                     image_dependencies: [ImageDependency::INVALID; 3],
                     image_rendering,
+                    clip_radius: ClipRadius::EMPTY,
+                    opacity: 1.0,
                     tile_descriptors: Vec::new(),
                 }
             );
@@ -858,6 +987,8 @@ impl CompositeState {
                             transform: slice_transform,
                             image_dependencies: [ImageDependency::INVALID; 3],
                             image_rendering,
+                            clip_radius: ClipRadius::EMPTY,
+                            opacity: tile_cache.composite_opacity,
                             tile_descriptors: sub_slice.opaque_tile_descriptors.clone(),
                         }
                     );
@@ -872,6 +1003,8 @@ impl CompositeState {
                             transform: slice_transform,
                             image_dependencies: [ImageDependency::INVALID; 3],
                             image_rendering,
+                            clip_radius: ClipRadius::EMPTY,
+                            opacity: tile_cache.composite_opacity,
                             tile_descriptors: sub_slice.alpha_tile_descriptors.clone(),
                         }
                     );
@@ -1001,13 +1134,14 @@ impl CompositeState {
                     update_params,
                 });
             },
-            ExternalSurfaceDependency::Rgb { .. } => {
+            ExternalSurfaceDependency::Rgb { format, .. } => {
                 let image_buffer_kind = planes[0].texture.image_buffer_kind();
 
                 self.external_surfaces.push(ResolvedExternalSurface {
                     color_data: ResolvedExternalSurfaceColorData::Rgb {
                         image_dependency: image_dependencies[0],
                         plane: planes[0],
+                        format,
                     },
                     image_buffer_kind,
                     update_params,
@@ -1092,6 +1226,10 @@ pub struct CompositorCapabilities {
     pub supports_surface_for_backdrop: bool,
     /// Whether external compositor surface supports negative scaling.
     pub supports_external_compositor_surface_negative_scaling: bool,
+    /// Whether the compositor can clip a surface to a rounded rect, via the
+    /// `clip_radius` passed to `add_surface`. If not, compositor surfaces
+    /// with a rounded clip are drawn into picture cache tiles instead.
+    pub supports_surface_clip_radius: bool,
 }
 
 impl Default for CompositorCapabilities {
@@ -1108,6 +1246,7 @@ impl Default for CompositorCapabilities {
             max_update_rects: 1,
             supports_surface_for_backdrop: false,
             supports_external_compositor_surface_negative_scaling: true,
+            supports_surface_clip_radius: false,
         }
     }
 }
@@ -1146,6 +1285,32 @@ impl Default for WindowVisibility {
 // pub struct CompositorSurfacePixel;
 pub type CompositorSurfaceTransform = ScaleOffset;
 
+/// The radii of the rounded corners of a compositor surface's clip rect, in
+/// device pixels. Corners are circular.
+#[repr(C)]
+#[cfg_attr(feature = "capture", derive(Serialize))]
+#[cfg_attr(feature = "replay", derive(Deserialize))]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ClipRadius {
+    pub top_left: i32,
+    pub top_right: i32,
+    pub bottom_left: i32,
+    pub bottom_right: i32,
+}
+
+impl ClipRadius {
+    pub const EMPTY: ClipRadius = ClipRadius {
+        top_left: 0,
+        top_right: 0,
+        bottom_left: 0,
+        bottom_right: 0,
+    };
+
+    pub fn is_empty(&self) -> bool {
+        *self == ClipRadius::EMPTY
+    }
+}
+
 /// Defines an interface to a native (OS level) compositor. If supplied
 /// by the client application, then picture cache slices will be
 /// composited by the OS compositor, rather than drawn via WR batches.
@@ -1163,12 +1328,14 @@ pub trait Compositor {
     /// Create a new OS compositor surface that can be used with an
     /// existing ExternalImageId, instead of being drawn to by WebRender.
     /// Surfaces created by this can only be used with attach_external_image,
-    /// and not create_tile/destroy_tile/bind/unbind.
+    /// and not create_tile/destroy_tile/bind/unbind. `format` is `Rgba` or
+    /// `Yuv` for opaque surfaces, and `RgbaPremultiplied` for surfaces with
+    /// premultiplied alpha that must be blended with the content beneath.
     fn create_external_surface(
         &mut self,
         device: &mut Device,
         id: NativeSurfaceId,
-        is_opaque: bool,
+        format: CompositeSurfaceFormat,
     );
 
     /// Create a new OS backdrop surface that will display a color.
@@ -1259,7 +1426,9 @@ pub trait Compositor {
     /// Add a surface to the visual tree to be composited. Visuals must
     /// be added every frame, between the begin/end transaction call. The
     /// z-order of the surfaces is determined by the order they are added
-    /// to the visual tree.
+    /// to the visual tree. A non-empty `clip_radius` is only passed if the
+    /// compositor reports `supports_surface_clip_radius`, and rounds the
+    /// corners of `clip_rect`.
     // TODO(gw): Adding visuals every frame makes the interface simple,
     //           but may have performance implications on some compositors?
     //           We might need to change the interface to maintain a visual
@@ -1271,6 +1440,7 @@ pub trait Compositor {
         id: NativeSurfaceId,
         transform: CompositorSurfaceTransform,
         clip_rect: DeviceIntRect,
+        clip_radius: ClipRadius,
         image_rendering: ImageRendering,
     );
 
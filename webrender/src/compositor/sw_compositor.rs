@@ -12,7 +12,7 @@ use std::sync::{Arc, Condvar, Mutex, MutexGuard};
 use std::thread;
 use crate::{
     api::units::*, api::ColorDepth, api::ColorF, api::ExternalImageId, api::ImageRendering, api::YuvRangedColorSpace,
-    Compositor, CompositorCapabilities, CompositorSurfaceTransform, NativeSurfaceId, NativeSurfaceInfo, NativeTileId,
+    ClipRadius, CompositeSurfaceFormat, Compositor, CompositorCapabilities, CompositorSurfaceTransform, NativeSurfaceId, NativeSurfaceInfo, NativeTileId,
     profiler, MappableCompositor, SWGLCompositeSurfaceInfo, WindowVisibility,
     device::Device,
 };
@@ -1201,10 +1201,11 @@ impl Compositor for SwCompositor {
         self.surfaces.insert(id, SwSurface::new(tile_size, is_opaque));
     }
 
-    fn create_external_surface(&mut self, device: &mut Device, id: NativeSurfaceId, is_opaque: bool) {
+    fn create_external_surface(&mut self, device: &mut Device, id: NativeSurfaceId, format: CompositeSurfaceFormat) {
         if self.use_native_compositor {
-            self.compositor.create_external_surface(device, id, is_opaque);
+            self.compositor.create_external_surface(device, id, format);
         }
+        let is_opaque = format != CompositeSurfaceFormat::RgbaPremultiplied;
         self.surfaces
             .insert(id, SwSurface::new(DeviceIntSize::zero(), is_opaque));
     }
@@ -1423,10 +1424,11 @@ impl Compositor for SwCompositor {
         id: NativeSurfaceId,
         transform: CompositorSurfaceTransform,
         clip_rect: DeviceIntRect,
+        clip_radius: ClipRadius,
         filter: ImageRendering,
     ) {
         if self.use_native_compositor {
-            self.compositor.add_surface(device, id, transform, clip_rect, filter);
+            self.compositor.add_surface(device, id, transform, clip_rect, clip_radius, filter);
         }
 
         if self.composite_thread.is_some() {
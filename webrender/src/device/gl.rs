@@ -4,6 +4,7 @@
 
 use super::super::shader_source::{OPTIMIZED_SHADERS, UNOPTIMIZED_SHADERS};
 use api::{ImageDescriptor, ImageFormat, Parameter, BoolParameter, IntParameter, ImageRendering};
+use api::ImageWrapMode;
 use api::{MixBlendMode, ImageBufferKind, VoidPtrToSizeFn};
 use api::{CrashAnnotator, CrashAnnotation, CrashAnnotatorGuard};
 use api::units::*;
@@ -64,6 +65,44 @@ pub struct TextureSlot(pub usize);
 // In some places we need to temporarily bind a texture to any slot.
 const DEFAULT_TEXTURE: TextureSlot = TextureSlot(0);
 
+// Tokens from GL_EXT_texture_sRGB_decode, which aren't exposed by gleam.
+const TEXTURE_SRGB_DECODE_EXT: gl::GLenum = 0x8A48;
+const DECODE_EXT: gl::GLenum = 0x8A49;
+const SKIP_DECODE_EXT: gl::GLenum = 0x8A4A;
+
+// Tokens from GL_EXT_texture_filter_anisotropic (core in GL 4.6), which
+// aren't exposed by gleam.
+const TEXTURE_MAX_ANISOTROPY_EXT: gl::GLenum = 0x84FE;
+const MAX_TEXTURE_MAX_ANISOTROPY_EXT: gl::GLenum = 0x84FF;
+
+/// The anisotropy level requested for `TextureFilter::Anisotropic` textures,
+/// before clamping to the device maximum.
+const ANISOTROPY_LEVEL: f32 = 16.0;
+
+// Error from GL_KHR_robustness (core in GL 4.5), which isn't exposed by gleam.
+pub const CONTEXT_LOST: gl::GLenum = 0x0507;
+
+// Token from GL_KHR_parallel_shader_compile, which isn't exposed by gleam.
+const COMPLETION_STATUS_KHR: gl::GLenum = 0x91B1;
+
+/// Notified from the render thread when the GL context is lost, e.g. after a
+/// GPU reset. The renderer can't recover from this by itself, so the embedder
+/// is expected to recreate the GL context and the renderer.
+pub trait ContextLostHandler {
+    fn context_lost(&self);
+}
+
+/// Records a GL error caught by the error checking wrapper. Context loss is
+/// remembered so that the renderer can report it, rather than treated as a
+/// bug. Returns true if the error is fatal.
+fn record_gl_error(context_lost: &Cell<bool>, code: gl::GLenum) -> bool {
+    if code == CONTEXT_LOST {
+        context_lost.set(true);
+        return false;
+    }
+    true
+}
+
 #[repr(u32)]
 pub enum DepthFunction {
     Always = gl::ALWAYS,
@@ -79,6 +118,16 @@ pub enum TextureFilter {
     Nearest,
     Linear,
     Trilinear,
+    /// Trilinear filtering with anisotropic filtering on top, where the
+    /// device supports it. See `Capabilities::max_texture_anisotropy`.
+    Anisotropic,
+}
+
+impl TextureFilter {
+    /// Whether textures with this filter have a mipmap chain.
+    pub fn uses_mipmaps(self) -> bool {
+        matches!(self, TextureFilter::Trilinear | TextureFilter::Anisotropic)
+    }
 }
 
 /// A structure defining a particular workflow of texture transfers.
@@ -421,6 +470,12 @@ bitflags! {
     pub struct TextureFlags: u32 {
         /// This texture corresponds to one of the shared texture caches.
         const IS_SHARED_TEXTURE_CACHE = 1 << 0;
+        /// The GL texture was supplied by the embedder, which keeps ownership
+        /// of it. Deleting this texture doesn't delete the GL texture.
+        const IS_EMBEDDER_OWNED = 1 << 1;
+        /// The texture storage is sRGB encoded. Sampling skips the sRGB decode
+        /// unless it is explicitly enabled with `set_texture_srgb_decode`.
+        const IS_SRGB = 1 << 2;
     }
 }
 
@@ -439,6 +494,8 @@ pub struct Texture {
     flags: TextureFlags,
     /// An internally mutable swizzling state that may change between batches.
     active_swizzle: Cell<Swizzle>,
+    /// An internally mutable sampler wrap mode that may change between batches.
+    active_wrap_mode: Cell<ImageWrapMode>,
     /// Framebuffer Object allowing this texture to be rendered to.
     ///
     /// Empty if this texture is not used as a render target or if a depth buffer is needed.
@@ -503,6 +560,11 @@ impl Texture {
         self.last_frame_used + threshold >= current_frame_id
     }
 
+    /// Returns the GL name of this texture.
+    pub fn gl_id(&self) -> gl::GLuint {
+        self.id
+    }
+
     /// Returns the flags for this texture.
     pub fn flags(&self) -> &TextureFlags {
         &self.flags
@@ -551,8 +613,22 @@ pub struct Program {
     id: gl::GLuint,
     u_transform: gl::GLint,
     u_texture_size: gl::GLint,
+    u_edge_aa_feather: gl::GLint,
     source_info: ProgramSourceInfo,
     is_initialized: bool,
+    pending_link: Option<PendingLink>,
+}
+
+/// What `Device::start_link_program` submitted to the driver for a program
+/// whose link status hasn't been checked yet.
+enum PendingLink {
+    /// The program was loaded from a cached binary.
+    Binary,
+    /// The shaders were compiled from source and are still attached.
+    Source {
+        vs_id: gl::GLuint,
+        fs_id: gl::GLuint,
+    },
 }
 
 impl Program {
@@ -641,6 +717,22 @@ pub struct FBOId(gl::GLuint);
 #[derive(PartialEq, Eq, Hash, Debug, Copy, Clone)]
 pub struct RBOId(gl::GLuint);
 
+/// A framebuffer backed by multisampled renderbuffers, created with
+/// `Device::create_multisampled_fbo`.
+#[derive(Debug)]
+pub struct MultisampledFbo {
+    pub fbo: FBOId,
+    pub size: DeviceIntSize,
+    color_rbo: RBOId,
+    depth_rbo: Option<RBOId>,
+}
+
+impl MultisampledFbo {
+    pub fn has_depth(&self) -> bool {
+        self.depth_rbo.is_some()
+    }
+}
+
 #[derive(PartialEq, Eq, Hash, Debug, Copy, Clone)]
 pub struct VBOId(gl::GLuint);
 
@@ -957,6 +1049,9 @@ pub struct Capabilities {
     /// Whether KHR_debug is supported for getting debug messages from
     /// the driver.
     pub supports_khr_debug: bool,
+    /// Whether the driver compiles and links shaders on its own threads, and
+    /// lets us poll for completion with `GL_COMPLETION_STATUS_KHR`.
+    pub supports_parallel_shader_compile: bool,
     /// Whether we can configure texture units to do swizzling on sampling.
     pub supports_texture_swizzle: bool,
     /// Whether the driver supports uploading to textures from a non-zero
@@ -999,6 +1094,15 @@ pub struct Capabilities {
     pub supports_image_external_essl3: bool,
     /// Whether the VAO must be rebound after an attached VBO has been orphaned.
     pub requires_vao_rebind_after_orphaning: bool,
+    /// Whether RGBA8 render targets can be allocated with sRGB storage, with
+    /// both the sRGB decode on sampling and the sRGB encode on writes being
+    /// toggleable at runtime.
+    pub supports_srgb_render_targets: bool,
+    /// Whether glReadPixels can return BGRA data.
+    pub supports_bgra_readback: bool,
+    /// The maximum anisotropy level for texture sampling. 1.0 if anisotropic
+    /// filtering isn't supported.
+    pub max_texture_anisotropy: f32,
     /// The name of the renderer, as reported by GL
     pub renderer_name: String,
 }
@@ -1060,6 +1164,12 @@ impl StrideAlignment {
 // precision problems.
 const RESERVE_DEPTH_BITS: i32 = 2;
 
+#[cfg(feature = "gl_trace")]
+thread_local! {
+    /// The GL calls recorded while a GL trace is active.
+    static GL_TRACE: RefCell<Vec<String>> = RefCell::new(Vec::new());
+}
+
 pub struct Device {
     gl: Rc<dyn gl::Gl>,
 
@@ -1067,6 +1177,11 @@ pub struct Device {
     /// underling Gl instance.
     base_gl: Option<Rc<dyn gl::Gl>>,
 
+    /// If non-None, |gl| points to a wrapper recording the GL calls, and this
+    /// points to the wrapped Gl instance.
+    #[cfg(feature = "gl_trace")]
+    trace_base_gl: Option<Rc<dyn gl::Gl>>,
+
     // device state
     bound_textures: [gl::GLuint; 16],
     bound_program: gl::GLuint,
@@ -1106,6 +1221,12 @@ pub struct Device {
     /// by sharing these across targets.
     depth_targets: FastHashMap<DeviceIntSize, SharedDepthTarget>,
 
+    /// Set once a GL error has reported that the context was lost.
+    context_lost: Rc<Cell<bool>>,
+    /// Notified the first time `report_context_lost` finds the context lost.
+    context_lost_handler: Option<Box<dyn ContextLostHandler>>,
+    context_lost_reported: bool,
+
     // debug
     inside_frame: bool,
     crash_annotator: Option<Box<dyn CrashAnnotator>>,
@@ -1118,6 +1239,8 @@ pub struct Device {
     use_optimized_shaders: bool,
 
     max_texture_size: i32,
+    /// The largest sample count supported for multisampled renderbuffers.
+    max_samples: u8,
     cached_programs: Option<Rc<ProgramCache>>,
 
     // Frame counter. This is used to map between CPU
@@ -1170,9 +1293,18 @@ pub struct Device {
     #[cfg(debug_assertions)]
     shader_is_ready: bool,
 
+    /// Set when the shader that should have been bound isn't linked yet, so
+    /// that draws until the next `bind_program` are dropped instead of using
+    /// whichever program is still bound.
+    skip_draws: bool,
+
     // count created/deleted textures to report in the profiler.
     pub textures_created: u32,
     pub textures_deleted: u32,
+
+    /// Width in device pixels of anti-aliased edges, passed to shaders by
+    /// `set_uniforms`.
+    edge_aa_feather: f32,
 }
 
 /// Contains the parameters necessary to bind a draw target.
@@ -1464,6 +1596,7 @@ impl Device {
 
         let renderer_name = gl.get_string(gl::RENDERER);
         info!("Renderer: {}", renderer_name);
+
         let version_string = gl.get_string(gl::VERSION);
         info!("Version: {}", version_string);
         info!("Max texture size: {}", max_texture_size);
@@ -1485,8 +1618,14 @@ impl Device {
         // GL_OUT_OF_MEMORY, causing us to panic in debug builds.
         let supports_khr_debug =
             supports_extension(&extensions, "GL_KHR_debug") && !is_mali_valhall(&renderer_name);
+        let context_lost = Rc::new(Cell::new(false));
         if panic_on_gl_error || cfg!(debug_assertions) {
+            let context_lost = Rc::clone(&context_lost);
             gl = gl::ErrorReactingGl::wrap(gl, move |gl, name, code| {
+                if !record_gl_error(&context_lost, code) {
+                    warn!("GL context lost at {}", name);
+                    return;
+                }
                 if supports_khr_debug {
                     Self::log_driver_messages(gl);
                 }
@@ -1552,6 +1691,22 @@ impl Device {
         }
         info!("GL context {:?} {}.{}", gl.get_type(), gl_version[0], gl_version[1]);
 
+        // Multisampled renderbuffers need GLES 3, and Software WebRender has
+        // none at all.
+        let supports_multisampled_renderbuffers = match gl.get_type() {
+            gl::GlType::Gl => !renderer_name.starts_with("Software WebRender"),
+            gl::GlType::Gles => gl_version >= [3, 0],
+        };
+        let max_samples = if supports_multisampled_renderbuffers {
+            let mut max_samples = [0];
+            unsafe {
+                gl.get_integer_v(gl::MAX_SAMPLES, &mut max_samples);
+            }
+            max_samples[0].max(0).min(u8::MAX as i32) as u8
+        } else {
+            0
+        };
+
         // We block texture storage on mac because it doesn't support BGRA
         let supports_texture_storage = allow_texture_storage_support && !cfg!(target_os = "macos") &&
             match gl.get_type() {
@@ -1698,6 +1853,10 @@ impl Device {
             supports_extension(&extensions, "GL_KHR_blend_equation_advanced") &&
             !is_adreno;
 
+        let supports_parallel_shader_compile =
+            supports_extension(&extensions, "GL_KHR_parallel_shader_compile") ||
+            supports_extension(&extensions, "GL_ARB_parallel_shader_compile");
+
         let supports_dual_source_blending = match gl.get_type() {
             gl::GlType::Gl => supports_extension(&extensions,"GL_ARB_blend_func_extended") &&
                 supports_extension(&extensions,"GL_ARB_explicit_attrib_location"),
@@ -1858,6 +2017,36 @@ impl Device {
             true
         };
 
+        // Toggling the sRGB conversions requires GL_EXT_texture_sRGB_decode for
+        // sampling, and on GLES GL_EXT_sRGB_write_control for rendering.
+        let supports_srgb_render_targets = !is_software_webrender &&
+            supports_extension(&extensions, "GL_EXT_texture_sRGB_decode") &&
+            match gl.get_type() {
+                gl::GlType::Gl => true,
+                gl::GlType::Gles => gl_version >= [3, 0] &&
+                    supports_extension(&extensions, "GL_EXT_sRGB_write_control"),
+            };
+
+        let supports_bgra_readback = match gl.get_type() {
+            gl::GlType::Gl => true,
+            gl::GlType::Gles => supports_extension(&extensions, "GL_EXT_read_format_bgra"),
+        };
+
+        let supports_anisotropic_filtering = !is_software_webrender && (
+            supports_extension(&extensions, "GL_EXT_texture_filter_anisotropic") ||
+            supports_extension(&extensions, "GL_ARB_texture_filter_anisotropic") ||
+            (gl.get_type() == gl::GlType::Gl && gl_version >= [4, 6])
+        );
+        let max_texture_anisotropy = if supports_anisotropic_filtering {
+            let mut max_anisotropy = [1.0];
+            unsafe {
+                gl.get_float_v(MAX_TEXTURE_MAX_ANISOTROPY_EXT, &mut max_anisotropy);
+            }
+            max_anisotropy[0].max(1.0)
+        } else {
+            1.0
+        };
+
         let supports_qcom_tiled_rendering = if is_adreno && version_string.contains("V@0490") {
             // We have encountered rendering errors on a variety of Adreno GPUs specifically on
             // driver version V@0490, so block this extension on that driver version. See bug 1828248.
@@ -1878,6 +2067,11 @@ impl Device {
         Device {
             gl,
             base_gl: None,
+            #[cfg(feature = "gl_trace")]
+            trace_base_gl: None,
+            context_lost,
+            context_lost_handler: None,
+            context_lost_reported: false,
             crash_annotator,
             annotate_draw_call_crashes: false,
             resource_override_path,
@@ -1890,13 +2084,14 @@ impl Device {
             inside_frame: false,
 
             capabilities: Capabilities {
-                supports_multisampling: false, //TODO
+                supports_multisampling: max_samples > 1,
                 supports_copy_image_sub_data,
                 supports_color_buffer_float,
                 supports_buffer_storage,
                 supports_advanced_blend_equation,
                 supports_dual_source_blending,
                 supports_khr_debug,
+                supports_parallel_shader_compile,
                 supports_texture_swizzle,
                 supports_nonzero_pbo_offsets,
                 supports_texture_usage,
@@ -1913,6 +2108,9 @@ impl Device {
                 uses_native_antialiasing,
                 supports_image_external_essl3,
                 requires_vao_rebind_after_orphaning,
+                supports_srgb_render_targets,
+                supports_bgra_readback,
+                max_texture_anisotropy,
                 renderer_name,
             },
 
@@ -1938,6 +2136,7 @@ impl Device {
             depth_available: true,
 
             max_texture_size,
+            max_samples,
             cached_programs,
             frame_id: GpuFrameId(0),
             extensions,
@@ -1951,9 +2150,12 @@ impl Device {
 
             #[cfg(debug_assertions)]
             shader_is_ready: false,
+            skip_draws: false,
 
             textures_created: 0,
             textures_deleted: 0,
+
+            edge_aa_feather: 1.0,
         }
     }
 
@@ -1961,6 +2163,36 @@ impl Device {
         &*self.gl
     }
 
+    /// Whether a GL error has reported that the context was lost. Once lost,
+    /// the device must be recreated along with the GL context.
+    pub fn is_context_lost(&self) -> bool {
+        self.context_lost.get()
+    }
+
+    /// Record a GL error that was queried outside of the error checking
+    /// wrapper. Returns false for errors that indicate context loss.
+    pub fn record_gl_error(&self, code: gl::GLenum) -> bool {
+        record_gl_error(&self.context_lost, code)
+    }
+
+    pub fn set_context_lost_handler(&mut self, handler: Option<Box<dyn ContextLostHandler>>) {
+        self.context_lost_handler = handler;
+    }
+
+    /// Notifies the context lost handler if the context was lost. Returns true
+    /// the first time only, since the embedder is expected to recreate the
+    /// device in response.
+    pub fn report_context_lost(&mut self) -> bool {
+        if !self.is_context_lost() || self.context_lost_reported {
+            return false;
+        }
+        self.context_lost_reported = true;
+        if let Some(ref handler) = self.context_lost_handler {
+            handler.context_lost();
+        }
+        true
+    }
+
     pub fn rc_gl(&self) -> &Rc<dyn gl::Gl> {
         &self.gl
     }
@@ -2003,6 +2235,12 @@ impl Device {
         self.max_texture_size
     }
 
+    /// Returns the largest sample count supported for multisampled render
+    /// targets, or zero if multisampling is unavailable.
+    pub fn max_samples(&self) -> u8 {
+        self.max_samples
+    }
+
     pub fn surface_origin_is_top_left(&self) -> bool {
         self.surface_origin_is_top_left
     }
@@ -2107,6 +2345,19 @@ impl Device {
         shader_type: gl::GLenum,
         source: &String,
     ) -> Result<gl::GLuint, ShaderError> {
+        let id = self.submit_shader(name, shader_type, source);
+        self.check_shader(id, name, shader_type, || source.clone())?;
+        Ok(id)
+    }
+
+    /// Hand a shader to the driver for compilation, without waiting for it
+    /// to complete.
+    fn submit_shader(
+        &self,
+        name: &str,
+        shader_type: gl::GLenum,
+        source: &String,
+    ) -> gl::GLuint {
         debug!("compile {}", name);
         let id = self.gl.create_shader(shader_type);
 
@@ -2119,6 +2370,20 @@ impl Device {
 
         self.gl.shader_source(id, &[new_source.as_bytes()]);
         self.gl.compile_shader(id);
+        id
+    }
+
+    /// Check the compile status of a shader returned by `submit_shader`,
+    /// blocking until the driver is done with it. The source is only needed
+    /// to print errors.
+    #[allow(unused_variables)]
+    fn check_shader<F: FnOnce() -> String>(
+        &self,
+        id: gl::GLuint,
+        name: &str,
+        shader_type: gl::GLenum,
+        source: F,
+    ) -> Result<(), ShaderError> {
         let log = self.gl.get_shader_info_log(id);
         let mut status = [0];
         unsafe {
@@ -2132,13 +2397,13 @@ impl Device {
             };
             error!("Failed to compile {} shader: {}\n{}", type_str, name, log);
             #[cfg(debug_assertions)]
-            Self::print_shader_errors(source, &log);
+            Self::print_shader_errors(&source(), &log);
             Err(ShaderError::Compilation(name.to_string(), log))
         } else {
             if !log.is_empty() {
                 warn!("Warnings detected on shader: {}\n{}", name, log);
             }
-            Ok(id)
+            Ok(())
         }
     }
 
@@ -2149,6 +2414,7 @@ impl Device {
         {
             self.shader_is_ready = false;
         }
+        self.skip_draws = false;
 
         self.textures_created = 0;
         self.textures_deleted = 0;
@@ -2196,6 +2462,11 @@ impl Device {
         // Reset common state
         self.reset_state();
 
+        // sRGB encoding is only enabled while drawing linear space blurs. It is
+        // enabled by default on GLES with GL_EXT_sRGB_write_control, and the
+        // embedder may also have left it enabled.
+        self.disable_framebuffer_srgb();
+
         // Pixel op state
         self.gl.pixel_store_i(gl::UNPACK_ALIGNMENT, 1);
         self.gl.bind_buffer(gl::PIXEL_UNPACK_BUFFER, 0);
@@ -2264,6 +2535,30 @@ impl Device {
         self.bind_texture_impl(slot.into(), texture.id, texture.target, set_swizzle, None);
     }
 
+    /// Binds the texture and sets how its sampler wraps coordinates outside
+    /// of the [0, 1] range. Textures are created with `ImageWrapMode::Clamp`.
+    pub fn set_texture_wrap_mode<S>(&mut self, slot: S, texture: &Texture, wrap_mode: ImageWrapMode)
+    where
+        S: Into<TextureSlot>,
+    {
+        let slot = slot.into();
+        self.bind_texture_impl(slot, texture.id, texture.target, None, None);
+
+        if texture.active_wrap_mode.replace(wrap_mode) == wrap_mode {
+            return;
+        }
+
+        let wrap = match wrap_mode {
+            ImageWrapMode::Clamp => gl::CLAMP_TO_EDGE,
+            ImageWrapMode::Repeat => gl::REPEAT,
+            ImageWrapMode::Mirror => gl::MIRRORED_REPEAT,
+        };
+        self.gl.active_texture(gl::TEXTURE0 + slot.0 as gl::GLuint);
+        self.gl.tex_parameter_i(texture.target, gl::TEXTURE_WRAP_S, wrap as gl::GLint);
+        self.gl.tex_parameter_i(texture.target, gl::TEXTURE_WRAP_T, wrap as gl::GLint);
+        self.gl.active_texture(gl::TEXTURE0);
+    }
+
     pub fn bind_external_texture<S>(&mut self, slot: S, external_texture: &ExternalTexture)
     where
         S: Into<TextureSlot>,
@@ -2385,6 +2680,84 @@ impl Device {
         fbo
     }
 
+    /// Creates an FBO backed by multisampled renderbuffers of the given size
+    /// and format, with a depth attachment if requested. The contents must be
+    /// resolved into a regular texture with `blit_render_target` before they
+    /// can be sampled.
+    pub fn create_multisampled_fbo(
+        &mut self,
+        size: DeviceIntSize,
+        format: ImageFormat,
+        samples: u8,
+        with_depth: bool,
+    ) -> MultisampledFbo {
+        debug_assert!(samples > 1 && samples <= self.max_samples);
+        let desc = self.gl_describe_format(format);
+        let color_rbo = self.create_multisampled_rbo(size, desc.internal, samples);
+        let depth_rbo = if with_depth {
+            Some(self.create_multisampled_rbo(size, self.depth_format, samples))
+        } else {
+            None
+        };
+
+        let fbo = self.create_fbo();
+        fbo.bind(self.gl(), FBOTarget::Draw);
+        self.gl.framebuffer_renderbuffer(
+            gl::DRAW_FRAMEBUFFER,
+            gl::COLOR_ATTACHMENT0,
+            gl::RENDERBUFFER,
+            color_rbo.0,
+        );
+        if let Some(depth_rbo) = depth_rbo {
+            self.gl.framebuffer_renderbuffer(
+                gl::DRAW_FRAMEBUFFER,
+                gl::DEPTH_ATTACHMENT,
+                gl::RENDERBUFFER,
+                depth_rbo.0,
+            );
+        }
+        debug_assert_eq!(
+            self.gl.check_frame_buffer_status(gl::DRAW_FRAMEBUFFER),
+            gl::FRAMEBUFFER_COMPLETE,
+            "Incomplete framebuffer",
+        );
+        self.bound_draw_fbo.bind(self.gl(), FBOTarget::Draw);
+
+        MultisampledFbo {
+            fbo,
+            size,
+            color_rbo,
+            depth_rbo,
+        }
+    }
+
+    fn create_multisampled_rbo(
+        &mut self,
+        size: DeviceIntSize,
+        internal_format: gl::GLenum,
+        samples: u8,
+    ) -> RBOId {
+        let rbo = RBOId(self.gl.gen_renderbuffers(1)[0]);
+        self.gl.bind_renderbuffer(gl::RENDERBUFFER, rbo.0);
+        self.gl.renderbuffer_storage_multisample(
+            gl::RENDERBUFFER,
+            samples as _,
+            internal_format,
+            size.width as _,
+            size.height as _,
+        );
+        self.gl.bind_renderbuffer(gl::RENDERBUFFER, 0);
+        rbo
+    }
+
+    pub fn delete_multisampled_fbo(&mut self, target: MultisampledFbo) {
+        self.delete_fbo(target.fbo);
+        self.gl.delete_renderbuffers(&[target.color_rbo.0]);
+        if let Some(depth_rbo) = target.depth_rbo {
+            self.gl.delete_renderbuffers(&[depth_rbo.0]);
+        }
+    }
+
     pub fn delete_fbo(&mut self, fbo: FBOId) {
         self.gl.delete_framebuffers(&[fbo.0]);
     }
@@ -2414,17 +2787,24 @@ impl Device {
         program: &mut Program,
         descriptor: &VertexDescriptor,
     ) -> Result<(), ShaderError> {
-        profile_scope!("compile shader");
-
-        let _guard = CrashAnnotatorGuard::new(
-            &self.crash_annotator,
-            CrashAnnotation::CompileShader,
-            &program.source_info.full_name_cstr
-        );
+        self.start_link_program(program, descriptor)?;
+        self.finish_link_program(program, descriptor)
+    }
 
+    /// Submit the compile and link of a program to the driver, without
+    /// checking the results. Drivers that support
+    /// `GL_KHR_parallel_shader_compile` do the work on their own threads,
+    /// and `is_program_link_complete` tells when `finish_link_program` can
+    /// be called without blocking.
+    pub fn start_link_program(
+        &mut self,
+        program: &mut Program,
+        descriptor: &VertexDescriptor,
+    ) -> Result<(), ShaderError> {
         assert!(!program.is_initialized());
-        let mut build_program = true;
-        let info = &program.source_info;
+        if program.pending_link.is_some() {
+            return Ok(());
+        }
 
         // See if we hit the binary shader cache
         if let Some(ref cached_programs) = self.cached_programs {
@@ -2438,77 +2818,138 @@ impl Device {
                 }
             }
 
-            if let Some(entry) = cached_programs.entries.borrow_mut().get_mut(&info.digest) {
-                let mut link_status = [0];
-                unsafe {
-                    self.gl.get_program_iv(program.id, gl::LINK_STATUS, &mut link_status);
-                }
-                if link_status[0] == 0 {
-                    let error_log = self.gl.get_program_info_log(program.id);
-                    error!(
-                      "Failed to load a program object with a program binary: {} renderer {}\n{}",
-                      &info.base_filename,
-                      self.capabilities.renderer_name,
-                      error_log
-                    );
-                    if let Some(ref program_cache_handler) = cached_programs.program_cache_handler {
-                        program_cache_handler.notify_program_binary_failed(&entry.binary);
-                    }
-                } else {
-                    entry.linked = true;
-                    build_program = false;
-                }
+            if cached_programs.entries.borrow().contains_key(&program.source_info.digest) {
+                program.pending_link = Some(PendingLink::Binary);
+                return Ok(());
             }
         }
 
         // If not, we need to do a normal compile + link pass.
-        if build_program {
-            // Compile the vertex shader
-            let vs_source = info.compute_source(self, ShaderKind::Vertex);
-            let vs_id = match self.compile_shader(&info.full_name(), gl::VERTEX_SHADER, &vs_source) {
-                    Ok(vs_id) => vs_id,
-                    Err(err) => return Err(err),
-                };
+        self.start_link_program_from_source(program, descriptor);
+        Ok(())
+    }
 
-            // Compile the fragment shader
-            let fs_source = info.compute_source(self, ShaderKind::Fragment);
-            let fs_id =
-                match self.compile_shader(&info.full_name(), gl::FRAGMENT_SHADER, &fs_source) {
-                    Ok(fs_id) => fs_id,
-                    Err(err) => {
-                        self.gl.delete_shader(vs_id);
-                        return Err(err);
-                    }
-                };
+    fn start_link_program_from_source(
+        &mut self,
+        program: &mut Program,
+        descriptor: &VertexDescriptor,
+    ) {
+        let info = &program.source_info;
 
-            // Check if shader source should be dumped
-            if Some(info.base_filename) == self.dump_shader_source.as_ref().map(String::as_ref) {
-                let path = std::path::Path::new(info.base_filename);
-                std::fs::write(path.with_extension("vert"), vs_source).unwrap();
-                std::fs::write(path.with_extension("frag"), fs_source).unwrap();
-            }
+        let vs_source = info.compute_source(self, ShaderKind::Vertex);
+        let vs_id = self.submit_shader(&info.full_name(), gl::VERTEX_SHADER, &vs_source);
+        let fs_source = info.compute_source(self, ShaderKind::Fragment);
+        let fs_id = self.submit_shader(&info.full_name(), gl::FRAGMENT_SHADER, &fs_source);
 
-            // Attach shaders
-            self.gl.attach_shader(program.id, vs_id);
-            self.gl.attach_shader(program.id, fs_id);
-
-            // Bind vertex attributes
-            for (i, attr) in descriptor
-                .vertex_attributes
-                .iter()
-                .chain(descriptor.instance_attributes.iter())
-                .enumerate()
-            {
-                self.gl
-                    .bind_attrib_location(program.id, i as gl::GLuint, attr.name);
-            }
+        // Check if shader source should be dumped
+        if Some(info.base_filename) == self.dump_shader_source.as_ref().map(String::as_ref) {
+            let path = std::path::Path::new(info.base_filename);
+            std::fs::write(path.with_extension("vert"), vs_source).unwrap();
+            std::fs::write(path.with_extension("frag"), fs_source).unwrap();
+        }
+
+        // Attach shaders
+        self.gl.attach_shader(program.id, vs_id);
+        self.gl.attach_shader(program.id, fs_id);
+
+        // Bind vertex attributes
+        for (i, attr) in descriptor
+            .vertex_attributes
+            .iter()
+            .chain(descriptor.instance_attributes.iter())
+            .enumerate()
+        {
+            self.gl
+                .bind_attrib_location(program.id, i as gl::GLuint, attr.name);
+        }
 
-            if self.cached_programs.is_some() {
-                self.gl.program_parameter_i(program.id, gl::PROGRAM_BINARY_RETRIEVABLE_HINT, gl::TRUE as gl::GLint);
+        if self.cached_programs.is_some() {
+            self.gl.program_parameter_i(program.id, gl::PROGRAM_BINARY_RETRIEVABLE_HINT, gl::TRUE as gl::GLint);
+        }
+
+        // Link! If either shader failed to compile, this fails too, and
+        // `finish_link_program` reports the compile error.
+        self.gl.link_program(program.id);
+
+        program.pending_link = Some(PendingLink::Source { vs_id, fs_id });
+    }
+
+    /// Whether the driver is done with the compile and link submitted by
+    /// `start_link_program`, so that `finish_link_program` won't block.
+    /// Without `GL_KHR_parallel_shader_compile` there is no way to tell, so
+    /// this always returns true.
+    pub fn is_program_link_complete(&self, program: &Program) -> bool {
+        if program.is_initialized() || !self.capabilities.supports_parallel_shader_compile {
+            return true;
+        }
+        let mut status = [0];
+        unsafe {
+            self.gl.get_program_iv(program.id, COMPLETION_STATUS_KHR, &mut status);
+        }
+        status[0] != 0
+    }
+
+    /// Check the results of `start_link_program`, and get the program ready
+    /// for use. This blocks until the driver is done compiling and linking.
+    pub fn finish_link_program(
+        &mut self,
+        program: &mut Program,
+        descriptor: &VertexDescriptor,
+    ) -> Result<(), ShaderError> {
+        profile_scope!("compile shader");
+
+        let _guard = CrashAnnotatorGuard::new(
+            &self.crash_annotator,
+            CrashAnnotation::CompileShader,
+            &program.source_info.full_name_cstr
+        );
+
+        assert!(!program.is_initialized());
+        if program.pending_link.is_none() {
+            self.start_link_program(program, descriptor)?;
+        }
+        let info = &program.source_info;
+
+        if let Some(PendingLink::Binary) = program.pending_link {
+            program.pending_link = None;
+            let cached_programs = self.cached_programs.as_ref().unwrap();
+            let mut entries = cached_programs.entries.borrow_mut();
+            let entry = entries.get_mut(&info.digest).unwrap();
+            let mut link_status = [0];
+            unsafe {
+                self.gl.get_program_iv(program.id, gl::LINK_STATUS, &mut link_status);
             }
+            if link_status[0] == 0 {
+                let error_log = self.gl.get_program_info_log(program.id);
+                error!(
+                  "Failed to load a program object with a program binary: {} renderer {}\n{}",
+                  &info.base_filename,
+                  self.capabilities.renderer_name,
+                  error_log
+                );
+                if let Some(ref program_cache_handler) = cached_programs.program_cache_handler {
+                    program_cache_handler.notify_program_binary_failed(&entry.binary);
+                }
+                drop(entries);
+                self.start_link_program_from_source(program, descriptor);
+            } else {
+                entry.linked = true;
+            }
+        }
 
-            // Link!
-            self.gl.link_program(program.id);
+        if let Some(PendingLink::Source { vs_id, fs_id }) = program.pending_link.take() {
+            let info = &program.source_info;
+            let compiled = self.check_shader(
+                vs_id,
+                &info.full_name(),
+                gl::VERTEX_SHADER,
+                || info.compute_source(self, ShaderKind::Vertex),
+            ).and_then(|_| self.check_shader(
+                fs_id,
+                &info.full_name(),
+                gl::FRAGMENT_SHADER,
+                || info.compute_source(self, ShaderKind::Fragment),
+            ));
 
             // GL recommends detaching and deleting shaders once the link
             // is complete (whether successful or not). This allows the driver
@@ -2518,6 +2959,10 @@ impl Device {
             self.gl.delete_shader(vs_id);
             self.gl.delete_shader(fs_id);
 
+            if let Err(err) = compiled {
+                return Err(err);
+            }
+
             let mut link_status = [0];
             unsafe {
                 self.gl.get_program_iv(program.id, gl::LINK_STATUS, &mut link_status);
@@ -2548,6 +2993,7 @@ impl Device {
         program.is_initialized = true;
         program.u_transform = self.gl.get_uniform_location(program.id, "uTransform");
         program.u_texture_size = self.gl.get_uniform_location(program.id, "uTextureSize");
+        program.u_edge_aa_feather = self.gl.get_uniform_location(program.id, "uEdgeAaFeather");
 
         Ok(())
     }
@@ -2562,6 +3008,7 @@ impl Device {
         {
             self.shader_is_ready = true;
         }
+        self.skip_draws = false;
 
         if self.bound_program != program.id {
             self.gl.use_program(program.id);
@@ -2571,7 +3018,59 @@ impl Device {
         true
     }
 
+    /// Drop draw calls until the next `bind_program`, because the program
+    /// they need is still being compiled.
+    pub fn skip_draws_until_bind(&mut self) {
+        self.skip_draws = true;
+    }
+
     pub fn create_texture(
+        &mut self,
+        target: ImageBufferKind,
+        format: ImageFormat,
+        width: i32,
+        height: i32,
+        filter: TextureFilter,
+        render_target: Option<RenderTargetInfo>,
+    ) -> Texture {
+        self.create_texture_impl(
+            target,
+            format,
+            width,
+            height,
+            filter,
+            render_target,
+            TextureFlags::default(),
+        )
+    }
+
+    /// Create an RGBA8 render target with sRGB storage. The sRGB decode is
+    /// initially disabled, so sampling and rendering behave as for a regular
+    /// RGBA8 texture until `set_texture_srgb_decode` and
+    /// `enable_framebuffer_srgb` are used.
+    pub fn create_srgb_render_target(
+        &mut self,
+        target: ImageBufferKind,
+        width: i32,
+        height: i32,
+        filter: TextureFilter,
+        render_target: RenderTargetInfo,
+    ) -> Texture {
+        assert!(self.capabilities.supports_srgb_render_targets);
+        let texture = self.create_texture_impl(
+            target,
+            ImageFormat::RGBA8,
+            width,
+            height,
+            filter,
+            Some(render_target),
+            TextureFlags::IS_SRGB,
+        );
+        self.set_texture_srgb_decode(&texture, false);
+        texture
+    }
+
+    fn create_texture_impl(
         &mut self,
         target: ImageBufferKind,
         format: ImageFormat,
@@ -2579,6 +3078,7 @@ impl Device {
         mut height: i32,
         filter: TextureFilter,
         render_target: Option<RenderTargetInfo>,
+        flags: TextureFlags,
     ) -> Texture {
         debug_assert!(self.inside_frame);
 
@@ -2596,10 +3096,11 @@ impl Device {
             format,
             filter,
             active_swizzle: Cell::default(),
+            active_wrap_mode: Cell::default(),
             fbo: None,
             fbo_with_depth: None,
             last_frame_used: self.frame_id,
-            flags: TextureFlags::default(),
+            flags,
         };
         self.bind_texture(DEFAULT_TEXTURE, &texture, Swizzle::default());
         self.set_texture_parameters(texture.target, filter);
@@ -2609,12 +3110,17 @@ impl Device {
         }
 
         // Allocate storage.
-        let desc = self.gl_describe_format(texture.format);
+        let mut desc = self.gl_describe_format(texture.format);
+        if flags.contains(TextureFlags::IS_SRGB) {
+            debug_assert_eq!(texture.format, ImageFormat::RGBA8);
+            desc.internal = gl::SRGB8_ALPHA8;
+            desc.external = gl::RGBA;
+        }
 
         // Firefox doesn't use mipmaps, but Servo uses them for standalone image
         // textures images larger than 512 pixels. This is the only case where
-        // we set the filter to trilinear.
-        let mipmap_levels =  if texture.filter == TextureFilter::Trilinear {
+        // we set the filter to trilinear or anisotropic.
+        let mipmap_levels =  if texture.filter.uses_mipmaps() {
             let max_dimension = cmp::max(width, height);
             ((max_dimension) as f64).log2() as gl::GLint + 1
         } else {
@@ -2667,16 +3173,57 @@ impl Device {
         texture
     }
 
+    /// Wrap a texture allocated by the embedder, whose storage must already
+    /// match the requested format and size. The resulting texture is flagged
+    /// `IS_EMBEDDER_OWNED`, so deleting it leaves the GL texture alive.
+    pub fn create_texture_from_embedder(
+        &mut self,
+        id: gl::GLuint,
+        target: ImageBufferKind,
+        format: ImageFormat,
+        width: i32,
+        height: i32,
+        filter: TextureFilter,
+        render_target: Option<RenderTargetInfo>,
+    ) -> Texture {
+        debug_assert!(self.inside_frame);
+
+        let mut texture = Texture {
+            id,
+            target: get_gl_target(target),
+            size: DeviceIntSize::new(width, height),
+            format,
+            filter,
+            active_swizzle: Cell::default(),
+            active_wrap_mode: Cell::default(),
+            fbo: None,
+            fbo_with_depth: None,
+            last_frame_used: self.frame_id,
+            flags: TextureFlags::IS_EMBEDDER_OWNED,
+        };
+        self.bind_texture(DEFAULT_TEXTURE, &texture, Swizzle::default());
+        self.set_texture_parameters(texture.target, filter);
+
+        if let Some(rt_info) = render_target {
+            self.init_fbos(&mut texture, false);
+            if rt_info.has_depth {
+                self.init_fbos(&mut texture, true);
+            }
+        }
+
+        texture
+    }
+
     fn set_texture_parameters(&mut self, target: gl::GLuint, filter: TextureFilter) {
         let mag_filter = match filter {
             TextureFilter::Nearest => gl::NEAREST,
-            TextureFilter::Linear | TextureFilter::Trilinear => gl::LINEAR,
+            TextureFilter::Linear | TextureFilter::Trilinear | TextureFilter::Anisotropic => gl::LINEAR,
         };
 
         let min_filter = match filter {
             TextureFilter::Nearest => gl::NEAREST,
             TextureFilter::Linear => gl::LINEAR,
-            TextureFilter::Trilinear => gl::LINEAR_MIPMAP_LINEAR,
+            TextureFilter::Trilinear | TextureFilter::Anisotropic => gl::LINEAR_MIPMAP_LINEAR,
         };
 
         self.gl
@@ -2684,6 +3231,14 @@ impl Device {
         self.gl
             .tex_parameter_i(target, gl::TEXTURE_MIN_FILTER, min_filter as gl::GLint);
 
+        if filter == TextureFilter::Anisotropic && self.capabilities.max_texture_anisotropy > 1.0 {
+            self.gl.tex_parameter_f(
+                target,
+                TEXTURE_MAX_ANISOTROPY_EXT,
+                ANISOTROPY_LEVEL.min(self.capabilities.max_texture_anisotropy),
+            );
+        }
+
         self.gl
             .tex_parameter_i(target, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as gl::GLint);
         self.gl
@@ -2912,7 +3467,7 @@ impl Device {
 
         let filter = match filter {
             TextureFilter::Nearest => gl::NEAREST,
-            TextureFilter::Linear | TextureFilter::Trilinear => gl::LINEAR,
+            TextureFilter::Linear | TextureFilter::Trilinear | TextureFilter::Anisotropic => gl::LINEAR,
         };
 
         let src_x0 = src_rect.min.x + self.bound_read_fbo.1.x;
@@ -2992,7 +3547,9 @@ impl Device {
             self.release_depth_target(texture.get_dimensions());
         }
 
-        self.gl.delete_textures(&[texture.id]);
+        if !texture.flags.contains(TextureFlags::IS_EMBEDDER_OWNED) {
+            self.gl.delete_textures(&[texture.id]);
+        }
 
         for bound_texture in &mut self.bound_textures {
             if *bound_texture == texture.id {
@@ -3058,8 +3615,10 @@ impl Device {
             id: pid,
             u_transform: 0,
             u_texture_size: 0,
+            u_edge_aa_feather: 0,
             source_info,
             is_initialized: false,
+            pending_link: None,
         };
 
         Ok(program)
@@ -3114,6 +3673,15 @@ impl Device {
 
         self.gl
             .uniform_matrix_4fv(program.u_transform, false, &transform.to_array());
+        if program.u_edge_aa_feather != -1 {
+            self.gl.uniform_1f(program.u_edge_aa_feather, self.edge_aa_feather);
+        }
+    }
+
+    /// Sets the width in device pixels of anti-aliased edges drawn by
+    /// shaders bound after this call.
+    pub fn set_edge_aa_feather(&mut self, feather: f32) {
+        self.edge_aa_feather = feather;
     }
 
     /// Sets the uTextureSize uniform. Most shaders do not require this to be called
@@ -3293,6 +3861,28 @@ impl Device {
         )
     }
 
+    /// Read the depth values of a rectangle of the bound read framebuffer.
+    /// GLES doesn't support reading DEPTH_COMPONENT, so this returns None there.
+    pub fn read_depth_pixels(&mut self, rect: FramebufferIntRect) -> Option<Vec<f32>> {
+        if let gl::GlType::Gles = self.gl.get_type() {
+            return None;
+        }
+        self.gl.flush();
+        let bytes = self.gl.read_pixels(
+            rect.min.x as _,
+            rect.min.y as _,
+            rect.width() as _,
+            rect.height() as _,
+            gl::DEPTH_COMPONENT,
+            gl::FLOAT,
+        );
+        let depth = bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        Some(depth)
+    }
+
     /// Read rectangle of pixels into the specified output slice.
     pub fn read_pixels_into(
         &mut self,
@@ -3621,6 +4211,9 @@ impl Device {
     }
 
     pub fn draw_triangles_u16(&mut self, first_vertex: i32, index_count: i32) {
+        if self.skip_draws {
+            return;
+        }
         debug_assert!(self.inside_frame);
         #[cfg(debug_assertions)]
         debug_assert!(self.shader_is_ready);
@@ -3644,6 +4237,9 @@ impl Device {
     }
 
     pub fn draw_triangles_u32(&mut self, first_vertex: i32, index_count: i32) {
+        if self.skip_draws {
+            return;
+        }
         debug_assert!(self.inside_frame);
         #[cfg(debug_assertions)]
         debug_assert!(self.shader_is_ready);
@@ -3667,6 +4263,9 @@ impl Device {
     }
 
     pub fn draw_nonindexed_points(&mut self, first_vertex: i32, vertex_count: i32) {
+        if self.skip_draws {
+            return;
+        }
         debug_assert!(self.inside_frame);
         #[cfg(debug_assertions)]
         debug_assert!(self.shader_is_ready);
@@ -3685,6 +4284,9 @@ impl Device {
     }
 
     pub fn draw_nonindexed_lines(&mut self, first_vertex: i32, vertex_count: i32) {
+        if self.skip_draws {
+            return;
+        }
         debug_assert!(self.inside_frame);
         #[cfg(debug_assertions)]
         debug_assert!(self.shader_is_ready);
@@ -3703,6 +4305,9 @@ impl Device {
     }
 
     pub fn draw_indexed_triangles(&mut self, index_count: i32) {
+        if self.skip_draws {
+            return;
+        }
         debug_assert!(self.inside_frame);
         #[cfg(debug_assertions)]
         debug_assert!(self.shader_is_ready);
@@ -3726,6 +4331,9 @@ impl Device {
     }
 
     pub fn draw_indexed_triangles_instanced_u16(&mut self, index_count: i32, instance_count: i32) {
+        if self.skip_draws {
+            return;
+        }
         debug_assert!(self.inside_frame);
         #[cfg(debug_assertions)]
         debug_assert!(self.shader_is_ready);
@@ -3776,6 +4384,35 @@ impl Device {
         }
     }
 
+    /// Start recording the GL calls issued through this device, until
+    /// `end_gl_trace` is called. Only the names of the calls are recorded.
+    #[cfg(feature = "gl_trace")]
+    pub fn begin_gl_trace(&mut self) {
+        if self.trace_base_gl.is_some() {
+            return;
+        }
+
+        fn record(name: &str, _duration: Duration) {
+            GL_TRACE.with(|trace| trace.borrow_mut().push(name.to_string()));
+        }
+
+        // A zero threshold reports every call to the callback.
+        let wrapped = gl::ProfilingGl::wrap(self.gl.clone(), Duration::new(0, 0), record);
+        let base = mem::replace(&mut self.gl, wrapped);
+        self.trace_base_gl = Some(base);
+    }
+
+    /// Stop recording GL calls, and return the calls recorded since
+    /// `begin_gl_trace`.
+    #[cfg(feature = "gl_trace")]
+    pub fn end_gl_trace(&mut self) -> Vec<String> {
+        if let Some(base) = self.trace_base_gl.take() {
+            self.gl = base;
+        }
+
+        GL_TRACE.with(|trace| mem::take(&mut *trace.borrow_mut()))
+    }
+
     pub fn clear_target(
         &self,
         color: Option<[f32; 4]>,
@@ -3831,6 +4468,26 @@ impl Device {
         self.gl.disable(gl::DEPTH_TEST);
     }
 
+    /// Enable or disable the sRGB decode when sampling from an sRGB texture.
+    pub fn set_texture_srgb_decode(&mut self, texture: &Texture, decode: bool) {
+        debug_assert!(texture.flags.contains(TextureFlags::IS_SRGB));
+        self.bind_texture(DEFAULT_TEXTURE, texture, Swizzle::default());
+        let value = if decode { DECODE_EXT } else { SKIP_DECODE_EXT };
+        self.gl.tex_parameter_i(texture.target, TEXTURE_SRGB_DECODE_EXT, value as gl::GLint);
+    }
+
+    /// Encode fragment outputs to sRGB when rendering to an sRGB target.
+    pub fn enable_framebuffer_srgb(&self) {
+        debug_assert!(self.capabilities.supports_srgb_render_targets);
+        self.gl.enable(gl::FRAMEBUFFER_SRGB);
+    }
+
+    pub fn disable_framebuffer_srgb(&self) {
+        if self.capabilities.supports_srgb_render_targets {
+            self.gl.disable(gl::FRAMEBUFFER_SRGB);
+        }
+    }
+
     pub fn enable_depth_write(&self) {
         assert!(self.depth_available, "Enabling depth write without depth target");
         self.gl.depth_mask(true);
@@ -4789,7 +5446,7 @@ impl<'a> TextureUploader<'a> {
         }
 
         // If using tri-linear filtering, build the mip-map chain for this texture.
-        if chunk.texture.filter == TextureFilter::Trilinear {
+        if chunk.texture.filter.uses_mipmaps() {
             device.gl.generate_mipmap(chunk.texture.target);
         }
 
@@ -4805,3 +5462,71 @@ fn texels_to_u8_slice<T: Texel>(texels: &[T]) -> &[u8] {
         slice::from_raw_parts(texels.as_ptr() as *const u8, texels.len() * mem::size_of::<T>())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_context_lost_is_not_fatal() {
+        let context_lost = Cell::new(false);
+
+        assert!(record_gl_error(&context_lost, gl::INVALID_OPERATION));
+        assert!(!context_lost.get());
+
+        assert!(!record_gl_error(&context_lost, CONTEXT_LOST));
+        assert!(context_lost.get());
+    }
+
+    #[cfg(feature = "sw_compositor")]
+    struct CountingContextLostHandler(Rc<Cell<usize>>);
+
+    #[cfg(feature = "sw_compositor")]
+    impl ContextLostHandler for CountingContextLostHandler {
+        fn context_lost(&self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "sw_compositor")]
+    fn test_context_lost_handler_fires_once() {
+        let context = swgl::Context::create();
+        context.make_current();
+
+        let mut device = Device::new(
+            Rc::new(context),
+            None,
+            None,
+            false,
+            UploadMethod::Immediate,
+            0,
+            None,
+            false,
+            false,
+            None,
+            false,
+            true,
+        );
+        let calls = Rc::new(Cell::new(0));
+        device.set_context_lost_handler(Some(Box::new(
+            CountingContextLostHandler(Rc::clone(&calls)),
+        )));
+
+        // Nothing to report while the context is alive.
+        assert!(!device.report_context_lost());
+        assert_eq!(calls.get(), 0);
+
+        // A lost context is recorded instead of panicking, and the handler
+        // only hears about it once.
+        assert!(!device.record_gl_error(CONTEXT_LOST));
+        assert!(device.is_context_lost());
+        assert!(device.report_context_lost());
+        assert_eq!(calls.get(), 1);
+        assert!(!device.report_context_lost());
+        assert_eq!(calls.get(), 1);
+
+        drop(device);
+        context.destroy();
+    }
+}
@@ -6,7 +6,6 @@ use gleam::gl;
 use std::mem;
 use std::rc::Rc;
 
-use crate::device::GpuFrameId;
 use crate::profiler::GpuProfileTag;
 
 #[derive(Copy, Clone, Debug)]
@@ -70,7 +69,7 @@ pub struct GpuFrameProfile {
     gl: Rc<dyn gl::Gl>,
     timers: QuerySet<GpuTimer>,
     samplers: QuerySet<GpuSampler>,
-    frame_id: GpuFrameId,
+    frame_id: u64,
     inside_frame: bool,
     debug_method: GpuDebugMethod,
 }
@@ -81,7 +80,7 @@ impl GpuFrameProfile {
             gl,
             timers: QuerySet::new(),
             samplers: QuerySet::new(),
-            frame_id: GpuFrameId::new(0),
+            frame_id: 0,
             inside_frame: false,
             debug_method
         }
@@ -109,7 +108,7 @@ impl GpuFrameProfile {
         self.samplers.set = Vec::new();
     }
 
-    fn begin_frame(&mut self, frame_id: GpuFrameId) {
+    fn begin_frame(&mut self, frame_id: u64) {
         self.frame_id = frame_id;
         self.timers.reset();
         self.samplers.reset();
@@ -160,7 +159,7 @@ impl GpuFrameProfile {
         GpuSampleQuery
     }
 
-    fn build_samples(&mut self) -> (GpuFrameId, Vec<GpuTimer>, Vec<GpuSampler>) {
+    fn build_samples(&mut self) -> (u64, Vec<GpuTimer>, Vec<GpuSampler>) {
         debug_assert!(!self.inside_frame);
         let gl = &self.gl;
 
@@ -236,11 +235,11 @@ impl GpuProfiler {
         }
     }
 
-    pub fn build_samples(&mut self) -> (GpuFrameId, Vec<GpuTimer>, Vec<GpuSampler>) {
+    pub fn build_samples(&mut self) -> (u64, Vec<GpuTimer>, Vec<GpuSampler>) {
         self.frames[self.next_frame].build_samples()
     }
 
-    pub fn begin_frame(&mut self, frame_id: GpuFrameId) {
+    pub fn begin_frame(&mut self, frame_id: u64) {
         self.frames[self.next_frame].begin_frame(frame_id);
     }
 
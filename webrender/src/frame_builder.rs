@@ -2,11 +2,11 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use api::{ColorF, DebugFlags, FontRenderMode, PremultipliedColorF, ExternalScrollId, MinimapData};
+use api::{ClipAaQuality, ColorF, DebugFlags, FontRenderMode, PremultipliedColorF, ExternalScrollId, MinimapData};
 use api::units::*;
 use plane_split::BspSplitter;
 use crate::batch::{BatchBuilder, AlphaBatchBuilder, AlphaBatchContainer};
-use crate::clip::{ClipStore, ClipTree};
+use crate::clip::{ClipDataStore, ClipItemKind, ClipStore, ClipTree};
 use crate::command_buffer::{PrimitiveCommand, CommandBufferList, CommandBufferIndex};
 use crate::debug_colors;
 use crate::spatial_node::SpatialNodeType;
@@ -16,7 +16,7 @@ use crate::debug_item::DebugItem;
 use crate::gpu_cache::{GpuCache, GpuCacheHandle};
 use crate::gpu_types::{PrimitiveHeaders, TransformPalette, ZBufferIdGenerator};
 use crate::gpu_types::{QuadSegment, TransformData};
-use crate::internal_types::{FastHashMap, PlaneSplitter, FrameId, FrameStamp};
+use crate::internal_types::{FastHashMap, FastHashSet, PlaneSplitter, FrameId, FrameStamp};
 use crate::picture::{DirtyRegion, SliceId, TileCacheInstance};
 use crate::picture::{SurfaceInfo, SurfaceIndex};
 use crate::picture::{SubpixelMode, RasterConfig, PictureCompositeMode};
@@ -30,6 +30,7 @@ use crate::render_target::{RenderTarget, PictureCacheTarget, TextureCacheRenderT
 use crate::render_target::{RenderTargetContext, RenderTargetKind, AlphaRenderTarget, ColorRenderTarget};
 use crate::render_task_graph::{RenderTaskGraph, Pass, SubPassSurface};
 use crate::render_task_graph::{RenderPass, RenderTaskGraphBuilder};
+use crate::render_api::FrameComplexity;
 use crate::render_task::{RenderTaskKind, StaticRenderTaskSurface};
 use crate::resource_cache::{ResourceCache};
 use crate::scene::{BuiltScene, SceneProperties};
@@ -38,7 +39,7 @@ use crate::segment::SegmentBuilder;
 use crate::surface::SurfaceBuilder;
 use std::{f32, mem};
 use crate::util::{VecHelper, Preallocator};
-use crate::visibility::{update_prim_visibility, FrameVisibilityState, FrameVisibilityContext};
+use crate::visibility::{update_prim_visibility, FrameVisibilityState, FrameVisibilityContext, VisibilityState};
 use crate::internal_types::{FrameVec, FrameMemory};
 
 #[derive(Clone, Copy, Debug)]
@@ -62,11 +63,48 @@ pub struct FrameBuilderConfig {
     pub tile_size_override: Option<DeviceIntSize>,
     pub max_surface_override: Option<usize>,
     pub max_depth_ids: i32,
+    /// A debug override that lowers the depth id budget used while batching.
+    /// Composite tiles always use the full `max_depth_ids`.
+    pub max_depth_ids_override: Option<i32>,
     pub max_target_size: i32,
     pub force_invalidation: bool,
     pub is_software: bool,
     pub low_quality_pinch_zoom: bool,
     pub max_shared_surface_size: i32,
+    /// If true, intermediate color targets have sRGB storage so that blurs
+    /// are computed in linear space.
+    pub use_srgb_render_targets: bool,
+    /// Taken from the document's quality settings when the scene is built.
+    pub clip_aa_quality: ClipAaQuality,
+    /// Distance in device pixels around the screen within which primitives
+    /// are not culled.
+    pub culling_margin: f32,
+    /// Blur render tasks larger than this are downscaled further before
+    /// blurring, and upsampled when they are used.
+    pub max_blur_raster_size: Option<DeviceIntSize>,
+    /// Upper bound on the number of intermediate render target textures
+    /// borrowed from the render target pool while building a frame.
+    pub max_render_targets_per_frame: Option<usize>,
+    /// The document is rasterized at its device size multiplied by this
+    /// factor, and upsampled to the device size when composited.
+    pub render_scale: f32,
+    /// Width in device pixels of anti-aliased primitive and clip edges.
+    pub edge_aa_feather: f32,
+    /// Outline the clips that clip a visible primitive in each frame. See
+    /// `DebugCommand::SetShowClipBounds`.
+    pub show_clip_bounds: bool,
+}
+
+impl FrameBuilderConfig {
+    /// The render scale that applies to scenes built with this config. Only
+    /// the draw compositor upsamples the document, so native compositing
+    /// always renders at full resolution.
+    pub fn effective_render_scale(&self) -> f32 {
+        match self.compositor_kind {
+            CompositorKind::Draw { .. } => self.render_scale,
+            CompositorKind::Native { .. } => 1.0,
+        }
+    }
 }
 
 /// A set of common / global resources that are retained between
@@ -239,6 +277,9 @@ pub struct PictureContext {
     pub surface_index: SurfaceIndex,
     pub dirty_region_count: usize,
     pub subpixel_mode: SubpixelMode,
+    /// The area of the surface known to have an opaque background once
+    /// composited, in picture space.
+    pub known_opaque_rect: PictureRect,
 }
 
 /// Mutable state of a picture that gets modified when
@@ -340,6 +381,12 @@ impl FrameBuilder {
                 root_spatial_node_index,
             };
 
+            // Split any limit on the number of native compositor tiles updated per
+            // frame evenly between the picture cache slices.
+            let max_tiles_per_slice = scene.config.compositor_kind
+                .get_max_tiles_per_frame()
+                .map(|max_tiles| (max_tiles / scene.tile_cache_pictures.len().max(1)).max(1));
+
             for pic_index in scene.tile_cache_pictures.iter().rev() {
                 let pic = &mut scene.prim_store.pictures[pic_index.0];
 
@@ -368,10 +415,20 @@ impl FrameBuilder {
                         let world_culling_rect = tile_cache.pre_update(
                             surface.unclipped_local_rect,
                             surface_index,
+                            max_tiles_per_slice,
                             &visibility_context,
                             &mut visibility_state,
                         );
 
+                        // Keep primitives that are about to scroll or animate into view,
+                        // so that they are prepared before they become visible.
+                        let world_culling_rect = if scene.config.culling_margin > 0.0 {
+                            let margin = scene.config.culling_margin / global_device_pixel_scale.get();
+                            world_culling_rect.union(&global_screen_world_rect.inflate(margin, margin))
+                        } else {
+                            world_culling_rect
+                        };
+
                         // Push a new surface, supplying the list of clips that should be
                         // ignored, since they are handled by clipping when drawing this surface.
                         visibility_state.push_surface(
@@ -453,6 +510,7 @@ impl FrameBuilder {
                     *pic_index,
                     None,
                     SubpixelMode::Allow,
+                    PictureRect::zero(),
                     &mut frame_state,
                     &frame_context,
                     data_stores,
@@ -545,6 +603,7 @@ impl FrameBuilder {
         scene.clip_store.begin_frame(&mut scratch.clip_store);
 
         rg_builder.begin_frame(stamp.frame_id());
+        rg_builder.set_max_blur_raster_size(scene.config.max_blur_raster_size);
 
         // TODO(dp): Remove me completely!!
         let global_device_pixel_scale = DevicePixelScale::new(1.0);
@@ -555,7 +614,9 @@ impl FrameBuilder {
         let mut composite_state = CompositeState::new(
             scene.config.compositor_kind,
             scene.config.max_depth_ids,
-            dirty_rects_are_valid,
+            // Dirty rects are in the rasterized document's pixels, which don't
+            // map to framebuffer pixels when the document is upsampled.
+            dirty_rects_are_valid && scene.config.effective_render_scale() == 1.0,
             scene.config.low_quality_pinch_zoom,
             &frame_memory,
         );
@@ -592,6 +653,16 @@ impl FrameBuilder {
 
         self.render_minimap(&mut scratch.primitive, &spatial_tree, minimap_data);
 
+        if scene.config.show_clip_bounds {
+            self.render_clip_bounds(
+                &mut scratch.primitive,
+                &scene.prim_instances,
+                &scene.clip_store,
+                &data_stores.clip,
+                &spatial_tree,
+            );
+        }
+
         profile.start_time(profiler::FRAME_BATCHING_TIME);
 
         let mut deferred_resolves = frame_memory.new_vec();
@@ -602,6 +673,8 @@ impl FrameBuilder {
             gpu_cache,
             &mut deferred_resolves,
             scene.config.max_shared_surface_size,
+            scene.config.use_srgb_render_targets,
+            scene.config.max_render_targets_per_frame,
             &frame_memory,
         );
 
@@ -615,7 +688,9 @@ impl FrameBuilder {
             profile_marker!("Batching");
 
             // Used to generated a unique z-buffer value per primitive.
-            let mut z_generator = ZBufferIdGenerator::new(scene.config.max_depth_ids);
+            let max_depth_ids = scene.config.max_depth_ids_override
+                .unwrap_or(scene.config.max_depth_ids);
+            let mut z_generator = ZBufferIdGenerator::new(max_depth_ids);
             let use_dual_source_blending = scene.config.dual_source_blending_is_supported;
 
             for pass in render_tasks.passes.iter().rev() {
@@ -628,6 +703,8 @@ impl FrameBuilder {
                     use_advanced_blending: scene.config.gpu_supports_advanced_blend,
                     break_advanced_blend_batches: !scene.config.advanced_blend_is_coherent,
                     batch_lookback_count: scene.config.batch_lookback_count,
+                    max_depth_ids,
+                    clip_aa_quality: scene.config.clip_aa_quality,
                     spatial_tree,
                     data_stores,
                     surfaces: &scene.surfaces,
@@ -670,6 +747,8 @@ impl FrameBuilder {
                 use_advanced_blending: scene.config.gpu_supports_advanced_blend,
                 break_advanced_blend_batches: !scene.config.advanced_blend_is_coherent,
                 batch_lookback_count: scene.config.batch_lookback_count,
+                max_depth_ids,
+                clip_aa_quality: scene.config.clip_aa_quality,
                 spatial_tree,
                 data_stores,
                 surfaces: &scene.surfaces,
@@ -706,6 +785,12 @@ impl FrameBuilder {
         let gpu_buffer_f = gpu_buffer_builder.f32.finalize(&render_tasks);
         let gpu_buffer_i = gpu_buffer_builder.i32.finalize(&render_tasks);
 
+        let complexity = compute_frame_complexity(
+            &passes,
+            &render_tasks,
+            profile,
+        );
+
         Frame {
             device_rect: DeviceIntRect::from_origin_and_size(
                 device_origin,
@@ -723,10 +808,68 @@ impl FrameBuilder {
             composite_state,
             gpu_buffer_f,
             gpu_buffer_i,
+            complexity,
+            render_scale: scene.config.effective_render_scale(),
+            edge_aa_feather: scene.config.edge_aa_feather,
             allocator_memory: frame_memory,
         }
     }
 
+    /// Outline the world space bounds of the clips that clip a visible
+    /// primitive this frame, colored by the kind of clip. Clips that don't
+    /// affect any visible primitive aren't outlined. See
+    /// `DebugCommand::SetShowClipBounds`.
+    fn render_clip_bounds(
+        &self,
+        scratch: &mut PrimitiveScratchBuffer,
+        prim_instances: &[PrimitiveInstance],
+        clip_store: &ClipStore,
+        clip_data_store: &ClipDataStore,
+        spatial_tree: &SpatialTree,
+    ) {
+        const STROKE_WIDTH: f32 = 2.0;
+
+        let mut seen = FastHashSet::default();
+
+        let handles = prim_instances
+            .iter()
+            .filter(|prim_instance| {
+                matches!(prim_instance.vis.state, VisibilityState::Visible { .. })
+            })
+            .flat_map(|prim_instance| {
+                let clips_range = prim_instance.vis.clip_chain.clips_range;
+                (0 .. clips_range.count).map(move |i| {
+                    clip_store.get_instance_from_range(&clips_range, i).handle
+                })
+            });
+
+        for handle in handles {
+            if !seen.insert(handle) {
+                continue;
+            }
+
+            let clip = &clip_data_store[handle];
+            let (local_rect, color) = match clip.item.kind {
+                ClipItemKind::Rectangle { rect, .. } => (rect, debug_colors::LIME),
+                ClipItemKind::RoundedRectangle { rect, .. } => (rect, debug_colors::BLUE),
+                ClipItemKind::Image { rect, .. } => (rect, debug_colors::MAGENTA),
+                ClipItemKind::BoxShadow { ref source } => (source.prim_shadow_rect, debug_colors::ORANGE),
+            };
+
+            let world_rect = spatial_tree
+                .get_world_transform(clip.item.spatial_node_index)
+                .into_transform()
+                .outer_transformed_box2d(&local_rect);
+
+            // Skip clips that are too small to outline, or can't be projected.
+            if let Some(world_rect) = world_rect {
+                if world_rect.width() > 2.0 * STROKE_WIDTH && world_rect.height() > 2.0 * STROKE_WIDTH {
+                    scratch.push_debug_rect_with_stroke_width(world_rect, color, STROKE_WIDTH);
+                }
+            }
+        }
+    }
+
     fn render_minimap(
         &self,
         scratch: &mut PrimitiveScratchBuffer,
@@ -1003,6 +1146,7 @@ pub fn build_render_pass(
                             screen_size,
                             ctx.break_advanced_blend_batches,
                             ctx.batch_lookback_count,
+                            ctx.max_depth_ids,
                             task_id,
                             task_id.into(),
                             &ctx.frame_memory,
@@ -1165,6 +1309,18 @@ pub struct Frame {
     pub gpu_buffer_f: GpuBufferF,
     pub gpu_buffer_i: GpuBufferI,
 
+    /// Summary of the amount of work in this frame, reported to the API
+    /// through `RenderApi::get_frame_complexity`.
+    pub complexity: FrameComplexity,
+
+    /// The factor the document was scaled by when it was rasterized. The
+    /// renderer upsamples `device_rect` by its inverse when compositing.
+    pub render_scale: f32,
+
+    /// Width in device pixels of anti-aliased edges, passed to the shaders
+    /// drawing the frame.
+    pub edge_aa_feather: f32,
+
     /// The backing store for the frame's allocator.
     ///
     /// # Safety
@@ -1196,3 +1352,46 @@ impl Frame {
         self.passes.is_empty()
     }
 }
+
+/// Gather the counts reported by `RenderApi::get_frame_complexity` from a
+/// freshly built frame.
+fn compute_frame_complexity(
+    passes: &[RenderPass],
+    render_tasks: &RenderTaskGraph,
+    profile: &TransactionProfile,
+) -> FrameComplexity {
+    fn container_batches(container: &AlphaBatchContainer) -> usize {
+        container.opaque_batches.len() + container.alpha_batches.len()
+    }
+
+    let mut batch_count = 0;
+    let mut estimated_pixels_shaded = 0;
+
+    for pass in passes {
+        for target in &pass.color.targets {
+            batch_count += target.alpha_batch_containers
+                .iter()
+                .map(container_batches)
+                .sum::<usize>();
+            estimated_pixels_shaded += target.used_rect.area() as u64;
+        }
+
+        for target in &pass.alpha.targets {
+            estimated_pixels_shaded += target.used_rect.area() as u64;
+        }
+
+        for target in &pass.picture_cache {
+            if let PictureCacheTargetKind::Draw { ref alpha_batch_container } = target.kind {
+                batch_count += container_batches(alpha_batch_container);
+            }
+            estimated_pixels_shaded += target.dirty_rect.area() as u64;
+        }
+    }
+
+    FrameComplexity {
+        primitive_count: profile.get(profiler::VISIBLE_PRIMITIVES).unwrap_or(0.0) as usize,
+        batch_count,
+        estimated_pixels_shaded,
+        render_task_count: render_tasks.tasks.len(),
+    }
+}
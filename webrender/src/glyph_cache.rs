@@ -2,10 +2,18 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use api::{FontKey, FontInstanceKey, IdNamespace};
+use api::{FontKey, FontInstanceKey, FontTemplate, IdNamespace, ImageFormat};
+#[cfg(feature = "capture")]
+use api::units::DeviceIntRect;
+#[cfg(feature = "capture")]
+use glyph_rasterizer::SubpixelOffset;
 use glyph_rasterizer::{FontInstance, GlyphFormat, GlyphKey, GlyphRasterizer};
-use crate::internal_types::{FrameId, FrameStamp, FastHashMap};
+#[cfg(feature = "capture")]
+use crate::internal_types::CacheTextureId;
+use crate::internal_types::{FastHashMap, FrameId, FrameStamp, ResourceHashMap};
 use crate::resource_cache::ResourceClassCache;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 use std::sync::Arc;
 use crate::texture_cache::{EvictionNotice, TextureCache};
 use crate::texture_cache::TextureCacheHandle;
@@ -16,6 +24,100 @@ use crate::texture_cache::TextureCacheHandle;
 pub struct CachedGlyphInfo {
     pub format: GlyphFormat,
     pub texture_cache_handle: TextureCacheHandle,
+    /// A CPU copy of the rasterized bitmap, kept only when glyph retention
+    /// is enabled so that the glyph can be exported.
+    #[cfg_attr(any(feature = "capture", feature = "replay"), serde(skip))]
+    pub retained: Option<Arc<RetainedGlyph>>,
+}
+
+/// A rasterized glyph bitmap along with the placement information needed to
+/// upload it to the texture cache again without going through the rasterizer.
+#[derive(Debug)]
+pub struct RetainedGlyph {
+    pub width: i32,
+    pub height: i32,
+    pub left: f32,
+    pub top: f32,
+    pub scale: f32,
+    pub format: GlyphFormat,
+    pub image_format: ImageFormat,
+    pub bytes: Arc<Vec<u8>>,
+}
+
+/// Identifies a rasterized glyph independently of the font and font instance
+/// keys, which are only meaningful to the renderer that allocated them.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct PortableGlyphKey {
+    /// Hash of the font template. Changing the font data behind a key changes
+    /// the fingerprint, so previously exported glyphs no longer match.
+    font_fingerprint: u64,
+    /// Hash of everything that affects rasterization in the font instance,
+    /// other than the keys.
+    instance_fingerprint: u64,
+    glyph_key: GlyphKey,
+}
+
+impl PortableGlyphKey {
+    pub fn new(font_fingerprint: u64, instance_fingerprint: u64, glyph_key: GlyphKey) -> Self {
+        PortableGlyphKey {
+            font_fingerprint,
+            instance_fingerprint,
+            glyph_key,
+        }
+    }
+}
+
+/// Computes the fingerprint of a font template used by `PortableGlyphKey`.
+pub fn font_template_fingerprint(template: &FontTemplate) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    template.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Computes the fingerprint of a font instance used by `PortableGlyphKey`.
+pub fn font_instance_fingerprint(font: &FontInstance) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    font.base.size.hash(&mut hasher);
+    font.base.options.hash(&mut hasher);
+    font.base.platform_options.hash(&mut hasher);
+    font.base.variations.hash(&mut hasher);
+    font.transform.hash(&mut hasher);
+    font.render_mode.hash(&mut hasher);
+    font.flags.hash(&mut hasher);
+    font.color.hash(&mut hasher);
+    font.size.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// A set of rasterized glyph bitmaps exported from a renderer.
+///
+/// Produced by `Renderer::export_glyph_cache` and handed to a new renderer
+/// through `WebRenderOptions::glyph_cache_import`, so that text can be drawn
+/// after context loss recovery without rasterizing the glyphs again. Glyphs
+/// are matched by font data rather than by key, so an imported glyph is only
+/// used if the new renderer is given identical font data for it.
+#[derive(Clone, Debug, Default)]
+pub struct GlyphCacheExport {
+    glyphs: FastHashMap<PortableGlyphKey, Arc<RetainedGlyph>>,
+}
+
+impl GlyphCacheExport {
+    /// The number of glyph bitmaps in the export.
+    pub fn len(&self) -> usize {
+        self.glyphs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.glyphs.is_empty()
+    }
+
+    pub fn get(&self, key: &PortableGlyphKey) -> Option<&Arc<RetainedGlyph>> {
+        self.glyphs.get(key)
+    }
+
+    pub fn insert(&mut self, key: PortableGlyphKey, glyph: Arc<RetainedGlyph>) {
+        self.glyphs.insert(key, glyph);
+    }
 }
 
 #[cfg_attr(feature = "capture", derive(Serialize))]
@@ -86,17 +188,30 @@ impl GlyphKeyCache {
     }
 }
 
+/// Describes the region of a texture cache atlas occupied by a single
+/// rasterized glyph. Used when exporting the glyph atlases for debugging.
+#[cfg(feature = "capture")]
+#[derive(Clone, Debug)]
+pub struct GlyphAtlasEntry {
+    pub texture_id: CacheTextureId,
+    pub rect: DeviceIntRect,
+    pub instance_key: FontInstanceKey,
+    pub glyph_index: u32,
+    pub subpixel_offset: (SubpixelOffset, SubpixelOffset),
+    pub format: GlyphFormat,
+}
+
 #[cfg_attr(feature = "capture", derive(Serialize))]
 #[cfg_attr(feature = "replay", derive(Deserialize))]
 pub struct GlyphCache {
-    glyph_key_caches: FastHashMap<FontInstance, GlyphKeyCache>,
+    glyph_key_caches: ResourceHashMap<FontInstance, GlyphKeyCache>,
     current_frame: FrameId,
 }
 
 impl GlyphCache {
     pub fn new() -> Self {
         GlyphCache {
-            glyph_key_caches: FastHashMap::default(),
+            glyph_key_caches: ResourceHashMap::default(),
             current_frame: Default::default(),
         }
     }
@@ -130,7 +245,7 @@ impl GlyphCache {
         }
         // We use this in on_memory_pressure where retaining memory allocations
         // isn't desirable, so we completely remove the hash map instead of clearing it.
-        self.glyph_key_caches = FastHashMap::default();
+        self.glyph_key_caches = ResourceHashMap::default();
     }
 
     pub fn delete_font_instances(
@@ -171,6 +286,54 @@ impl GlyphCache {
         });
     }
 
+    /// Collect the atlas location of every glyph currently resident in the
+    /// texture cache.
+    #[cfg(feature = "capture")]
+    pub fn atlas_entries(&self, texture_cache: &TextureCache) -> Vec<GlyphAtlasEntry> {
+        let mut entries = Vec::new();
+        for (font, cache) in &self.glyph_key_caches {
+            for (key, entry) in cache.iter() {
+                let glyph = match *entry {
+                    GlyphCacheEntry::Cached(ref glyph) => glyph,
+                    GlyphCacheEntry::Blank | GlyphCacheEntry::Pending => continue,
+                };
+                if let Some((texture_id, rect)) = texture_cache.get_allocation(&glyph.texture_cache_handle) {
+                    entries.push(GlyphAtlasEntry {
+                        texture_id,
+                        rect,
+                        instance_key: font.instance_key,
+                        glyph_index: key.index(),
+                        subpixel_offset: key.subpixel_offset(),
+                        format: glyph.format,
+                    });
+                }
+            }
+        }
+        entries
+    }
+
+    /// Collect the retained bitmap of every cached glyph whose font has a
+    /// known fingerprint.
+    pub fn export(&self, font_fingerprints: &FastHashMap<FontKey, u64>) -> GlyphCacheExport {
+        let mut export = GlyphCacheExport::default();
+        for (font, cache) in &self.glyph_key_caches {
+            let font_fingerprint = match font_fingerprints.get(&font.font_key) {
+                Some(fingerprint) => *fingerprint,
+                None => continue,
+            };
+            let instance_fingerprint = font_instance_fingerprint(font);
+            for (key, entry) in cache.iter() {
+                if let GlyphCacheEntry::Cached(CachedGlyphInfo { retained: Some(ref glyph), .. }) = *entry {
+                    export.insert(
+                        PortableGlyphKey::new(font_fingerprint, instance_fingerprint, *key),
+                        Arc::clone(glyph),
+                    );
+                }
+            }
+        }
+        export
+    }
+
     /// Clear out evicted entries from glyph key caches.
     fn clear_evicted(&mut self, texture_cache: &TextureCache) {
         for cache in self.glyph_key_caches.values_mut() {
@@ -51,9 +51,11 @@ impl ZBufferIdGenerator {
         }
     }
 
+    /// Once the budget is used up, every further primitive gets the last
+    /// id. The batcher draws those in the alpha pass, in order, so they
+    /// still end up in front of everything that came before them.
     pub fn next(&mut self) -> ZBufferId {
-        debug_assert!(self.next < self.max_depth_ids);
-        let id = ZBufferId(self.next);
+        let id = ZBufferId(self.next.min(self.max_depth_ids - 1));
         self.next += 1;
         id
     }
@@ -296,10 +298,11 @@ pub struct CompositeInstance {
     color: PremultipliedColorF,
 
     // Packed into a single vec4 (aParams)
-    _padding: f32,
+    saturation: f32,            // Saturation of the composited color, 1.0 is unchanged
     color_space_or_uv_type: f32, // YuvColorSpace for YUV;
                                  // UV coordinate space for RGB
-    yuv_format: f32,            // YuvFormat
+    yuv_format_or_straight_alpha: f32, // YuvFormat for YUV;
+                                       // 1.0 to un-premultiply the output for RGB
     yuv_channel_bit_depth: f32,
 
     // UV rectangles (pixel space) for color / yuv texture planes
@@ -307,6 +310,33 @@ pub struct CompositeInstance {
 
     // Whether to flip the x and y axis respectively, where 0.0 is no-flip and 1.0 is flip.
     flip: (f32, f32),
+
+    // Compositing order of the surface, used as the depth of the instance.
+    z: f32,
+
+    // Vignette darkening applied after any other color adjustments
+    vignette: CompositeVignette,
+}
+
+/// Vignette parameters of a composite instance, in device space.
+/// Packed into aVignette and aVignetteColor.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[repr(C)]
+pub struct CompositeVignette {
+    pub center: DevicePoint,
+    pub inner_radius: f32,
+    pub outer_radius: f32,
+    // Color blended in towards the outer radius. A transparent color disables the vignette.
+    pub color: PremultipliedColorF,
+}
+
+impl CompositeVignette {
+    pub const NONE: Self = CompositeVignette {
+        center: DevicePoint::new(0.0, 0.0),
+        inner_radius: 0.0,
+        outer_radius: 0.0,
+        color: PremultipliedColorF::TRANSPARENT,
+    };
 }
 
 impl CompositeInstance {
@@ -321,12 +351,14 @@ impl CompositeInstance {
             rect,
             clip_rect,
             color,
-            _padding: 0.0,
+            saturation: 1.0,
             color_space_or_uv_type: pack_as_float(UV_TYPE_NORMALIZED),
-            yuv_format: 0.0,
+            yuv_format_or_straight_alpha: 0.0,
             yuv_channel_bit_depth: 0.0,
             uv_rects: [uv, uv, uv],
             flip: (flip.0.into(), flip.1.into()),
+            z: 0.0,
+            vignette: CompositeVignette::NONE,
         }
     }
 
@@ -346,12 +378,14 @@ impl CompositeInstance {
             rect,
             clip_rect,
             color,
-            _padding: 0.0,
+            saturation: 1.0,
             color_space_or_uv_type: pack_as_float(uv_type),
-            yuv_format: 0.0,
+            yuv_format_or_straight_alpha: 0.0,
             yuv_channel_bit_depth: 0.0,
             uv_rects: [uv_rect, uv_rect, uv_rect],
             flip: (flip.0.into(), flip.1.into()),
+            z: 0.0,
+            vignette: CompositeVignette::NONE,
         }
     }
 
@@ -368,15 +402,44 @@ impl CompositeInstance {
             rect,
             clip_rect,
             color: PremultipliedColorF::WHITE,
-            _padding: 0.0,
+            saturation: 1.0,
             color_space_or_uv_type: pack_as_float(yuv_color_space as u32),
-            yuv_format: pack_as_float(yuv_format as u32),
+            yuv_format_or_straight_alpha: pack_as_float(yuv_format as u32),
             yuv_channel_bit_depth: pack_as_float(yuv_channel_bit_depth),
             uv_rects,
             flip: (flip.0.into(), flip.1.into()),
+            z: 0.0,
+            vignette: CompositeVignette::NONE,
         }
     }
 
+    // Set the compositing order of this instance, which is written as its depth
+    // when linear depth output is enabled. Composite z_ids count from the
+    // front-most tile, while the orthographic projection puts larger z values
+    // nearer, so the id is flipped within the `max_depth_ids` range.
+    pub fn with_z_id(mut self, z_id: ZBufferId, max_depth_ids: i32) -> Self {
+        self.z = (max_depth_ids - 1 - z_id.0) as f32;
+        self
+    }
+
+    // Set the saturation applied to the color of this instance when composited.
+    pub fn with_saturation(mut self, saturation: f32) -> Self {
+        self.saturation = saturation;
+        self
+    }
+
+    // Set the vignette applied to this instance when composited.
+    pub fn with_vignette(mut self, vignette: CompositeVignette) -> Self {
+        self.vignette = vignette;
+        self
+    }
+
+    // Divide the color of this RGB instance by its alpha when composited.
+    pub fn with_straight_alpha(mut self) -> Self {
+        self.yuv_format_or_straight_alpha = 1.0;
+        self
+    }
+
     // Returns the CompositeFeatures that can be used to composite
     // this RGB instance.
     pub fn get_rgb_features(&self) -> CompositeFeatures {
@@ -390,7 +453,10 @@ impl CompositeInstance {
             features |= CompositeFeatures::NO_UV_CLAMP;
         }
 
-        if self.color == PremultipliedColorF::WHITE {
+        if self.color == PremultipliedColorF::WHITE &&
+            self.saturation == 1.0 &&
+            self.vignette.color.a == 0.0
+        {
             features |= CompositeFeatures::NO_COLOR_MODULATION
         }
 
@@ -620,7 +686,8 @@ pub struct MaskInstance {
     pub clip_transform_id: TransformPaletteId,
     pub clip_address: i32,
     pub clip_space: u32,
-    pub unused: i32,
+    /// Non-zero for high quality AA of the clip edges, see `ClipAaQuality`.
+    pub aa_quality: i32,
 }
 
 
@@ -664,6 +731,9 @@ bitflags! {
         const FORCE_AA = 1024;
         /// Specifies UV coordinates are normalized
         const NORMALIZED_UVS = 2048;
+        /// Let the texture sampler wrap the UVs instead of clamping them
+        /// to the image bounds.
+        const SAMPLER_WRAP = 4096;
     }
 }
 
@@ -3,13 +3,13 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use api::{BorderRadius, ClipMode, HitTestResultItem, HitTestResult, ItemTag, PrimitiveFlags};
-use api::{PipelineId, ApiHitTester};
+use api::{ExternalScrollId, PipelineId, ApiHitTester};
 use api::units::*;
 use crate::clip::{rounded_rectangle_contains_point, ClipNodeId, ClipTreeBuilder};
 use crate::clip::{polygon_contains_point, ClipItemKey, ClipItemKeyKind};
 use crate::prim_store::PolygonKey;
 use crate::scene_builder_thread::Interners;
-use crate::spatial_tree::{SpatialNodeIndex, SpatialTree, get_external_scroll_offset};
+use crate::spatial_tree::{SpatialNodeIndex, SpatialTree, get_external_scroll_offset, get_enclosing_scroll_id};
 use crate::internal_types::{FastHashMap, LayoutPrimitiveInfo};
 use std::sync::{Arc, Mutex};
 use crate::util::{LayoutToWorldFastTransform};
@@ -64,6 +64,10 @@ struct HitTestSpatialNode {
 
     /// The accumulated external scroll offset for this spatial node.
     external_scroll_offset: LayoutVector2D,
+
+    /// The external id of the closest enclosing scroll frame, if any.
+    #[ignore_malloc_size_of = "Copy"]
+    scroll_id: Option<ExternalScrollId>,
 }
 
 #[derive(MallocSizeOf)]
@@ -282,6 +286,9 @@ pub struct HitTester {
     #[ignore_malloc_size_of = "Arc"]
     scene: Arc<HitTestingScene>,
     spatial_nodes: FastHashMap<SpatialNodeIndex, HitTestSpatialNode>,
+    /// The scale the document is rendered at. Hit test points are in
+    /// output device pixels, so they are scaled by this to reach world space.
+    render_scale: f32,
 }
 
 impl HitTester {
@@ -289,16 +296,19 @@ impl HitTester {
         HitTester {
             scene: Arc::new(HitTestingScene::new(&HitTestingSceneStats::empty())),
             spatial_nodes: FastHashMap::default(),
+            render_scale: 1.0,
         }
     }
 
     pub fn new(
         scene: Arc<HitTestingScene>,
         spatial_tree: &SpatialTree,
+        render_scale: f32,
     ) -> HitTester {
         let mut hit_tester = HitTester {
             scene,
             spatial_nodes: FastHashMap::default(),
+            render_scale,
         };
         hit_tester.read_spatial_tree(spatial_tree);
         hit_tester
@@ -325,12 +335,14 @@ impl HitTester {
                     .get_world_viewport_transform(index)
                     .into_fast_transform(),
                 external_scroll_offset: get_external_scroll_offset(spatial_tree, index),
+                scroll_id: get_enclosing_scroll_id(spatial_tree, index),
             });
         });
     }
 
     pub fn hit_test(&self, test: HitTest) -> HitTestResult {
         let mut result = HitTestResult::default();
+        let point = test.point * self.render_scale;
 
         let mut current_spatial_node_index = SpatialNodeIndex::INVALID;
         let mut point_in_layer = None;
@@ -346,7 +358,7 @@ impl HitTester {
                 point_in_layer = scroll_node
                     .world_content_transform
                     .inverse()
-                    .and_then(|inverted| inverted.project_point2d(test.point));
+                    .and_then(|inverted| inverted.project_point2d(point));
                 current_spatial_node_index = item.spatial_node_index;
             }
 
@@ -374,7 +386,7 @@ impl HitTester {
                     .world_content_transform;
                 if let Some(transformed_point) = transform
                     .inverse()
-                    .and_then(|inverted| inverted.project_point2d(test.point))
+                    .and_then(|inverted| inverted.project_point2d(point))
                 {
                     if !clip_node.region.contains(&transformed_point) {
                         is_valid = false;
@@ -398,6 +410,7 @@ impl HitTester {
                 pipeline: pipeline_id,
                 tag: item.tag,
                 animation_id: item.animation_id,
+                scroll_id: scroll_node.scroll_id,
             });
         }
 
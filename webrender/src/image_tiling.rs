@@ -2,7 +2,7 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use crate::api::TileSize;
+use crate::api::{ImageRepeatMode, TileSize};
 use crate::api::units::*;
 use crate::segment::EdgeAaSegmentMask;
 use euclid::{point2, size2};
@@ -31,6 +31,22 @@ pub fn simplify_repeated_primitive(
     }
 }
 
+/// Shrink the primitive rect to a single tile along the axes that
+/// `repeat_mode` doesn't repeat, so that the image is drawn once at the
+/// origin of the rect along those axes.
+pub fn clamp_to_repeat_mode(
+    repeat_mode: ImageRepeatMode,
+    stretch_size: &LayoutSize,
+    prim_rect: &mut LayoutRect,
+) {
+    if !repeat_mode.repeats_x() {
+        prim_rect.max.x = f32::min(prim_rect.min.x + stretch_size.width, prim_rect.max.x);
+    }
+    if !repeat_mode.repeats_y() {
+        prim_rect.max.y = f32::min(prim_rect.min.y + stretch_size.height, prim_rect.max.y);
+    }
+}
+
 pub struct Repetition {
     pub origin: LayoutPoint,
     pub edge_flags: EdgeAaSegmentMask,
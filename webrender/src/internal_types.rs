@@ -6,7 +6,8 @@ use api::{ColorF, DocumentId, ExternalImageId, PrimitiveFlags, Parameter, Render
 use api::{ImageFormat, NotificationRequest, Shadow, FilterOpGraphPictureBufferId, FilterOpGraphPictureReference, FilterOpGraphNode, FilterOp, ImageBufferKind};
 use api::FramePublishId;
 use api::units::*;
-use crate::render_api::DebugCommand;
+use api::channel::Sender;
+use crate::render_api::{ApiMsg, DebugCommand};
 use crate::composite::NativeSurfaceOperation;
 use crate::device::TextureFilter;
 use crate::renderer::{FullFrameStats, PipelineInfo};
@@ -24,7 +25,8 @@ use std::collections::{HashMap, HashSet};
 use std::f32;
 use std::hash::BuildHasherDefault;
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::AtomicU64;
 use std::time::{UNIX_EPOCH, SystemTime};
 use peek_poke::PeekPoke;
 
@@ -32,6 +34,8 @@ use peek_poke::PeekPoke;
 use crate::capture::CaptureConfig;
 #[cfg(feature = "capture")]
 use crate::capture::ExternalCaptureImage;
+#[cfg(feature = "capture")]
+use crate::glyph_cache::GlyphAtlasEntry;
 #[cfg(feature = "replay")]
 use crate::capture::PlainExternalImage;
 
@@ -44,6 +48,17 @@ pub fn size_of_frame_vec<T>(vec: &FrameVec<T>) -> usize {
 pub type FastHashMap<K, V> = HashMap<K, V, BuildHasherDefault<FxHasher>>;
 pub type FastHashSet<K> = HashSet<K, BuildHasherDefault<FxHasher>>;
 
+/// The hasher used by the resource and glyph caches, which can grow to hold
+/// very large numbers of keys. FxHash is cheap but can collide heavily for
+/// some key distributions, so embedders can opt in to the standard library's
+/// SipHash-based hasher with the `std_resource_hasher` feature.
+#[cfg(not(feature = "std_resource_hasher"))]
+pub type ResourceBuildHasher = BuildHasherDefault<FxHasher>;
+#[cfg(feature = "std_resource_hasher")]
+pub type ResourceBuildHasher = std::collections::hash_map::RandomState;
+
+pub type ResourceHashMap<K, V> = HashMap<K, V, ResourceBuildHasher>;
+
 #[derive(Copy, Clone, Hash, MallocSizeOf, PartialEq, PartialOrd, Debug, Eq, Ord, PeekPoke)]
 #[cfg_attr(feature = "capture", derive(Serialize))]
 #[cfg_attr(feature = "replay", derive(Deserialize))]
@@ -774,6 +789,8 @@ pub enum Filter {
         width: f32,
         height: f32,
         should_inflate: bool,
+        /// Use the approximate downsample/upsample blur (see `FilterOp::CheapBlur`).
+        cheap: bool,
     },
     Brightness(f32),
     Contrast(f32),
@@ -887,7 +904,8 @@ impl From<FilterOp> for Filter {
     fn from(op: FilterOp) -> Self {
         match op {
             FilterOp::Identity => Filter::Identity,
-            FilterOp::Blur(width, height) => Filter::Blur { width, height, should_inflate: true },
+            FilterOp::Blur(width, height) => Filter::Blur { width, height, should_inflate: true, cheap: false },
+            FilterOp::CheapBlur(radius) => Filter::Blur { width: radius, height: radius, should_inflate: true, cheap: true },
             FilterOp::Brightness(b) => Filter::Brightness(b),
             FilterOp::Contrast(c) => Filter::Contrast(c),
             FilterOp::Grayscale(g) => Filter::Grayscale(g),
@@ -901,6 +919,12 @@ impl From<FilterOp> for Filter {
             FilterOp::LinearToSrgb => Filter::LinearToSrgb,
             FilterOp::ComponentTransfer => Filter::ComponentTransfer,
             FilterOp::DropShadow(shadow) => Filter::DropShadows(smallvec![shadow]),
+            FilterOp::DropShadows(shadows, count) => {
+                // Shadows are drawn in order, so the first one goes last to
+                // end up on top.
+                let count = (count as usize).min(api::MAX_DROP_SHADOWS);
+                Filter::DropShadows(shadows[.. count].iter().rev().cloned().collect())
+            }
             FilterOp::Flood(color) => Filter::Flood(color),
             FilterOp::SVGFEBlendColor{node} => Filter::SVGGraphNode(node.into(), FilterGraphOp::SVGFEBlendColor),
             FilterOp::SVGFEBlendColorBurn{node} => Filter::SVGGraphNode(node.into(), FilterGraphOp::SVGFEBlendColorBurn),
@@ -1129,6 +1153,8 @@ pub struct TextureCacheAllocInfo {
     pub is_shared_cache: bool,
     /// If true, this texture requires a depth target.
     pub has_depth: bool,
+    /// If true, the texture is an RGBA8 render target with sRGB storage.
+    pub is_srgb: bool,
     pub category: TextureCacheCategory
 }
 
@@ -1339,7 +1365,79 @@ pub struct RenderedDocument {
     pub frame: Frame,
     pub profile: TransactionProfile,
     pub render_reasons: RenderReasons,
-    pub frame_stats: Option<FullFrameStats>
+    /// Trace ids of the transactions that contributed to this frame.
+    pub trace_ids: Vec<u64>,
+    pub frame_stats: Option<FullFrameStats>,
+    /// Keeps the frame counted as in flight until it is rendered or dropped.
+    pub frame_in_flight: Option<FrameInFlight>,
+    /// The document's pending frame id, shared with the render backend, and
+    /// the publish id of this frame. The renderer clears the shared id only
+    /// if it still refers to this frame.
+    pub frame_pending: Option<(Arc<AtomicU64>, FramePublishId)>,
+    /// When the oldest frame request this frame answers was made, in
+    /// `precise_time_ns`. None if the frame wasn't requested.
+    pub frame_requested_time: Option<u64>,
+}
+
+/// Limits the number of frames the render backend has published but the
+/// renderer hasn't rendered yet. See `WebRenderOptions::max_frames_in_flight`.
+///
+/// The render backend never waits for the renderer: when the limit is reached
+/// it defers building the frame, and builds it once the renderer retires a
+/// frame, which sends `ApiMsg::FrameRetired` to the render backend.
+pub struct FramesInFlight {
+    /// The number of frames in flight, and the maximum (zero if unlimited).
+    state: Mutex<(usize, usize)>,
+    api_tx: Mutex<Sender<ApiMsg>>,
+}
+
+impl FramesInFlight {
+    pub fn new(max_frames_in_flight: usize, api_tx: Sender<ApiMsg>) -> Arc<Self> {
+        Arc::new(FramesInFlight {
+            state: Mutex::new((0, max_frames_in_flight)),
+            api_tx: Mutex::new(api_tx),
+        })
+    }
+
+    pub fn set_max(&self, max_frames_in_flight: usize) {
+        self.state.lock().unwrap().1 = max_frames_in_flight;
+        // Let the render backend build any frame it deferred.
+        self.notify_render_backend();
+    }
+
+    /// Returns a token that keeps a frame in flight until it is dropped, or
+    /// None if the maximum number of frames is already in flight.
+    pub fn try_acquire(self: &Arc<Self>) -> Option<FrameInFlight> {
+        let mut state = self.state.lock().unwrap();
+        if state.1 > 0 && state.0 >= state.1 {
+            return None;
+        }
+        state.0 += 1;
+
+        Some(FrameInFlight(Arc::clone(self)))
+    }
+
+    fn notify_render_backend(&self) {
+        // The render backend may already be shut down.
+        let _ = self.api_tx.lock().unwrap().send(ApiMsg::FrameRetired);
+    }
+}
+
+pub struct FrameInFlight(Arc<FramesInFlight>);
+
+impl Drop for FrameInFlight {
+    fn drop(&mut self) {
+        let was_limited = {
+            let mut state = self.0.state.lock().unwrap();
+            let was_limited = state.1 > 0 && state.0 >= state.1;
+            state.0 -= 1;
+            was_limited
+        };
+        // Only the render backend deferring a frame needs to know.
+        if was_limited {
+            self.0.notify_render_backend();
+        }
+    }
 }
 
 pub enum DebugOutput {
@@ -1347,6 +1445,8 @@ pub enum DebugOutput {
     SaveCapture(CaptureConfig, Vec<ExternalCaptureImage>),
     #[cfg(feature = "replay")]
     LoadCapture(CaptureConfig, Vec<PlainExternalImage>),
+    #[cfg(feature = "capture")]
+    ExportGlyphAtlas(PathBuf, Vec<GlyphAtlasEntry>),
 }
 
 #[allow(dead_code)]
@@ -166,21 +166,25 @@ pub extern crate api;
 extern crate webrender_build;
 
 #[doc(hidden)]
-pub use crate::composite::{CompositorConfig, Compositor, CompositorCapabilities, CompositorSurfaceTransform};
+pub use crate::composite::{CompositorConfig, Compositor, CompositorCapabilities, CompositorKind, CompositorSurfaceTransform};
+pub use crate::composite::{ClipRadius, CompositeSurfaceFormat};
+pub use crate::composite::{CompositeSurfaceContent, CompositeSurfaceInfo};
 pub use crate::composite::{NativeSurfaceId, NativeTileId, NativeSurfaceInfo, PartialPresentCompositor};
 pub use crate::composite::{MappableCompositor, MappedTileInfo, SWGLCompositeSurfaceInfo, WindowVisibility};
 pub use crate::device::{UploadMethod, VertexUsageHint, get_gl_target, get_unoptimized_shader_source};
 pub use crate::device::{ProgramBinary, ProgramCache, ProgramCacheObserver, FormatDesc};
-pub use crate::device::Device;
+pub use crate::device::{ContextLostHandler, Device};
 pub use crate::profiler::{ProfilerHooks, set_profiler_hooks};
 pub use crate::renderer::{
-    CpuProfile, DebugFlags, GpuProfile, GraphicsApi,
-    GraphicsApiInfo, PipelineInfo, Renderer, RendererError, RenderResults,
-    RendererStats, Shaders, SharedShaders, ShaderPrecacheFlags,
+    AlphaMode, CpuProfile, DebugFlags, FrameSummary, GpuProfile, GraphicsApi,
+    GraphicsApiInfo, IsolatedBatchKind, PassInfo, PipelineInfo, PresentMode, Renderer, RendererError, RenderResults,
+    RendererStats, Shaders, SharedShaders, ShaderPrecacheFlags, VignetteParams,
     MAX_VERTEX_TEXTURE_WIDTH,
 };
 pub use crate::renderer::init::{WebRenderOptions, create_webrender_instance, AsyncPropertySampler, SceneBuilderHooks, RenderBackendHooks, ONE_TIME_USAGE_HINT};
+pub use crate::renderer::init::{TextureAllocatorHook, TextureAllocationRequest};
 pub use crate::hit_test::SharedHitTester;
+pub use crate::glyph_cache::GlyphCacheExport;
 pub use crate::internal_types::FastHashMap;
 pub use crate::screen_capture::{AsyncScreenshotHandle, RecordedFrameHandle};
 pub use crate::texture_cache::TextureCacheConfig;
@@ -94,7 +94,7 @@
 //! blend the overlay tile (this is not always optimal right now, but will be
 //! improved as a follow up).
 
-use api::{MixBlendMode, PremultipliedColorF, FilterPrimitiveKind, SVGFE_GRAPH_MAX};
+use api::{ClipMode, MixBlendMode, PremultipliedColorF, FilterPrimitiveKind, SVGFE_GRAPH_MAX};
 use api::{PropertyBinding, PropertyBindingId, FilterPrimitive, FilterOpGraphPictureBufferId, RasterSpace};
 use api::{DebugFlags, ImageKey, ColorF, ColorU, PrimitiveFlags};
 use api::{ImageRendering, ColorDepth, YuvRangedColorSpace, YuvFormat, AlphaType};
@@ -102,11 +102,12 @@ use api::units::*;
 use crate::command_buffer::PrimitiveCommand;
 use crate::box_shadow::BLUR_SAMPLE_SCALE;
 use crate::clip::{ClipStore, ClipChainInstance, ClipLeafId, ClipNodeId, ClipTreeBuilder};
+use crate::clip::{ClipDataStore, ClipItemKind, ClipNodeFlags};
 use crate::profiler::{self, TransactionProfile};
 use crate::spatial_tree::{SpatialTree, CoordinateSpaceMapping, SpatialNodeIndex, VisibleFace};
 use crate::composite::{CompositorKind, CompositeState, NativeSurfaceId, NativeTileId, CompositeTileSurface, tile_kind};
 use crate::composite::{ExternalSurfaceDescriptor, ExternalSurfaceDependency, CompositeTileDescriptor, CompositeTile};
-use crate::composite::{CompositorTransformIndex, CompositorSurfaceKind};
+use crate::composite::{ClipRadius, CompositorTransformIndex, CompositorSurfaceKind, CompositeSurfaceFormat};
 use crate::debug_colors;
 use euclid::{vec3, Point2D, Scale, Vector2D, Box2D};
 use euclid::approxeq::ApproxEq;
@@ -289,6 +290,10 @@ pub const TILE_SIZE_SCROLLBAR_VERTICAL: DeviceIntSize = DeviceIntSize {
 pub const MAX_SURFACE_SIZE: usize = 4096;
 /// Maximum size of a compositor surface.
 const MAX_COMPOSITOR_SURFACES_SIZE: f32 = 8192.0;
+/// Maximum size per axis of a tile that has been coalesced from adjacent tiles
+/// to stay within the native compositor's tile update budget. This is further
+/// limited by the maximum render target size of the device.
+const MAX_COALESCED_TILE_SIZE: i32 = 8192;
 
 /// Used to get unique tile IDs, even when the tile cache is
 /// destroyed between display lists / scenes.
@@ -302,6 +307,55 @@ fn clampf(value: f32, low: f32, high: f32) -> f32 {
     value.max(low).min(high)
 }
 
+/// Work out the grid of tiles needed to cover the on-screen part of a picture
+/// cache. Starting from `min_factor`, the tile size is doubled in each dimension,
+/// merging each 2x2 block of adjacent tiles, until the grid has at most `max_tiles`
+/// tiles or `max_factor` is reached. Returns the coalesce factor and the grid.
+fn coalesce_tile_grid(
+    screen_rect: PictureRect,
+    pic_rect: PictureRect,
+    base_tile_size: PictureSize,
+    max_tiles: usize,
+    min_factor: i32,
+    max_factor: i32,
+) -> (i32, TileRect) {
+    let mut factor = min_factor;
+
+    loop {
+        let tile_size = base_tile_size * factor as f32;
+
+        // Inflate the needed rect a bit, so that we retain tiles that we have drawn
+        // but have just recently gone off-screen. This means that we avoid re-drawing
+        // tiles if the user is scrolling up and down small amounts, at the cost of
+        // a bit of extra texture memory.
+        let desired_rect = screen_rect.inflate(0.0, 1.0 * tile_size.height);
+
+        let needed_rect = desired_rect
+            .intersection(&pic_rect)
+            .unwrap_or_else(Box2D::zero);
+
+        let p0 = needed_rect.min;
+        let p1 = needed_rect.max;
+
+        let tile_rect = TileRect {
+            min: TileOffset::new(
+                (p0.x / tile_size.width).floor() as i32,
+                (p0.y / tile_size.height).floor() as i32,
+            ),
+            max: TileOffset::new(
+                (p1.x / tile_size.width).ceil() as i32,
+                (p1.y / tile_size.height).ceil() as i32,
+            ),
+        };
+
+        if tile_rect.area() as usize <= max_tiles || factor * 2 > max_factor {
+            return (factor, tile_rect);
+        }
+
+        factor *= 2;
+    }
+}
+
 /// An index into the prims array in a TileDescriptor.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[cfg_attr(feature = "capture", derive(Serialize))]
@@ -1632,6 +1686,9 @@ pub struct TileCacheParams {
     // Optional background color of this tilecache. If present, can be used as an optimization
     // to enable opaque blending and/or subpixel AA in more places.
     pub background_color: Option<ColorF>,
+    // Optional (animated) opacity that is applied to the tiles of this cache
+    // when they are composited, rather than when they are rasterized.
+    pub composite_opacity: Option<PropertyBinding<f32>>,
     // Node in the clip-tree that defines where we exclude clips from child prims
     pub shared_clip_node_id: ClipNodeId,
     // Clip leaf that is used to build the clip-chain for this tile cache.
@@ -1809,11 +1866,17 @@ pub struct TileCacheInstance {
     /// The background color from the renderer. If this is set opaque, we know it's
     /// fine to clear the tiles to this and allow subpixel text on the first slice.
     pub background_color: Option<ColorF>,
+    /// Optional opacity binding applied to the tiles during compositing.
+    composite_opacity_binding: Option<PropertyBinding<f32>>,
+    /// The resolved value of the composite opacity for the current frame.
+    pub composite_opacity: f32,
     /// Information about the calculated backdrop content of this cache.
     pub backdrop: BackdropInfo,
     /// The allowed subpixel mode for this surface, which depends on the detected
     /// opacity of the background.
     pub subpixel_mode: SubpixelMode,
+    /// The area of this slice known to have an opaque background once composited.
+    pub known_opaque_rect: PictureRect,
     // Node in the clip-tree that defines where we exclude clips from child prims
     pub shared_clip_node_id: ClipNodeId,
     // Clip leaf that is used to build the clip-chain for this tile cache.
@@ -1823,6 +1886,12 @@ pub struct TileCacheInstance {
     /// we don't want to constantly invalidate and reallocate different tile size
     /// configuration each frame.
     frames_until_size_eval: usize,
+    /// The tile size selected at the last size evaluation, before any coalescing.
+    base_tile_size: DeviceIntSize,
+    /// How many times the base tile size has been doubled in each dimension to
+    /// keep the tile grid within the native compositor's update budget. This can
+    /// grow on any frame, but only shrinks when the tile size is re-evaluated.
+    tile_coalesce_factor: i32,
     /// For DirectComposition, virtual surfaces don't support negative coordinates. However,
     /// picture cache tile coordinates can be negative. To handle this, we apply an offset
     /// to each tile in DirectComposition. We want to change this as little as possible,
@@ -1850,6 +1919,8 @@ pub struct TileCacheInstance {
     invalidate_all_tiles: bool,
     /// The current raster scale for tiles in this cache
     current_raster_scale: f32,
+    /// The width of anti-aliased edges the tiles were rasterized with
+    edge_aa_feather: f32,
     /// Depth of off-screen surfaces that are currently pushed during dependency updates
     current_surface_traversal_depth: usize,
     /// A list of extra dirty invalidation tests that can only be checked once we
@@ -1940,12 +2011,17 @@ impl TileCacheInstance {
             screen_rect_in_pic_space: PictureRect::zero(),
             surface_index: SurfaceIndex(0),
             background_color: params.background_color,
+            composite_opacity_binding: params.composite_opacity,
+            composite_opacity: 1.0,
             backdrop: BackdropInfo::empty(),
             subpixel_mode: SubpixelMode::Allow,
+            known_opaque_rect: PictureRect::zero(),
             shared_clip_node_id: params.shared_clip_node_id,
             shared_clip_leaf_id: params.shared_clip_leaf_id,
             current_tile_size: DeviceIntSize::zero(),
             frames_until_size_eval: 0,
+            base_tile_size: DeviceIntSize::zero(),
+            tile_coalesce_factor: 1,
             // Default to centering the virtual offset in the middle of the DC virtual surface
             virtual_offset: DeviceIntPoint::new(
                 params.virtual_surface_size / 2,
@@ -1960,6 +2036,7 @@ impl TileCacheInstance {
             local_to_raster: ScaleOffset::identity(),
             invalidate_all_tiles: true,
             current_raster_scale: 1.0,
+            edge_aa_feather: 1.0,
             current_surface_traversal_depth: 0,
             deferred_dirty_tests: Vec::new(),
             found_prims_after_backdrop: false,
@@ -2043,6 +2120,7 @@ impl TileCacheInstance {
         self.slice_flags = params.slice_flags;
         self.spatial_node_index = params.spatial_node_index;
         self.background_color = params.background_color;
+        self.composite_opacity_binding = params.composite_opacity;
         self.shared_clip_leaf_id = params.shared_clip_leaf_id;
         self.shared_clip_node_id = params.shared_clip_node_id;
 
@@ -2106,6 +2184,7 @@ impl TileCacheInstance {
         &mut self,
         pic_rect: PictureRect,
         surface_index: SurfaceIndex,
+        max_tiles: Option<usize>,
         frame_context: &FrameVisibilityContext,
         frame_state: &mut FrameVisibilityState,
     ) -> WorldRect {
@@ -2218,21 +2297,8 @@ impl TileCacheInstance {
                 }
             };
 
-            // If the desired tile size has changed, then invalidate and drop any
-            // existing tiles.
-            if desired_tile_size != self.current_tile_size {
-                for sub_slice in &mut self.sub_slices {
-                    // Destroy any native surfaces on the tiles that will be dropped due
-                    // to resizing.
-                    if let Some(native_surface) = sub_slice.native_surface.take() {
-                        frame_state.resource_cache.destroy_compositor_surface(native_surface.opaque);
-                        frame_state.resource_cache.destroy_compositor_surface(native_surface.alpha);
-                    }
-                    sub_slice.tiles.clear();
-                }
-                self.tile_rect = TileRect::zero();
-                self.current_tile_size = desired_tile_size;
-            }
+            self.base_tile_size = desired_tile_size;
+            self.tile_coalesce_factor = 1;
 
             // Reset counter until next evaluating the desired tile size. This is an
             // arbitrary value.
@@ -2271,17 +2337,34 @@ impl TileCacheInstance {
         let surface_scale_changed =
             !local_to_raster.scale.x.approx_eq_eps(&self.local_to_raster.scale.x, &EPSILON) ||
             !local_to_raster.scale.y.approx_eq_eps(&self.local_to_raster.scale.y, &EPSILON);
+        let edge_aa_feather_changed =
+            frame_context.config.edge_aa_feather != self.edge_aa_feather;
 
         if compositor_translation_changed ||
            compositor_scale_changed ||
            surface_scale_changed ||
+           edge_aa_feather_changed ||
            frame_context.config.force_invalidation {
             frame_state.composite_state.dirty_rects_are_valid = false;
         }
 
         self.raster_to_device = raster_to_device;
         self.local_to_raster = local_to_raster;
-        self.invalidate_all_tiles = surface_scale_changed || frame_context.config.force_invalidation;
+        self.edge_aa_feather = frame_context.config.edge_aa_feather;
+        self.invalidate_all_tiles = surface_scale_changed ||
+            edge_aa_feather_changed ||
+            frame_context.config.force_invalidation;
+
+        // Resolve the opacity applied when compositing the tiles. Changing it
+        // doesn't invalidate any tile content, but the composite can't rely
+        // on the dirty rects of the tiles alone.
+        let composite_opacity = self.composite_opacity_binding
+            .as_ref()
+            .map_or(1.0, |binding| frame_context.scene_properties.resolve_float(binding));
+        if composite_opacity != self.composite_opacity {
+            frame_state.composite_state.dirty_rects_are_valid = false;
+        }
+        self.composite_opacity = composite_opacity;
 
         // Do a hacky diff of opacity binding values from the last frame. This is
         // used later on during tile invalidation tests.
@@ -2318,39 +2401,63 @@ impl TileCacheInstance {
         }
 
         let world_tile_size = WorldSize::new(
-            self.current_tile_size.width as f32 / frame_context.global_device_pixel_scale.0,
-            self.current_tile_size.height as f32 / frame_context.global_device_pixel_scale.0,
+            self.base_tile_size.width as f32 / frame_context.global_device_pixel_scale.0,
+            self.base_tile_size.height as f32 / frame_context.global_device_pixel_scale.0,
         );
 
-        self.tile_size = PictureSize::new(
+        let base_tile_size = PictureSize::new(
             world_tile_size.width / self.local_to_raster.scale.x,
             world_tile_size.height / self.local_to_raster.scale.y,
         );
 
-        // Inflate the needed rect a bit, so that we retain tiles that we have drawn
-        // but have just recently gone off-screen. This means that we avoid re-drawing
-        // tiles if the user is scrolling up and down small amounts, at the cost of
-        // a bit of extra texture memory.
-        let desired_rect_in_pic_space = self.screen_rect_in_pic_space
-            .inflate(0.0, 1.0 * self.tile_size.height);
-
-        let needed_rect_in_pic_space = desired_rect_in_pic_space
-            .intersection(&pic_rect)
-            .unwrap_or_else(Box2D::zero);
-
-        let p0 = needed_rect_in_pic_space.min;
-        let p1 = needed_rect_in_pic_space.max;
+        // If the native compositor limits the number of tiles updated per frame,
+        // split this tile cache's share between its sub-slices, and coalesce
+        // adjacent tiles until the grid fits.
+        let max_grid_tiles = max_tiles.map_or(usize::MAX, |max_tiles| {
+            (max_tiles / self.sub_slices.len()).max(1)
+        });
+        // Coalesced tiles are still rasterized into a single render target, so
+        // they can't be larger than the device supports.
+        let mut max_coalesced_tile_size = MAX_COALESCED_TILE_SIZE;
+        if frame_context.config.max_target_size > 0 {
+            max_coalesced_tile_size = max_coalesced_tile_size.min(frame_context.config.max_target_size);
+        }
+        let max_coalesce_factor = (max_coalesced_tile_size /
+            self.base_tile_size.width.max(self.base_tile_size.height)).max(1);
+
+        let (coalesce_factor, new_tile_rect) = coalesce_tile_grid(
+            self.screen_rect_in_pic_space,
+            pic_rect,
+            base_tile_size,
+            max_grid_tiles,
+            self.tile_coalesce_factor,
+            max_coalesce_factor,
+        );
 
-        let x0 = (p0.x / self.tile_size.width).floor() as i32;
-        let x1 = (p1.x / self.tile_size.width).ceil() as i32;
+        self.tile_coalesce_factor = coalesce_factor;
+        self.tile_size = base_tile_size * coalesce_factor as f32;
 
-        let y0 = (p0.y / self.tile_size.height).floor() as i32;
-        let y1 = (p1.y / self.tile_size.height).ceil() as i32;
+        // If the desired tile size has changed, then invalidate and drop any
+        // existing tiles.
+        let desired_tile_size = self.base_tile_size * coalesce_factor;
+        if desired_tile_size != self.current_tile_size {
+            for sub_slice in &mut self.sub_slices {
+                // Destroy any native surfaces on the tiles that will be dropped due
+                // to resizing.
+                if let Some(native_surface) = sub_slice.native_surface.take() {
+                    frame_state.resource_cache.destroy_compositor_surface(native_surface.opaque);
+                    frame_state.resource_cache.destroy_compositor_surface(native_surface.alpha);
+                }
+                sub_slice.tiles.clear();
+            }
+            self.tile_rect = TileRect::zero();
+            self.current_tile_size = desired_tile_size;
+        }
 
-        let new_tile_rect = TileRect {
-            min: TileOffset::new(x0, y0),
-            max: TileOffset::new(x1, y1),
-        };
+        let x0 = new_tile_rect.min.x;
+        let x1 = new_tile_rect.max.x;
+        let y0 = new_tile_rect.min.y;
+        let y1 = new_tile_rect.max.y;
 
         // Determine whether the current bounds of the tile grid will exceed the
         // bounds of the DC virtual surface, taking into account the current
@@ -2520,6 +2627,7 @@ impl TileCacheInstance {
         sub_slice_index: usize,
         surface_kind: CompositorSurfaceKind,
         pic_coverage_rect: PictureRect,
+        compositor_clip_radius: Option<ClipRadius>,
         frame_context: &FrameVisibilityContext,
     ) -> Result<CompositorSurfaceKind, SurfacePromotionFailure> {
         use crate::picture::SurfacePromotionFailure::*;
@@ -2535,8 +2643,9 @@ impl TileCacheInstance {
                 }
 
                 // If a complex clip is being applied to this primitive, it can't be
-                // promoted directly to a compositor surface.
-                if prim_clip_chain.needs_mask {
+                // promoted directly to a compositor surface, unless it's a rounded
+                // rect that the native compositor can apply itself.
+                if prim_clip_chain.needs_mask && compositor_clip_radius.is_none() {
                     return Err(OverlayNeedsMask);
                 }
             }
@@ -2616,6 +2725,7 @@ impl TileCacheInstance {
         color_space: YuvRangedColorSpace,
         format: YuvFormat,
         surface_kind: CompositorSurfaceKind,
+        clip_radius: ClipRadius,
     ) -> Result<CompositorSurfaceKind, SurfacePromotionFailure> {
         for &key in api_keys {
             if key != ImageKey::DUMMY {
@@ -2650,6 +2760,7 @@ impl TileCacheInstance {
             image_rendering,
             true,
             surface_kind,
+            clip_radius,
         )
     }
 
@@ -2670,6 +2781,7 @@ impl TileCacheInstance {
         image_rendering: ImageRendering,
         is_opaque: bool,
         surface_kind: CompositorSurfaceKind,
+        clip_radius: ClipRadius,
     ) -> Result<CompositorSurfaceKind, SurfacePromotionFailure> {
         let mut api_keys = [ImageKey::DUMMY; 3];
         api_keys[0] = api_key;
@@ -2698,6 +2810,11 @@ impl TileCacheInstance {
             frame_context,
             ExternalSurfaceDependency::Rgb {
                 image_dependency,
+                format: if is_opaque {
+                    CompositeSurfaceFormat::Rgba
+                } else {
+                    CompositeSurfaceFormat::RgbaPremultiplied
+                },
             },
             &api_keys,
             resource_cache,
@@ -2705,6 +2822,7 @@ impl TileCacheInstance {
             image_rendering,
             is_opaque,
             surface_kind,
+            clip_radius,
         )
     }
 
@@ -2726,6 +2844,7 @@ impl TileCacheInstance {
         image_rendering: ImageRendering,
         is_opaque: bool,
         surface_kind: CompositorSurfaceKind,
+        clip_radius: ClipRadius,
     ) -> Result<CompositorSurfaceKind, SurfacePromotionFailure> {
         use crate::picture::SurfacePromotionFailure::*;
 
@@ -2839,7 +2958,11 @@ impl TileCacheInstance {
                             Some(_external_image) => {
                                 // If we have a suitable external image, then create an external
                                 // surface to attach to.
-                                resource_cache.create_compositor_external_surface(is_opaque)
+                                let format = match dependency {
+                                    ExternalSurfaceDependency::Yuv { .. } => CompositeSurfaceFormat::Yuv,
+                                    ExternalSurfaceDependency::Rgb { format, .. } => format,
+                                };
+                                resource_cache.create_compositor_external_surface(format)
                             }
                             None => {
                                 // Otherwise create a normal compositor surface and a single
@@ -2921,6 +3044,7 @@ impl TileCacheInstance {
             z_id: ZBufferId::invalid(),
             native_surface_id,
             update_params,
+            clip_radius,
         };
 
         // If the surface is opaque, we can draw it an an underlay (which avoids
@@ -3242,6 +3366,15 @@ impl TileCacheInstance {
                 }
 
                 if image_key.common.flags.contains(PrimitiveFlags::PREFER_COMPOSITOR_SURFACE) {
+                    let compositor_clip_radius = get_compositor_clip_radius(
+                        prim_clip_chain,
+                        prim_spatial_node_index,
+                        local_prim_rect,
+                        clip_store,
+                        &data_stores.clip,
+                        frame_context,
+                    );
+
                     // Only consider promoting Images if all of our YuvImages have been
                     // processed (whether they were promoted or not).
                     if self.yuv_images_remaining > 0 {
@@ -3253,6 +3386,7 @@ impl TileCacheInstance {
                                                           sub_slice_index,
                                                           CompositorSurfaceKind::Overlay,
                                                           pic_coverage_rect,
+                                                          compositor_clip_radius,
                                                           frame_context);
                     }
 
@@ -3282,6 +3416,7 @@ impl TileCacheInstance {
                             image_data.image_rendering,
                             is_opaque,
                             kind,
+                            compositor_clip_radius.unwrap_or(ClipRadius::EMPTY),
                         );
                     }
                 }
@@ -3323,6 +3458,15 @@ impl TileCacheInstance {
                         self.yuv_images_remaining -= 1;
                     }
 
+                    let compositor_clip_radius = get_compositor_clip_radius(
+                        prim_clip_chain,
+                        prim_spatial_node_index,
+                        local_prim_rect,
+                        clip_store,
+                        &data_stores.clip,
+                        frame_context,
+                    );
+
                     let clip_on_top = prim_clip_chain.needs_mask;
                     let prefer_underlay = clip_on_top || !cfg!(target_os = "macos");
                     let promotion_attempts = if prefer_underlay {
@@ -3342,6 +3486,7 @@ impl TileCacheInstance {
                                                     sub_slice_index,
                                                     kind,
                                                     pic_coverage_rect,
+                                                    compositor_clip_radius,
                                                     frame_context);
                         if promotion_result.is_ok() {
                             break;
@@ -3387,6 +3532,13 @@ impl TileCacheInstance {
                             prim_data.kind.color_space.with_range(prim_data.kind.color_range),
                             prim_data.kind.format,
                             kind,
+                            // Underlays are masked by a cutout in the tiles instead.
+                            match kind {
+                                CompositorSurfaceKind::Overlay => {
+                                    compositor_clip_radius.unwrap_or(ClipRadius::EMPTY)
+                                }
+                                _ => ClipRadius::EMPTY,
+                            },
                         );
                     }
                 }
@@ -3515,8 +3667,14 @@ impl TileCacheInstance {
                     });
                 }
             }
+            PrimitiveInstanceKind::NormalBorder { data_handle, .. } => {
+                // Dashed borders can depend on an animated dash phase.
+                let border_data = &data_stores.normal_border[data_handle].kind;
+                if let PropertyBinding::Binding(..) = border_data.dash_phase {
+                    prim_info.opacity_bindings.push(border_data.dash_phase.into());
+                }
+            }
             PrimitiveInstanceKind::LineDecoration { .. } |
-            PrimitiveInstanceKind::NormalBorder { .. } |
             PrimitiveInstanceKind::BoxShadow { .. } |
             PrimitiveInstanceKind::TextRun { .. } => {
                 // These don't contribute dependencies
@@ -3697,6 +3855,20 @@ impl TileCacheInstance {
         pt.end_level();
     }
 
+    /// The area of the slice that is covered by an opaque background color or
+    /// opaque backdrop primitive, if the slice is composited fully opaque.
+    fn calculate_known_opaque_rect(&self) -> PictureRect {
+        if self.composite_opacity < 1.0 {
+            return PictureRect::zero();
+        }
+
+        if self.background_color.map_or(false, |c| c.a >= 1.0) {
+            return self.local_rect;
+        }
+
+        self.backdrop.opaque_rect
+    }
+
     fn calculate_subpixel_mode(&self) -> SubpixelMode {
         // We can only consider the full opaque cases if there's no underlays
         if self.underlays.is_empty() {
@@ -3760,6 +3932,7 @@ impl TileCacheInstance {
 
         self.dirty_region.reset(self.spatial_node_index);
         self.subpixel_mode = self.calculate_subpixel_mode();
+        self.known_opaque_rect = self.calculate_known_opaque_rect();
 
         self.transform_index = frame_state.composite_state.register_transform(
             self.local_to_raster,
@@ -3926,8 +4099,9 @@ impl TileCacheInstance {
         }
 
         // Register the opaque region of this tile cache as an occluder, which
-        // is used later in the frame to occlude other tiles.
-        if !self.backdrop.opaque_rect.is_empty() {
+        // is used later in the frame to occlude other tiles. If the tiles are
+        // composited with an opacity, nothing behind them can be occluded.
+        if !self.backdrop.opaque_rect.is_empty() && self.composite_opacity >= 1.0 {
             let z_id_backdrop = frame_state.composite_state.z_generator.next();
 
             let backdrop_rect = self.backdrop.opaque_rect
@@ -4239,7 +4413,7 @@ impl PictureCompositeMode {
         };
 
         match self {
-            PictureCompositeMode::Filter(Filter::Blur { width, height, should_inflate }) => {
+            PictureCompositeMode::Filter(Filter::Blur { width, height, should_inflate, .. }) => {
                 if *should_inflate {
                     let (width_factor, height_factor) = surface.clamp_blur_radius(*width, *height);
 
@@ -4335,7 +4509,7 @@ impl PictureCompositeMode {
         };
 
         match self {
-            PictureCompositeMode::Filter(Filter::Blur { width, height, should_inflate }) => {
+            PictureCompositeMode::Filter(Filter::Blur { width, height, should_inflate, .. }) => {
                 if *should_inflate {
                     let (width_factor, height_factor) = surface.clamp_blur_radius(*width, *height);
 
@@ -5145,6 +5319,7 @@ impl PicturePrimitive {
         pic_index: PictureIndex,
         parent_surface_index: Option<SurfaceIndex>,
         parent_subpixel_mode: SubpixelMode,
+        parent_known_opaque_rect: PictureRect,
         frame_state: &mut FrameBuildingState,
         frame_context: &FrameBuildingContext,
         data_stores: &mut DataStores,
@@ -5676,6 +5851,7 @@ impl PicturePrimitive {
                                 )
                             }
                         };
+                        let is_opaque = is_opaque && tile_cache.composite_opacity >= 1.0;
 
                         if is_opaque {
                             sub_slice.opaque_tile_descriptors.push(descriptor);
@@ -5692,6 +5868,8 @@ impl PicturePrimitive {
                             device_clip_rect,
                             z_id: tile.z_id,
                             transform_index: tile_cache.transform_index,
+                            opacity: tile_cache.composite_opacity,
+                            tile_id: Some(tile.id),
                         };
 
                         sub_slice.composite_tiles.push(composite_tile);
@@ -5840,10 +6018,17 @@ impl PicturePrimitive {
                     self.prev_local_rect = local_rect;
                 }
 
-                let max_surface_size = frame_context
+                // Never allocate a surface larger than the render targets supported
+                // by the device. Pictures that don't fit are rasterized at a reduced
+                // scale and upsampled when composited into their parent.
+                let mut max_surface_size = frame_context
                     .fb_config
                     .max_surface_override
-                    .unwrap_or(MAX_SURFACE_SIZE) as f32;
+                    .unwrap_or(MAX_SURFACE_SIZE);
+                if frame_context.fb_config.max_target_size > 0 {
+                    max_surface_size = max_surface_size.min(frame_context.fb_config.max_target_size as usize);
+                }
+                let max_surface_size = max_surface_size as f32;
 
                 let surface_rects = match get_surface_rects(
                     raster_config.surface_index,
@@ -5870,7 +6055,7 @@ impl PicturePrimitive {
                     PictureCompositeMode::TileCache { .. } => {
                         unreachable!("handled above");
                     }
-                    PictureCompositeMode::Filter(Filter::Blur { width, height, .. }) => {
+                    PictureCompositeMode::Filter(Filter::Blur { width, height, cheap, .. }) => {
                         let surface = &frame_state.surfaces[raster_config.surface_index.0];
                         let (width, height) = surface.clamp_blur_radius(width, height);
 
@@ -5886,10 +6071,17 @@ impl PicturePrimitive {
                         // Adjust the size to avoid introducing sampling errors during the down-scaling passes.
                         // what would be even better is to rasterize the picture at the down-scaled size
                         // directly.
-                        let adjusted_size = BlurTask::adjusted_blur_source_size(
-                            original_size,
-                            blur_std_deviation,
-                        );
+                        let adjusted_size = if cheap {
+                            BlurTask::adjusted_cheap_blur_source_size(
+                                original_size,
+                                blur_std_deviation,
+                            )
+                        } else {
+                            BlurTask::adjusted_blur_source_size(
+                                original_size,
+                                blur_std_deviation,
+                            )
+                        };
 
                         let cmd_buffer_index = frame_state.cmd_buffers.create_cmd_buffer();
 
@@ -5919,14 +6111,24 @@ impl PicturePrimitive {
                             ).with_uv_rect_kind(uv_rect_kind)
                         );
 
-                        let blur_render_task_id = RenderTask::new_blur(
-                            blur_std_deviation,
-                            picture_task_id,
-                            frame_state.rg_builder,
-                            RenderTargetKind::Color,
-                            None,
-                            original_size.to_i32(),
-                        );
+                        let blur_render_task_id = if cheap {
+                            RenderTask::new_cheap_blur(
+                                blur_std_deviation,
+                                picture_task_id,
+                                frame_state.rg_builder,
+                                RenderTargetKind::Color,
+                                original_size.to_i32(),
+                            )
+                        } else {
+                            RenderTask::new_blur(
+                                blur_std_deviation,
+                                picture_task_id,
+                                frame_state.rg_builder,
+                                RenderTargetKind::Color,
+                                None,
+                                original_size.to_i32(),
+                            )
+                        };
 
                         primary_render_task_id = blur_render_task_id;
 
@@ -6425,6 +6627,17 @@ impl PicturePrimitive {
             }
         };
 
+        // Pictures drawn into their parent's surface share its known opaque
+        // area. Tile caches have their own, and nothing is known about the
+        // background of other intermediate surfaces.
+        let known_opaque_rect = match self.raster_config {
+            Some(RasterConfig { composite_mode: PictureCompositeMode::TileCache { slice_id }, .. }) => {
+                tile_caches[&slice_id].known_opaque_rect
+            }
+            Some(..) => PictureRect::zero(),
+            None => parent_known_opaque_rect,
+        };
+
         let context = PictureContext {
             pic_index,
             raster_spatial_node_index: frame_state.surfaces[surface_index.0].raster_spatial_node_index,
@@ -6432,6 +6645,7 @@ impl PicturePrimitive {
             surface_index,
             dirty_region_count,
             subpixel_mode,
+            known_opaque_rect,
         };
 
         let prim_list = mem::replace(&mut self.prim_list, PrimitiveList::empty());
@@ -7645,6 +7859,78 @@ impl CompositeState {
     }
 }
 
+/// If the only clip on a compositor surface candidate that needs a mask is a
+/// single rounded rect bounding the primitive, and the native compositor can
+/// round the corners of a surface clip itself, return the device space radii
+/// to pass to the compositor.
+fn get_compositor_clip_radius(
+    prim_clip_chain: &ClipChainInstance,
+    prim_spatial_node_index: SpatialNodeIndex,
+    local_prim_rect: LayoutRect,
+    clip_store: &ClipStore,
+    clip_data_store: &ClipDataStore,
+    frame_context: &FrameVisibilityContext,
+) -> Option<ClipRadius> {
+    match frame_context.config.compositor_kind {
+        CompositorKind::Native { capabilities, .. } if capabilities.supports_surface_clip_radius => {}
+        _ => return None,
+    }
+
+    if !prim_clip_chain.needs_mask {
+        return None;
+    }
+
+    let mut clip_radius = None;
+
+    let clip_instances = &clip_store
+        .clip_node_instances[prim_clip_chain.clips_range.to_range()];
+    for clip_instance in clip_instances {
+        let clip = &clip_data_store[clip_instance.handle];
+
+        match clip.item.kind {
+            // Handled by the surface clip rect.
+            ClipItemKind::Rectangle { mode: ClipMode::Clip, .. }
+                if clip_instance.flags.contains(ClipNodeFlags::SAME_COORD_SYSTEM) => {}
+            ClipItemKind::RoundedRectangle { rect, ref radius, mode: ClipMode::Clip }
+                if clip_radius.is_none() &&
+                   clip_instance.flags.contains(ClipNodeFlags::SAME_SPATIAL_NODE) => {
+                // The corners only line up with the surface clip rect if the
+                // rounded rect is the tightest clip on the primitive.
+                if !local_prim_rect.contains_box(&rect) ||
+                   !prim_clip_chain.local_clip_rect.contains_box(&rect) {
+                    return None;
+                }
+
+                let scale = match frame_context.spatial_tree.get_relative_transform(
+                    prim_spatial_node_index,
+                    frame_context.root_spatial_node_index,
+                ) {
+                    CoordinateSpaceMapping::Local => Vector2D::new(1.0, 1.0),
+                    CoordinateSpaceMapping::ScaleOffset(scale_offset) => scale_offset.scale,
+                    CoordinateSpaceMapping::Transform(..) => return None,
+                } * frame_context.global_device_pixel_scale.0;
+
+                // Compositors only support circular corners.
+                let corner = |size: LayoutSize| {
+                    let width = (size.width * scale.x.abs()).round();
+                    let height = (size.height * scale.y.abs()).round();
+                    if width == height { Some(width as i32) } else { None }
+                };
+
+                clip_radius = Some(ClipRadius {
+                    top_left: corner(radius.top_left)?,
+                    top_right: corner(radius.top_right)?,
+                    bottom_left: corner(radius.bottom_left)?,
+                    bottom_right: corner(radius.bottom_right)?,
+                });
+            }
+            _ => return None,
+        }
+    }
+
+    clip_radius
+}
+
 fn get_relative_scale_offset(
     child_spatial_node_index: SpatialNodeIndex,
     parent_spatial_node_index: SpatialNodeIndex,
@@ -7861,6 +8147,12 @@ fn get_surface_rects(
                     source.height()
                 ))).ceil();
     if max_dimension > max_surface_size {
+        debug!(
+            "Surface of {} device pixels exceeds the max surface size of {}, rasterizing at a reduced scale",
+            max_dimension,
+            max_surface_size,
+        );
+
         let max_dimension =
             clipped_local.width().max(
                 clipped_local.height().max(
@@ -8102,3 +8394,61 @@ fn test_drop_filter_dirty_region_outside_prim() {
     ).expect("No surface rect");
     assert_eq!(info.task_size, DeviceIntSize::new(432, 578));
 }
+
+#[test]
+fn test_coalesce_tile_grid() {
+    // A very large display where every tile is dirty, as on the first frame.
+    let screen_rect = PictureRect::new(
+        PicturePoint::new(0.0, 0.0),
+        PicturePoint::new(8192.0, 4096.0),
+    );
+    let tile_size = PictureSize::new(1024.0, 512.0);
+
+    // Without a limit, the default tiles are used.
+    let (factor, tile_rect) = coalesce_tile_grid(
+        screen_rect,
+        screen_rect,
+        tile_size,
+        usize::MAX,
+        1,
+        8,
+    );
+    assert_eq!(factor, 1);
+    assert_eq!(tile_rect.area(), 64);
+
+    // With a limit, 2x2 blocks of tiles are merged until the number of tile
+    // updates fits.
+    let (factor, tile_rect) = coalesce_tile_grid(
+        screen_rect,
+        screen_rect,
+        tile_size,
+        16,
+        1,
+        8,
+    );
+    assert_eq!(factor, 2);
+    assert!(tile_rect.area() <= 16);
+
+    // Coalescing is bounded by the maximum factor.
+    let (factor, tile_rect) = coalesce_tile_grid(
+        screen_rect,
+        screen_rect,
+        tile_size,
+        1,
+        1,
+        4,
+    );
+    assert_eq!(factor, 4);
+    assert_eq!(tile_rect.area(), 4);
+
+    // An existing coalesce factor is kept even if the grid would fit without it.
+    let (factor, _) = coalesce_tile_grid(
+        screen_rect,
+        screen_rect,
+        tile_size,
+        usize::MAX,
+        2,
+        8,
+    );
+    assert_eq!(factor, 2);
+}
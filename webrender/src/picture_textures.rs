@@ -201,6 +201,7 @@ impl PictureTextures {
                 filter: self.filter,
                 is_shared_cache: false,
                 has_depth: true,
+                is_srgb: false,
                 category: TextureCacheCategory::PictureTile,
             };
 
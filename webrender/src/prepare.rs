@@ -6,7 +6,7 @@
 //!
 //! TODO: document this!
 
-use api::{ColorF, PropertyBinding};
+use api::{ColorF, FontInstanceFlags, PropertyBinding};
 use api::{BoxShadowClipMode, BorderStyle, ClipMode};
 use api::units::*;
 use euclid::Scale;
@@ -35,6 +35,7 @@ use crate::render_task_cache::RenderTaskCacheKeyKind;
 use crate::render_task_cache::{RenderTaskCacheKey, to_cache_size, RenderTaskParent};
 use crate::render_task::{EmptyTask, MaskSubPass, RenderTask, RenderTaskKind, SubPass};
 use crate::segment::SegmentBuilder;
+use crate::space::snap_rect_to_device_pixels;
 use crate::util::{clamp_to_scale_factor, pack_as_float, ScaleOffset};
 use crate::visibility::{compute_conservative_visible_rect, PrimitiveVisibility, VisibilityState};
 
@@ -168,6 +169,7 @@ fn prepare_prim_for_render(
             pic_index,
             Some(pic_context.surface_index),
             pic_context.subpixel_mode,
+            pic_context.known_opaque_rect,
             frame_state,
             frame_context,
             data_stores,
@@ -320,6 +322,19 @@ fn prepare_interned_prim_for_render(
     let prim_spatial_node_index = cluster.spatial_node_index;
     let device_pixel_scale = frame_state.surfaces[pic_context.surface_index.0].device_pixel_scale;
 
+    // The primitive rect itself is snapped when it is written out for
+    // rendering, but the clip rect is per-instance and can be snapped here.
+    let snap_to_device_pixels = data_stores.prim_snaps_to_device_pixels(prim_instance);
+    if snap_to_device_pixels {
+        prim_instance.vis.clip_chain.local_clip_rect = snap_rect_to_device_pixels(
+            &prim_instance.vis.clip_chain.local_clip_rect,
+            prim_spatial_node_index,
+            pic_context.raster_spatial_node_index,
+            device_pixel_scale,
+            frame_context.spatial_tree,
+        );
+    }
+
     match &mut prim_instance.kind {
         PrimitiveInstanceKind::BoxShadow { data_handle } => {
             let prim_data = &mut data_stores.box_shadow[*data_handle];
@@ -476,6 +491,12 @@ fn prepare_interned_prim_for_render(
                 }
             };
 
+            // Text runs that opt out of subpixel AA over transparent content
+            // additionally need a known opaque background.
+            let allow_subpixel = allow_subpixel &&
+                (!prim_data.font.flags.contains(FontInstanceFlags::NO_SUBPIXEL_OVER_TRANSPARENT) ||
+                 pic_context.known_opaque_rect.contains_box(&prim_instance.vis.clip_chain.pic_coverage_rect));
+
             run.request_resources(
                 prim_offset,
                 &prim_data.font,
@@ -552,11 +573,15 @@ fn prepare_interned_prim_for_render(
             // this segment will be available for batching later in the frame.
             let mut handles: SmallVec<[RenderTaskId; 8]> = SmallVec::new();
 
+            let dash_phase = frame_context.scene_properties.resolve_float(&border_data.dash_phase);
+            let corner_cache = &mut scratch.border_corner_cache;
+
             for segment in &border_data.border_segments {
                 // Update the cache key device size based on requested scale.
                 let cache_size = to_cache_size(segment.local_task_size, &mut scale);
+                let segment_key = segment.cache_key.with_dash_phase(dash_phase);
                 let cache_key = RenderTaskCacheKey {
-                    kind: RenderTaskCacheKeyKind::BorderSegment(segment.cache_key.clone()),
+                    kind: RenderTaskCacheKeyKind::BorderSegment(segment_key.clone()),
                     size: cache_size,
                 };
 
@@ -574,10 +599,11 @@ fn prepare_interned_prim_for_render(
                             cache_size,
                             RenderTaskKind::new_border_segment(
                                 build_border_instances(
-                                    &segment.cache_key,
+                                    &segment_key,
                                     cache_size,
                                     &border_data.border,
                                     scale,
+                                    corner_cache,
                                 )
                             ),
                         ))
@@ -654,9 +680,21 @@ fn prepare_interned_prim_for_render(
             } else {
                 let prim_data = &data_stores.prim[*data_handle];
 
+                let prim_rect = if snap_to_device_pixels {
+                    snap_rect_to_device_pixels(
+                        &prim_data.common.prim_rect,
+                        prim_spatial_node_index,
+                        pic_context.raster_spatial_node_index,
+                        device_pixel_scale,
+                        frame_context.spatial_tree,
+                    )
+                } else {
+                    prim_data.common.prim_rect
+                };
+
                 quad::prepare_quad(
                     prim_data,
-                    &prim_data.common.prim_rect,
+                    &prim_rect,
                     prim_instance_index,
                     prim_spatial_node_index,
                     &prim_instance.vis.clip_chain,
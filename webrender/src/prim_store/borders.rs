@@ -2,7 +2,7 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use api::{NormalBorder, PremultipliedColorF, Shadow, RasterSpace};
+use api::{NormalBorder, PremultipliedColorF, PropertyBinding, PropertyBindingKey, Shadow, RasterSpace};
 use api::units::*;
 use crate::border::create_border_segments;
 use crate::border::NormalBorderAu;
@@ -12,7 +12,7 @@ use crate::gpu_cache::GpuDataRequest;
 use crate::intern;
 use crate::internal_types::{LayoutPrimitiveInfo, FrameId};
 use crate::prim_store::{
-    BorderSegmentInfo, BrushSegment, NinePatchDescriptor, PrimKey,
+    BorderSegmentInfo, BrushSegment, FloatKey, NinePatchDescriptor, PrimKey,
     PrimTemplate, PrimTemplateCommonData,
     PrimitiveInstanceKind, PrimitiveOpacity,
     PrimitiveStore, InternablePrimitive,
@@ -29,6 +29,26 @@ use super::storage;
 pub struct NormalBorderPrim {
     pub border: NormalBorderAu,
     pub widths: LayoutSideOffsetsAu,
+    pub dash_phase: PropertyBinding<FloatKey>,
+}
+
+/// Convert a dash phase binding to the hashable form used in the interning key.
+pub fn dash_phase_key(dash_phase: PropertyBinding<f32>) -> PropertyBinding<FloatKey> {
+    match dash_phase {
+        PropertyBinding::Value(value) => PropertyBinding::Value(value.into()),
+        PropertyBinding::Binding(key, value) => {
+            PropertyBinding::Binding(PropertyBindingKey::new(key.into()), value.into())
+        }
+    }
+}
+
+fn dash_phase_from_key(dash_phase: PropertyBinding<FloatKey>) -> PropertyBinding<f32> {
+    match dash_phase {
+        PropertyBinding::Value(value) => PropertyBinding::Value(value.into()),
+        PropertyBinding::Binding(key, value) => {
+            PropertyBinding::Binding(PropertyBindingKey::new(key.into()), value.into())
+        }
+    }
 }
 
 pub type NormalBorderKey = PrimKey<NormalBorderPrim>;
@@ -55,6 +75,8 @@ pub struct NormalBorderData {
     pub border_segments: Vec<BorderSegmentInfo>,
     pub border: NormalBorder,
     pub widths: LayoutSideOffsets,
+    /// Offset of the dash pattern of the dashed edges, resolved each frame.
+    pub dash_phase: PropertyBinding<f32>,
 }
 
 impl NormalBorderData {
@@ -137,6 +159,7 @@ impl From<NormalBorderKey> for NormalBorderTemplate {
                 border_segments,
                 border,
                 widths,
+                dash_phase: dash_phase_from_key(key.kind.dash_phase),
             }
         }
     }
@@ -185,6 +208,7 @@ impl CreateShadow for NormalBorderPrim {
         NormalBorderPrim {
             border,
             widths: self.widths,
+            dash_phase: self.dash_phase,
         }
     }
 }
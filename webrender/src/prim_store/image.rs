@@ -4,7 +4,7 @@
 
 use api::{
     AlphaType, ColorDepth, ColorF, ColorU, ExternalImageType,
-    ImageKey as ApiImageKey, ImageBufferKind, ImageRendering, PremultipliedColorF,
+    ImageKey as ApiImageKey, ImageBufferKind, ImageRendering, ImageWrapMode, PremultipliedColorF,
     RasterSpace, Shadow, YuvColorSpace, ColorRange, YuvFormat,
 };
 use api::units::*;
@@ -72,6 +72,9 @@ pub struct ImageInstance {
     pub visible_tiles: Vec<VisibleImageTile>,
     pub src_color: Option<RenderTaskId>,
     pub normalized_uvs: bool,
+    /// The wrap mode the texture sampler should use, or `Clamp` if the
+    /// image is repeated by WebRender instead.
+    pub wrap_mode: ImageWrapMode,
 }
 
 #[cfg_attr(feature = "capture", derive(Serialize))]
@@ -84,6 +87,7 @@ pub struct Image {
     pub color: ColorU,
     pub image_rendering: ImageRendering,
     pub alpha_type: AlphaType,
+    pub wrap_mode: ImageWrapMode,
 }
 
 pub type ImageKey = PrimKey<Image>;
@@ -112,6 +116,7 @@ pub struct ImageData {
     pub color: ColorF,
     pub image_rendering: ImageRendering,
     pub alpha_type: AlphaType,
+    pub wrap_mode: ImageWrapMode,
 }
 
 impl From<Image> for ImageData {
@@ -123,6 +128,7 @@ impl From<Image> for ImageData {
             tile_spacing: image.tile_spacing.into(),
             image_rendering: image.image_rendering,
             alpha_type: image.alpha_type,
+            wrap_mode: image.wrap_mode,
         }
     }
 }
@@ -169,6 +175,27 @@ impl ImageData {
             tile: None,
         };
 
+        image_instance.wrap_mode = ImageWrapMode::Clamp;
+        if self.wrap_mode != ImageWrapMode::Clamp {
+            // Sampler wrapping would bleed into neighbouring items if the image
+            // lives in an atlas, so it is only used for standalone textures.
+            if self.tile_spacing == LayoutSize::zero() &&
+                frame_state.resource_cache.is_image_standalone(request) {
+                image_instance.wrap_mode = self.wrap_mode;
+                // The sampler takes care of the repetition, draw a single quad.
+                common.may_need_repetition = false;
+            } else {
+                // The template is shared with other primitives and the image
+                // may move to a standalone texture later, so only this
+                // instance is clamped.
+                debug!(
+                    "Image {:?} can't use {:?} wrapping outside of a standalone texture, clamping instead",
+                    self.key,
+                    self.wrap_mode,
+                );
+            }
+        }
+
         match image_properties {
             // Non-tiled (most common) path.
             Some(ImageProperties { tiling: None, ref descriptor, ref external_image, .. }) => {
@@ -450,6 +477,7 @@ impl InternablePrimitive for Image {
             visible_tiles: Vec::new(),
             src_color: None,
             normalized_uvs: false,
+            wrap_mode: ImageWrapMode::Clamp,
         });
 
         PrimitiveInstanceKind::Image {
@@ -474,6 +502,7 @@ impl CreateShadow for Image {
             image_rendering: self.image_rendering,
             alpha_type: self.alpha_type,
             color: shadow.color.into(),
+            wrap_mode: self.wrap_mode,
         }
     }
 }
@@ -2,7 +2,7 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use api::{BorderRadius, ClipMode, ColorF, ColorU, RasterSpace};
+use api::{BorderRadius, ClipAaQuality, ClipMode, ColorF, ColorU, RasterSpace};
 use api::{ImageRendering, RepeatMode, PrimitiveFlags};
 use api::{PremultipliedColorF, PropertyBinding, Shadow};
 use api::{PrimitiveKeyKind, FillRule, POLYGON_CLIP_VERTEX_MAX};
@@ -14,7 +14,7 @@ use crate::clip::ClipLeafId;
 use crate::pattern::{Pattern, PatternBuilder, PatternBuilderContext, PatternBuilderState};
 use crate::quad::QuadTileClassifier;
 use crate::segment::EdgeAaSegmentMask;
-use crate::border::BorderSegmentCacheKey;
+use crate::border::{BorderCornerCache, BorderSegmentCacheKey};
 use crate::debug_item::{DebugItem, DebugMessage};
 use crate::debug_colors;
 use crate::scene_building::{CreateShadow, IsVisible};
@@ -423,6 +423,18 @@ impl hash::Hash for FloatKey {
     }
 }
 
+impl From<f32> for FloatKey {
+    fn from(value: f32) -> FloatKey {
+        FloatKey(value)
+    }
+}
+
+impl From<FloatKey> for f32 {
+    fn from(key: FloatKey) -> f32 {
+        key.0
+    }
+}
+
 
 #[cfg_attr(feature = "capture", derive(Serialize))]
 #[cfg_attr(feature = "replay", derive(Deserialize))]
@@ -774,6 +786,7 @@ impl BrushSegment {
 struct ClipRect {
     rect: LayoutRect,
     mode: f32,
+    aa_quality: f32,
 }
 
 #[derive(Debug, Clone)]
@@ -824,6 +837,7 @@ impl ClipData {
             rect: ClipRect {
                 rect,
                 mode: mode as u32 as f32,
+                aa_quality: 0.0,
             },
             top_left: ClipCorner {
                 rect: LayoutRect::from_origin_and_size(
@@ -877,6 +891,14 @@ impl ClipData {
         }
     }
 
+    /// Selects how the edges of the clip mask are anti-aliased.
+    pub fn set_aa_quality(&mut self, quality: ClipAaQuality) {
+        self.rect.aa_quality = match quality {
+            ClipAaQuality::Standard => 0.0,
+            ClipAaQuality::High => 1.0,
+        };
+    }
+
     pub fn uniform(size: LayoutSize, radius: f32, mode: ClipMode) -> ClipData {
         // TODO(gw): For simplicity, keep most of the clip GPU structs the
         //           same as they were, even though the origin is now always
@@ -888,6 +910,7 @@ impl ClipData {
             rect: ClipRect {
                 rect,
                 mode: mode as u32 as f32,
+                aa_quality: 0.0,
             },
             top_left: ClipCorner::uniform(
                 LayoutRect::from_origin_and_size(
@@ -1267,6 +1290,9 @@ pub struct PrimitiveScratchBuffer {
     /// A retained classifier for checking which segments of a tiled primitive
     /// need a mask / are clipped / can be rendered directly
     pub quad_tile_classifier: QuadTileClassifier,
+
+    /// Tessellated dashed and dotted border corners, retained across frames.
+    pub border_corner_cache: BorderCornerCache,
 }
 
 impl Default for PrimitiveScratchBuffer {
@@ -1285,6 +1311,7 @@ impl Default for PrimitiveScratchBuffer {
             quad_color_segments: Vec::new(),
             quad_indirect_segments: Vec::new(),
             quad_tile_classifier: QuadTileClassifier::new(),
+            border_corner_cache: BorderCornerCache::default(),
         }
     }
 }
@@ -67,7 +67,7 @@ static PROFILER_PRESETS: &'static[(&'static str, &'static str)] = &[
     // Timing information for per-frame stages.
     (&"Frame times", &"Frame CPU total,Frame building,Visibility,Prepare,Batching,Glyph resolve,Texture cache update,Shader build time,Renderer,GPU"),
     // Stats about the content of the frame.
-    (&"Frame stats", &"Primitives,Visible primitives,Draw calls,Vertices,Color passes,Alpha passes,Rendered picture tiles,Rasterized glyphs"),
+    (&"Frame stats", &"Primitives,Visible primitives,Draw calls,Vertices,Color passes,Alpha passes,Rendered picture tiles,Rasterized glyphs,Render task cache hits,Render task cache misses"),
     // Texture cache allocation stats.
     (&"Texture cache stats", &"Atlas textures mem, Standalone textures mem, Picture tiles mem, Render targets mem, Depth targets mem, Atlas items mem,
         Texture cache standalone pressure, Texture cache eviction count, Texture cache youngest evicted, ,
@@ -286,7 +286,14 @@ pub const COMPOSITOR_SURFACE_UNDERLAYS: usize = 138;
 pub const COMPOSITOR_SURFACE_OVERLAYS: usize = 139;
 pub const COMPOSITOR_SURFACE_BLITS: usize = 140;
 
-pub const NUM_PROFILER_EVENTS: usize = 141;
+pub const RENDER_TASK_CACHE_HITS: usize = 141;
+pub const RENDER_TASK_CACHE_MISSES: usize = 142;
+
+pub const CANCELLED_SCENE_BUILDS: usize = 143;
+
+pub const BACKEND_WAIT_TIME: usize = 144;
+
+pub const NUM_PROFILER_EVENTS: usize = 145;
 
 pub struct Profiler {
     counters: Vec<Counter>,
@@ -507,6 +514,13 @@ impl Profiler {
             int("Compositor surface underlays", "", COMPOSITOR_SURFACE_UNDERLAYS, Expected::none()),
             int("Compositor surface overlays", "", COMPOSITOR_SURFACE_OVERLAYS, Expected::none()),
             int("Compositor surface blits", "", COMPOSITOR_SURFACE_BLITS, Expected::none()),
+
+            int("Render task cache hits", "", RENDER_TASK_CACHE_HITS, Expected::none()),
+            int("Render task cache misses", "", RENDER_TASK_CACHE_MISSES, expected(0..10)),
+
+            int("Cancelled scene builds", "", CANCELLED_SCENE_BUILDS, Expected::none()),
+
+            float("Backend wait", "ms", BACKEND_WAIT_TIME, Expected::none()),
         ];
 
         let mut counters = Vec::with_capacity(profile_counters.len());
@@ -5,29 +5,33 @@
 #![deny(missing_docs)]
 
 use std::cell::Cell;
+use std::collections::BTreeMap;
 use std::fmt;
 use std::marker::PhantomData;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 use std::u32;
 use api::MinimapData;
 use time::precise_time_ns;
-use crate::api::channel::{Sender, single_msg_channel, unbounded_channel};
+use crate::api::channel::{Receiver, Sender, single_msg_channel, unbounded_channel};
 use crate::api::{BuiltDisplayList, IdNamespace, ExternalScrollId, Parameter, BoolParameter};
 use crate::api::{FontKey, FontInstanceKey, NativeFontHandle};
 use crate::api::{BlobImageData, BlobImageKey, ImageData, ImageDescriptor, ImageKey, Epoch, QualitySettings};
 use crate::api::{BlobImageParams, BlobImageRequest, BlobImageResult, AsyncBlobImageRasterizer, BlobImageHandler};
 use crate::api::{DocumentId, PipelineId, PropertyBindingId, PropertyBindingKey, ExternalEvent};
 use crate::api::{HitTestResult, HitTesterRequest, ApiHitTester, PropertyValue, DynamicProperties};
-use crate::api::{SampledScrollOffset, TileSize, NotificationRequest, DebugFlags};
+use crate::api::{SampledScrollOffset, ScrollConfig, SpatialId, TileSize, NotificationRequest, DebugFlags};
 use crate::api::{GlyphDimensionRequest, GlyphIndexRequest, GlyphIndex, GlyphDimensions};
 use crate::api::{FontInstanceOptions, FontInstancePlatformOptions, FontVariation, RenderReasons};
+use crate::api::{GradientStop, GradientTemplate, GradientTemplateKey, GradientTemplateKind};
 use crate::api::DEFAULT_TILE_SIZE;
 use crate::api::units::*;
 use crate::api_resources::ApiResources;
 use glyph_rasterizer::SharedFontResources;
-use crate::scene_builder_thread::{SceneBuilderRequest, SceneBuilderResult};
+use crate::scene_builder_thread::{SceneBuildCancellation, SceneBuilderRequest, SceneBuilderResult};
 use crate::intern::InterningMemoryReport;
+use crate::glyph_cache::GlyphCacheExport;
 use crate::profiler::{self, TransactionProfile};
 
 #[repr(C)]
@@ -45,6 +49,8 @@ pub enum ResourceUpdate {
     AddImage(AddImage),
     /// See `UpdateImage`.
     UpdateImage(UpdateImage),
+    /// See `UpdateImageRegions`.
+    UpdateImageRegions(UpdateImageRegions),
     /// Delete an existing image resource.
     ///
     /// It is invalid to continue referring to the image key in any display list
@@ -88,6 +94,10 @@ impl fmt::Debug for ResourceUpdate {
                 "ResourceUpdate::UpdateImage size({:?})",
                 &i.descriptor.size
             )),
+            ResourceUpdate::UpdateImageRegions(ref i) => f.write_fmt(format_args!(
+                "ResourceUpdate::UpdateImageRegions regions({:?})",
+                i.regions.len()
+            )),
             ResourceUpdate::AddBlobImage(ref i) => f.write_fmt(format_args!(
                 "ResourceUFpdate::AddBlobImage size({:?})",
                 &i.descriptor.size
@@ -179,6 +189,14 @@ pub struct Transaction {
 
     low_priority: bool,
 
+    /// Whether pending scene builds of the document should be abandoned in
+    /// favour of this transaction.
+    supersedes_pending: bool,
+
+    /// Embedder-provided id reported back with the frame this transaction
+    /// contributes to.
+    trace_id: Option<u64>,
+
     ///
     pub render_reasons: RenderReasons,
 }
@@ -196,6 +214,8 @@ impl Transaction {
             creation_time: precise_time_ns(),
             invalidate_rendered_frame: false,
             low_priority: false,
+            supersedes_pending: false,
+            trace_id: None,
             render_reasons: RenderReasons::empty(),
         }
     }
@@ -217,6 +237,24 @@ impl Transaction {
         self.use_scene_builder_thread = true;
     }
 
+    /// Marks this transaction as superseding the scene builds of the document
+    /// that are still pending on the scene builder thread.
+    ///
+    /// This is useful when transactions arrive faster than scenes can be built,
+    /// for example while resizing. Scene updates of the superseded transactions
+    /// are still applied, but only the scene of this transaction gets built.
+    pub fn set_supersedes_pending(&mut self, supersedes_pending: bool) {
+        self.supersedes_pending = supersedes_pending;
+    }
+
+    /// Tag this transaction with an id that is reported, in
+    /// `RenderResults::trace_ids` and in profiler markers, for the frame
+    /// built from it. This allows embedders to find the frame that resulted
+    /// from a given transaction when correlating traces.
+    pub fn set_trace_id(&mut self, trace_id: u64) {
+        self.trace_id = Some(trace_id);
+    }
+
     /// Returns true if the transaction has no effect.
     pub fn is_empty(&self) -> bool {
         !self.generate_frame.as_bool() &&
@@ -393,6 +431,15 @@ impl Transaction {
         self.frame_ops.push(FrameMsg::AppendDynamicTransformProperties(transforms));
     }
 
+    /// Replace the transforms of reference frames in the current scene, without
+    /// building a new scene. Only reference frames with a transform that isn't
+    /// bound to a property can be updated, and ones that were axis-aligned in
+    /// the display list must stay axis-aligned. The transforms from the display
+    /// list are restored when a new scene is built.
+    pub fn update_spatial_nodes(&mut self, transforms: Vec<(SpatialId, LayoutTransform)>) {
+        self.frame_ops.push(FrameMsg::UpdateSpatialNodes(transforms));
+    }
+
     /// Consumes this object and just returns the frame ops.
     pub fn get_frame_ops(self) -> Vec<FrameMsg> {
         self.frame_ops
@@ -410,6 +457,9 @@ impl Transaction {
             creation_time: Some(self.creation_time),
             invalidate_rendered_frame: self.invalidate_rendered_frame,
             low_priority: self.low_priority,
+            supersedes_pending: self.supersedes_pending,
+            trace_id: self.trace_id,
+            scene_build_seq: 0,
             blob_rasterizer: None,
             blob_requests: Vec::new(),
             rasterized_blobs: Vec::new(),
@@ -450,6 +500,20 @@ impl Transaction {
         }));
     }
 
+    /// See `ResourceUpdate::UpdateImageRegions`.
+    pub fn update_image_regions(
+        &mut self,
+        key: ImageKey,
+        source: Arc<Vec<u8>>,
+        regions: Vec<(DeviceIntRect, usize)>,
+    ) {
+        self.resource_updates.push(ResourceUpdate::UpdateImageRegions(UpdateImageRegions {
+            key,
+            source,
+            regions,
+        }));
+    }
+
     /// See `ResourceUpdate::DeleteImage`.
     pub fn delete_image(&mut self, key: ImageKey) {
         self.resource_updates.push(ResourceUpdate::DeleteImage(key));
@@ -591,6 +655,14 @@ pub struct TransactionMsg {
     pub use_scene_builder_thread: bool,
     ///
     pub low_priority: bool,
+    /// Whether pending scene builds of the document should be abandoned in
+    /// favour of this transaction.
+    pub supersedes_pending: bool,
+    /// See `Transaction::set_trace_id`.
+    pub trace_id: Option<u64>,
+    /// Order of this transaction among the ones sent to the scene builder,
+    /// used to detect builds that were superseded.
+    pub scene_build_seq: u64,
 
     /// Handlers to notify at certain points of the pipeline.
     pub notifications: Vec<NotificationRequest>,
@@ -639,6 +711,17 @@ impl TransactionMsg {
             self.resource_updates.is_empty() &&
             self.notifications.is_empty()
     }
+
+    /// Returns true if this transaction only updates resources, and can be
+    /// applied without building a scene or a frame.
+    pub fn is_resource_only(&self) -> bool {
+        !self.resource_updates.is_empty() &&
+            !self.generate_frame.as_bool() &&
+            !self.invalidate_rendered_frame &&
+            self.scene_ops.is_empty() &&
+            self.frame_ops.is_empty() &&
+            self.notifications.is_empty()
+    }
 }
 
 /// Creates an image resource with provided parameters.
@@ -678,6 +761,26 @@ pub struct UpdateImage {
     pub dirty_rect: ImageDirtyRect,
 }
 
+/// Updates several regions of an already existing raw image from a single
+/// shared buffer.
+///
+/// This avoids sending one `UpdateImage` per region when the embedder keeps
+/// the contents of the image, e.g. a sprite atlas, in a buffer of its own.
+/// Each region is uploaded to the GPU straight from the buffer, and regions
+/// that don't fit in the image or the buffer are ignored.
+#[derive(Clone)]
+#[cfg_attr(any(feature = "serde"), derive(Deserialize, Serialize))]
+pub struct UpdateImageRegions {
+    /// The key identfying the image resource to update.
+    pub key: ImageKey,
+    /// The buffer holding the new pixels of every region, in the format of the
+    /// image.
+    pub source: Arc<Vec<u8>>,
+    /// The rects of the image to update, each with the offset in `source` of
+    /// its pixels. The rows of a region are tightly packed.
+    pub regions: Vec<(DeviceIntRect, usize)>,
+}
+
 /// Creates a blob-image resource with provided parameters.
 ///
 /// Must be matched with a `DeleteImage` at some point to prevent memory leaks.
@@ -804,9 +907,13 @@ pub enum FrameMsg {
     ///
     AppendDynamicTransformProperties(Vec<PropertyValue<LayoutTransform>>),
     ///
+    UpdateSpatialNodes(Vec<(SpatialId, LayoutTransform)>),
+    ///
     SetIsTransformAsyncZooming(bool, PropertyBindingId),
     ///
-    SetMinimapData(ExternalScrollId, MinimapData)
+    SetMinimapData(ExternalScrollId, MinimapData),
+    ///
+    SetScrollConfig(ScrollConfig),
 }
 
 impl fmt::Debug for SceneMsg {
@@ -832,8 +939,10 @@ impl fmt::Debug for FrameMsg {
             FrameMsg::ResetDynamicProperties => "FrameMsg::ResetDynamicProperties",
             FrameMsg::AppendDynamicProperties(..) => "FrameMsg::AppendDynamicProperties",
             FrameMsg::AppendDynamicTransformProperties(..) => "FrameMsg::AppendDynamicTransformProperties",
+            FrameMsg::UpdateSpatialNodes(..) => "FrameMsg::UpdateSpatialNodes",
             FrameMsg::SetIsTransformAsyncZooming(..) => "FrameMsg::SetIsTransformAsyncZooming",
             FrameMsg::SetMinimapData(..) => "FrameMsg::SetMinimapData",
+            FrameMsg::SetScrollConfig(..) => "FrameMsg::SetScrollConfig",
         })
     }
 }
@@ -881,8 +990,49 @@ pub struct CapturedDocument {
     pub document_id: DocumentId,
     ///
     pub root_pipeline_id: Option<PipelineId>,
+    /// The metadata attached to the display list of each pipeline in the
+    /// captured scene. See `DisplayListBuilder::set_metadata`.
+    pub display_list_metadata: Vec<(PipelineId, BTreeMap<String, String>)>,
 }
 
+/// A rough measure of how expensive the most recently built frame of a
+/// document is to render, as returned by `RenderApi::get_frame_complexity`.
+///
+/// The numbers are gathered while building the frame, so they reflect the
+/// work the renderer is about to do without having to draw anything first.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(any(feature = "serde"), derive(Deserialize, Serialize))]
+pub struct FrameComplexity {
+    /// Number of primitives that survived culling.
+    pub primitive_count: usize,
+    /// Number of draw call batches across all render passes.
+    pub batch_count: usize,
+    /// Sum of the areas of the render target and picture cache regions
+    /// drawn to, in device pixels.
+    pub estimated_pixels_shaded: u64,
+    /// Number of render tasks in the frame's render task graph.
+    pub render_task_count: usize,
+}
+
+/// A document known to the render backend, as returned by
+/// `RenderApi::list_documents`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DocumentInfo {
+    /// The id of the document.
+    pub id: DocumentId,
+    /// The size of the document's device rect.
+    pub size: DeviceIntSize,
+    /// True if a frame of the document was sent to the renderer and hasn't
+    /// been rendered yet.
+    pub has_pending_frame: bool,
+}
+
+/// Returned by `RenderApi::flush_and_wait` when the pending work wasn't
+/// completed before the timeout expired.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FlushTimeout;
+
 /// Update of the state of built-in debugging facilities.
 #[derive(Clone)]
 pub enum DebugCommand {
@@ -898,6 +1048,9 @@ pub enum DebugCommand {
     StopCaptureSequence,
     /// Clear cached resources, forcing them to be re-uploaded from templates.
     ClearCaches(ClearCache),
+    /// Mark the contents of every texture cache entry as dirty, so that they
+    /// are uploaded again from their source data on the next update.
+    ReuploadTextureCache,
     /// Enable/disable native compositor usage
     EnableNativeCompositor(bool),
     /// Sets the maximum amount of existing batches to visit before creating a new one.
@@ -907,10 +1060,43 @@ pub enum DebugCommand {
     /// Causes the scene builder to pause for a given amount of milliseconds each time it
     /// processes a transaction.
     SimulateLongSceneBuild(u32),
+    /// Causes the low priority scene builder to pause for a given amount of
+    /// milliseconds each time it processes a transaction. Has no effect unless
+    /// `support_low_priority_transactions` is enabled.
+    SimulateLongLowPrioritySceneBuild(u32),
     /// Set an override tile size to use for picture caches
     SetPictureTileSize(Option<DeviceIntSize>),
     /// Set an override for max off-screen surface size
     SetMaximumSurfaceSize(Option<usize>),
+    /// Lower the number of depth ids the frame builder may hand out while
+    /// batching, to help debug z-precision issues. Clamped to at least one and
+    /// at most the device maximum.
+    SetMaxDepthIds(u32),
+    /// Allocate intermediate color targets with sRGB storage, so that blurs
+    /// are computed in linear space.
+    SetSrgbRenderTargets(bool),
+    /// Set the distance in device pixels around the screen within which
+    /// primitives are not culled.
+    SetCullingMargin(f32),
+    /// Set the size above which blur render tasks are rasterized at a
+    /// reduced resolution. See `WebRenderOptions::max_blur_raster_size`.
+    SetMaxBlurRasterSize(Option<DeviceIntSize>),
+    /// Override the maximum number of dirty rects per surface update reported
+    /// by the native compositor. Has no effect with the draw compositor.
+    SetCompositorMaxUpdateRects(usize),
+    /// Rasterize documents at their device size multiplied by the given
+    /// factor when scenes are next built. See `Renderer::set_render_scale`.
+    SetRenderScale(f32),
+    /// Set the width in device pixels of anti-aliased edges when scenes are
+    /// next built. See `Renderer::set_edge_aa_feather`.
+    SetEdgeAaFeather(f32),
+    /// Outline the world space bounds of the clips that affect visible
+    /// primitives, colored by the kind of clip: rectangles in green, rounded
+    /// rectangles in blue, image masks in magenta and box shadows in orange.
+    SetShowClipBounds(bool),
+    /// Write the glyph cache atlases and a listing of the glyphs they contain
+    /// to the given directory.
+    ExportGlyphAtlas(PathBuf),
 }
 
 /// Message sent by the `RenderApi` to the render backend thread.
@@ -927,10 +1113,24 @@ pub enum ApiMsg {
     MemoryPressure,
     /// Collects a memory report.
     ReportMemory(Sender<Box<MemoryReport>>),
+    /// Queries the complexity of the last frame built for a document.
+    GetFrameComplexity(DocumentId, Sender<FrameComplexity>),
+    /// Queries the deepest clip-chain in the last scene built for a document.
+    GetMaxClipChainDepth(DocumentId, Sender<usize>),
+    /// Collects the retained bitmaps of the glyphs in the glyph cache.
+    ExportGlyphCache(Sender<GlyphCacheExport>),
+    /// Lists the documents that are alive.
+    ListDocuments(Sender<Vec<DocumentInfo>>),
     /// Change debugging options.
     DebugCommand(DebugCommand),
     /// Message from the scene builder thread.
     SceneBuilderResult(SceneBuilderResult),
+    /// The renderer rendered or dropped a frame that counted towards
+    /// `WebRenderOptions::max_frames_in_flight`.
+    FrameRetired,
+    /// The renderer finished linking the shaders compiled with
+    /// `WebRenderOptions::async_shader_compile`.
+    ShadersReady,
 }
 
 impl fmt::Debug for ApiMsg {
@@ -942,7 +1142,13 @@ impl fmt::Debug for ApiMsg {
             ApiMsg::UpdateDocuments(..) => "ApiMsg::UpdateDocuments",
             ApiMsg::MemoryPressure => "ApiMsg::MemoryPressure",
             ApiMsg::ReportMemory(..) => "ApiMsg::ReportMemory",
+            ApiMsg::GetFrameComplexity(..) => "ApiMsg::GetFrameComplexity",
+            ApiMsg::GetMaxClipChainDepth(..) => "ApiMsg::GetMaxClipChainDepth",
+            ApiMsg::ExportGlyphCache(..) => "ApiMsg::ExportGlyphCache",
+            ApiMsg::ListDocuments(..) => "ApiMsg::ListDocuments",
             ApiMsg::DebugCommand(..) => "ApiMsg::DebugCommand",
+            ApiMsg::FrameRetired => "ApiMsg::FrameRetired",
+            ApiMsg::ShadersReady => "ApiMsg::ShadersReady",
             ApiMsg::SceneBuilderResult(..) => "ApiMsg::SceneBuilderResult",
         })
     }
@@ -956,6 +1162,7 @@ pub struct RenderApiSender {
     api_sender: Sender<ApiMsg>,
     scene_sender: Sender<SceneBuilderRequest>,
     low_priority_scene_sender: Sender<SceneBuilderRequest>,
+    scene_build_cancellation: SceneBuildCancellation,
     blob_image_handler: Option<Box<dyn BlobImageHandler>>,
     fonts: SharedFontResources,
 }
@@ -966,6 +1173,7 @@ impl RenderApiSender {
         api_sender: Sender<ApiMsg>,
         scene_sender: Sender<SceneBuilderRequest>,
         low_priority_scene_sender: Sender<SceneBuilderRequest>,
+        scene_build_cancellation: SceneBuildCancellation,
         blob_image_handler: Option<Box<dyn BlobImageHandler>>,
         fonts: SharedFontResources,
     ) -> Self {
@@ -973,6 +1181,7 @@ impl RenderApiSender {
             api_sender,
             scene_sender,
             low_priority_scene_sender,
+            scene_build_cancellation,
             blob_image_handler,
             fonts,
         }
@@ -988,6 +1197,7 @@ impl RenderApiSender {
             api_sender: self.api_sender.clone(),
             scene_sender: self.scene_sender.clone(),
             low_priority_scene_sender: self.low_priority_scene_sender.clone(),
+            scene_build_cancellation: self.scene_build_cancellation.clone(),
             namespace_id,
             next_id: Cell::new(ResourceId(0)),
             resources: ApiResources::new(
@@ -1009,6 +1219,7 @@ impl RenderApiSender {
             api_sender: self.api_sender.clone(),
             scene_sender: self.scene_sender.clone(),
             low_priority_scene_sender: self.low_priority_scene_sender.clone(),
+            scene_build_cancellation: self.scene_build_cancellation.clone(),
             namespace_id,
             next_id: Cell::new(ResourceId(0)),
             resources: ApiResources::new(
@@ -1024,6 +1235,7 @@ pub struct RenderApi {
     api_sender: Sender<ApiMsg>,
     scene_sender: Sender<SceneBuilderRequest>,
     low_priority_scene_sender: Sender<SceneBuilderRequest>,
+    scene_build_cancellation: SceneBuildCancellation,
     namespace_id: IdNamespace,
     next_id: Cell<ResourceId>,
     resources: ApiResources,
@@ -1041,6 +1253,7 @@ impl RenderApi {
             self.api_sender.clone(),
             self.scene_sender.clone(),
             self.low_priority_scene_sender.clone(),
+            self.scene_build_cancellation.clone(),
             self.resources.blob_image_handler.as_ref().map(|handler| handler.create_similar()),
             self.resources.get_fonts(),
         )
@@ -1142,6 +1355,36 @@ impl RenderApi {
         BlobImageKey(self.generate_image_key())
     }
 
+    /// Registers a gradient that display items can reference with
+    /// `DisplayListBuilder::push_gradient_ref`.
+    ///
+    /// The gradient is rendered once per item size into the render task cache
+    /// and shared by all the items of that size referencing it, so it is never
+    /// stretched beyond the resolution it was rendered at. The cached rendering
+    /// is evicted like any other render task cache entry when no item has used
+    /// it for a few frames, while the template itself lives until
+    /// `delete_gradient_template` is called or the namespace is cleared.
+    pub fn create_gradient_template(
+        &self,
+        stops: Vec<GradientStop>,
+        kind: GradientTemplateKind,
+    ) -> GradientTemplateKey {
+        let key = GradientTemplateKey::new(self.namespace_id, self.next_unique_id());
+        self.scene_sender.send(
+            SceneBuilderRequest::AddGradientTemplate(key, GradientTemplate { kind, stops })
+        ).unwrap();
+
+        key
+    }
+
+    /// Deletes a gradient template. Items still referencing it are skipped
+    /// the next time their scene is built.
+    pub fn delete_gradient_template(&self, key: GradientTemplateKey) {
+        self.scene_sender.send(
+            SceneBuilderRequest::DeleteGradientTemplate(key)
+        ).unwrap();
+    }
+
     /// A Gecko-specific notification mechanism to get some code executed on the
     /// `Renderer`'s thread, mostly replaced by `NotificationHandler`. You should
     /// probably use the latter instead.
@@ -1163,6 +1406,37 @@ impl RenderApi {
         *rx.recv().unwrap()
     }
 
+    /// Synchronously queries the complexity of the most recently built frame
+    /// of a document.
+    ///
+    /// This can be used to decide whether to simplify content before it is
+    /// rendered. All fields are zero if no frame has been built yet.
+    pub fn get_frame_complexity(&self, document_id: DocumentId) -> FrameComplexity {
+        let (tx, rx) = single_msg_channel();
+        self.api_sender.send(ApiMsg::GetFrameComplexity(document_id, tx)).unwrap();
+        rx.recv().unwrap()
+    }
+
+    /// Synchronously queries the largest number of clips in the clip-chain
+    /// of any primitive in the most recently built scene of a document,
+    /// including clips inherited from enclosing stacking contexts and
+    /// iframes. Deeply nested clips are expensive to evaluate, so this can
+    /// be used to find over-nested content. Zero if no scene has been built.
+    pub fn max_clip_chain_depth(&self, document_id: DocumentId) -> usize {
+        let (tx, rx) = single_msg_channel();
+        self.api_sender.send(ApiMsg::GetMaxClipChainDepth(document_id, tx)).unwrap();
+        rx.recv().unwrap()
+    }
+
+    /// Synchronously lists the documents that have been added and not yet
+    /// deleted, with their sizes and whether a frame is waiting to be
+    /// rendered. The order of the list is unspecified.
+    pub fn list_documents(&self) -> Vec<DocumentInfo> {
+        let (tx, rx) = single_msg_channel();
+        self.api_sender.send(ApiMsg::ListDocuments(tx)).unwrap();
+        rx.recv().unwrap()
+    }
+
     /// Update debugging flags.
     pub fn set_debug_flags(&mut self, flags: DebugFlags) {
         self.resources.set_debug_flags(flags);
@@ -1227,6 +1501,9 @@ impl RenderApi {
             invalidate_rendered_frame: false,
             use_scene_builder_thread: false,
             low_priority: false,
+            supersedes_pending: false,
+            trace_id: None,
+            scene_build_seq: 0,
             blob_rasterizer: None,
             blob_requests: Vec::new(),
             rasterized_blobs: Vec::new(),
@@ -1257,6 +1534,11 @@ impl RenderApi {
         }
 
         if transaction.use_scene_builder_thread {
+            transaction.scene_build_seq = self.scene_build_cancellation.next_seq(
+                document_id,
+                transaction.supersedes_pending,
+            );
+
             let sender = if transaction.low_priority {
                 &mut self.low_priority_scene_sender
             } else {
@@ -1300,6 +1582,15 @@ impl RenderApi {
         HitTesterRequest { rx }
     }
 
+    /// Set how scroll offsets that fall outside of a scroll frame's scrollable
+    /// range are handled for the given document. See `ScrollConfig`.
+    pub fn set_scroll_config(&self, document_id: DocumentId, config: ScrollConfig) {
+        self.send_frame_msg(
+            document_id,
+            FrameMsg::SetScrollConfig(config),
+        );
+    }
+
     // Some internal scheduling magic that leaked into the API.
     // Buckle up and see APZUpdater.cpp for more info about what this is about.
     #[doc(hidden)]
@@ -1311,9 +1602,32 @@ impl RenderApi {
     /// ensures that any transactions (including ones deferred to the scene
     /// builder thread) have been processed.
     pub fn flush_scene_builder(&self) {
+        self.send_flush().recv().unwrap(); // Block until done.
+    }
+
+    /// Send a flush request through the scene builder threads, returning the
+    /// channel that receives the reply once the request has been handled.
+    fn send_flush(&self) -> Receiver<()> {
         let (tx, rx) = single_msg_channel();
         self.low_priority_scene_sender.send(SceneBuilderRequest::Flush(tx)).unwrap();
-        rx.recv().unwrap(); // Block until done.
+        rx
+    }
+
+    /// Block until every transaction sent before this call has been built by the
+    /// scene builder, processed by the render backend and the resulting frames
+    /// published to the renderer. Unlike waiting for a notifier wake-up, this
+    /// doesn't depend on whether a given transaction generates a frame.
+    ///
+    /// Frames deferred because `WebRenderOptions::max_frames_in_flight` frames
+    /// are in flight are waited for too, so they are only published once the
+    /// renderer renders the frames ahead of them.
+    ///
+    /// Returns an error if the pipeline didn't become idle within `timeout`.
+    pub fn flush_and_wait(&self, timeout: Duration) -> Result<(), FlushTimeout> {
+        // The flush request travels through the low priority and regular scene
+        // builder threads before reaching the render backend, which replies once
+        // all messages queued ahead of it have been handled.
+        self.send_flush().recv_timeout(timeout).map_err(|_| FlushTimeout)
     }
 
     /// Save a capture of the current frame state for debugging.
@@ -1353,6 +1667,16 @@ impl RenderApi {
 
     /// Update the state of builtin debugging facilities.
     pub fn send_debug_cmd(&self, cmd: DebugCommand) {
+        // Low priority transactions reach the low priority scene builder
+        // without going through the render backend, so it is sent there
+        // directly.
+        if let DebugCommand::SimulateLongLowPrioritySceneBuild(time_ms) = cmd {
+            let _ = self.low_priority_scene_sender.send(
+                SceneBuilderRequest::SimulateLongLowPrioritySceneBuild(time_ms)
+            );
+            return;
+        }
+
         let msg = ApiMsg::DebugCommand(cmd);
         self.send_message(msg);
     }
@@ -1428,3 +1752,26 @@ pub struct MemoryReport {
     pub render_texture_hosts: usize,
     pub upload_staging_textures: usize,
 }
+
+impl MemoryReport {
+    /// Raise each field to the value of the same field in `other`, if it is
+    /// larger. The interning report is left unchanged.
+    pub fn max_assign(&mut self, other: &MemoryReport) {
+        macro_rules! max_fields {
+            ($($field:ident),*) => {
+                $( self.$field = self.$field.max(other.$field); )*
+            };
+        }
+
+        max_fields!(
+            clip_stores, gpu_cache_metadata, gpu_cache_cpu_mirror, hit_testers,
+            fonts, weak_fonts, images, rasterized_blobs, shader_cache,
+            display_list, upload_staging_memory, swgl, frame_allocator,
+            render_tasks, gpu_cache_textures, vertex_data_textures,
+            render_target_textures, picture_tile_textures, atlas_textures,
+            standalone_textures, texture_cache_structures, depth_target_textures,
+            texture_upload_pbos, swap_chain, render_texture_hosts,
+            upload_staging_textures
+        );
+    }
+}
@@ -9,10 +9,12 @@
 //! how these two pieces interact.
 
 use api::{DebugFlags, Parameter, BoolParameter, PrimitiveFlags, MinimapData};
-use api::{DocumentId, ExternalScrollId, HitTestResult};
-use api::{IdNamespace, PipelineId, RenderNotifier, SampledScrollOffset};
+use api::{BlobImageRequest, BlobImageResult, DocumentId, ExternalScrollId, HitTestResult};
+use api::{IdNamespace, PipelineId, RenderNotifier, SampledScrollOffset, ScrollConfig};
 use api::{NotificationRequest, Checkpoint, QualitySettings};
-use api::{FramePublishId, PrimitiveKeyKind, RenderReasons};
+#[cfg(feature = "replay")]
+use api::{GradientTemplate, GradientTemplateKey};
+use api::{FramePublishId, PrimitiveKeyKind, PropertyBinding, ReferenceFrameKind, RenderReasons, SpatialId};
 use api::units::*;
 use api::channel::{single_msg_channel, Sender, Receiver};
 use crate::AsyncPropertySampler;
@@ -21,7 +23,7 @@ use crate::box_shadow::BoxShadow;
 use crate::render_api::CaptureBits;
 #[cfg(feature = "replay")]
 use crate::render_api::CapturedDocument;
-use crate::render_api::{MemoryReport, TransactionMsg, ResourceUpdate, ApiMsg, FrameMsg, ClearCache, DebugCommand};
+use crate::render_api::{DocumentInfo, FrameComplexity, MemoryReport, TransactionMsg, ResourceUpdate, ApiMsg, FrameMsg, ClearCache, DebugCommand};
 use crate::clip::{ClipIntern, PolygonIntern, ClipStoreScratchBuffer};
 use crate::filterdata::FilterDataIntern;
 #[cfg(any(feature = "capture", feature = "replay"))]
@@ -34,7 +36,7 @@ use crate::hit_test::{HitTest, HitTester, SharedHitTester};
 use crate::intern::DataStore;
 #[cfg(any(feature = "capture", feature = "replay"))]
 use crate::internal_types::{DebugOutput};
-use crate::internal_types::{FastHashMap, RenderedDocument, ResultMsg, FrameId, FrameStamp};
+use crate::internal_types::{FastHashMap, FramesInFlight, RenderedDocument, ResultMsg, FrameId, FrameStamp};
 use malloc_size_of::{MallocSizeOf, MallocSizeOfOps};
 use crate::picture::{PictureScratchBuffer, SliceId, TileCacheInstance, TileCacheParams, SurfaceInfo, RasterConfig};
 use crate::picture::{PicturePrimitive};
@@ -53,7 +55,8 @@ use crate::resource_cache::PlainResources;
 use crate::scene::Scene;
 use crate::scene::{BuiltScene, SceneProperties};
 use crate::scene_builder_thread::*;
-use crate::spatial_tree::SpatialTree;
+use crate::spatial_node::SpatialNodeType;
+use crate::spatial_tree::{SpatialNodeIndex, SpatialTree};
 #[cfg(feature = "replay")]
 use crate::spatial_tree::SceneSpatialTree;
 use crate::telemetry::Telemetry;
@@ -64,7 +67,7 @@ use serde::Deserialize;
 #[cfg(feature = "replay")]
 use std::collections::hash_map::Entry::{Occupied, Vacant};
 use std::sync::Arc;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::{mem, u32};
 #[cfg(feature = "capture")]
 use std::path::PathBuf;
@@ -228,6 +231,26 @@ impl DataStores {
         }
     }
 
+    /// Returns true if this primitive should have its rect snapped to
+    /// device pixels during frame building. Text runs are excluded since
+    /// glyphs are snapped separately.
+    pub fn prim_snaps_to_device_pixels(
+        &self,
+        prim_instance: &PrimitiveInstance,
+    ) -> bool {
+        match prim_instance.kind {
+            PrimitiveInstanceKind::Picture { .. } |
+            PrimitiveInstanceKind::TextRun { .. } => {
+                false
+            }
+            _ => {
+                let flags = self.as_common_data(prim_instance).flags;
+                flags.contains(PrimitiveFlags::SNAP_TO_DEVICE_PIXELS) &&
+                    !flags.contains(PrimitiveFlags::ANTIALISED)
+            }
+        }
+    }
+
     pub fn as_common_data(
         &self,
         prim_inst: &PrimitiveInstance
@@ -375,8 +398,19 @@ struct Document {
     /// Retained frame-building version of the spatial tree
     spatial_tree: SpatialTree,
 
+    /// Reference frames whose transform was replaced by
+    /// `Transaction::update_spatial_nodes`, with the transform from the display
+    /// list, which is restored when a new scene is swapped in.
+    spatial_node_overrides: FastHashMap<SpatialNodeIndex, LayoutTransform>,
+
     minimap_data: FastHashMap<ExternalScrollId, MinimapData>,
 
+    /// Trace ids of the transactions applied since the last built frame.
+    trace_ids: Vec<u64>,
+
+    /// How scroll offsets outside of the scrollable range are handled.
+    scroll_config: ScrollConfig,
+
     /// Contains various vecs of data that is used only during frame building,
     /// where we want to recycle the memory each new display list, to avoid constantly
     /// re-allocating and moving memory around.
@@ -394,6 +428,27 @@ struct Document {
 
     profile: TransactionProfile,
     frame_stats: Option<FullFrameStats>,
+
+    /// Complexity of the last frame built for this document.
+    frame_complexity: FrameComplexity,
+
+    /// The publish id of the latest frame sent to the renderer, or 0 once
+    /// the renderer has rendered it. Tagging the flag with the frame means
+    /// rendering an older frame can't clear it while a newer one is queued.
+    frame_pending: Arc<AtomicU64>,
+
+    /// The reasons for a frame build that was deferred because the maximum
+    /// number of frames was in flight. It is built once a frame is retired.
+    deferred_frame: Option<RenderReasons>,
+    /// Frame built notifications waiting for the deferred frame.
+    deferred_notifications: Vec<NotificationRequest>,
+
+    /// When the oldest frame request that wasn't published yet was made, in
+    /// `precise_time_ns`.
+    frame_requested_time: Option<u64>,
+
+    /// Set when the next frame build has to redraw every picture cache tile.
+    invalidate_all: bool,
 }
 
 impl Document {
@@ -422,7 +477,10 @@ impl Document {
             has_built_scene: false,
             data_stores: DataStores::default(),
             spatial_tree: SpatialTree::new(),
+            spatial_node_overrides: FastHashMap::default(),
             minimap_data: FastHashMap::default(),
+            trace_ids: Vec::new(),
+            scroll_config: ScrollConfig::default(),
             scratch: ScratchBuffer::default(),
             #[cfg(feature = "replay")]
             loaded_scene: Scene::new(),
@@ -431,6 +489,12 @@ impl Document {
             profile: TransactionProfile::new(),
             rg_builder: RenderTaskGraphBuilder::new(),
             frame_stats: None,
+            frame_complexity: FrameComplexity::default(),
+            frame_pending: Arc::new(AtomicU64::new(FramePublishId::INVALID.0)),
+            deferred_frame: None,
+            deferred_notifications: Vec::new(),
+            frame_requested_time: None,
+            invalidate_all: false,
         }
     }
 
@@ -489,6 +553,12 @@ impl Document {
             FrameMsg::AppendDynamicTransformProperties(property_bindings) => {
                 self.dynamic_properties.add_transforms(property_bindings);
             }
+            FrameMsg::UpdateSpatialNodes(transforms) => {
+                if self.update_spatial_nodes(transforms) {
+                    self.hit_tester_is_valid = false;
+                    self.frame_is_valid = false;
+                }
+            }
             FrameMsg::SetIsTransformAsyncZooming(is_zooming, animation_id) => {
                 if let Some(node_index) = self.spatial_tree.find_spatial_node_by_anim_id(animation_id) {
                     let node = self.spatial_tree.get_spatial_node_mut(node_index);
@@ -502,6 +572,9 @@ impl Document {
             FrameMsg::SetMinimapData(id, minimap_data) => {
               self.minimap_data.insert(id, minimap_data);
             }
+            FrameMsg::SetScrollConfig(config) => {
+                self.scroll_config = config;
+            }
         }
 
         DocumentOps::nop()
@@ -524,6 +597,9 @@ impl Document {
         assert!(self.stamp.frame_id() != FrameId::INVALID,
                 "First frame increment must happen before build_frame()");
 
+        let force_invalidation = self.scene.config.force_invalidation;
+        self.scene.config.force_invalidation |= mem::take(&mut self.invalidate_all);
+
         let frame = {
             let frame = self.frame_builder.build(
                 &mut self.scene,
@@ -549,8 +625,10 @@ impl Document {
             frame
         };
 
+        self.scene.config.force_invalidation = force_invalidation;
         self.frame_is_valid = true;
         self.dirty_rects_are_valid = true;
+        self.frame_complexity = frame.complexity;
 
         self.has_built_scene = false;
 
@@ -569,6 +647,10 @@ impl Document {
             profile: self.profile.take_and_reset(),
             frame_stats: frame_stats,
             render_reasons,
+            trace_ids: mem::take(&mut self.trace_ids),
+            frame_in_flight: None,
+            frame_pending: None,
+            frame_requested_time: None,
         }
     }
 
@@ -596,7 +678,65 @@ impl Document {
         id: ExternalScrollId,
         offsets: Vec<SampledScrollOffset>,
     ) -> bool {
-        self.spatial_tree.set_scroll_offsets(id, offsets)
+        self.spatial_tree.set_scroll_offsets(id, offsets, &self.scroll_config)
+    }
+
+    /// Replace the transforms of reference frames of the current scene.
+    /// Returns true if any of them changed.
+    fn update_spatial_nodes(&mut self, transforms: Vec<(SpatialId, LayoutTransform)>) -> bool {
+        let mut changed = false;
+
+        for (id, transform) in transforms {
+            let index = match self.scene.reference_frames.get(&id) {
+                Some(index) => *index,
+                None => {
+                    warn!("Unknown reference frame {:?}", id);
+                    continue;
+                }
+            };
+
+            let info = match self.spatial_tree.get_spatial_node_mut(index).node_type {
+                SpatialNodeType::ReferenceFrame(ref mut info) => info,
+                _ => unreachable!(),
+            };
+            let current = match info.source_transform {
+                PropertyBinding::Value(transform) => transform,
+                PropertyBinding::Binding(..) => {
+                    warn!("Reference frame {:?} has an animated transform", id);
+                    continue;
+                }
+            };
+
+            // The scene was built assuming that axis-aligned reference frames
+            // don't establish a new coordinate system.
+            let original = self.spatial_node_overrides.get(&index).cloned().unwrap_or(current);
+            let is_axis_aligned = match info.kind {
+                ReferenceFrameKind::Transform { is_2d_scale_translation: true, .. } => true,
+                _ => original.is_2d_scale_translation(),
+            };
+            if is_axis_aligned && !transform.is_2d_scale_translation() {
+                warn!("Reference frame {:?} must keep an axis-aligned transform", id);
+                continue;
+            }
+
+            if transform != current {
+                info.source_transform = PropertyBinding::Value(transform);
+                self.spatial_node_overrides.entry(index).or_insert(original);
+                changed = true;
+            }
+        }
+
+        changed
+    }
+
+    /// Put back the transforms from the display list, before the spatial tree
+    /// of a new scene is applied.
+    fn restore_spatial_nodes(&mut self) {
+        for (index, transform) in self.spatial_node_overrides.drain() {
+            if let SpatialNodeType::ReferenceFrame(ref mut info) = self.spatial_tree.get_spatial_node_mut(index).node_type {
+                info.source_transform = PropertyBinding::Value(transform);
+            }
+        }
     }
 
     /// Update the state of tile caches when a new scene is being swapped in to
@@ -739,6 +879,13 @@ pub struct RenderBackend {
 
     /// The id of the latest PublishDocument
     frame_publish_id: FramePublishId,
+
+    /// Shared with the renderer, to wait for frames to be rendered before
+    /// publishing more of them.
+    frames_in_flight: Arc<FramesInFlight>,
+
+    /// Flush requests waiting for the deferred frames to be published.
+    pending_flushes: Vec<Sender<()>>,
 }
 
 impl RenderBackend {
@@ -753,6 +900,7 @@ impl RenderBackend {
         size_of_ops: Option<MallocSizeOfOps>,
         debug_flags: DebugFlags,
         namespace_alloc_by_client: bool,
+        frames_in_flight: Arc<FramesInFlight>,
     ) -> RenderBackend {
         RenderBackend {
             api_rx,
@@ -775,6 +923,8 @@ impl RenderBackend {
             loaded_resource_sequence_id: 0,
             tile_caches: FastHashMap::default(),
             frame_publish_id: FramePublishId::first(),
+            frames_in_flight,
+            pending_flushes: Vec::new(),
         }
     }
 
@@ -864,6 +1014,16 @@ impl RenderBackend {
 
         let mut built_frame = false;
         for mut txn in txns.drain(..) {
+            if txn.is_resource_only() && !self.requires_frame_build() {
+                self.update_resources(
+                    txn.document_id,
+                    txn.resource_updates.take(),
+                    txn.rasterized_blobs.take(),
+                    &mut txn.profile,
+                );
+                continue;
+            }
+
            let has_built_scene = txn.built_scene.is_some();
 
             if let Some(doc) = self.documents.get_mut(&txn.document_id) {
@@ -885,6 +1045,10 @@ impl RenderBackend {
                     None
                 };
 
+                if txn.built_scene.is_some() {
+                    doc.restore_spatial_nodes();
+                }
+
                 if let Some(updates) = txn.spatial_tree_updates.take() {
                     doc.spatial_tree.apply_updates(updates);
                 }
@@ -959,6 +1123,7 @@ impl RenderBackend {
                 frame_counter,
                 has_built_scene,
                 None,
+                txn.trace_id,
             );
         }
 
@@ -1018,6 +1183,32 @@ impl RenderBackend {
             ApiMsg::ReportMemory(tx) => {
                 self.report_memory(tx);
             }
+            ApiMsg::GetFrameComplexity(document_id, tx) => {
+                let complexity = self.documents
+                    .get(&document_id)
+                    .map_or(FrameComplexity::default(), |doc| doc.frame_complexity);
+                tx.send(complexity).unwrap();
+            }
+            ApiMsg::GetMaxClipChainDepth(document_id, tx) => {
+                let depth = self.documents
+                    .get(&document_id)
+                    .map_or(0, |doc| doc.scene.max_clip_chain_depth);
+                tx.send(depth).unwrap();
+            }
+            ApiMsg::ExportGlyphCache(tx) => {
+                tx.send(self.resource_cache.export_glyph_cache()).unwrap();
+            }
+            ApiMsg::ListDocuments(tx) => {
+                let documents = self.documents
+                    .values()
+                    .map(|doc| DocumentInfo {
+                        id: doc.id,
+                        size: doc.view.scene.device_rect.size(),
+                        has_pending_frame: doc.frame_pending.load(Ordering::Acquire) != FramePublishId::INVALID.0,
+                    })
+                    .collect();
+                tx.send(documents).unwrap();
+            }
             ApiMsg::DebugCommand(option) => {
                 let msg = match option {
                     DebugCommand::SetPictureTileSize(tile_size) => {
@@ -1032,12 +1223,87 @@ impl RenderBackend {
 
                         return RenderBackendStatus::Continue;
                     }
+                    DebugCommand::SetMaxDepthIds(max_depth_ids) => {
+                        let device_max = self.frame_config.max_depth_ids;
+                        let max_depth_ids = max_depth_ids.max(1).min(device_max as u32) as i32;
+                        info!("Depth id budget set to {} (device max {})", max_depth_ids, device_max);
+                        self.frame_config.max_depth_ids_override = Some(max_depth_ids);
+                        self.update_frame_builder_config();
+
+                        return RenderBackendStatus::Continue;
+                    }
+                    DebugCommand::SetSrgbRenderTargets(enabled) => {
+                        self.frame_config.use_srgb_render_targets = enabled;
+                        self.update_frame_builder_config();
+
+                        return RenderBackendStatus::Continue;
+                    }
+                    DebugCommand::SetCullingMargin(margin) => {
+                        self.frame_config.culling_margin = margin.max(0.0);
+                        self.update_frame_builder_config();
+
+                        return RenderBackendStatus::Continue;
+                    }
+                    DebugCommand::SetMaxBlurRasterSize(size) => {
+                        self.frame_config.max_blur_raster_size = size;
+                        self.update_frame_builder_config();
+
+                        return RenderBackendStatus::Continue;
+                    }
+                    DebugCommand::SetRenderScale(scale) => {
+                        self.frame_config.render_scale = scale;
+                        self.update_frame_builder_config();
+
+                        return RenderBackendStatus::Continue;
+                    }
+                    DebugCommand::SetEdgeAaFeather(feather) => {
+                        self.frame_config.edge_aa_feather = feather;
+                        self.update_frame_builder_config();
+
+                        return RenderBackendStatus::Continue;
+                    }
+                    DebugCommand::SetShowClipBounds(show) => {
+                        for (_, doc) in &mut self.documents {
+                            doc.scene.config.show_clip_bounds = show;
+                            doc.frame_is_valid = false;
+                        }
+
+                        self.frame_config.show_clip_bounds = show;
+                        self.update_frame_builder_config();
+
+                        return RenderBackendStatus::Continue;
+                    }
+                    DebugCommand::SetCompositorMaxUpdateRects(max_update_rects) => {
+                        if let CompositorKind::Native { ref mut capabilities, .. } = self.default_compositor_kind {
+                            capabilities.max_update_rects = max_update_rects;
+                        }
+
+                        // Documents only use the default compositor kind while
+                        // the native compositor is enabled.
+                        if let CompositorKind::Native { .. } = self.frame_config.compositor_kind {
+                            let compositor_kind = self.default_compositor_kind;
+                            for (_, doc) in &mut self.documents {
+                                doc.scene.config.compositor_kind = compositor_kind;
+                                doc.frame_is_valid = false;
+                            }
+
+                            self.frame_config.compositor_kind = compositor_kind;
+                            self.update_frame_builder_config();
+                        }
+
+                        return RenderBackendStatus::Continue;
+                    }
                     #[cfg(feature = "capture")]
                     DebugCommand::SaveCapture(root, bits) => {
                         let output = self.save_capture(root, bits);
                         ResultMsg::DebugOutput(output)
                     },
                     #[cfg(feature = "capture")]
+                    DebugCommand::ExportGlyphAtlas(root) => {
+                        let entries = self.resource_cache.glyph_atlas_entries();
+                        ResultMsg::DebugOutput(DebugOutput::ExportGlyphAtlas(root, entries))
+                    },
+                    #[cfg(feature = "capture")]
                     DebugCommand::StartCaptureSequence(root, bits) => {
                         self.start_capture_sequence(root, bits);
                         return RenderBackendStatus::Continue;
@@ -1064,6 +1330,10 @@ impl RenderBackend {
                             let captured = CapturedDocument {
                                 document_id: *id,
                                 root_pipeline_id: doc.loaded_scene.root_pipeline_id,
+                                display_list_metadata: doc.loaded_scene.pipelines
+                                    .iter()
+                                    .map(|(id, pipeline)| (*id, pipeline.display_list.metadata()))
+                                    .collect(),
                             };
                             tx.send(captured).unwrap();
                         }
@@ -1076,6 +1346,10 @@ impl RenderBackend {
                         self.resource_cache.clear(mask);
                         return RenderBackendStatus::Continue;
                     }
+                    DebugCommand::ReuploadTextureCache => {
+                        self.resource_cache.reupload_texture_cache();
+                        return RenderBackendStatus::Continue;
+                    }
                     DebugCommand::EnableNativeCompositor(enable) => {
                         // Default CompositorKind should be Native
                         if let CompositorKind::Draw { .. } = self.default_compositor_kind {
@@ -1150,11 +1424,105 @@ impl RenderBackend {
             ApiMsg::SceneBuilderResult(msg) => {
                 return self.process_scene_builder_result(msg, frame_counter);
             }
+            ApiMsg::FrameRetired => {
+                self.build_deferred_frames(frame_counter);
+            }
+            ApiMsg::ShadersReady => {
+                self.redraw_all_documents(frame_counter);
+            }
         }
 
         RenderBackendStatus::Continue
     }
 
+    /// Build the frames that were deferred while the maximum number of frames
+    /// was in flight. Documents still over the limit are deferred again.
+    fn build_deferred_frames(&mut self, frame_counter: &mut u32) {
+        let deferred_documents: Vec<DocumentId> = self.documents
+            .iter()
+            .filter(|(_, doc)| doc.deferred_frame.is_some())
+            .map(|(document_id, _)| *document_id)
+            .collect();
+
+        #[allow(unused_variables)]
+        let mut built_frame = false;
+        for document_id in deferred_documents {
+            let doc = self.documents.get_mut(&document_id).unwrap();
+            let render_reasons = doc.deferred_frame.take().unwrap();
+            let notifications = mem::take(&mut doc.deferred_notifications);
+            built_frame |= self.update_document(
+                document_id,
+                Vec::default(),
+                Vec::default(),
+                notifications,
+                true,
+                render_reasons,
+                None,
+                false,
+                frame_counter,
+                false,
+                None,
+                None,
+            );
+        }
+
+        if built_frame {
+            self.bookkeep_after_frames();
+        }
+        #[cfg(feature = "capture")]
+        match built_frame {
+            true => self.save_capture_sequence(),
+            _ => {},
+        }
+
+        self.send_pending_flushes();
+    }
+
+    /// Reply to the flush requests once no document has a deferred frame.
+    fn send_pending_flushes(&mut self) {
+        if self.documents.values().any(|doc| doc.deferred_frame.is_some()) {
+            return;
+        }
+        for tx in self.pending_flushes.drain(..) {
+            tx.send(()).ok();
+        }
+    }
+
+    /// Build new frames that redraw every document from scratch, once all
+    /// shaders are linked. Picture cache tiles and cached render tasks that
+    /// were drawn before then are missing whatever needed the shaders that
+    /// weren't ready.
+    fn redraw_all_documents(&mut self, frame_counter: &mut u32) {
+        self.resource_cache.clear(ClearCache::RENDER_TASKS);
+
+        let document_ids: Vec<DocumentId> = self.documents.keys().cloned().collect();
+
+        let mut built_frame = false;
+        for document_id in document_ids {
+            let doc = self.documents.get_mut(&document_id).unwrap();
+            doc.invalidate_all = true;
+            doc.frame_is_valid = false;
+            built_frame |= self.update_document(
+                document_id,
+                Vec::default(),
+                Vec::default(),
+                Vec::default(),
+                true,
+                RenderReasons::CLEAR_RESOURCES,
+                None,
+                false,
+                frame_counter,
+                false,
+                None,
+                None,
+            );
+        }
+
+        if built_frame {
+            self.bookkeep_after_frames();
+        }
+    }
+
     fn process_scene_builder_result(
         &mut self,
         msg: SceneBuilderResult,
@@ -1221,7 +1589,10 @@ impl RenderBackend {
                 request.sender.send(glyph_indices).unwrap();
             }
             SceneBuilderResult::FlushComplete(tx) => {
-                tx.send(()).ok();
+                // Frames that are deferred until a frame in flight is rendered
+                // haven't been published yet, so reply once they are.
+                self.pending_flushes.push(tx);
+                self.send_pending_flushes();
             }
             SceneBuilderResult::ExternalEvent(evt) => {
                 self.notifier.external_event(evt);
@@ -1229,9 +1600,11 @@ impl RenderBackend {
             SceneBuilderResult::ClearNamespace(id) => {
                 self.resource_cache.clear_namespace(id);
                 self.documents.retain(|doc_id, _doc| doc_id.namespace_id != id);
+                self.send_pending_flushes();
             }
             SceneBuilderResult::DeleteDocument(document_id) => {
                 self.documents.remove(&document_id);
+                self.send_pending_flushes();
             }
             SceneBuilderResult::SetParameter(param) => {
                 if let Parameter::Bool(BoolParameter::Multithreading, enabled) = param {
@@ -1283,6 +1656,16 @@ impl RenderBackend {
 
         let mut built_frame = false;
         for mut txn in txns {
+            if txn.is_resource_only() && !self.requires_frame_build() {
+                self.update_resources(
+                    txn.document_id,
+                    txn.resource_updates.take(),
+                    Vec::new(),
+                    &mut txn.profile,
+                );
+                continue;
+            }
+
             if txn.generate_frame.as_bool() {
                 txn.profile.end_time(profiler::API_SEND_TIME);
             }
@@ -1301,6 +1684,7 @@ impl RenderBackend {
                 frame_counter,
                 false,
                 txn.creation_time,
+                txn.trace_id,
             );
         }
         if built_frame {
@@ -1339,6 +1723,7 @@ impl RenderBackend {
                     false,
                     frame_counter,
                     false,
+                    None,
                     None);
             }
             #[cfg(feature = "capture")]
@@ -1349,6 +1734,59 @@ impl RenderBackend {
         }
     }
 
+    /// Applies the resource updates of a transaction that has no other effect,
+    /// without building a frame. Any resulting texture cache updates are sent
+    /// straight to the renderer.
+    fn update_resources(
+        &mut self,
+        document_id: DocumentId,
+        resource_updates: Vec<ResourceUpdate>,
+        rasterized_blobs: Vec<(BlobImageRequest, BlobImageResult)>,
+        profile: &mut TransactionProfile,
+    ) {
+        let doc = match self.documents.get_mut(&document_id) {
+            Some(doc) => doc,
+            None => return,
+        };
+        doc.profile.merge(profile);
+
+        // Updated images may be visible in the current frame, which then has
+        // to be rebuilt on the next frame request.
+        if !rasterized_blobs.is_empty() {
+            doc.frame_is_valid = false;
+        }
+        for update in &resource_updates {
+            match update {
+                ResourceUpdate::UpdateImage(..) |
+                ResourceUpdate::UpdateImageRegions(..) |
+                ResourceUpdate::UpdateBlobImage(..) |
+                ResourceUpdate::SetBlobImageVisibleArea(..) => {
+                    doc.frame_is_valid = false;
+                }
+                _ => {}
+            }
+        }
+
+        self.resource_cache.add_rasterized_blob_images(
+            rasterized_blobs,
+            &mut doc.profile,
+        );
+        self.resource_cache.post_scene_building_update(
+            resource_updates,
+            &mut doc.profile,
+        );
+
+        let resource_updates = self.resource_cache.pending_updates();
+        if !resource_updates.is_nop() {
+            let msg = ResultMsg::UpdateResources {
+                resource_updates,
+                memory_pressure: false,
+            };
+            self.result_tx.send(msg).unwrap();
+            self.notifier.wake_up(false);
+        }
+    }
+
     fn update_document(
         &mut self,
         document_id: DocumentId,
@@ -1361,7 +1799,8 @@ impl RenderBackend {
         invalidate_rendered_frame: bool,
         frame_counter: &mut u32,
         has_built_scene: bool,
-        start_time: Option<u64>
+        start_time: Option<u64>,
+        trace_id: Option<u64>,
     ) -> bool {
         let update_doc_start = precise_time_ns();
 
@@ -1370,6 +1809,15 @@ impl RenderBackend {
         let requires_frame_build = self.requires_frame_build();
         let doc = self.documents.get_mut(&document_id).unwrap();
 
+        doc.trace_ids.extend(trace_id);
+
+        if requested_frame {
+            let requested_time = start_time.unwrap_or_else(precise_time_ns);
+            doc.frame_requested_time = Some(
+                doc.frame_requested_time.map_or(requested_time, |time| time.min(requested_time))
+            );
+        }
+
         // If we have a sampler, get more frame ops from it and add them
         // to the transaction. This is a hook to allow the WR user code to
         // fiddle with things after a potentially long scene build, but just
@@ -1392,7 +1840,7 @@ impl RenderBackend {
         }
 
         for update in &resource_updates {
-            if let ResourceUpdate::UpdateImage(..) = update {
+            if let ResourceUpdate::UpdateImage(..) | ResourceUpdate::UpdateImageRegions(..) = update {
                 doc.frame_is_valid = false;
             }
         }
@@ -1433,6 +1881,34 @@ impl RenderBackend {
             }
         }
 
+        // If the renderer hasn't caught up with the frames in flight, defer
+        // building this frame rather than waiting for it, so that the render
+        // backend keeps handling API messages. Resource updates stay pending
+        // in the resource cache until the frame is built.
+        let mut frame_in_flight = None;
+        if build_frame {
+            frame_in_flight = self.frames_in_flight.try_acquire();
+            if frame_in_flight.is_none() {
+                doc.deferred_frame = Some(doc.deferred_frame.unwrap_or(RenderReasons::empty()) | render_reasons);
+                drain_filter(
+                    &mut notifications,
+                    |n| { n.when() == Checkpoint::FrameBuilt },
+                    |n| { doc.deferred_notifications.push(n); },
+                );
+                if !notifications.is_empty() {
+                    self.result_tx.send(ResultMsg::AppendNotificationRequests(notifications)).unwrap();
+                }
+                if !doc.hit_tester_is_valid {
+                    doc.rebuild_hit_tester();
+                }
+                return false;
+            }
+
+            // This frame supersedes any deferred one.
+            doc.deferred_frame = None;
+            notifications.extend(doc.deferred_notifications.drain(..));
+        }
+
         if build_frame {
             if start_time.is_some() {
               Telemetry::record_time_to_frame_build(Duration::from_nanos(precise_time_ns() - start_time.unwrap()));
@@ -1521,7 +1997,11 @@ impl RenderBackend {
             self.result_tx.send(msg).unwrap();
 
             // Publish the frame
+            rendered_document.frame_in_flight = frame_in_flight;
+            rendered_document.frame_requested_time = doc.frame_requested_time.take();
             self.frame_publish_id.advance();
+            doc.frame_pending.store(self.frame_publish_id.0, Ordering::Release);
+            rendered_document.frame_pending = Some((Arc::clone(&doc.frame_pending), self.frame_publish_id));
             let msg = ResultMsg::PublishDocument(
                 self.frame_publish_id,
                 document_id,
@@ -1536,6 +2016,7 @@ impl RenderBackend {
             // new_frame_ready callback below) has the right flags.
             let msg = ResultMsg::PublishPipelineInfo(doc.updated_pipeline_info());
             self.result_tx.send(msg).unwrap();
+            doc.frame_requested_time = None;
         }
 
         drain_filter(
@@ -1843,6 +2324,15 @@ impl RenderBackend {
 
         self.frame_config = backend.frame_config;
 
+        // The scene builder needs the gradient templates before it rebuilds
+        // scenes that reference them.
+        let gradient_templates = config
+            .deserialize_for_scene::<FastHashMap<GradientTemplateKey, GradientTemplate>, _>("gradient-templates")
+            .unwrap_or_default();
+        for (key, template) in gradient_templates {
+            self.send_backend_message(SceneBuilderRequest::AddGradientTemplate(key, template));
+        }
+
         let mut scenes_to_build = Vec::new();
 
         for (id, view) in backend.documents {
@@ -1906,12 +2396,20 @@ impl RenderBackend {
                         scratch: ScratchBuffer::default(),
                         spatial_tree: frame_spatial_tree,
                         minimap_data: FastHashMap::default(),
+                        trace_ids: Vec::new(),
+                        scroll_config: ScrollConfig::default(),
                         loaded_scene: scene.clone(),
                         prev_composite_descriptor: CompositeDescriptor::empty(),
                         dirty_rects_are_valid: false,
                         profile: TransactionProfile::new(),
                         rg_builder: RenderTaskGraphBuilder::new(),
                         frame_stats: None,
+                        frame_complexity: FrameComplexity::default(),
+                        frame_pending: Arc::new(AtomicU64::new(FramePublishId::INVALID.0)),
+                        deferred_frame: None,
+                        deferred_notifications: Vec::new(),
+                        frame_requested_time: None,
+                        invalidate_all: false,
                     };
                     entry.insert(doc);
                 }
@@ -1934,7 +2432,11 @@ impl RenderBackend {
                             frame,
                             profile: TransactionProfile::new(),
                             render_reasons: RenderReasons::empty(),
+                            trace_ids: Vec::new(),
                             frame_stats: None,
+                            frame_in_flight: None,
+                            frame_pending: None,
+                            frame_requested_time: None,
                         },
                         self.resource_cache.pending_updates(),
                     );
@@ -4,7 +4,7 @@
 
 
 use api::units::*;
-use api::{ColorF, LineOrientation, BorderStyle};
+use api::{ClipAaQuality, ColorF, LineOrientation, BorderStyle};
 use crate::batch::{AlphaBatchBuilder, AlphaBatchContainer, BatchTextures};
 use crate::batch::{ClipBatcher, BatchBuilder, INVALID_SEGMENT_INDEX, ClipMaskInstanceList};
 use crate::command_buffer::{CommandBufferList, QuadFlags};
@@ -56,6 +56,8 @@ pub struct RenderTargetContext<'a, 'rc> {
     pub use_advanced_blending: bool,
     pub break_advanced_blend_batches: bool,
     pub batch_lookback_count: usize,
+    pub max_depth_ids: i32,
+    pub clip_aa_quality: ClipAaQuality,
     pub spatial_tree: &'a SpatialTree,
     pub data_stores: &'a DataStores,
     pub surfaces: &'a [SurfaceInfo],
@@ -307,6 +309,7 @@ impl RenderTarget for ColorRenderTarget {
                         self.screen_size,
                         ctx.break_advanced_blend_batches,
                         ctx.batch_lookback_count,
+                        ctx.max_depth_ids,
                         *task_id,
                         (*task_id).into(),
                         &ctx.frame_memory,
@@ -1205,13 +1208,22 @@ fn build_mask_tasks(
     render_tasks: &RenderTaskGraph,
     results: &mut ClipMaskInstanceList,
     memory: &FrameMemory,
+    clip_aa_quality: ClipAaQuality,
 ) {
     for i in 0 .. info.clip_node_range.count {
         let clip_instance = clip_store.get_instance_from_range(&info.clip_node_range, i);
         let clip_node = &data_stores.clip[clip_instance.handle];
 
+        // Only rounded corners benefit from high quality AA.
+        let mut aa_quality = 0;
+
         let (clip_address, fast_path) = match clip_node.item.kind {
             ClipItemKind::RoundedRectangle { rect, radius, mode } => {
+                aa_quality = match clip_aa_quality {
+                    ClipAaQuality::Standard => 0,
+                    ClipAaQuality::High => 1,
+                };
+
                 let (fast_path, clip_address) = if radius.is_uniform().is_some() {
                     let mut writer = gpu_buffer_builder.f32.write_blocks(3);
                     writer.push_one(rect);
@@ -1413,7 +1425,7 @@ fn build_mask_tasks(
                     clip_transform_id,
                     clip_address: clip_address.as_int(),
                     clip_space: clip_space.as_int(),
-                    unused: 0,
+                    aa_quality,
                 };
 
                 if clip_needs_scissor_rect {
@@ -1489,6 +1501,7 @@ fn build_sub_pass(
                     render_tasks,
                     output,
                     &ctx.frame_memory,
+                    ctx.clip_aa_quality,
                 );
             }
         }
@@ -36,6 +36,11 @@ use smallvec::SmallVec;
 const FLOATS_PER_RENDER_TASK_INFO: usize = 8;
 pub const MAX_BLUR_STD_DEVIATION: f32 = 4.0;
 pub const MIN_DOWNSCALING_RT_SIZE: i32 = 8;
+/// The largest std deviation that a cheap blur runs its separable blur passes
+/// at. Keeping this low means most of the blur comes from the downscale chain
+/// and the bilinear upsample when the result is composited, rather than from
+/// the (more expensive) blur shader.
+pub const CHEAP_BLUR_MAX_STD_DEVIATION: f32 = 1.0;
 
 fn render_task_sanity_check(size: &DeviceIntSize) {
     if size.width > MAX_RENDER_TASK_SIZE ||
@@ -261,10 +266,24 @@ impl BlurTask {
     // In order to do the blur down-scaling passes without introducing errors, we need the
     // source of each down-scale pass to be a multuple of two. If need be, this inflates
     // the source size so that each down-scale pass will sample correctly.
-    pub fn adjusted_blur_source_size(original_size: DeviceSize, mut std_dev: DeviceSize) -> DeviceIntSize {
+    pub fn adjusted_blur_source_size(original_size: DeviceSize, std_dev: DeviceSize) -> DeviceIntSize {
+        Self::adjusted_source_size_impl(original_size, std_dev, MAX_BLUR_STD_DEVIATION)
+    }
+
+    /// As `adjusted_blur_source_size`, for the deeper downscale chain used by
+    /// `RenderTask::new_cheap_blur`.
+    pub fn adjusted_cheap_blur_source_size(original_size: DeviceSize, std_dev: DeviceSize) -> DeviceIntSize {
+        Self::adjusted_source_size_impl(original_size, std_dev, CHEAP_BLUR_MAX_STD_DEVIATION)
+    }
+
+    fn adjusted_source_size_impl(
+        original_size: DeviceSize,
+        mut std_dev: DeviceSize,
+        max_std_deviation: f32,
+    ) -> DeviceIntSize {
         let mut adjusted_size = original_size;
         let mut scale_factor = 1.0;
-        while std_dev.width > MAX_BLUR_STD_DEVIATION && std_dev.height > MAX_BLUR_STD_DEVIATION {
+        while std_dev.width > max_std_deviation && std_dev.height > max_std_deviation {
             if adjusted_size.width < MIN_DOWNSCALING_RT_SIZE as f32 ||
                adjusted_size.height < MIN_DOWNSCALING_RT_SIZE as f32 {
                 break;
@@ -1166,14 +1185,63 @@ impl RenderTask {
     //           +---- This is stored as the input task to the primitive shader.
     //
     pub fn new_blur(
+        blur_std_deviation: DeviceSize,
+        src_task_id: RenderTaskId,
+        rg_builder: &mut RenderTaskGraphBuilder,
+        target_kind: RenderTargetKind,
+        blur_cache: Option<&mut BlurTaskCache>,
+        blur_region: DeviceIntSize,
+    ) -> RenderTaskId {
+        RenderTask::new_blur_impl(
+            blur_std_deviation,
+            src_task_id,
+            rg_builder,
+            target_kind,
+            blur_cache,
+            blur_region,
+            MAX_BLUR_STD_DEVIATION,
+        )
+    }
+
+    // An approximate blur, used by `FilterOp::CheapBlur`. This is the same task
+    // chain as `new_blur`, but keeps downscaling until the std deviation is at
+    // most CHEAP_BLUR_MAX_STD_DEVIATION, so that the separable blur passes run
+    // on a much smaller target with only a handful of taps. The output is
+    // upsampled with bilinear filtering when composited.
+    //
+    // Compared to the real Gaussian, the result is slightly boxier and can show
+    // faint blocky artifacts on high-contrast edges, and small changes to the
+    // radius can snap between downscale levels. It is intended for live
+    // backdrop blurs where cost matters more than fidelity.
+    pub fn new_cheap_blur(
+        blur_std_deviation: DeviceSize,
+        src_task_id: RenderTaskId,
+        rg_builder: &mut RenderTaskGraphBuilder,
+        target_kind: RenderTargetKind,
+        blur_region: DeviceIntSize,
+    ) -> RenderTaskId {
+        RenderTask::new_blur_impl(
+            blur_std_deviation,
+            src_task_id,
+            rg_builder,
+            target_kind,
+            None,
+            blur_region,
+            CHEAP_BLUR_MAX_STD_DEVIATION,
+        )
+    }
+
+    fn new_blur_impl(
         blur_std_deviation: DeviceSize,
         src_task_id: RenderTaskId,
         rg_builder: &mut RenderTaskGraphBuilder,
         target_kind: RenderTargetKind,
         mut blur_cache: Option<&mut BlurTaskCache>,
         blur_region: DeviceIntSize,
+        max_std_deviation: f32,
     ) -> RenderTaskId {
-        // Adjust large std deviation value.
+        // Adjust large std deviation value, and downscale targets larger
+        // than the max blur raster size.
         let mut adjusted_blur_std_deviation = blur_std_deviation;
         let (blur_target_size, uv_rect_kind) = {
             let src_task = rg_builder.get_task(src_task_id);
@@ -1183,8 +1251,13 @@ impl RenderTask {
         let mut downscaling_src_task_id = src_task_id;
         let mut scale_factor = 1.0;
         let mut n_downscales = 1;
-        while adjusted_blur_std_deviation.width > MAX_BLUR_STD_DEVIATION &&
-              adjusted_blur_std_deviation.height > MAX_BLUR_STD_DEVIATION {
+        let max_raster_size = rg_builder.max_blur_raster_size();
+        let exceeds_max_raster_size = |size: DeviceIntSize| {
+            max_raster_size.map_or(false, |max| size.width > max.width || size.height > max.height)
+        };
+        while (adjusted_blur_std_deviation.width > max_std_deviation &&
+               adjusted_blur_std_deviation.height > max_std_deviation) ||
+              exceeds_max_raster_size(adjusted_blur_target_size) {
             if adjusted_blur_target_size.width < MIN_DOWNSCALING_RT_SIZE ||
                adjusted_blur_target_size.height < MIN_DOWNSCALING_RT_SIZE {
                 break;
@@ -11,6 +11,7 @@ use crate::device::TextureFilter;
 use crate::freelist::{FreeList, FreeListHandle, WeakFreeListHandle};
 use crate::gpu_cache::GpuCache;
 use crate::internal_types::FastHashMap;
+use crate::profiler::{self, TransactionProfile};
 use crate::prim_store::image::ImageCacheKey;
 use crate::prim_store::gradient::{
     FastLinearGradientCacheKey, LinearGradientCacheKey, RadialGradientCacheKey,
@@ -91,6 +92,13 @@ pub struct RenderTaskCache {
     map: FastHashMap<RenderTaskCacheKey, FreeListHandle<RenderTaskCacheMarker>>,
     cache_entries: FreeList<RenderTaskCacheEntry, RenderTaskCacheMarker>,
     frame_id: u64,
+    /// Number of requests this frame that reused a task cached by a
+    /// previous frame.
+    #[cfg_attr(any(feature = "capture", feature = "replay"), serde(skip))]
+    frame_hits: usize,
+    /// Number of requests this frame that had to render the task.
+    #[cfg_attr(any(feature = "capture", feature = "replay"), serde(skip))]
+    frame_misses: usize,
 }
 
 pub type RenderTaskCacheEntryHandle = WeakFreeListHandle<RenderTaskCacheMarker>;
@@ -101,6 +109,8 @@ impl RenderTaskCache {
             map: FastHashMap::default(),
             cache_entries: FreeList::new(),
             frame_id: 0,
+            frame_hits: 0,
+            frame_misses: 0,
         }
     }
 
@@ -114,6 +124,8 @@ impl RenderTaskCache {
         texture_cache: &mut TextureCache,
     ) {
         self.frame_id += 1;
+        self.frame_hits = 0;
+        self.frame_misses = 0;
         profile_scope!("begin_frame");
         // Drop any items from the cache that have been
         // evicted from the texture cache.
@@ -259,6 +271,8 @@ impl RenderTaskCache {
 
         // Check if this texture cache handle is valid.
         if texture_cache.request(&cache_entry.handle, gpu_cache) {
+            self.frame_misses += 1;
+
             // Invoke user closure to get render task chain
             // to draw this into the texture cache.
             let render_task_id = f(rg_builder, gpu_buffer_builder)?;
@@ -309,6 +323,8 @@ impl RenderTaskCache {
             return Ok(render_task_id);
         }
 
+        self.frame_hits += 1;
+
         let target_kind = cache_entry.target_kind;
         let mut task = RenderTask::new(
             RenderTaskLocation::CacheRequest { size, },
@@ -322,6 +338,11 @@ impl RenderTaskCache {
         Ok(render_task_id)
     }
 
+    pub fn update_profile(&self, profile: &mut TransactionProfile) {
+        profile.set(profiler::RENDER_TASK_CACHE_HITS, self.frame_hits);
+        profile.set(profiler::RENDER_TASK_CACHE_MISSES, self.frame_misses);
+    }
+
     pub fn get_cache_entry(
         &self,
         handle: &RenderTaskCacheEntryHandle,
@@ -92,8 +92,9 @@ struct Surface {
     allocator: GuillotineAllocator,
     /// We can only allocate into this for reuse if it's a shared surface
     is_shared: bool,
-    /// The pass that we can free this surface after (guaranteed
-    /// to be the same for all tasks assigned to this surface)
+    /// The pass that we can free this surface after. Tasks added with
+    /// `alloc_rect` all share this pass, while `alloc_rect_any_lifetime`
+    /// moves it to the latest pass that any of the tasks is needed by.
     free_after: PassId,
 }
 
@@ -115,6 +116,30 @@ impl Surface {
             None
         }
     }
+
+    /// Allocate a rect within a shared surface regardless of when the tasks
+    /// already in it are freed. The surface is then kept alive until the last
+    /// of its tasks can be freed.
+    fn alloc_rect_any_lifetime(
+        &mut self,
+        size: DeviceIntSize,
+        kind: RenderTargetKind,
+        free_after: PassId,
+    ) -> Option<DeviceIntPoint> {
+        if self.kind != kind || !self.is_shared {
+            return None;
+        }
+
+        let origin = self.allocator
+            .allocate(&size)
+            .map(|(_slice, origin)| origin)?;
+
+        // Passes are built in reverse order, so the lower pass id is the
+        // later one to be drawn.
+        self.free_after = self.free_after.min(free_after);
+
+        Some(origin)
+    }
 }
 
 /// A sub-pass can draw to either a dynamic (temporary render target) surface,
@@ -208,6 +233,10 @@ pub struct RenderTaskGraphBuilder {
     // Keep a map of `texture_id` to metadata about surfaces that are currently
     // borrowed from the render target pool.
     active_surfaces: FastHashMap<CacheTextureId, Surface>,
+
+    /// Blur tasks larger than this are downscaled until they fit, see
+    /// `FrameBuilderConfig::max_blur_raster_size`.
+    max_blur_raster_size: Option<DeviceIntSize>,
 }
 
 impl RenderTaskGraphBuilder {
@@ -220,6 +249,7 @@ impl RenderTaskGraphBuilder {
             frame_id: FrameId::INVALID,
             textures_to_free: FastHashSet::default(),
             active_surfaces: FastHashMap::default(),
+            max_blur_raster_size: None,
         }
     }
 
@@ -233,6 +263,16 @@ impl RenderTaskGraphBuilder {
         self.roots.clear();
     }
 
+    /// Set the size above which blur tasks added to the graph are rasterized
+    /// at a reduced resolution.
+    pub fn set_max_blur_raster_size(&mut self, size: Option<DeviceIntSize>) {
+        self.max_blur_raster_size = size;
+    }
+
+    pub fn max_blur_raster_size(&self) -> Option<DeviceIntSize> {
+        self.max_blur_raster_size
+    }
+
     /// Get immutable access to a task
     // TODO(gw): There's only a couple of places that existing code needs to access
     //           a task during the building step. Perhaps we can remove this?
@@ -284,6 +324,8 @@ impl RenderTaskGraphBuilder {
         gpu_cache: &mut GpuCache,
         deferred_resolves: &mut FrameVec<DeferredResolve>,
         max_shared_surface_size: i32,
+        use_srgb_render_targets: bool,
+        max_render_targets: Option<usize>,
         memory: &FrameMemory,
     ) -> RenderTaskGraph {
         // Copy the render tasks over to the immutable graph output
@@ -394,6 +436,11 @@ impl RenderTaskGraphBuilder {
         // to a target and destination rect.
         assert!(self.active_surfaces.is_empty());
 
+        // The unique set of render target textures borrowed from the pool during
+        // this frame, used to enforce `max_render_targets`.
+        let mut frame_surfaces = FastHashSet::default();
+        let mut exceeded_max_render_targets = false;
+
         for (pass_id, pass) in graph.passes.iter_mut().enumerate().rev() {
             assert!(self.textures_to_free.is_empty());
 
@@ -421,7 +468,15 @@ impl RenderTaskGraphBuilder {
                             for sub_pass in &mut pass.sub_passes {
                                 if let SubPassSurface::Dynamic { texture_id, ref mut used_rect, .. } = sub_pass.surface {
                                     let surface = self.active_surfaces.get_mut(&texture_id).unwrap();
-                                    if let Some(p) = surface.alloc_rect(size, kind, true, task.free_after) {
+                                    // When the number of render targets is limited, tasks with
+                                    // different lifetimes are packed together, at the cost of
+                                    // keeping the surface alive for longer.
+                                    let p = if max_render_targets.is_some() {
+                                        surface.alloc_rect_any_lifetime(size, kind, task.free_after)
+                                    } else {
+                                        surface.alloc_rect(size, kind, true, task.free_after)
+                                    };
+                                    if let Some(p) = p {
                                         location = Some((texture_id, p));
                                         *used_rect = used_rect.union(&DeviceIntRect::from_origin_and_size(p, size));
                                         sub_pass.task_ids.push(*task_id);
@@ -467,10 +522,15 @@ impl RenderTaskGraphBuilder {
                                 RenderTargetKind::Alpha => ImageFormat::R8,
                             };
 
+                            // Color targets may feed into filters, so they are given sRGB
+                            // storage when filter math should happen in linear space.
+                            let is_srgb = use_srgb_render_targets && kind == RenderTargetKind::Color;
+
                             // Get render target of appropriate size and format from resource cache
                             let texture_id = resource_cache.get_or_create_render_target_from_pool(
                                 surface_size,
                                 format,
+                                is_srgb,
                             );
 
                             // Allocate metadata we need about this surface while it's active
@@ -496,6 +556,20 @@ impl RenderTaskGraphBuilder {
                             let _prev_surface = self.active_surfaces.insert(texture_id, surface);
                             assert!(_prev_surface.is_none());
 
+                            // If this goes over the render target limit, there's no way to fit
+                            // the task in the existing targets since the passes are fixed by the
+                            // task dependencies. Prefer a correct frame over the limit.
+                            if frame_surfaces.insert(texture_id) &&
+                               max_render_targets.map_or(false, |max| frame_surfaces.len() > max) &&
+                               !exceeded_max_render_targets {
+                                warn!(
+                                    "Frame {:?} needs more than {:?} render targets",
+                                    self.frame_id,
+                                    max_render_targets,
+                                );
+                                exceeded_max_render_targets = true;
+                            }
+
                             // Store some information about surface allocations if in test mode
                             #[cfg(test)]
                             {
@@ -601,6 +675,11 @@ impl RenderTaskGraphBuilder {
             // Return no longer used textures to the pool, so that they can be reused / aliased
             // by later passes.
             for texture_id in self.textures_to_free.drain() {
+                // A surface holding tasks with different lifetimes is only returned
+                // once the last of them has been consumed.
+                if self.active_surfaces[&texture_id].free_after < PassId(pass_id) {
+                    continue;
+                }
                 resource_cache.return_render_target_to_pool(texture_id);
                 self.active_surfaces.remove(&texture_id).unwrap();
                 pass.textures_to_invalidate.push(texture_id);
@@ -1087,7 +1166,22 @@ fn pc_target(
 #[cfg(test)]
 impl RenderTaskGraphBuilder {
     fn test_expect(
+        self,
+        pass_count: usize,
+        total_surface_count: usize,
+        unique_surfaces: &[(i32, i32, ImageFormat)],
+    ) {
+        self.test_expect_with_max_render_targets(
+            None,
+            pass_count,
+            total_surface_count,
+            unique_surfaces,
+        );
+    }
+
+    fn test_expect_with_max_render_targets(
         mut self,
+        max_render_targets: Option<usize>,
         pass_count: usize,
         total_surface_count: usize,
         unique_surfaces: &[(i32, i32, ImageFormat)],
@@ -1104,7 +1198,7 @@ impl RenderTaskGraphBuilder {
         gc.begin_frame(frame_stamp);
 
         let frame_memory = FrameMemory::fallback();
-        let g = self.end_frame(&mut rc, &mut gc, &mut frame_memory.new_vec(), 2048, &frame_memory);
+        let g = self.end_frame(&mut rc, &mut gc, &mut frame_memory.new_vec(), 2048, false, max_render_targets, &frame_memory);
         g.print();
 
         assert_eq!(g.passes.len(), pass_count);
@@ -1277,3 +1371,32 @@ fn fg_test_7() {
         (2048, 2048, ImageFormat::RGBA8),
     ]);
 }
+
+#[test]
+fn fg_test_8() {
+    // Test that limiting the number of render targets packs tasks with
+    // different lifetimes into the same surface. Without a limit, this
+    // graph allocates three surfaces (see fg_test_7).
+
+    let mut gb = RenderTaskGraphBuilder::new();
+
+    let pc_root = gb.add().init(task_location(pc_target(0, 0, 0)));
+
+    let child0 = gb.add().init(task_dynamic(16));
+    let child1 = gb.add().init(task_dynamic(16));
+
+    let child2 = gb.add().init(task_dynamic(16));
+    let child3 = gb.add().init(task_dynamic(16));
+
+    gb.add_dependency(pc_root, child0);
+    gb.add_dependency(child0, child1);
+    gb.add_dependency(pc_root, child1);
+
+    gb.add_dependency(pc_root, child2);
+    gb.add_dependency(child2, child3);
+
+    gb.test_expect_with_max_render_targets(Some(2), 3, 2, &[
+        (2048, 2048, ImageFormat::RGBA8),
+        (2048, 2048, ImageFormat::RGBA8),
+    ]);
+}
@@ -87,17 +87,20 @@ enum GpuCacheBus {
 pub struct GpuCacheTexture {
     texture: Option<Texture>,
     bus: GpuCacheBus,
+    /// The number of times an existing texture was replaced by a larger one.
+    reallocation_count: usize,
 }
 
 impl GpuCacheTexture {
-    /// Ensures that we have an appropriately-sized texture.
-    fn ensure_texture(&mut self, device: &mut Device, height: i32) {
+    /// Ensures that we have an appropriately-sized texture. Returns true if
+    /// an existing texture was replaced by a larger one.
+    fn ensure_texture(&mut self, device: &mut Device, height: i32) -> bool {
         // If we already have a texture that works, we're done.
         if self.texture.as_ref().map_or(false, |t| t.get_dimensions().height >= height) {
             if GPU_CACHE_RESIZE_TEST {
                 // Special debug mode - resize the texture even though it's fine.
             } else {
-                return;
+                return false;
             }
         }
 
@@ -132,6 +135,7 @@ impl GpuCacheTexture {
         );
 
         // Copy the contents of the previous texture, if applicable.
+        let resized = blit_source.is_some();
         if let Some(blit_source) = blit_source {
             if !supports_copy_image_sub_data && !supports_color_buffer_float {
                 // Cannot copy texture, so must re-upload everything.
@@ -152,6 +156,10 @@ impl GpuCacheTexture {
         }
 
         self.texture = Some(texture);
+        if resized {
+            self.reallocation_count += 1;
+        }
+        resized
     }
 
     pub fn new(device: &mut Device, use_scatter: bool) -> Result<Self, super::RendererError> {
@@ -191,6 +199,7 @@ impl GpuCacheTexture {
         Ok(GpuCacheTexture {
             texture: None,
             bus,
+            reallocation_count: 0,
         })
     }
 
@@ -210,18 +219,23 @@ impl GpuCacheTexture {
         self.texture.as_ref().map_or(0, |t| t.get_dimensions().height)
     }
 
+    pub fn reallocation_count(&self) -> usize {
+        self.reallocation_count
+    }
+
     #[cfg(feature = "capture")]
     pub fn get_texture(&self) -> &Texture {
         self.texture.as_ref().unwrap()
     }
 
+    /// Returns true if the texture was resized.
     fn prepare_for_updates(
         &mut self,
         device: &mut Device,
         total_block_count: usize,
         max_height: i32,
-    ) {
-        self.ensure_texture(device, max_height);
+    ) -> bool {
+        let resized = self.ensure_texture(device, max_height);
         match self.bus {
             GpuCacheBus::PixelBuffer { .. } => {},
             GpuCacheBus::Scatter {
@@ -237,6 +251,7 @@ impl GpuCacheTexture {
                 }
             }
         }
+        resized
     }
 
     pub fn invalidate(&mut self) {
@@ -458,11 +473,13 @@ impl super::Renderer {
         // Note: if we decide to switch to scatter-style GPU cache update
         // permanently, we can have this code nicer with `BufferUploader` kind
         // of helper, similarly to how `TextureUploader` API is used.
-        self.gpu_cache_texture.prepare_for_updates(
+        if self.gpu_cache_texture.prepare_for_updates(
             &mut self.device,
             updated_blocks,
             max_requested_height,
-        );
+        ) {
+            self.gpu_cache_resized = true;
+        }
 
         for update_list in self.pending_gpu_cache_updates.drain(..) {
             assert!(update_list.height <= max_requested_height);
@@ -484,25 +501,64 @@ impl super::Renderer {
         self.profile.set(profiler::GPU_CACHE_BLOCKS_UPDATED, updated_blocks);
     }
 
+    /// Allocates the GPU cache texture with at least the given number of
+    /// rows up front, so that it doesn't have to grow (and copy its contents)
+    /// over the next frames, e.g. on startup or after clearing the caches.
+    /// Does nothing if the texture is already that tall.
+    pub fn reserve_gpu_cache_height(&mut self, rows: i32) -> Result<(), super::RendererError> {
+        if rows > self.get_max_texture_size() {
+            return Err(super::RendererError::MaxTextureSize);
+        }
+
+        // A pending clear would throw the reserved texture away.
+        self.apply_pending_gpu_cache_clear()?;
+
+        if rows <= self.gpu_cache_texture.get_height() {
+            return Ok(());
+        }
+
+        self.device.begin_frame();
+        self.gpu_cache_texture.ensure_texture(&mut self.device, cmp::max(rows, 2));
+        self.device.end_frame();
+
+        Ok(())
+    }
+
+    /// The number of rows in the GPU cache texture.
+    pub fn gpu_cache_height(&self) -> i32 {
+        self.gpu_cache_texture.get_height()
+    }
+
+    /// The number of times the GPU cache texture had to be reallocated at a
+    /// larger size.
+    pub fn gpu_cache_reallocation_count(&self) -> usize {
+        self.gpu_cache_texture.reallocation_count()
+    }
+
+    fn apply_pending_gpu_cache_clear(&mut self) -> Result<(), super::RendererError> {
+        if !self.pending_gpu_cache_clear {
+            return Ok(());
+        }
+
+        let use_scatter =
+            matches!(self.gpu_cache_texture.bus, GpuCacheBus::Scatter { .. });
+        let new_cache = GpuCacheTexture::new(&mut self.device, use_scatter)?;
+        let old_cache = mem::replace(&mut self.gpu_cache_texture, new_cache);
+        old_cache.deinit(&mut self.device);
+        self.pending_gpu_cache_clear = false;
+
+        Ok(())
+    }
+
     pub fn prepare_gpu_cache(
         &mut self,
         deferred_resolves: &[DeferredResolve],
     ) -> Result<(), super::RendererError> {
         self.profile.start_time(profiler::GPU_CACHE_PREPARE_TIME);
 
-        if self.pending_gpu_cache_clear {
-            let use_scatter =
-                matches!(self.gpu_cache_texture.bus, GpuCacheBus::Scatter { .. });
-            let new_cache = match GpuCacheTexture::new(&mut self.device, use_scatter) {
-                Ok(cache) => cache,
-                Err(err) => {
-                    self.profile.end_time(profiler::GPU_CACHE_PREPARE_TIME);
-                    return Err(err);
-                }
-            };
-            let old_cache = mem::replace(&mut self.gpu_cache_texture, new_cache);
-            old_cache.deinit(&mut self.device);
-            self.pending_gpu_cache_clear = false;
+        if let Err(err) = self.apply_pending_gpu_cache_clear() {
+            self.profile.end_time(profiler::GPU_CACHE_PREPARE_TIME);
+            return Err(err);
         }
 
         let deferred_update_list = self.update_deferred_resolves(deferred_resolves);
@@ -2,35 +2,37 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use api::{BlobImageHandler, ColorF, CrashAnnotator, DocumentId, IdNamespace};
+use api::{BlobImageHandler, ClipAaQuality, ColorF, CrashAnnotator, DocumentId, IdNamespace};
 use api::{VoidPtrToSizeFn, FontRenderMode, ImageFormat};
 use api::{RenderNotifier, ImageBufferKind};
 use api::units::*;
 use api::channel::unbounded_channel;
 pub use api::DebugFlags;
 
-use crate::render_api::{RenderApiSender, FrameMsg};
+use crate::api_resources::NamespacedBlobImageHandler;
+use crate::render_api::{RenderApiSender, FrameMsg, MemoryReport};
 use crate::composite::{CompositorKind, CompositorConfig};
 use crate::device::{
-    UploadMethod, UploadPBOPool, VertexUsageHint, Device, ProgramCache, TextureFilter
+    ContextLostHandler, UploadMethod, UploadPBOPool, VertexUsageHint, Device, ProgramCache, TextureFilter
 };
 use crate::frame_builder::FrameBuilderConfig;
-use crate::glyph_cache::GlyphCache;
+use crate::glyph_cache::{GlyphCache, GlyphCacheExport};
 use glyph_rasterizer::{GlyphRasterThread, GlyphRasterizer, SharedFontResources};
 use crate::gpu_types::PrimitiveInstanceData;
-use crate::internal_types::{FastHashMap, FastHashSet, FrameId};
+use crate::internal_types::{FastHashMap, FastHashSet, FrameId, FramesInFlight};
 use crate::picture;
 use crate::profiler::{self, Profiler, TransactionProfile};
 use crate::device::query::{GpuProfiler, GpuDebugMethod};
 use crate::render_backend::RenderBackend;
 use crate::resource_cache::ResourceCache;
 use crate::scene_builder_thread::{SceneBuilderThread, SceneBuilderThreadChannels, LowPrioritySceneBuilderThread};
+use crate::scene_builder_thread::SceneBuildCancellation;
 use crate::texture_cache::{TextureCache, TextureCacheConfig};
 use crate::picture_textures::PictureTextures;
 use crate::renderer::{
     debug, gpu_cache, vertex, gl,
-    Renderer, DebugOverlayState, BufferDamageTracker, PipelineInfo, TextureResolver,
-    RendererError, ShaderPrecacheFlags, VERTEX_DATA_TEXTURE_COUNT,
+    Renderer, DebugOverlayState, ExternalImageLocks, PipelineInfo, TextureResolver,
+    RendererError, ShaderPrecacheFlags, FrameSummary, AlphaMode, VERTEX_DATA_TEXTURE_COUNT,
     upload::UploadTexturePool,
     shade::{Shaders, SharedShaders},
 };
@@ -115,6 +117,30 @@ pub trait RenderBackendHooks {
     fn init_thread(&self);
 }
 
+/// Describes a shared texture cache texture that WebRender is about to
+/// allocate.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TextureAllocationRequest {
+    pub size: DeviceIntSize,
+    pub format: ImageFormat,
+    pub target: ImageBufferKind,
+}
+
+/// Allows callers to supply the textures backing the shared texture cache,
+/// for example to share atlases with their own renderer. These functions are
+/// all called from the render thread, with the GL context current.
+pub trait TextureAllocatorHook {
+    /// This is called when WebRender needs a new shared texture cache texture.
+    /// Returning the name of an existing GL texture, whose storage matches the
+    /// request, makes WebRender render into and sample from it instead of
+    /// allocating its own. Returning `None` falls back to the internal
+    /// allocation.
+    fn allocate(&mut self, request: &TextureAllocationRequest) -> Option<gl::GLuint>;
+    /// This is called when WebRender stops using a texture returned by
+    /// `allocate`. The caller keeps ownership of the GL texture.
+    fn release(&mut self, texture_id: gl::GLuint);
+}
+
 pub struct WebRenderOptions {
     pub resource_override_path: Option<PathBuf>,
     /// Whether to use shaders that have been optimized at build time.
@@ -123,6 +149,16 @@ pub struct WebRenderOptions {
     pub enable_dithering: bool,
     pub max_recorded_profiles: usize,
     pub precache_flags: ShaderPrecacheFlags,
+    /// Submit every shader compile and link to the driver when the renderer
+    /// is created, without waiting for them. Drivers with
+    /// `GL_KHR_parallel_shader_compile` do the work on their own threads;
+    /// others may still block when a shader is first used. Until every
+    /// shader is linked, `Renderer::render` skips the draws that need the
+    /// missing ones and calls `RenderNotifier::wake_up` to be rendered
+    /// again. Once they are all linked, the documents are redrawn and
+    /// `RenderNotifier::new_frame_ready` is called. Overrides
+    /// `precache_flags`.
+    pub async_shader_compile: bool,
     /// Enable sub-pixel anti-aliasing if a fast implementation is available.
     pub enable_subpixel_aa: bool,
     pub clear_color: ColorF,
@@ -132,11 +168,20 @@ pub struct WebRenderOptions {
     pub upload_method: UploadMethod,
     /// The default size in bytes for PBOs used to upload texture data.
     pub upload_pbo_default_size: usize,
+    /// If set, `RendererStats::over_upload_budget` is raised for any frame
+    /// that uploads more than this many bytes to the texture cache.
+    pub texture_upload_budget_bytes: Option<usize>,
     pub batched_upload_threshold: i32,
     pub workers: Option<Arc<ThreadPool>>,
     pub dedicated_glyph_raster_thread: Option<GlyphRasterThread>,
     pub enable_multithreading: bool,
     pub blob_image_handler: Option<Box<dyn BlobImageHandler>>,
+    /// Blob image handlers for the blob images whose keys are in the given
+    /// namespaces. Other blob images go to `blob_image_handler`, and are not
+    /// rasterized if it is None. The namespaces are only known ahead of time
+    /// when `namespace_alloc_by_client` is set and the APIs are created with
+    /// `RenderApiSender::create_api_by_client`.
+    pub namespaced_blob_image_handlers: FastHashMap<IdNamespace, Box<dyn BlobImageHandler>>,
     pub crash_annotator: Option<Box<dyn CrashAnnotator>>,
     pub size_of_op: Option<VoidPtrToSizeFn>,
     pub enclosing_size_of_op: Option<VoidPtrToSizeFn>,
@@ -146,8 +191,30 @@ pub struct WebRenderOptions {
     pub scene_builder_hooks: Option<Box<dyn SceneBuilderHooks + Send>>,
     pub render_backend_hooks: Option<Box<dyn RenderBackendHooks + Send>>,
     pub sampler: Option<Box<dyn AsyncPropertySampler + Send>>,
+    /// Consulted whenever a shared texture cache texture is allocated.
+    pub texture_allocator_hook: Option<Box<dyn TextureAllocatorHook>>,
+    /// Invoked instead of panicking when a GL error reports context loss.
+    pub on_context_lost: Option<Box<dyn ContextLostHandler>>,
+    /// Invoked on the render thread once per presented frame with a compact
+    /// summary of it. Cheap enough to be left on for telemetry, unlike the
+    /// profiler.
+    pub frame_summary_sink: Option<Arc<dyn Fn(FrameSummary) + Send + Sync>>,
+    /// Invoked on the render thread with the combined dirty rect of each
+    /// frame that is partially presented, returning the rect to present
+    /// instead. Embedders that draw their own content over the framebuffer
+    /// can use this to add their damage. The result is clamped to the
+    /// framebuffer, and is redrawn and reported in `RenderResults::dirty_rects`.
+    pub dirty_rect_hook: Option<Arc<dyn Fn(DeviceIntRect) -> DeviceIntRect + Send + Sync>>,
     pub support_low_priority_transactions: bool,
     pub namespace_alloc_by_client: bool,
+    /// The maximum number of frames the render backend publishes before they
+    /// are rendered by `Renderer::render`. Once the limit is reached, the
+    /// render backend defers building frames until a frame is rendered (or
+    /// replaced by a newer one), and then builds the latest state, so that
+    /// latency sensitive embedders can keep strictly one frame in flight. The
+    /// render backend keeps handling other API messages in the meantime. Zero,
+    /// the default, doesn't limit it.
+    pub max_frames_in_flight: usize,
     /// If namespaces are allocated by the client, then the namespace for fonts
     /// must also be allocated by the client to avoid namespace collisions with
     /// the backend.
@@ -172,11 +239,35 @@ pub struct WebRenderOptions {
     /// This helps to work around some Intel drivers
     /// that incorrectly synchronize clears to following draws.
     pub clear_caches_with_quads: bool,
+    /// Flush GL before drawing each frame, so that the GPU cache updates get
+    /// a head start. Tiled GPUs on mobile (such as Mali and Adreno) can pay
+    /// more for submitting the partial command stream than they gain, so
+    /// disabling this may help there. Desktop drivers generally benefit.
+    pub early_gl_flush: bool,
+    /// Invalidate render targets that are no longer needed at the end of
+    /// each pass, via `glInvalidateFramebuffer`. This lets tiled GPUs (such
+    /// as Mali, Adreno and PowerVR) skip writing them back to memory. Some
+    /// desktop drivers do extra work or misbehave on these calls, so
+    /// disabling this may help there.
+    pub invalidate_targets_on_pass_end: bool,
+    /// Number of samples used when rasterizing clip masks. Zero disables
+    /// multisampling. Clamped to the maximum supported by the device.
+    pub clip_mask_msaa_samples: u8,
+    /// Whether the colors written to the framebuffer have premultiplied or
+    /// straight alpha. Only supported by the draw compositor.
+    pub output_alpha_mode: AlphaMode,
     /// Output the source of the shader with the given name.
     pub dump_shader_source: Option<String>,
+    /// File that GL calls are appended to while `DebugFlags::TRACE_GL_CALLS`
+    /// is set. Requires the `gl_trace` feature.
+    pub gl_trace_path: PathBuf,
     pub surface_origin_is_top_left: bool,
     /// The configuration options defining how WR composites the final scene.
     pub compositor_config: CompositorConfig,
+    /// If true, `Renderer::render` doesn't end the native compositor's frame.
+    /// The embedder decides whether to present the drawn frame, by calling
+    /// `Renderer::present`. Ignored unless a native compositor is used.
+    pub explicit_native_present: bool,
     pub enable_gpu_markers: bool,
     /// If true, panic whenever a GL error occurs. This has a significant
     /// performance impact, so only use when debugging specific problems!
@@ -197,6 +288,53 @@ pub struct WebRenderOptions {
     /// make the result look quite close to the high-quality zoom, except for glyphs.
     pub low_quality_pinch_zoom: bool,
     pub max_shared_surface_size: i32,
+    /// If enabled, intermediate color render targets are allocated with sRGB
+    /// storage, and blurs reading from them decode to linear space and encode
+    /// the result back to sRGB. This avoids the darkening of blurred edges
+    /// caused by filtering gamma-encoded values. Ignored if the device can't
+    /// toggle the sRGB conversions.
+    pub use_srgb_render_targets: bool,
+    /// If enabled, a CPU copy of each rasterized glyph is kept so that the
+    /// glyph cache can be exported with `Renderer::export_glyph_cache`.
+    pub retain_glyph_bitmaps: bool,
+    /// Glyphs exported from a previous renderer, typically one that was torn
+    /// down after a context loss. Glyphs in the export are uploaded instead of
+    /// being rasterized again, as long as the font data they were rasterized
+    /// from is identical.
+    pub glyph_cache_import: Option<GlyphCacheExport>,
+    /// If enabled, the draw compositor writes a depth value for every
+    /// composited surface to the depth attachment of the framebuffer, so that
+    /// the output can be depth-composited with 3D content. The embedder must
+    /// provide the depth attachment. Depth is linear in the compositing order
+    /// `z` of the surface (0 for the front-most surface): with `M` the value
+    /// returned by `Renderer::get_max_depth_ids`, a surface writes
+    /// `(z + 1) / (2M - 1)` for the default `[0, 1]` depth range, so nearer
+    /// surfaces have smaller depth and uncovered areas are cleared to 1.0.
+    /// Ignored by native compositors.
+    pub output_linear_depth: bool,
+    /// Distance in device pixels around the screen within which primitives
+    /// are kept during culling. Content animating or scrolling in from
+    /// off-screen is then prepared a little ahead of becoming visible, at
+    /// the cost of some extra frame building work. Defaults to 0.
+    pub culling_margin: f32,
+    /// Blur render tasks larger than this size are rasterized at a reduced
+    /// resolution and upsampled, trading quality for speed. Large blurs hide
+    /// the downsampling well, but small radius blurs of large areas lose
+    /// some sharpness. None rasterizes blurs at full resolution.
+    pub max_blur_raster_size: Option<DeviceIntSize>,
+    /// Limits the number of intermediate render target textures allocated
+    /// for a frame, trading some batching for bounded memory use. When set,
+    /// tasks drawn in the same pass share render targets even if their
+    /// results are needed for different lengths of time, which keeps some
+    /// targets alive for longer than strictly necessary. If a pass still
+    /// needs more targets than the limit allows, they are allocated anyway
+    /// and a warning is logged, since the passes themselves are determined
+    /// by the dependencies between render tasks. None (the default) doesn't
+    /// limit the number of targets.
+    pub max_render_targets_per_frame: Option<usize>,
+    /// Width in device pixels over which the edges of anti-aliased
+    /// primitives and clips fade out. Clamped to [0.5, 2]. Defaults to 1.
+    pub edge_aa_feather: f32,
 }
 
 impl WebRenderOptions {
@@ -223,6 +361,7 @@ impl Default for WebRenderOptions {
             debug_flags: DebugFlags::empty(),
             max_recorded_profiles: 0,
             precache_flags: ShaderPrecacheFlags::empty(),
+            async_shader_compile: false,
             enable_subpixel_aa: false,
             clear_color: ColorF::new(1.0, 1.0, 1.0, 1.0),
             enable_clear_scissor: None,
@@ -232,11 +371,13 @@ impl Default for WebRenderOptions {
             // but we are unable to make this decision here, so picking the reasonable medium.
             upload_method: UploadMethod::PixelBuffer(ONE_TIME_USAGE_HINT),
             upload_pbo_default_size: 512 * 512 * 4,
+            texture_upload_budget_bytes: None,
             batched_upload_threshold: 512 * 512,
             workers: None,
             dedicated_glyph_raster_thread: None,
             enable_multithreading: true,
             blob_image_handler: None,
+            namespaced_blob_image_handlers: FastHashMap::default(),
             crash_annotator: None,
             size_of_op: None,
             enclosing_size_of_op: None,
@@ -245,8 +386,13 @@ impl Default for WebRenderOptions {
             scene_builder_hooks: None,
             render_backend_hooks: None,
             sampler: None,
+            texture_allocator_hook: None,
+            on_context_lost: None,
+            frame_summary_sink: None,
+            dirty_rect_hook: None,
             support_low_priority_transactions: false,
             namespace_alloc_by_client: false,
+            max_frames_in_flight: 0,
             shared_font_namespace: None,
             testing: false,
             gpu_supports_fast_clears: false,
@@ -255,9 +401,15 @@ impl Default for WebRenderOptions {
             allow_texture_storage_support: true,
             allow_texture_swizzling: true,
             clear_caches_with_quads: true,
+            early_gl_flush: true,
+            invalidate_targets_on_pass_end: true,
+            clip_mask_msaa_samples: 0,
+            output_alpha_mode: AlphaMode::Premultiplied,
             dump_shader_source: None,
+            gl_trace_path: PathBuf::from("gl-trace.txt"),
             surface_origin_is_top_left: false,
             compositor_config: CompositorConfig::default(),
+            explicit_native_present: false,
             enable_gpu_markers: true,
             panic_on_gl_error: false,
             picture_tile_size: None,
@@ -268,6 +420,14 @@ impl Default for WebRenderOptions {
             reject_software_rasterizer: false,
             low_quality_pinch_zoom: false,
             max_shared_surface_size: 2048,
+            use_srgb_render_targets: false,
+            retain_glyph_bitmaps: false,
+            glyph_cache_import: None,
+            output_linear_depth: false,
+            culling_margin: 0.0,
+            max_blur_raster_size: None,
+            max_render_targets_per_frame: None,
+            edge_aa_feather: 1.0,
         }
     }
 }
@@ -329,6 +489,7 @@ pub fn create_webrender_instance(
         options.surface_origin_is_top_left,
         options.panic_on_gl_error,
     );
+    device.set_context_lost_handler(options.on_context_lost.take());
 
     let color_cache_formats = device.preferred_color_formats();
     let swizzle_settings = device.swizzle_settings();
@@ -514,11 +675,12 @@ pub fn create_webrender_instance(
         CompositorConfig::Draw { max_partial_present_rects, draw_previous_partial_present_regions, .. } => {
             CompositorKind::Draw { max_partial_present_rects, draw_previous_partial_present_regions }
         }
-        CompositorConfig::Native { ref compositor } => {
+        CompositorConfig::Native { ref compositor, max_tiles_per_frame } => {
             let capabilities = compositor.get_capabilities(&mut device);
 
             CompositorKind::Native {
                 capabilities,
+                max_tiles_per_frame,
             }
         }
     };
@@ -538,11 +700,21 @@ pub fn create_webrender_instance(
         tile_size_override: None,
         max_surface_override: None,
         max_depth_ids: device.max_depth_ids(),
+        max_depth_ids_override: None,
         max_target_size: max_internal_texture_size,
         force_invalidation: false,
         is_software,
         low_quality_pinch_zoom: options.low_quality_pinch_zoom,
         max_shared_surface_size: options.max_shared_surface_size,
+        use_srgb_render_targets: options.use_srgb_render_targets &&
+            device.get_capabilities().supports_srgb_render_targets,
+        clip_aa_quality: ClipAaQuality::Standard,
+        culling_margin: options.culling_margin.max(0.0),
+        max_blur_raster_size: options.max_blur_raster_size,
+        max_render_targets_per_frame: options.max_render_targets_per_frame,
+        render_scale: 1.0,
+        edge_aa_feather: options.edge_aa_feather.max(0.5).min(2.0),
+        show_clip_bounds: false,
     };
     info!("WR {:?}", config);
 
@@ -569,6 +741,8 @@ pub fn create_webrender_instance(
         });
     let sampler = options.sampler;
     let namespace_alloc_by_client = options.namespace_alloc_by_client;
+    let frames_in_flight = FramesInFlight::new(options.max_frames_in_flight, api_tx.clone());
+    let rb_frames_in_flight = Arc::clone(&frames_in_flight);
 
     // Ensure shared font keys exist within their own unique namespace so
     // that they don't accidentally collide across Renderer instances.
@@ -579,7 +753,13 @@ pub fn create_webrender_instance(
     };
     let fonts = SharedFontResources::new(font_namespace);
 
-    let blob_image_handler = options.blob_image_handler.take();
+    if !namespace_alloc_by_client && !options.namespaced_blob_image_handlers.is_empty() {
+        warn!("namespaced_blob_image_handlers requires namespace_alloc_by_client");
+    }
+    let blob_image_handler = NamespacedBlobImageHandler::combine(
+        options.blob_image_handler.take(),
+        mem::take(&mut options.namespaced_blob_image_handlers),
+    );
     let scene_builder_hooks = options.scene_builder_hooks;
     let rb_thread_name = format!("WRRenderBackend#{}", options.renderer_id.unwrap_or(0));
     let scene_thread_name = format!("WRSceneBuilder#{}", options.renderer_id.unwrap_or(0));
@@ -595,6 +775,8 @@ pub fn create_webrender_instance(
         SceneBuilderThreadChannels::new(api_tx.clone());
 
     let sb_fonts = fonts.clone();
+    let scene_build_cancellation = SceneBuildCancellation::default();
+    let sb_scene_build_cancellation = scene_build_cancellation.clone();
 
     thread::Builder::new().name(scene_thread_name.clone()).spawn(move || {
         register_thread_with_profiler(scene_thread_name.clone());
@@ -606,6 +788,7 @@ pub fn create_webrender_instance(
             make_size_of_ops(),
             scene_builder_hooks,
             scene_builder_channels,
+            sb_scene_build_cancellation,
         );
         scene_builder.run();
 
@@ -618,6 +801,7 @@ pub fn create_webrender_instance(
             rx: low_priority_scene_rx,
             tx: scene_tx.clone(),
             tile_pool: api::BlobTilePool::new(),
+            simulate_slow_ms: 0,
         };
 
         thread::Builder::new().name(lp_scene_thread_name.clone()).spawn(move || {
@@ -656,6 +840,8 @@ pub fn create_webrender_instance(
     let rb_scene_tx = scene_tx.clone();
     let rb_fonts = fonts.clone();
     let enable_multithreading = options.enable_multithreading;
+    let retain_glyph_bitmaps = options.retain_glyph_bitmaps;
+    let glyph_cache_import = options.glyph_cache_import.take();
     thread::Builder::new().name(rb_thread_name.clone()).spawn(move || {
         if let Some(hooks) = render_backend_hooks {
             hooks.init_thread();
@@ -688,6 +874,7 @@ pub fn create_webrender_instance(
         );
 
         resource_cache.enable_multithreading(enable_multithreading);
+        resource_cache.set_glyph_persistence(retain_glyph_bitmaps, glyph_cache_import);
 
         let mut backend = RenderBackend::new(
             api_rx,
@@ -700,6 +887,7 @@ pub fn create_webrender_instance(
             make_size_of_ops(),
             debug_flags,
             namespace_alloc_by_client,
+            rb_frames_in_flight,
         );
         backend.run();
         profiler::unregister_thread();
@@ -737,18 +925,32 @@ pub fn create_webrender_instance(
         shaders,
         debug: debug::LazyInitializedDebugRenderer::new(),
         debug_flags: DebugFlags::empty(),
+        #[cfg(feature = "gl_trace")]
+        gl_trace_path: options.gl_trace_path.clone(),
         profile: TransactionProfile::new(),
         frame_counter: 0,
         resource_upload_time: 0.0,
         gpu_cache_upload_time: 0.0,
+        gpu_cache_resized: false,
+        texture_upload_bytes: 0,
+        texture_upload_budget_bytes: options.texture_upload_budget_bytes,
         profiler: Profiler::new(),
         max_recorded_profiles: options.max_recorded_profiles,
         clear_color: options.clear_color,
+        document_clear_colors: FastHashMap::default(),
         enable_clear_scissor,
         enable_advanced_blend_barriers: !ext_blend_equation_advanced_coherent,
         clear_caches_with_quads: options.clear_caches_with_quads,
+        early_gl_flush: options.early_gl_flush,
+        invalidate_targets_on_pass_end: options.invalidate_targets_on_pass_end,
         clear_alpha_targets_with_quads,
+        clip_mask_msaa_samples: 0,
+        clip_mask_msaa_target: None,
+        output_alpha_mode: options.output_alpha_mode,
+        output_alpha_texture: None,
         last_time: 0,
+        backend_wait_start: None,
+        backend_wait_time: 0.0,
         gpu_profiler,
         vaos,
         vertex_data_textures,
@@ -756,7 +958,13 @@ pub fn create_webrender_instance(
         pipeline_info: PipelineInfo::default(),
         dither_matrix_texture,
         external_image_handler: None,
+        texture_allocator_hook: options.texture_allocator_hook.take(),
+        locked_external_images: ExternalImageLocks::default(),
         size_of_ops: make_size_of_ops(),
+        peak_memory: MemoryReport::default(),
+        frame_summary_sink: options.frame_summary_sink.take(),
+        dirty_rect_hook: options.dirty_rect_hook.take(),
+        last_gpu_time_ns: None,
         cpu_profiles: VecDeque::new(),
         gpu_profiles: VecDeque::new(),
         gpu_cache_texture,
@@ -777,14 +985,34 @@ pub fn create_webrender_instance(
         device_size: None,
         zoom_debug_texture: None,
         cursor_position: DeviceIntPoint::zero(),
+        debug_grid_spacing: DeviceIntSize::new(8, 8),
         shared_texture_cache_cleared: false,
         documents_seen: FastHashSet::default(),
         force_redraw: true,
+        global_saturation: 1.0,
+        vignette: None,
+        window_backdrop_image: None,
+        window_backdrop_blur: None,
+        window_backdrop_targets: None,
+        window_backdrop_task_texture: vertex::VertexDataTexture::new(ImageFormat::RGBAF32),
+        output_linear_depth: options.output_linear_depth,
+        present_mode: None,
+        integer_scaling: None,
+        render_scale_target: None,
+        safe_area_insets: DeviceIntSideOffsets::zero(),
         compositor_config: options.compositor_config,
+        explicit_native_present: options.explicit_native_present,
+        frames_in_flight,
+        pending_native_present: None,
+        pending_shader_compile: options.async_shader_compile ||
+            options.precache_flags.contains(ShaderPrecacheFlags::ASYNC_COMPILE),
+        notifier,
+        primitive_isolation: None,
+        opaque_only: false,
         current_compositor_kind: compositor_kind,
         allocated_native_surfaces: FastHashSet::default(),
         debug_overlay_state: DebugOverlayState::new(),
-        buffer_damage_tracker: BufferDamageTracker::default(),
+        buffer_damage_trackers: FastHashMap::default(),
         max_primitive_instance_count,
         enable_instancing: options.enable_instancing,
         consecutive_oom_frames: 0,
@@ -795,11 +1023,13 @@ pub fn create_webrender_instance(
     // We initially set the flags to default and then now call set_debug_flags
     // to ensure any potential transition when enabling a flag is run.
     renderer.set_debug_flags(debug_flags);
+    renderer.set_clip_mask_msaa_samples(options.clip_mask_msaa_samples);
 
     let sender = RenderApiSender::new(
         api_tx,
         scene_tx,
         low_priority_scene_tx,
+        scene_build_cancellation,
         blob_image_handler,
         fonts,
     );
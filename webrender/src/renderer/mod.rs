@@ -35,16 +35,16 @@
 //! calling `DrawTarget::to_framebuffer_rect`
 
 use api::{ColorF, ColorU, MixBlendMode};
-use api::{DocumentId, Epoch, ExternalImageHandler, RenderReasons};
+use api::{DocumentId, Epoch, ExternalImageHandler, RenderNotifier, RenderReasons};
 #[cfg(feature = "replay")]
 use api::ExternalImageId;
-use api::{ExternalImageSource, ExternalImageType, ImageFormat, PremultipliedColorF};
+use api::{ExternalImageData, ExternalImageSource, ExternalImageType, ImageFormat, PremultipliedColorF};
 use api::{PipelineId, ImageRendering, Checkpoint, NotificationRequest, ImageBufferKind};
-#[cfg(feature = "replay")]
+use api::ImageWrapMode;
 use api::ExternalImage;
 use api::FramePublishId;
 use api::units::*;
-use api::channel::{Sender, Receiver};
+use api::channel::{Sender, Receiver, single_msg_channel};
 pub use api::DebugFlags;
 use core::time::Duration;
 
@@ -57,34 +57,38 @@ use crate::capture::{CaptureConfig, ExternalCaptureImage, PlainExternalImage};
 use crate::composite::{CompositeState, CompositeTileSurface, ResolvedExternalSurface, CompositorSurfaceTransform};
 use crate::composite::{CompositorKind, Compositor, NativeTileId, CompositeFeatures, CompositeSurfaceFormat, ResolvedExternalSurfaceColorData};
 use crate::composite::{CompositorConfig, NativeSurfaceOperationDetails, NativeSurfaceId, NativeSurfaceOperation};
-use crate::composite::{TileKind};
+use crate::composite::{ClipRadius, CompositeSurfaceInfo, CompositorCapabilities, TileKind};
 use crate::debug_colors;
-use crate::device::{DepthFunction, Device, DrawTarget, ExternalTexture, GpuFrameId, UploadPBOPool};
+use crate::device::{DepthFunction, Device, DrawTarget, ExternalTexture, UploadPBOPool};
 use crate::device::{ReadTarget, ShaderError, Texture, TextureFilter, TextureFlags, TextureSlot, Texel};
 use crate::device::query::{GpuSampler, GpuTimer};
-#[cfg(feature = "capture")]
-use crate::device::FBOId;
+use crate::device::{FBOId, MultisampledFbo};
 use crate::debug_item::DebugItem;
+use crate::frame_allocator::FrameAllocator;
 use crate::frame_builder::Frame;
+#[cfg(feature = "capture")]
+use crate::glyph_cache::GlyphAtlasEntry;
+use crate::glyph_cache::GlyphCacheExport;
 use glyph_rasterizer::GlyphFormat;
 use crate::gpu_cache::{GpuCacheUpdate, GpuCacheUpdateList};
 use crate::gpu_cache::{GpuCacheDebugChunk, GpuCacheDebugCmd};
 use crate::gpu_types::{ScalingInstance, SvgFilterInstance, SVGFEFilterInstance, CopyInstance, PrimitiveInstanceData};
-use crate::gpu_types::{BlurInstance, ClearInstance, CompositeInstance};
+use crate::gpu_types::{BlurDirection, BlurInstance, ClearInstance, CompositeInstance, CompositeVignette};
 use crate::internal_types::{TextureSource, TextureSourceExternal, TextureCacheCategory, FrameId, FrameVec};
 #[cfg(any(feature = "capture", feature = "replay"))]
 use crate::internal_types::DebugOutput;
-use crate::internal_types::{CacheTextureId, FastHashMap, FastHashSet, RenderedDocument, ResultMsg};
+use crate::internal_types::{CacheTextureId, FastHashMap, FastHashSet, FramesInFlight, RenderedDocument, ResultMsg};
 use crate::internal_types::{TextureCacheAllocInfo, TextureCacheAllocationKind, TextureUpdateList};
 use crate::internal_types::{RenderTargetInfo, Swizzle, DeferredResolveIndex};
-use crate::picture::ResolvedSurfaceTexture;
+use crate::picture::{ResolvedSurfaceTexture, TileId};
 use crate::prim_store::DeferredResolve;
 use crate::profiler::{self, GpuProfileTag, TransactionProfile};
 use crate::profiler::{Profiler, add_event_marker, add_text_marker, thread_is_being_profiled};
 use crate::device::query::GpuProfiler;
 use crate::render_target::{ResolveOp};
 use crate::render_task_graph::{RenderTaskGraph};
-use crate::render_task::{RenderTask, RenderTaskKind, ReadbackTask};
+use crate::render_task::{RenderTask, RenderTaskAddress, RenderTaskData, RenderTaskKind, ReadbackTask};
+use crate::render_task::{MAX_BLUR_STD_DEVIATION, MIN_DOWNSCALING_RT_SIZE};
 use crate::screen_capture::AsyncScreenshotGrabber;
 use crate::render_target::{AlphaRenderTarget, ColorRenderTarget, PictureCacheTarget, PictureCacheTargetKind};
 use crate::render_target::{RenderTarget, TextureCacheRenderTarget};
@@ -100,8 +104,8 @@ use euclid::{rect, Transform3D, Scale, default};
 use gleam::gl;
 use malloc_size_of::MallocSizeOfOps;
 
-#[cfg(feature = "replay")]
 use std::sync::Arc;
+use std::sync::atomic::Ordering;
 
 use std::{
     cell::RefCell,
@@ -113,7 +117,6 @@ use std::{
     path::PathBuf,
     rc::Rc,
 };
-#[cfg(any(feature = "capture", feature = "replay"))]
 use std::collections::hash_map::Entry;
 use time::precise_time_ns;
 
@@ -145,6 +148,10 @@ pub const VERTEX_DATA_TEXTURE_COUNT: usize = 3;
 /// Number of GPU blocks per UV rectangle provided for an image.
 pub const BLOCKS_PER_UV_RECT: usize = 2;
 
+/// How long each call to `Renderer::render` may spend finishing the links of
+/// shaders that were compiled asynchronously, in nanoseconds.
+const SHADER_LINK_BUDGET_NS: u64 = 8_000_000;
+
 const GPU_TAG_BRUSH_OPACITY: GpuProfileTag = GpuProfileTag {
     label: "B_Opacity",
     color: debug_colors::DARKMAGENTA,
@@ -282,7 +289,8 @@ impl BatchKind {
             BatchKind::Brush(kind) => {
                 match kind {
                     BrushBatchKind::Solid => GPU_TAG_BRUSH_SOLID,
-                    BrushBatchKind::Image(..) => GPU_TAG_BRUSH_IMAGE,
+                    BrushBatchKind::Image(..) |
+                    BrushBatchKind::WrappedImage(..) => GPU_TAG_BRUSH_IMAGE,
                     BrushBatchKind::Blend => GPU_TAG_BRUSH_BLEND,
                     BrushBatchKind::MixBlend { .. } => GPU_TAG_BRUSH_MIXBLEND,
                     BrushBatchKind::YuvImage(..) => GPU_TAG_BRUSH_YUV_IMAGE,
@@ -297,6 +305,14 @@ impl BatchKind {
             BatchKind::Quad(PatternKind::Mask) => GPU_TAG_INDIRECT_MASK,
         }
     }
+
+    /// The sampler wrap mode the batch's image texture needs, if any.
+    fn wrap_mode(&self) -> Option<ImageWrapMode> {
+        match *self {
+            BatchKind::Brush(BrushBatchKind::WrappedImage(_, wrap_mode)) => Some(wrap_mode),
+            _ => None,
+        }
+    }
 }
 
 fn flag_changed(before: DebugFlags, after: DebugFlags, select: DebugFlags) -> Option<bool> {
@@ -390,6 +406,73 @@ pub enum GraphicsApi {
     OpenGL,
 }
 
+/// How the alpha of the colors written to the framebuffer is encoded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AlphaMode {
+    /// Color channels are multiplied by alpha, as WebRender renders them.
+    Premultiplied,
+    /// Color channels are divided by alpha before being written, for
+    /// compositors that expect unassociated alpha.
+    Straight,
+}
+
+/// How the composited document is mapped to the framebuffer when their sizes
+/// don't match. Only supported by the draw compositor.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PresentMode {
+    /// Scale the document to fill the whole framebuffer.
+    Stretch,
+    /// Scale the document to fit the framebuffer while preserving its aspect
+    /// ratio, centering it and filling the remaining area with the given color.
+    Letterbox(ColorF),
+    /// Center the document in the framebuffer without scaling it.
+    Center,
+}
+
+impl PresentMode {
+    /// Returns the rect the document is presented into, with a top-left
+    /// origin, for a document of `document_size` in a framebuffer of
+    /// `framebuffer_size`.
+    fn present_rect(
+        &self,
+        document_size: DeviceIntSize,
+        framebuffer_size: DeviceIntSize,
+    ) -> DeviceIntRect {
+        let size = match *self {
+            PresentMode::Stretch => framebuffer_size,
+            PresentMode::Letterbox(..) => {
+                let scale = (framebuffer_size.width as f32 / document_size.width as f32)
+                    .min(framebuffer_size.height as f32 / document_size.height as f32);
+                (document_size.to_f32() * scale).round().to_i32()
+            }
+            PresentMode::Center => document_size,
+        };
+
+        DeviceIntRect::from_origin_and_size(
+            DeviceIntPoint::new(
+                (framebuffer_size.width - size.width) / 2,
+                (framebuffer_size.height - size.height) / 2,
+            ),
+            size,
+        )
+    }
+}
+
+/// A radial darkening of the composited frame towards its edges, centered on
+/// the document. Only supported by the draw compositor.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VignetteParams {
+    /// Distance from the center, in device pixels, within which the content
+    /// is left unchanged.
+    pub inner_radius: f32,
+    /// Distance from the center, in device pixels, at which the content is
+    /// fully blended towards `color`.
+    pub outer_radius: f32,
+    /// The color blended in towards the edges. Its alpha is the strength of
+    /// the effect.
+    pub color: ColorF,
+}
+
 #[derive(Clone, Debug)]
 pub struct GraphicsApiInfo {
     pub kind: GraphicsApi,
@@ -399,12 +482,13 @@ pub struct GraphicsApiInfo {
 
 #[derive(Debug)]
 pub struct GpuProfile {
-    pub frame_id: GpuFrameId,
+    /// The `RenderResults::frame_id` of the frame these timings belong to.
+    pub frame_id: u64,
     pub paint_time_ns: u64,
 }
 
 impl GpuProfile {
-    fn new(frame_id: GpuFrameId, timers: &[GpuTimer]) -> GpuProfile {
+    fn new(frame_id: u64, timers: &[GpuTimer]) -> GpuProfile {
         let mut paint_time_ns = 0;
         for timer in timers {
             paint_time_ns += timer.time_ns;
@@ -418,7 +502,8 @@ impl GpuProfile {
 
 #[derive(Debug)]
 pub struct CpuProfile {
-    pub frame_id: GpuFrameId,
+    /// The `RenderResults::frame_id` of the frame these timings belong to.
+    pub frame_id: u64,
     pub backend_time_ns: u64,
     pub composite_time_ns: u64,
     pub draw_calls: usize,
@@ -426,7 +511,7 @@ pub struct CpuProfile {
 
 impl CpuProfile {
     fn new(
-        frame_id: GpuFrameId,
+        frame_id: u64,
         backend_time_ns: u64,
         composite_time_ns: u64,
         draw_calls: usize,
@@ -512,7 +597,12 @@ impl TextureResolver {
         &mut self,
         device: &mut Device,
         textures_to_invalidate: &[CacheTextureId],
+        invalidate: bool,
     ) {
+        if !invalidate {
+            return;
+        }
+
         // For any texture that is no longer needed, immediately
         // invalidate it so that tiled GPUs don't need to resolve it
         // back to memory.
@@ -548,6 +638,21 @@ impl TextureResolver {
         }
     }
 
+    // Set how the sampler wraps a given source texture. Only texture cache
+    // textures are allocated standalone, so other sources are left alone.
+    fn set_wrap_mode(
+        &self,
+        texture_id: &TextureSource,
+        sampler: TextureSampler,
+        wrap_mode: ImageWrapMode,
+        device: &mut Device,
+    ) {
+        if let TextureSource::TextureCache(index, _) = *texture_id {
+            let texture = &self.texture_cache_map[&index].texture;
+            device.set_texture_wrap_mode(sampler, texture, wrap_mode);
+        }
+    }
+
     // Get the real (OpenGL) texture ID for a given source texture.
     // For a texture cache texture, the IDs are stored in a vector
     // map for fast access.
@@ -715,6 +820,47 @@ impl DebugOverlayState {
     }
 }
 
+/// Tracks which external images the renderer currently holds a lock on, so
+/// that handler lifecycle bugs (an image that is never unlocked) can be
+/// observed. Locks are counted since the same image may be locked more than
+/// once during an update.
+#[derive(Default)]
+struct ExternalImageLocks {
+    counts: FastHashMap<(ExternalImageId, u8), usize>,
+}
+
+impl ExternalImageLocks {
+    fn lock<'a, H: ExternalImageHandler + ?Sized>(
+        &mut self,
+        handler: &'a mut H,
+        id: ExternalImageId,
+        channel_index: u8,
+    ) -> ExternalImage<'a> {
+        let image = handler.lock(id, channel_index);
+        *self.counts.entry((id, channel_index)).or_insert(0) += 1;
+        image
+    }
+
+    fn unlock<H: ExternalImageHandler + ?Sized>(
+        &mut self,
+        handler: &mut H,
+        id: ExternalImageId,
+        channel_index: u8,
+    ) {
+        handler.unlock(id, channel_index);
+        if let Entry::Occupied(mut entry) = self.counts.entry((id, channel_index)) {
+            *entry.get_mut() -= 1;
+            if *entry.get() == 0 {
+                entry.remove();
+            }
+        }
+    }
+
+    fn to_vec(&self) -> Vec<(ExternalImageId, u8)> {
+        self.counts.keys().cloned().collect()
+    }
+}
+
 /// Tracks buffer damage rects over a series of frames.
 #[derive(Debug, Default)]
 pub(crate) struct BufferDamageTracker {
@@ -784,20 +930,53 @@ pub struct Renderer {
     max_recorded_profiles: usize,
 
     clear_color: ColorF,
+    /// Per-document overrides of `clear_color`.
+    document_clear_colors: FastHashMap<DocumentId, ColorF>,
     enable_clear_scissor: bool,
     enable_advanced_blend_barriers: bool,
     clear_caches_with_quads: bool,
+    early_gl_flush: bool,
+    invalidate_targets_on_pass_end: bool,
     clear_alpha_targets_with_quads: bool,
+    /// Number of samples used to rasterize clip masks, or zero if disabled.
+    clip_mask_msaa_samples: u8,
+    /// The multisampled target clip masks are drawn into before being
+    /// resolved into the alpha target texture.
+    clip_mask_msaa_target: Option<MultisampledFbo>,
+    /// Alpha encoding of the framebuffer output.
+    output_alpha_mode: AlphaMode,
+    /// Copy of the framebuffer that is un-premultiplied back into it when
+    /// the output alpha mode is straight.
+    output_alpha_texture: Option<Texture>,
 
     debug: debug::LazyInitializedDebugRenderer,
     debug_flags: DebugFlags,
+    primitive_isolation: Option<IsolatedBatchKind>,
+    /// See `Renderer::set_opaque_only`.
+    opaque_only: bool,
+
+    /// File that GL calls are appended to while `DebugFlags::TRACE_GL_CALLS` is set.
+    #[cfg(feature = "gl_trace")]
+    gl_trace_path: PathBuf,
     profile: TransactionProfile,
     frame_counter: u64,
     resource_upload_time: f64,
     gpu_cache_upload_time: f64,
+    /// Whether the GPU cache texture was reallocated at a larger size since
+    /// the last rendered frame.
+    gpu_cache_resized: bool,
+    /// Bytes uploaded to the texture cache since the last rendered frame.
+    texture_upload_bytes: usize,
+    texture_upload_budget_bytes: Option<usize>,
     profiler: Profiler,
 
     last_time: u64,
+    /// When `update` first found no new frame from the render backend since
+    /// the last render, in nanoseconds.
+    backend_wait_start: Option<u64>,
+    /// Time in milliseconds that `update` waited on the render backend for a
+    /// requested frame since the last render.
+    backend_wait_time: f64,
 
     pub gpu_profiler: GpuProfiler,
     vaos: vertex::RendererVAOs,
@@ -828,10 +1007,27 @@ pub struct Renderer {
     /// application to provide external buffers for image data.
     external_image_handler: Option<Box<dyn ExternalImageHandler>>,
 
+    /// Optional hook supplying the textures of the shared texture cache.
+    texture_allocator_hook: Option<Box<dyn TextureAllocatorHook>>,
+
+    /// External images locked through `external_image_handler` and not yet
+    /// unlocked.
+    locked_external_images: ExternalImageLocks,
+
     /// Optional function pointers for measuring memory used by a given
     /// heap-allocated pointer.
     size_of_ops: Option<MallocSizeOfOps>,
 
+    /// The high-water mark of the GPU memory usage, see `peak_gpu_memory`.
+    peak_memory: MemoryReport,
+
+    /// See `WebRenderOptions::frame_summary_sink`.
+    frame_summary_sink: Option<Arc<dyn Fn(FrameSummary) + Send + Sync>>,
+    /// See `WebRenderOptions::dirty_rect_hook`.
+    dirty_rect_hook: Option<Arc<dyn Fn(DeviceIntRect) -> DeviceIntRect + Send + Sync>>,
+    /// The GPU time of the last frame whose timer queries have resolved.
+    last_gpu_time_ns: Option<u64>,
+
     pub renderer_errors: Vec<RendererError>,
 
     pub(in crate) async_frame_recorder: Option<AsyncScreenshotGrabber>,
@@ -854,6 +1050,9 @@ pub struct Renderer {
     /// functionality only, such as the debug zoom widget.
     cursor_position: DeviceIntPoint,
 
+    /// The spacing of the lines drawn by `DebugFlags::LAYOUT_GRID_DBG`.
+    debug_grid_spacing: DeviceIntSize,
+
     /// Guards to check if we might be rendering a frame with expired texture
     /// cache entries.
     shared_texture_cache_cleared: bool,
@@ -871,6 +1070,26 @@ pub struct Renderer {
 
     current_compositor_kind: CompositorKind,
 
+    /// If true, the native compositor's frame is ended by `present` rather
+    /// than at the end of `render`.
+    explicit_native_present: bool,
+
+    /// The document whose native compositor frame was drawn but not
+    /// presented yet.
+    pending_native_present: Option<DocumentId>,
+
+    /// Set while shaders compiled with `WebRenderOptions::async_shader_compile`
+    /// aren't all linked yet.
+    pending_shader_compile: bool,
+
+    /// Used to ask the embedder for another `render` while shaders are
+    /// still being compiled.
+    notifier: Box<dyn RenderNotifier>,
+
+    /// Shared with the render backend, which defers building frames once
+    /// `max_frames_in_flight` frames are published.
+    frames_in_flight: Arc<FramesInFlight>,
+
     /// Maintains a set of allocated native composite surfaces. This allows any
     /// currently allocated surfaces to be cleaned up as soon as deinit() is
     /// called (the normal bookkeeping for native surfaces exists in the
@@ -881,13 +1100,53 @@ pub struct Renderer {
     /// be drawn on the next render.
     force_redraw: bool,
 
+    /// Saturation applied when compositing picture cache tiles and compositor
+    /// surfaces into the framebuffer. 1.0 leaves colors unchanged.
+    global_saturation: f32,
+
+    /// Vignette applied when compositing, after the global saturation.
+    vignette: Option<VignetteParams>,
+
+    /// External image that is blurred and composited behind the document
+    /// while a window backdrop blur is set.
+    window_backdrop_image: Option<ExternalImageData>,
+    /// Standard deviation of the window backdrop blur, in device pixels.
+    window_backdrop_blur: Option<f32>,
+    /// Targets the window backdrop is downscaled and blurred in. The blurred
+    /// backdrop ends up in the first one.
+    window_backdrop_targets: Option<(Texture, Texture)>,
+    /// Render task data for the window backdrop blur passes. The blur shader
+    /// reads its target rect from there, and the backdrop isn't a task of the
+    /// frame, so it has its own single-entry texture.
+    window_backdrop_task_texture: vertex::VertexDataTexture<RenderTaskData>,
+
+    /// If true, compositing writes the linear depth of each composited surface
+    /// to the depth attachment of the framebuffer.
+    output_linear_depth: bool,
+
+    /// How the document is mapped to the framebuffer. None presents the
+    /// document at its own device rect.
+    present_mode: Option<PresentMode>,
+
+    /// An exact factor the document is upscaled by when presented, see
+    /// `set_integer_scaling`. Takes precedence over `present_mode`.
+    integer_scaling: Option<u32>,
+
+    /// The target the document is composited into before it is upsampled to
+    /// the framebuffer, while it is rendered below full resolution.
+    render_scale_target: Option<Texture>,
+
+    /// Margins of the framebuffer that are left cleared, with the document
+    /// presented inside them.
+    safe_area_insets: DeviceIntSideOffsets,
+
     /// State related to the debug / profiling overlays
     debug_overlay_state: DebugOverlayState,
 
-    /// Tracks the dirty rectangles from previous frames. Used on platforms
-    /// that require keeping the front buffer fully correct when doing
-    /// partial present (e.g. unix desktop with EGL_EXT_buffer_age).
-    buffer_damage_tracker: BufferDamageTracker,
+    /// Tracks the dirty rectangles from previous frames of each document. Used
+    /// on platforms that require keeping the front buffer fully correct when
+    /// doing partial present (e.g. unix desktop with EGL_EXT_buffer_age).
+    buffer_damage_trackers: FastHashMap<DocumentId, BufferDamageTracker>,
 
     max_primitive_instance_count: usize,
     enable_instancing: bool,
@@ -911,6 +1170,8 @@ pub enum RendererError {
     MaxTextureSize,
     SoftwareRasterizer,
     OutOfMemory,
+    ContextLost,
+    ReadPixelsSizeMismatch,
 }
 
 impl From<ShaderError> for RendererError {
@@ -938,10 +1199,46 @@ impl Renderer {
         self.cursor_position = position;
     }
 
+    /// Set the spacing, in device pixels, of the grid drawn over the
+    /// framebuffer when `DebugFlags::LAYOUT_GRID_DBG` is enabled. Defaults to
+    /// 8 pixels.
+    pub fn set_debug_grid_spacing(&mut self, spacing: DeviceIntSize) {
+        self.debug_grid_spacing = DeviceIntSize::new(spacing.width.max(1), spacing.height.max(1));
+    }
+
+    /// Set the number of samples used when rasterizing clip masks. Zero
+    /// disables multisampling. The count is clamped to the maximum supported
+    /// by the device.
+    pub fn set_clip_mask_msaa_samples(&mut self, samples: u8) {
+        // A single sample is the same as no multisampling at all.
+        let samples = match samples.min(self.device.max_samples()) {
+            1 => 0,
+            samples => samples,
+        };
+        if samples == self.clip_mask_msaa_samples {
+            return;
+        }
+        self.clip_mask_msaa_samples = samples;
+        if let Some(target) = self.clip_mask_msaa_target.take() {
+            self.device.delete_multisampled_fbo(target);
+        }
+        self.force_redraw();
+    }
+
+    /// Returns the number of samples used when rasterizing clip masks, after
+    /// clamping to the device limits.
+    pub fn clip_mask_msaa_samples(&self) -> u8 {
+        self.clip_mask_msaa_samples
+    }
+
     pub fn get_max_texture_size(&self) -> i32 {
         self.device.max_texture_size()
     }
 
+    pub fn get_max_depth_ids(&self) -> i32 {
+        self.device.max_depth_ids()
+    }
+
     pub fn get_graphics_api_info(&self) -> GraphicsApiInfo {
         GraphicsApiInfo {
             kind: GraphicsApi::OpenGL,
@@ -962,6 +1259,271 @@ impl Renderer {
         self.clear_color = color;
     }
 
+    /// Override the clear color for a single document, taking precedence over
+    /// `set_clear_color`. Passing `None` removes the override.
+    pub fn set_document_clear_color(&mut self, document_id: DocumentId, color: Option<ColorF>) {
+        match color {
+            Some(color) => {
+                self.document_clear_colors.insert(document_id, color);
+            }
+            None => {
+                self.document_clear_colors.remove(&document_id);
+            }
+        }
+    }
+
+    /// Returns the color the framebuffer will be cleared to when the document
+    /// is next rendered, or `None` if the renderer has no frame for it. The
+    /// per-document override takes precedence over `set_clear_color`, which in
+    /// turn replaces `WebRenderOptions::clear_color`.
+    pub fn effective_clear_color(&self, document_id: DocumentId) -> Option<ColorF> {
+        if !self.active_documents.contains_key(&document_id) {
+            return None;
+        }
+
+        Some(self.resolve_clear_color(document_id))
+    }
+
+    fn resolve_clear_color(&self, document_id: DocumentId) -> ColorF {
+        self.document_clear_colors
+            .get(&document_id)
+            .cloned()
+            .unwrap_or(self.clear_color)
+    }
+
+    /// Returns the capabilities reported by the native compositor, or `None`
+    /// when compositing with the draw compositor. Overrides made with
+    /// `DebugCommand::SetCompositorMaxUpdateRects` are reflected once a frame
+    /// built with them has been rendered.
+    pub fn compositor_capabilities(&self) -> Option<CompositorCapabilities> {
+        match self.current_compositor_kind {
+            CompositorKind::Native { capabilities, .. } => Some(capabilities),
+            CompositorKind::Draw { .. } => None,
+        }
+    }
+
+    /// Returns the compositor kind the last rendered frame was composited
+    /// with. This can change at runtime, e.g. with
+    /// `DebugCommand::EnableNativeCompositor`, so embedders that present
+    /// differently for each kind should check it after each render.
+    pub fn last_frame_compositor_kind(&self) -> CompositorKind {
+        self.current_compositor_kind
+    }
+
+    /// Set the saturation applied to the whole frame when it is composited,
+    /// e.g. to dim inactive windows. 1.0 is the normal saturation and 0.0 is
+    /// fully grayscale. This is only supported by the draw compositor.
+    pub fn set_global_saturation(&mut self, saturation: f32) {
+        let saturation = saturation.max(0.0);
+        if saturation != self.global_saturation {
+            self.global_saturation = saturation;
+            self.force_redraw();
+        }
+    }
+
+    /// Set whether the colors written to the framebuffer have premultiplied
+    /// or straight alpha. Straight alpha costs an extra full-screen pass and
+    /// disables partial present. Only supported by the draw compositor.
+    pub fn set_output_alpha_mode(&mut self, mode: AlphaMode) {
+        if mode != self.output_alpha_mode {
+            self.output_alpha_mode = mode;
+            self.force_redraw();
+        }
+    }
+
+    /// Set a vignette that darkens the composited frame towards its edges,
+    /// e.g. to draw attention to a modal dialog. It is applied after the
+    /// global saturation. This is only supported by the draw compositor.
+    pub fn set_vignette(&mut self, vignette: Option<VignetteParams>) {
+        if vignette != self.vignette {
+            self.vignette = vignette;
+            self.force_redraw();
+        }
+    }
+
+    /// Set the external image that is composited behind the document while a
+    /// window backdrop blur is set. It must be a texture handle, and is
+    /// stretched over the whole document.
+    pub fn set_window_backdrop_image(&mut self, image: Option<ExternalImageData>) {
+        if image != self.window_backdrop_image {
+            self.window_backdrop_image = image;
+            self.force_redraw();
+        }
+    }
+
+    /// Blur the window backdrop image with the given standard deviation, in
+    /// device pixels, and composite the document over it, for frosted-glass
+    /// windows on platforms without a native backdrop blur. The backdrop shows
+    /// through wherever the document is transparent. `None` disables it.
+    ///
+    /// This is only supported by the draw compositor. Partial present is
+    /// disabled while it is set, and the embedder should request a redraw
+    /// whenever the contents of the backdrop image change.
+    pub fn set_window_backdrop_blur(&mut self, std_deviation: Option<f32>) {
+        if std_deviation != self.window_backdrop_blur {
+            self.window_backdrop_blur = std_deviation;
+            self.force_redraw();
+        }
+    }
+
+    /// Change the maximum number of frames published by the render backend
+    /// that haven't been rendered yet. See
+    /// `WebRenderOptions::max_frames_in_flight`.
+    pub fn set_max_frames_in_flight(&mut self, max_frames_in_flight: usize) {
+        self.frames_in_flight.set_max(max_frames_in_flight);
+    }
+
+    /// Change the per-frame texture upload budget. See
+    /// `WebRenderOptions::texture_upload_budget_bytes`.
+    pub fn set_texture_upload_budget_bytes(&mut self, budget: Option<usize>) {
+        self.texture_upload_budget_bytes = budget;
+    }
+
+    /// Present the frame drawn for `document_id` by the last call to `render`,
+    /// by ending the native compositor's frame. Only needed when
+    /// `WebRenderOptions::explicit_native_present` is set, in which case
+    /// `render` draws the frame without presenting it. Returns false if there
+    /// is no frame of this document waiting to be presented.
+    ///
+    /// A frame that isn't presented is presented when `render` is next
+    /// called, before the next frame is drawn, since the compositor expects
+    /// each frame it begins to be ended.
+    pub fn present(&mut self, document_id: DocumentId) -> bool {
+        if self.pending_native_present != Some(document_id) {
+            return false;
+        }
+
+        self.end_native_compositor_frame();
+        true
+    }
+
+    fn end_native_compositor_frame(&mut self) {
+        if self.pending_native_present.take().is_none() {
+            return;
+        }
+
+        profile_scope!("compositor.end_frame");
+        let compositor = self.compositor_config.compositor().unwrap();
+        compositor.end_frame(&mut self.device);
+    }
+
+    /// Enable or disable writing linear depth when compositing into the
+    /// framebuffer. See `WebRenderOptions::output_linear_depth`.
+    pub fn set_output_linear_depth(&mut self, enabled: bool) {
+        if enabled != self.output_linear_depth {
+            self.output_linear_depth = enabled;
+            self.force_redraw();
+        }
+    }
+
+    /// Set how the composited document is mapped to the framebuffer when
+    /// their sizes don't match. Passing None presents the document at its
+    /// own device rect. This is only supported by the draw compositor, and
+    /// disables partial present while set.
+    pub fn set_present_mode(&mut self, present_mode: Option<PresentMode>) {
+        if present_mode != self.present_mode {
+            self.present_mode = present_mode;
+            self.force_redraw();
+        }
+    }
+
+    /// Upscale the document by an exact integer factor when it is presented,
+    /// centering it in the framebuffer and clearing the remainder to the
+    /// clear color. The document is composited at its own size into an
+    /// intermediate target, which is upscaled with nearest filtering so that
+    /// each document pixel becomes a hard-edged block, as pixel-art apps
+    /// expect, whatever filter the picture cache textures use. A document
+    /// larger than the framebuffer at this scale is cropped. This overrides
+    /// the present mode, is only supported by the draw compositor, and
+    /// disables partial present while set.
+    pub fn set_integer_scaling(&mut self, factor: Option<u32>) {
+        let factor = factor.map(|factor| factor.max(1));
+        if factor != self.integer_scaling {
+            self.integer_scaling = factor;
+            self.force_redraw();
+        }
+    }
+
+    /// Rasterize the document at its device size multiplied by `scale`, and
+    /// upsample it to the device size with bilinear filtering when it is
+    /// composited, trading sharpness for GPU time. The scale is clamped to
+    /// [0.1, 1] and takes effect when the next scene is built. This is only
+    /// supported by the draw compositor, and disables partial present while
+    /// the scale is below 1.
+    pub fn set_render_scale(&self, scale: f32) {
+        let scale = scale.max(0.1).min(1.0);
+        let _ = self.api_tx.send(ApiMsg::DebugCommand(DebugCommand::SetRenderScale(scale)));
+    }
+
+    /// Set the width in device pixels over which anti-aliased edges fade out,
+    /// clamped to [0.5, 2]. Takes effect when the next scene is built. See
+    /// `WebRenderOptions::edge_aa_feather`.
+    pub fn set_edge_aa_feather(&self, feather: f32) {
+        let feather = feather.max(0.5).min(2.0);
+        let _ = self.api_tx.send(ApiMsg::DebugCommand(DebugCommand::SetEdgeAaFeather(feather)));
+    }
+
+    /// Whether all shaders are linked. This is only false for a while after
+    /// the renderer is created with `WebRenderOptions::async_shader_compile`,
+    /// during which draws that need the missing shaders are skipped.
+    pub fn shaders_are_ready(&self) -> bool {
+        !self.pending_shader_compile
+    }
+
+    /// Finishes linking the asynchronously compiled shaders that the driver
+    /// is done with, within the per-frame budget. Once all are linked, the
+    /// render backend is asked to redraw everything that was drawn without
+    /// them.
+    fn link_pending_shaders(&mut self) -> Result<(), RendererError> {
+        self.device.begin_frame();
+        let deadline = precise_time_ns() + SHADER_LINK_BUDGET_NS;
+        let linked = self.shaders
+            .borrow_mut()
+            .link_pending(&mut self.device, deadline, &mut self.profile);
+        self.device.end_frame();
+
+        if linked? {
+            self.pending_shader_compile = false;
+            self.force_redraw();
+            let _ = self.api_tx.send(ApiMsg::ShadersReady);
+        }
+
+        Ok(())
+    }
+
+    /// The size of the target the last frame was composited into before it
+    /// was upsampled to the framebuffer, if it was rendered below full
+    /// resolution or with integer scaling.
+    pub fn render_scale_target_size(&self) -> Option<DeviceIntSize> {
+        self.render_scale_target.as_ref().map(|texture| texture.get_dimensions())
+    }
+
+    /// Set margins of the framebuffer that content isn't composited into, e.g.
+    /// to avoid a display notch or rounded corners. The document is offset
+    /// by the top-left insets (or presented into the remaining area when a
+    /// present mode is set), and the insets are cleared to the clear color.
+    /// This is only supported by the draw compositor, and disables partial
+    /// present while set, so that the reported dirty rect always covers the
+    /// whole framebuffer including the insets.
+    pub fn set_safe_area_insets(&mut self, insets: DeviceIntSideOffsets) {
+        if insets != self.safe_area_insets {
+            self.safe_area_insets = insets;
+            self.force_redraw();
+        }
+    }
+
+    /// Toggle the GL flush done before drawing each frame. See
+    /// `WebRenderOptions::early_gl_flush`.
+    pub fn set_early_gl_flush(&mut self, enabled: bool) {
+        self.early_gl_flush = enabled;
+    }
+
+    /// Toggle the invalidation of no longer needed render targets at the end
+    /// of each pass. See `WebRenderOptions::invalidate_targets_on_pass_end`.
+    pub fn set_invalidate_targets_on_pass_end(&mut self, enabled: bool) {
+        self.invalidate_targets_on_pass_end = enabled;
+    }
+
     pub fn flush_pipeline_info(&mut self) -> PipelineInfo {
         mem::replace(&mut self.pipeline_info, PipelineInfo::default())
     }
@@ -996,6 +1558,9 @@ impl Renderer {
     pub fn update(&mut self) {
         profile_scope!("update");
 
+        let mut frame_published = false;
+        let mut frame_requested_time: Option<u64> = None;
+
         // Pull any pending results and return the most recent.
         while let Some(msg) = self.get_next_result_msg() {
             match msg {
@@ -1011,6 +1576,11 @@ impl Renderer {
                     mut doc,
                     resource_update_list,
                 ) => {
+                    frame_published = true;
+                    if let Some(time) = doc.frame_requested_time {
+                        frame_requested_time = Some(frame_requested_time.map_or(time, |t| t.min(time)));
+                    }
+
                     // Add a new document to the active set
 
                     // If the document we are replacing must be drawn (in order to
@@ -1022,6 +1592,12 @@ impl Renderer {
                     let prev_frame_memory = if let Some(mut prev_doc) = self.active_documents.remove(&document_id) {
                         doc.profile.merge(&mut prev_doc.profile);
 
+                        // Transactions of a frame that is replaced before being
+                        // presented are reported with the frame replacing it.
+                        let mut trace_ids = mem::take(&mut prev_doc.trace_ids);
+                        trace_ids.append(&mut doc.trace_ids);
+                        doc.trace_ids = trace_ids;
+
                         if prev_doc.frame.must_be_drawn() {
                             prev_doc.render_reasons |= RenderReasons::TEXTURE_CACHE_FLUSH;
                             self.render_impl(
@@ -1174,14 +1750,38 @@ impl Renderer {
                     #[cfg(feature = "replay")]
                     DebugOutput::LoadCapture(config, plain_externals) => {
                         self.active_documents.clear();
+                        self.buffer_damage_trackers.clear();
                         self.load_capture(config, plain_externals);
                     }
+                    #[cfg(feature = "capture")]
+                    DebugOutput::ExportGlyphAtlas(root, entries) => {
+                        if let Err(e) = self.save_glyph_atlas(root.clone(), entries) {
+                            error!("Unable to export glyph atlas to {:?}: {:?}", root, e);
+                        }
+                    }
                 },
                 ResultMsg::DebugCommand(command) => {
                     self.handle_debug_command(command);
                 }
             }
         }
+
+        // Measure how long updates found no new frame from the render backend
+        // while one had been requested, i.e. how long the renderer sat idle
+        // waiting on `result_rx` for a frame it asked for. Time before the
+        // request is the embedder being idle, and isn't counted.
+        let now = precise_time_ns();
+        if frame_published {
+            let start = self.backend_wait_start.take();
+            if let (Some(start), Some(requested)) = (start, frame_requested_time) {
+                let start = start.max(requested);
+                if now > start {
+                    self.backend_wait_time += profiler::ns_to_ms(now - start);
+                }
+            }
+        } else if self.backend_wait_start.is_none() {
+            self.backend_wait_start = Some(now);
+        }
     }
 
     /// update() defers processing of ResultMsg, if frame_publish_id of
@@ -1193,17 +1793,28 @@ impl Renderer {
     fn handle_debug_command(&mut self, command: DebugCommand) {
         match command {
             DebugCommand::SetPictureTileSize(_) |
-            DebugCommand::SetMaximumSurfaceSize(_) => {
+            DebugCommand::SetMaximumSurfaceSize(_) |
+            DebugCommand::SetMaxDepthIds(_) |
+            DebugCommand::SetSrgbRenderTargets(_) |
+            DebugCommand::SetCullingMargin(_) |
+            DebugCommand::SetMaxBlurRasterSize(_) |
+            DebugCommand::SetCompositorMaxUpdateRects(_) |
+            DebugCommand::SetRenderScale(_) |
+            DebugCommand::SetEdgeAaFeather(_) |
+            DebugCommand::SetShowClipBounds(_) => {
                 panic!("Should be handled by render backend");
             }
             DebugCommand::SaveCapture(..) |
             DebugCommand::LoadCapture(..) |
             DebugCommand::StartCaptureSequence(..) |
-            DebugCommand::StopCaptureSequence => {
+            DebugCommand::StopCaptureSequence |
+            DebugCommand::ExportGlyphAtlas(..) => {
                 panic!("Capture commands are not welcome here! Did you build with 'capture' feature?")
             }
             DebugCommand::ClearCaches(_)
+            | DebugCommand::ReuploadTextureCache
             | DebugCommand::SimulateLongSceneBuild(_)
+            | DebugCommand::SimulateLongLowPrioritySceneBuild(_)
             | DebugCommand::EnableNativeCompositor(_)
             | DebugCommand::SetBatchingLookback(_) => {}
             DebugCommand::InvalidateGpuCache => {
@@ -1220,6 +1831,83 @@ impl Renderer {
         self.external_image_handler = Some(handler);
     }
 
+    /// Set the hook consulted when allocating shared texture cache textures.
+    /// Textures already supplied by a previous hook are still released
+    /// through the hook that is set when they are freed.
+    pub fn set_texture_allocator_hook(&mut self, hook: Option<Box<dyn TextureAllocatorHook>>) {
+        self.texture_allocator_hook = hook;
+    }
+
+    /// Delete a texture cache texture, handing it back to the texture
+    /// allocator hook if it was supplied by it.
+    fn delete_cache_texture(&mut self, texture: Texture) {
+        if texture.flags().contains(TextureFlags::IS_EMBEDDER_OWNED) {
+            if let Some(ref mut hook) = self.texture_allocator_hook {
+                hook.release(texture.gl_id());
+            }
+        }
+        self.device.delete_texture(texture);
+    }
+
+    /// Append the GL calls recorded for a frame to the GL trace file.
+    #[cfg(feature = "gl_trace")]
+    fn write_gl_trace(&mut self, frame_id: u64) {
+        use std::io::Write;
+
+        let calls = self.device.end_gl_trace();
+        if calls.is_empty() {
+            return;
+        }
+
+        let result = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.gl_trace_path)
+            .and_then(|mut file| {
+                writeln!(file, "# frame {}", frame_id)?;
+                for call in &calls {
+                    writeln!(file, "{}", call)?;
+                }
+                Ok(())
+            });
+
+        if let Err(e) = result {
+            error!("Failed to write GL trace to {:?}: {}", self.gl_trace_path, e);
+        }
+    }
+
+    /// Export the glyph cache atlases as images, along with a `glyphs.txt`
+    /// listing which glyph occupies each region, into the given directory.
+    ///
+    /// The request round-trips through the render backend, so the files are
+    /// written during a subsequent call to `update`.
+    #[cfg(feature = "capture")]
+    pub fn export_glyph_atlas(&self, root: PathBuf) {
+        let _ = self.api_tx.send(ApiMsg::DebugCommand(DebugCommand::ExportGlyphAtlas(root)));
+    }
+
+    /// Synchronously collects the rasterized glyph bitmaps currently in the
+    /// glyph cache, so that they can be passed to a new renderer through
+    /// `WebRenderOptions::glyph_cache_import`.
+    ///
+    /// The export is empty unless `WebRenderOptions::retain_glyph_bitmaps` was
+    /// enabled, since glyph bitmaps otherwise only live in GPU memory.
+    pub fn export_glyph_cache(&self) -> GlyphCacheExport {
+        let (tx, rx) = single_msg_channel();
+        if self.api_tx.send(ApiMsg::ExportGlyphCache(tx)).is_err() {
+            return GlyphCacheExport::default();
+        }
+        rx.recv().unwrap_or_default()
+    }
+
+    /// Returns the external images that are currently locked, i.e. for which
+    /// `ExternalImageHandler::lock` has been called without a matching
+    /// `unlock`. Outside of an in-progress update this is expected to be
+    /// empty; anything left here points to a lock that was never released.
+    pub fn locked_external_images(&self) -> Vec<(ExternalImageId, u8)> {
+        self.locked_external_images.to_vec()
+    }
+
     /// Retrieve (and clear) the current list of recorded frame profiles.
     pub fn get_frame_profiles(&mut self) -> (Vec<CpuProfile>, Vec<GpuProfile>) {
         let cpu_profiles = self.cpu_profiles.drain(..).collect();
@@ -1238,13 +1926,14 @@ impl Renderer {
     /// A Frame is supplied by calling [`generate_frame()`][webrender_api::Transaction::generate_frame].
     /// buffer_age is the age of the current backbuffer. It is only relevant if partial present
     /// is active, otherwise 0 should be passed here.
+    ///
+    /// With a native compositor and `WebRenderOptions::explicit_native_present`, the frame
+    /// is drawn but not presented until [`present()`][Renderer::present] is called.
     pub fn render(
         &mut self,
         device_size: DeviceIntSize,
         buffer_age: usize,
     ) -> Result<RenderResults, Vec<RendererError>> {
-        self.device_size = Some(device_size);
-
         // TODO(gw): We want to make the active document that is
         //           being rendered configurable via the public
         //           API in future. For now, just select the last
@@ -1253,6 +1942,43 @@ impl Renderer {
         //           per renderer right now).
         let doc_id = self.active_documents.keys().last().cloned();
 
+        self.render_internal(doc_id, device_size, buffer_age)
+    }
+
+    /// Renders the current frame of a specific document.
+    ///
+    /// This is the same as [`render()`][Renderer::render], for embedders that
+    /// drive more than one document with a single renderer. Each document keeps
+    /// its own partial present history, so buffer_age is the age of the
+    /// backbuffer of the surface this document is drawn into. If the document
+    /// has no frame yet, nothing is drawn.
+    pub fn render_document(
+        &mut self,
+        doc_id: DocumentId,
+        device_size: DeviceIntSize,
+        buffer_age: usize,
+    ) -> Result<RenderResults, Vec<RendererError>> {
+        let doc_id = Some(doc_id).filter(|id| self.active_documents.contains_key(id));
+
+        self.render_internal(doc_id, device_size, buffer_age)
+    }
+
+    fn render_internal(
+        &mut self,
+        doc_id: Option<DocumentId>,
+        device_size: DeviceIntSize,
+        buffer_age: usize,
+    ) -> Result<RenderResults, Vec<RendererError>> {
+        self.device_size = Some(device_size);
+
+        // Draw with whichever shaders are ready. Draws that need the others
+        // are skipped until they are linked.
+        if self.pending_shader_compile {
+            if let Err(e) = self.link_pending_shaders() {
+                return Err(vec![e]);
+            }
+        }
+
         let result = match doc_id {
             Some(doc_id) => {
                 // Remove the doc from the map to appease the borrow checker
@@ -1302,6 +2028,11 @@ impl Renderer {
             self.consecutive_oom_frames = 0;
         }
 
+        // Keep rendering until the shaders are ready, even if nothing changes.
+        if self.pending_shader_compile {
+            self.notifier.wake_up(true);
+        }
+
         // This is the end of the rendering pipeline. If some notifications are is still there,
         // just clear them and they will autimatically fire the Checkpoint::TransactionDropped
         // event. Otherwise they would just pile up in this vector forever.
@@ -1329,7 +2060,8 @@ impl Renderer {
             DebugFlags::PICTURE_CACHING_DBG |
             DebugFlags::PRIMITIVE_DBG |
             DebugFlags::ZOOM_DBG |
-            DebugFlags::WINDOW_VISIBILITY_DBG
+            DebugFlags::WINDOW_VISIBILITY_DBG |
+            DebugFlags::LAYOUT_GRID_DBG
         );
 
         // Update the debug overlay surface, if we are running in native compositor mode.
@@ -1428,6 +2160,7 @@ impl Renderer {
                     DeviceIntRect::from_size(
                         self.debug_overlay_state.current_size.unwrap(),
                     ),
+                    ClipRadius::EMPTY,
                     ImageRendering::Auto,
                 );
             }
@@ -1447,6 +2180,25 @@ impl Renderer {
     ) -> Result<RenderResults, Vec<RendererError>> {
         profile_scope!("render");
         let mut results = RenderResults::default();
+
+        self.frame_counter += 1;
+        results.frame_id = self.frame_counter;
+
+        // Once rendered, the frame no longer counts as in flight.
+        let _frame_in_flight = active_doc.frame_in_flight.take();
+        if let Some((frame_pending, publish_id)) = active_doc.frame_pending.take() {
+            // A newer frame may have been published since; leave it pending.
+            let _ = frame_pending.compare_exchange(
+                publish_id.0,
+                FramePublishId::INVALID.0,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            );
+        }
+
+        // Present any frame the embedder didn't, before beginning a new one.
+        self.end_native_compositor_frame();
+
         self.profile.end_time_if_started(profiler::FRAME_SEND_TIME);
         self.profile.start_time(profiler::RENDERER_TIME);
 
@@ -1464,20 +2216,26 @@ impl Renderer {
                             .destroy_surface(&mut self.device, NativeSurfaceId::DEBUG_OVERLAY);
                         self.debug_overlay_state.current_size = None;
                     }
-                    false
+                    Some(false)
                 }
                 (CompositorKind::Draw { .. }, CompositorKind::Native { .. }) => {
-                    true
+                    Some(true)
+                }
+                (CompositorKind::Native { .. }, CompositorKind::Native { .. }) => {
+                    // Only the capabilities changed, the compositor stays enabled.
+                    None
                 }
                 (current_compositor_kind, active_doc_compositor_kind) => {
                     warn!("Compositor mismatch, assuming this is Wrench running. Current {:?}, active {:?}",
                         current_compositor_kind, active_doc_compositor_kind);
-                    false
+                    Some(false)
                 }
             };
 
-            if let Some(config) = self.compositor_config.compositor() {
-                config.enable_native_compositor(&mut self.device, enable);
+            if let Some(enable) = enable {
+                if let Some(config) = self.compositor_config.compositor() {
+                    config.enable_native_compositor(&mut self.device, enable);
+                }
             }
             self.current_compositor_kind = compositor_kind;
         }
@@ -1494,10 +2252,16 @@ impl Renderer {
             self.update_gpu_profile(device_size);
         }
 
-        let cpu_frame_id = {
+        {
             let _gm = self.gpu_profiler.start_marker("begin frame");
-            let frame_id = self.device.begin_frame();
-            self.gpu_profiler.begin_frame(frame_id);
+            self.device.begin_frame();
+            self.device.set_edge_aa_feather(active_doc.frame.edge_aa_feather);
+            self.gpu_profiler.begin_frame(self.frame_counter);
+
+            #[cfg(feature = "gl_trace")]
+            if self.debug_flags.contains(DebugFlags::TRACE_GL_CALLS) {
+                self.device.begin_gl_trace();
+            }
 
             self.device.disable_scissor();
             self.device.disable_depth();
@@ -1506,9 +2270,7 @@ impl Renderer {
 
             self.update_texture_cache();
             self.update_native_surfaces();
-
-            frame_id
-        };
+        }
 
         if let Some(device_size) = device_size {
             // Inform the client that we are starting a composition transaction if native
@@ -1539,15 +2301,19 @@ impl Renderer {
                     "Received frame depends on a later GPU cache epoch ({:?}) than one we received last via `UpdateGpuCache` ({:?})",
                     frame.gpu_cache_frame_id, self.gpu_cache_frame_id);
 
-                {
+                if self.early_gl_flush {
                     profile_scope!("gl.flush");
                     self.device.gl().flush();  // early start on gpu cache updates
                 }
 
+                let clear_color = self.resolve_clear_color(doc_id);
+
                 self.draw_frame(
+                    doc_id,
                     frame,
                     device_size,
                     buffer_age,
+                    clear_color,
                     &mut results,
                 );
 
@@ -1593,6 +2359,7 @@ impl Renderer {
                 self.draw_texture_cache_debug(&draw_target);
                 self.draw_gpu_cache_debug(device_size);
                 self.draw_zoom_debug(device_size);
+                self.draw_layout_grid_debug(device_size);
                 self.draw_epoch_debug();
                 self.draw_window_visibility_debug();
                 draw_target
@@ -1609,7 +2376,7 @@ impl Renderer {
                 self.cpu_profiles.pop_front();
             }
             let cpu_profile = CpuProfile::new(
-                cpu_frame_id,
+                self.frame_counter,
                 (self.profile.get_or(profiler::FRAME_BUILDING_TIME, 0.0) * 1000000.0) as u64,
                 (self.profile.get_or(profiler::RENDERER_TIME, 0.0) * 1000000.0) as u64,
                 self.profile.get_or(profiler::DRAW_CALLS, 0.0) as usize,
@@ -1635,11 +2402,24 @@ impl Renderer {
         self.profile.set(profiler::TEXTURES_DELETED, self.device.textures_deleted);
 
         results.stats.texture_upload_mb = self.profile.get_or(profiler::TEXTURE_UPLOADS_MEM, 0.0);
-        self.frame_counter += 1;
+        results.stats.render_task_cache_hits = self.profile.get_or(profiler::RENDER_TASK_CACHE_HITS, 0.0) as usize;
+        results.stats.render_task_cache_misses = self.profile.get_or(profiler::RENDER_TASK_CACHE_MISSES, 0.0) as usize;
+        results.stats.cancelled_scene_builds = self.profile.get_or(profiler::CANCELLED_SCENE_BUILDS, 0.0) as usize;
         results.stats.resource_upload_time = self.resource_upload_time;
         self.resource_upload_time = 0.0;
         results.stats.gpu_cache_upload_time = self.gpu_cache_upload_time;
         self.gpu_cache_upload_time = 0.0;
+        if device_size.is_some() {
+            // Waiting starts over with the next update.
+            let wait_time = mem::replace(&mut self.backend_wait_time, 0.0);
+            self.backend_wait_start = None;
+            self.profile.set(profiler::BACKEND_WAIT_TIME, wait_time);
+            results.stats.backend_wait_time = wait_time;
+        }
+        results.stats.gpu_cache_resized = mem::replace(&mut self.gpu_cache_resized, false);
+        results.stats.over_upload_budget = self.texture_upload_budget_bytes
+            .map_or(false, |budget| self.texture_upload_bytes > budget);
+        self.texture_upload_bytes = 0;
 
         if let Some(stats) = active_doc.frame_stats.take() {
           // Copy the full frame stats to RendererStats
@@ -1670,6 +2450,13 @@ impl Renderer {
         }
         active_doc.render_reasons = RenderReasons::empty();
 
+        if add_markers {
+            for trace_id in &active_doc.trace_ids {
+                add_event_marker(&format!("Trace id {}", trace_id));
+            }
+        }
+        results.trace_ids = mem::take(&mut active_doc.trace_ids);
+
 
         self.texture_resolver.update_profile(&mut self.profile);
 
@@ -1719,6 +2506,12 @@ impl Renderer {
         self.texture_upload_pbo_pool.end_frame(&mut self.device);
         self.device.end_frame();
 
+        let memory = self.report_gpu_memory();
+        self.peak_memory.max_assign(&memory);
+
+        #[cfg(feature = "gl_trace")]
+        self.write_gl_trace(self.frame_counter);
+
         if debug_overlay.is_some() {
             self.last_time = current_time;
 
@@ -1732,9 +2525,29 @@ impl Renderer {
             // compositing is enabled. This must be called after any debug / profiling compositor
             // surfaces have been drawn and added to the visual tree.
             if let CompositorKind::Native { .. } = self.current_compositor_kind {
-                profile_scope!("compositor.end_frame");
-                let compositor = self.compositor_config.compositor().unwrap();
-                compositor.end_frame(&mut self.device);
+                self.pending_native_present = Some(doc_id);
+                if !self.explicit_native_present {
+                    self.end_native_compositor_frame();
+                }
+            }
+
+            if let Some(ref sink) = self.frame_summary_sink {
+                let epoch = self.pipeline_info.epochs
+                    .iter()
+                    .filter(|((_, document_id), _)| *document_id == doc_id)
+                    .map(|(_, epoch)| *epoch)
+                    .max();
+                sink(FrameSummary {
+                    frame_id: self.frame_counter,
+                    document_id: doc_id,
+                    epoch,
+                    draw_calls: results.stats.total_draw_calls,
+                    gpu_time_ns: self.last_gpu_time_ns,
+                    dirty_area: results.dirty_rects
+                        .iter()
+                        .map(|rect| rect.area() as usize)
+                        .sum(),
+                });
             }
         }
 
@@ -1756,6 +2569,10 @@ impl Renderer {
         // In general this shouldn't block unless heavily GPU limited.
         let (gpu_frame_id, timers, samplers) = self.gpu_profiler.build_samples();
 
+        if !timers.is_empty() {
+            self.last_gpu_time_ns = Some(timers.iter().map(|timer| timer.time_ns).sum());
+        }
+
         if self.max_recorded_profiles > 0 {
             while self.gpu_profiles.len() >= self.max_recorded_profiles {
                 self.gpu_profiles.pop_front();
@@ -1869,6 +2686,7 @@ impl Renderer {
                         target: old.texture.get_target(),
                         is_shared_cache: old.texture.flags().contains(TextureFlags::IS_SHARED_TEXTURE_CACHE),
                         has_depth: old.texture.supports_depth(),
+                        is_srgb: old.texture.flags().contains(TextureFlags::IS_SRGB),
                         category: old.category,
                     };
                     pending_deletes.push((old.texture, info));
@@ -1895,7 +2713,7 @@ impl Renderer {
                 let delete_texture_start = precise_time_ns();
                 for (texture, _) in pending_deletes {
                     add_event_marker("TextureCacheFree");
-                    self.device.delete_texture(texture);
+                    self.delete_cache_texture(texture);
                 }
                 delete_cache_texture_time += precise_time_ns() - delete_texture_start;
             }
@@ -1916,16 +2734,48 @@ impl Renderer {
                         // Ensure no PBO is bound when creating the texture storage,
                         // or GL will attempt to read data from there.
                         let mut texture = reused_textures.pop_front().unwrap_or(None).unwrap_or_else(|| {
-                            self.device.create_texture(
-                                info.target,
-                                info.format,
-                                info.width,
-                                info.height,
-                                info.filter,
-                                // This needs to be a render target because some render
-                                // tasks get rendered into the texture cache.
-                                Some(RenderTargetInfo { has_depth: info.has_depth }),
-                            )
+                            // Give the embedder a chance to supply shared cache textures.
+                            let embedder_texture = match self.texture_allocator_hook {
+                                Some(ref mut hook) if info.is_shared_cache => {
+                                    hook.allocate(&TextureAllocationRequest {
+                                        size: DeviceIntSize::new(info.width, info.height),
+                                        format: info.format,
+                                        target: info.target,
+                                    })
+                                }
+                                _ => None,
+                            };
+
+                            match embedder_texture {
+                                Some(id) => self.device.create_texture_from_embedder(
+                                    id,
+                                    info.target,
+                                    info.format,
+                                    info.width,
+                                    info.height,
+                                    info.filter,
+                                    Some(RenderTargetInfo { has_depth: info.has_depth }),
+                                ),
+                                // Devices that can't toggle the sRGB conversions fall back to
+                                // regular storage, in which case blurs stay in gamma space.
+                                None if info.is_srgb && self.device.get_capabilities().supports_srgb_render_targets => self.device.create_srgb_render_target(
+                                    info.target,
+                                    info.width,
+                                    info.height,
+                                    info.filter,
+                                    RenderTargetInfo { has_depth: info.has_depth },
+                                ),
+                                None => self.device.create_texture(
+                                    info.target,
+                                    info.format,
+                                    info.width,
+                                    info.height,
+                                    info.filter,
+                                    // This needs to be a render target because some render
+                                    // tasks get rendered into the texture cache.
+                                    Some(RenderTargetInfo { has_depth: info.has_depth }),
+                                ),
+                            }
                         });
 
                         if info.is_shared_cache {
@@ -1993,6 +2843,14 @@ impl Renderer {
         let err = self.device.gl().get_error();
         if err == gl::OUT_OF_MEMORY {
             self.renderer_errors.push(RendererError::OutOfMemory);
+        } else if err != gl::NO_ERROR {
+            self.device.record_gl_error(err);
+        }
+
+        // Context loss may also have been caught by the device's error
+        // checking wrapper.
+        if self.device.report_context_lost() {
+            self.renderer_errors.push(RendererError::ContextLost);
         }
 
         // Probably should check for other errors?
@@ -2019,6 +2877,15 @@ impl Renderer {
         }
     }
 
+    fn set_image_wrap_mode(&mut self, textures: &BatchTextures, wrap_mode: ImageWrapMode) {
+        self.texture_resolver.set_wrap_mode(
+            &textures.input.colors[0],
+            TextureSampler::color(0),
+            wrap_mode,
+            &mut self.device,
+        );
+    }
+
     fn draw_instanced_batch<T: Clone>(
         &mut self,
         data: &[T],
@@ -2820,8 +3687,11 @@ impl Renderer {
             self.device.set_scissor_rect(scissor_rect)
         }
 
-        if !alpha_batch_container.opaque_batches.is_empty()
-            && !self.debug_flags.contains(DebugFlags::DISABLE_OPAQUE_PASS) {
+        let opaque_only = self.opaque_only;
+        let draw_opaque_pass = opaque_only || !self.debug_flags.contains(DebugFlags::DISABLE_OPAQUE_PASS);
+        let draw_alpha_pass = !opaque_only && !self.debug_flags.contains(DebugFlags::DISABLE_ALPHA_PASS);
+
+        if !alpha_batch_container.opaque_batches.is_empty() && draw_opaque_pass {
             let _gl = self.gpu_profiler.start_marker("opaque batches");
             let opaque_sampler = self.gpu_profiler.start_sampler(GPU_SAMPLER_TAG_OPAQUE);
             self.set_blend(false, framebuffer_kind);
@@ -2836,7 +3706,7 @@ impl Renderer {
                 .iter()
                 .rev()
                 {
-                    if should_skip_batch(&batch.key.kind, self.debug_flags) {
+                    if should_skip_batch(&batch.key.kind, self.debug_flags, self.primitive_isolation) {
                         continue;
                     }
 
@@ -2849,12 +3719,19 @@ impl Renderer {
                         );
 
                     let _timer = self.gpu_profiler.start_timer(batch.key.kind.sampler_tag());
+                    let wrap_mode = batch.key.kind.wrap_mode();
+                    if let Some(wrap_mode) = wrap_mode {
+                        self.set_image_wrap_mode(&batch.key.textures, wrap_mode);
+                    }
                     self.draw_instanced_batch(
                         &batch.instances,
                         VertexArrayKind::Primitive,
                         &batch.key.textures,
                         stats
                     );
+                    if wrap_mode.is_some() {
+                        self.set_image_wrap_mode(&batch.key.textures, ImageWrapMode::Clamp);
+                    }
                 }
 
             self.device.disable_depth_write();
@@ -2863,8 +3740,20 @@ impl Renderer {
             self.device.disable_depth();
         }
 
+        let has_depth = match draw_target {
+            DrawTarget::Texture { with_depth, .. } => with_depth,
+            DrawTarget::NativeSurface { .. } => true,
+            DrawTarget::Default { .. } | DrawTarget::External { .. } => false,
+        };
+
         if !alpha_batch_container.alpha_batches.is_empty()
-            && !self.debug_flags.contains(DebugFlags::DISABLE_ALPHA_PASS) {
+            && draw_alpha_pass
+            && self.debug_flags.contains(DebugFlags::ALPHA_DEPTH_PREPASS)
+            && has_depth {
+            self.draw_alpha_depth_prepass(alpha_batch_container, projection, stats);
+        }
+
+        if !alpha_batch_container.alpha_batches.is_empty() && draw_alpha_pass {
             let _gl = self.gpu_profiler.start_marker("alpha batches");
             let transparent_sampler = self.gpu_profiler.start_sampler(GPU_SAMPLER_TAG_TRANSPARENT);
             self.set_blend(true, framebuffer_kind);
@@ -2873,7 +3762,7 @@ impl Renderer {
             let shaders_rc = self.shaders.clone();
 
             for batch in &alpha_batch_container.alpha_batches {
-                if should_skip_batch(&batch.key.kind, self.debug_flags) {
+                if should_skip_batch(&batch.key.kind, self.debug_flags, self.primitive_isolation) {
                     continue;
                 }
 
@@ -2892,7 +3781,10 @@ impl Renderer {
                             self.device.set_blend_mode_show_overdraw();
                         }
                         BlendMode::None => {
-                            unreachable!("bug: opaque blend in alpha pass");
+                            // Opaque primitives only end up in the alpha pass once the
+                            // depth id budget is used up. They write an alpha of one, so
+                            // premultiplied blending leaves their output unchanged.
+                            self.device.set_blend_mode_premultiplied_alpha();
                         }
                         BlendMode::Alpha => {
                             self.device.set_blend_mode_alpha();
@@ -2950,12 +3842,19 @@ impl Renderer {
                     &mut self.profile,
                 );
 
+                let wrap_mode = batch.key.kind.wrap_mode();
+                if let Some(wrap_mode) = wrap_mode {
+                    self.set_image_wrap_mode(&batch.key.textures, wrap_mode);
+                }
                 self.draw_instanced_batch(
                     &batch.instances,
                     VertexArrayKind::Primitive,
                     &batch.key.textures,
                     stats
                 );
+                if wrap_mode.is_some() {
+                    self.set_image_wrap_mode(&batch.key.textures, ImageWrapMode::Clamp);
+                }
             }
 
             self.set_blend(false, framebuffer_kind);
@@ -2968,6 +3867,60 @@ impl Renderer {
         }
     }
 
+    /// Draw the alpha batches of a container into the depth buffer only, so
+    /// that the alpha pass that follows only shades the front-most fragment
+    /// of each pixel. See `DebugFlags::ALPHA_DEPTH_PREPASS`.
+    ///
+    /// Leaves depth testing enabled and depth writes disabled.
+    fn draw_alpha_depth_prepass(
+        &mut self,
+        alpha_batch_container: &AlphaBatchContainer,
+        projection: &default::Transform3D<f32>,
+        stats: &mut RendererStats,
+    ) {
+        let _gl = self.gpu_profiler.start_marker("alpha depth prepass");
+
+        self.device.enable_depth(DepthFunction::LessEqual);
+        self.device.enable_depth_write();
+        self.device.disable_color_write();
+
+        for batch in &alpha_batch_container.alpha_batches {
+            if should_skip_batch(&batch.key.kind, self.debug_flags, self.primitive_isolation) {
+                continue;
+            }
+
+            // Mix blends read back the framebuffer, which is pointless here.
+            if let BatchKind::Brush(BrushBatchKind::MixBlend { .. }) = batch.key.kind {
+                continue;
+            }
+
+            self.shaders.borrow_mut()
+                .get(&batch.key, batch.features | BatchFeatures::ALPHA_PASS, self.debug_flags, &self.device)
+                .bind(
+                    &mut self.device, projection, None,
+                    &mut self.renderer_errors,
+                    &mut self.profile,
+                );
+
+            let wrap_mode = batch.key.kind.wrap_mode();
+            if let Some(wrap_mode) = wrap_mode {
+                self.set_image_wrap_mode(&batch.key.textures, wrap_mode);
+            }
+            self.draw_instanced_batch(
+                &batch.instances,
+                VertexArrayKind::Primitive,
+                &batch.key.textures,
+                stats
+            );
+            if wrap_mode.is_some() {
+                self.set_image_wrap_mode(&batch.key.textures, ImageWrapMode::Clamp);
+            }
+        }
+
+        self.device.enable_color_write();
+        self.device.disable_depth_write();
+    }
+
     /// Rasterize any external compositor surfaces that require updating
     fn update_external_native_surfaces(
         &mut self,
@@ -2995,19 +3948,21 @@ impl Renderer {
             let surface_rect = surface_size.into();
 
             // Bind the native compositor surface to update
+            let tile_id = NativeTileId {
+                surface_id: native_surface_id,
+                x: 0,
+                y: 0,
+            };
             let surface_info = self.compositor_config
                 .compositor()
                 .unwrap()
                 .bind(
                     &mut self.device,
-                    NativeTileId {
-                        surface_id: native_surface_id,
-                        x: 0,
-                        y: 0,
-                    },
+                    tile_id,
                     surface_rect,
                     surface_rect,
                 );
+            results.native_surface_dirty_rects.push((tile_id, surface_rect));
 
             // Bind the native surface to current FBO target
             let draw_target = DrawTarget::NativeSurface {
@@ -3076,11 +4031,11 @@ impl Renderer {
 
                     ( textures, instance )
                 },
-                ResolvedExternalSurfaceColorData::Rgb{ ref plane, .. } => {
+                ResolvedExternalSurfaceColorData::Rgb{ ref plane, format, .. } => {
                     self.shaders
                         .borrow_mut()
                         .get_composite_shader(
-                            CompositeSurfaceFormat::Rgba,
+                            format,
                             surface.image_buffer_kind,
                             CompositeFeatures::empty(),
                         ).bind(
@@ -3129,6 +4084,7 @@ impl Renderer {
         composite_state: &CompositeState,
         external_surfaces: &[ResolvedExternalSurface],
         projection: &default::Transform3D<f32>,
+        vignette: CompositeVignette,
         stats: &mut RendererStats,
     ) {
         let mut current_shader_params = (
@@ -3139,6 +4095,7 @@ impl Renderer {
         );
         let mut current_textures = BatchTextures::empty();
         let mut instances = Vec::new();
+        let max_depth_ids = self.device.max_depth_ids();
 
         self.shaders
             .borrow_mut()
@@ -3170,9 +4127,9 @@ impl Renderer {
                     let instance = CompositeInstance::new(
                         tile_rect,
                         clip_rect,
-                        color.premultiplied(),
+                        color.scale_alpha(tile.opacity).premultiplied(),
                         flip,
-                    );
+                    ).with_saturation(self.global_saturation).with_vignette(vignette);
                     let features = instance.get_rgb_features();
                     (
                         instance,
@@ -3181,12 +4138,15 @@ impl Renderer {
                     )
                 }
                 CompositeTileSurface::Texture { surface: ResolvedSurfaceTexture::TextureCache { texture } } => {
+                    // Tile textures are premultiplied, so the composite opacity
+                    // is applied by modulating all channels.
+                    let opacity = tile.opacity;
                     let instance = CompositeInstance::new(
                         tile_rect,
                         clip_rect,
-                        PremultipliedColorF::WHITE,
+                        PremultipliedColorF { r: opacity, g: opacity, b: opacity, a: opacity },
                         flip,
-                    );
+                    ).with_saturation(self.global_saturation).with_vignette(vignette);
                     let features = instance.get_rgb_features();
                     (
                         instance,
@@ -3230,7 +4190,7 @@ impl Renderer {
                                     channel_bit_depth,
                                     uv_rects,
                                     flip,
-                                ),
+                                ).with_saturation(self.global_saturation).with_vignette(vignette),
                                 textures,
                                 (
                                     CompositeSurfaceFormat::Yuv,
@@ -3240,7 +4200,7 @@ impl Renderer {
                                 ),
                             )
                         },
-                        ResolvedExternalSurfaceColorData::Rgb { ref plane, .. } => {
+                        ResolvedExternalSurfaceColorData::Rgb { ref plane, format, .. } => {
                             let uv_rect = self.texture_resolver.get_uv_rect(&plane.texture, plane.uv_rect);
                             let instance = CompositeInstance::new_rgb(
                                 tile_rect,
@@ -3249,13 +4209,13 @@ impl Renderer {
                                 uv_rect,
                                 plane.texture.uses_normalized_uvs(),
                                 flip,
-                            );
+                            ).with_saturation(self.global_saturation).with_vignette(vignette);
                             let features = instance.get_rgb_features();
                             (
                                 instance,
                                 BatchTextures::composite_rgb(plane.texture),
                                 (
-                                    CompositeSurfaceFormat::Rgba,
+                                    format,
                                     surface.image_buffer_kind,
                                     features,
                                     Some(self.texture_resolver.get_texture_size(&plane.texture).to_f32()),
@@ -3284,6 +4244,7 @@ impl Renderer {
                     unreachable!("bug: found native surface in simple composite path");
                 }
             };
+            let instance = instance.with_z_id(tile.z_id, max_depth_ids);
 
             // Flush batch if shader params or textures changed
             let flush_batch = !current_textures.is_compatible_with(&textures) ||
@@ -3342,15 +4303,36 @@ impl Renderer {
         composite_state: &CompositeState,
         draw_target: DrawTarget,
         projection: &default::Transform3D<f32>,
+        clear_color: ColorF,
         results: &mut RenderResults,
         partial_present_mode: Option<PartialPresentMode>,
+        content_clip_rect: DeviceRect,
+        vignette: CompositeVignette,
+        window_backdrop_rect: Option<DeviceRect>,
     ) {
         let _gm = self.gpu_profiler.start_marker("framebuffer");
         let _timer = self.gpu_profiler.start_timer(GPU_TAG_COMPOSITE);
 
+        // Depth is written for the whole framebuffer, so partial present is
+        // not used while linear depth output is enabled.
+        let partial_present_mode = if self.output_linear_depth {
+            None
+        } else {
+            partial_present_mode
+        };
+
         self.device.bind_draw_target(draw_target);
-        self.device.disable_depth_write();
-        self.device.disable_depth();
+        let clear_depth = if self.output_linear_depth {
+            // Nearer surfaces have a smaller depth, so a depth test keeps the
+            // front-most surface where opaque and alpha tiles overlap.
+            self.device.enable_depth(DepthFunction::LessEqual);
+            self.device.enable_depth_write();
+            Some(1.0)
+        } else {
+            self.device.disable_depth_write();
+            self.device.disable_depth();
+            None
+        };
 
         // If using KHR_partial_update, call eglSetDamageRegion.
         // This must be called exactly once per frame, and prior to any rendering to the main
@@ -3391,7 +4373,8 @@ impl Renderer {
             let rect = device_tile_box
                 .intersection_unchecked(&tile.device_clip_rect)
                 .intersection_unchecked(&partial_clip_rect)
-                .intersection_unchecked(&device_valid_rect);
+                .intersection_unchecked(&device_valid_rect)
+                .intersection_unchecked(&content_clip_rect);
 
             if rect.is_empty() {
                 continue;
@@ -3410,7 +4393,7 @@ impl Renderer {
         }
 
         // Clear the framebuffer
-        let clear_color = Some(self.clear_color.to_array());
+        let clear_color = Some(clear_color.to_array());
 
         match partial_present_mode {
             Some(PartialPresentMode::Single { dirty_rect }) => {
@@ -3421,18 +4404,36 @@ impl Renderer {
                 if !dirty_rect.is_empty() && occlusion.test(&dirty_rect) {
                     // We have a single dirty rect, so clear only that
                     self.device.clear_target(clear_color,
-                                             None,
+                                             clear_depth,
                                              Some(draw_target.to_framebuffer_rect(dirty_rect.to_i32())));
                 }
             }
             None => {
                 // Partial present is disabled, so clear the entire framebuffer
-                self.device.clear_target(clear_color,
-                                         None,
-                                         None);
+                match (self.present_mode, draw_target) {
+                    (Some(PresentMode::Letterbox(bar_color)), DrawTarget::Default { rect, .. }) => {
+                        // Fill the bars around the document, then clear the document
+                        // area itself as usual.
+                        self.device.clear_target(Some(bar_color.to_array()),
+                                                 clear_depth,
+                                                 None);
+                        self.device.clear_target(clear_color,
+                                                 None,
+                                                 Some(rect));
+                    }
+                    _ => {
+                        self.device.clear_target(clear_color,
+                                                 clear_depth,
+                                                 None);
+                    }
+                }
             }
         }
 
+        if let Some(rect) = window_backdrop_rect {
+            self.composite_window_backdrop(rect, projection, clear_depth.is_some(), &mut results.stats);
+        }
+
         // We are only interested in tiles backed with actual cached pixels so we don't
         // count clear tiles here.
         let num_tiles = composite_state.tiles
@@ -3448,6 +4449,7 @@ impl Renderer {
                 &composite_state,
                 &composite_state.external_surfaces,
                 projection,
+                vignette,
                 &mut results.stats,
             );
             self.gpu_profiler.finish_sampler(opaque_sampler);
@@ -3462,6 +4464,7 @@ impl Renderer {
                 &composite_state,
                 &composite_state.external_surfaces,
                 projection,
+                vignette,
                 &mut results.stats,
             );
             self.gpu_profiler.finish_sampler(transparent_sampler);
@@ -3477,10 +4480,16 @@ impl Renderer {
                 &composite_state,
                 &composite_state.external_surfaces,
                 projection,
+                vignette,
                 &mut results.stats,
             );
             self.gpu_profiler.finish_sampler(transparent_sampler);
         }
+
+        if clear_depth.is_some() {
+            self.device.disable_depth_write();
+            self.device.disable_depth();
+        }
     }
 
     fn draw_color_target(
@@ -3604,9 +4613,16 @@ impl Renderer {
             self.shaders.borrow_mut().cs_blur_rgba8
                 .bind(&mut self.device, projection, None, &mut self.renderer_errors, &mut self.profile);
 
+            // If the target has sRGB storage, blur in linear space.
+            let linear = self.texture_resolver.texture_cache_map[&target.texture_id()]
+                .texture
+                .flags()
+                .contains(TextureFlags::IS_SRGB);
+
             if !target.vertical_blurs.is_empty() {
                 self.draw_blurs(
                     &target.vertical_blurs,
+                    linear,
                     stats,
                 );
             }
@@ -3614,6 +4630,7 @@ impl Renderer {
             if !target.horizontal_blurs.is_empty() {
                 self.draw_blurs(
                     &target.horizontal_blurs,
+                    linear,
                     stats,
                 );
             }
@@ -3672,9 +4689,14 @@ impl Renderer {
         }
     }
 
+    /// Draw blur instances to the current target. If `linear` is true, the
+    /// target has sRGB storage, and sources that also have sRGB storage are
+    /// decoded to linear space for sampling, with the result encoded back to
+    /// sRGB when written.
     fn draw_blurs(
         &mut self,
         blurs: &FastHashMap<TextureSource, FrameVec<BlurInstance>>,
+        linear: bool,
         stats: &mut RendererStats,
     ) {
         for (texture, blurs) in blurs {
@@ -3682,12 +4704,27 @@ impl Renderer {
                 *texture,
             );
 
+            let srgb_source = match self.texture_resolver.resolve(texture) {
+                Some((source, _)) if linear && source.flags().contains(TextureFlags::IS_SRGB) => {
+                    self.device.set_texture_srgb_decode(source, true);
+                    self.device.enable_framebuffer_srgb();
+                    true
+                }
+                _ => false,
+            };
+
             self.draw_instanced_batch(
                 blurs,
                 VertexArrayKind::Blur,
                 &textures,
                 stats,
             );
+
+            if srgb_source {
+                let (source, _) = self.texture_resolver.resolve(texture).unwrap();
+                self.device.set_texture_srgb_decode(source, false);
+                self.device.disable_framebuffer_srgb();
+            }
         }
     }
 
@@ -3872,6 +4909,7 @@ impl Renderer {
             if !target.vertical_blurs.is_empty() {
                 self.draw_blurs(
                     &target.vertical_blurs,
+                    false,
                     stats,
                 );
             }
@@ -3879,6 +4917,7 @@ impl Renderer {
             if !target.horizontal_blurs.is_empty() {
                 self.draw_blurs(
                     &target.horizontal_blurs,
+                    false,
                     stats,
                 );
             }
@@ -3928,6 +4967,43 @@ impl Renderer {
         self.gpu_profiler.finish_sampler(alpha_sampler);
     }
 
+    /// Returns a multisampled draw target of the given size for clip masks,
+    /// growing the cached target if it is too small, or adding a depth
+    /// attachment to it if the alpha depth prepass needs one.
+    fn acquire_clip_mask_msaa_target(&mut self, size: DeviceIntSize) -> DrawTarget {
+        let with_depth = self.debug_flags.contains(DebugFlags::ALPHA_DEPTH_PREPASS);
+        let alloc_size = match self.clip_mask_msaa_target.take() {
+            Some(target) if target.size.width >= size.width &&
+                target.size.height >= size.height &&
+                target.has_depth() == with_depth => {
+                self.clip_mask_msaa_target = Some(target);
+                None
+            }
+            Some(target) => {
+                let alloc_size = DeviceIntSize::new(
+                    target.size.width.max(size.width),
+                    target.size.height.max(size.height),
+                );
+                self.device.delete_multisampled_fbo(target);
+                Some(alloc_size)
+            }
+            None => Some(size),
+        };
+        if let Some(alloc_size) = alloc_size {
+            self.clip_mask_msaa_target = Some(self.device.create_multisampled_fbo(
+                alloc_size,
+                ImageFormat::R8,
+                self.clip_mask_msaa_samples,
+                with_depth,
+            ));
+        }
+
+        DrawTarget::External {
+            fbo: self.clip_mask_msaa_target.as_ref().unwrap().fbo,
+            size: device_size_as_framebuffer_size(size),
+        }
+    }
+
     fn draw_texture_cache_target(
         &mut self,
         texture: &CacheTextureId,
@@ -4195,6 +5271,7 @@ impl Renderer {
 
             self.draw_blurs(
                 &target.horizontal_blurs,
+                false,
                 stats,
             );
         }
@@ -4229,7 +5306,11 @@ impl Renderer {
                 .external_image
                 .expect("BUG: Deferred resolves must be external images!");
             // Provide rendering information for NativeTexture external images.
-            let image = handler.lock(ext_image.id, ext_image.channel_index);
+            let image = self.locked_external_images.lock(
+                &mut **handler,
+                ext_image.id,
+                ext_image.channel_index,
+            );
             let texture_target = match ext_image.image_type {
                 ExternalImageType::TextureHandle(target) => target,
                 ExternalImageType::Buffer => {
@@ -4300,7 +5381,11 @@ impl Renderer {
                 let ext_image = props
                     .external_image
                     .expect("BUG: Deferred resolves must be external images!");
-                handler.unlock(ext_image.id, ext_image.channel_index);
+                self.locked_external_images.unlock(
+                    &mut **handler,
+                    ext_image.id,
+                    ext_image.channel_index,
+                );
             }
         }
     }
@@ -4310,6 +5395,7 @@ impl Renderer {
     //           is implemented in terms of the compositor trait.
     fn calculate_dirty_rects(
         &mut self,
+        doc_id: DocumentId,
         buffer_age: usize,
         composite_state: &CompositeState,
         draw_target_dimensions: DeviceIntSize,
@@ -4331,8 +5417,9 @@ impl Renderer {
         };
 
         if max_partial_present_rects > 0 {
+            let buffer_damage_tracker = self.buffer_damage_trackers.entry(doc_id).or_default();
             let prev_frames_damage_rect = if let Some(..) = self.compositor_config.partial_present() {
-                self.buffer_damage_tracker
+                buffer_damage_tracker
                     .get_damage_rect(buffer_age)
                     .or_else(|| Some(DeviceRect::from_size(draw_target_dimensions.to_f32())))
             } else {
@@ -4343,7 +5430,12 @@ impl Renderer {
                 composite_state.dirty_rects_are_valid &&
                 !self.force_redraw &&
                 !(prev_frames_damage_rect.is_none() && draw_previous_partial_present_regions) &&
-                !self.debug_overlay_state.is_enabled;
+                !self.debug_overlay_state.is_enabled &&
+                self.present_mode.is_none() &&
+                self.integer_scaling.is_none() &&
+                self.window_backdrop_blur.is_none() &&
+                self.output_alpha_mode == AlphaMode::Premultiplied &&
+                self.safe_area_insets == DeviceIntSideOffsets::zero();
 
             if can_use_partial_present {
                 let mut combined_dirty_rect = DeviceRect::zero();
@@ -4370,6 +5462,17 @@ impl Renderer {
                 }
 
                 let combined_dirty_rect = combined_dirty_rect.round();
+
+                // Let the embedder add its own damage to the presented region.
+                let combined_dirty_rect = match self.dirty_rect_hook {
+                    Some(ref hook) => {
+                        hook(combined_dirty_rect.to_i32())
+                            .intersection(&fb_rect.to_i32())
+                            .unwrap_or_else(DeviceIntRect::zero)
+                            .to_f32()
+                    }
+                    None => combined_dirty_rect,
+                };
                 let combined_dirty_rect_i32 = combined_dirty_rect.to_i32();
                 // Return this frame's dirty region. If nothing has changed, don't return any dirty
                 // rects at all (the client can use this as a signal to skip present completely).
@@ -4379,7 +5482,7 @@ impl Renderer {
 
                 // Track this frame's dirty region, for calculating subsequent frames' damage.
                 if draw_previous_partial_present_regions {
-                    self.buffer_damage_tracker.push_dirty_rect(&combined_dirty_rect);
+                    buffer_damage_tracker.push_dirty_rect(&combined_dirty_rect);
                 }
 
                 // If the implementation requires manually keeping the buffer consistent,
@@ -4405,7 +5508,7 @@ impl Renderer {
                 results.dirty_rects.push(fb_rect);
 
                 if draw_previous_partial_present_regions {
-                    self.buffer_damage_tracker.push_dirty_rect(&fb_rect.to_f32());
+                    buffer_damage_tracker.push_dirty_rect(&fb_rect.to_f32());
                 }
             }
 
@@ -4447,13 +5550,13 @@ impl Renderer {
                                     is_opaque,
                             );
                         }
-                        NativeSurfaceOperationDetails::CreateExternalSurface { id, is_opaque } => {
+                        NativeSurfaceOperationDetails::CreateExternalSurface { id, format } => {
                             let _inserted = self.allocated_native_surfaces.insert(id);
                             debug_assert!(_inserted, "bug: creating existing surface");
                             compositor.create_external_surface(
                                 &mut self.device,
                                 id,
-                                is_opaque,
+                                format,
                             );
                         }
                         NativeSurfaceOperationDetails::CreateBackdropSurface { id, color } => {
@@ -4524,9 +5627,11 @@ impl Renderer {
 
     fn draw_frame(
         &mut self,
+        doc_id: DocumentId,
         frame: &mut Frame,
         device_size: Option<DeviceIntSize>,
         buffer_age: usize,
+        clear_color: ColorF,
         results: &mut RenderResults,
     ) {
         profile_scope!("draw_frame");
@@ -4580,6 +5685,7 @@ impl Renderer {
         //           is occurring.
         let present_mode = device_size.and_then(|device_size| {
             self.calculate_dirty_rects(
+                doc_id,
                 buffer_age,
                 &frame.composite_state,
                 device_size,
@@ -4632,7 +5738,7 @@ impl Renderer {
             // composition as surfaces are updated.
             if device_size.is_some() {
                 frame.composite_state.composite_native(
-                    self.clear_color,
+                    clear_color,
                     &results.dirty_rects,
                     &mut self.device,
                     &mut **compositor,
@@ -4640,12 +5746,16 @@ impl Renderer {
             }
         }
 
+        let mut render_target_textures = FastHashSet::default();
+
         for (_pass_index, pass) in frame.passes.iter_mut().enumerate() {
             #[cfg(not(target_os = "android"))]
             let _gm = self.gpu_profiler.start_marker(&format!("pass {}", _pass_index));
 
             profile_scope!("offscreen target");
 
+            let mut pass_info = PassInfo::default();
+
             // If this frame has already been drawn, then any texture
             // cache targets have already been updated and can be
             // skipped this time.
@@ -4682,6 +5792,7 @@ impl Renderer {
                             let surface_info = match self.current_compositor_kind {
                                 CompositorKind::Native { .. } => {
                                     let compositor = self.compositor_config.compositor().unwrap();
+                                    results.native_surface_dirty_rects.push((id, picture_target.dirty_rect));
                                     compositor.bind(
                                         &mut self.device,
                                         id,
@@ -4738,6 +5849,7 @@ impl Renderer {
                 results.stats.alpha_target_count += 1;
 
                 let texture_id = target.texture_id();
+                render_target_textures.insert(texture_id);
 
                 let alpha_tex = self.texture_resolver.get_cache_texture_mut(&texture_id);
 
@@ -4745,6 +5857,8 @@ impl Renderer {
                     alpha_tex,
                     false,
                 );
+                pass_info.alpha_target_count += 1;
+                pass_info.alpha_target_pixels += draw_target.dimensions().area() as usize;
 
                 let projection = Transform3D::ortho(
                     0.0,
@@ -4755,21 +5869,49 @@ impl Renderer {
                     self.device.ortho_far_plane(),
                 );
 
-                self.draw_alpha_target(
-                    draw_target,
-                    target,
-                    &projection,
-                    &frame.render_tasks,
-                    &mut results.stats,
-                );
+                if self.clip_mask_msaa_samples > 0 {
+                    // Rasterize into a multisampled target and resolve the
+                    // result into the alpha target texture.
+                    let size = draw_target.dimensions();
+                    let msaa_target = self.acquire_clip_mask_msaa_target(size);
+                    self.draw_alpha_target(
+                        msaa_target,
+                        target,
+                        &projection,
+                        &frame.render_tasks,
+                        &mut results.stats,
+                    );
+                    let rect = FramebufferIntRect::from_size(device_size_as_framebuffer_size(size));
+                    self.device.blit_render_target(
+                        msaa_target.into(),
+                        rect,
+                        draw_target,
+                        rect,
+                        TextureFilter::Nearest,
+                    );
+                } else {
+                    self.draw_alpha_target(
+                        draw_target,
+                        target,
+                        &projection,
+                        &frame.render_tasks,
+                        &mut results.stats,
+                    );
+                }
             }
 
-            let color_rt_info = RenderTargetInfo { has_depth: pass.color.needs_depth() };
+            // The alpha depth prepass needs a depth buffer even for targets
+            // without opaque batches.
+            let alpha_depth_prepass = self.debug_flags.contains(DebugFlags::ALPHA_DEPTH_PREPASS);
+            let color_rt_info = RenderTargetInfo {
+                has_depth: pass.color.needs_depth() || alpha_depth_prepass,
+            };
 
             for target in &pass.color.targets {
                 results.stats.color_target_count += 1;
 
                 let texture_id = target.texture_id();
+                render_target_textures.insert(texture_id);
 
                 let color_tex = self.texture_resolver.get_cache_texture_mut(&texture_id);
 
@@ -4778,9 +5920,10 @@ impl Renderer {
                     color_rt_info,
                 );
 
+                let with_depth = target.needs_depth() || alpha_depth_prepass;
                 let draw_target = DrawTarget::from_texture(
                     color_tex,
-                    target.needs_depth(),
+                    with_depth,
                 );
 
                 let projection = Transform3D::ortho(
@@ -4792,7 +5935,10 @@ impl Renderer {
                     self.device.ortho_far_plane(),
                 );
 
-                let clear_depth = if target.needs_depth() {
+                pass_info.color_target_count += 1;
+                pass_info.color_target_pixels += draw_target.dimensions().area() as usize;
+
+                let clear_depth = if with_depth {
                     Some(1.0)
                 } else {
                     None
@@ -4816,18 +5962,35 @@ impl Renderer {
             self.texture_resolver.end_pass(
                 &mut self.device,
                 &pass.textures_to_invalidate,
+                self.invalidate_targets_on_pass_end,
             );
             {
                 profile_scope!("gl.flush");
                 self.device.gl().flush();
             }
+
+            results.passes.push(pass_info);
         }
 
+        results.stats.render_target_texture_count = render_target_textures.len();
+
+        let window_backdrop = device_size.and_then(|_| {
+            self.window_backdrop_blur_params(frame.device_rect.size())
+        });
+        let draw_window_backdrop = match window_backdrop {
+            Some((size, std_deviation)) => {
+                self.blur_window_backdrop(size, std_deviation, &mut results.stats)
+            }
+            None => false,
+        };
+
         self.composite_frame(
             frame,
             device_size,
+            clear_color,
             results,
             present_mode,
+            draw_window_backdrop,
         );
 
         if let Some(gpu_buffer_texture_f) = gpu_buffer_texture_f {
@@ -4844,8 +6007,10 @@ impl Renderer {
         &mut self,
         frame: &mut Frame,
         device_size: Option<DeviceIntSize>,
+        clear_color: ColorF,
         results: &mut RenderResults,
         present_mode: Option<PartialPresentMode>,
+        draw_window_backdrop: bool,
     ) {
         profile_scope!("main target");
 
@@ -4855,6 +6020,7 @@ impl Renderer {
                 &mut frame.composite_state.picture_cache_debug,
                 PictureCacheDebugInfo::new(),
             );
+            results.composite_surfaces = frame.composite_state.surface_infos();
 
             let size = frame.device_rect.size().to_f32();
             let surface_origin_is_top_left = self.device.surface_origin_is_top_left();
@@ -4873,8 +6039,62 @@ impl Renderer {
                 self.device.ortho_far_plane(),
             );
 
+            // The size the document is presented at, before any present mode
+            // or scaling is applied.
+            let output_size = if frame.render_scale != 1.0 {
+                (frame.device_rect.size().to_f32() / frame.render_scale).round().to_i32()
+            } else {
+                frame.device_rect.size()
+            };
+
+            let safe_rect = DeviceIntRect::from_size(device_size)
+                .inner_box(self.safe_area_insets);
+            let device_rect = match (self.integer_scaling, self.present_mode) {
+                (Some(factor), _) if !frame.device_rect.is_empty() => {
+                    let factor = factor.min(i32::MAX as u32) as i32;
+                    let size = DeviceIntSize::new(
+                        output_size.width.saturating_mul(factor),
+                        output_size.height.saturating_mul(factor),
+                    );
+                    DeviceIntRect::from_origin_and_size(
+                        DeviceIntPoint::new(
+                            (safe_rect.width() - size.width) / 2,
+                            (safe_rect.height() - size.height) / 2,
+                        ),
+                        size,
+                    )
+                }
+                (None, Some(present_mode)) if !frame.device_rect.is_empty() => {
+                    present_mode.present_rect(output_size, safe_rect.size())
+                }
+                _ => DeviceIntRect::from_origin_and_size(frame.device_rect.min, output_size),
+            }.translate(safe_rect.min.to_vector());
+
+            // The safe area, mapped to the document space that tiles are
+            // composited in, so that nothing is drawn over the insets.
+            let content_clip_rect = if device_rect.is_empty() {
+                DeviceRect::zero()
+            } else {
+                let scale_x = frame.device_rect.width() as f32 / device_rect.width() as f32;
+                let scale_y = frame.device_rect.height() as f32 / device_rect.height() as f32;
+                DeviceRect::from_origin_and_size(
+                    (safe_rect.min - device_rect.min).to_f32().to_point(),
+                    safe_rect.size().to_f32(),
+                ).scale(scale_x, scale_y)
+            };
+
+            let vignette = match self.vignette {
+                Some(ref params) => CompositeVignette {
+                    center: DeviceRect::from_size(size).center(),
+                    inner_radius: params.inner_radius,
+                    outer_radius: params.outer_radius,
+                    color: params.color.premultiplied(),
+                },
+                None => CompositeVignette::NONE,
+            };
+
             let fb_scale = Scale::<_, _, FramebufferPixel>::new(1i32);
-            let mut fb_rect = frame.device_rect * fb_scale;
+            let mut fb_rect = device_rect * fb_scale;
 
             if !surface_origin_is_top_left {
                 let h = fb_rect.height();
@@ -4901,13 +6121,75 @@ impl Renderer {
                     );
                 }
                 CompositorKind::Draw { .. } => {
+                    // When rendering below full resolution or with integer
+                    // scaling, the document is composited at its rasterized
+                    // size into an intermediate target, which is then
+                    // upsampled to the framebuffer.
+                    let upsample = (frame.render_scale != 1.0 || self.integer_scaling.is_some()) &&
+                        !frame.device_rect.is_empty();
+                    if upsample {
+                        let target_size = frame.device_rect.size();
+                        let needs_alloc = match self.render_scale_target {
+                            Some(ref texture) => texture.get_dimensions() != target_size,
+                            None => true,
+                        };
+                        if needs_alloc {
+                            if let Some(texture) = self.render_scale_target.take() {
+                                self.device.delete_texture(texture);
+                            }
+                            self.render_scale_target = Some(self.device.create_texture(
+                                ImageBufferKind::Texture2D,
+                                ImageFormat::RGBA8,
+                                target_size.width,
+                                target_size.height,
+                                TextureFilter::Linear,
+                                Some(RenderTargetInfo { has_depth: false }),
+                            ));
+                        }
+                    } else if let Some(texture) = self.render_scale_target.take() {
+                        self.device.delete_texture(texture);
+                    }
+
+                    let (composite_target, composite_projection) = match self.render_scale_target {
+                        Some(ref texture) => {
+                            let projection = Transform3D::ortho(
+                                0.0,
+                                size.width,
+                                0.0,
+                                size.height,
+                                self.device.ortho_near_plane(),
+                                self.device.ortho_far_plane(),
+                            );
+                            (DrawTarget::from_texture(texture, false), projection)
+                        }
+                        None => (draw_target, projection),
+                    };
+
                     self.composite_simple(
                         &frame.composite_state,
-                        draw_target,
-                        &projection,
+                        composite_target,
+                        &composite_projection,
+                        clear_color,
                         results,
                         present_mode,
+                        content_clip_rect,
+                        vignette,
+                        if draw_window_backdrop { Some(DeviceRect::from_size(size)) } else { None },
                     );
+
+                    if upsample {
+                        self.upsample_render_scale_target(draw_target, clear_color);
+                    }
+
+                    if self.output_alpha_mode == AlphaMode::Straight {
+                        self.unpremultiply_framebuffer(
+                            device_size,
+                            surface_origin_is_top_left,
+                            &mut results.stats,
+                        );
+                    } else if let Some(texture) = self.output_alpha_texture.take() {
+                        self.device.delete_texture(texture);
+                    }
                 }
             }
         } else {
@@ -4917,6 +6199,411 @@ impl Renderer {
         }
     }
 
+    /// Clear the framebuffer and upsample the document, composited into the
+    /// render scale target, into the presented rect with bilinear filtering,
+    /// or nearest filtering for integer scaling.
+    fn upsample_render_scale_target(
+        &mut self,
+        draw_target: DrawTarget,
+        clear_color: ColorF,
+    ) {
+        let _gm = self.gpu_profiler.start_marker("upsample render scale target");
+
+        let (fb_rect, surface_origin_is_top_left) = match draw_target {
+            DrawTarget::Default { rect, surface_origin_is_top_left, .. } => {
+                (rect, surface_origin_is_top_left)
+            }
+            _ => unreachable!(),
+        };
+
+        // The composite only cleared the intermediate target, so clear the
+        // parts of the framebuffer around the document here.
+        let clear_color = match self.present_mode {
+            Some(PresentMode::Letterbox(bar_color)) => bar_color,
+            _ => clear_color,
+        };
+        self.device.bind_draw_target(draw_target);
+        self.device.clear_target(Some(clear_color.to_array()), None, None);
+
+        let texture = self.render_scale_target.as_ref().unwrap();
+        let src_rect = FramebufferIntRect::from_size(
+            device_size_as_framebuffer_size(texture.get_dimensions()),
+        );
+        let filter = if self.integer_scaling.is_some() {
+            TextureFilter::Nearest
+        } else {
+            TextureFilter::Linear
+        };
+        // The target has origin-bottom-left rows, like any texture target.
+        let mut dest_rect = fb_rect;
+        if !surface_origin_is_top_left {
+            dest_rect.min.y = fb_rect.max.y;
+            dest_rect.max.y = fb_rect.min.y;
+        }
+        self.device.blit_render_target(
+            ReadTarget::from_texture(texture),
+            src_rect,
+            draw_target,
+            dest_rect,
+            filter,
+        );
+    }
+
+    /// Divide the color channels of the whole framebuffer by its alpha, by
+    /// copying it into a texture and compositing that back without blending.
+    fn unpremultiply_framebuffer(
+        &mut self,
+        device_size: DeviceIntSize,
+        surface_origin_is_top_left: bool,
+        stats: &mut RendererStats,
+    ) {
+        let _gm = self.gpu_profiler.start_marker("unpremultiply framebuffer");
+
+        let needs_alloc = match self.output_alpha_texture {
+            Some(ref texture) => texture.get_dimensions() != device_size,
+            None => true,
+        };
+        if needs_alloc {
+            if let Some(texture) = self.output_alpha_texture.take() {
+                self.device.delete_texture(texture);
+            }
+            self.output_alpha_texture = Some(self.device.create_texture(
+                ImageBufferKind::Texture2D,
+                ImageFormat::RGBA8,
+                device_size.width,
+                device_size.height,
+                TextureFilter::Nearest,
+                Some(RenderTargetInfo { has_depth: false }),
+            ));
+        }
+        self.device.disable_depth();
+        self.set_blend(false, FramebufferKind::Main);
+        let texture = self.output_alpha_texture.as_ref().unwrap();
+
+        let fb_rect = FramebufferIntRect::from_size(device_size_as_framebuffer_size(device_size));
+        let draw_target = DrawTarget::Default {
+            rect: fb_rect,
+            total_size: fb_rect.size(),
+            surface_origin_is_top_left,
+        };
+        self.device.blit_render_target(
+            ReadTarget::Default,
+            fb_rect,
+            DrawTarget::from_texture(texture, false),
+            fb_rect,
+            TextureFilter::Nearest,
+        );
+
+        let size = device_size.to_f32();
+        let (bottom, top) = if surface_origin_is_top_left {
+            (0.0, size.height)
+        } else {
+            (size.height, 0.0)
+        };
+        let projection = Transform3D::ortho(
+            0.0,
+            size.width,
+            bottom,
+            top,
+            self.device.ortho_near_plane(),
+            self.device.ortho_far_plane(),
+        );
+
+        self.device.bind_draw_target(draw_target);
+        self.shaders
+            .borrow_mut()
+            .get_composite_shader(
+                CompositeSurfaceFormat::Rgba,
+                ImageBufferKind::Texture2D,
+                CompositeFeatures::empty(),
+            ).bind(
+                &mut self.device,
+                &projection,
+                None,
+                &mut self.renderer_errors,
+                &mut self.profile,
+            );
+        self.device.bind_texture(TextureSampler::Color0, texture, Swizzle::default());
+
+        // The copy has the row order of the framebuffer, so it is flipped
+        // when that doesn't match the projection.
+        let rect = DeviceRect::from_size(size);
+        let instance = CompositeInstance::new_rgb(
+            rect,
+            rect,
+            PremultipliedColorF::WHITE,
+            TexelRect::new(0.0, 0.0, 1.0, 1.0),
+            true,
+            (false, !surface_origin_is_top_left),
+        ).with_straight_alpha();
+
+        self.draw_instanced_batch(
+            &[instance],
+            VertexArrayKind::Composite,
+            &BatchTextures::empty(),
+            stats,
+        );
+    }
+
+    /// The size of the targets the window backdrop is blurred in, and the
+    /// standard deviation of the blur at that size, if a backdrop is set.
+    /// Like blur render tasks, large blurs run on a downscaled target.
+    fn window_backdrop_blur_params(&self, document_size: DeviceIntSize) -> Option<(DeviceIntSize, f32)> {
+        if let CompositorKind::Native { .. } = self.current_compositor_kind {
+            return None;
+        }
+        self.window_backdrop_image?;
+        let mut std_deviation = self.window_backdrop_blur?;
+        if document_size.is_empty() {
+            return None;
+        }
+
+        let mut size = document_size;
+        while std_deviation > MAX_BLUR_STD_DEVIATION &&
+              size.width >= MIN_DOWNSCALING_RT_SIZE * 2 &&
+              size.height >= MIN_DOWNSCALING_RT_SIZE * 2 {
+            std_deviation *= 0.5;
+            size = DeviceIntSize::new((size.width + 1) / 2, (size.height + 1) / 2);
+        }
+
+        Some((size, std_deviation))
+    }
+
+    /// Downscale the window backdrop image into the first backdrop target, and
+    /// blur it there with a vertical and a horizontal pass. Returns false if
+    /// the backdrop image couldn't be resolved.
+    fn blur_window_backdrop(
+        &mut self,
+        size: DeviceIntSize,
+        std_deviation: f32,
+        stats: &mut RendererStats,
+    ) -> bool {
+        let image = self.window_backdrop_image.unwrap();
+        let target = match image.image_type {
+            ExternalImageType::TextureHandle(target) => target,
+            ExternalImageType::Buffer => {
+                warn!("Window backdrop images must be texture handles");
+                return false;
+            }
+        };
+        let handler = match self.external_image_handler.as_mut() {
+            Some(handler) => handler,
+            None => {
+                warn!("Window backdrop image set, but no external image handler");
+                return false;
+            }
+        };
+
+        let ext_image = self.locked_external_images.lock(
+            &mut **handler,
+            image.id,
+            image.channel_index,
+        );
+        let texture = match ext_image.source {
+            ExternalImageSource::NativeTexture(texture_id) => {
+                Some(ExternalTexture::new(texture_id, target, ext_image.uv, ImageRendering::Auto))
+            }
+            _ => None,
+        };
+
+        // The external image handler may have changed some GL state.
+        self.device.reset_state();
+
+        let texture = match texture {
+            Some(texture) => texture,
+            None => {
+                warn!("Invalid window backdrop image");
+                let handler = self.external_image_handler.as_mut().unwrap();
+                self.locked_external_images.unlock(&mut **handler, image.id, image.channel_index);
+                return false;
+            }
+        };
+
+        let _gm = self.gpu_profiler.start_marker("window backdrop");
+        let _timer = self.gpu_profiler.start_timer(GPU_TAG_BLUR);
+
+        let needs_alloc = match self.window_backdrop_targets {
+            Some((ref texture, _)) => texture.get_dimensions() != size,
+            None => true,
+        };
+        if needs_alloc {
+            if let Some((first, second)) = self.window_backdrop_targets.take() {
+                self.device.delete_texture(first);
+                self.device.delete_texture(second);
+            }
+            let mut create_target = || self.device.create_texture(
+                ImageBufferKind::Texture2D,
+                ImageFormat::RGBA8,
+                size.width,
+                size.height,
+                TextureFilter::Linear,
+                Some(RenderTargetInfo { has_depth: false }),
+            );
+            let targets = (create_target(), create_target());
+            self.window_backdrop_targets = Some(targets);
+        }
+
+        let projection = Transform3D::ortho(
+            0.0,
+            size.width as f32,
+            0.0,
+            size.height as f32,
+            self.device.ortho_near_plane(),
+            self.device.ortho_far_plane(),
+        );
+        self.device.disable_depth();
+        self.set_blend(false, FramebufferKind::Other);
+
+        // Stretch the backdrop image over the first target.
+        {
+            let (ref first, _) = *self.window_backdrop_targets.as_ref().unwrap();
+            self.device.bind_draw_target(DrawTarget::from_texture(first, false));
+        }
+        self.shaders
+            .borrow_mut()
+            .get_composite_shader(
+                CompositeSurfaceFormat::Rgba,
+                target,
+                CompositeFeatures::empty(),
+            ).bind(
+                &mut self.device,
+                &projection,
+                None,
+                &mut self.renderer_errors,
+                &mut self.profile,
+            );
+        self.device.bind_external_texture(TextureSampler::Color0, &texture);
+
+        let rect = DeviceRect::from_size(size.to_f32());
+        let instance = CompositeInstance::new_rgb(
+            rect,
+            rect,
+            PremultipliedColorF::WHITE,
+            texture.get_uv_rect(),
+            image.normalized_uvs,
+            (false, false),
+        );
+        self.draw_instanced_batch(
+            &[instance],
+            VertexArrayKind::Composite,
+            &BatchTextures::empty(),
+            stats,
+        );
+
+        // Blur it vertically into the second target, and back horizontally.
+        // Both targets use the same task rect, so a single task serves as
+        // the source and destination of each pass.
+        {
+            let mut task_data = FrameAllocator::fallback().new_vec();
+            task_data.push(RenderTaskData {
+                data: [0.0, 0.0, size.width as f32, size.height as f32, 0.0, 0.0, 0.0, 0.0],
+            });
+            let mut uploader = self.device.upload_texture(&mut self.texture_upload_pbo_pool);
+            self.window_backdrop_task_texture.update(&mut self.device, &mut uploader, &mut task_data);
+            uploader.flush(&mut self.device);
+        }
+        self.device.bind_texture(
+            TextureSampler::RenderTasks,
+            self.window_backdrop_task_texture.texture(),
+            Swizzle::default(),
+        );
+        let task_address = RenderTaskAddress(0);
+
+        self.shaders.borrow_mut().cs_blur_rgba8
+            .bind(&mut self.device, &projection, None, &mut self.renderer_errors, &mut self.profile);
+        for &direction in &[BlurDirection::Vertical, BlurDirection::Horizontal] {
+            {
+                let (ref first, ref second) = *self.window_backdrop_targets.as_ref().unwrap();
+                let (source, dest) = match direction {
+                    BlurDirection::Vertical => (first, second),
+                    BlurDirection::Horizontal => (second, first),
+                };
+                self.device.bind_draw_target(DrawTarget::from_texture(dest, false));
+                self.device.bind_texture(TextureSampler::Color0, source, Swizzle::default());
+            }
+
+            let instance = BlurInstance {
+                task_address,
+                src_task_address: task_address,
+                blur_direction: direction.as_int(),
+                blur_std_deviation: std_deviation,
+                blur_region: size.to_f32(),
+            };
+            self.draw_instanced_batch(
+                &[instance],
+                VertexArrayKind::Blur,
+                &BatchTextures::empty(),
+                stats,
+            );
+        }
+
+        // Put the frame's render task data back for anything drawn afterwards.
+        let frame_textures = (self.current_vertex_data_textures + VERTEX_DATA_TEXTURE_COUNT - 1)
+            % VERTEX_DATA_TEXTURE_COUNT;
+        self.vertex_data_textures[frame_textures].bind_render_tasks(&mut self.device);
+
+        let handler = self.external_image_handler.as_mut().unwrap();
+        self.locked_external_images.unlock(&mut **handler, image.id, image.channel_index);
+
+        true
+    }
+
+    /// Composite the blurred window backdrop over the cleared framebuffer,
+    /// below all picture cache tiles.
+    fn composite_window_backdrop(
+        &mut self,
+        rect: DeviceRect,
+        projection: &default::Transform3D<f32>,
+        has_depth: bool,
+        stats: &mut RendererStats,
+    ) {
+        // The backdrop is behind everything, so it must not write depth.
+        if has_depth {
+            self.device.disable_depth_write();
+            self.device.disable_depth();
+        }
+        self.set_blend(true, FramebufferKind::Main);
+        self.set_blend_mode_premultiplied_alpha(FramebufferKind::Main);
+
+        self.shaders
+            .borrow_mut()
+            .get_composite_shader(
+                CompositeSurfaceFormat::Rgba,
+                ImageBufferKind::Texture2D,
+                CompositeFeatures::empty(),
+            ).bind(
+                &mut self.device,
+                projection,
+                None,
+                &mut self.renderer_errors,
+                &mut self.profile,
+            );
+        {
+            let (ref blurred, _) = *self.window_backdrop_targets.as_ref().unwrap();
+            self.device.bind_texture(TextureSampler::Color0, blurred, Swizzle::default());
+        }
+
+        let instance = CompositeInstance::new_rgb(
+            rect,
+            rect,
+            PremultipliedColorF::WHITE,
+            TexelRect::new(0.0, 0.0, 1.0, 1.0),
+            true,
+            (false, false),
+        );
+        self.draw_instanced_batch(
+            &[instance],
+            VertexArrayKind::Composite,
+            &BatchTextures::empty(),
+            stats,
+        );
+
+        if has_depth {
+            self.device.enable_depth(DepthFunction::LessEqual);
+            self.device.enable_depth_write();
+        }
+    }
+
     pub fn debug_renderer(&mut self) -> Option<&mut DebugRenderer> {
         self.debug.get_mut(&mut self.device)
     }
@@ -4925,6 +6612,38 @@ impl Renderer {
         self.debug_flags
     }
 
+    /// Only draw the batches of the given kind, skipping all others, or
+    /// everything if None. Meant for isolating primitives while debugging
+    /// shaders. Picture cache tiles are only affected when they are redrawn,
+    /// see `DebugFlags::FORCE_PICTURE_INVALIDATION`.
+    pub fn set_primitive_isolation(&mut self, kind: Option<IsolatedBatchKind>) {
+        if kind != self.primitive_isolation {
+            self.primitive_isolation = kind;
+            self.force_redraw();
+        }
+    }
+
+    pub fn get_primitive_isolation(&self) -> Option<IsolatedBatchKind> {
+        self.primitive_isolation
+    }
+
+    /// Only draw opaque batches, skipping the alpha pass and the alpha depth
+    /// prepass, to isolate rendering bugs to the opaque pass. Transparent
+    /// content is missing from the output as a result. Takes precedence over
+    /// `DebugFlags::DISABLE_OPAQUE_PASS`, so that exactly one pass runs.
+    /// Picture cache tiles are only affected when they are redrawn, see
+    /// `DebugFlags::FORCE_PICTURE_INVALIDATION`.
+    pub fn set_opaque_only(&mut self, opaque_only: bool) {
+        if opaque_only != self.opaque_only {
+            self.opaque_only = opaque_only;
+            self.force_redraw();
+        }
+    }
+
+    pub fn get_opaque_only(&self) -> bool {
+        self.opaque_only
+    }
+
     pub fn set_debug_flags(&mut self, flags: DebugFlags) {
         if let Some(enabled) = flag_changed(self.debug_flags, flags, DebugFlags::GPU_TIME_QUERIES) {
             if enabled {
@@ -5231,6 +6950,27 @@ impl Renderer {
         }
     }
 
+    fn draw_layout_grid_debug(&mut self, device_size: DeviceIntSize) {
+        if !self.debug_flags.contains(DebugFlags::LAYOUT_GRID_DBG) {
+            return;
+        }
+
+        let debug_renderer = match self.debug.get_mut(&mut self.device) {
+            Some(render) => render,
+            None => return,
+        };
+
+        let color = ColorU::new(255, 0, 255, 96);
+        let spacing = self.debug_grid_spacing;
+
+        for x in (0 .. device_size.width).step_by(spacing.width as usize) {
+            debug_renderer.add_line(x, 0, color, x, device_size.height, color);
+        }
+        for y in (0 .. device_size.height).step_by(spacing.height as usize) {
+            debug_renderer.add_line(0, y, color, device_size.width, y, color);
+        }
+    }
+
     fn draw_epoch_debug(&mut self) {
         if !self.debug_flags.contains(DebugFlags::EPOCHS) {
             return;
@@ -5337,17 +7077,90 @@ impl Renderer {
         }
     }
 
-    /// Pass-through to `Device::read_pixels_into`, used by Gecko's WR bindings.
-    pub fn read_pixels_into(&mut self, rect: FramebufferIntRect, format: ImageFormat, output: &mut [u8]) {
-        self.device.read_pixels_into(rect, format, output);
+    /// Read back a rect of the currently bound framebuffer in the given format,
+    /// used by Gecko's WR bindings. BGRA8 is read as RGBA8 and swizzled on the
+    /// CPU if the driver can't read it directly. The output must be exactly
+    /// the size of the rect in that format.
+    pub fn read_pixels_into(
+        &mut self,
+        rect: FramebufferIntRect,
+        format: ImageFormat,
+        output: &mut [u8],
+    ) -> Result<(), RendererError> {
+        if output.len() != (rect.area() * format.bytes_per_pixel()) as usize {
+            return Err(RendererError::ReadPixelsSizeMismatch);
+        }
+
+        match format {
+            ImageFormat::BGRA8 if !self.device.get_capabilities().supports_bgra_readback => {
+                self.device.read_pixels_into(rect, ImageFormat::RGBA8, output);
+                for pixel in output.chunks_exact_mut(4) {
+                    pixel.swap(0, 2);
+                }
+            }
+            _ => {
+                self.device.read_pixels_into(rect, format, output);
+            }
+        }
+
+        Ok(())
     }
 
     pub fn read_pixels_rgba8(&mut self, rect: FramebufferIntRect) -> Vec<u8> {
         let mut pixels = vec![0; (rect.area() * 4) as usize];
-        self.device.read_pixels_into(rect, ImageFormat::RGBA8, &mut pixels);
+        self.read_pixels_into(rect, ImageFormat::RGBA8, &mut pixels).unwrap();
         pixels
     }
 
+    /// Read back the contents of a picture cache tile composited in the last
+    /// frame of a document, as RGBA8 rows starting from the top of the tile.
+    /// Only the part of the tile that was composited is read, which is the
+    /// `device_rect` of the tile in `RenderResults::composite_surfaces`, where
+    /// tile ids are reported. Returns None if the tile isn't part of that
+    /// frame, or if it has no texture, e.g. for solid color tiles or with a
+    /// native compositor.
+    pub fn read_picture_cache_tile(&mut self, doc_id: DocumentId, tile_id: TileId) -> Option<Vec<u8>> {
+        let composite_state = &self.active_documents.get(&doc_id)?.frame.composite_state;
+        let (texture_source, region) = composite_state.tiles
+            .iter()
+            .filter(|tile| tile.tile_id == Some(tile_id))
+            .find_map(|tile| {
+                let texture = match tile.surface {
+                    CompositeTileSurface::Texture { surface: ResolvedSurfaceTexture::TextureCache { texture } } => texture,
+                    _ => return None,
+                };
+                let tile_rect = composite_state.get_device_rect(&tile.local_rect, tile.transform_index);
+                let region = tile_rect
+                    .intersection(&tile.device_clip_rect)?
+                    .intersection(&composite_state.get_device_rect(&tile.local_valid_rect, tile.transform_index))?;
+                Some((texture, region.translate(-tile_rect.min.to_vector()).round().to_i32()))
+            })?;
+        let (texture, _) = self.texture_resolver.resolve(&texture_source)?;
+        let region = region.intersection(&DeviceIntRect::from_size(texture.get_dimensions()))?;
+
+        let mut pixels = vec![0; (region.area() * 4) as usize];
+
+        self.device.begin_frame();
+        self.device.bind_read_target_impl(self.read_fbo, DeviceIntPoint::zero());
+        self.device.attach_read_texture(texture);
+        self.device.read_pixels_into(
+            region.cast_unit(),
+            ImageFormat::RGBA8,
+            &mut pixels,
+        );
+        self.device.reset_read_target();
+        self.device.end_frame();
+
+        Some(pixels)
+    }
+
+    /// Read back the depth values of the currently bound framebuffer, e.g. to
+    /// check the output of `WebRenderOptions::output_linear_depth`. Returns
+    /// None on GLES, which can't read back depth attachments.
+    pub fn read_depth_pixels(&mut self, rect: FramebufferIntRect) -> Option<Vec<f32>> {
+        self.device.read_depth_pixels(rect)
+    }
+
     // De-initialize the Renderer safely, assuming the GL is still alive and active.
     pub fn deinit(mut self) {
         //Note: this is a fake frame, only needed because texture deletion is require to happen inside a frame
@@ -5365,6 +7178,20 @@ impl Renderer {
             compositor.deinit(&mut self.device);
         }
         self.gpu_cache_texture.deinit(&mut self.device);
+        if let Some(target) = self.clip_mask_msaa_target.take() {
+            self.device.delete_multisampled_fbo(target);
+        }
+        if let Some(texture) = self.output_alpha_texture.take() {
+            self.device.delete_texture(texture);
+        }
+        if let Some(texture) = self.render_scale_target.take() {
+            self.device.delete_texture(texture);
+        }
+        if let Some((first, second)) = self.window_backdrop_targets.take() {
+            self.device.delete_texture(first);
+            self.device.delete_texture(second);
+        }
+        self.window_backdrop_task_texture.deinit(&mut self.device);
         if let Some(dither_matrix_texture) = self.dither_matrix_texture {
             self.device.delete_texture(dither_matrix_texture);
         }
@@ -5376,6 +7203,13 @@ impl Renderer {
         }
         self.texture_upload_pbo_pool.deinit(&mut self.device);
         self.staging_texture_pool.delete_textures(&mut self.device);
+        if let Some(ref mut hook) = self.texture_allocator_hook {
+            for item in self.texture_resolver.texture_cache_map.values() {
+                if item.texture.flags().contains(TextureFlags::IS_EMBEDDER_OWNED) {
+                    hook.release(item.texture.gl_id());
+                }
+            }
+        }
         self.texture_resolver.deinit(&mut self.device);
         self.vaos.deinit(&mut self.device);
         self.debug.deinit(&mut self.device);
@@ -5434,6 +7268,35 @@ impl Renderer {
         report
     }
 
+    /// The GPU memory fields of `report_memory`, which don't require measuring
+    /// heap allocations.
+    fn report_gpu_memory(&self) -> MemoryReport {
+        let mut report = MemoryReport::default();
+        report.gpu_cache_textures = self.gpu_cache_texture.gpu_size_in_bytes();
+        self.staging_texture_pool.report_gpu_memory_to(&mut report);
+        for textures in &self.vertex_data_textures {
+            report.vertex_data_textures += textures.size_in_bytes();
+        }
+        report += self.texture_resolver.report_memory();
+        report += self.texture_upload_pbo_pool.report_memory();
+        report.depth_target_textures = self.device.depth_targets_memory();
+
+        report
+    }
+
+    /// Returns the largest value of each GPU memory field of `report_memory`
+    /// observed at the end of a frame, since the renderer was created or since
+    /// the last call to `reset_peak_gpu_memory`. The CPU memory fields are
+    /// left at zero.
+    pub fn peak_gpu_memory(&self) -> MemoryReport {
+        self.peak_memory.clone()
+    }
+
+    /// Restart tracking the peak GPU memory usage from the current usage.
+    pub fn reset_peak_gpu_memory(&mut self) {
+        self.peak_memory = self.report_gpu_memory();
+    }
+
     // Sets the blend mode. Blend is unconditionally set if the "show overdraw" debugging mode is
     // enabled.
     fn set_blend(&mut self, mut blend: bool, framebuffer_kind: FramebufferKind) {
@@ -5522,15 +7385,34 @@ pub struct RendererStats {
     pub total_draw_calls: usize,
     pub alpha_target_count: usize,
     pub color_target_count: usize,
+    /// Number of distinct intermediate render target textures drawn into. See
+    /// `WebRenderOptions::max_render_targets_per_frame`.
+    pub render_target_texture_count: usize,
     pub texture_upload_mb: f64,
     pub resource_upload_time: f64,
     pub gpu_cache_upload_time: f64,
+    /// Time in milliseconds that `Renderer::update` waited on the render
+    /// backend for a requested frame since the previous render. It runs from
+    /// the later of the first update that found no new frame and the
+    /// `generate_frame` transaction being created, to the update that
+    /// received the frame. Zero if each update found a frame, or if no frame
+    /// was requested.
+    pub backend_wait_time: f64,
     pub gecko_display_list_time: f64,
     pub wr_display_list_time: f64,
     pub scene_build_time: f64,
     pub frame_build_time: f64,
     pub full_display_list: bool,
     pub full_paint: bool,
+    pub render_task_cache_hits: usize,
+    pub render_task_cache_misses: usize,
+    pub cancelled_scene_builds: usize,
+    /// Whether the frame's texture uploads exceeded
+    /// `WebRenderOptions::texture_upload_budget_bytes`.
+    pub over_upload_budget: bool,
+    /// Whether the GPU cache texture had to grow this frame, which copies
+    /// or re-uploads its entire contents.
+    pub gpu_cache_resized: bool,
 }
 
 impl RendererStats {
@@ -5561,9 +7443,65 @@ pub struct RenderResults {
     ///           specific picture cache slice / OS compositor surface).
     pub dirty_rects: Vec<DeviceIntRect>,
 
+    /// The native compositor tiles that were drawn this frame, both picture
+    /// cache tiles and external surfaces, with the dirty rect passed to
+    /// `Compositor::bind` for each. Only populated when using
+    /// `CompositorKind::Native`; empty otherwise.
+    pub native_surface_dirty_rects: Vec<(NativeTileId, DeviceIntRect)>,
+
     /// Information about the state of picture cache tiles. This is only
     /// allocated and stored if config.testing is true (such as wrench)
     pub picture_cache_debug: PictureCacheDebugInfo,
+
+    /// The surfaces that were composited this frame, with their device rects
+    /// and transforms. Empty if the frame was not composited.
+    pub composite_surfaces: Vec<CompositeSurfaceInfo>,
+
+    /// The off-screen render passes drawn this frame, in order.
+    pub passes: Vec<PassInfo>,
+
+    /// Ids set with `Transaction::set_trace_id` on the transactions that
+    /// contributed to this frame, in the order they were applied.
+    pub trace_ids: Vec<u64>,
+
+    /// Identifies this frame. Starts at 1 and increases by exactly one
+    /// with each call to `Renderer::render` that draws a frame, across all
+    /// documents. The `CpuProfile` and `GpuProfile` recorded for the frame
+    /// carry the same id. Zero if there was no document to render.
+    pub frame_id: u64,
+}
+
+/// Describes the render targets drawn in an off-screen render pass. See
+/// `RenderResults::passes`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PassInfo {
+    /// Number of color render targets drawn in this pass.
+    pub color_target_count: usize,
+    /// Sum of the areas of the color render targets, in device pixels.
+    pub color_target_pixels: usize,
+    /// Number of alpha render targets drawn in this pass.
+    pub alpha_target_count: usize,
+    /// Sum of the areas of the alpha render targets, in device pixels.
+    pub alpha_target_pixels: usize,
+}
+
+/// A compact description of a presented frame, passed to
+/// `WebRenderOptions::frame_summary_sink`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FrameSummary {
+    /// The frame's `RenderResults::frame_id`.
+    pub frame_id: u64,
+    /// The document that was drawn.
+    pub document_id: DocumentId,
+    /// The newest epoch of the document's pipelines, if any.
+    pub epoch: Option<Epoch>,
+    /// Number of draw calls issued for the frame.
+    pub draw_calls: usize,
+    /// The GPU time of the most recent frame whose timer queries have
+    /// resolved. Only available when `DebugFlags::GPU_TIME_QUERIES` is set.
+    pub gpu_time_ns: Option<u64>,
+    /// Sum of the areas of the frame's dirty rects, in device pixels.
+    pub dirty_area: usize,
 }
 
 #[cfg(any(feature = "capture", feature = "replay"))]
@@ -5716,6 +7654,76 @@ impl Renderer {
         (texture, texels)
     }
 
+    #[cfg(feature = "capture")]
+    fn save_glyph_atlas(
+        &mut self,
+        root: PathBuf,
+        entries: Vec<GlyphAtlasEntry>,
+    ) -> std::io::Result<()> {
+        use std::fs;
+        use std::io::Write;
+
+        if !root.is_dir() {
+            fs::create_dir_all(&root)?;
+        }
+
+        let mut texture_ids: Vec<CacheTextureId> = entries.iter().map(|e| e.texture_id).collect();
+        texture_ids.sort_by_key(|id| id.0);
+        texture_ids.dedup();
+
+        self.device.begin_frame();
+        let _gm = self.gpu_profiler.start_marker("read glyph atlas");
+        self.device.bind_read_target_impl(self.read_fbo, DeviceIntPoint::zero());
+
+        for id in &texture_ids {
+            let texture = match self.texture_resolver.texture_cache_map.get(id) {
+                Some(item) => &item.texture,
+                None => continue,
+            };
+            let size = texture.get_dimensions();
+            // Read back as RGBA8 so that R8 glyph atlases produce a viewable image.
+            let mut data = vec![0; (size.width * size.height * 4) as usize];
+            self.device.attach_read_texture(texture);
+            self.device.read_pixels_into(
+                device_size_as_framebuffer_size(size).into(),
+                ImageFormat::RGBA8,
+                &mut data,
+            );
+            #[cfg(feature = "png")]
+            CaptureConfig::save_png(
+                root.join(format!("atlas-{}.png", id.0)),
+                size,
+                ImageFormat::RGBA8,
+                None,
+                &data,
+            );
+        }
+
+        self.device.reset_read_target();
+        self.device.end_frame();
+
+        let mut file = fs::File::create(root.join("glyphs.txt"))?;
+        for entry in &entries {
+            writeln!(
+                file,
+                "atlas-{} {} {} {} {} {:?} {} {:?} {:?} {:?}",
+                entry.texture_id.0,
+                entry.rect.min.x,
+                entry.rect.min.y,
+                entry.rect.width(),
+                entry.rect.height(),
+                entry.instance_key,
+                entry.glyph_index,
+                entry.subpixel_offset.0,
+                entry.subpixel_offset.1,
+                entry.format,
+            )?;
+        }
+
+        info!("exported {} glyphs from {} atlases", entries.len(), texture_ids.len());
+        Ok(())
+    }
+
     #[cfg(feature = "capture")]
     fn save_capture(
         &mut self,
@@ -5990,7 +7998,74 @@ enum FramebufferKind {
     Other,
 }
 
-fn should_skip_batch(kind: &BatchKind, flags: DebugFlags) -> bool {
+/// The kinds of batches that can be drawn in isolation, see
+/// `Renderer::set_primitive_isolation`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum IsolatedBatchKind {
+    Solid,
+    Image,
+    YuvImage,
+    Blend,
+    MixBlend,
+    LinearGradient,
+    Opacity,
+    Text,
+    SplitComposite,
+    Quad,
+    RadialGradient,
+    ConicGradient,
+    Mask,
+}
+
+impl IsolatedBatchKind {
+    pub const ALL: [IsolatedBatchKind; 13] = [
+        IsolatedBatchKind::Solid,
+        IsolatedBatchKind::Image,
+        IsolatedBatchKind::YuvImage,
+        IsolatedBatchKind::Blend,
+        IsolatedBatchKind::MixBlend,
+        IsolatedBatchKind::LinearGradient,
+        IsolatedBatchKind::Opacity,
+        IsolatedBatchKind::Text,
+        IsolatedBatchKind::SplitComposite,
+        IsolatedBatchKind::Quad,
+        IsolatedBatchKind::RadialGradient,
+        IsolatedBatchKind::ConicGradient,
+        IsolatedBatchKind::Mask,
+    ];
+
+    fn matches(&self, kind: &BatchKind) -> bool {
+        match (*self, kind) {
+            (IsolatedBatchKind::Solid, BatchKind::Brush(BrushBatchKind::Solid)) |
+            (IsolatedBatchKind::Image, BatchKind::Brush(BrushBatchKind::Image(..))) |
+            (IsolatedBatchKind::Image, BatchKind::Brush(BrushBatchKind::WrappedImage(..))) |
+            (IsolatedBatchKind::YuvImage, BatchKind::Brush(BrushBatchKind::YuvImage(..))) |
+            (IsolatedBatchKind::Blend, BatchKind::Brush(BrushBatchKind::Blend)) |
+            (IsolatedBatchKind::MixBlend, BatchKind::Brush(BrushBatchKind::MixBlend { .. })) |
+            (IsolatedBatchKind::LinearGradient, BatchKind::Brush(BrushBatchKind::LinearGradient)) |
+            (IsolatedBatchKind::Opacity, BatchKind::Brush(BrushBatchKind::Opacity)) |
+            (IsolatedBatchKind::Text, BatchKind::TextRun(..)) |
+            (IsolatedBatchKind::SplitComposite, BatchKind::SplitComposite) |
+            (IsolatedBatchKind::Quad, BatchKind::Quad(PatternKind::ColorOrTexture)) |
+            (IsolatedBatchKind::RadialGradient, BatchKind::Quad(PatternKind::RadialGradient)) |
+            (IsolatedBatchKind::ConicGradient, BatchKind::Quad(PatternKind::ConicGradient)) |
+            (IsolatedBatchKind::Mask, BatchKind::Quad(PatternKind::Mask)) => true,
+            _ => false,
+        }
+    }
+}
+
+fn should_skip_batch(
+    kind: &BatchKind,
+    flags: DebugFlags,
+    isolation: Option<IsolatedBatchKind>,
+) -> bool {
+    if let Some(isolated) = isolation {
+        if !isolated.matches(kind) {
+            return true;
+        }
+    }
+
     match kind {
         BatchKind::TextRun(_) => {
             flags.contains(DebugFlags::DISABLE_TEXT_PRIMS)
@@ -6021,6 +8096,7 @@ impl CompositeState {
                 surface.surface_id.expect("bug: no native surface allocated"),
                 surface.transform,
                 surface.clip_rect.to_i32(),
+                surface.clip_radius,
                 surface.image_rendering,
             );
         }
@@ -6029,6 +8105,44 @@ impl CompositeState {
 }
 
 mod tests {
+    #[test]
+    fn test_external_image_locks() {
+        use super::ExternalImageLocks;
+        use api::{ExternalImage, ExternalImageHandler, ExternalImageId, ExternalImageSource};
+        use api::units::TexelRect;
+
+        struct FakeHandler {
+            data: Vec<u8>,
+        }
+
+        impl ExternalImageHandler for FakeHandler {
+            fn lock(&mut self, _key: ExternalImageId, _channel_index: u8) -> ExternalImage {
+                ExternalImage {
+                    uv: TexelRect::invalid(),
+                    source: ExternalImageSource::RawData(&self.data),
+                }
+            }
+            fn unlock(&mut self, _key: ExternalImageId, _channel_index: u8) {}
+        }
+
+        let mut handler = FakeHandler { data: vec![0xFF; 4] };
+        let mut locks = ExternalImageLocks::default();
+
+        locks.lock(&mut handler, ExternalImageId(1), 0);
+        locks.lock(&mut handler, ExternalImageId(2), 0);
+        locks.lock(&mut handler, ExternalImageId(2), 0);
+        assert_eq!(locks.to_vec().len(), 2);
+
+        // Release the first image, and only one of the two locks on the second,
+        // leaking the other.
+        locks.unlock(&mut handler, ExternalImageId(1), 0);
+        locks.unlock(&mut handler, ExternalImageId(2), 0);
+        assert_eq!(locks.to_vec(), vec![(ExternalImageId(2), 0)]);
+
+        locks.unlock(&mut handler, ExternalImageId(2), 0);
+        assert!(locks.to_vec().is_empty());
+    }
+
     #[test]
     fn test_buffer_damage_tracker() {
         use super::BufferDamageTracker;
@@ -5,7 +5,7 @@
 use api::{ImageBufferKind, units::DeviceSize};
 use crate::batch::{BatchKey, BatchKind, BrushBatchKind, BatchFeatures};
 use crate::composite::{CompositeFeatures, CompositeSurfaceFormat};
-use crate::device::{Device, Program, ShaderError};
+use crate::device::{Device, Program, ShaderError, VertexDescriptor};
 use crate::pattern::PatternKind;
 use euclid::default::Transform3D;
 use glyph_rasterizer::GlyphFormat;
@@ -151,6 +151,15 @@ impl LazilyCompiledShader {
         renderer_errors: &mut Vec<RendererError>,
         profile: &mut TransactionProfile,
     ) {
+        // Don't stall on a shader the driver is still compiling. The draws
+        // that need it are dropped until it's ready.
+        if let Some(ref program) = self.program {
+            if !device.is_program_link_complete(program) {
+                device.skip_draws_until_bind();
+                return;
+            }
+        }
+
         let update_projection = self.cached_projection != *projection;
         let program = match self.get_internal(device, ShaderPrecacheFlags::FULL_COMPILE, profile) {
             Ok(program) => program,
@@ -228,48 +237,18 @@ impl LazilyCompiledShader {
             profile.add(profiler::SHADER_BUILD_TIME, ns_to_ms(end_time - start_time));
         }
 
+        let vertex_descriptor = self.vertex_descriptor();
         let program = self.program.as_mut().unwrap();
 
+        // Hand the compile and link to the driver now, so that it can work
+        // on them in the background until the shader is first bound.
+        if precache_flags.contains(ShaderPrecacheFlags::ASYNC_COMPILE) && !program.is_initialized() {
+            device.start_link_program(program, vertex_descriptor)?;
+        }
+
         if precache_flags.contains(ShaderPrecacheFlags::FULL_COMPILE) && !program.is_initialized() {
             let start_time = precise_time_ns();
 
-            let vertex_format = match self.kind {
-                ShaderKind::Primitive |
-                ShaderKind::Brush |
-                ShaderKind::Text => VertexArrayKind::Primitive,
-                ShaderKind::Cache(format) => format,
-                ShaderKind::VectorStencil => VertexArrayKind::VectorStencil,
-                ShaderKind::VectorCover => VertexArrayKind::VectorCover,
-                ShaderKind::ClipCache(format) => format,
-                ShaderKind::Resolve => VertexArrayKind::Resolve,
-                ShaderKind::Composite => VertexArrayKind::Composite,
-                ShaderKind::Clear => VertexArrayKind::Clear,
-                ShaderKind::Copy => VertexArrayKind::Copy,
-            };
-
-            let vertex_descriptor = match vertex_format {
-                VertexArrayKind::Primitive => &desc::PRIM_INSTANCES,
-                VertexArrayKind::LineDecoration => &desc::LINE,
-                VertexArrayKind::FastLinearGradient => &desc::FAST_LINEAR_GRADIENT,
-                VertexArrayKind::LinearGradient => &desc::LINEAR_GRADIENT,
-                VertexArrayKind::RadialGradient => &desc::RADIAL_GRADIENT,
-                VertexArrayKind::ConicGradient => &desc::CONIC_GRADIENT,
-                VertexArrayKind::Blur => &desc::BLUR,
-                VertexArrayKind::ClipRect => &desc::CLIP_RECT,
-                VertexArrayKind::ClipBoxShadow => &desc::CLIP_BOX_SHADOW,
-                VertexArrayKind::VectorStencil => &desc::VECTOR_STENCIL,
-                VertexArrayKind::VectorCover => &desc::VECTOR_COVER,
-                VertexArrayKind::Border => &desc::BORDER,
-                VertexArrayKind::Scale => &desc::SCALE,
-                VertexArrayKind::Resolve => &desc::RESOLVE,
-                VertexArrayKind::SvgFilter => &desc::SVG_FILTER,
-                VertexArrayKind::SvgFilterNode => &desc::SVG_FILTER_NODE,
-                VertexArrayKind::Composite => &desc::COMPOSITE,
-                VertexArrayKind::Clear => &desc::CLEAR,
-                VertexArrayKind::Copy => &desc::COPY,
-                VertexArrayKind::Mask => &desc::MASK,
-            };
-
             device.link_program(program, vertex_descriptor)?;
             device.bind_program(program);
             match self.kind {
@@ -316,6 +295,57 @@ impl LazilyCompiledShader {
         Ok(program)
     }
 
+    /// Whether the program is compiled and linked, so that binding it
+    /// doesn't stall.
+    fn is_linked(&self) -> bool {
+        self.program.as_ref().map_or(false, |program| program.is_initialized())
+    }
+
+    /// Whether the driver is done with the compile and link, so that
+    /// finishing it doesn't stall.
+    fn is_link_complete(&self, device: &Device) -> bool {
+        self.program.as_ref().map_or(true, |program| device.is_program_link_complete(program))
+    }
+
+    fn vertex_descriptor(&self) -> &'static VertexDescriptor {
+        let vertex_format = match self.kind {
+            ShaderKind::Primitive |
+            ShaderKind::Brush |
+            ShaderKind::Text => VertexArrayKind::Primitive,
+            ShaderKind::Cache(format) => format,
+            ShaderKind::VectorStencil => VertexArrayKind::VectorStencil,
+            ShaderKind::VectorCover => VertexArrayKind::VectorCover,
+            ShaderKind::ClipCache(format) => format,
+            ShaderKind::Resolve => VertexArrayKind::Resolve,
+            ShaderKind::Composite => VertexArrayKind::Composite,
+            ShaderKind::Clear => VertexArrayKind::Clear,
+            ShaderKind::Copy => VertexArrayKind::Copy,
+        };
+
+        match vertex_format {
+            VertexArrayKind::Primitive => &desc::PRIM_INSTANCES,
+            VertexArrayKind::LineDecoration => &desc::LINE,
+            VertexArrayKind::FastLinearGradient => &desc::FAST_LINEAR_GRADIENT,
+            VertexArrayKind::LinearGradient => &desc::LINEAR_GRADIENT,
+            VertexArrayKind::RadialGradient => &desc::RADIAL_GRADIENT,
+            VertexArrayKind::ConicGradient => &desc::CONIC_GRADIENT,
+            VertexArrayKind::Blur => &desc::BLUR,
+            VertexArrayKind::ClipRect => &desc::CLIP_RECT,
+            VertexArrayKind::ClipBoxShadow => &desc::CLIP_BOX_SHADOW,
+            VertexArrayKind::VectorStencil => &desc::VECTOR_STENCIL,
+            VertexArrayKind::VectorCover => &desc::VECTOR_COVER,
+            VertexArrayKind::Border => &desc::BORDER,
+            VertexArrayKind::Scale => &desc::SCALE,
+            VertexArrayKind::Resolve => &desc::RESOLVE,
+            VertexArrayKind::SvgFilter => &desc::SVG_FILTER,
+            VertexArrayKind::SvgFilterNode => &desc::SVG_FILTER_NODE,
+            VertexArrayKind::Composite => &desc::COMPOSITE,
+            VertexArrayKind::Clear => &desc::CLEAR,
+            VertexArrayKind::Copy => &desc::COPY,
+            VertexArrayKind::Mask => &desc::MASK,
+        }
+    }
+
     fn deinit(self, device: &mut Device) {
         if let Some(program) = self.program {
             device.delete_program(program);
@@ -468,6 +498,18 @@ impl BrushShader {
         }
     }
 
+    fn visit_mut(&mut self, f: &mut dyn FnMut(&mut LazilyCompiledShader)) {
+        f(&mut self.opaque);
+        f(&mut self.alpha);
+        if let Some(ref mut advanced_blend) = self.advanced_blend {
+            f(advanced_blend);
+        }
+        if let Some(ref mut dual_source) = self.dual_source {
+            f(dual_source);
+        }
+        f(&mut self.debug_overdraw);
+    }
+
     fn deinit(self, device: &mut Device) {
         self.opaque.deinit(device);
         self.alpha.deinit(device);
@@ -558,6 +600,12 @@ impl TextShader {
         }
     }
 
+    fn visit_mut(&mut self, f: &mut dyn FnMut(&mut LazilyCompiledShader)) {
+        f(&mut self.simple);
+        f(&mut self.glyph_transform);
+        f(&mut self.debug_overdraw);
+    }
+
     fn deinit(self, device: &mut Device) {
         self.simple.deinit(device);
         self.glyph_transform.deinit(device);
@@ -672,11 +720,19 @@ impl Shaders {
         shader_flags.set(ShaderFeatureFlags::DITHERING, options.enable_dithering);
         let shader_list = get_shader_features(shader_flags);
 
+        // With async compilation, compiles and links are only submitted to
+        // the driver here, and finished by `link_pending` once it's done.
+        let precache_flags = if options.async_shader_compile {
+            ShaderPrecacheFlags::ASYNC_COMPILE
+        } else {
+            options.precache_flags
+        };
+
         let brush_solid = BrushShader::new(
             "brush_solid",
             device,
             &[],
-            options.precache_flags,
+            precache_flags,
             &shader_list,
             false /* advanced blend */,
             false /* dual source */,
@@ -687,7 +743,7 @@ impl Shaders {
             "brush_blend",
             device,
             &[],
-            options.precache_flags,
+            precache_flags,
             &shader_list,
             false /* advanced blend */,
             false /* dual source */,
@@ -698,7 +754,7 @@ impl Shaders {
             "brush_mix_blend",
             device,
             &[],
-            options.precache_flags,
+            precache_flags,
             &shader_list,
             false /* advanced blend */,
             false /* dual source */,
@@ -713,7 +769,7 @@ impl Shaders {
             } else {
                &[]
             },
-            options.precache_flags,
+            precache_flags,
             &shader_list,
             false /* advanced blend */,
             false /* dual source */,
@@ -724,7 +780,7 @@ impl Shaders {
             "brush_opacity",
             device,
             &["ANTIALIASING"],
-            options.precache_flags,
+            precache_flags,
             &shader_list,
             false /* advanced blend */,
             false /* dual source */,
@@ -735,7 +791,7 @@ impl Shaders {
             "brush_opacity",
             device,
             &[],
-            options.precache_flags,
+            precache_flags,
             &shader_list,
             false /* advanced blend */,
             false /* dual source */,
@@ -747,7 +803,7 @@ impl Shaders {
             "cs_blur",
             &["ALPHA_TARGET"],
             device,
-            options.precache_flags,
+            precache_flags,
             &shader_list,
             profile,
         )?;
@@ -757,7 +813,7 @@ impl Shaders {
             "cs_blur",
             &["COLOR_TARGET"],
             device,
-            options.precache_flags,
+            precache_flags,
             &shader_list,
             profile,
         )?;
@@ -767,7 +823,7 @@ impl Shaders {
             "cs_svg_filter",
             &[],
             device,
-            options.precache_flags,
+            precache_flags,
             &shader_list,
             profile,
         )?;
@@ -777,7 +833,7 @@ impl Shaders {
             "cs_svg_filter_node",
             &[],
             device,
-            options.precache_flags,
+            precache_flags,
             &shader_list,
             profile,
         )?;
@@ -787,7 +843,7 @@ impl Shaders {
             "ps_quad_mask",
             &[],
             device,
-            options.precache_flags,
+            precache_flags,
             &shader_list,
             profile,
         )?;
@@ -797,7 +853,7 @@ impl Shaders {
             "ps_quad_mask",
             &[FAST_PATH_FEATURE],
             device,
-            options.precache_flags,
+            precache_flags,
             &shader_list,
             profile,
         )?;
@@ -807,7 +863,7 @@ impl Shaders {
             "cs_clip_rectangle",
             &[],
             device,
-            options.precache_flags,
+            precache_flags,
             &shader_list,
             profile,
         )?;
@@ -817,7 +873,7 @@ impl Shaders {
             "cs_clip_rectangle",
             &[FAST_PATH_FEATURE],
             device,
-            options.precache_flags,
+            precache_flags,
             &shader_list,
             profile,
         )?;
@@ -827,7 +883,7 @@ impl Shaders {
             "cs_clip_box_shadow",
             &["TEXTURE_2D"],
             device,
-            options.precache_flags,
+            precache_flags,
             &shader_list,
             profile,
         )?;
@@ -855,7 +911,7 @@ impl Shaders {
                     "cs_scale",
                     &features,
                     device,
-                    options.precache_flags,
+                    precache_flags,
                     &shader_list,
                     profile,
                  )?;
@@ -874,7 +930,7 @@ impl Shaders {
         let ps_text_run = TextShader::new("ps_text_run",
             device,
             &[],
-            options.precache_flags,
+            precache_flags,
             &shader_list,
             profile,
         )?;
@@ -884,7 +940,7 @@ impl Shaders {
             Some(TextShader::new("ps_text_run",
                 device,
                 &dual_source_features,
-                options.precache_flags,
+                precache_flags,
                 &shader_list,
                 profile,
             )?)
@@ -897,7 +953,7 @@ impl Shaders {
             "ps_quad_textured",
             &[],
             device,
-            options.precache_flags,
+            precache_flags,
             &shader_list,
             profile,
         )?;
@@ -907,7 +963,7 @@ impl Shaders {
             "ps_quad_radial_gradient",
             &[],
             device,
-            options.precache_flags,
+            precache_flags,
             &shader_list,
             profile,
         )?;
@@ -917,7 +973,7 @@ impl Shaders {
             "ps_quad_conic_gradient",
             &[],
             device,
-            options.precache_flags,
+            precache_flags,
             &shader_list,
             profile,
         )?;
@@ -927,7 +983,7 @@ impl Shaders {
             "ps_split_composite",
             &[],
             device,
-            options.precache_flags,
+            precache_flags,
             &shader_list,
             profile,
         )?;
@@ -937,7 +993,7 @@ impl Shaders {
             "ps_clear",
             &[],
             device,
-            options.precache_flags,
+            precache_flags,
             &shader_list,
             profile,
         )?;
@@ -947,7 +1003,7 @@ impl Shaders {
             "ps_copy",
             &[],
             device,
-            options.precache_flags,
+            precache_flags,
             &shader_list,
             profile,
         )?;
@@ -982,7 +1038,7 @@ impl Shaders {
                 "brush_image",
                 device,
                 &image_features,
-                options.precache_flags,
+                precache_flags,
                 &shader_list,
                 use_advanced_blend_equation,
                 use_dual_source_blending,
@@ -996,7 +1052,7 @@ impl Shaders {
                 "brush_image",
                 device,
                 &image_features,
-                options.precache_flags,
+                precache_flags,
                 &shader_list,
                 use_advanced_blend_equation,
                 use_dual_source_blending,
@@ -1042,7 +1098,7 @@ impl Shaders {
                         "brush_yuv_image",
                         device,
                         &yuv_features,
-                        options.precache_flags,
+                        precache_flags,
                         &shader_list,
                         false /* advanced blend */,
                         false /* dual source */,
@@ -1062,7 +1118,7 @@ impl Shaders {
             "cs_line_decoration",
             &[],
             device,
-            options.precache_flags,
+            precache_flags,
             &shader_list,
             profile,
         )?;
@@ -1072,7 +1128,7 @@ impl Shaders {
             "cs_fast_linear_gradient",
             &[],
             device,
-            options.precache_flags,
+            precache_flags,
             &shader_list,
             profile,
         )?;
@@ -1082,7 +1138,7 @@ impl Shaders {
             "cs_linear_gradient",
             &[],
             device,
-            options.precache_flags,
+            precache_flags,
             &shader_list,
             profile,
         )?;
@@ -1092,7 +1148,7 @@ impl Shaders {
             "cs_radial_gradient",
             &[],
             device,
-            options.precache_flags,
+            precache_flags,
             &shader_list,
             profile,
         )?;
@@ -1102,7 +1158,7 @@ impl Shaders {
             "cs_conic_gradient",
             &[],
             device,
-            options.precache_flags,
+            precache_flags,
             &shader_list,
             profile,
         )?;
@@ -1112,7 +1168,7 @@ impl Shaders {
             "cs_border_segment",
              &[],
              device,
-             options.precache_flags,
+             precache_flags,
             &shader_list,
             profile,
         )?;
@@ -1122,12 +1178,12 @@ impl Shaders {
             "cs_border_solid",
             &[],
             device,
-            options.precache_flags,
+            precache_flags,
             &shader_list,
             profile,
         )?;
 
-        let composite = CompositorShaders::new(device, options.precache_flags, gl_type)?;
+        let composite = CompositorShaders::new(device, precache_flags, gl_type)?;
 
         Ok(Shaders {
             cs_blur_a8,
@@ -1237,7 +1293,8 @@ impl Shaders {
                     BrushBatchKind::Solid => {
                         &mut self.brush_solid
                     }
-                    BrushBatchKind::Image(image_buffer_kind) => {
+                    BrushBatchKind::Image(image_buffer_kind) |
+                    BrushBatchKind::WrappedImage(image_buffer_kind, _) => {
                         if features.contains(BatchFeatures::ANTIALIASING) ||
                             features.contains(BatchFeatures::REPETITION) {
 
@@ -1304,6 +1361,81 @@ impl Shaders {
         }
     }
 
+    fn visit_mut(&mut self, f: &mut dyn FnMut(&mut LazilyCompiledShader)) {
+        for shader in self.cs_scale.iter_mut().flatten() {
+            f(shader);
+        }
+        f(&mut self.cs_blur_a8);
+        f(&mut self.cs_blur_rgba8);
+        f(&mut self.cs_svg_filter);
+        f(&mut self.cs_svg_filter_node);
+        self.brush_solid.visit_mut(f);
+        self.brush_blend.visit_mut(f);
+        self.brush_mix_blend.visit_mut(f);
+        self.brush_linear_gradient.visit_mut(f);
+        self.brush_opacity.visit_mut(f);
+        self.brush_opacity_aa.visit_mut(f);
+        f(&mut self.cs_clip_rectangle_slow);
+        f(&mut self.cs_clip_rectangle_fast);
+        f(&mut self.cs_clip_box_shadow);
+        self.ps_text_run.visit_mut(f);
+        if let Some(ref mut shader) = self.ps_text_run_dual_source {
+            shader.visit_mut(f);
+        }
+        for shader in self.brush_image.iter_mut().flatten() {
+            shader.visit_mut(f);
+        }
+        for shader in self.brush_fast_image.iter_mut().flatten() {
+            shader.visit_mut(f);
+        }
+        for shader in self.brush_yuv_image.iter_mut().flatten() {
+            shader.visit_mut(f);
+        }
+        f(&mut self.cs_border_solid);
+        f(&mut self.cs_fast_linear_gradient);
+        f(&mut self.cs_linear_gradient);
+        f(&mut self.cs_radial_gradient);
+        f(&mut self.cs_conic_gradient);
+        f(&mut self.cs_line_decoration);
+        f(&mut self.cs_border_segment);
+        f(&mut self.ps_split_composite);
+        f(&mut self.ps_quad_textured);
+        f(&mut self.ps_quad_radial_gradient);
+        f(&mut self.ps_quad_conic_gradient);
+        f(&mut self.ps_mask);
+        f(&mut self.ps_mask_fast);
+        f(&mut self.ps_clear);
+        f(&mut self.ps_copy);
+        self.composite.visit_mut(f);
+    }
+
+    /// Finishes linking the asynchronously compiled shaders that the driver
+    /// is done with, until `deadline` (in `precise_time_ns`) is reached.
+    /// Returns true once every shader is linked.
+    pub fn link_pending(
+        &mut self,
+        device: &mut Device,
+        deadline: u64,
+        profile: &mut TransactionProfile,
+    ) -> Result<bool, ShaderError> {
+        let mut all_linked = true;
+        let mut result = Ok(());
+        self.visit_mut(&mut |shader| {
+            if shader.is_linked() || result.is_err() {
+                return;
+            }
+            if !shader.is_link_complete(device) || precise_time_ns() >= deadline {
+                all_linked = false;
+                return;
+            }
+            if let Err(e) = shader.get_internal(device, ShaderPrecacheFlags::FULL_COMPILE, profile) {
+                result = Err(e);
+            }
+        });
+
+        result.map(|_| all_linked)
+    }
+
     pub fn deinit(mut self, device: &mut Device) {
         for shader in self.cs_scale {
             if let Some(shader) = shader {
@@ -1488,7 +1620,7 @@ impl CompositorShaders {
         features: CompositeFeatures,
     ) -> &mut LazilyCompiledShader {
         match format {
-            CompositeSurfaceFormat::Rgba => {
+            CompositeSurfaceFormat::Rgba | CompositeSurfaceFormat::RgbaPremultiplied => {
                 if features.contains(CompositeFeatures::NO_UV_CLAMP)
                     && features.contains(CompositeFeatures::NO_COLOR_MODULATION)
                 {
@@ -1516,6 +1648,15 @@ impl CompositorShaders {
         buffer_kind as usize
     }
 
+    fn visit_mut(&mut self, f: &mut dyn FnMut(&mut LazilyCompiledShader)) {
+        for shader in self.rgba.iter_mut().chain(&mut self.yuv).flatten() {
+            f(shader);
+        }
+        for shader in self.rgba_fast_path.iter_mut().flatten() {
+            f(shader);
+        }
+    }
+
     pub fn deinit(&mut self, device: &mut Device) {
         for shader in self.rgba.drain(..) {
             if let Some(shader) = shader {
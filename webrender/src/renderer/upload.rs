@@ -100,7 +100,7 @@ pub fn upload_to_texture_cache(
                         .as_mut()
                         .expect("Found external image, but no handler set!");
                     // The filter is only relevant for NativeTexture external images.
-                    match handler.lock(id, channel_index).source {
+                    match renderer.locked_external_images.lock(&mut **handler, id, channel_index).source {
                         ExternalImageSource::RawData(data) => {
                             &data[offset as usize ..]
                         }
@@ -195,7 +195,7 @@ pub fn upload_to_texture_cache(
                 let handler = renderer.external_image_handler
                     .as_mut()
                     .expect("Found external image, but no handler set!");
-                handler.unlock(id, channel_index);
+                renderer.locked_external_images.unlock(&mut **handler, id, channel_index);
             }
         }
     }
@@ -296,6 +296,8 @@ pub fn upload_to_texture_cache(
         renderer.profile.add(profiler::TEXTURE_UPLOADS, num_updates);
     }
 
+    renderer.texture_upload_bytes += stats.bytes_uploaded;
+
     if stats.bytes_uploaded > 0 {
         renderer.profile.add(
             profiler::TEXTURE_UPLOADS_MEM,
@@ -800,6 +802,10 @@ impl UploadTexturePool {
             report.upload_staging_memory += unsafe { (size_op_funs.size_of_op)(buf.as_ptr() as *const _) };
         }
 
+        self.report_gpu_memory_to(report);
+    }
+
+    pub fn report_gpu_memory_to(&self, report: &mut MemoryReport) {
         for format in &self.textures {
             for texture in format {
                 report.upload_staging_textures += texture.0.size_in_bytes();
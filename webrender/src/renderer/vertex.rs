@@ -400,6 +400,11 @@ pub mod desc {
                 count: 1,
                 kind: VertexAttributeKind::F32,
             },
+            VertexAttribute {
+                name: "aClipAaQuality",
+                count: 1,
+                kind: VertexAttributeKind::F32,
+            },
             VertexAttribute {
                 name: "aClipRect_TL",
                 count: 4,
@@ -776,6 +781,21 @@ pub mod desc {
                 count: 2,
                 kind: VertexAttributeKind::F32,
             },
+            VertexAttribute {
+                name: "aZ",
+                count: 1,
+                kind: VertexAttributeKind::F32,
+            },
+            VertexAttribute {
+                name: "aVignette",
+                count: 4,
+                kind: VertexAttributeKind::F32,
+            },
+            VertexAttribute {
+                name: "aVignetteColor",
+                count: 4,
+                kind: VertexAttributeKind::F32,
+            },
         ],
     };
 
@@ -1038,6 +1058,16 @@ impl VertexDataTextures {
         );
     }
 
+    /// Binds the render task data of the frame these textures were last
+    /// updated with.
+    pub fn bind_render_tasks(&self, device: &mut Device) {
+        device.bind_texture(
+            super::TextureSampler::RenderTasks,
+            &self.render_task_texture.texture(),
+            Swizzle::default(),
+        );
+    }
+
     pub fn size_in_bytes(&self) -> usize {
         self.prim_header_f_texture.size_in_bytes()
             + self.prim_header_i_texture.size_in_bytes()
@@ -18,16 +18,19 @@ use crate::capture::ExternalCaptureImage;
 use crate::capture::PlainExternalImage;
 #[cfg(any(feature = "replay", feature = "png", feature="capture"))]
 use crate::capture::CaptureConfig;
-use crate::composite::{NativeSurfaceId, NativeSurfaceOperation, NativeTileId, NativeSurfaceOperationDetails};
+use crate::composite::{CompositeSurfaceFormat, NativeSurfaceId, NativeSurfaceOperation, NativeTileId, NativeSurfaceOperationDetails};
 use crate::device::TextureFilter;
 use crate::glyph_cache::{GlyphCache, CachedGlyphInfo};
-use crate::glyph_cache::GlyphCacheEntry;
+use crate::glyph_cache::{GlyphCacheEntry, GlyphCacheExport, PortableGlyphKey, RetainedGlyph};
+use crate::glyph_cache::{font_instance_fingerprint, font_template_fingerprint};
+#[cfg(feature = "capture")]
+use crate::glyph_cache::GlyphAtlasEntry;
 use glyph_rasterizer::{GLYPH_FLASHING, FontInstance, GlyphFormat, GlyphKey, GlyphRasterizer, GlyphRasterJob};
 use glyph_rasterizer::{SharedFontResources, BaseFontInstance};
 use crate::gpu_cache::{GpuCache, GpuCacheAddress, GpuCacheHandle};
 use crate::gpu_types::UvRectKind;
 use crate::internal_types::{
-    CacheTextureId, FastHashMap, FastHashSet, TextureSource, ResourceUpdateList,
+    CacheTextureId, FastHashMap, FastHashSet, ResourceHashMap, TextureSource, ResourceUpdateList,
     FrameId, FrameStamp,
 };
 use crate::profiler::{self, TransactionProfile, bytes_to_mb};
@@ -50,7 +53,7 @@ use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::u32;
-use crate::texture_cache::{TextureCache, TextureCacheHandle, Eviction, TargetShader};
+use crate::texture_cache::{TextureCache, TextureCacheHandle, Eviction, EvictionNotice, TargetShader};
 use crate::picture_textures::PictureTextures;
 use peek_poke::PeekPoke;
 
@@ -195,7 +198,7 @@ struct ImageResource {
 
 #[derive(Default)]
 struct ImageTemplates {
-    images: FastHashMap<ImageKey, ImageResource>,
+    images: ResourceHashMap<ImageKey, ImageResource>,
 }
 
 impl ImageTemplates {
@@ -241,7 +244,7 @@ impl Drop for CachedImageInfo {
 #[cfg_attr(feature = "capture", derive(Serialize))]
 #[cfg_attr(feature = "replay", derive(Deserialize))]
 pub struct ResourceClassCache<K: Hash + Eq, V, U: Default> {
-    resources: FastHashMap<K, V>,
+    resources: ResourceHashMap<K, V>,
     pub user_data: U,
 }
 
@@ -252,7 +255,7 @@ where
 {
     pub fn new() -> Self {
         ResourceClassCache {
-            resources: FastHashMap::default(),
+            resources: ResourceHashMap::default(),
             user_data: Default::default(),
         }
     }
@@ -406,12 +409,13 @@ struct Resources {
 // We only use this to report glyph dimensions to the user of the API, so using
 // the font instance key should be enough. If we start using it to cache dimensions
 // for internal font instances we should change the hash key accordingly.
-pub type GlyphDimensionsCache = FastHashMap<(FontInstanceKey, GlyphIndex), Option<GlyphDimensions>>;
+pub type GlyphDimensionsCache = ResourceHashMap<(FontInstanceKey, GlyphIndex), Option<GlyphDimensions>>;
 
 /// Internal information about allocated render targets in the pool
 struct RenderTarget {
     size: DeviceIntSize,
     format: ImageFormat,
+    is_srgb: bool,
     texture_id: CacheTextureId,
     /// If true, this is currently leant out, and not available to other passes
     is_active: bool,
@@ -463,7 +467,12 @@ pub struct ResourceCache {
     /// both blobs and regular images.
     pending_image_requests: FastHashSet<ImageRequest>,
 
-    rasterized_blob_images: FastHashMap<BlobImageKey, RasterizedBlob>,
+    /// Regions of untiled images updated with `update_image_regions`, along
+    /// with the buffer holding their pixels, to upload next time the image
+    /// is requested.
+    pending_image_regions: FastHashMap<ImageKey, Vec<(Arc<Vec<u8>>, DeviceIntRect, usize)>>,
+
+    rasterized_blob_images: ResourceHashMap<BlobImageKey, RasterizedBlob>,
 
     /// A log of the last three frames worth of deleted image keys kept
     /// for debugging purposes.
@@ -482,6 +491,16 @@ pub struct ResourceCache {
 
     /// A pool of render targets for use by the render task graph
     render_target_pool: Vec<RenderTarget>,
+
+    /// Whether to keep a CPU copy of rasterized glyph bitmaps so that they
+    /// can be exported with `export_glyph_cache`.
+    retain_glyph_bitmaps: bool,
+    /// Glyph bitmaps exported by another renderer. These are uploaded in
+    /// place of rasterizing a glyph when its font data matches.
+    imported_glyphs: GlyphCacheExport,
+    /// Fingerprints of the font templates, only tracked while glyph bitmaps
+    /// are retained or imported.
+    font_fingerprints: FastHashMap<FontKey, u64>,
 }
 
 impl ResourceCache {
@@ -502,14 +521,15 @@ impl ResourceCache {
                 image_templates: ImageTemplates::default(),
                 weak_fonts: WeakTable::new(),
             },
-            cached_glyph_dimensions: FastHashMap::default(),
+            cached_glyph_dimensions: ResourceHashMap::default(),
             texture_cache,
             picture_textures,
             state: State::Idle,
             current_frame_id: FrameId::INVALID,
             pending_image_requests: FastHashSet::default(),
+            pending_image_regions: FastHashMap::default(),
             glyph_rasterizer,
-            rasterized_blob_images: FastHashMap::default(),
+            rasterized_blob_images: ResourceHashMap::default(),
             // We want to keep three frames worth of delete blob keys
             deleted_blob_keys: vec![Vec::new(), Vec::new(), Vec::new()].into(),
             blob_image_handler,
@@ -519,6 +539,9 @@ impl ResourceCache {
             image_templates_memory: 0,
             font_templates_memory: 0,
             render_target_pool: Vec::new(),
+            retain_glyph_bitmaps: false,
+            imported_glyphs: GlyphCacheExport::default(),
+            font_fingerprints: FastHashMap::default(),
         }
     }
 
@@ -564,6 +587,18 @@ impl ResourceCache {
         self.glyph_rasterizer.enable_multithreading(enable);
     }
 
+    /// Configure glyph bitmap retention and provide glyphs exported by another
+    /// renderer. Must be called before any fonts are added.
+    pub fn set_glyph_persistence(&mut self, retain: bool, import: Option<GlyphCacheExport>) {
+        self.retain_glyph_bitmaps = retain;
+        self.imported_glyphs = import.unwrap_or_default();
+    }
+
+    /// Collect the retained bitmaps of all glyphs currently in the cache.
+    pub fn export_glyph_cache(&self) -> GlyphCacheExport {
+        self.cached_glyphs.export(&self.font_fingerprints)
+    }
+
     fn should_tile(limit: i32, descriptor: &ImageDescriptor, data: &CachedImageData) -> bool {
         let size_check = descriptor.size.width > limit || descriptor.size.height > limit;
         match *data {
@@ -643,6 +678,9 @@ impl ResourceCache {
                 ResourceUpdate::UpdateImage(img) => {
                     self.update_image_template(img.key, img.descriptor, img.data.into(), &img.dirty_rect);
                 }
+                ResourceUpdate::UpdateImageRegions(img) => {
+                    self.update_image_regions(img.key, img.source, img.regions);
+                }
                 ResourceUpdate::AddBlobImage(img) => {
                     self.add_image_template(
                         img.key.as_image(),
@@ -765,11 +803,15 @@ impl ResourceCache {
             self.resources.weak_fonts.insert(Arc::downgrade(data));
             self.font_templates_memory += data.len();
         }
+        if self.retain_glyph_bitmaps || !self.imported_glyphs.is_empty() {
+            self.font_fingerprints.insert(font_key, font_template_fingerprint(&template));
+        }
         self.glyph_rasterizer.add_font(font_key, template.clone());
         self.resources.fonts.templates.add_font(font_key, template);
     }
 
     pub fn delete_font_template(&mut self, font_key: FontKey) {
+        self.font_fingerprints.remove(&font_key);
         self.glyph_rasterizer.delete_font(font_key);
         if let Some(FontTemplate::Raw(data, _)) = self.resources.fonts.templates.delete_font(&font_key) {
             self.font_templates_memory -= data.len();
@@ -842,6 +884,13 @@ impl ResourceCache {
         match self.cached_images.try_get_mut(&image_key) {
             Some(&mut ImageResult::UntiledAuto(ref mut entry)) => {
                 entry.dirty_rect = entry.dirty_rect.union(dirty_rect);
+                // Regions waiting to be uploaded on their own are now covered
+                // by the dirty rect instead.
+                if let Some(regions) = self.pending_image_regions.remove(&image_key) {
+                    for (_, rect, _) in regions {
+                        entry.dirty_rect = entry.dirty_rect.union(&DirtyRect::Partial(rect));
+                    }
+                }
             }
             Some(&mut ImageResult::Multi(ref mut entries)) => {
                 for (key, entry) in entries.iter_mut() {
@@ -886,9 +935,96 @@ impl ResourceCache {
         };
     }
 
+    /// Copy the regions of `source` into a raw image template. If the image
+    /// is otherwise up to date in the texture cache, each region is uploaded
+    /// on its own straight from `source`, so that the template isn't kept
+    /// alive by pending uploads and can be written to in place the next
+    /// time. Regions that don't fit in the image or in `source` are ignored.
+    pub fn update_image_regions(
+        &mut self,
+        image_key: ImageKey,
+        source: Arc<Vec<u8>>,
+        regions: Vec<(DeviceIntRect, usize)>,
+    ) {
+        let image = match self.resources.image_templates.get_mut(image_key) {
+            Some(res) => res,
+            None => {
+                warn!("Attempt to update regions of non-existent image {:?}", image_key);
+                return;
+            }
+        };
+
+        let descriptor = image.descriptor;
+        let bpp = descriptor.format.bytes_per_pixel() as usize;
+        let stride = descriptor.compute_stride() as usize;
+        let image_rect = DeviceIntRect::from_size(descriptor.size);
+
+        let regions: Vec<_> = regions.into_iter().filter(|&(rect, offset)| {
+            let len = rect.width() as usize * bpp * rect.height() as usize;
+            let valid = !rect.is_empty() &&
+                image_rect.contains_box(&rect) &&
+                offset.checked_add(len).map_or(false, |end| end <= source.len());
+            if !valid {
+                warn!("Ignoring image region {:?} at offset {} of {:?}", rect, offset, image_key);
+            }
+            valid
+        }).collect();
+        if regions.is_empty() {
+            return;
+        }
+
+        let data = match image.data {
+            // This only copies the template if something else still holds it,
+            // e.g. the embedder or an upload of the whole image.
+            CachedImageData::Raw(ref mut data) => Arc::make_mut(data),
+            _ => {
+                warn!("Image regions can only be updated for raw images {:?}", image_key);
+                return;
+            }
+        };
+
+        for &(rect, offset) in &regions {
+            let row_bytes = rect.width() as usize * bpp;
+            for y in 0 .. rect.height() as usize {
+                let src = offset + y * row_bytes;
+                let dst = descriptor.offset as usize +
+                    (rect.min.y as usize + y) * stride +
+                    rect.min.x as usize * bpp;
+                data[dst .. dst + row_bytes].copy_from_slice(&source[src .. src + row_bytes]);
+            }
+        }
+
+        match self.cached_images.try_get_mut(&image_key) {
+            Some(&mut ImageResult::UntiledAuto(ref entry)) if entry.dirty_rect.is_empty() => {
+                image.generation = ImageGeneration(image.generation.0 + 1);
+                self.pending_image_regions
+                    .entry(image_key)
+                    .or_insert_with(Vec::new)
+                    .extend(regions.into_iter().map(|(rect, offset)| {
+                        (Arc::clone(&source), rect, offset)
+                    }));
+            }
+            _ => {
+                // The whole template is uploaded again anyway, or the image is
+                // tiled, in which case the regions are marked dirty in the
+                // tiles they touch.
+                let data = image.data.clone();
+                for (rect, _) in regions {
+                    self.update_image_template(
+                        image_key,
+                        descriptor,
+                        data.clone(),
+                        &DirtyRect::Partial(rect),
+                    );
+                }
+            }
+        }
+    }
+
     pub fn delete_image_template(&mut self, image_key: ImageKey) {
         // Remove the template.
         let value = self.resources.image_templates.remove(image_key);
+        self.pending_image_regions.remove(&image_key);
 
         // Release the corresponding texture cache entry, if any.
         if let Some(mut cached) = self.cached_images.remove(&image_key) {
@@ -979,7 +1115,12 @@ impl ResourceCache {
                         _ => None
                     };
 
-                    if let Some(untiled_entry) = untiled_entry {
+                    if let Some(mut untiled_entry) = untiled_entry {
+                        // Region updates are only uploaded on their own for
+                        // untiled entries.
+                        if self.pending_image_regions.remove(&request.key).is_some() {
+                            untiled_entry.dirty_rect = DirtyRect::All;
+                        }
                         let mut entries = ResourceClassCache::new();
                         let untiled_key = CachedImageKey {
                             rendering: ImageRendering::Auto,
@@ -1021,7 +1162,8 @@ impl ResourceCache {
 
         let needs_upload = self.texture_cache.request(&entry.texture_cache_handle, gpu_cache);
 
-        if !needs_upload && entry.dirty_rect.is_empty() {
+        if !needs_upload && entry.dirty_rect.is_empty() &&
+            !self.pending_image_regions.contains_key(&request.key) {
             return size;
         }
 
@@ -1098,6 +1240,15 @@ impl ResourceCache {
         debug_assert_eq!(self.state, State::AddResources);
 
         self.glyph_rasterizer.prepare_font(&mut font);
+        let imported = match self.font_fingerprints.get(&font.font_key) {
+            Some(&font_fingerprint) if !self.imported_glyphs.is_empty() => {
+                Some((font_fingerprint, font_instance_fingerprint(&font)))
+            }
+            _ => None,
+        };
+        let imported_glyphs = &self.imported_glyphs;
+        let retain_glyph_bitmaps = self.retain_glyph_bitmaps;
+        let can_use_r8_format = self.glyph_rasterizer.can_use_r8_format();
         let glyph_key_cache = self.cached_glyphs.insert_glyph_key_cache_for_font(&font);
         let texture_cache = &mut self.texture_cache;
         self.glyph_rasterizer.request_glyphs(
@@ -1120,6 +1271,27 @@ impl ResourceCache {
                     }
                 };
 
+                // Upload a matching imported bitmap rather than rasterizing the glyph again.
+                if let Some((font_fingerprint, instance_fingerprint)) = imported {
+                    let portable_key = PortableGlyphKey::new(font_fingerprint, instance_fingerprint, *key);
+                    if let Some(glyph) = imported_glyphs.get(&portable_key) {
+                        if glyph.image_format == glyph.format.image_format(can_use_r8_format) {
+                            let texture_cache_handle = upload_glyph(
+                                glyph,
+                                texture_cache,
+                                gpu_cache,
+                                glyph_key_cache.eviction_notice(),
+                            );
+                            glyph_key_cache.add_glyph(*key, GlyphCacheEntry::Cached(CachedGlyphInfo {
+                                texture_cache_handle,
+                                format: glyph.format,
+                                retained: if retain_glyph_bitmaps { Some(Arc::clone(glyph)) } else { None },
+                            }));
+                            return false;
+                        }
+                    }
+                }
+
                 glyph_key_cache.add_glyph(*key, GlyphCacheEntry::Pending);
 
                 true
@@ -1263,6 +1435,32 @@ impl ResourceCache {
         })
     }
 
+    /// Returns true if the image is uploaded into its own texture rather than
+    /// packed into a shared texture cache atlas, such that the texture sampler
+    /// can wrap texture coordinates without sampling neighbouring items.
+    pub fn is_image_standalone(&self, request: ImageRequest) -> bool {
+        let image_template = match self.resources.image_templates.get(request.key) {
+            Some(template) => template,
+            None => return false,
+        };
+
+        if image_template.tiling.is_some() {
+            return false;
+        }
+
+        match image_template.data {
+            // External textures may only cover part of their texture.
+            CachedImageData::External(ExternalImageData { image_type: ExternalImageType::TextureHandle(_), .. }) => false,
+            CachedImageData::External(..) | CachedImageData::Raw(..) | CachedImageData::Blob => {
+                let filter = match request.rendering {
+                    ImageRendering::Pixelated => TextureFilter::Nearest,
+                    ImageRendering::Auto | ImageRendering::CrispEdges => TextureFilter::Linear,
+                };
+                !self.texture_cache.is_allowed_in_shared_cache(filter, &image_template.descriptor)
+            }
+        }
+    }
+
     pub fn begin_frame(&mut self, stamp: FrameStamp, gpu_cache: &mut GpuCache, profile: &mut TransactionProfile) {
         profile_scope!("begin_frame");
         debug_assert_eq!(self.state, State::Idle);
@@ -1299,6 +1497,7 @@ impl ResourceCache {
 
         let cached_glyphs = &mut self.cached_glyphs;
         let texture_cache = &mut self.texture_cache;
+        let retain_glyph_bitmaps = self.retain_glyph_bitmaps;
 
         self.glyph_rasterizer.resolve_glyphs(
             |job, can_use_r8_format| {
@@ -1310,30 +1509,26 @@ impl ResourceCache {
                         GlyphCacheEntry::Blank
                     }
                     Ok(glyph) => {
-                        let mut texture_cache_handle = TextureCacheHandle::invalid();
-                        texture_cache.request(&texture_cache_handle, gpu_cache);
-                        texture_cache.update(
-                            &mut texture_cache_handle,
-                            ImageDescriptor {
-                                size: size2(glyph.width, glyph.height),
-                                stride: None,
-                                format: glyph.format.image_format(can_use_r8_format),
-                                flags: ImageDescriptorFlags::empty(),
-                                offset: 0,
-                            },
-                            TextureFilter::Linear,
-                            Some(CachedImageData::Raw(Arc::new(glyph.bytes))),
-                            [glyph.left, -glyph.top, glyph.scale, 0.0],
-                            DirtyRect::All,
+                        let glyph = RetainedGlyph {
+                            width: glyph.width,
+                            height: glyph.height,
+                            left: glyph.left,
+                            top: glyph.top,
+                            scale: glyph.scale,
+                            format: glyph.format,
+                            image_format: glyph.format.image_format(can_use_r8_format),
+                            bytes: Arc::new(glyph.bytes),
+                        };
+                        let texture_cache_handle = upload_glyph(
+                            &glyph,
+                            texture_cache,
                             gpu_cache,
-                            Some(glyph_key_cache.eviction_notice()),
-                            UvRectKind::Rect,
-                            Eviction::Auto,
-                            TargetShader::Text,
+                            glyph_key_cache.eviction_notice(),
                         );
                         GlyphCacheEntry::Cached(CachedGlyphInfo {
                             texture_cache_handle,
                             format: glyph.format,
+                            retained: if retain_glyph_bitmaps { Some(Arc::new(glyph)) } else { None },
                         })
                     }
                 };
@@ -1352,6 +1547,26 @@ impl ResourceCache {
             let image_template = self.resources.image_templates.get_mut(request.key).unwrap();
             debug_assert!(image_template.data.uses_texture_cache());
 
+            // Upload the regions updated since the last upload on their own,
+            // unless the whole image or a dirty rect has to be uploaded anyway.
+            if let Some(regions) = self.pending_image_regions.remove(&request.key) {
+                if let ImageResult::UntiledAuto(ref entry) = *self.cached_images.get(&request.key) {
+                    if entry.dirty_rect.is_empty() &&
+                        self.texture_cache.is_allocated(&entry.texture_cache_handle) {
+                        for (source, rect, offset) in regions {
+                            self.texture_cache.update_region(
+                                &entry.texture_cache_handle,
+                                image_template.descriptor.format,
+                                source,
+                                rect,
+                                offset,
+                            );
+                        }
+                        continue;
+                    }
+                }
+            }
+
             let mut updates: SmallVec<[(CachedImageData, Option<DeviceIntRect>); 1]> = SmallVec::new();
 
             match image_template.data {
@@ -1428,7 +1643,11 @@ impl ResourceCache {
                             TextureFilter::Linear,
                             &descriptor,
                         ) {
-                            TextureFilter::Trilinear
+                            if descriptor.anisotropic_filtering() {
+                                TextureFilter::Anisotropic
+                            } else {
+                                TextureFilter::Trilinear
+                            }
                         } else {
                             TextureFilter::Linear
                         }
@@ -1504,7 +1723,7 @@ impl ResourceCache {
 
     pub fn create_compositor_external_surface(
         &mut self,
-        is_opaque: bool,
+        format: CompositeSurfaceFormat,
     ) -> NativeSurfaceId {
         let id = NativeSurfaceId(NEXT_NATIVE_SURFACE_ID.fetch_add(1, Ordering::Relaxed) as u64);
 
@@ -1512,7 +1731,7 @@ impl ResourceCache {
             NativeSurfaceOperation {
                 details: NativeSurfaceOperationDetails::CreateExternalSurface {
                     id,
-                    is_opaque,
+                    format,
                 },
             }
         );
@@ -1608,6 +1827,7 @@ impl ResourceCache {
         );
 
         self.picture_textures.update_profile(profile);
+        self.cached_render_tasks.update_profile(profile);
     }
 
     pub fn set_debug_flags(&mut self, flags: DebugFlags) {
@@ -1621,6 +1841,7 @@ impl ResourceCache {
             for (_key, mut cached) in self.cached_images.resources.drain() {
                 cached.drop_from_cache(&mut self.texture_cache);
             }
+            self.pending_image_regions.clear();
         }
         if what.contains(ClearCache::GLYPHS) {
             self.cached_glyphs.clear();
@@ -1640,6 +1861,43 @@ impl ResourceCache {
         }
     }
 
+    /// Mark the contents of every cached image as dirty so that it is uploaded
+    /// again from its template on the next update. Blob tiles whose rasterized
+    /// data is no longer retained, along with glyphs and cached render tasks,
+    /// have no source to upload from and are dropped so they get re-requested.
+    pub fn reupload_texture_cache(&mut self) {
+        let texture_cache = &mut self.texture_cache;
+        let rasterized_blob_images = &self.rasterized_blob_images;
+        let image_templates = &self.resources.image_templates;
+        for (key, cached) in self.cached_images.iter_mut() {
+            match *cached {
+                ImageResult::UntiledAuto(ref mut entry) => {
+                    entry.dirty_rect = DirtyRect::All;
+                }
+                ImageResult::Multi(ref mut entries) => {
+                    let is_blob = image_templates.get(*key).map_or(false, |t| t.data.is_blob());
+                    let tiles = rasterized_blob_images.get(&BlobImageKey(*key));
+                    entries.retain(|cached_key, entry| {
+                        let missing = is_blob && match (tiles, cached_key.tile) {
+                            (Some(tiles), Some(tile)) => !tiles.contains_key(&tile),
+                            _ => true,
+                        };
+                        if missing {
+                            entry.mark_unused(texture_cache);
+                            return false;
+                        }
+                        entry.dirty_rect = DirtyRect::All;
+                        true
+                    });
+                }
+                ImageResult::Err(_) => {}
+            }
+        }
+
+        self.cached_glyphs.clear();
+        self.cached_render_tasks.clear();
+    }
+
     pub fn clear_namespace(&mut self, namespace: IdNamespace) {
         self.clear_images(|k| k.0 == namespace);
 
@@ -1675,6 +1933,12 @@ impl ResourceCache {
         }
     }
 
+    /// Returns the atlas location of every glyph currently in the glyph cache.
+    #[cfg(feature = "capture")]
+    pub fn glyph_atlas_entries(&self) -> Vec<GlyphAtlasEntry> {
+        self.cached_glyphs.atlas_entries(&self.texture_cache)
+    }
+
     /// Reports the CPU heap usage of this ResourceCache.
     ///
     /// NB: It would be much better to use the derive(MallocSizeOf) machinery
@@ -1756,10 +2020,12 @@ impl ResourceCache {
         &mut self,
         size: DeviceIntSize,
         format: ImageFormat,
+        is_srgb: bool,
     ) -> CacheTextureId {
         for target in &mut self.render_target_pool {
             if target.size == size &&
                target.format == format &&
+               target.is_srgb == is_srgb &&
                !target.is_active {
                 // Found a target that's not currently in use which matches. Update
                 // the last_frame_used for GC purposes.
@@ -1774,11 +2040,13 @@ impl ResourceCache {
         let texture_id = self.texture_cache.alloc_render_target(
             size,
             format,
+            is_srgb,
         );
 
         self.render_target_pool.push(RenderTarget {
             size,
             format,
+            is_srgb,
             texture_id,
             is_active: true,
             last_frame_used: self.current_frame_id,
@@ -1874,6 +2142,37 @@ impl ResourceCache {
     }
 }
 
+/// Upload a rasterized glyph bitmap to the texture cache.
+fn upload_glyph(
+    glyph: &RetainedGlyph,
+    texture_cache: &mut TextureCache,
+    gpu_cache: &mut GpuCache,
+    eviction_notice: &EvictionNotice,
+) -> TextureCacheHandle {
+    let mut texture_cache_handle = TextureCacheHandle::invalid();
+    texture_cache.request(&texture_cache_handle, gpu_cache);
+    texture_cache.update(
+        &mut texture_cache_handle,
+        ImageDescriptor {
+            size: size2(glyph.width, glyph.height),
+            stride: None,
+            format: glyph.image_format,
+            flags: ImageDescriptorFlags::empty(),
+            offset: 0,
+        },
+        TextureFilter::Linear,
+        Some(CachedImageData::Raw(Arc::clone(&glyph.bytes))),
+        [glyph.left, -glyph.top, glyph.scale, 0.0],
+        DirtyRect::All,
+        gpu_cache,
+        Some(eviction_notice),
+        UvRectKind::Rect,
+        Eviction::Auto,
+        TargetShader::Text,
+    );
+    texture_cache_handle
+}
+
 impl Drop for ResourceCache {
     fn drop(&mut self) {
         self.clear_images(|_| true);
@@ -2299,3 +2598,65 @@ impl ResourceCache {
         }
     }
 }
+
+#[cfg(test)]
+fn rasterize_glyphs_for_testing(
+    resource_cache: &mut ResourceCache,
+    font: &FontInstance,
+    glyph_keys: &[GlyphKey],
+) -> f64 {
+    use api::DocumentId;
+
+    let mut gpu_cache = GpuCache::new_for_testing();
+    let mut profile = TransactionProfile::new();
+    let mut frame_stamp = FrameStamp::first(DocumentId::new(IdNamespace(1), 1));
+    frame_stamp.advance();
+
+    resource_cache.begin_frame(frame_stamp, &mut gpu_cache, &mut profile);
+    resource_cache.request_glyphs(font.clone(), glyph_keys, &mut gpu_cache);
+    resource_cache.block_until_all_resources_added(&mut gpu_cache, &mut profile);
+    resource_cache.end_frame(&mut profile);
+
+    profile.get(profiler::RASTERIZED_GLYPHS).unwrap_or(0.0)
+}
+
+#[test]
+fn test_glyph_cache_export_import() {
+    use glyph_rasterizer::SubpixelDirection;
+
+    let font_key = FontKey::new(IdNamespace(0), 1);
+    let font_data = Arc::new(include_bytes!("../../wrench/reftests/text/FreeSans.ttf").to_vec());
+    let font = FontInstance::from_base(Arc::new(BaseFontInstance::new(
+        FontInstanceKey::new(IdNamespace(0), 1),
+        font_key,
+        16.0,
+        None,
+        None,
+        Vec::new(),
+    )));
+    let glyph_keys: Vec<GlyphKey> = (36 .. 44)
+        .map(|index| GlyphKey::new(index, DevicePoint::zero(), SubpixelDirection::None))
+        .collect();
+
+    // Rasterize the glyphs in a renderer that retains their bitmaps.
+    let mut rc = ResourceCache::new_for_testing();
+    rc.set_glyph_persistence(true, None);
+    rc.add_font_template(font_key, FontTemplate::Raw(Arc::clone(&font_data), 0));
+    assert!(rasterize_glyphs_for_testing(&mut rc, &font, &glyph_keys) > 0.0);
+    let export = rc.export_glyph_cache();
+    assert_eq!(export.len(), glyph_keys.len());
+
+    // A fresh cache importing the glyphs shouldn't rasterize any of them,
+    // even though the font data is a separate copy.
+    let mut rc = ResourceCache::new_for_testing();
+    rc.set_glyph_persistence(false, Some(export.clone()));
+    rc.add_font_template(font_key, FontTemplate::Raw(Arc::new(font_data.to_vec()), 0));
+    assert_eq!(rasterize_glyphs_for_testing(&mut rc, &font, &glyph_keys), 0.0);
+
+    // If the font data behind the key changed, the imported glyphs are ignored.
+    let other_font_data = include_bytes!("../../wrench/reftests/text/VeraBd.ttf").to_vec();
+    let mut rc = ResourceCache::new_for_testing();
+    rc.set_glyph_persistence(false, Some(export));
+    rc.add_font_template(font_key, FontTemplate::Raw(Arc::new(other_font_data), 0));
+    assert!(rasterize_glyphs_for_testing(&mut rc, &font, &glyph_keys) > 0.0);
+}
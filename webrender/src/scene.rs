@@ -2,15 +2,15 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use api::{BuiltDisplayList, DisplayListWithCache, ColorF, DynamicProperties, Epoch, FontRenderMode};
-use api::{PipelineId, PropertyBinding, PropertyBindingId, PropertyValue, MixBlendMode, StackingContext};
+use api::{BuiltDisplayList, ClipAaQuality, DisplayListWithCache, ColorF, DynamicProperties, Epoch, FontRenderMode};
+use api::{PipelineId, PropertyBinding, PropertyBindingId, PropertyValue, MixBlendMode, SpatialId, StackingContext};
 use api::units::*;
 use api::channel::Sender;
 use malloc_size_of::{MallocSizeOf, MallocSizeOfOps};
 use crate::render_api::MemoryReport;
 use crate::composite::CompositorKind;
 use crate::clip::{ClipStore, ClipTree};
-use crate::spatial_tree::SpatialTree;
+use crate::spatial_tree::{SpatialNodeIndex, SpatialTree};
 use crate::frame_builder::FrameBuilderConfig;
 use crate::hit_test::{HitTester, HitTestingScene, HitTestingSceneStats};
 use crate::internal_types::FastHashMap;
@@ -285,6 +285,12 @@ pub struct BuiltScene {
     pub prim_instances: Vec<PrimitiveInstance>,
     pub surfaces: Vec<SurfaceInfo>,
     pub clip_tree: ClipTree,
+    /// The most clips in the clip-chain of any primitive, as reported by
+    /// `RenderApi::max_clip_chain_depth`.
+    pub max_clip_chain_depth: usize,
+    /// The spatial nodes of the reference frames in the display lists, for
+    /// `Transaction::update_spatial_nodes`.
+    pub reference_frames: FastHashMap<SpatialId, SpatialNodeIndex>,
 
     /// Deallocating memory outside of the thread that allocated it causes lock
     /// contention in jemalloc. To avoid this we send the built scene back to
@@ -309,6 +315,8 @@ impl BuiltScene {
             prim_instances: Vec::new(),
             surfaces: Vec::new(),
             clip_tree: ClipTree::new(),
+            max_clip_chain_depth: 0,
+            reference_frames: FastHashMap::default(),
             recycler_tx: None,
             config: FrameBuilderConfig {
                 default_font_render_mode: FontRenderMode::Mono,
@@ -325,11 +333,20 @@ impl BuiltScene {
                 tile_size_override: None,
                 max_surface_override: None,
                 max_depth_ids: 0,
+                max_depth_ids_override: None,
                 max_target_size: 0,
                 force_invalidation: false,
                 is_software: false,
                 low_quality_pinch_zoom: false,
                 max_shared_surface_size: 2048,
+                use_srgb_render_targets: false,
+                clip_aa_quality: ClipAaQuality::Standard,
+                culling_margin: 0.0,
+                max_blur_raster_size: None,
+                max_render_targets_per_frame: None,
+                render_scale: 1.0,
+                edge_aa_feather: 1.0,
+                show_clip_bounds: false,
             },
         }
     }
@@ -357,6 +374,7 @@ impl BuiltScene {
         HitTester::new(
             Arc::clone(&self.hit_testing_scene),
             spatial_tree,
+            self.config.effective_render_scale(),
         )
     }
 }
@@ -6,6 +6,7 @@ use api::{AsyncBlobImageRasterizer, BlobImageResult, DebugFlags, Parameter};
 use api::{DocumentId, PipelineId, ExternalEvent, BlobImageRequest};
 use api::{NotificationRequest, Checkpoint, IdNamespace, QualitySettings};
 use api::{PrimitiveKeyKind, GlyphDimensionRequest, GlyphIndexRequest};
+use api::{GradientTemplate, GradientTemplateKey};
 use api::channel::{unbounded_channel, single_msg_channel, Receiver, Sender};
 use api::units::*;
 use crate::render_api::{ApiMsg, FrameMsg, SceneMsg, ResourceUpdate, TransactionMsg, MemoryReport};
@@ -35,6 +36,9 @@ use crate::spatial_tree::{SceneSpatialTree, SpatialTreeUpdates};
 use crate::telemetry::Telemetry;
 use crate::SceneBuilderHooks;
 use std::iter;
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
 use time::precise_time_ns;
 use crate::util::drain_filter;
 use std::thread;
@@ -72,6 +76,23 @@ pub struct BuiltTransaction {
     pub invalidate_rendered_frame: bool,
     pub profile: TransactionProfile,
     pub frame_stats: FullFrameStats,
+    pub trace_id: Option<u64>,
+}
+
+impl BuiltTransaction {
+    /// Returns true if this transaction only updates resources, and can be
+    /// applied without touching the document.
+    pub fn is_resource_only(&self) -> bool {
+        (!self.resource_updates.is_empty() || !self.rasterized_blobs.is_empty()) &&
+            self.built_scene.is_none() &&
+            !self.render_frame &&
+            !self.invalidate_rendered_frame &&
+            self.frame_ops.is_empty() &&
+            self.removed_pipelines.is_empty() &&
+            self.notifications.is_empty() &&
+            self.interner_updates.is_none() &&
+            self.spatial_tree_updates.is_none()
+    }
 }
 
 #[cfg(feature = "replay")]
@@ -94,7 +115,10 @@ pub enum SceneBuilderRequest {
     GetGlyphDimensions(GlyphDimensionRequest),
     GetGlyphIndices(GlyphIndexRequest),
     ClearNamespace(IdNamespace),
+    AddGradientTemplate(GradientTemplateKey, GradientTemplate),
+    DeleteGradientTemplate(GradientTemplateKey),
     SimulateLongSceneBuild(u32),
+    SimulateLongLowPrioritySceneBuild(u32),
     ExternalEvent(ExternalEvent),
     WakeUp,
     StopRenderBackend,
@@ -147,6 +171,47 @@ pub enum SceneSwapResult {
     Aborted,
 }
 
+/// Tracks transactions that supersede pending scene builds.
+///
+/// Shared between the API, which assigns a sequence number to each transaction
+/// sent to the scene builder, and the scene builder thread, which uses it to
+/// abandon the build of scenes that a queued transaction is about to replace.
+#[derive(Clone, Default)]
+pub struct SceneBuildCancellation {
+    next_seq: Arc<AtomicU64>,
+    /// The sequence number of the most recent superseding transaction of each
+    /// document.
+    superseding_seqs: Arc<Mutex<FastHashMap<DocumentId, u64>>>,
+}
+
+impl SceneBuildCancellation {
+    /// Returns the sequence number of a transaction about to be sent to the
+    /// scene builder, and records it if it supersedes the pending ones.
+    pub fn next_seq(&self, document_id: DocumentId, supersedes_pending: bool) -> u64 {
+        let seq = self.next_seq.fetch_add(1, Ordering::Relaxed) + 1;
+        if supersedes_pending {
+            let mut superseding_seqs = self.superseding_seqs.lock().unwrap();
+            let latest = superseding_seqs.entry(document_id).or_insert(0);
+            *latest = (*latest).max(seq);
+        }
+        seq
+    }
+
+    /// Returns true if a transaction sent after the one with the given sequence
+    /// number supersedes it.
+    fn is_superseded(&self, document_id: DocumentId, seq: u64) -> bool {
+        self.superseding_seqs
+            .lock()
+            .unwrap()
+            .get(&document_id)
+            .map_or(false, |&latest| latest > seq)
+    }
+
+    fn remove_document(&self, document_id: DocumentId) {
+        self.superseding_seqs.lock().unwrap().remove(&document_id);
+    }
+}
+
 macro_rules! declare_interners {
     ( $( $name:ident : $ty:ident, )+ ) => {
         /// This struct contains all items that can be shared between
@@ -213,6 +278,13 @@ struct Document {
     stats: SceneStats,
     view: SceneView,
     spatial_tree: SceneSpatialTree,
+    /// The highest sequence number of the transactions processed so far.
+    processed_seq: u64,
+    /// Set when a scene build was cancelled, so that the next transaction
+    /// rebuilds the scene even if it doesn't change it.
+    pending_rebuild: bool,
+    /// Number of scene builds cancelled since the last built scene.
+    cancelled_builds: usize,
 }
 
 impl Document {
@@ -226,12 +298,17 @@ impl Document {
                 device_rect,
                 quality_settings: QualitySettings::default(),
             },
+            processed_seq: 0,
+            pending_rebuild: false,
+            cancelled_builds: 0,
         }
     }
 }
 
 pub struct SceneBuilderThread {
     documents: FastHashMap<DocumentId, Document>,
+    /// Gradients shared by the documents, see `RenderApi::create_gradient_template`.
+    gradient_templates: FastHashMap<GradientTemplateKey, GradientTemplate>,
     rx: Receiver<SceneBuilderRequest>,
     tx: Sender<ApiMsg>,
     config: FrameBuilderConfig,
@@ -245,6 +322,7 @@ pub struct SceneBuilderThread {
     debug_flags: DebugFlags,
     recycler: SceneRecycler,
     tile_pool: api::BlobTilePool,
+    scene_build_cancellation: SceneBuildCancellation,
 }
 
 pub struct SceneBuilderThreadChannels {
@@ -274,11 +352,13 @@ impl SceneBuilderThread {
         size_of_ops: Option<MallocSizeOfOps>,
         hooks: Option<Box<dyn SceneBuilderHooks + Send>>,
         channels: SceneBuilderThreadChannels,
+        scene_build_cancellation: SceneBuildCancellation,
     ) -> Self {
         let SceneBuilderThreadChannels { rx, tx } = channels;
 
         Self {
             documents: Default::default(),
+            gradient_templates: FastHashMap::default(),
             rx,
             tx,
             config,
@@ -293,6 +373,7 @@ impl SceneBuilderThread {
             recycler: SceneRecycler::new(),
             // TODO: tile size is hard-coded here.
             tile_pool: api::BlobTilePool::new(),
+            scene_build_cancellation,
         }
     }
 
@@ -344,12 +425,20 @@ impl SceneBuilderThread {
                 }
                 Ok(SceneBuilderRequest::DeleteDocument(document_id)) => {
                     self.documents.remove(&document_id);
+                    self.scene_build_cancellation.remove_document(document_id);
                     self.send(SceneBuilderResult::DeleteDocument(document_id));
                 }
                 Ok(SceneBuilderRequest::ClearNamespace(id)) => {
                     self.documents.retain(|doc_id, _doc| doc_id.namespace_id != id);
+                    self.gradient_templates.retain(|key, _| key.0 != id);
                     self.send(SceneBuilderResult::ClearNamespace(id));
                 }
+                Ok(SceneBuilderRequest::AddGradientTemplate(key, template)) => {
+                    self.gradient_templates.insert(key, template);
+                }
+                Ok(SceneBuilderRequest::DeleteGradientTemplate(key)) => {
+                    self.gradient_templates.remove(&key);
+                }
                 Ok(SceneBuilderRequest::ExternalEvent(evt)) => {
                     self.send(SceneBuilderResult::ExternalEvent(evt));
                 }
@@ -369,6 +458,11 @@ impl SceneBuilderThread {
                 Ok(SceneBuilderRequest::SimulateLongSceneBuild(time_ms)) => {
                     self.simulate_slow_ms = time_ms
                 }
+                Ok(SceneBuilderRequest::SimulateLongLowPrioritySceneBuild(_)) => {
+                    // Only reaches this thread when low priority transactions are
+                    // not supported, in which case they can't be slowed down
+                    // separately from the others.
+                }
                 Ok(SceneBuilderRequest::ReportMemory(mut report, tx)) => {
                     (*report) += self.report_memory();
                     tx.send(report).unwrap();
@@ -417,6 +511,8 @@ impl SceneBuilderThread {
 
     #[cfg(feature = "capture")]
     fn save_scene(&mut self, config: CaptureConfig) {
+        config.serialize_for_scene(&self.gradient_templates, "gradient-templates");
+
         for (id, doc) in &self.documents {
             let interners_name = format!("interners-{}-{}", id.namespace_id.0, id.id);
             config.serialize_for_scene(&doc.interners, interners_name);
@@ -451,6 +547,7 @@ impl SceneBuilderThread {
                     &mut item.spatial_tree,
                     &mut self.recycler,
                     &SceneStats::empty(),
+                    &self.gradient_templates,
                     self.debug_flags,
                 ));
 
@@ -471,6 +568,9 @@ impl SceneBuilderThread {
                     stats: SceneStats::empty(),
                     view: item.view.clone(),
                     spatial_tree: item.spatial_tree,
+                    processed_seq: 0,
+                    pending_rebuild: false,
+                    cancelled_builds: 0,
                 },
             );
 
@@ -490,6 +590,7 @@ impl SceneBuilderThread {
                 spatial_tree_updates,
                 profile: TransactionProfile::new(),
                 frame_stats: FullFrameStats::default(),
+                trace_id: None,
             })];
 
             self.forward_built_transactions(txns);
@@ -502,6 +603,7 @@ impl SceneBuilderThread {
     ) {
         if let Some(ref mut config) = self.capture_config {
             config.prepare_scene();
+            config.serialize_for_scene(&self.gradient_templates, "gradient-templates");
             for (id, doc) in &self.documents {
                 let interners_name = format!("interners-{}-{}", id.namespace_id.0, id.id);
                 config.serialize_for_scene(&doc.interners, interners_name);
@@ -602,6 +704,19 @@ impl SceneBuilderThread {
 
         self.removed_pipelines.clear();
 
+        // This is the only point at which a scene build is cancelled: the scene
+        // has been updated with this transaction, but the interners and the
+        // spatial tree haven't been touched yet. If a transaction superseding
+        // this one is still queued, leave the build to it.
+        doc.processed_seq = doc.processed_seq.max(txn.scene_build_seq);
+        rebuild_scene |= mem::replace(&mut doc.pending_rebuild, false);
+        if rebuild_scene &&
+            self.scene_build_cancellation.is_superseded(txn.document_id, doc.processed_seq) {
+            doc.pending_rebuild = true;
+            doc.cancelled_builds += 1;
+            rebuild_scene = false;
+        }
+
         let mut built_scene = None;
         let mut interner_updates = None;
         let mut spatial_tree_updates = None;
@@ -617,12 +732,15 @@ impl SceneBuilderThread {
                 &mut doc.spatial_tree,
                 &mut self.recycler,
                 &doc.stats,
+                &self.gradient_templates,
                 self.debug_flags,
             );
 
             // Update the allocation stats for next scene
             doc.stats = built.get_stats();
 
+            profile.set(profiler::CANCELLED_SCENE_BUILDS, mem::replace(&mut doc.cancelled_builds, 0));
+
             // Retrieve the list of updates from the clip interner.
             interner_updates = Some(
                 doc.interners.end_frame_and_get_pending_updates()
@@ -677,6 +795,7 @@ impl SceneBuilderThread {
             spatial_tree_updates,
             profile,
             frame_stats,
+            trace_id: txn.trace_id,
         })
     }
 
@@ -776,6 +895,9 @@ pub struct LowPrioritySceneBuilderThread {
     pub rx: Receiver<SceneBuilderRequest>,
     pub tx: Sender<SceneBuilderRequest>,
     pub tile_pool: api::BlobTilePool,
+    /// Time to pause for after processing each transaction, set with
+    /// `DebugCommand::SimulateLongLowPrioritySceneBuild`.
+    pub simulate_slow_ms: u32,
 }
 
 impl LowPrioritySceneBuilderThread {
@@ -793,6 +915,9 @@ impl LowPrioritySceneBuilderThread {
                     self.tx.send(SceneBuilderRequest::ShutDown(sync)).unwrap();
                     break;
                 }
+                Ok(SceneBuilderRequest::SimulateLongLowPrioritySceneBuild(time_ms)) => {
+                    self.simulate_slow_ms = time_ms;
+                }
                 Ok(other) => {
                     self.tx.send(other).unwrap();
                 }
@@ -811,6 +936,10 @@ impl LowPrioritySceneBuilderThread {
         Telemetry::record_rasterize_blobs_time(Duration::from_micros((txn.profile.get(profiler::BLOB_RASTERIZATION_TIME).unwrap() * 1000.00) as u64));
         txn.blob_requests = Vec::new();
 
+        if self.simulate_slow_ms > 0 {
+            thread::sleep(Duration::from_millis(self.simulate_slow_ms as u64));
+        }
+
         txn
     }
 }
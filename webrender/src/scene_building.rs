@@ -39,20 +39,22 @@ use api::{AlphaType, BorderDetails, BorderDisplayItem, BuiltDisplayListIter, Bui
 use api::{ClipId, ColorF, CommonItemProperties, ComplexClipRegion, ComponentTransferFuncType, RasterSpace};
 use api::{DebugFlags, DisplayItem, DisplayItemRef, ExtendMode, ExternalScrollId, FilterData};
 use api::{FilterOp, FilterPrimitive, FontInstanceKey, FontSize, GlyphInstance, GlyphOptions, GradientStop};
-use api::{IframeDisplayItem, ImageKey, ImageRendering, ItemRange, ColorDepth, QualitySettings};
+use api::{IframeDisplayItem, ImageKey, ImageRendering, ImageWrapMode, ItemRange, ColorDepth, QualitySettings};
 use api::{LineOrientation, LineStyle, NinePatchBorderSource, PipelineId, MixBlendMode, StackingContextFlags};
 use api::{PropertyBinding, ReferenceFrameKind, ScrollFrameDescriptor};
+use api::{GradientTemplate, GradientTemplateKey, GradientTemplateKind};
 use api::{APZScrollGeneration, HasScrollLinkedEffect, Shadow, SpatialId, StickyFrameDescriptor, ImageMask, ItemTag};
 use api::{ClipMode, PrimitiveKeyKind, TransformStyle, YuvColorSpace, ColorRange, YuvData, TempFilterData};
 use api::{ReferenceTransformBinding, Rotation, FillRule, SpatialTreeItem, ReferenceFrameDescriptor};
 use api::{FilterOpGraphPictureBufferId, SVGFE_GRAPH_MAX};
 use api::channel::{unbounded_channel, Receiver, Sender};
 use api::units::*;
-use crate::image_tiling::simplify_repeated_primitive;
+use crate::image_tiling::{clamp_to_repeat_mode, simplify_repeated_primitive};
 use crate::box_shadow::BLUR_SAMPLE_SCALE;
 use crate::clip::{ClipIntern, ClipItemKey, ClipItemKeyKind, ClipStore};
 use crate::clip::{ClipInternData, ClipNodeId, ClipLeafId};
 use crate::clip::{PolygonDataHandle, ClipTreeBuilder};
+use crate::composite::CompositorKind;
 use crate::segment::EdgeAaSegmentMask;
 use crate::spatial_tree::{SceneSpatialTree, SpatialNodeContainer, SpatialNodeIndex, get_external_scroll_offset};
 use crate::frame_builder::FrameBuilderConfig;
@@ -441,6 +443,9 @@ pub struct SceneBuilder<'a> {
     /// The map of all font instances.
     fonts: SharedFontResources,
 
+    /// The gradient templates that gradient ref items can refer to.
+    gradient_templates: &'a FastHashMap<GradientTemplateKey, GradientTemplate>,
+
     /// The data structure that converts between ClipId/SpatialId and the various
     /// index types that the SpatialTree uses.
     id_to_index_mapper_stack: Vec<NodeIdToIndexMapper>,
@@ -523,6 +528,9 @@ pub struct SceneBuilder<'a> {
     /// pipeline instance ids as they are encountered.
     pipeline_instance_ids: FastHashMap<PipelineId, u32>,
 
+    /// The spatial nodes created for the reference frames of the display lists.
+    reference_frames: FastHashMap<SpatialId, SpatialNodeIndex>,
+
     /// A list of surfaces (backing textures) that are relevant for this scene.
     /// Every picture is assigned to a surface (either a new surface if the picture
     /// has a composite mode, or the parent surface if it's a pass-through).
@@ -542,6 +550,7 @@ impl<'a> SceneBuilder<'a> {
         spatial_tree: &mut SceneSpatialTree,
         recycler: &mut SceneRecycler,
         stats: &SceneStats,
+        gradient_templates: &FastHashMap<GradientTemplateKey, GradientTemplate>,
         debug_flags: DebugFlags,
     ) -> BuiltScene {
         profile_scope!("build_scene");
@@ -561,7 +570,11 @@ impl<'a> SceneBuilder<'a> {
             scene,
             spatial_tree,
             fonts,
-            config: *frame_builder_config,
+            gradient_templates,
+            config: FrameBuilderConfig {
+                clip_aa_quality: view.quality_settings.clip_aa_quality,
+                ..*frame_builder_config
+            },
             id_to_index_mapper_stack: mem::take(&mut recycler.id_to_index_mapper_stack),
             hit_testing_scene: recycler.hit_testing_scene.take().unwrap_or_else(|| HitTestingScene::new(&stats.hit_test_stats)),
             pending_shadow_items: mem::take(&mut recycler.pending_shadow_items),
@@ -585,6 +598,7 @@ impl<'a> SceneBuilder<'a> {
             next_plane_splitter_index: 0,
             prim_instances: mem::take(&mut recycler.prim_instances),
             pipeline_instance_ids: FastHashMap::default(),
+            reference_frames: FastHashMap::default(),
             surfaces: mem::take(&mut recycler.surfaces),
             clip_tree_builder: recycler.clip_tree_builder.take().unwrap_or_else(|| ClipTreeBuilder::new()),
         };
@@ -636,6 +650,7 @@ impl<'a> SceneBuilder<'a> {
         }
 
         let clip_tree = builder.clip_tree_builder.finalize();
+        let max_clip_chain_depth = clip_tree.max_leaf_depth();
 
         recycler.clip_tree_builder = Some(builder.clip_tree_builder);
         recycler.sc_stack = builder.sc_stack;
@@ -648,7 +663,10 @@ impl<'a> SceneBuilder<'a> {
         BuiltScene {
             has_root_pipeline: scene.has_root_pipeline(),
             pipeline_epochs: scene.pipeline_epochs.clone(),
-            output_rect: view.device_rect.size().into(),
+            output_rect: (view.device_rect.size().to_f32() * builder.config.effective_render_scale())
+                .round()
+                .to_i32()
+                .into(),
             hit_testing_scene: Arc::new(builder.hit_testing_scene),
             prim_store: builder.prim_store,
             clip_store: builder.clip_store,
@@ -660,6 +678,8 @@ impl<'a> SceneBuilder<'a> {
             prim_instances: builder.prim_instances,
             surfaces: builder.surfaces,
             clip_tree,
+            max_clip_chain_depth,
+            reference_frames: builder.reference_frames,
             recycler_tx: Some(recycler.tx.clone()),
         }
     }
@@ -1144,6 +1164,7 @@ impl<'a> SceneBuilder<'a> {
             pipeline_id,
             info.reference_frame.transform_style,
             transform,
+            info.reference_frame.transform_origin,
             info.reference_frame.kind,
             (info.origin + external_scroll_offset).to_vector(),
             SpatialNodeUid::external(info.reference_frame.key, pipeline_id, instance_id),
@@ -1237,6 +1258,7 @@ impl<'a> SceneBuilder<'a> {
             iframe_pipeline_id,
             TransformStyle::Flat,
             PropertyBinding::Value(LayoutTransform::identity()),
+            LayoutPoint::zero(),
             ReferenceFrameKind::Transform {
                 is_2d_scale_translation: true,
                 should_snap: true,
@@ -1390,6 +1412,7 @@ impl<'a> SceneBuilder<'a> {
                     info.image_rendering,
                     info.alpha_type,
                     info.color,
+                    ImageWrapMode::Clamp,
                 );
             }
             DisplayItem::RepeatingImage(ref info) => {
@@ -1406,6 +1429,9 @@ impl<'a> SceneBuilder<'a> {
                     info.stretch_size,
                 );
 
+                let mut layout = layout;
+                clamp_to_repeat_mode(info.repeat_mode, &stretch_size, &mut layout.rect);
+
                 self.add_image(
                     spatial_node_index,
                     clip_node_id,
@@ -1416,6 +1442,7 @@ impl<'a> SceneBuilder<'a> {
                     info.image_rendering,
                     info.alpha_type,
                     info.color,
+                    info.wrap_mode,
                 );
             }
             DisplayItem::YuvImage(ref info) => {
@@ -1632,6 +1659,65 @@ impl<'a> SceneBuilder<'a> {
                     }
                 }
             }
+            DisplayItem::GradientRef(ref info) => {
+                profile_scope!("gradient_ref");
+
+                let gradient_templates = self.gradient_templates;
+                let template = match gradient_templates.get(&info.template) {
+                    Some(template) => template,
+                    None => {
+                        warn!("Missing gradient template {:?}", info.template);
+                        return;
+                    }
+                };
+
+                let (layout, _, spatial_node_index, clip_node_id) = self.process_common_properties_with_bounds(
+                    &info.common,
+                    info.bounds,
+                );
+
+                if layout.rect.is_empty() {
+                    return;
+                }
+
+                let stops = template.stops.iter().map(|stop| {
+                    GradientStopKey {
+                        offset: stop.offset,
+                        color: stop.color.into(),
+                    }
+                }).collect();
+
+                match template.kind {
+                    GradientTemplateKind::Linear { start_point, end_point, extend_mode } => {
+                        // Template points are relative to the item's bounds. The
+                        // gradient is rendered at the item's size, so items of the
+                        // same size share a render task cache entry through the
+                        // regular linear gradient cache key.
+                        let size = layout.rect.size();
+                        if let Some(mut prim) = self.create_linear_gradient_prim(
+                            &layout,
+                            LayoutPoint::new(start_point.x * size.width, start_point.y * size.height),
+                            LayoutPoint::new(end_point.x * size.width, end_point.y * size.height),
+                            stops,
+                            extend_mode,
+                            size,
+                            LayoutSize::zero(),
+                            None,
+                            EdgeAaSegmentMask::all(),
+                        ) {
+                            prim.cached = true;
+
+                            self.add_nonshadowable_primitive(
+                                spatial_node_index,
+                                clip_node_id,
+                                &layout,
+                                Vec::new(),
+                                prim,
+                            );
+                        }
+                    }
+                }
+            }
             DisplayItem::RadialGradient(ref info) => {
                 profile_scope!("radial");
 
@@ -2116,6 +2202,54 @@ impl<'a> SceneBuilder<'a> {
         }
     }
 
+    /// Returns the opacity binding of a stacking context that can be applied
+    /// when compositing picture cache tiles, rather than via an off-screen
+    /// surface. This is only the case for root level stacking contexts whose
+    /// only effect is an animated opacity.
+    fn get_slice_opacity(
+        &self,
+        composite_ops: &CompositeOps,
+        context_3d: &Picture3DContext<ExtendedPrimitiveInstance>,
+        blit_reason: BlitReason,
+        flags: StackingContextFlags,
+        prim_flags: PrimitiveFlags,
+        spatial_node_index: SpatialNodeIndex,
+        clip_node_id: ClipNodeId,
+    ) -> Option<PropertyBinding<f32>> {
+        if !self.sc_stack.is_empty() || self.tile_cache_builder.has_slice_opacity() {
+            return None;
+        }
+
+        // Native compositors don't support applying an opacity to surfaces.
+        if !matches!(self.config.compositor_kind, CompositorKind::Draw { .. }) {
+            return None;
+        }
+
+        let binding = match composite_ops.filters.as_slice() {
+            [Filter::Opacity(binding @ PropertyBinding::Binding(..), _)] => *binding,
+            _ => return None,
+        };
+
+        if !composite_ops.filter_primitives.is_empty() ||
+           composite_ops.mix_blend_mode.is_some() ||
+           !blit_reason.is_empty() ||
+           !flags.is_empty() ||
+           !prim_flags.contains(PrimitiveFlags::IS_BACKFACE_VISIBLE) {
+            return None;
+        }
+
+        if let Picture3DContext::In { .. } = context_3d {
+            return None;
+        }
+
+        if !self.spatial_tree.is_root_coord_system(spatial_node_index) ||
+           self.clip_tree_builder.clip_node_has_complex_clips(clip_node_id, &self.interners) {
+            return None;
+        }
+
+        Some(binding)
+    }
+
     /// Push a new stacking context. Returns context that must be passed to pop_stacking_context().
     fn push_stacking_context(
         &mut self,
@@ -2278,6 +2412,7 @@ impl<'a> SceneBuilder<'a> {
                     // atomic (so that compositor surfaces don't get promoted within it)
                     // and use that slice as the backing surface for the blend container
                     if self.tile_cache_builder.is_current_slice_empty() &&
+                       !self.tile_cache_builder.has_slice_opacity() &&
                        self.spatial_tree.is_root_coord_system(spatial_node_index) &&
                        !self.clip_tree_builder.clip_node_has_complex_clips(clip_node_id, &self.interners)
                     {
@@ -2294,6 +2429,28 @@ impl<'a> SceneBuilder<'a> {
             }
         }
 
+        // If this is a root level stacking context whose only effect is an animated
+        // opacity, draw its content into a picture cache slice of its own and apply
+        // the opacity when compositing the tiles. This allows fading the content
+        // without rasterizing the tiles again each frame.
+        let pop_slice_opacity = match self.get_slice_opacity(
+            &composite_ops,
+            &context_3d,
+            blit_reason,
+            flags,
+            prim_flags,
+            spatial_node_index,
+            clip_node_id,
+        ) {
+            Some(opacity) => {
+                self.tile_cache_builder.set_slice_opacity(Some(opacity));
+                self.add_tile_cache_barrier_if_needed(SliceFlags::IS_ATOMIC);
+                is_redundant = true;
+                true
+            }
+            None => false,
+        };
+
         // If stacking context is a scrollbar, force a new slice for the primitives
         // within. The stacking context will be redundant and removed by above check.
         let set_tile_cache_barrier = prim_flags.contains(PrimitiveFlags::IS_SCROLLBAR_CONTAINER);
@@ -2306,6 +2463,7 @@ impl<'a> SceneBuilder<'a> {
             pop_stacking_context: false,
             pop_containing_block: false,
             set_tile_cache_barrier,
+            pop_slice_opacity,
         };
 
         // If this is not 3d, then it establishes an ancestor root for child 3d contexts.
@@ -2358,6 +2516,11 @@ impl<'a> SceneBuilder<'a> {
             self.add_tile_cache_barrier_if_needed(SliceFlags::empty());
         }
 
+        if info.pop_slice_opacity {
+            self.tile_cache_builder.set_slice_opacity(None);
+            self.add_tile_cache_barrier_if_needed(SliceFlags::empty());
+        }
+
         // If the stacking context was otherwise redundant, early exit
         if !info.pop_stacking_context {
             return;
@@ -2662,6 +2825,7 @@ impl<'a> SceneBuilder<'a> {
         pipeline_id: PipelineId,
         transform_style: TransformStyle,
         source_transform: PropertyBinding<LayoutTransform>,
+        transform_origin: LayoutPoint,
         kind: ReferenceFrameKind,
         origin_in_parent_reference_frame: LayoutVector2D,
         uid: SpatialNodeUid,
@@ -2670,12 +2834,14 @@ impl<'a> SceneBuilder<'a> {
             parent_index,
             transform_style,
             source_transform,
+            transform_origin,
             kind,
             origin_in_parent_reference_frame,
             pipeline_id,
             uid,
         );
         self.id_to_index_mapper_stack.last_mut().unwrap().add_spatial_node(reference_frame_id, index);
+        self.reference_frames.insert(reference_frame_id, index);
 
         index
     }
@@ -2685,12 +2851,16 @@ impl<'a> SceneBuilder<'a> {
         pipeline_id: PipelineId,
         instance: PipelineInstanceId,
     ) {
+        // The whole document is scaled down at its root when it is rendered
+        // at a lower internal resolution.
+        let render_scale = self.config.effective_render_scale();
         let spatial_node_index = self.push_reference_frame(
             SpatialId::root_reference_frame(pipeline_id),
             self.spatial_tree.root_reference_frame_index(),
             pipeline_id,
             TransformStyle::Flat,
-            PropertyBinding::Value(LayoutTransform::identity()),
+            PropertyBinding::Value(LayoutTransform::scale(render_scale, render_scale, 1.0)),
+            LayoutPoint::zero(),
             ReferenceFrameKind::Transform {
                 is_2d_scale_translation: true,
                 should_snap: true,
@@ -2929,6 +3099,7 @@ impl<'a> SceneBuilder<'a> {
                         width: std_deviation,
                         height: std_deviation,
                         should_inflate: pending_shadow.should_inflate,
+                        cheap: false,
                     };
                     let blur_is_noop = blur_filter.is_noop();
 
@@ -3323,6 +3494,7 @@ impl<'a> SceneBuilder<'a> {
                     info,
                     border,
                     border_item.widths,
+                    border_item.dash_phase,
                     spatial_node_index,
                     clip_node_id,
                 );
@@ -3575,6 +3747,7 @@ impl<'a> SceneBuilder<'a> {
         image_rendering: ImageRendering,
         alpha_type: AlphaType,
         color: ColorF,
+        wrap_mode: ImageWrapMode,
     ) {
         let mut prim_rect = info.rect;
         simplify_repeated_primitive(&stretch_size, &mut tile_spacing, &mut prim_rect);
@@ -3595,6 +3768,7 @@ impl<'a> SceneBuilder<'a> {
                 color: color.into(),
                 image_rendering,
                 alpha_type,
+                wrap_mode,
             },
         );
     }
@@ -4450,6 +4624,9 @@ struct StackingContextInfo {
     pop_stacking_context: bool,
     /// If true, set a tile cache barrier when popping the stacking context.
     set_tile_cache_barrier: bool,
+    /// If true, the content was drawn into a slice with a composite opacity,
+    /// which must be ended when popping the stacking context.
+    pop_slice_opacity: bool,
 }
 
 /// Properties of a stacking context that are maintained
@@ -15,6 +15,36 @@ use crate::util::project_rect;
 use crate::util::{MatrixHelpers, ScaleOffset, RectHelpers, PointHelpers};
 
 
+/// Adjust a local rect so that its edges land on whole device pixels in the
+/// given raster space. The rect is returned untouched if the primitive's
+/// transform to the raster space isn't a 2d scale and offset, since a rotated
+/// or skewed rect can't be snapped meaningfully.
+pub fn snap_rect_to_device_pixels(
+    rect: &LayoutRect,
+    prim_spatial_node_index: SpatialNodeIndex,
+    raster_spatial_node_index: SpatialNodeIndex,
+    device_pixel_scale: DevicePixelScale,
+    spatial_tree: &SpatialTree,
+) -> LayoutRect {
+    let scale_offset = match spatial_tree.get_relative_transform(
+        prim_spatial_node_index,
+        raster_spatial_node_index,
+    ) {
+        CoordinateSpaceMapping::Local => ScaleOffset::identity(),
+        CoordinateSpaceMapping::ScaleOffset(scale_offset) => scale_offset,
+        CoordinateSpaceMapping::Transform(ref transform) => {
+            match ScaleOffset::from_transform(transform) {
+                Some(scale_offset) => scale_offset,
+                None => return *rect,
+            }
+        }
+    };
+
+    let local_to_device = scale_offset.then_scale(device_pixel_scale.0);
+    let device_rect: DeviceRect = local_to_device.map_rect(rect);
+    local_to_device.unmap_rect(&device_rect.snap())
+}
+
 #[derive(Debug, Clone)]
 pub struct SpaceMapper<F, T> {
     kind: CoordinateSpaceMapping<F, T>,
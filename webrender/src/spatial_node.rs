@@ -4,7 +4,7 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use api::{ExternalScrollId, PipelineId, PropertyBinding, PropertyBindingId, ReferenceFrameKind};
-use api::{APZScrollGeneration, HasScrollLinkedEffect, SampledScrollOffset};
+use api::{APZScrollGeneration, HasScrollLinkedEffect, OverscrollMode, SampledScrollOffset, ScrollConfig};
 use api::{TransformStyle, StickyOffsetBounds, SpatialTreeItemKey};
 use api::units::*;
 use crate::internal_types::PipelineInstanceId;
@@ -166,6 +166,7 @@ impl SceneSpatialNode {
         parent_index: Option<SpatialNodeIndex>,
         transform_style: TransformStyle,
         source_transform: PropertyBinding<LayoutTransform>,
+        transform_origin: LayoutPoint,
         kind: ReferenceFrameKind,
         origin_in_parent_reference_frame: LayoutVector2D,
         pipeline_id: PipelineId,
@@ -175,6 +176,7 @@ impl SceneSpatialNode {
         let info = ReferenceFrameInfo {
             transform_style,
             source_transform,
+            transform_origin,
             kind,
             origin_in_parent_reference_frame,
             is_pipeline_root,
@@ -320,12 +322,48 @@ fn snap_offset<OffsetUnits, ScaleUnits>(
     )
 }
 
+/// The maximum distance a rubber-banded scroll frame can be pulled past its
+/// edge, as a fraction of the viewport size along that axis.
+const MAX_RUBBER_BAND_FRACTION: f32 = 0.25;
+
+/// Adjust a scroll position along one axis according to the document's scroll
+/// config, given the scrollable range [0, scrollable] and the viewport extent.
+fn apply_scroll_config(
+    position: f32,
+    scrollable: f32,
+    viewport: f32,
+    config: &ScrollConfig,
+) -> f32 {
+    let max = scrollable.max(0.0);
+    match config.overscroll {
+        OverscrollMode::Clamp => position.max(0.0).min(max),
+        OverscrollMode::Allow if !config.rubber_band => position,
+        OverscrollMode::Allow => {
+            let limit = viewport * MAX_RUBBER_BAND_FRACTION;
+            // Map the overscroll distance d to limit * d / (d + limit), which
+            // is roughly linear for small pulls and approaches `limit`.
+            let damp = |d: f32| if limit > 0.0 { limit * d / (d + limit) } else { 0.0 };
+            if position < 0.0 {
+                -damp(-position)
+            } else if position > max {
+                max + damp(position - max)
+            } else {
+                position
+            }
+        }
+    }
+}
+
 impl SpatialNode {
     pub fn add_child(&mut self, child: SpatialNodeIndex) {
         self.children.push(child);
     }
 
-    pub fn set_scroll_offsets(&mut self, mut offsets: Vec<SampledScrollOffset>) -> bool {
+    pub fn set_scroll_offsets(
+        &mut self,
+        mut offsets: Vec<SampledScrollOffset>,
+        config: &ScrollConfig,
+    ) -> bool {
         debug_assert!(offsets.len() > 0);
 
         let scrolling = match self.node_type {
@@ -336,8 +374,17 @@ impl SpatialNode {
             }
         };
 
+        let viewport_size = scrolling.viewport_rect.size();
         for element in offsets.iter_mut() {
-            element.offset = -element.offset - scrolling.external_scroll_offset;
+            // Work with the total scroll position, which includes the amount
+            // pre-scrolled in the display list, so that clamping applies to the
+            // full scrollable range.
+            let position = element.offset + scrolling.external_scroll_offset;
+            let position = LayoutVector2D::new(
+                apply_scroll_config(position.x, scrolling.scrollable_size.width, viewport_size.width, config),
+                apply_scroll_config(position.y, scrolling.scrollable_size.height, viewport_size.height, config),
+            );
+            element.offset = -position;
         }
 
         if scrolling.offsets == offsets {
@@ -404,7 +451,13 @@ impl SpatialNode {
 
                 // Resolve the transform against any property bindings.
                 let source_transform = {
-                    let source_transform = scene_properties.resolve_layout_transform(&info.source_transform);
+                    let mut source_transform = scene_properties.resolve_layout_transform(&info.source_transform);
+                    if info.transform_origin != LayoutPoint::zero() {
+                        let origin = info.transform_origin.to_vector().to_3d();
+                        source_transform = LayoutTransform::translation(-origin.x, -origin.y, 0.0)
+                            .then(&source_transform)
+                            .then_translate(origin);
+                    }
                     if let ReferenceFrameKind::Transform { is_2d_scale_translation: true, .. } = info.kind {
                         assert!(source_transform.is_2d_scale_translation(), "Reference frame was marked as only having 2d scale or translation");
                     }
@@ -887,6 +940,9 @@ pub struct ReferenceFrameInfo {
     /// here so that we can resolve the animated transform and update the tree each
     /// frame.
     pub source_transform: PropertyBinding<LayoutTransform>,
+    /// The point in the local space of the reference frame that the source
+    /// transform is applied around.
+    pub transform_origin: LayoutPoint,
     pub transform_style: TransformStyle,
     pub kind: ReferenceFrameKind,
 
@@ -3,7 +3,7 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use api::{ExternalScrollId, PropertyBinding, ReferenceFrameKind, TransformStyle, PropertyBindingId};
-use api::{APZScrollGeneration, HasScrollLinkedEffect, PipelineId, SampledScrollOffset, SpatialTreeItemKey};
+use api::{APZScrollGeneration, HasScrollLinkedEffect, PipelineId, SampledScrollOffset, ScrollConfig, SpatialTreeItemKey};
 use api::units::*;
 use euclid::Transform3D;
 use crate::gpu_types::TransformPalette;
@@ -254,6 +254,7 @@ impl SceneSpatialTree {
             None,
             TransformStyle::Flat,
             PropertyBinding::Value(LayoutTransform::identity()),
+            LayoutPoint::zero(),
             ReferenceFrameKind::Transform {
                 should_snap: true,
                 is_2d_scale_translation: true,
@@ -502,6 +503,7 @@ impl SceneSpatialTree {
         parent_index: SpatialNodeIndex,
         transform_style: TransformStyle,
         source_transform: PropertyBinding<LayoutTransform>,
+        transform_origin: LayoutPoint,
         kind: ReferenceFrameKind,
         origin_in_parent_reference_frame: LayoutVector2D,
         pipeline_id: PipelineId,
@@ -540,6 +542,7 @@ impl SceneSpatialTree {
             Some(parent_index),
             transform_style,
             source_transform,
+            transform_origin,
             kind,
             origin_in_parent_reference_frame,
             pipeline_id,
@@ -1151,12 +1154,13 @@ impl SpatialTree {
         &mut self,
         id: ExternalScrollId,
         offsets: Vec<SampledScrollOffset>,
+        config: &ScrollConfig,
     ) -> bool {
         let mut did_change = false;
 
         self.visit_nodes_mut(|_, node| {
             if node.matches_external_id(id) {
-                did_change |= node.set_scroll_offsets(offsets.clone());
+                did_change |= node.set_scroll_offsets(offsets.clone(), config);
             }
         });
 
@@ -1385,6 +1389,27 @@ pub fn get_external_scroll_offset<S: SpatialNodeContainer>(
     offset
 }
 
+/// Returns the external id of the closest scroll frame that is an ancestor of
+/// (or is) the given node, or `None` if the node isn't inside a scroll frame.
+pub fn get_enclosing_scroll_id<S: SpatialNodeContainer>(
+    spatial_tree: &S,
+    node_index: SpatialNodeIndex,
+) -> Option<ExternalScrollId> {
+    let mut current_node = Some(node_index);
+
+    while let Some(node_index) = current_node {
+        let node_info = spatial_tree.get_node_info(node_index);
+
+        if let SpatialNodeType::ScrollFrame(ref scrolling) = node_info.node_type {
+            return Some(scrolling.external_id);
+        }
+
+        current_node = node_info.parent;
+    }
+
+    None
+}
+
 fn calculate_snapping_transform(
     parent_snapping_transform: Option<ScaleOffset>,
     node_type: &SpatialNodeType,
@@ -1442,6 +1467,7 @@ fn add_reference_frame(
         parent,
         TransformStyle::Preserve3D,
         PropertyBinding::Value(transform),
+        LayoutPoint::zero(),
         ReferenceFrameKind::Transform {
             is_2d_scale_translation: false,
             should_snap: false,
@@ -1737,6 +1763,7 @@ fn test_find_scroll_root_simple() {
         st.root_reference_frame_index(),
         TransformStyle::Flat,
         PropertyBinding::Value(LayoutTransform::identity()),
+        LayoutPoint::zero(),
         ReferenceFrameKind::Transform {
             is_2d_scale_translation: true,
             should_snap: true,
@@ -1773,6 +1800,7 @@ fn test_find_scroll_root_sub_scroll_frame() {
         st.root_reference_frame_index(),
         TransformStyle::Flat,
         PropertyBinding::Value(LayoutTransform::identity()),
+        LayoutPoint::zero(),
         ReferenceFrameKind::Transform {
             is_2d_scale_translation: true,
             should_snap: true,
@@ -1822,6 +1850,7 @@ fn test_find_scroll_root_not_scrollable() {
         st.root_reference_frame_index(),
         TransformStyle::Flat,
         PropertyBinding::Value(LayoutTransform::identity()),
+        LayoutPoint::zero(),
         ReferenceFrameKind::Transform {
             is_2d_scale_translation: true,
             should_snap: true,
@@ -1871,6 +1900,7 @@ fn test_find_scroll_root_too_small() {
         st.root_reference_frame_index(),
         TransformStyle::Flat,
         PropertyBinding::Value(LayoutTransform::identity()),
+        LayoutPoint::zero(),
         ReferenceFrameKind::Transform {
             is_2d_scale_translation: true,
             should_snap: true,
@@ -1921,6 +1951,7 @@ fn test_find_scroll_root_perspective() {
         st.root_reference_frame_index(),
         TransformStyle::Flat,
         PropertyBinding::Value(LayoutTransform::identity()),
+        LayoutPoint::zero(),
         ReferenceFrameKind::Transform {
             is_2d_scale_translation: true,
             should_snap: true,
@@ -1948,6 +1979,7 @@ fn test_find_scroll_root_perspective() {
         root_scroll,
         TransformStyle::Flat,
         PropertyBinding::Value(LayoutTransform::identity()),
+        LayoutPoint::zero(),
         ReferenceFrameKind::Perspective {
             scrolling_relative_to: None,
         },
@@ -1983,6 +2015,7 @@ fn test_find_scroll_root_2d_scale() {
         st.root_reference_frame_index(),
         TransformStyle::Flat,
         PropertyBinding::Value(LayoutTransform::identity()),
+        LayoutPoint::zero(),
         ReferenceFrameKind::Transform {
             is_2d_scale_translation: true,
             should_snap: true,
@@ -2010,6 +2043,7 @@ fn test_find_scroll_root_2d_scale() {
         root_scroll,
         TransformStyle::Flat,
         PropertyBinding::Value(LayoutTransform::identity()),
+        LayoutPoint::zero(),
         ReferenceFrameKind::Transform {
             is_2d_scale_translation: true,
             should_snap: false,
@@ -2047,6 +2081,7 @@ fn test_find_scroll_root_sticky() {
         st.root_reference_frame_index(),
         TransformStyle::Flat,
         PropertyBinding::Value(LayoutTransform::identity()),
+        LayoutPoint::zero(),
         ReferenceFrameKind::Transform {
             is_2d_scale_translation: true,
             should_snap: true,
@@ -2125,6 +2160,62 @@ fn test_world_transforms() {
       LayoutToWorldTransform::identity());
 }
 
+/// Tests that scrolling past the content bounds is clamped or rubber-banded
+/// according to the scroll config.
+#[test]
+fn test_scroll_config_overscroll() {
+    use api::OverscrollMode;
+
+    let mut cst = SceneSpatialTree::new();
+    let pid = PipelineInstanceId::new(0);
+    let scroll_id = ExternalScrollId(1, PipelineId::dummy());
+    let scroll = cst.add_scroll_frame(
+        cst.root_reference_frame_index(),
+        scroll_id,
+        PipelineId::dummy(),
+        &LayoutRect::from_size(LayoutSize::new(400.0, 400.0)),
+        &LayoutSize::new(400.0, 800.0),
+        ScrollFrameKind::Explicit,
+        LayoutVector2D::zero(),
+        APZScrollGeneration::default(),
+        HasScrollLinkedEffect::No,
+        SpatialNodeUid::external(SpatialTreeItemKey::new(0, 1), PipelineId::dummy(), pid));
+
+    let mut st = SpatialTree::new();
+    st.apply_updates(cst.end_frame_and_get_pending_updates());
+
+    let scroll_to = |st: &mut SpatialTree, y: f32, config: ScrollConfig| -> f32 {
+        st.set_scroll_offsets(
+            scroll_id,
+            vec![SampledScrollOffset {
+                offset: LayoutVector2D::new(0.0, y),
+                generation: APZScrollGeneration::default(),
+            }],
+            &config,
+        );
+        st.update_tree(&SceneProperties::new());
+        -st.get_world_transform(scroll).into_transform().m42
+    };
+
+    // Unconfigured documents apply offsets as-is.
+    assert_eq!(scroll_to(&mut st, 1000.0, ScrollConfig::default()), 1000.0);
+
+    let clamp = ScrollConfig { overscroll: OverscrollMode::Clamp, rubber_band: true };
+    assert_eq!(scroll_to(&mut st, 1000.0, clamp), 400.0);
+    assert_eq!(scroll_to(&mut st, -50.0, clamp), 0.0);
+
+    // Rubber-banding lets the frame overscroll, but by no more than a quarter
+    // of the viewport however far past the edge we go.
+    let rubber_band = ScrollConfig { overscroll: OverscrollMode::Allow, rubber_band: true };
+    let near = scroll_to(&mut st, 420.0, rubber_band);
+    let far = scroll_to(&mut st, 100000.0, rubber_band);
+    assert!(near > 400.0 && near < 420.0);
+    assert!(far > near && far < 500.0);
+    let before_start = scroll_to(&mut st, -100000.0, rubber_band);
+    assert!(before_start < 0.0 && before_start > -100.0);
+    assert_eq!(scroll_to(&mut st, 200.0, rubber_band), 200.0);
+}
+
 /// Tests that a spatial node that is async zooming and all of its descendants
 /// are correctly marked as having themselves an ancestor that is zooming.
 #[test]
@@ -25,6 +25,7 @@ use crate::texture_pack::{
 use std::cell::Cell;
 use std::mem;
 use std::rc::Rc;
+use std::sync::Arc;
 use euclid::size2;
 use malloc_size_of::{MallocSizeOf, MallocSizeOfOps};
 
@@ -984,6 +985,32 @@ impl TextureCache {
         }
     }
 
+    // Upload a region of an allocated item from a tightly packed buffer,
+    // rather than from the image data the item was last updated with.
+    pub fn update_region(
+        &mut self,
+        handle: &TextureCacheHandle,
+        format: ImageFormat,
+        source: Arc<Vec<u8>>,
+        rect: DeviceIntRect,
+        offset: usize,
+    ) {
+        let entry = self.get_entry_opt(handle)
+            .expect("BUG: Regions can only be uploaded to allocated items");
+        debug_assert!(DeviceIntRect::from_size(entry.size).contains_box(&rect));
+
+        let origin = entry.details.describe();
+        let texture_id = entry.texture_id;
+        let op = TextureCacheUpdate {
+            rect: rect.translate(origin.to_vector()),
+            stride: Some(rect.width() * format.bytes_per_pixel()),
+            offset: offset as i32,
+            format_override: if self.swizzle.is_none() { Some(format) } else { None },
+            source: TextureUpdateSource::Bytes { data: source },
+        };
+        self.pending_updates.push_update(texture_id, op);
+    }
+
     // Check if a given texture handle has a valid allocation
     // in the texture cache.
     pub fn is_allocated(&self, handle: &TextureCacheHandle) -> bool {
@@ -998,6 +1025,17 @@ impl TextureCache {
         })
     }
 
+    /// Return the texture and device rect of a handle's allocation, if it is
+    /// currently resident. Unlike `get_cache_location`, this doesn't require
+    /// the handle to have been requested this frame.
+    #[cfg(feature = "capture")]
+    pub fn get_allocation(&self, handle: &TextureCacheHandle) -> Option<(CacheTextureId, DeviceIntRect)> {
+        self.get_entry_opt(handle).map(|entry| {
+            let origin = entry.details.describe();
+            (entry.texture_id, DeviceIntRect::from_origin_and_size(origin, entry.size))
+        })
+    }
+
     // Retrieve the details of an item in the cache. This is used
     // during batch creation to provide the resource rect address
     // to the shaders and texture ID to the batching logic.
@@ -1310,6 +1348,7 @@ impl TextureCache {
                         filter: parameters.filter,
                         is_shared_cache: true,
                         has_depth: false,
+                        is_srgb: false,
                         category: TextureCacheCategory::Atlas,
                     },
                 );
@@ -1393,6 +1432,7 @@ impl TextureCache {
         &mut self,
         size: DeviceIntSize,
         format: ImageFormat,
+        is_srgb: bool,
     ) -> CacheTextureId {
         let texture_id = self.next_id;
         self.next_id.0 += 1;
@@ -1406,6 +1446,7 @@ impl TextureCache {
             filter: TextureFilter::Linear,
             is_shared_cache: false,
             has_depth: false,
+            is_srgb,
             category: TextureCacheCategory::RenderTarget,
         };
 
@@ -1439,6 +1480,7 @@ impl TextureCache {
             filter: params.filter,
             is_shared_cache: false,
             has_depth: false,
+            is_srgb: false,
             category: TextureCacheCategory::Standalone,
         };
 
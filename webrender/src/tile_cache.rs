@@ -3,6 +3,7 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use api::{ColorF, DebugFlags, PrimitiveFlags, QualitySettings, RasterSpace, ClipId};
+use api::PropertyBinding;
 use api::units::*;
 use crate::clip::{ClipNodeKind, ClipLeafId, ClipNodeId, ClipTreeBuilder};
 use crate::frame_builder::FrameBuilderConfig;
@@ -63,6 +64,8 @@ struct PrimarySlice {
     iframe_clip: Option<ClipId>,
     /// Information about how to draw and composite this slice
     slice_flags: SliceFlags,
+    /// Optional opacity applied when compositing the tiles of this slice
+    opacity: Option<PropertyBinding<f32>>,
 }
 
 impl PrimarySlice {
@@ -70,13 +73,23 @@ impl PrimarySlice {
         slice_flags: SliceFlags,
         iframe_clip: Option<ClipId>,
         background_color: Option<ColorF>,
+        opacity: Option<PropertyBinding<f32>>,
     ) -> Self {
-        PrimarySlice {
+        let mut slice = PrimarySlice {
             kind: SliceKind::default(),
             background_color,
             iframe_clip,
             slice_flags,
+            opacity,
+        };
+
+        // The opacity is applied to the whole slice at composite time, so
+        // the content must end up in a single tile cache.
+        if slice.opacity.is_some() {
+            slice.merge();
         }
+
+        slice
     }
 
     fn has_too_many_slices(&self) -> bool {
@@ -121,6 +134,8 @@ pub struct TileCacheBuilder {
     root_spatial_node_index: SpatialNodeIndex,
     /// Debug flags to provide to our TileCacheInstances.
     debug_flags: DebugFlags,
+    /// If set, the opacity binding applied to slices created from now on.
+    slice_opacity: Option<PropertyBinding<f32>>,
 }
 
 /// The output of a tile cache builder, containing all details needed to construct the
@@ -150,13 +165,25 @@ impl TileCacheBuilder {
         debug_flags: DebugFlags,
     ) -> Self {
         TileCacheBuilder {
-            primary_slices: vec![PrimarySlice::new(SliceFlags::empty(), None, background_color)],
+            primary_slices: vec![PrimarySlice::new(SliceFlags::empty(), None, background_color, None)],
             prev_scroll_root_cache: (SpatialNodeIndex::INVALID, SpatialNodeIndex::INVALID),
             root_spatial_node_index,
             debug_flags,
+            slice_opacity: None,
         }
     }
 
+    /// Set (or clear) the opacity binding that will be applied at composite
+    /// time to slices created by subsequent tile cache barriers.
+    pub fn set_slice_opacity(&mut self, opacity: Option<PropertyBinding<f32>>) {
+        self.slice_opacity = opacity;
+    }
+
+    /// Returns true if slices are currently being created with a composite opacity.
+    pub fn has_slice_opacity(&self) -> bool {
+        self.slice_opacity.is_some()
+    }
+
     pub fn make_current_slice_atomic(&mut self) {
         self.primary_slices
             .last_mut()
@@ -193,6 +220,7 @@ impl TileCacheBuilder {
             slice_flags,
             iframe_clip,
             None,
+            self.slice_opacity,
         );
 
         self.primary_slices.push(new_slice);
@@ -481,6 +509,7 @@ impl TileCacheBuilder {
                             primary_slice.iframe_clip,
                             descriptor.prim_list,
                             primary_slice.background_color,
+                            primary_slice.opacity,
                             descriptor.shared_clip_node_id,
                             prim_store,
                             config,
@@ -499,6 +528,7 @@ impl TileCacheBuilder {
                             primary_slice.iframe_clip,
                             descriptor.prim_list,
                             primary_slice.background_color,
+                            primary_slice.opacity,
                             descriptor.shared_clip_node_id,
                             prim_store,
                             config,
@@ -578,6 +608,7 @@ fn create_tile_cache(
     iframe_clip: Option<ClipId>,
     prim_list: PrimitiveList,
     background_color: Option<ColorF>,
+    composite_opacity: Option<PropertyBinding<f32>>,
     shared_clip_node_id: ClipNodeId,
     prim_store: &mut PrimitiveStore,
     frame_builder_config: &FrameBuilderConfig,
@@ -607,7 +638,7 @@ fn create_tile_cache(
 
     let slice = tile_cache_pictures.len();
 
-    let background_color = if slice == 0 {
+    let background_color = if slice == 0 && composite_opacity.is_none() {
         background_color
     } else {
         None
@@ -623,6 +654,7 @@ fn create_tile_cache(
         slice_flags,
         spatial_node_index: scroll_root,
         background_color,
+        composite_opacity,
         shared_clip_node_id,
         shared_clip_leaf_id,
         virtual_surface_size: frame_builder_config.compositor_kind.get_virtual_surface_size(),
@@ -1144,10 +1144,10 @@ impl<Src, Dst> FastTransform<Src, Dst> {
     pub fn is_backface_visible(&self) -> bool {
         match *self {
             FastTransform::Offset(..) => false,
-            FastTransform::Transform { inverse: None, .. } => false,
-            //TODO: fix this properly by taking "det|M33| * det|M34| > 0"
-            // see https://www.w3.org/Bugs/Public/show_bug.cgi?id=23014
-            FastTransform::Transform { inverse: Some(ref inverse), .. } => inverse.m33 < 0.0,
+            // The back face is showing if the (3, 3) element of the inverse is
+            // negative. This is derived from the sign of the determinant and the
+            // matching cofactor, so non-invertible transforms are front facing.
+            FastTransform::Transform { ref transform, .. } => transform.is_backface_visible(),
         }
     }
 
@@ -7,7 +7,7 @@ use peek_poke::PeekPoke;
 use std::ops::Not;
 // local imports
 use crate::font;
-use crate::{APZScrollGeneration, HasScrollLinkedEffect, PipelineId, PropertyBinding};
+use crate::{APZScrollGeneration, HasScrollLinkedEffect, IdNamespace, PipelineId, PropertyBinding};
 use crate::serde::{Serialize, Deserialize};
 use crate::color::ColorF;
 use crate::image::{ColorDepth, ImageKey};
@@ -57,6 +57,12 @@ bitflags! {
         const ANTIALISED = 1 << 4;
         /// If true, this primitive is used as a background for checkerboarding
         const CHECKERBOARD_BACKGROUND = 1 << 5;
+        /// Round the primitive's device space rect to whole pixels during frame
+        /// building, so that fractional offsets (e.g. from scrolling or animated
+        /// transforms) don't produce soft edges. Applies to rectangles, images and
+        /// borders, and is ignored when the primitive isn't axis-aligned with its
+        /// raster space or when ANTIALISED is set.
+        const SNAP_TO_DEVICE_PIXELS = 1 << 6;
     }
 }
 
@@ -148,6 +154,15 @@ impl SpatialTreeItemKey {
             key1,
         }
     }
+
+    /// Derive a key for the `index`-th item generated on behalf of this one,
+    /// e.g. the per-glyph reference frames of a text run laid out on a path.
+    pub fn derive(&self, index: u64) -> Self {
+        SpatialTreeItemKey {
+            key0: self.key0,
+            key1: self.key1.wrapping_add(index),
+        }
+    }
 }
 
 #[repr(u8)]
@@ -174,6 +189,7 @@ pub enum DisplayItem {
     Gradient(GradientDisplayItem),
     RadialGradient(RadialGradientDisplayItem),
     ConicGradient(ConicGradientDisplayItem),
+    GradientRef(GradientRefDisplayItem),
     Image(ImageDisplayItem),
     RepeatingImage(RepeatingImageDisplayItem),
     YuvImage(YuvImageDisplayItem),
@@ -224,6 +240,7 @@ pub enum DebugDisplayItem {
     Gradient(GradientDisplayItem),
     RadialGradient(RadialGradientDisplayItem),
     ConicGradient(ConicGradientDisplayItem),
+    GradientRef(GradientRefDisplayItem),
     Image(ImageDisplayItem),
     RepeatingImage(RepeatingImageDisplayItem),
     YuvImage(YuvImageDisplayItem),
@@ -556,6 +573,10 @@ pub struct BorderDisplayItem {
     pub bounds: LayoutRect,
     pub widths: LayoutSideOffsets,
     pub details: BorderDetails,
+    /// Offset of the dash pattern along the dashed edges of a normal border,
+    /// as a fraction of the dash period (one dash and one gap). Animating it
+    /// produces "marching ants". Corners and other styles ignore it.
+    pub dash_phase: PropertyBinding<f32>,
 }
 
 #[repr(C)]
@@ -693,6 +714,48 @@ pub struct GradientStop {
     pub color: ColorF,
 }
 
+/// Identifies a gradient created with `RenderApi::create_gradient_template`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, MallocSizeOf, PartialEq, Serialize, PeekPoke)]
+pub struct GradientTemplateKey(pub IdNamespace, pub u32);
+
+impl GradientTemplateKey {
+    /// Mints a new GradientTemplateKey. The given ID must be unique.
+    pub fn new(namespace: IdNamespace, key: u32) -> Self {
+        GradientTemplateKey(namespace, key)
+    }
+}
+
+/// The geometry of a gradient template.
+///
+/// Points are in unit coordinates relative to the bounds of the items that
+/// reference the template: (0, 0) is the top-left corner and (1, 1) the
+/// bottom-right one.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum GradientTemplateKind {
+    Linear {
+        start_point: LayoutPoint,
+        end_point: LayoutPoint,
+        extend_mode: ExtendMode,
+    },
+}
+
+/// A gradient that is rasterized once per item size and shared by all the
+/// `GradientRefDisplayItem`s of that size that reference it.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct GradientTemplate {
+    pub kind: GradientTemplateKind,
+    pub stops: Vec<GradientStop>,
+}
+
+/// Draws a gradient template over `bounds`.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize, PeekPoke)]
+pub struct GradientRefDisplayItem {
+    pub common: CommonItemProperties,
+    pub bounds: LayoutRect,
+    pub template: GradientTemplateKey,
+}
+
 #[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize, PeekPoke)]
 pub struct RadialGradient {
     pub center: LayoutPoint,
@@ -871,6 +934,9 @@ pub struct ReferenceFrame {
     /// The transform matrix, either the perspective matrix or the transform
     /// matrix.
     pub transform: ReferenceTransformBinding,
+    /// The point, in the local space of the reference frame, that the
+    /// transform is applied around.
+    pub transform_origin: LayoutPoint,
     pub id: SpatialId,
     /// A unique (per-pipeline) key for this spatial that is stable across display lists.
     pub key: SpatialTreeItemKey,
@@ -1249,6 +1315,9 @@ pub struct FilterOpGraphNode {
 /// a good user experience, performance-wise.
 pub const SVGFE_GRAPH_MAX: usize = 256;
 
+/// The maximum number of shadows in a `FilterOp::DropShadows`.
+pub const MAX_DROP_SHADOWS: usize = 8;
+
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Deserialize, Serialize, PeekPoke)]
 pub enum FilterOp {
@@ -1261,6 +1330,13 @@ pub enum FilterOp {
     /// parameters: stdDeviationX, stdDeviationY
     /// CSS filter semantics - operates on previous picture, uses sRGB space (non-linear)
     Blur(f32, f32),
+    /// apply an approximate blur, produced by downsampling the picture and
+    /// upsampling it again rather than running a full Gaussian. Much cheaper
+    /// for large radii, at the cost of a slightly boxier, less smooth result.
+    /// Intended for live-updating backdrop blurs.
+    /// parameters: stdDeviation
+    /// CSS filter semantics - operates on previous picture, uses sRGB space (non-linear)
+    CheapBlur(f32),
     /// apply brightness effect
     /// parameters: amount
     /// CSS filter semantics - operates on previous picture, uses sRGB space (non-linear)
@@ -1297,6 +1373,13 @@ pub enum FilterOp {
     /// parameters: shadow
     /// CSS filter semantics - operates on previous picture, uses sRGB space (non-linear)
     DropShadow(Shadow),
+    /// add several drop shadows of the image to the image in a single filter.
+    /// Unlike successive `DropShadow` filters, the shadows don't cast shadows
+    /// of each other. The first shadow is drawn on top. Only the first `count`
+    /// shadows are used, see `FilterOp::drop_shadows`.
+    /// parameters: shadows, count
+    /// CSS filter semantics - operates on previous picture, uses sRGB space (non-linear)
+    DropShadows([Shadow; MAX_DROP_SHADOWS], u32),
     /// transform color and alpha in image through 4x5 color matrix (transposed for efficiency)
     /// parameters: matrix[5][4]
     /// CSS filter semantics - operates on previous picture, uses sRGB space (non-linear)
@@ -1706,6 +1789,17 @@ pub enum FilterOp {
         base_frequency_x: f32, base_frequency_y: f32, num_octaves: u32, seed: u32},
 }
 
+impl FilterOp {
+    /// Build a `FilterOp::DropShadows` from a list of at most
+    /// `MAX_DROP_SHADOWS` shadows, the first of which is drawn on top.
+    pub fn drop_shadows(shadows: &[Shadow]) -> FilterOp {
+        assert!(shadows.len() <= MAX_DROP_SHADOWS, "Too many drop shadows");
+        let mut array = [Shadow::default(); MAX_DROP_SHADOWS];
+        array[.. shadows.len()].copy_from_slice(shadows);
+        FilterOp::DropShadows(array, shadows.len() as u32)
+    }
+}
+
 #[repr(u8)]
 #[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize, PeekPoke)]
 pub enum ComponentTransferFuncType {
@@ -1849,6 +1943,10 @@ pub struct RepeatingImageDisplayItem {
     pub alpha_type: AlphaType,
     /// A hack used by gecko to color a simple bitmap font used for tofu glyphs
     pub color: ColorF,
+    /// How the texture sampler wraps between tiles.
+    pub wrap_mode: ImageWrapMode,
+    /// The axes along which the image repeats.
+    pub repeat_mode: ImageRepeatMode,
 }
 
 #[repr(u8)]
@@ -1859,6 +1957,54 @@ pub enum ImageRendering {
     Pixelated = 2,
 }
 
+/// Selects how a repeating image is tiled. `Clamp` lets WebRender repeat
+/// the image itself (in the shader or by decomposing it into tiles). The
+/// other modes draw a single quad and let the texture sampler wrap the
+/// coordinates, which is only possible when the image is in a standalone
+/// texture; atlas-packed images fall back to `Clamp`.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, MallocSizeOf, PartialEq, Serialize, PeekPoke)]
+pub enum ImageWrapMode {
+    Clamp = 0,
+    Repeat = 1,
+    Mirror = 2,
+}
+
+impl Default for ImageWrapMode {
+    fn default() -> Self {
+        ImageWrapMode::Clamp
+    }
+}
+
+/// Selects the axes a repeating image is tiled along, as with CSS
+/// `background-repeat`. Along an axis that doesn't repeat, a single tile
+/// of `stretch_size` is drawn at the origin of the bounds and the rest of
+/// the bounds along that axis is left unpainted.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, MallocSizeOf, PartialEq, Serialize, PeekPoke)]
+pub enum ImageRepeatMode {
+    Both = 0,
+    X = 1,
+    Y = 2,
+    None = 3,
+}
+
+impl ImageRepeatMode {
+    pub fn repeats_x(self) -> bool {
+        matches!(self, ImageRepeatMode::Both | ImageRepeatMode::X)
+    }
+
+    pub fn repeats_y(self) -> bool {
+        matches!(self, ImageRepeatMode::Both | ImageRepeatMode::Y)
+    }
+}
+
+impl Default for ImageRepeatMode {
+    fn default() -> Self {
+        ImageRepeatMode::Both
+    }
+}
+
 #[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, MallocSizeOf, PartialEq, Serialize, PeekPoke)]
 pub enum AlphaType {
     Alpha = 0,
@@ -2234,6 +2380,7 @@ impl DisplayItem {
             DisplayItem::ClipChain(..) => "clip_chain",
             DisplayItem::ConicGradient(..) => "conic_gradient",
             DisplayItem::Gradient(..) => "gradient",
+            DisplayItem::GradientRef(..) => "gradient_ref",
             DisplayItem::Iframe(..) => "iframe",
             DisplayItem::Image(..) => "image",
             DisplayItem::RepeatingImage(..) => "repeating_image",
@@ -2,7 +2,8 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use euclid::SideOffsets2D;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use euclid::{Angle, SideOffsets2D};
 use peek_poke::{ensure_red_zone, peek_from_slice, poke_extend_vec, strip_red_zone};
 use peek_poke::{poke_inplace_slice, poke_into_vec, Poke};
 #[cfg(feature = "deserialize")]
@@ -14,7 +15,7 @@ use std::io::Write;
 use std::marker::PhantomData;
 use std::ops::Range;
 use std::mem;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use time::precise_time_ns;
 use malloc_size_of::{MallocSizeOf, MallocSizeOfOps};
 // local imports
@@ -123,6 +124,11 @@ pub struct DisplayListPayload {
 
     /// Serde encoded SpatialTreeItem structs
     pub spatial_tree: Vec<u8>,
+
+    /// Embedder metadata, see `DisplayListBuilder::set_metadata`. Each entry
+    /// is encoded as its length-prefixed key followed by its length-prefixed
+    /// value.
+    pub metadata: Vec<u8>,
 }
 
 impl DisplayListPayload {
@@ -131,6 +137,7 @@ impl DisplayListPayload {
             items_data: Vec::new(),
             cache_data: Vec::new(),
             spatial_tree: Vec::new(),
+            metadata: Vec::new(),
         }
     }
 
@@ -156,12 +163,55 @@ impl DisplayListPayload {
         self.items_data.clear();
         self.cache_data.clear();
         self.spatial_tree.clear();
+        self.metadata.clear();
     }
 
     fn size_in_bytes(&self) -> usize {
         self.items_data.len() +
         self.cache_data.len() +
-        self.spatial_tree.len()
+        self.spatial_tree.len() +
+        self.metadata.len()
+    }
+
+    fn encode_metadata(metadata: &BTreeMap<String, String>) -> Vec<u8> {
+        let mut data = Vec::new();
+        for (key, value) in metadata {
+            for s in &[key, value] {
+                data.write_u32::<LittleEndian>(s.len() as u32).unwrap();
+                data.extend_from_slice(s.as_bytes());
+            }
+        }
+        data
+    }
+
+    /// Decodes the metadata written by `encode_metadata`. The payload may
+    /// come from another process or a capture, so truncated data stops the
+    /// decoding rather than panicking.
+    fn decode_metadata(&self) -> BTreeMap<String, String> {
+        fn read_string(data: &mut &[u8]) -> Option<String> {
+            let len = data.read_u32::<LittleEndian>().ok()? as usize;
+            if len > data.len() {
+                return None;
+            }
+            let (s, rest) = (*data).split_at(len);
+            *data = rest;
+            Some(String::from_utf8_lossy(s).into_owned())
+        }
+
+        let mut metadata = BTreeMap::new();
+        let mut data = &self.metadata[..];
+        while !data.is_empty() {
+            match (read_string(&mut data), read_string(&mut data)) {
+                (Some(key), Some(value)) => {
+                    metadata.insert(key, value);
+                }
+                _ => {
+                    warn!("Truncated display list metadata");
+                    break;
+                }
+            }
+        }
+        metadata
     }
 
     #[cfg(feature = "serialize")]
@@ -180,7 +230,8 @@ impl MallocSizeOf for DisplayListPayload {
     fn size_of(&self, ops: &mut MallocSizeOfOps) -> usize {
         self.items_data.size_of(ops) +
         self.cache_data.size_of(ops) +
-        self.spatial_tree.size_of(ops)
+        self.spatial_tree.size_of(ops) +
+        self.metadata.size_of(ops)
     }
 }
 
@@ -263,6 +314,10 @@ impl DisplayListWithCache {
     pub fn items_data(&self) -> &[u8] {
         self.display_list.items_data()
     }
+
+    pub fn metadata(&self) -> BTreeMap<String, String> {
+        self.display_list.metadata()
+    }
 }
 
 impl MallocSizeOf for DisplayListWithCache {
@@ -280,6 +335,8 @@ struct DisplayListCapture {
     display_items: Vec<di::DebugDisplayItem>,
     spatial_tree_items: Vec<di::SpatialTreeItem>,
     descriptor: BuiltDisplayListDescriptor,
+    #[serde(default)]
+    metadata: BTreeMap<String, String>,
 }
 
 #[cfg(feature = "serialize")]
@@ -295,6 +352,7 @@ impl Serialize for DisplayListWithCache {
             display_items,
             spatial_tree_items,
             descriptor: self.display_list.descriptor,
+            metadata: self.display_list.metadata(),
         };
 
         dl.serialize(serializer)
@@ -380,6 +438,7 @@ impl<'de> Deserialize<'de> for DisplayListWithCache {
                 Debug::Gradient(v) => Real::Gradient(v),
                 Debug::RadialGradient(v) => Real::RadialGradient(v),
                 Debug::ConicGradient(v) => Real::ConicGradient(v),
+                Debug::GradientRef(v) => Real::GradientRef(v),
                 Debug::PushStackingContext(v) => Real::PushStackingContext(v),
                 Debug::PushShadow(v) => Real::PushShadow(v),
                 Debug::BackdropFilter(v) => Real::BackdropFilter(v),
@@ -405,6 +464,7 @@ impl<'de> Deserialize<'de> for DisplayListWithCache {
                     cache_data: Vec::new(),
                     items_data,
                     spatial_tree,
+                    metadata: DisplayListPayload::encode_metadata(&capture.metadata),
                 },
             },
             cache: DisplayItemCache::new(),
@@ -581,6 +641,12 @@ impl BuiltDisplayList {
         &self.descriptor
     }
 
+    /// The metadata the embedder attached with `DisplayListBuilder::set_metadata`.
+    /// It is part of the payload, so it is kept by `into_data` and `from_data`.
+    pub fn metadata(&self) -> BTreeMap<String, String> {
+        self.payload.decode_metadata()
+    }
+
     pub fn set_send_time_ns(&mut self, time: u64) {
         self.descriptor.send_start_time = time;
     }
@@ -632,6 +698,57 @@ impl BuiltDisplayList {
         self.payload.size_in_bytes()
     }
 
+    /// Returns the number of serialized bytes used by each kind of display
+    /// item, keyed by `DisplayItem::debug_name`. The arrays that follow an
+    /// item, such as the glyphs of a text run, are counted with the item.
+    /// Spatial tree items are counted as "spatial_tree", and the zeroed space
+    /// at the end of each buffer as "padding", so that the sizes add up to
+    /// `size_in_bytes`.
+    pub fn size_breakdown(&self) -> HashMap<&'static str, usize> {
+        let mut breakdown = HashMap::new();
+        let mut add = |name: &'static str, num_bytes: usize| {
+            if num_bytes > 0 {
+                *breakdown.entry(name).or_insert(0) += num_bytes;
+            }
+        };
+
+        for buffer in &[&self.payload.items_data, &self.payload.cache_data] {
+            let mut data = &buffer[..];
+            let mut item = di::DisplayItem::PopStackingContext;
+            while data.len() > di::DisplayItem::max_size() {
+                let start_len = data.len();
+                data = peek_from_slice(data, &mut item);
+
+                let num_slices = match item {
+                    di::DisplayItem::SetFilterData => 5,
+                    di::DisplayItem::SetGradientStops |
+                    di::DisplayItem::SetFilterOps |
+                    di::DisplayItem::SetFilterPrimitives |
+                    di::DisplayItem::SetPoints |
+                    di::DisplayItem::ClipChain(..) |
+                    di::DisplayItem::Text(..) => 1,
+                    _ => 0,
+                };
+                for _ in 0 .. num_slices {
+                    skip_slice::<u8>(&mut data);
+                }
+
+                add(item.debug_name(), start_len - data.len());
+            }
+            add("padding", data.len());
+        }
+
+        let mut data = &self.payload.spatial_tree[..];
+        let mut item = di::SpatialTreeItem::Invalid;
+        while data.len() > di::SpatialTreeItem::max_size() {
+            data = peek_from_slice(data, &mut item);
+        }
+        add("spatial_tree", self.payload.spatial_tree.len() - data.len());
+        add("padding", data.len());
+
+        breakdown
+    }
+
     pub fn iter_spatial_tree<F>(&self, f: F) where F: FnMut(&di::SpatialTreeItem) {
         iter_spatial_tree(&self.payload.spatial_tree, f)
     }
@@ -701,6 +818,7 @@ impl BuiltDisplayList {
                 Real::Gradient(v) => Debug::Gradient(v),
                 Real::RadialGradient(v) => Debug::RadialGradient(v),
                 Real::ConicGradient(v) => Debug::ConicGradient(v),
+                Real::GradientRef(v) => Debug::GradientRef(v),
                 Real::Iframe(v) => Debug::Iframe(v),
                 Real::PushReferenceFrame(v) => Debug::PushReferenceFrame(v),
                 Real::PushStackingContext(v) => Debug::PushStackingContext(v),
@@ -1038,6 +1156,22 @@ pub enum DisplayListSection {
     Chunk,
 }
 
+/// A problem found by `DisplayListBuilder::validate`. `index` is the position
+/// of the offending item in the display list.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DisplayListError {
+    /// The item refers to a spatial node that this builder didn't define.
+    DanglingSpatialId { index: usize, id: di::SpatialId },
+    /// The item refers to a clip that this builder didn't define.
+    DanglingClipId { index: usize, id: di::ClipId },
+    /// The item refers to a clip chain that this builder didn't define.
+    DanglingClipChainId { index: usize, id: di::ClipChainId },
+    /// A rect whose min corner is past its max corner.
+    InvertedRect { index: usize, rect: LayoutRect },
+    /// A rect with NaN or infinite coordinates.
+    NonFiniteRect { index: usize, rect: LayoutRect },
+}
+
 pub struct DisplayListBuilder {
     payload: DisplayListPayload,
     pub pipeline_id: PipelineId,
@@ -1055,6 +1189,7 @@ pub struct DisplayListBuilder {
     cache_size: usize,
     serialized_content_buffer: Option<String>,
     state: BuildState,
+    metadata: BTreeMap<String, String>,
 
     /// Helper struct to map stacking context coords <-> reference frame coords.
     rf_mapper: ReferenceFrameMapper,
@@ -1094,6 +1229,7 @@ impl DisplayListBuilder {
             cache_size: 0,
             serialized_content_buffer: None,
             state: BuildState::Idle,
+            metadata: BTreeMap::new(),
 
             rf_mapper: ReferenceFrameMapper::new(),
         }
@@ -1111,6 +1247,7 @@ impl DisplayListBuilder {
         self.save_state = None;
         self.cache_size = 0;
         self.serialized_content_buffer = None;
+        self.metadata.clear();
 
         self.rf_mapper = ReferenceFrameMapper::new();
     }
@@ -1194,6 +1331,47 @@ impl DisplayListBuilder {
         index
     }
 
+    /// Checks the display items pushed so far for references to spatial
+    /// nodes, clips and clip chains that this builder didn't define, and for
+    /// inverted or non-finite rects, so that embedders can catch mistakes
+    /// before sending the list. This is a CPU-side check and doesn't affect
+    /// the list.
+    pub fn validate(&mut self) -> Result<(), Vec<DisplayListError>> {
+        let mut validator = DisplayListValidator {
+            pipeline_id: self.pipeline_id,
+            spatial_count: self.next_spatial_index,
+            clip_count: self.next_clip_index,
+            clip_chain_count: self.next_clip_chain_id,
+            index: 0,
+            errors: Vec::new(),
+        };
+
+        let mut temp = BuiltDisplayList::default();
+        ensure_red_zone::<di::DisplayItem>(&mut self.payload.items_data);
+        ensure_red_zone::<di::DisplayItem>(&mut self.payload.cache_data);
+        mem::swap(&mut temp.payload, &mut self.payload);
+
+        {
+            let mut cache = DisplayItemCache::new();
+            cache.update(&temp);
+            let mut iter = temp.iter_with_cache(&cache);
+            while let Some(item) = iter.next_raw() {
+                validator.item(&item);
+                validator.index += 1;
+            }
+        }
+
+        self.payload = temp.payload;
+        strip_red_zone::<di::DisplayItem>(&mut self.payload.items_data);
+        strip_red_zone::<di::DisplayItem>(&mut self.payload.cache_data);
+
+        if validator.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(validator.errors)
+        }
+    }
+
     /// Print the display items in the list to stdout.
     pub fn dump_serialized_display_list(&mut self) {
         self.serialized_content_buffer = Some(String::new());
@@ -1346,6 +1524,23 @@ impl DisplayListBuilder {
         self.push_item(&item);
     }
 
+    /// Push a rectangle faded by `opacity`, without the intermediate surface
+    /// that wrapping it in an opacity stacking context would require.
+    ///
+    /// The opacity is folded into the alpha the primitive is drawn with. This
+    /// matches group opacity because a single rectangle never overlaps itself.
+    /// Overlapping items that should fade as a group still need a stacking
+    /// context with an opacity filter.
+    pub fn push_rect_with_opacity(
+        &mut self,
+        common: &di::CommonItemProperties,
+        bounds: LayoutRect,
+        color: ColorF,
+        opacity: f32,
+    ) {
+        self.push_rect(common, bounds, color.scale_alpha(opacity.max(0.0).min(1.0)));
+    }
+
     pub fn push_rect_with_animation(
         &mut self,
         common: &di::CommonItemProperties,
@@ -1362,6 +1557,74 @@ impl DisplayListBuilder {
         self.push_item(&item);
     }
 
+    /// Push a set of rectangles sharing one color, such as the lines of a
+    /// text selection. The rectangles are solid rects, so they are batched
+    /// into a single draw call. If `blend_mode` isn't `Normal`, they're
+    /// wrapped in a stacking context that blends them with the content
+    /// behind as a group.
+    pub fn push_highlight(
+        &mut self,
+        common: &di::CommonItemProperties,
+        rects: &[LayoutRect],
+        color: ColorF,
+        blend_mode: di::MixBlendMode,
+    ) {
+        let needs_group = blend_mode != di::MixBlendMode::Normal;
+        if needs_group {
+            self.push_stacking_context(
+                LayoutPoint::zero(),
+                common.spatial_id,
+                common.flags,
+                None,
+                di::TransformStyle::Flat,
+                blend_mode,
+                &[],
+                &[],
+                &[],
+                di::RasterSpace::Screen,
+                di::StackingContextFlags::empty(),
+            );
+        }
+
+        for rect in rects {
+            self.push_rect(common, *rect, color);
+        }
+
+        if needs_group {
+            self.pop_stacking_context();
+        }
+    }
+
+    /// Push a text caret whose visibility is driven by `blink`, an opacity
+    /// between 0 and 1. Binding it to a dynamic property lets the caret
+    /// blink by updating the property, without rebuilding the display list.
+    pub fn push_caret(
+        &mut self,
+        common: &di::CommonItemProperties,
+        bounds: LayoutRect,
+        color: ColorF,
+        blink: PropertyBinding<f32>,
+    ) {
+        let opacity = match blink {
+            PropertyBinding::Value(value) | PropertyBinding::Binding(_, value) => value,
+        };
+        self.push_stacking_context(
+            LayoutPoint::zero(),
+            common.spatial_id,
+            common.flags,
+            None,
+            di::TransformStyle::Flat,
+            di::MixBlendMode::Normal,
+            &[di::FilterOp::Opacity(blink, opacity)],
+            &[],
+            &[],
+            di::RasterSpace::Screen,
+            di::StackingContextFlags::empty(),
+        );
+        self.push_rect(common, bounds, color);
+        self.pop_stacking_context();
+    }
+
     pub fn push_clear_rect(
         &mut self,
         common: &di::CommonItemProperties,
@@ -1442,6 +1705,29 @@ impl DisplayListBuilder {
         self.push_item(&item);
     }
 
+    /// Push an image faded by `opacity`, without the intermediate surface
+    /// that wrapping it in an opacity stacking context would require. See
+    /// `push_rect_with_opacity` for when this matches group opacity.
+    pub fn push_image_with_opacity(
+        &mut self,
+        common: &di::CommonItemProperties,
+        bounds: LayoutRect,
+        image_rendering: di::ImageRendering,
+        alpha_type: di::AlphaType,
+        key: ImageKey,
+        color: ColorF,
+        opacity: f32,
+    ) {
+        self.push_image(
+            common,
+            bounds,
+            image_rendering,
+            alpha_type,
+            key,
+            color.scale_alpha(opacity.max(0.0).min(1.0)),
+        );
+    }
+
     pub fn push_repeating_image(
         &mut self,
         common: &di::CommonItemProperties,
@@ -1452,6 +1738,88 @@ impl DisplayListBuilder {
         alpha_type: di::AlphaType,
         key: ImageKey,
         color: ColorF,
+    ) {
+        self.push_repeating_image_with_wrap_mode(
+            common,
+            bounds,
+            stretch_size,
+            tile_spacing,
+            image_rendering,
+            alpha_type,
+            key,
+            color,
+            di::ImageWrapMode::Clamp,
+        );
+    }
+
+    /// Push a repeating image whose tiling is done by the texture sampler
+    /// with the given wrap mode. See `ImageWrapMode` for the restrictions.
+    pub fn push_repeating_image_with_wrap_mode(
+        &mut self,
+        common: &di::CommonItemProperties,
+        bounds: LayoutRect,
+        stretch_size: LayoutSize,
+        tile_spacing: LayoutSize,
+        image_rendering: di::ImageRendering,
+        alpha_type: di::AlphaType,
+        key: ImageKey,
+        color: ColorF,
+        wrap_mode: di::ImageWrapMode,
+    ) {
+        self.push_repeating_image_impl(
+            common,
+            bounds,
+            stretch_size,
+            tile_spacing,
+            image_rendering,
+            alpha_type,
+            key,
+            color,
+            wrap_mode,
+            di::ImageRepeatMode::Both,
+        );
+    }
+
+    /// Push an image that only repeats along the axes selected by
+    /// `repeat_mode`. See `ImageRepeatMode` for the behavior along the other axes.
+    pub fn push_repeating_image_with_repeat_mode(
+        &mut self,
+        common: &di::CommonItemProperties,
+        bounds: LayoutRect,
+        stretch_size: LayoutSize,
+        tile_spacing: LayoutSize,
+        image_rendering: di::ImageRendering,
+        alpha_type: di::AlphaType,
+        key: ImageKey,
+        color: ColorF,
+        repeat_mode: di::ImageRepeatMode,
+    ) {
+        self.push_repeating_image_impl(
+            common,
+            bounds,
+            stretch_size,
+            tile_spacing,
+            image_rendering,
+            alpha_type,
+            key,
+            color,
+            di::ImageWrapMode::Clamp,
+            repeat_mode,
+        );
+    }
+
+    fn push_repeating_image_impl(
+        &mut self,
+        common: &di::CommonItemProperties,
+        bounds: LayoutRect,
+        stretch_size: LayoutSize,
+        tile_spacing: LayoutSize,
+        image_rendering: di::ImageRendering,
+        alpha_type: di::AlphaType,
+        key: ImageKey,
+        color: ColorF,
+        wrap_mode: di::ImageWrapMode,
+        repeat_mode: di::ImageRepeatMode,
     ) {
         let (common, bounds) = self.remap_common_coordinates_and_bounds(common, bounds);
 
@@ -1464,6 +1832,8 @@ impl DisplayListBuilder {
             image_rendering,
             alpha_type,
             color,
+            wrap_mode,
+            repeat_mode,
         });
 
         self.push_item(&item);
@@ -1521,6 +1891,105 @@ impl DisplayListBuilder {
         }
     }
 
+    /// Push a run of glyphs laid out along a polyline `path`.
+    ///
+    /// The x coordinate of each glyph's `point` is its distance along the
+    /// path, and the y coordinate is its offset perpendicular to the path
+    /// (positive values being to the right of the direction of travel, which
+    /// matches +y for a horizontal path). Curves should be flattened into
+    /// line segments by the caller. Glyphs must be sorted by distance, and
+    /// any that fall outside the path are dropped.
+    ///
+    /// The glyphs of each path segment share a rotated reference frame and
+    /// are pushed as one text run, rasterized with one of the transformed
+    /// glyph formats. `glyph_bounds` should conservatively cover any single
+    /// glyph relative to its origin. The reference frames use keys derived
+    /// from `key` (see `SpatialTreeItemKey::derive`), one per path segment.
+    pub fn push_text_on_path(
+        &mut self,
+        common: &di::CommonItemProperties,
+        glyph_bounds: LayoutRect,
+        glyphs: &[GlyphInstance],
+        path: &[LayoutPoint],
+        font_key: FontInstanceKey,
+        color: ColorF,
+        glyph_options: Option<GlyphOptions>,
+        key: di::SpatialTreeItemKey,
+    ) {
+        // The item clip rect is specified in the parent space, which the
+        // rotated segment frames can't express, so turn it into a clip.
+        let clip_id = self.define_clip_rect(common.spatial_id, common.clip_rect);
+        let parent = if common.clip_chain_id == di::ClipChainId::INVALID {
+            None
+        } else {
+            Some(common.clip_chain_id)
+        };
+        let clip_chain_id = self.define_clip_chain(parent, [clip_id]);
+
+        let mut glyphs = glyphs.iter().peekable();
+        let mut segment_start = 0.0;
+        let mut run = Vec::new();
+
+        for (i, points) in path.windows(2).enumerate() {
+            let (start, dir) = (points[0], points[1] - points[0]);
+            let length = dir.length();
+            let segment_end = segment_start + length;
+
+            // Collect the glyphs that fall on this segment, relative to its
+            // start, dropping any before the start of the path.
+            run.clear();
+            while let Some(glyph) = glyphs.next_if(|glyph| glyph.point.x < segment_end) {
+                if glyph.point.x >= segment_start {
+                    run.push(GlyphInstance {
+                        index: glyph.index,
+                        point: LayoutPoint::new(glyph.point.x - segment_start, glyph.point.y),
+                    });
+                }
+            }
+            segment_start = segment_end;
+
+            if run.is_empty() {
+                continue;
+            }
+
+            let angle = Angle::radians(dir.y.atan2(dir.x));
+            let spatial_id = self.push_reference_frame(
+                start,
+                common.spatial_id,
+                di::TransformStyle::Flat,
+                PropertyBinding::Value(LayoutTransform::rotation(0.0, 0.0, 1.0, angle)),
+                di::ReferenceFrameKind::Transform {
+                    is_2d_scale_translation: false,
+                    should_snap: false,
+                    paired_with_perspective: false,
+                },
+                key.derive(i as u64),
+            );
+
+            let bounds = run
+                .iter()
+                .map(|glyph| glyph_bounds.translate(glyph.point.to_vector()))
+                .reduce(|a, b| a.union(&b))
+                .unwrap();
+            let run_common = di::CommonItemProperties {
+                clip_rect: bounds,
+                clip_chain_id,
+                spatial_id,
+                flags: common.flags,
+            };
+            self.push_text(
+                &run_common,
+                bounds,
+                &run,
+                font_key,
+                color,
+                glyph_options,
+            );
+
+            self.pop_reference_frame();
+        }
+    }
+
     /// NOTE: gradients must be pushed in the order they're created
     /// because create_gradient stores the stops in anticipation.
     pub fn create_gradient(
@@ -1572,6 +2041,25 @@ impl DisplayListBuilder {
         bounds: LayoutRect,
         widths: LayoutSideOffsets,
         details: di::BorderDetails,
+    ) {
+        self.push_border_with_dash_phase(
+            common,
+            bounds,
+            widths,
+            details,
+            PropertyBinding::Value(0.0),
+        );
+    }
+
+    /// Push a border whose dashed edges have their dash pattern offset by
+    /// `dash_phase`, which may be animated. See `BorderDisplayItem::dash_phase`.
+    pub fn push_border_with_dash_phase(
+        &mut self,
+        common: &di::CommonItemProperties,
+        bounds: LayoutRect,
+        widths: LayoutSideOffsets,
+        details: di::BorderDetails,
+        dash_phase: PropertyBinding<f32>,
     ) {
         let (common, bounds) = self.remap_common_coordinates_and_bounds(common, bounds);
 
@@ -1580,6 +2068,7 @@ impl DisplayListBuilder {
             bounds,
             details,
             widths,
+            dash_phase,
         });
 
         self.push_item(&item);
@@ -1695,6 +2184,28 @@ impl DisplayListBuilder {
         self.push_item(&item);
     }
 
+    /// Pushes a gradient template over `bounds`.
+    ///
+    /// Unlike the other gradients, the stops don't follow the item: the
+    /// template is created beforehand with `RenderApi::create_gradient_template`
+    /// and rendered once for all the items of the same size that reference it.
+    pub fn push_gradient_ref(
+        &mut self,
+        common: &di::CommonItemProperties,
+        bounds: LayoutRect,
+        template: di::GradientTemplateKey,
+    ) {
+        let (common, bounds) = self.remap_common_coordinates_and_bounds(common, bounds);
+
+        let item = di::DisplayItem::GradientRef(di::GradientRefDisplayItem {
+            common,
+            bounds,
+            template,
+        });
+
+        self.push_item(&item);
+    }
+
     pub fn push_reference_frame(
         &mut self,
         origin: LayoutPoint,
@@ -1703,6 +2214,32 @@ impl DisplayListBuilder {
         transform: PropertyBinding<LayoutTransform>,
         kind: di::ReferenceFrameKind,
         key: di::SpatialTreeItemKey,
+    ) -> di::SpatialId {
+        self.push_reference_frame_with_transform_origin(
+            origin,
+            parent_spatial_id,
+            transform_style,
+            transform,
+            kind,
+            key,
+            LayoutPoint::zero(),
+        )
+    }
+
+    /// Push a reference frame whose transform is applied around
+    /// `transform_origin`, like CSS `transform-origin`. WebRender composes
+    /// `translate(transform_origin) * transform * translate(-transform_origin)`,
+    /// including for animated transforms, so the bound value doesn't need to
+    /// account for the origin. `push_reference_frame` uses an origin of zero.
+    pub fn push_reference_frame_with_transform_origin(
+        &mut self,
+        origin: LayoutPoint,
+        parent_spatial_id: di::SpatialId,
+        transform_style: di::TransformStyle,
+        transform: PropertyBinding<LayoutTransform>,
+        kind: di::ReferenceFrameKind,
+        key: di::SpatialTreeItemKey,
+        transform_origin: LayoutPoint,
     ) -> di::SpatialId {
         let id = self.generate_spatial_index();
 
@@ -1717,6 +2254,7 @@ impl DisplayListBuilder {
                 transform: di::ReferenceTransformBinding::Static {
                     binding: transform,
                 },
+                transform_origin,
                 kind,
                 id,
                 key,
@@ -1757,6 +2295,7 @@ impl DisplayListBuilder {
                     vertical_flip,
                     rotation,
                 },
+                transform_origin: LayoutPoint::zero(),
                 kind: di::ReferenceFrameKind::Transform {
                     is_2d_scale_translation: false,
                     should_snap: false,
@@ -2189,6 +2728,15 @@ impl DisplayListBuilder {
         );
     }
 
+    /// Attach a piece of embedder metadata, such as the URL of the content,
+    /// to the display list being built. Metadata doesn't affect rendering, but
+    /// is saved in captures along with the display list. Setting a key again
+    /// replaces its value.
+    pub fn set_metadata(&mut self, key: &str, value: String) {
+        assert_eq!(self.state, BuildState::Build);
+        self.metadata.insert(key.to_string(), value);
+    }
+
     pub fn set_cache_size(&mut self, cache_size: usize) {
         self.cache_size = cache_size;
     }
@@ -2216,6 +2764,9 @@ impl DisplayListBuilder {
         ensure_red_zone::<di::DisplayItem>(&mut self.payload.cache_data);
         ensure_red_zone::<di::SpatialTreeItem>(&mut self.payload.spatial_tree);
 
+        self.payload.metadata = DisplayListPayload::encode_metadata(&self.metadata);
+        self.metadata.clear();
+
         // While the first display list after tab-switch can be large, the
         // following ones are always smaller thanks to interning. We attempt
         // to reserve the same capacity again, although it may fail. Memory
@@ -2252,6 +2803,136 @@ impl DisplayListBuilder {
     }
 }
 
+/// Collects the errors reported by `DisplayListBuilder::validate`.
+struct DisplayListValidator {
+    pipeline_id: PipelineId,
+    spatial_count: usize,
+    clip_count: usize,
+    clip_chain_count: u64,
+    index: usize,
+    errors: Vec<DisplayListError>,
+}
+
+impl DisplayListValidator {
+    fn item(&mut self, item: &DisplayItemRef) {
+        match *item.item() {
+            di::DisplayItem::Rectangle(ref info) => self.bounded(&info.common, info.bounds),
+            di::DisplayItem::ClearRectangle(ref info) => self.bounded(&info.common, info.bounds),
+            di::DisplayItem::Text(ref info) => self.bounded(&info.common, info.bounds),
+            di::DisplayItem::Line(ref info) => self.bounded(&info.common, info.area),
+            di::DisplayItem::Border(ref info) => self.bounded(&info.common, info.bounds),
+            di::DisplayItem::BoxShadow(ref info) => self.bounded(&info.common, info.box_bounds),
+            di::DisplayItem::Gradient(ref info) => self.bounded(&info.common, info.bounds),
+            di::DisplayItem::RadialGradient(ref info) => self.bounded(&info.common, info.bounds),
+            di::DisplayItem::ConicGradient(ref info) => self.bounded(&info.common, info.bounds),
+            di::DisplayItem::GradientRef(ref info) => self.bounded(&info.common, info.bounds),
+            di::DisplayItem::Image(ref info) => self.bounded(&info.common, info.bounds),
+            di::DisplayItem::RepeatingImage(ref info) => self.bounded(&info.common, info.bounds),
+            di::DisplayItem::YuvImage(ref info) => self.bounded(&info.common, info.bounds),
+            di::DisplayItem::BackdropFilter(ref info) => self.common(&info.common),
+            di::DisplayItem::HitTest(ref info) => {
+                self.spatial_id(info.spatial_id);
+                self.clip_chain_id(info.clip_chain_id);
+                self.rect(info.rect);
+            }
+            di::DisplayItem::PushShadow(ref info) => self.space_and_clip(&info.space_and_clip),
+            di::DisplayItem::RectClip(ref info) => {
+                self.clip_id(info.id);
+                self.spatial_id(info.spatial_id);
+                self.rect(info.clip_rect);
+            }
+            di::DisplayItem::RoundedRectClip(ref info) => {
+                self.clip_id(info.id);
+                self.spatial_id(info.spatial_id);
+                self.rect(info.clip.rect);
+            }
+            di::DisplayItem::ImageMaskClip(ref info) => {
+                self.clip_id(info.id);
+                self.spatial_id(info.spatial_id);
+                self.rect(info.image_mask.rect);
+            }
+            di::DisplayItem::ClipChain(ref info) => {
+                self.clip_chain_id(info.id);
+                if let Some(parent) = info.parent {
+                    self.clip_chain_id(parent);
+                }
+                for clip_id in item.clip_chain_items().iter() {
+                    self.clip_id(clip_id);
+                }
+            }
+            di::DisplayItem::Iframe(ref info) => {
+                self.space_and_clip(&info.space_and_clip);
+                self.rect(info.bounds);
+                self.rect(info.clip_rect);
+            }
+            di::DisplayItem::PushStackingContext(ref info) => {
+                self.spatial_id(info.spatial_id);
+                if let Some(clip_chain_id) = info.stacking_context.clip_chain_id {
+                    self.clip_chain_id(clip_chain_id);
+                }
+            }
+            di::DisplayItem::PushReferenceFrame(..) |
+            di::DisplayItem::SetGradientStops |
+            di::DisplayItem::SetFilterOps |
+            di::DisplayItem::SetFilterData |
+            di::DisplayItem::SetFilterPrimitives |
+            di::DisplayItem::SetPoints |
+            di::DisplayItem::PopReferenceFrame |
+            di::DisplayItem::PopStackingContext |
+            di::DisplayItem::PopAllShadows |
+            di::DisplayItem::ReuseItems(..) |
+            di::DisplayItem::RetainedItems(..) => {}
+        }
+    }
+
+    fn bounded(&mut self, common: &di::CommonItemProperties, bounds: LayoutRect) {
+        self.common(common);
+        self.rect(bounds);
+    }
+
+    fn common(&mut self, common: &di::CommonItemProperties) {
+        self.spatial_id(common.spatial_id);
+        self.clip_chain_id(common.clip_chain_id);
+        self.rect(common.clip_rect);
+    }
+
+    fn space_and_clip(&mut self, space_and_clip: &di::SpaceAndClipInfo) {
+        self.spatial_id(space_and_clip.spatial_id);
+        self.clip_chain_id(space_and_clip.clip_chain_id);
+    }
+
+    fn spatial_id(&mut self, id: di::SpatialId) {
+        if id.pipeline_id() != self.pipeline_id || id.0 >= self.spatial_count {
+            self.errors.push(DisplayListError::DanglingSpatialId { index: self.index, id });
+        }
+    }
+
+    fn clip_id(&mut self, id: di::ClipId) {
+        if id.pipeline_id() != self.pipeline_id || id.0 >= self.clip_count {
+            self.errors.push(DisplayListError::DanglingClipId { index: self.index, id });
+        }
+    }
+
+    fn clip_chain_id(&mut self, id: di::ClipChainId) {
+        // The invalid id means the item has no clip chain.
+        if id == di::ClipChainId::INVALID {
+            return;
+        }
+        if id.1 != self.pipeline_id || id.0 >= self.clip_chain_count {
+            self.errors.push(DisplayListError::DanglingClipChainId { index: self.index, id });
+        }
+    }
+
+    fn rect(&mut self, rect: LayoutRect) {
+        let coords = [rect.min.x, rect.min.y, rect.max.x, rect.max.y];
+        if coords.iter().any(|coord| !coord.is_finite()) {
+            self.errors.push(DisplayListError::NonFiniteRect { index: self.index, rect });
+        } else if rect.min.x > rect.max.x || rect.min.y > rect.max.y {
+            self.errors.push(DisplayListError::InvertedRect { index: self.index, rect });
+        }
+    }
+}
+
 fn iter_spatial_tree<F>(spatial_tree: &[u8], mut f: F) where F: FnMut(&di::SpatialTreeItem) {
     let mut src = spatial_tree;
     let mut item = di::SpatialTreeItem::Invalid;
@@ -2335,3 +3016,31 @@ impl ReferenceFrameMapper {
         *self.frames.last().unwrap().offsets.last().unwrap()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::DisplayListPayload;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn truncated_metadata() {
+        let mut metadata = BTreeMap::new();
+        metadata.insert("a".to_string(), "first".to_string());
+        metadata.insert("b".to_string(), "second".to_string());
+
+        let mut payload = DisplayListPayload::default();
+        payload.metadata = DisplayListPayload::encode_metadata(&metadata);
+        assert_eq!(payload.decode_metadata(), metadata);
+
+        // Cut off the last value, only the first entry survives.
+        let len = payload.metadata.len();
+        payload.metadata.truncate(len - 2);
+        let decoded = payload.decode_metadata();
+        assert_eq!(decoded.len(), 1);
+        assert_eq!(decoded["a"], "first");
+
+        // A length prefix that is itself cut short.
+        payload.metadata.truncate(2);
+        assert!(payload.decode_metadata().is_empty());
+    }
+}
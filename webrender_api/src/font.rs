@@ -9,7 +9,7 @@ use std::hash::{Hash, Hasher};
 use std::path::PathBuf;
 use std::sync::Arc;
 // local imports
-use crate::IdNamespace;
+use crate::{ColorF, ColorU, IdNamespace};
 use crate::channel::Sender;
 use crate::units::LayoutPoint;
 
@@ -199,6 +199,15 @@ bitflags! {
         const VERTICAL          = 1 << 8;
         // Explicitly use multi-strike bold emulation.
         const MULTISTRIKE_BOLD  = 1 << 9;
+        // Fall back to grayscale AA unless the text run is known to be drawn
+        // over an opaque background, i.e. covered by an opaque backdrop or
+        // background color of its picture cache slice, and the slice itself is
+        // composited without opacity.
+        const NO_SUBPIXEL_OVER_TRANSPARENT = 1 << 10;
+        // Disable hinting and subpixel positioning, and round glyph advances
+        // to whole pixels, so that glyph placement is the same on every
+        // platform even if the rasterized glyphs differ. Meant for tests.
+        const DETERMINISTIC_POSITIONING = 1 << 11;
 
         // Internal flags
         const TRANSFORM_GLYPHS  = 1 << 12;
@@ -294,6 +303,48 @@ impl Default for SyntheticItalics {
     }
 }
 
+/// An outline drawn around each glyph of a font instance.
+///
+/// The width is stored in 8.8 fixed-point so that the stroke can be hashed
+/// and compared as part of the font instance options. A zero width means
+/// the glyphs aren't stroked.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Deserialize, Hash, Eq, MallocSizeOf, PartialEq, PartialOrd, Ord, Serialize)]
+pub struct FontStroke {
+    /// Width of the outline in layout pixels, in 8.8 fixed-point.
+    pub width: u16,
+    pub color: ColorU,
+}
+
+impl FontStroke {
+    pub const WIDTH_SCALE: f32 = 256.0;
+
+    /// No outline.
+    pub const NONE: FontStroke = FontStroke {
+        width: 0,
+        color: ColorU { r: 0, g: 0, b: 0, a: 0 },
+    };
+
+    pub fn new(width: f32, color: ColorF) -> Self {
+        FontStroke {
+            width: (width.max(0.0).min(u16::MAX as f32 / Self::WIDTH_SCALE) * Self::WIDTH_SCALE) as u16,
+            color: color.into(),
+        }
+    }
+
+    pub fn width(self) -> f32 {
+        self.width as f32 / Self::WIDTH_SCALE
+    }
+
+    pub fn color(self) -> ColorF {
+        self.color.into()
+    }
+
+    pub fn is_none(self) -> bool {
+        self.width == 0
+    }
+}
+
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Deserialize, Hash, Eq, MallocSizeOf, PartialEq, PartialOrd, Ord, Serialize)]
 pub struct FontInstanceOptions {
@@ -305,6 +356,17 @@ pub struct FontInstanceOptions {
     // ParamTraits_TiedFields.
     // The sizeof(T) must be equal to the sum of the sizeof each field in T.
     pub _padding: u8,
+    /// An outline of the given width and color drawn around the filled
+    /// glyph. `FontStroke::NONE` if the glyphs aren't stroked.
+    pub stroke: FontStroke,
+    /// Gamma used to correct glyph coverage, in percent.
+    /// `FontInstanceOptions::DEFAULT_GAMMA` uses the platform default.
+    pub gamma: u16,
+    /// Contrast enhancement applied to glyph coverage, in percent.
+    /// `FontInstanceOptions::DEFAULT_CONTRAST` uses the platform default.
+    pub contrast: u8,
+    pub _padding2: u8,
+    pub _padding3: u16,
 }
 
 impl Default for FontInstanceOptions {
@@ -314,6 +376,45 @@ impl Default for FontInstanceOptions {
             flags: Default::default(),
             synthetic_italics: SyntheticItalics::disabled(),
             _padding: 0,
+            stroke: FontStroke::NONE,
+            gamma: FontInstanceOptions::DEFAULT_GAMMA,
+            contrast: FontInstanceOptions::DEFAULT_CONTRAST,
+            _padding2: 0,
+            _padding3: 0,
+        }
+    }
+}
+
+impl FontInstanceOptions {
+    /// Sentinel for `gamma` that selects the platform default.
+    pub const DEFAULT_GAMMA: u16 = u16::MAX;
+    /// Sentinel for `contrast` that selects the platform default.
+    pub const DEFAULT_CONTRAST: u8 = u8::MAX;
+
+    /// Returns the gamma override in percent, if any.
+    pub fn gamma(&self) -> Option<u16> {
+        if self.gamma == Self::DEFAULT_GAMMA {
+            None
+        } else {
+            Some(self.gamma)
+        }
+    }
+
+    /// Returns the contrast override in percent, if any.
+    pub fn contrast(&self) -> Option<u8> {
+        if self.contrast == Self::DEFAULT_CONTRAST {
+            None
+        } else {
+            Some(self.contrast)
+        }
+    }
+
+    /// Returns the outline to draw around the glyphs, if any.
+    pub fn stroke(&self) -> Option<FontStroke> {
+        if self.stroke.is_none() {
+            None
+        } else {
+            Some(self.stroke)
         }
     }
 }
@@ -247,6 +247,12 @@ bitflags! {
         ///
         /// See https://github.com/servo/webrender/pull/2555/
         const ALLOW_MIPMAPS = 2;
+        /// Whether to sample the image with anisotropic filtering, which
+        /// reduces aliasing when it's drawn at an oblique angle. Only takes
+        /// effect for images that get mipmaps (see `ALLOW_MIPMAPS`), which
+        /// are those too large for the shared texture cache that are given
+        /// a standalone texture, and when the device supports it.
+        const ANISOTROPIC_FILTERING = 4;
     }
 }
 
@@ -317,6 +323,11 @@ impl ImageDescriptor {
     pub fn allow_mipmaps(&self) -> bool {
         self.flags.contains(ImageDescriptorFlags::ALLOW_MIPMAPS)
     }
+
+    /// Returns true if this descriptor requests anisotropic filtering
+    pub fn anisotropic_filtering(&self) -> bool {
+        self.flags.contains(ImageDescriptorFlags::ANISOTROPIC_FILTERING)
+    }
 }
 
 /// Represents the backing store of an arbitrary series of pixels for display by
@@ -586,3 +597,87 @@ pub struct BlobImageRequest {
     /// Tiling offset in number of tiles.
     pub tile: TileOffset,
 }
+
+/// Premultiplies 8-bit color data by its alpha in place. The data is four
+/// bytes per pixel, with alpha last (e.g. RGBA8 or BGRA8). The result is
+/// rounded to the nearest value, like when the shaders write premultiplied
+/// colors to an 8-bit target.
+pub fn premultiply_rgba8(data: &mut [u8]) {
+    for pixel in data.chunks_exact_mut(4) {
+        let a = pixel[3] as u32;
+        for c in &mut pixel[0 .. 3] {
+            *c = ((*c as u32 * a + 127) / 255) as u8;
+        }
+    }
+}
+
+/// The inverse of `premultiply_rgba8`. Pixels with zero alpha become
+/// transparent black, and colors greater than alpha are clamped.
+pub fn unpremultiply_rgba8(data: &mut [u8]) {
+    for pixel in data.chunks_exact_mut(4) {
+        let a = pixel[3] as u32;
+        for c in &mut pixel[0 .. 3] {
+            *c = if a == 0 {
+                0
+            } else {
+                ((*c as u32 * 255 + a / 2) / a).min(255) as u8
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{premultiply_rgba8, unpremultiply_rgba8};
+
+    #[test]
+    fn premultiply() {
+        let mut data = [
+            255, 255, 255, 255,
+            255, 128, 0, 128,
+            200, 100, 50, 0,
+            // 1 * 127 / 255 is just under a half.
+            255, 127, 1, 127,
+        ];
+        premultiply_rgba8(&mut data);
+        assert_eq!(data, [
+            255, 255, 255, 255,
+            128, 64, 0, 128,
+            0, 0, 0, 0,
+            127, 63, 0, 127,
+        ]);
+    }
+
+    #[test]
+    fn unpremultiply() {
+        let mut data = [
+            128, 64, 0, 128,
+            10, 20, 30, 0,
+            // Invalid, the color is greater than alpha.
+            200, 50, 0, 100,
+        ];
+        unpremultiply_rgba8(&mut data);
+        assert_eq!(data, [
+            255, 128, 0, 128,
+            0, 0, 0, 0,
+            255, 128, 0, 100,
+        ]);
+    }
+
+    #[test]
+    fn round_trip() {
+        for a in 1 ..= 255u8 {
+            // Premultiplying loses precision at low alpha, by up to half a
+            // step of 255 / alpha either way.
+            let max_error = 127.5 / a as f32 + 0.5;
+            for c in 0 ..= 255u8 {
+                let mut data = [c, c, c, a];
+                premultiply_rgba8(&mut data);
+                unpremultiply_rgba8(&mut data);
+                assert_eq!(data[3], a);
+                let error = (data[0] as f32 - c as f32).abs();
+                assert!(error <= max_error, "{} became {} at alpha {}", c, data[0], a);
+            }
+        }
+    }
+}
@@ -25,6 +25,8 @@ extern crate byteorder;
 #[cfg(feature = "nightly")]
 extern crate core;
 #[macro_use]
+extern crate log;
+#[macro_use]
 extern crate malloc_size_of_derive;
 extern crate serde;
 #[macro_use]
@@ -76,6 +78,8 @@ pub struct QualitySettings {
     /// opaque background, which enables subpixel AA. However, it is
     /// usually significantly more expensive to render when scrolling.
     pub force_subpixel_aa_where_possible: bool,
+    /// The anti-aliasing quality of rounded-rect clip masks.
+    pub clip_aa_quality: ClipAaQuality,
 }
 
 impl Default for QualitySettings {
@@ -84,10 +88,22 @@ impl Default for QualitySettings {
             // Prefer performance over maximum subpixel AA quality, since WR
             // already enables subpixel AA in more situations than other browsers.
             force_subpixel_aa_where_possible: false,
+            clip_aa_quality: ClipAaQuality::Standard,
         }
     }
 }
 
+/// Anti-aliasing quality of the edges of rounded-rect clip masks.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ClipAaQuality {
+    /// A single analytic coverage sample per pixel.
+    Standard,
+    /// Four coverage samples per pixel, which gives smoother edges on very
+    /// large radii. This roughly quadruples the fragment shading cost of the
+    /// rounded-rect clip masks, and disables the span fast path on SWGL.
+    High,
+}
+
 /// An epoch identifies the state of a pipeline in time.
 ///
 /// This is mostly used as a synchronization mechanism to observe how/when particular pipeline
@@ -369,6 +385,12 @@ pub struct HitTestResultItem {
 
     /// The animation id from the stacking context.
     pub animation_id: u64,
+
+    /// The closest scroll frame enclosing the hit display item. Items in the
+    /// root scroll frame of a pipeline (`SpatialId::root_scroll_node`)
+    /// report `ExternalScrollId(0, pipeline)`, and items attached to a
+    /// reference frame outside of any scroll frame report `None`.
+    pub scroll_id: Option<ExternalScrollId>,
 }
 
 /// Returned by `RenderApi::hit_test`.
@@ -722,6 +744,8 @@ bitflags! {
         const SMART_PROFILER        = 1 << 22;
         /// If set, dump picture cache invalidation debug to console.
         const INVALIDATION_DBG = 1 << 23;
+        /// Draw a grid over the framebuffer, see `Renderer::set_debug_grid_spacing`.
+        const LAYOUT_GRID_DBG = 1 << 24;
         /// Collect and dump profiler statistics to captures.
         const PROFILER_CAPTURE = (1 as u32) << 25; // need "as u32" until we have cbindgen#556
         /// Invalidate picture tiles every frames (useful when inspecting GPU work in external tools).
@@ -733,6 +757,14 @@ bitflags! {
         const RESTRICT_BLOB_SIZE        = 1 << 28;
         /// Enable surface promotion logging.
         const SURFACE_PROMOTION_LOGGING = 1 << 29;
+        /// Record the GL calls issued for each frame to a file. Requires the
+        /// `gl_trace` feature.
+        const TRACE_GL_CALLS = 1 << 30;
+        /// Experimental: draw alpha batches into the depth buffer before the
+        /// alpha pass, then only shade the front-most alpha fragment of each
+        /// pixel. This is a research aid for order-independent transparency and
+        /// renders overlapping translucent content incorrectly on purpose.
+        const ALPHA_DEPTH_PREPASS = (1 as u32) << 31;
     }
 }
 
@@ -770,6 +802,43 @@ pub enum ScrollLocation {
     End,
 }
 
+/// How scroll offsets that land outside of a scroll frame's scrollable range
+/// are treated by the render backend.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum OverscrollMode {
+    /// Offsets are clamped to the scrollable range of the scroll frame. The
+    /// rubber-band setting is ignored.
+    Clamp,
+    /// Offsets outside of the scrollable range are allowed, either as-is or
+    /// damped when rubber-banding is enabled.
+    Allow,
+}
+
+/// Per-document configuration applied to scroll offsets set through
+/// `Transaction::set_scroll_offsets`.
+///
+/// The offsets are interpreted in terms of the total scroll position of a
+/// scroll frame (including any pre-scrolled amount from the display list), so
+/// clamping is relative to the frame's full scrollable range.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ScrollConfig {
+    /// Whether scroll offsets outside the scrollable range are clamped or
+    /// allowed, see `OverscrollMode`.
+    pub overscroll: OverscrollMode,
+    /// When overscroll is allowed, damp the distance scrolled past the edge
+    /// so that it approaches, but never exceeds, a fraction of the viewport.
+    pub rubber_band: bool,
+}
+
+impl Default for ScrollConfig {
+    fn default() -> Self {
+        ScrollConfig {
+            overscroll: OverscrollMode::Allow,
+            rubber_band: false,
+        }
+    }
+}
+
 /// Crash annotations included in crash reports.
 #[repr(C)]
 #[derive(Clone, Copy)]
@@ -32,6 +32,11 @@ use std::sync::Arc;
 
 const INITIAL_CG_CONTEXT_SIDE_LENGTH: u32 = 32;
 
+// Gamma and contrast, in percent, used unless the font instance overrides
+// them. A gamma of zero selects the sRGB transfer function.
+const DEFAULT_GAMMA: u16 = 0;
+const DEFAULT_CONTRAST: u8 = 0;
+
 pub struct FontContext {
     ct_font_descs: FastHashMap<FontKey, CTFontDescriptor>,
     // Table mapping a sized font key with variations to its instantiated CoreText font.
@@ -40,6 +45,9 @@ pub struct FontContext {
     graphics_context: GraphicsContext,
     #[allow(dead_code)]
     gamma_lut: GammaLut,
+    // Gamma tables for font instances that override the gamma or contrast,
+    // keyed by (gamma, contrast) in percent.
+    gamma_luts: FastHashMap<(u16, u8), GammaLut>,
 }
 
 // core text is safe to use on multiple threads and non-shareable resources are
@@ -241,14 +249,15 @@ impl FontContext {
         debug!("Test for subpixel AA support: {:?}", *FONT_SMOOTHING_MODE);
 
         // Force CG to use sRGB color space to gamma correct.
-        let contrast = 0.0;
-        let gamma = 0.0;
+        let contrast = DEFAULT_CONTRAST as f32 / 100.0;
+        let gamma = DEFAULT_GAMMA as f32 / 100.0;
 
         FontContext {
             ct_font_descs: FastHashMap::default(),
             ct_fonts: FastHashMap::default(),
             graphics_context: GraphicsContext::new(),
             gamma_lut: GammaLut::new(contrast, gamma, gamma),
+            gamma_luts: FastHashMap::default(),
         }
     }
 
@@ -445,18 +454,34 @@ impl FontContext {
 
     // Assumes the pixels here are linear values from CG
     fn gamma_correct_pixels(
-        &self,
+        &mut self,
         pixels: &mut Vec<u8>,
         render_mode: FontRenderMode,
         color: ColorU,
+        gamma: Option<u16>,
+        contrast: Option<u8>,
     ) {
+        let gamma_lut = if gamma.is_none() && contrast.is_none() {
+            &self.gamma_lut
+        } else {
+            let gamma = gamma.unwrap_or(DEFAULT_GAMMA);
+            let contrast = contrast.unwrap_or(DEFAULT_CONTRAST);
+            &*self.gamma_luts
+                .entry((gamma, contrast))
+                .or_insert_with(||
+                    GammaLut::new(
+                        contrast as f32 / 100.0,
+                        gamma as f32 / 100.0,
+                        gamma as f32 / 100.0,
+                    ))
+        };
         // Then convert back to gamma corrected values.
         match render_mode {
             FontRenderMode::Alpha => {
-                self.gamma_lut.preblend_grayscale(pixels, color);
+                gamma_lut.preblend_grayscale(pixels, color);
             }
             FontRenderMode::Subpixel => {
-                self.gamma_lut.preblend(pixels, color);
+                gamma_lut.preblend(pixels, color);
             }
             _ => {} // Again, give mono untouched since only the alpha matters.
         }
@@ -783,6 +808,8 @@ impl FontContext {
                     &mut rasterized_pixels,
                     font.render_mode,
                     font.color,
+                    font.gamma(),
+                    font.contrast(),
                 );
             }
         }
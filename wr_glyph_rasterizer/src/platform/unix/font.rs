@@ -20,6 +20,7 @@ use freetype::freetype::{FT_LOAD_NO_BITMAP, FT_LOAD_NO_HINTING};
 use freetype::freetype::{FT_FACE_FLAG_SCALABLE, FT_FACE_FLAG_FIXED_SIZES};
 use freetype::freetype::{FT_FACE_FLAG_MULTIPLE_MASTERS};
 use freetype::succeeded;
+use crate::gamma_lut::GammaLut;
 use crate::rasterizer::{FontInstance, GlyphFormat, GlyphKey};
 use crate::rasterizer::{GlyphRasterError, GlyphRasterResult, RasterizedGlyph};
 use crate::types::FastHashMap;
@@ -280,6 +281,9 @@ lazy_static! {
 
 pub struct FontContext {
     fonts: FastHashMap<FontKey, Arc<Mutex<CachedFont>>>,
+    // Gamma tables for font instances that request gamma correction,
+    // keyed by (gamma, contrast) in percent.
+    gamma_luts: FastHashMap<(u16, u8), GammaLut>,
 }
 
 fn get_skew_bounds(bottom: i32, top: i32, skew_factor: f32, _vertical: bool) -> (f32, f32) {
@@ -378,6 +382,7 @@ impl FontContext {
     pub fn new() -> FontContext {
         FontContext {
             fonts: FastHashMap::default(),
+            gamma_luts: FastHashMap::default(),
         }
     }
 
@@ -447,8 +452,10 @@ impl FontContext {
 
         let mut load_flags = FT_LOAD_DEFAULT;
         let FontInstancePlatformOptions { mut hinting, .. } = font.platform_options.unwrap_or_default();
-        // Disable hinting if there is a non-axis-aligned transform.
+        // Disable hinting if there is a non-axis-aligned transform, or if the
+        // glyph metrics need to be the same on every platform.
         if font.synthetic_italics.is_enabled() ||
+           font.flags.contains(FontInstanceFlags::DETERMINISTIC_POSITIONING) ||
            ((font.transform.scale_x != 0.0 || font.transform.scale_y != 0.0) &&
             (font.transform.skew_x != 0.0 || font.transform.skew_y != 0.0)) {
             hinting = FontHinting::None;
@@ -881,9 +888,32 @@ impl FontContext {
         }
     }
 
+    // FreeType produces linear coverage, so gamma correction is only applied
+    // if the font instance explicitly asks for it.
+    fn gamma_correct_pixels(&mut self, pixels: &mut [u8], font: &FontInstance) {
+        if font.gamma().is_none() && font.contrast().is_none() {
+            return;
+        }
+        let gamma = font.gamma().unwrap_or(100);
+        let contrast = font.contrast().unwrap_or(0);
+        let gamma_lut = self.gamma_luts
+            .entry((gamma, contrast))
+            .or_insert_with(||
+                GammaLut::new(
+                    contrast as f32 / 100.0,
+                    gamma as f32 / 100.0,
+                    gamma as f32 / 100.0,
+                ));
+        match font.render_mode {
+            FontRenderMode::Alpha => gamma_lut.preblend_grayscale(pixels, font.color),
+            FontRenderMode::Subpixel => gamma_lut.preblend(pixels, font.color),
+            FontRenderMode::Mono => {}
+        }
+    }
+
     pub fn rasterize_glyph(&mut self, font: &FontInstance, key: &GlyphKey) -> GlyphRasterResult {
-        let (_cached, slot, scale) = self.load_glyph(font, key)
-                                         .ok_or(GlyphRasterError::LoadFailed)?;
+        let (cached, slot, scale) = self.load_glyph(font, key)
+                                        .ok_or(GlyphRasterError::LoadFailed)?;
 
         // Get dimensions of the glyph, to see if we need to rasterize it.
         // Don't apply scaling to the dimensions, as the glyph cache needs to know the actual
@@ -1085,6 +1115,18 @@ impl FontContext {
             _ => font.get_alpha_glyph_format(),
         };
 
+        // The glyph slot is no longer needed, so release the font before
+        // touching the gamma tables.
+        drop(cached);
+        match pixel_mode {
+            FT_Pixel_Mode::FT_PIXEL_MODE_GRAY |
+            FT_Pixel_Mode::FT_PIXEL_MODE_LCD |
+            FT_Pixel_Mode::FT_PIXEL_MODE_LCD_V => {
+                self.gamma_correct_pixels(&mut final_buffer, font);
+            }
+            _ => {}
+        }
+
         Ok(RasterizedGlyph {
             left: left as f32,
             top: top as f32,
@@ -93,7 +93,12 @@ fn dwrite_measure_mode(
     font: &FontInstance,
     bitmaps: bool,
 ) -> dwrote::DWRITE_MEASURING_MODE {
-    if bitmaps || font.flags.contains(FontInstanceFlags::FORCE_GDI) {
+    if bitmaps {
+        dwrote::DWRITE_MEASURING_MODE_GDI_CLASSIC
+    } else if font.flags.contains(FontInstanceFlags::DETERMINISTIC_POSITIONING) {
+        // Natural mode uses the unhinted metrics.
+        dwrote::DWRITE_MEASURING_MODE_NATURAL
+    } else if font.flags.contains(FontInstanceFlags::FORCE_GDI) {
         dwrote::DWRITE_MEASURING_MODE_GDI_CLASSIC
     } else {
       match font.render_mode {
@@ -603,6 +608,10 @@ impl FontContext {
 
         let FontInstancePlatformOptions { gamma, contrast, cleartype_level, .. } =
             font.platform_options.unwrap_or_default();
+        // Explicit font instance options take precedence over the platform
+        // rendering parameters.
+        let gamma = font.gamma().unwrap_or(gamma);
+        let contrast = font.contrast().unwrap_or(contrast);
         let gamma_lut = self.gamma_luts
             .entry((gamma, contrast))
             .or_insert_with(||
@@ -111,6 +111,10 @@ impl GlyphRasterizer {
         self.enable_multithreading = enable;
     }
 
+    pub fn can_use_r8_format(&self) -> bool {
+        self.can_use_r8_format
+    }
+
     /// Internal method to flush a list of glyph requests to a set of worker threads,
     /// or process on this thread if there isn't much work to do (in which case the
     /// overhead of processing these on a thread is unlikely to be a performance win).
@@ -788,6 +792,11 @@ impl FontInstanceMap {
                   flags: instance.flags,
                   synthetic_italics: instance.synthetic_italics,
                   _padding: 0,
+                  stroke: instance.stroke,
+                  gamma: instance.gamma,
+                  contrast: instance.contrast,
+                  _padding2: 0,
+                  _padding3: 0,
                 }),
                 platform_options: instance.platform_options,
                 variations: instance.variations.clone(),
@@ -969,6 +978,7 @@ impl FontInstance {
 
     pub fn use_subpixel_position(&self) -> bool {
         self.flags.contains(FontInstanceFlags::SUBPIXEL_POSITION) &&
+        !self.flags.contains(FontInstanceFlags::DETERMINISTIC_POSITIONING) &&
         self.render_mode != FontRenderMode::Mono
     }
 
@@ -1024,6 +1034,16 @@ impl FontInstance {
         let (_, y_scale) = self.transform.compute_scale().unwrap_or((1.0, 1.0));
         self.size.to_f64_px() * y_scale
     }
+
+    /// Returns the stroke width in device pixels, if the font is stroked.
+    pub fn get_stroke_width(&self) -> Option<f64> {
+        let stroke = self.stroke()?;
+        let base_size = self.base.size.to_f64_px();
+        if base_size <= 0.0 {
+            return None;
+        }
+        Some(stroke.width() as f64 * self.get_transformed_size() / base_size)
+    }
 }
 
 #[repr(u32)]
@@ -1127,7 +1147,7 @@ impl GlyphKey {
         self.0 & 0x0FFFFFFF
     }
 
-    fn subpixel_offset(&self) -> (SubpixelOffset, SubpixelOffset) {
+    pub fn subpixel_offset(&self) -> (SubpixelOffset, SubpixelOffset) {
         let x = (self.0 >> 28) as u8 & 3;
         let y = (self.0 >> 30) as u8 & 3;
         unsafe {
@@ -1375,6 +1395,97 @@ impl RasterizedGlyph {
         self.scale = new_scale;
         self.bytes = new_bytes;
     }
+
+    /// Composites the glyph over an outline of its coverage, dilated by the
+    /// font's stroke width, producing a premultiplied color bitmap that holds
+    /// both the fill (in the text color) and the stroke (in the stroke color).
+    pub fn apply_stroke_if_required(&mut self, font: &FontInstance) {
+        let stroke = match font.stroke() {
+            Some(stroke) => stroke,
+            None => return,
+        };
+        // Color glyphs already carry their own colors, so leave them as is.
+        if self.format == GlyphFormat::ColorBitmap {
+            return;
+        }
+        let radius = match font.get_stroke_width() {
+            Some(width) if self.scale > 0.0 => width / self.scale as f64,
+            _ => return,
+        };
+        let extent = radius.ceil() as i32;
+        if extent <= 0 {
+            return;
+        }
+
+        let src_width = self.width as usize;
+        let src_height = self.height as usize;
+        // Use the strongest component as the coverage so that this works for
+        // both alpha and subpixel masks.
+        let coverage: Vec<u32> = self.bytes
+            .chunks(4)
+            .map(|px| px[0].max(px[1]).max(px[2]).max(px[3]) as u32)
+            .collect();
+        let src_coverage = |x: i32, y: i32| -> u32 {
+            if x < 0 || y < 0 || x >= src_width as i32 || y >= src_height as i32 {
+                0
+            } else {
+                coverage[y as usize * src_width + x as usize]
+            }
+        };
+
+        // Precompute the weight of each offset within the stroke radius,
+        // antialiasing the edge of the disc over one pixel.
+        let mut kernel = Vec::new();
+        for dy in -extent ..= extent {
+            for dx in -extent ..= extent {
+                let dist = ((dx * dx + dy * dy) as f64).sqrt();
+                let weight = (radius + 0.5 - dist).max(0.0).min(1.0);
+                if weight > 0.0 {
+                    kernel.push((dx, dy, (weight * 256.0) as u32));
+                }
+            }
+        }
+
+        let fill = [font.color.b as u32, font.color.g as u32, font.color.r as u32];
+        let stroke_color = stroke.color;
+        let stroke_alpha = stroke_color.a as u32;
+        let stroke_premul = [
+            stroke_color.b as u32 * stroke_alpha / 255,
+            stroke_color.g as u32 * stroke_alpha / 255,
+            stroke_color.r as u32 * stroke_alpha / 255,
+            stroke_alpha,
+        ];
+
+        let new_width = src_width + 2 * extent as usize;
+        let new_height = src_height + 2 * extent as usize;
+        let mut new_bytes = Vec::with_capacity(new_width * new_height * 4);
+        for y in 0 .. new_height as i32 {
+            for x in 0 .. new_width as i32 {
+                let (sx, sy) = (x - extent, y - extent);
+                let outline = kernel
+                    .iter()
+                    .map(|&(dx, dy, weight)| (src_coverage(sx + dx, sy + dy) * weight) >> 8)
+                    .max()
+                    .unwrap_or(0);
+                let fill_alpha = src_coverage(sx, sy);
+                // Blend the fill over the outline, both premultiplied.
+                let under = outline * (255 - fill_alpha) / 255;
+                new_bytes.extend_from_slice(&[
+                    ((fill[0] * fill_alpha + stroke_premul[0] * under) / 255) as u8,
+                    ((fill[1] * fill_alpha + stroke_premul[1] * under) / 255) as u8,
+                    ((fill[2] * fill_alpha + stroke_premul[2] * under) / 255) as u8,
+                    ((255 * fill_alpha + stroke_premul[3] * under) / 255) as u8,
+                ]);
+            }
+        }
+
+        self.left -= extent as f32;
+        self.top += extent as f32;
+        self.width = new_width as i32;
+        self.height = new_height as i32;
+        self.format = GlyphFormat::ColorBitmap;
+        self.bytes = new_bytes;
+    }
 }
 
 pub struct FontContexts {
@@ -1555,8 +1666,23 @@ impl GlyphRasterizer {
     }
 
     pub fn prepare_font(&self, font: &mut FontInstance) {
+        // Stroked glyphs bake the fill and stroke colors into a color bitmap,
+        // so they can't use subpixel AA and need to keep the text color, which
+        // then also becomes part of the glyph cache key. The alpha is left to
+        // the text shader.
+        let stroke_color = if font.stroke().is_some() {
+            font.disable_subpixel_aa();
+            Some(ColorU { a: 0xFF, ..font.color })
+        } else {
+            None
+        };
+
         FontContext::prepare_font(font);
 
+        if let Some(color) = stroke_color {
+            font.color = color;
+        }
+
         // Quantize the transform to minimize thrashing of the glyph cache, but
         // only quantize the transform when preparing to access the glyph cache.
         // This way, the glyph subpixel positions, which are calculated before
@@ -1580,9 +1706,17 @@ impl GlyphRasterizer {
             SubpixelDirection::None,
         );
 
-        self.font_contexts
+        let mut dimensions = self.font_contexts
             .lock_any_context()
-            .get_glyph_dimensions(font, &glyph_key)
+            .get_glyph_dimensions(font, &glyph_key)?;
+
+        // Unhinted advances only differ between platforms by rounding errors,
+        // snap them so that glyphs get placed at the same integer positions.
+        if font.flags.contains(FontInstanceFlags::DETERMINISTIC_POSITIONING) {
+            dimensions.advance = dimensions.advance.round();
+        }
+
+        Some(dimensions)
     }
 
     pub fn get_glyph_index(&mut self, font_key: FontKey, ch: char) -> Option<u32> {
@@ -1711,6 +1845,9 @@ fn process_glyph(
         // Check if the glyph has a bitmap that needs to be downscaled.
         glyph.downscale_bitmap_if_required(&job.font);
 
+        // Outline the glyph if the font requests a stroke.
+        glyph.apply_stroke_if_required(&job.font);
+
         // Convert from BGRA8 to R8 if required. In the future we can make it the
         // backends' responsibility to output glyphs in the desired format,
         // potentially reducing the number of copies.
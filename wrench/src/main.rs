@@ -118,6 +118,33 @@ impl HeadlessContext {
         HeadlessContext { width, height }
     }
 
+    #[cfg(feature = "headless")]
+    fn resize(&mut self, width: i32, height: i32) {
+        let mut buffer = vec![0; (width * height) as usize];
+
+        unsafe {
+            let ret = osmesa_sys::OSMesaMakeCurrent(
+                self._context,
+                buffer.as_mut_ptr() as *mut _,
+                gl::UNSIGNED_BYTE,
+                width,
+                height,
+            );
+            assert!(ret != 0);
+        };
+
+        // The old buffer is only released once the context no longer uses it.
+        self._buffer = buffer;
+        self.width = width;
+        self.height = height;
+    }
+
+    #[cfg(not(feature = "headless"))]
+    fn resize(&mut self, width: i32, height: i32) {
+        self.width = width;
+        self.height = height;
+    }
+
     #[cfg(feature = "headless")]
     fn get_proc_address(s: &str) -> *const c_void {
         let c_str = CString::new(s).expect("Unable to create CString");
@@ -215,7 +242,9 @@ impl WindowWrapper {
             WindowWrapper::Angle(ref mut window, ..) => {
                 window.set_inner_size(LogicalSize::new(size.width as f64, size.height as f64))
             },
-            WindowWrapper::Headless(..) => unimplemented!(), // requites Glutin update
+            WindowWrapper::Headless(ref mut context, ..) => {
+                context.resize(size.width, size.height)
+            },
         }
     }
 
@@ -689,7 +718,7 @@ pub fn main() {
     let dim = window.get_inner_size();
 
     let needs_frame_notifier = args.subcommand_name().map_or(false, |name| {
-        ["perf", "reftest", "png", "rawtest", "test_invalidation"].contains(&name)
+        ["perf", "capture-bench", "reftest", "png", "rawtest", "test_invalidation"].contains(&name)
     });
     let (notifier, rx) = if needs_frame_notifier {
         let (notifier, rx) = create_notifier();
@@ -718,6 +747,13 @@ pub fn main() {
         wrench.renderer.set_profiler_ui(ui_str);
     }
 
+    if let Some(s) = args.value_of("max_blur_raster_size") {
+        let x = s.find('x').expect("Max blur raster size must be specified as width x height");
+        let w = s[0 .. x].parse::<i32>().expect("Invalid max blur raster width");
+        let h = s[x + 1 ..].parse::<i32>().expect("Invalid max blur raster height");
+        wrench.api.send_debug_cmd(DebugCommand::SetMaxBlurRasterSize(Some(DeviceIntSize::new(w, h))));
+    }
+
     window.update(&mut wrench);
 
     if let Some(window_title) = wrench.take_title() {
@@ -783,6 +819,21 @@ pub fn main() {
         }
         harness.run(base_manifest, &filename, as_csv);
         return;
+    } else if let Some(subargs) = args.subcommand_matches("capture-bench") {
+        let input_path = PathBuf::from(subargs.value_of("INPUT").unwrap());
+        let filename = subargs.value_of("filename").unwrap();
+        let fps = subargs.value_of("fps").map_or(60.0, |s| s.parse().unwrap());
+        let frames = subargs.value_of("frames").map_or(300, |s| s.parse().unwrap());
+        let warmup_frames = subargs.value_of("warmup_frames").map(|s| s.parse().unwrap());
+
+        let harness = PerfHarness::new(&mut wrench,
+                                       &mut window,
+                                       rx.unwrap(),
+                                       warmup_frames,
+                                       None);
+
+        harness.run_capture(&input_path, fps, frames, filename);
+        return;
     } else if args.subcommand_matches("test_invalidation").is_some() {
         let harness = test_invalidation::TestHarness::new(
             &mut wrench,
@@ -864,10 +915,15 @@ fn render<'a>(
     }
 
     let mut show_help = false;
+    let mut show_clip_bounds = false;
+    let mut opaque_only = false;
     let mut do_loop = false;
     let mut cursor_position = WorldPoint::zero();
     let mut do_render = false;
     let mut do_frame = false;
+    let device_max_depth_ids = wrench.renderer.get_max_depth_ids() as u32;
+    let mut max_depth_ids = device_max_depth_ids;
+    let mut debug_grid_spacing = 8;
 
     events_loop.run_return(|event, _elwt, control_flow| {
         // By default after each iteration of the event loop we block the thread until the next
@@ -1003,6 +1059,68 @@ fn render<'a>(
                         wrench.api.send_debug_cmd(DebugCommand::ClearCaches(ClearCache::all()));
                         do_frame = true;
                     }
+                    VirtualKeyCode::A => {
+                        // Experimental, and expected to render overlapping
+                        // translucent content incorrectly.
+                        debug_flags.toggle(DebugFlags::ALPHA_DEPTH_PREPASS);
+                        wrench.api.send_debug_cmd(DebugCommand::SetFlags(debug_flags));
+                        do_render = true;
+                    }
+                    VirtualKeyCode::K => {
+                        max_depth_ids = if max_depth_ids > 1 {
+                            max_depth_ids / 2
+                        } else {
+                            device_max_depth_ids
+                        };
+                        println!("Depth id budget: {} (device max {})", max_depth_ids, device_max_depth_ids);
+                        wrench.api.send_debug_cmd(DebugCommand::SetMaxDepthIds(max_depth_ids));
+                        do_frame = true;
+                    }
+                    VirtualKeyCode::U => {
+                        // Transparent content is expected to be missing.
+                        opaque_only = !opaque_only;
+                        wrench.renderer.set_opaque_only(opaque_only);
+                        do_render = true;
+                    }
+                    VirtualKeyCode::E => {
+                        let kinds = &webrender::IsolatedBatchKind::ALL;
+                        let isolation = match wrench.renderer.get_primitive_isolation() {
+                            None => Some(kinds[0]),
+                            Some(kind) => kinds
+                                .iter()
+                                .position(|k| *k == kind)
+                                .and_then(|i| kinds.get(i + 1))
+                                .cloned(),
+                        };
+                        println!("Primitive isolation: {:?}", isolation);
+                        wrench.renderer.set_primitive_isolation(isolation);
+                        // Tiles must be redrawn for the isolation to show up.
+                        debug_flags.set(DebugFlags::FORCE_PICTURE_INVALIDATION, isolation.is_some());
+                        wrench.api.send_debug_cmd(DebugCommand::SetFlags(debug_flags));
+                        do_frame = true;
+                    }
+                    VirtualKeyCode::F => {
+                        show_clip_bounds = !show_clip_bounds;
+                        wrench.api.send_debug_cmd(DebugCommand::SetShowClipBounds(show_clip_bounds));
+                        do_frame = true;
+                    }
+                    VirtualKeyCode::N => {
+                        debug_flags.toggle(DebugFlags::LAYOUT_GRID_DBG);
+                        wrench.api.send_debug_cmd(DebugCommand::SetFlags(debug_flags));
+                        do_render = true;
+                    }
+                    VirtualKeyCode::J => {
+                        debug_grid_spacing = if debug_grid_spacing < 64 {
+                            debug_grid_spacing * 2
+                        } else {
+                            4
+                        };
+                        println!("Debug grid spacing: {}px", debug_grid_spacing);
+                        wrench.renderer.set_debug_grid_spacing(
+                            DeviceIntSize::new(debug_grid_spacing, debug_grid_spacing),
+                        );
+                        do_render = true;
+                    }
                     _ => {}
                 }
                 _ => {}
@@ -10,6 +10,8 @@ use std::io::{BufRead, BufReader};
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
 use std::sync::mpsc::Receiver;
+use std::thread;
+use std::time::{Duration, Instant};
 use crate::wrench::{Wrench, WrenchThing};
 use crate::yaml_frame_reader::YamlFrameReader;
 use webrender::DebugFlags;
@@ -72,11 +74,13 @@ impl BenchmarkManifest {
     }
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Default, Serialize, Deserialize)]
 struct TestProfileRange {
     min: u64,
     avg: u64,
     max: u64,
+    #[serde(default)]
+    p95: u64,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -195,6 +199,74 @@ impl<'a> PerfHarness<'a> {
         profile.save(filename, as_csv);
     }
 
+    /// Replay the frame of a capture `frame_count` times, paced to `fps`, and
+    /// save the timings in the same format as `run`, so that they can be used
+    /// with `compare_perf`.
+    pub fn run_capture(mut self, capture: &Path, fps: f64, frame_count: usize, filename: &str) {
+        let mut documents = self.wrench.api.load_capture(capture.to_path_buf(), None);
+        if documents.is_empty() {
+            println!("Error: no documents found in the capture at {}", capture.display());
+            return;
+        }
+        let captured = documents.swap_remove(0);
+        self.wrench.document_id = captured.document_id;
+
+        // The GPU timings are only reported if the device supports queries.
+        let mut debug_flags = DebugFlags::empty();
+        debug_flags.set(DebugFlags::GPU_TIME_QUERIES | DebugFlags::GPU_SAMPLE_QUERIES, true);
+        self.wrench.api.send_debug_cmd(DebugCommand::SetFlags(debug_flags));
+
+        let frame_interval = Duration::from_secs_f64(1.0 / fps);
+        let mut cpu_frame_profiles = Vec::new();
+        let mut gpu_frame_profiles = Vec::new();
+
+        for frame in 0 .. self.warmup_frames + frame_count {
+            let frame_start = Instant::now();
+
+            self.wrench.refresh();
+            self.rx.recv().unwrap();
+            self.wrench.render();
+            self.window.swap_buffers();
+
+            let (cpu_profiles, gpu_profiles) = self.wrench.get_frame_profiles();
+            if frame >= self.warmup_frames {
+                cpu_frame_profiles.extend(cpu_profiles);
+                gpu_frame_profiles.extend(gpu_profiles);
+            }
+
+            if let Some(remaining) = frame_interval.checked_sub(frame_start.elapsed()) {
+                thread::sleep(remaining);
+            }
+        }
+
+        let profile = TestProfile {
+            name: capture.to_str().unwrap().to_string(),
+            composite_time_ns: extract_sample(&mut cpu_frame_profiles, |a| a.composite_time_ns),
+            paint_time_ns: extract_sample(&mut gpu_frame_profiles, |a| a.paint_time_ns),
+            backend_time_ns: extract_sample(&mut cpu_frame_profiles, |a| a.backend_time_ns),
+            draw_calls: cpu_frame_profiles.last().map_or(0, |p| p.draw_calls),
+        };
+
+        for (label, range) in &[
+            ("backend", &profile.backend_time_ns),
+            ("composite", &profile.composite_time_ns),
+            ("paint", &profile.paint_time_ns),
+        ] {
+            println!(
+                "{:10} min {:.2}ms, max {:.2}ms, mean {:.2}ms, p95 {:.2}ms",
+                label,
+                range.min as f64 / 1000000.0,
+                range.max as f64 / 1000000.0,
+                range.avg as f64 / 1000000.0,
+                range.p95 as f64 / 1000000.0,
+            );
+        }
+
+        let mut results = Profile::new();
+        results.add(profile);
+        results.save(filename, false);
+    }
+
     fn render_yaml(&mut self, filename: &Path) -> TestProfile {
         let mut reader = YamlFrameReader::new(filename);
 
@@ -252,20 +324,29 @@ impl<'a> PerfHarness<'a> {
     }
 }
 
-// returns min, average, max, after removing the lowest and highest SAMPLE_EXCLUDE_COUNT
-// samples (each).
+// returns min, average, max and 95th percentile, after removing the lowest and highest
+// SAMPLE_EXCLUDE_COUNT samples (each) if there are enough of them.
 fn extract_sample<F, T>(profiles: &mut [T], f: F) -> TestProfileRange
 where
     F: Fn(&T) -> u64,
 {
     let mut samples: Vec<u64> = profiles.iter().map(f).collect();
+    if samples.is_empty() {
+        return TestProfileRange::default();
+    }
     samples.sort_unstable();
-    let useful_samples = &samples[SAMPLE_EXCLUDE_COUNT .. samples.len() - SAMPLE_EXCLUDE_COUNT];
+    let useful_samples = if samples.len() > 2 * SAMPLE_EXCLUDE_COUNT {
+        &samples[SAMPLE_EXCLUDE_COUNT .. samples.len() - SAMPLE_EXCLUDE_COUNT]
+    } else {
+        &samples[..]
+    };
     let total_time: u64 = useful_samples.iter().sum();
+    let p95_index = (useful_samples.len() - 1) * 95 / 100;
     TestProfileRange {
         min: useful_samples[0],
         avg: total_time / useful_samples.len() as u64,
-        max: useful_samples[useful_samples.len()-1]
+        max: useful_samples[useful_samples.len()-1],
+        p95: useful_samples[p95_index],
     }
 }
 
@@ -2,39 +2,22 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-// These are slow. Gecko's gfx/2d/Swizzle.cpp has better versions
+use webrender::api::{premultiply_rgba8, unpremultiply_rgba8};
+
+/// Premultiplies RGBA8 data, and swaps it to BGRA8.
 pub fn premultiply(data: &mut [u8]) {
+    premultiply_rgba8(data);
     for pixel in data.chunks_mut(4) {
-        let a = pixel[3] as u32;
-        let b = pixel[2] as u32;
-        let g = pixel[1] as u32;
-        let r = pixel[0] as u32;
-
-        pixel[3] = a as u8;
-        pixel[2] = ((r * a + 128) / 255) as u8;
-        pixel[1] = ((g * a + 128) / 255) as u8;
-        pixel[0] = ((b * a + 128) / 255) as u8;
+        pixel.swap(0, 2);
     }
 }
 
+/// Unpremultiplies BGRA8 data, and swaps it to RGBA8.
 #[allow(unused)]
 pub fn unpremultiply(data: &mut [u8]) {
+    unpremultiply_rgba8(data);
     for pixel in data.chunks_mut(4) {
-        let a = pixel[3] as u32;
-        let mut b = pixel[2] as u32;
-        let mut g = pixel[1] as u32;
-        let mut r = pixel[0] as u32;
-
-        if a > 0 {
-            r = r * 255 / a;
-            g = g * 255 / a;
-            b = b * 255 / a;
-        }
-
-        pixel[3] = a as u8;
-        pixel[2] = r as u8;
-        pixel[1] = g as u8;
-        pixel[0] = b as u8;
+        pixel.swap(0, 2);
     }
 }
 
@@ -2,12 +2,19 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use euclid::{point2, size2, rect, Box2D};
-use std::sync::Arc;
-use std::sync::atomic::{AtomicIsize, Ordering};
+use euclid::{point2, size2, rect, Angle, Box2D};
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicIsize, Ordering};
 use std::sync::mpsc::Receiver;
+use std::time::Duration;
 use webrender::api::*;
 use webrender::render_api::*;
+use webrender::{AlphaMode, CompositeSurfaceContent, FrameSummary, IsolatedBatchKind, PresentMode, RenderResults, TextureAllocatorHook, TextureAllocationRequest, VignetteParams};
+use webrender::{ClipRadius, CompositeSurfaceFormat, Compositor, CompositorCapabilities, CompositorConfig, CompositorKind, CompositorSurfaceTransform};
+use webrender::{Device, NativeSurfaceId, NativeSurfaceInfo, NativeTileId, PartialPresentCompositor, RendererError, TileId, WindowVisibility};
+use gleam::gl;
 use webrender::api::units::*;
 use crate::{WindowWrapper, NotifierEvent};
 use crate::blob;
@@ -34,6 +41,7 @@ impl<'a> RawtestHarness<'a> {
 
     pub fn run(mut self) {
         self.test_hit_testing();
+        self.test_hit_testing_scroll_id();
         self.test_resize_image();
         self.test_retained_blob_images_test();
         self.test_blob_update_test();
@@ -45,9 +53,72 @@ impl<'a> RawtestHarness<'a> {
         self.test_offscreen_blob();
         self.test_save_restore();
         self.test_blur_cache();
+        self.test_render_task_cache_counters();
+        self.test_border_dash_phase_caching();
+        self.test_superseded_scene_builds();
+        self.test_low_priority_scene_build();
+        self.test_max_frames_in_flight();
+        self.test_texture_upload_budget();
+        self.test_flush_and_wait();
+        self.test_flush_and_wait_max_frames_in_flight();
+        self.test_transaction_trace_id();
+        self.test_frame_complexity();
+        self.test_display_list_size_breakdown();
+        self.test_culling_margin();
+        self.test_max_depth_ids();
+        self.test_letterbox_present_mode();
+        self.test_safe_area_insets();
+        self.test_vignette();
+        self.test_caret_blink();
+        self.test_composite_surfaces();
+        self.test_premultiplied_composite_surface();
+        self.test_render_passes();
+        #[cfg(feature = "gl_trace")]
+        self.test_invalidate_targets_on_pass_end();
+        self.test_resource_only_transaction();
+        self.test_effective_clear_color();
+        self.test_output_alpha_mode();
+        self.test_output_linear_depth();
+        #[cfg(feature = "gl_trace")]
+        self.test_gl_trace();
+        #[cfg(feature = "gl_trace")]
+        self.test_early_gl_flush();
         self.test_capture();
+        self.test_display_list_metadata();
         self.test_zero_height_window();
         self.test_clear_cache();
+        self.test_texture_allocator_hook();
+        self.test_export_glyph_atlas();
+        self.test_display_list_validation();
+        self.test_explicit_native_present();
+        self.test_compositor_capabilities();
+        self.test_last_frame_compositor_kind();
+        self.test_update_image_regions();
+        self.test_peak_gpu_memory();
+        self.test_frame_summary_sink();
+        self.test_frame_ids();
+        self.test_list_documents();
+        self.test_gradient_templates();
+        self.test_deterministic_glyph_positioning();
+        self.test_reupload_texture_cache();
+        self.test_window_backdrop_blur();
+        self.test_gpu_cache_resized();
+        self.test_render_scale();
+        self.test_max_clip_chain_depth();
+        self.test_dirty_rect_hook();
+        self.test_opaque_only();
+        self.test_backend_wait_time();
+        self.test_async_shader_compile();
+        self.test_primitive_isolation();
+        self.test_render_document();
+        self.test_reserve_gpu_cache_height();
+        self.test_update_spatial_nodes();
+        self.test_read_picture_cache_tile();
+        self.test_read_pixels_format();
+        self.test_max_render_targets_per_frame();
+        self.test_show_clip_bounds();
+        self.test_native_surface_dirty_rects();
+        self.test_native_surface_clip_radius();
     }
 
     fn render_and_get_pixels(&mut self, window_rect: FramebufferIntRect) -> Vec<u8> {
@@ -1197,254 +1268,3781 @@ impl<'a> RawtestHarness<'a> {
         assert_ne!(first, second);
     }
 
-    fn test_capture(&mut self) {
-        println!("\tcapture...");
-        let path = "../captures/test";
-        let layout_size = LayoutSize::new(400., 400.);
-        let dim = self.window.get_inner_size();
-        let window_rect = FramebufferIntRect::from_origin_and_size(
-            point2(0, dim.height - layout_size.height as i32),
-            size2(layout_size.width as i32, layout_size.height as i32),
-        );
+    fn test_render_task_cache_counters(&mut self) {
+        println!("\trender task cache counters...");
 
-        // 1. render some scene
+        let mut epoch = Epoch(0);
+        let mut do_frame = |frame: usize| {
+            let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+            builder.begin();
 
-        let mut txn = Transaction::new();
-        let image = self.wrench.api.generate_image_key();
-        txn.add_image(
-            image,
-            ImageDescriptor::new(1, 1, ImageFormat::BGRA8, ImageDescriptorFlags::IS_OPAQUE),
-            ImageData::new(vec![0xFF, 0, 0, 0xFF]),
-            None,
-        );
+            let info = self.make_common_properties(rect(0., 0., 400., 400.).to_box2d());
+            builder.push_box_shadow(
+                &info,
+                rect(100., 100., 100., 100.).to_box2d(),
+                LayoutVector2D::new(10.0, 10.0),
+                ColorF::new(0.0, 0.0, 0.0, 1.0),
+                10.0,
+                0.0,
+                BorderRadius::uniform(8.0),
+                BoxShadowClipMode::Outset,
+            );
+
+            // Change a primitive over the shadow every frame so that its
+            // picture cache tile is invalidated and the shadow is re-requested.
+            let info = self.make_common_properties(rect(120., 120., 20., 20.).to_box2d());
+            builder.push_rect(
+                &info,
+                info.clip_rect,
+                ColorF::new(0.0, (frame % 2) as f32, 1.0, 1.0),
+            );
+
+            self.submit_dl(&mut epoch, builder, Transaction::new());
+            self.rx.recv().unwrap();
+            self.wrench.render().stats
+        };
+
+        let first = do_frame(0);
+        assert!(first.render_task_cache_misses > 0);
+
+        let mut hits = 0;
+        for frame in 1 .. 4 {
+            let stats = do_frame(frame);
+            assert_eq!(stats.render_task_cache_misses, 0);
+            assert!(stats.render_task_cache_hits > 0);
+            hits += stats.render_task_cache_hits;
+        }
+        assert!(hits >= 3);
+    }
+
+    fn test_border_dash_phase_caching(&mut self) {
+        println!("\tborder dash phase caching...");
+
+        let phase_key = PropertyBindingKey::new(0x4441_5348);
 
         let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
         builder.begin();
-
-        let info = self.make_common_properties(rect(300.0, 70.0, 150.0, 50.0).to_box2d());
-        builder.push_image(
+        let info = self.make_common_properties(rect(0., 0., 240., 100.).to_box2d());
+        let side = BorderSide { color: ColorF::BLACK, style: BorderStyle::Dashed };
+        builder.push_border_with_dash_phase(
             &info,
             info.clip_rect,
-            ImageRendering::Auto,
-            AlphaType::PremultipliedAlpha,
-            image,
-            ColorF::WHITE,
-        );
-
-        let mut txn = Transaction::new();
-
-        txn.set_display_list(
-            Epoch(0),
-            builder.end(),
+            LayoutSideOffsets::new_all_same(10.0),
+            BorderDetails::Normal(NormalBorder {
+                left: side,
+                right: side,
+                top: side,
+                bottom: side,
+                radius: BorderRadius::zero(),
+                do_aa: true,
+            }),
+            PropertyBinding::Binding(phase_key, 0.0),
         );
-        txn.generate_frame(0, RenderReasons::TESTING);
 
-        self.wrench.api.send_transaction(self.wrench.document_id, txn);
+        let mut epoch = Epoch(0);
+        self.submit_dl(&mut epoch, builder, Transaction::new());
+        self.rx.recv().unwrap();
+        let first = self.wrench.render().stats;
+        assert!(first.render_task_cache_misses > 0);
+
+        let mut render_with_phase = |phase: f32| {
+            let mut txn = Transaction::new();
+            txn.reset_dynamic_properties();
+            txn.append_dynamic_properties(DynamicProperties {
+                transforms: Vec::new(),
+                floats: vec![phase_key.with(phase)],
+                colors: Vec::new(),
+            });
+            txn.generate_frame(0, RenderReasons::TESTING);
+            self.wrench.api.send_transaction(self.wrench.document_id, txn);
+            self.rx.recv().unwrap();
+            self.wrench.render().stats
+        };
 
-        let pixels0 = self.render_and_get_pixels(window_rect);
+        // The first value of the binding matches the default, so the cached
+        // border segments are reused.
+        assert_eq!(render_with_phase(0.0).render_task_cache_misses, 0);
 
-        // 2. capture it
-        self.wrench.api.save_capture(path.into(), CaptureBits::all());
+        // A new phase re-renders the dashed edges.
+        assert!(render_with_phase(0.5).render_task_cache_misses > 0);
 
-        // 3. set a different scene
+        // An unchanged phase doesn't.
+        assert_eq!(render_with_phase(0.5).render_task_cache_misses, 0);
+    }
 
-        builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
-        builder.begin();
+    fn test_superseded_scene_builds(&mut self) {
+        println!("\tsuperseded scene builds...");
 
-        let mut txn = Transaction::new();
-        txn.set_display_list(
-            Epoch(1),
-            builder.end(),
+        let window_size = self.window.get_inner_size();
+        let test_size = FramebufferIntSize::new(100, 100);
+        let window_rect = FramebufferIntRect::from_origin_and_size(
+            FramebufferIntPoint::new(0, window_size.height - test_size.height),
+            test_size,
         );
-        self.wrench.api.send_transaction(self.wrench.document_id, txn);
-
-        // 4. load the first one
-
-        let mut documents = self.wrench.api.load_capture(path.into(), None);
-        let captured = documents.swap_remove(0);
 
-        // 5. render the built frame and compare
-        let pixels1 = self.render_and_get_pixels(window_rect);
-        self.compare_pixels(pixels0.clone(), pixels1, window_rect.size());
+        // Slow scene building down so that the transactions pile up in the
+        // scene builder's queue.
+        self.wrench.api.send_debug_cmd(DebugCommand::SimulateLongSceneBuild(50));
 
-        // 6. rebuild the scene and compare again
-        let mut txn = Transaction::new();
-        txn.set_root_pipeline(captured.root_pipeline_id.unwrap());
-        txn.generate_frame(0, RenderReasons::TESTING);
-        self.wrench.api.send_transaction(captured.document_id, txn);
-        let pixels2 = self.render_and_get_pixels(window_rect);
-        self.compare_pixels(pixels0, pixels2, window_rect.size());
-    }
+        let mut epoch = Epoch(0);
+        let count = 8;
+        for i in 0 .. count {
+            let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+            builder.begin();
 
-    fn test_zero_height_window(&mut self) {
-        println!("\tzero height test...");
+            let info = self.make_common_properties(rect(0., 0., 100., 100.).to_box2d());
+            builder.push_rect(
+                &info,
+                info.clip_rect,
+                ColorF::new(0.0, 0.0, (i + 1) as f32 / count as f32, 1.0),
+            );
 
-        let layout_size = LayoutSize::new(120.0, 0.0);
-        let window_size = DeviceIntSize::new(layout_size.width as i32, layout_size.height as i32);
-        let doc_id = self.wrench.api.add_document(window_size);
+            let mut txn = Transaction::new();
+            txn.set_supersedes_pending(true);
+            self.submit_dl(&mut epoch, builder, txn);
+        }
 
-        let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
-        builder.begin();
-        let info = self.make_common_properties(
-            LayoutRect::from_size(LayoutSize::new(100.0, 100.0))
-        );
-        builder.push_rect(
-            &info,
-            info.clip_rect,
-            ColorF::new(0.0, 1.0, 0.0, 1.0),
-        );
+        self.wrench.api.flush_scene_builder();
+        self.wrench.api.send_debug_cmd(DebugCommand::SimulateLongSceneBuild(0));
 
-        let mut txn = Transaction::new();
-        txn.set_root_pipeline(self.wrench.root_pipeline_id);
-        txn.set_display_list(
-            Epoch(1),
-            builder.end(),
-        );
-        txn.generate_frame(0, RenderReasons::TESTING);
-        self.wrench.api.send_transaction(doc_id, txn);
+        // Every transaction still generates a frame, only render the last one.
+        self.rx.recv().unwrap();
+        while self.rx.try_recv().is_ok() {}
+        let stats = self.wrench.render().stats;
+        assert!(stats.cancelled_scene_builds > 0);
 
-        // Ensure we get a notification from rendering the above, even though
-        // there are zero visible pixels
-        assert!(self.rx.recv().unwrap() == NotifierEvent::WakeUp { composite_needed: true });
+        // The scene of the last transaction was built.
+        let pixels = self.wrench.renderer.read_pixels_rgba8(window_rect);
+        assert_eq!(&pixels[0 .. 4], &[0, 0, 255, 255]);
     }
 
+    fn test_low_priority_scene_build(&mut self) {
+        println!("\tlow priority scene build...");
 
-    fn test_hit_testing(&mut self) {
-        println!("\thit testing test...");
+        struct RecordFrameBuilt {
+            name: &'static str,
+            order: Arc<Mutex<Vec<&'static str>>>,
+        }
 
-        let layout_size = LayoutSize::new(400., 400.);
-        let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
-        builder.begin();
+        impl NotificationHandler for RecordFrameBuilt {
+            fn notify(&self, when: Checkpoint) {
+                if when == Checkpoint::FrameBuilt {
+                    self.order.lock().unwrap().push(self.name);
+                }
+            }
+        }
 
-        // Add a rectangle that covers the entire scene.
-        let space_and_clip = SpaceAndClipInfo::root_scroll(self.wrench.root_pipeline_id);
-        builder.push_hit_test(
-            LayoutRect::from_size(layout_size),
-            ClipChainId::INVALID,
-            space_and_clip.spatial_id,
-            PrimitiveFlags::default(),
-            (0, 1),
-        );
+        // Slow down the low priority scene builder only, so that a normal
+        // transaction sent after a low priority one overtakes it.
+        self.wrench.api.send_debug_cmd(DebugCommand::SimulateLongLowPrioritySceneBuild(200));
 
-        // Add a simple 100x100 rectangle at 100,0.
-        builder.push_hit_test(
-            LayoutRect::from_origin_and_size(
-                LayoutPoint::new(100., 0.),
-                LayoutSize::new(100., 100.)
-            ),
-            ClipChainId::INVALID,
-            space_and_clip.spatial_id,
-            PrimitiveFlags::default(),
-            (0, 2),
-        );
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let mut epoch = Epoch(0);
+        for &(name, low_priority) in &[("low priority", true), ("normal", false)] {
+            let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+            builder.begin();
 
-        let make_rounded_complex_clip = |rect: &LayoutRect, radius: f32| -> ComplexClipRegion {
-            ComplexClipRegion::new(
-                *rect,
-                BorderRadius::uniform_size(LayoutSize::new(radius, radius)),
-                ClipMode::Clip
-            )
-        };
+            let info = self.make_common_properties(rect(0., 0., 100., 100.).to_box2d());
+            builder.push_rect(&info, info.clip_rect, ColorF::new(0.0, 1.0, 0.0, 1.0));
 
-        // Add a rectangle that is clipped by a rounded rect clip item.
-        let rect = LayoutRect::from_origin_and_size(LayoutPoint::new(100., 100.), LayoutSize::new(100., 100.));
-        let temp_clip_id = builder.define_clip_rounded_rect(
-            space_and_clip.spatial_id,
-            make_rounded_complex_clip(&rect, 20.),
-        );
-        let clip_chain_id = builder.define_clip_chain(None, vec![temp_clip_id]);
-        builder.push_hit_test(
-            rect,
-            clip_chain_id,
-            space_and_clip.spatial_id,
-            PrimitiveFlags::default(),
-            (0, 4),
-        );
+            let mut txn = Transaction::new();
+            txn.set_low_priority(low_priority);
+            txn.notify(NotificationRequest::new(
+                Checkpoint::FrameBuilt,
+                Box::new(RecordFrameBuilt { name, order: order.clone() }),
+            ));
+            self.submit_dl(&mut epoch, builder, txn);
+        }
 
-        // Add a rectangle that is clipped by a ClipChain containing a rounded rect.
-        let rect = LayoutRect::from_origin_and_size(LayoutPoint::new(200., 100.), LayoutSize::new(100., 100.));
-        let clip_id = builder.define_clip_rounded_rect(
-            space_and_clip.spatial_id,
-            make_rounded_complex_clip(&rect, 20.),
-        );
-        let clip_chain_id = builder.define_clip_chain(None, vec![clip_id]);
-        builder.push_hit_test(
-            rect,
-            clip_chain_id,
-            space_and_clip.spatial_id,
-            PrimitiveFlags::default(),
-            (0, 5),
-        );
+        for _ in 0 .. 2 {
+            self.rx.recv().unwrap();
+            self.wrench.render();
+        }
 
-        let mut epoch = Epoch(0);
-        let txn = Transaction::new();
-        self.submit_dl(&mut epoch, builder, txn);
+        self.wrench.api.send_debug_cmd(DebugCommand::SimulateLongLowPrioritySceneBuild(0));
 
-        // We render to ensure that the hit tester is up to date with the current scene.
-        self.rx.recv().unwrap();
-        self.wrench.render();
+        assert_eq!(*order.lock().unwrap(), vec!["normal", "low priority"]);
+    }
 
-        let hit_test = |point: WorldPoint| -> HitTestResult {
-            self.wrench.api.hit_test(
-                self.wrench.document_id,
-                point,
-            )
-        };
+    fn test_max_frames_in_flight(&mut self) {
+        println!("\tmax frames in flight...");
 
-        let assert_hit_test = |point: WorldPoint, tags: Vec<ItemTag>| {
-            let result = hit_test(point);
-            assert_eq!(result.items.len(), tags.len());
+        struct SetFlag(Arc<AtomicBool>);
 
-            for (hit_test_item, item_b) in result.items.iter().zip(tags.iter()) {
-                assert_eq!(hit_test_item.tag, *item_b);
+        impl NotificationHandler for SetFlag {
+            fn notify(&self, when: Checkpoint) {
+                if when == Checkpoint::FrameBuilt {
+                    self.0.store(true, Ordering::SeqCst);
+                }
             }
-        };
+        }
 
-        // We should not have any hits outside the boundaries of the scene.
-        assert_hit_test(WorldPoint::new(-10., -10.), Vec::new());
-        assert_hit_test(WorldPoint::new(-10., 10.), Vec::new());
-        assert_hit_test(WorldPoint::new(450., 450.), Vec::new());
-        assert_hit_test(WorldPoint::new(100., 450.), Vec::new());
+        self.wrench.renderer.set_max_frames_in_flight(1);
 
-        // The top left corner of the scene should only contain the background.
-        assert_hit_test(WorldPoint::new(50., 50.), vec![(0, 1)]);
+        let mut epoch = Epoch(0);
+        let mut submit_frame = |harness: &mut Self, color: ColorF| {
+            let mut builder = DisplayListBuilder::new(harness.wrench.root_pipeline_id);
+            builder.begin();
+            let info = harness.make_common_properties(rect(0., 0., 100., 100.).to_box2d());
+            builder.push_rect(&info, info.clip_rect, color);
+
+            // Frame built notifications are sent once the frame is published.
+            let published = Arc::new(AtomicBool::new(false));
+            let mut txn = Transaction::new();
+            txn.notify(NotificationRequest::new(
+                Checkpoint::FrameBuilt,
+                Box::new(SetFlag(published.clone())),
+            ));
+            harness.submit_dl(&mut epoch, builder, txn);
+            published
+        };
 
-        // The middle of the normal rectangle should be hit.
-        assert_hit_test(WorldPoint::new(150., 50.), vec![(0, 2), (0, 1)]);
+        let wait_for = |flag: &AtomicBool| {
+            for _ in 0 .. 1000 {
+                if flag.load(Ordering::SeqCst) {
+                    return;
+                }
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            panic!("timed out waiting for the frame to be published");
+        };
 
-        let test_rounded_rectangle = |point: WorldPoint, size: WorldSize, tag: ItemTag| {
-            // The cut out corners of the rounded rectangle should not be hit.
-            let top_left = point + WorldVector2D::new(5., 5.);
-            let bottom_right = point + size.to_vector() - WorldVector2D::new(5., 5.);
+        let first = submit_frame(self, ColorF::new(1.0, 0.0, 0.0, 1.0));
+        wait_for(&first);
 
-            assert_hit_test(
-                WorldPoint::new(point.x + (size.width / 2.), point.y + (size.height / 2.)),
-                vec![tag, (0, 1)]
-            );
+        // The first frame hasn't been rendered, so the render backend defers
+        // building the second one. It keeps answering synchronous API calls
+        // meanwhile, which also ensures it has seen the second transaction.
+        let second = submit_frame(self, ColorF::new(0.0, 1.0, 0.0, 1.0));
+        self.wrench.api.flush_scene_builder();
+        assert!(!self.wrench.api.list_documents().is_empty());
+        assert!(!second.load(Ordering::SeqCst));
 
-            assert_hit_test(top_left, vec![(0, 1)]);
-            assert_hit_test(WorldPoint::new(bottom_right.x, top_left.y), vec![(0, 1)]);
-            assert_hit_test(WorldPoint::new(top_left.x, bottom_right.y), vec![(0, 1)]);
-            assert_hit_test(bottom_right, vec![(0, 1)]);
-        };
+        // Rendering the first frame lets the second one be built.
+        self.wrench.render();
+        wait_for(&second);
+        self.wrench.render();
 
-        test_rounded_rectangle(WorldPoint::new(100., 100.), WorldSize::new(100., 100.), (0, 4));
-        test_rounded_rectangle(WorldPoint::new(200., 100.), WorldSize::new(100., 100.), (0, 5));
+        self.wrench.renderer.set_max_frames_in_flight(0);
+        while self.rx.try_recv().is_ok() {}
     }
 
-    fn test_clear_cache(&mut self) {
-        println!("\tclear cache test...");
-
-        self.wrench.api.send_message(ApiMsg::DebugCommand(DebugCommand::ClearCaches(ClearCache::all())));
+    fn test_flush_and_wait_max_frames_in_flight(&mut self) {
+        println!("\tflush and wait with max frames in flight...");
 
-        let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
-        builder.begin();
+        self.wrench.renderer.set_max_frames_in_flight(1);
 
-        let txn = Transaction::new();
         let mut epoch = Epoch(0);
-        self.submit_dl(&mut epoch, builder, txn);
-
-        self.rx.recv().unwrap();
+        for color in &[ColorF::new(1.0, 0.0, 0.0, 1.0), ColorF::new(0.0, 1.0, 0.0, 1.0)] {
+            let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+            builder.begin();
+            let info = self.make_common_properties(rect(0., 0., 100., 100.).to_box2d());
+            builder.push_rect(&info, info.clip_rect, *color);
+            self.submit_dl(&mut epoch, builder, Transaction::new());
+        }
+
+        // The second frame is deferred until the first one is rendered, so
+        // the flush can't complete before then.
+        assert_eq!(
+            self.wrench.api.flush_and_wait(Duration::from_millis(100)),
+            Err(FlushTimeout),
+        );
+
+        // Rendering the first frame lets the second one be published.
+        self.wrench.render();
+        assert_eq!(
+            self.wrench.api.flush_and_wait(Duration::from_secs(10)),
+            Ok(()),
+        );
+        self.wrench.renderer.update();
+        assert_eq!(
+            self.wrench.renderer.current_epoch(self.wrench.document_id, self.wrench.root_pipeline_id),
+            Some(Epoch(epoch.0 - 1)),
+        );
+        self.wrench.render();
+
+        self.wrench.renderer.set_max_frames_in_flight(0);
+        while self.rx.try_recv().is_ok() {}
+    }
+
+    fn test_texture_upload_budget(&mut self) {
+        println!("\ttexture upload budget...");
+
+        self.wrench.renderer.set_texture_upload_budget_bytes(Some(64 * 1024));
+
+        let mut epoch = Epoch(0);
+        let mut submit_image = |harness: &mut Self, size: i32| {
+            let mut txn = Transaction::new();
+            let img = harness.wrench.api.generate_image_key();
+            txn.add_image(
+                img,
+                ImageDescriptor::new(size, size, ImageFormat::BGRA8, ImageDescriptorFlags::IS_OPAQUE),
+                ImageData::new(vec![255; (size * size * 4) as usize]),
+                None,
+            );
+
+            let mut builder = DisplayListBuilder::new(harness.wrench.root_pipeline_id);
+            builder.begin();
+            let info = harness.make_common_properties(rect(0.0, 0.0, size as f32, size as f32).to_box2d());
+            builder.push_image(
+                &info,
+                info.clip_rect,
+                ImageRendering::Auto,
+                AlphaType::PremultipliedAlpha,
+                img,
+                ColorF::WHITE,
+            );
+
+            harness.submit_dl(&mut epoch, builder, txn);
+            harness.rx.recv().unwrap();
+            let results = harness.wrench.render();
+
+            let mut txn = Transaction::new();
+            txn.delete_image(img);
+            harness.wrench.api.send_transaction(harness.wrench.document_id, txn);
+
+            results.stats.over_upload_budget
+        };
+
+        // A 16KB upload fits in the budget, a 1MB one doesn't.
+        assert!(!submit_image(self, 64));
+        assert!(submit_image(self, 512));
+
+        self.wrench.renderer.set_texture_upload_budget_bytes(None);
+    }
+
+    fn test_flush_and_wait(&mut self) {
+        println!("\tflush and wait...");
+
+        let mut epoch = Epoch(0);
+        for i in 0 .. 5 {
+            let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+            builder.begin();
+
+            let info = self.make_common_properties(rect(0., 0., 100., 100.).to_box2d());
+            builder.push_rect(
+                &info,
+                info.clip_rect,
+                ColorF::new(0.0, i as f32 / 4.0, 0.0, 1.0),
+            );
+
+            self.submit_dl(&mut epoch, builder, Transaction::new());
+        }
+
+        assert_eq!(
+            self.wrench.api.flush_and_wait(Duration::from_secs(10)),
+            Ok(()),
+        );
+
+        // Everything has been published, so the renderer picks up the most
+        // recent epoch without waiting on any notification.
+        self.wrench.renderer.update();
+        let last_epoch = Epoch(epoch.0 - 1);
+        assert_eq!(
+            self.wrench.renderer.current_epoch(self.wrench.document_id, self.wrench.root_pipeline_id),
+            Some(last_epoch),
+        );
+
+        while self.rx.try_recv().is_ok() {}
+        self.wrench.render();
+    }
+
+    fn test_transaction_trace_id(&mut self) {
+        println!("\ttransaction trace id...");
+
+        let mut epoch = Epoch(0);
+        let mut submit_frame = |harness: &mut Self, trace_id: Option<u64>| {
+            let mut builder = DisplayListBuilder::new(harness.wrench.root_pipeline_id);
+            builder.begin();
+            let info = harness.make_common_properties(rect(0., 0., 100., 100.).to_box2d());
+            builder.push_rect(&info, info.clip_rect, ColorF::new(0.0, 1.0, 0.0, 1.0));
+
+            let mut txn = Transaction::new();
+            if let Some(trace_id) = trace_id {
+                txn.set_trace_id(trace_id);
+            }
+            harness.submit_dl(&mut epoch, builder, txn);
+            harness.rx.recv().unwrap();
+            harness.wrench.render()
+        };
+
+        let results = submit_frame(self, Some(42));
+        assert_eq!(results.trace_ids, vec![42]);
+
+        // The id is only reported for the frame built from the transaction.
+        let results = submit_frame(self, None);
+        assert!(results.trace_ids.is_empty());
+    }
+
+    fn test_frame_complexity(&mut self) {
+        println!("\tframe complexity...");
+
+        let mut epoch = Epoch(0);
+        let mut do_frame = |rect_count: usize| {
+            let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+            builder.begin();
+
+            for i in 0 .. rect_count {
+                let offset = (i % 20) as f32 * 10.0;
+                let info = self.make_common_properties(
+                    rect(offset, offset, 200., 200.).to_box2d()
+                );
+                builder.push_rect(
+                    &info,
+                    info.clip_rect,
+                    ColorF::new(1.0, 0.0, (i % 2) as f32, 0.5),
+                );
+            }
+
+            if rect_count > 1 {
+                let info = self.make_common_properties(rect(0., 0., 400., 400.).to_box2d());
+                builder.push_box_shadow(
+                    &info,
+                    rect(100., 100., 100., 100.).to_box2d(),
+                    LayoutVector2D::new(10.0, 10.0),
+                    ColorF::new(0.0, 0.0, 0.0, 1.0),
+                    10.0,
+                    0.0,
+                    BorderRadius::uniform(8.0),
+                    BoxShadowClipMode::Outset,
+                );
+            }
+
+            self.submit_dl(&mut epoch, builder, Transaction::new());
+            self.rx.recv().unwrap();
+            let complexity = self.wrench.api.get_frame_complexity(self.wrench.document_id);
+            self.wrench.render();
+            complexity
+        };
+
+        let simple = do_frame(1);
+        let complex = do_frame(100);
+
+        assert!(simple.primitive_count > 0);
+        assert!(complex.primitive_count > simple.primitive_count);
+        assert!(complex.batch_count >= simple.batch_count);
+        assert!(complex.render_task_count > simple.render_task_count);
+        assert!(complex.estimated_pixels_shaded >= simple.estimated_pixels_shaded);
+    }
+
+    fn test_display_list_size_breakdown(&mut self) {
+        println!("\tdisplay list size breakdown...");
+
+        let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+        builder.begin();
+
+        let root_space = SpatialId::root_scroll_node(self.wrench.root_pipeline_id);
+        let clip_id = builder.define_clip_rect(root_space, rect(0., 0., 100., 100.).to_box2d());
+        let clip_chain_id = builder.define_clip_chain(None, [clip_id]);
+
+        for i in 0 .. 10 {
+            let info = self.make_common_properties(rect(i as f32 * 10., 0., 10., 10.).to_box2d());
+            builder.push_rect(&info, info.clip_rect, ColorF::new(0.0, 1.0, 0.0, 1.0));
+        }
+
+        let glyphs: Vec<GlyphInstance> = (0 .. 100)
+            .map(|i| GlyphInstance {
+                index: i,
+                point: LayoutPoint::new(i as f32 * 10., 50.),
+            })
+            .collect();
+        let info = CommonItemProperties {
+            clip_rect: rect(0., 0., 1000., 100.).to_box2d(),
+            clip_chain_id,
+            spatial_id: root_space,
+            flags: PrimitiveFlags::default(),
+        };
+        builder.push_text(
+            &info,
+            info.clip_rect,
+            &glyphs,
+            FontInstanceKey::new(IdNamespace(0), 0),
+            ColorF::BLACK,
+            None,
+        );
+
+        let (_, display_list) = builder.end();
+        let breakdown = display_list.size_breakdown();
+
+        assert_eq!(breakdown.values().sum::<usize>(), display_list.size_in_bytes());
+        assert!(breakdown.values().all(|&num_bytes| num_bytes > 0));
+        for name in &["rectangle", "text", "clip_chain", "rect_clip"] {
+            assert!(breakdown.contains_key(name), "missing {}", name);
+        }
+
+        // The glyphs are counted with the text item.
+        assert!(breakdown["text"] > breakdown["rectangle"]);
+    }
+
+    fn test_culling_margin(&mut self) {
+        println!("\tculling margin...");
+
+        let window_size = self.window.get_inner_size();
+
+        // Use small picture cache tiles, so that the visible tiles don't extend
+        // much past the edge of the window.
+        self.wrench.api.send_debug_cmd(
+            DebugCommand::SetPictureTileSize(Some(DeviceIntSize::new(64, 64)))
+        );
+
+        let mut epoch = Epoch(0);
+        let mut do_frame = |culling_margin: f32| {
+            self.wrench.api.send_debug_cmd(DebugCommand::SetCullingMargin(culling_margin));
+
+            let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+            builder.begin();
+
+            let info = self.make_common_properties(rect(0., 0., 100., 100.).to_box2d());
+            builder.push_rect(&info, info.clip_rect, ColorF::new(0.0, 0.0, 1.0, 1.0));
+
+            // A primitive just past the right edge of the window.
+            let info = self.make_common_properties(
+                rect(window_size.width as f32 + 100., 0., 50., 50.).to_box2d()
+            );
+            builder.push_rect(&info, info.clip_rect, ColorF::new(1.0, 0.0, 0.0, 1.0));
+
+            self.submit_dl(&mut epoch, builder, Transaction::new());
+            self.rx.recv().unwrap();
+            let complexity = self.wrench.api.get_frame_complexity(self.wrench.document_id);
+            self.wrench.render();
+            complexity.primitive_count
+        };
+
+        let without_margin = do_frame(0.0);
+        let with_margin = do_frame(200.0);
+        assert_eq!(with_margin, without_margin + 1);
+
+        self.wrench.api.send_debug_cmd(DebugCommand::SetCullingMargin(0.0));
+        self.wrench.api.send_debug_cmd(DebugCommand::SetPictureTileSize(None));
+    }
+
+    fn test_max_depth_ids(&mut self) {
+        println!("\tmax depth ids...");
+
+        let window_size = self.window.get_inner_size();
+        let test_size = FramebufferIntSize::new(400, 400);
+        let window_rect = FramebufferIntRect::from_origin_and_size(
+            point2(0, window_size.height - test_size.height),
+            test_size,
+        );
+
+        let device_max = self.wrench.renderer.get_max_depth_ids() as u32;
+        let mut epoch = Epoch(0);
+        let mut do_frame = |max_depth_ids: u32| {
+            self.wrench.api.send_debug_cmd(DebugCommand::SetMaxDepthIds(max_depth_ids));
+
+            let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+            builder.begin();
+
+            // Overlapping opaque and translucent rects, so that the order in
+            // which they are drawn matters once they share depth ids.
+            for i in 0 .. 20 {
+                let offset = i as f32 * 10.0;
+                let info = self.make_common_properties(
+                    rect(offset, offset, 150., 150.).to_box2d()
+                );
+                let alpha = if i % 3 == 0 { 0.5 } else { 1.0 };
+                builder.push_rect(
+                    &info,
+                    info.clip_rect,
+                    ColorF::new((i % 2) as f32, 0.0, 1.0 - (i % 2) as f32, alpha),
+                );
+            }
+
+            self.submit_dl(&mut epoch, builder, Transaction::new());
+            self.render_and_get_pixels(window_rect)
+        };
+
+        let full_budget = do_frame(device_max);
+        let exhausted_budget = do_frame(4);
+        let min_budget = do_frame(0);
+        do_frame(device_max);
+
+        self.compare_pixels(full_budget.clone(), exhausted_budget, window_rect.size());
+        self.compare_pixels(full_budget, min_budget, window_rect.size());
+    }
+
+    fn test_letterbox_present_mode(&mut self) {
+        println!("\tletterbox present mode...");
+
+        // A 16:9 document presented into a 4:3 framebuffer should be scaled
+        // to 400x225 and leave bars above and below it.
+        let doc_size = DeviceIntSize::new(320, 180);
+        let fb_size = DeviceIntSize::new(400, 300);
+        let bar_color = ColorF::new(1.0, 0.0, 0.0, 1.0);
+
+        let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+        builder.begin();
+        let info = self.make_common_properties(
+            LayoutRect::from_size(LayoutSize::new(320.0, 180.0))
+        );
+        builder.push_rect(&info, info.clip_rect, ColorF::new(0.0, 1.0, 0.0, 1.0));
+
+        let mut txn = Transaction::new();
+        txn.set_document_view(DeviceIntRect::from_size(doc_size));
+        self.submit_dl(&mut Epoch(0), builder, txn);
+        self.rx.recv().unwrap();
+
+        self.wrench.renderer.set_present_mode(Some(PresentMode::Letterbox(bar_color)));
+        self.wrench.renderer.update();
+        self.wrench.renderer.render(fb_size, 0).unwrap();
+        let pixels = self.wrench.renderer.read_pixels_rgba8(
+            FramebufferIntRect::from_size(FramebufferIntSize::new(fb_size.width, fb_size.height))
+        );
+        self.wrench.renderer.set_present_mode(None);
+
+        let pixel_at = |x: usize, y: usize| {
+            let p = (y * fb_size.width as usize + x) * 4;
+            &pixels[p .. p + 4]
+        };
+        for &y in &[0, 30, 270, 299] {
+            assert_eq!(pixel_at(200, y), &[255, 0, 0, 255]);
+        }
+        for &(x, y) in &[(0, 150), (200, 50), (200, 150), (399, 250)] {
+            assert_eq!(pixel_at(x, y), &[0, 255, 0, 255]);
+        }
+
+        let mut txn = Transaction::new();
+        txn.set_document_view(DeviceIntRect::from_size(self.window.get_inner_size()));
+        txn.generate_frame(0, RenderReasons::TESTING);
+        self.wrench.api.send_transaction(self.wrench.document_id, txn);
+        self.rx.recv().unwrap();
+        self.wrench.render();
+    }
+
+    fn test_safe_area_insets(&mut self) {
+        println!("\tsafe area insets...");
+
+        // A full-size document with blue strips along its top and left edges
+        // is offset by the top-left insets, and clipped by the bottom inset.
+        let fb_size = DeviceIntSize::new(400, 300);
+
+        let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+        builder.begin();
+        let info = self.make_common_properties(rect(0., 0., 400., 300.).to_box2d());
+        builder.push_rect(&info, info.clip_rect, ColorF::new(0.0, 1.0, 0.0, 1.0));
+        let info = self.make_common_properties(rect(0., 0., 400., 20.).to_box2d());
+        builder.push_rect(&info, info.clip_rect, ColorF::new(0.0, 0.0, 1.0, 1.0));
+        let info = self.make_common_properties(rect(0., 0., 10., 300.).to_box2d());
+        builder.push_rect(&info, info.clip_rect, ColorF::new(0.0, 0.0, 1.0, 1.0));
+
+        let mut txn = Transaction::new();
+        txn.set_document_view(DeviceIntRect::from_size(fb_size));
+        self.submit_dl(&mut Epoch(0), builder, txn);
+        self.rx.recv().unwrap();
+
+        self.wrench.renderer.set_safe_area_insets(DeviceIntSideOffsets::new(20, 0, 20, 10));
+        self.wrench.renderer.update();
+        let results = self.wrench.renderer.render(fb_size, 0).unwrap();
+        let pixels = self.wrench.renderer.read_pixels_rgba8(
+            FramebufferIntRect::from_size(FramebufferIntSize::new(fb_size.width, fb_size.height))
+        );
+        self.wrench.renderer.set_safe_area_insets(DeviceIntSideOffsets::zero());
+
+        // The dirty rect covers the cleared insets as well as the content.
+        assert!(results.dirty_rects.iter().all(|r| *r == DeviceIntRect::from_size(fb_size)));
+
+        // Pixels are read back bottom-up, this takes a top-left origin.
+        let pixel_at = |x: usize, y: usize| {
+            let p = ((fb_size.height as usize - 1 - y) * fb_size.width as usize + x) * 4;
+            &pixels[p .. p + 4]
+        };
+        let white = [255, 255, 255, 255];
+        let green = [0, 255, 0, 255];
+        let blue = [0, 0, 255, 255];
+
+        // The insets are cleared, including where the blue strips would be
+        // drawn without them.
+        for &(x, y) in &[(200, 0), (200, 19), (0, 150), (9, 150), (200, 280), (200, 299)] {
+            assert_eq!(pixel_at(x, y), &white, "inset at {},{}", x, y);
+        }
+        // The strips are shifted down and right by the top-left insets.
+        for &(x, y) in &[(200, 20), (200, 39), (10, 150), (19, 150)] {
+            assert_eq!(pixel_at(x, y), &blue, "strip at {},{}", x, y);
+        }
+        for &(x, y) in &[(200, 40), (20, 150), (399, 279)] {
+            assert_eq!(pixel_at(x, y), &green, "content at {},{}", x, y);
+        }
+
+        let mut txn = Transaction::new();
+        txn.set_document_view(DeviceIntRect::from_size(self.window.get_inner_size()));
+        txn.generate_frame(0, RenderReasons::TESTING);
+        self.wrench.api.send_transaction(self.wrench.document_id, txn);
+        self.rx.recv().unwrap();
+        self.wrench.render();
+    }
+
+    fn test_peak_gpu_memory(&mut self) {
+        println!("\tpeak gpu memory...");
+
+        let image_bytes = 2048 * 2048 * 4;
+        self.wrench.renderer.reset_peak_gpu_memory();
+        assert!(self.wrench.renderer.peak_gpu_memory().standalone_textures < image_bytes);
+
+        // Large images get a standalone texture.
+        let img = self.wrench.api.generate_image_key();
+        let mut txn = Transaction::new();
+        txn.add_image(
+            img,
+            ImageDescriptor::new(2048, 2048, ImageFormat::BGRA8, ImageDescriptorFlags::IS_OPAQUE),
+            ImageData::new(vec![255; image_bytes]),
+            None,
+        );
+
+        let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+        builder.begin();
+        let info = self.make_common_properties(rect(0.0, 0.0, 256.0, 256.0).to_box2d());
+        builder.push_image(
+            &info,
+            info.clip_rect,
+            ImageRendering::Auto,
+            AlphaType::PremultipliedAlpha,
+            img,
+            ColorF::WHITE,
+        );
+        let mut epoch = Epoch(0);
+        self.submit_dl(&mut epoch, builder, txn);
+        self.rx.recv().unwrap();
+        self.wrench.render();
+        assert!(self.wrench.renderer.peak_gpu_memory().standalone_textures >= image_bytes);
+
+        // Free the texture, the peak is unchanged.
+        let mut txn = Transaction::new();
+        txn.delete_image(img);
+        let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+        builder.begin();
+        self.submit_dl(&mut epoch, builder, txn);
+        self.rx.recv().unwrap();
+        self.wrench.render();
+        self.wrench.render();
+        assert!(self.wrench.renderer.peak_gpu_memory().standalone_textures >= image_bytes);
+
+        // Resetting starts again from the current usage.
+        self.wrench.renderer.reset_peak_gpu_memory();
+        assert!(self.wrench.renderer.peak_gpu_memory().standalone_textures < image_bytes);
+    }
+
+    fn test_update_image_regions(&mut self) {
+        println!("\tupdate image regions...");
+
+        let window_size = self.window.get_inner_size();
+        let window_rect = FramebufferIntRect::from_size(
+            FramebufferIntSize::new(window_size.width, window_size.height),
+        );
+
+        let img = self.wrench.api.generate_image_key();
+        let mut txn = Transaction::new();
+        txn.add_image(
+            img,
+            ImageDescriptor::new(100, 10, ImageFormat::BGRA8, ImageDescriptorFlags::IS_OPAQUE),
+            ImageData::new(vec![255; 100 * 10 * 4]),
+            None,
+        );
+
+        let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+        builder.begin();
+        let info = self.make_common_properties(rect(0.0, 0.0, 100.0, 10.0).to_box2d());
+        builder.push_image(
+            &info,
+            info.clip_rect,
+            ImageRendering::Pixelated,
+            AlphaType::PremultipliedAlpha,
+            img,
+            ColorF::WHITE,
+        );
+        self.submit_dl(&mut Epoch(0), builder, txn);
+        self.rx.recv().unwrap();
         self.wrench.render();
+
+        // Replace the image with ten 10x10 squares, alternating between blue
+        // and green, all read from a single buffer.
+        let colors = [[255, 0, 0, 255], [0, 255, 0, 255]];
+        let mut source = Vec::new();
+        let mut regions = Vec::new();
+        for i in 0 .. 10 {
+            regions.push((DeviceIntRect::from_origin_and_size(
+                DeviceIntPoint::new(i * 10, 0),
+                DeviceIntSize::new(10, 10),
+            ), source.len()));
+            for _ in 0 .. 10 * 10 {
+                source.extend_from_slice(&colors[i as usize % 2]);
+            }
+        }
+        // Regions that don't fit in the image or the buffer are ignored.
+        let square = DeviceIntSize::new(10, 10);
+        let out_of_bounds = DeviceIntRect::from_origin_and_size(DeviceIntPoint::new(95, 0), square);
+        regions.push((out_of_bounds, 0));
+        regions.push((DeviceIntRect::from_size(square), source.len()));
+
+        let mut txn = Transaction::new();
+        txn.update_image_regions(img, Arc::new(source), regions);
+        txn.generate_frame(0, RenderReasons::TESTING);
+        self.wrench.api.send_transaction(self.wrench.document_id, txn);
+        let pixels = self.render_and_get_pixels(window_rect);
+
+        // Pixels are read back bottom-up, in RGBA.
+        let y = (window_size.height - 1 - 5) as usize;
+        for i in 0 .. 10 {
+            let p = (y * window_size.width as usize + i * 10 + 5) * 4;
+            let expected = match i % 2 {
+                0 => [0, 0, 255, 255],
+                _ => [0, 255, 0, 255],
+            };
+            assert_eq!(&pixels[p .. p + 4], &expected, "region {}", i);
+        }
+
+        let mut txn = Transaction::new();
+        txn.delete_image(img);
+        self.wrench.api.send_transaction(self.wrench.document_id, txn);
+    }
+
+    fn test_vignette(&mut self) {
+        println!("\tvignette...");
+
+        let window_size = self.window.get_inner_size();
+        let window_rect = FramebufferIntRect::from_size(
+            FramebufferIntSize::new(window_size.width, window_size.height),
+        );
+
+        let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+        builder.begin();
+        let info = self.make_common_properties(
+            LayoutRect::from_size(LayoutSize::new(window_size.width as f32, window_size.height as f32)),
+        );
+        builder.push_rect(&info, info.clip_rect, ColorF::new(0.5, 0.5, 0.5, 1.0));
+
+        // Leave the middle of the window untouched and fully darken the corners.
+        let half_diagonal = window_size.to_f32().to_vector().length() * 0.5;
+        self.wrench.renderer.set_vignette(Some(VignetteParams {
+            inner_radius: window_size.width.min(window_size.height) as f32 * 0.25,
+            outer_radius: half_diagonal,
+            color: ColorF::BLACK,
+        }));
+        self.submit_dl(&mut Epoch(0), builder, Transaction::new());
+        let pixels = self.render_and_get_pixels(window_rect);
+        self.wrench.renderer.set_vignette(None);
+
+        let pixel_at = |x: i32, y: i32| {
+            let p = (y * window_size.width + x) as usize * 4;
+            &pixels[p .. p + 4]
+        };
+        let center = pixel_at(window_size.width / 2, window_size.height / 2);
+        assert_eq!(center, &[128, 128, 128, 255]);
+        for &(x, y) in &[
+            (0, 0),
+            (window_size.width - 1, 0),
+            (0, window_size.height - 1),
+            (window_size.width - 1, window_size.height - 1),
+        ] {
+            let corner = pixel_at(x, y);
+            assert!(corner[0] < 16, "corner {:?} is not darkened", corner);
+            assert_eq!(corner[3], 255);
+        }
+    }
+
+    fn test_caret_blink(&mut self) {
+        println!("\tcaret blink...");
+
+        let window_size = self.window.get_inner_size();
+        let test_size = FramebufferIntSize::new(100, 100);
+        let window_rect = FramebufferIntRect::from_origin_and_size(
+            FramebufferIntPoint::new(0, window_size.height - test_size.height),
+            test_size,
+        );
+
+        let blink_key = PropertyBindingKey::new(0x4341_5245);
+
+        let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+        builder.begin();
+        let info = self.make_common_properties(rect(0., 0., 100., 100.).to_box2d());
+        builder.push_rect(&info, info.clip_rect, ColorF::WHITE);
+        builder.push_caret(
+            &info,
+            rect(50., 20., 10., 40.).to_box2d(),
+            ColorF::new(1.0, 0.0, 0.0, 1.0),
+            PropertyBinding::Binding(blink_key, 1.0),
+        );
+
+        let mut epoch = Epoch(0);
+        self.submit_dl(&mut epoch, builder, Transaction::new());
+
+        // The pixels are read back bottom-up.
+        let caret_pixel = |pixels: &[u8]| {
+            let p = ((test_size.height - 40) * test_size.width + 55) as usize * 4;
+            pixels[p .. p + 4].to_vec()
+        };
+        let red = vec![255, 0, 0, 255];
+        let white = vec![255, 255, 255, 255];
+
+        let pixels = self.render_and_get_pixels(window_rect);
+        assert_eq!(caret_pixel(&pixels), red);
+
+        let mut render_with_blink = |blink: Option<f32>| {
+            let mut txn = Transaction::new();
+            if let Some(blink) = blink {
+                txn.reset_dynamic_properties();
+                txn.append_dynamic_properties(DynamicProperties {
+                    transforms: Vec::new(),
+                    floats: vec![blink_key.with(blink)],
+                    colors: Vec::new(),
+                });
+            }
+            txn.generate_frame(0, RenderReasons::TESTING);
+            self.wrench.api.send_transaction(self.wrench.document_id, txn);
+            self.render_and_get_pixels(window_rect)
+        };
+
+        // Updating the binding hides the caret without a new display list.
+        assert_eq!(caret_pixel(&render_with_blink(Some(0.0))), white);
+
+        // Other frames leave it hidden until the binding changes again.
+        assert_eq!(caret_pixel(&render_with_blink(None)), white);
+        assert_eq!(caret_pixel(&render_with_blink(Some(1.0))), red);
+    }
+
+    fn test_composite_surfaces(&mut self) {
+        println!("\tcomposite surfaces...");
+
+        let mut txn = Transaction::new();
+        let img = self.wrench.api.generate_image_key();
+        txn.add_image(
+            img,
+            ImageDescriptor::new(64, 64, ImageFormat::BGRA8, ImageDescriptorFlags::IS_OPAQUE),
+            ImageData::new(vec![255; 64 * 64 * 4]),
+            None,
+        );
+
+        let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+        builder.begin();
+        let mut info = self.make_common_properties(rect(100., 50., 200., 150.).to_box2d());
+        info.flags |= PrimitiveFlags::PREFER_COMPOSITOR_SURFACE;
+        builder.push_image(
+            &info,
+            info.clip_rect,
+            ImageRendering::Auto,
+            AlphaType::PremultipliedAlpha,
+            img,
+            ColorF::WHITE,
+        );
+
+        self.submit_dl(&mut Epoch(0), builder, txn);
+        self.rx.recv().unwrap();
+        let results = self.wrench.render();
+
+        // Wrench uses the draw compositor, so the promoted image is drawn by
+        // WR rather than handed to a native compositor.
+        let external: Vec<_> = results.composite_surfaces
+            .iter()
+            .filter(|surface| surface.content == CompositeSurfaceContent::External)
+            .collect();
+        assert_eq!(external.len(), 1);
+        assert!(!external[0].is_native);
+        assert_eq!(external[0].device_rect, rect(100., 50., 200., 150.).to_box2d());
+
+        let mut txn = Transaction::new();
+        txn.delete_image(img);
+        self.wrench.api.send_transaction(self.wrench.document_id, txn);
+    }
+
+    fn test_premultiplied_composite_surface(&mut self) {
+        println!("\tpremultiplied composite surface...");
+        let window_size = self.window.get_inner_size();
+
+        let test_size = FramebufferIntSize::new(64, 64);
+        let window_rect = FramebufferIntRect::from_origin_and_size(
+            point2(0, window_size.height - test_size.height),
+            test_size,
+        );
+
+        // Half transparent red, with premultiplied BGRA channels.
+        let mut txn = Transaction::new();
+        let img = self.wrench.api.generate_image_key();
+        txn.add_image(
+            img,
+            ImageDescriptor::new(64, 64, ImageFormat::BGRA8, ImageDescriptorFlags::empty()),
+            ImageData::new([0, 0, 128, 128].repeat(64 * 64)),
+            None,
+        );
+
+        let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+        builder.begin();
+        let info = self.make_common_properties(rect(0., 0., 64., 64.).to_box2d());
+        builder.push_rect(&info, info.clip_rect, ColorF::new(0.0, 0.0, 1.0, 1.0));
+        let mut info = self.make_common_properties(rect(0., 0., 64., 64.).to_box2d());
+        info.flags |= PrimitiveFlags::PREFER_COMPOSITOR_SURFACE;
+        builder.push_image(
+            &info,
+            info.clip_rect,
+            ImageRendering::Auto,
+            AlphaType::PremultipliedAlpha,
+            img,
+            ColorF::WHITE,
+        );
+
+        self.submit_dl(&mut Epoch(0), builder, txn);
+        self.rx.recv().unwrap();
+        let results = self.wrench.render();
+
+        let external = results.composite_surfaces
+            .iter()
+            .filter(|surface| surface.content == CompositeSurfaceContent::External)
+            .count();
+        assert_eq!(external, 1);
+
+        // The surface is blended over the blue background, rather than
+        // replacing it or being treated as straight alpha.
+        let pixels = self.wrench.renderer.read_pixels_rgba8(window_rect);
+        let expected = [128, 0, 127, 255];
+        for (actual, expected) in pixels[0 .. 4].iter().zip(expected.iter()) {
+            assert!((*actual as i32 - *expected as i32).abs() <= 2, "{:?}", &pixels[0 .. 4]);
+        }
+
+        let mut txn = Transaction::new();
+        txn.delete_image(img);
+        self.wrench.api.send_transaction(self.wrench.document_id, txn);
+
+        // Native compositors are told which external surfaces have to be
+        // blended with the content beneath them.
+        struct DummyHandler;
+
+        impl ExternalImageHandler for DummyHandler {
+            fn lock(&mut self, _key: ExternalImageId, _channel_index: u8) -> ExternalImage {
+                ExternalImage {
+                    uv: TexelRect::new(0.0, 0.0, 1.0, 1.0),
+                    source: ExternalImageSource::NativeTexture(0),
+                }
+            }
+            fn unlock(&mut self, _key: ExternalImageId, _channel_index: u8) {}
+        }
+
+        let compositor = MockCompositor::new();
+        let calls = Arc::clone(&compositor.calls);
+        let opts = webrender::WebRenderOptions {
+            compositor_config: compositor.into_config(),
+            ..Default::default()
+        };
+        let mut test = TestRenderer::new(self.window, DeviceIntSize::new(256, 256), opts);
+        test.renderer.set_external_image_handler(Box::new(DummyHandler));
+
+        let mut txn = Transaction::new();
+        let mut builder = test.builder();
+        for (i, flags) in [ImageDescriptorFlags::IS_OPAQUE, ImageDescriptorFlags::empty()].iter().enumerate() {
+            let img = test.api.generate_image_key();
+            txn.add_image(
+                img,
+                ImageDescriptor::new(64, 64, ImageFormat::BGRA8, *flags),
+                ImageData::External(ExternalImageData {
+                    id: ExternalImageId(i as u64),
+                    channel_index: 0,
+                    image_type: ExternalImageType::TextureHandle(ImageBufferKind::Texture2D),
+                    normalized_uvs: true,
+                }),
+                None,
+            );
+            let mut info = test.common_properties(rect(100. * i as f32, 0., 64., 64.).to_box2d());
+            info.flags |= PrimitiveFlags::PREFER_COMPOSITOR_SURFACE |
+                PrimitiveFlags::SUPPORTS_EXTERNAL_COMPOSITOR_SURFACE;
+            builder.push_image(
+                &info,
+                info.clip_rect,
+                ImageRendering::Auto,
+                AlphaType::PremultipliedAlpha,
+                img,
+                ColorF::WHITE,
+            );
+        }
+        test.render_display_list(Epoch(0), builder, txn);
+
+        let mut formats: Vec<_> = calls.lock().unwrap().iter().filter_map(|call| {
+            match *call {
+                CompositorCall::CreateExternalSurface(_, format) => Some(format),
+                _ => None,
+            }
+        }).collect();
+        formats.sort_by_key(|format| *format == CompositeSurfaceFormat::RgbaPremultiplied);
+        assert_eq!(formats, vec![CompositeSurfaceFormat::Rgba, CompositeSurfaceFormat::RgbaPremultiplied]);
+
+        test.deinit();
+    }
+
+    fn test_render_passes(&mut self) {
+        println!("\trender passes...");
+
+        let mut do_test = |filters: &[FilterOp]| {
+            let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+            builder.begin();
+            let space_and_clip = SpaceAndClipInfo::root_scroll(self.wrench.root_pipeline_id);
+            for filter in filters {
+                builder.push_simple_stacking_context_with_filters(
+                    LayoutPoint::zero(),
+                    space_and_clip.spatial_id,
+                    PrimitiveFlags::IS_BACKFACE_VISIBLE,
+                    &[*filter],
+                    &[],
+                    &[],
+                );
+            }
+            let info = self.make_common_properties(rect(100., 100., 100., 100.).to_box2d());
+            builder.push_rect(&info, info.clip_rect, ColorF::new(0.0, 0.0, 1.0, 1.0));
+            for _ in filters {
+                builder.pop_stacking_context();
+            }
+
+            self.submit_dl(&mut Epoch(0), builder, Transaction::new());
+            self.rx.recv().unwrap();
+            self.wrench.render().passes
+        };
+
+        let plain_passes = do_test(&[]);
+        let filter_passes = do_test(&[
+            FilterOp::Blur(4.0, 4.0),
+            FilterOp::Blur(2.0, 2.0),
+            FilterOp::Blur(1.0, 1.0),
+        ]);
+
+        assert!(filter_passes.len() > plain_passes.len());
+        assert!(filter_passes.iter().any(|pass| pass.color_target_count > 0));
+        for pass in &filter_passes {
+            assert_eq!(pass.color_target_count == 0, pass.color_target_pixels == 0);
+            assert_eq!(pass.alpha_target_count == 0, pass.alpha_target_pixels == 0);
+        }
+    }
+
+    #[cfg(feature = "gl_trace")]
+    fn test_invalidate_targets_on_pass_end(&mut self) {
+        println!("\tinvalidate targets on pass end...");
+        let trace_path = "gl-trace.txt";
+
+        // Render a frame with intermediate targets and GL tracing enabled,
+        // and count the framebuffer invalidations.
+        let mut count_invalidations = |invalidate: bool, blue: f32| {
+            let _ = std::fs::remove_file(trace_path);
+            self.wrench.renderer.set_invalidate_targets_on_pass_end(invalidate);
+
+            let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+            builder.begin();
+            let space_and_clip = SpaceAndClipInfo::root_scroll(self.wrench.root_pipeline_id);
+            for filter in &[FilterOp::Blur(4.0, 4.0), FilterOp::Blur(2.0, 2.0)] {
+                builder.push_simple_stacking_context_with_filters(
+                    LayoutPoint::zero(),
+                    space_and_clip.spatial_id,
+                    PrimitiveFlags::IS_BACKFACE_VISIBLE,
+                    &[*filter],
+                    &[],
+                    &[],
+                );
+            }
+            // Change the content each time so that the blurs are redrawn.
+            let info = self.make_common_properties(rect(100., 100., 100., 100.).to_box2d());
+            builder.push_rect(&info, info.clip_rect, ColorF::new(0.0, 0.0, blue, 1.0));
+            builder.pop_stacking_context();
+            builder.pop_stacking_context();
+            self.submit_dl(&mut Epoch(0), builder, Transaction::new());
+
+            let flags = self.wrench.renderer.get_debug_flags();
+            self.wrench.renderer.set_debug_flags(flags | DebugFlags::TRACE_GL_CALLS);
+            self.rx.recv().unwrap();
+            self.wrench.render();
+            self.wrench.renderer.set_debug_flags(flags);
+
+            let trace = std::fs::read_to_string(trace_path).expect("GL trace was not written");
+            std::fs::remove_file(trace_path).unwrap();
+            trace.lines().filter(|call| *call == "invalidate_framebuffer").count()
+        };
+
+        // Depth attachments are still invalidated when the option is off, so
+        // compare against the same content rendered with it on.
+        let with_invalidation = count_invalidations(true, 1.0);
+        let without_invalidation = count_invalidations(false, 0.5);
+        let with_invalidation_again = count_invalidations(true, 1.0);
+        assert!(without_invalidation < with_invalidation);
+        assert_eq!(with_invalidation, with_invalidation_again);
+
+        self.wrench.renderer.set_invalidate_targets_on_pass_end(true);
+    }
+
+    fn test_resource_only_transaction(&mut self) {
+        println!("\tresource only transaction...");
+        let window_size = self.window.get_inner_size();
+
+        let test_size = FramebufferIntSize::new(400, 400);
+        let window_rect = FramebufferIntRect::from_origin_and_size(
+            point2(0, window_size.height - test_size.height),
+            test_size,
+        );
+
+        // Upload the image ahead of any display list that uses it.
+        let img = self.wrench.api.generate_image_key();
+        let mut txn = Transaction::new();
+        txn.add_image(
+            img,
+            ImageDescriptor::new(64, 64, ImageFormat::BGRA8, ImageDescriptorFlags::IS_OPAQUE),
+            ImageData::new([0, 255, 0, 255].repeat(64 * 64)),
+            None,
+        );
+        self.wrench.api.send_transaction(self.wrench.document_id, txn);
+
+        // No frame should be published for it.
+        while let Ok(event) = self.rx.recv_timeout(Duration::from_millis(100)) {
+            match event {
+                NotifierEvent::WakeUp { composite_needed } => assert!(!composite_needed),
+                _ => panic!("unexpected notifier event"),
+            }
+        }
+
+        let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+        builder.begin();
+        let info = self.make_common_properties(rect(0., 0., 64., 64.).to_box2d());
+        builder.push_image(
+            &info,
+            info.clip_rect,
+            ImageRendering::Auto,
+            AlphaType::PremultipliedAlpha,
+            img,
+            ColorF::WHITE,
+        );
+
+        let mut epoch = Epoch(0);
+        self.submit_dl(&mut epoch, builder, Transaction::new());
+        let pixels = self.render_and_get_pixels(window_rect);
+
+        // The image was available when the display list was rendered.
+        let offset = ((test_size.height - 32) * test_size.width + 32) as usize * 4;
+        assert_eq!(&pixels[offset .. offset + 4], &[0, 255, 0, 255]);
+
+        // Large images get a standalone texture.
+        let image_bytes = 2048 * 2048 * 4;
+        let large_img = self.wrench.api.generate_image_key();
+        let mut txn = Transaction::new();
+        txn.add_image(
+            large_img,
+            ImageDescriptor::new(2048, 2048, ImageFormat::BGRA8, ImageDescriptorFlags::IS_OPAQUE),
+            ImageData::new(vec![255; image_bytes]),
+            None,
+        );
+        let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+        builder.begin();
+        builder.push_image(
+            &info,
+            info.clip_rect,
+            ImageRendering::Auto,
+            AlphaType::PremultipliedAlpha,
+            large_img,
+            ColorF::WHITE,
+        );
+        self.submit_dl(&mut epoch, builder, txn);
+        self.rx.recv().unwrap();
+        self.wrench.render();
+
+        let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+        builder.begin();
+        self.submit_dl(&mut epoch, builder, Transaction::new());
+        self.rx.recv().unwrap();
+        self.wrench.render();
+
+        // Deleting the images frees the standalone texture right away, rather
+        // than with the next frame.
+        let mut txn = Transaction::new();
+        txn.delete_image(img);
+        txn.delete_image(large_img);
+        self.wrench.api.send_transaction(self.wrench.document_id, txn);
+        match self.rx.recv_timeout(Duration::from_secs(1)) {
+            Ok(NotifierEvent::WakeUp { composite_needed }) => assert!(!composite_needed),
+            event => panic!("expected the resource updates, got {:?}", event),
+        }
+        self.wrench.renderer.update();
+        self.wrench.renderer.reset_peak_gpu_memory();
+        assert!(self.wrench.renderer.peak_gpu_memory().standalone_textures < image_bytes);
+    }
+
+    fn test_effective_clear_color(&mut self) {
+        println!("\teffective clear color...");
+        let window_size = self.window.get_inner_size();
+        let document_id = self.wrench.document_id;
+
+        let test_size = FramebufferIntSize::new(64, 64);
+        let window_rect = FramebufferIntRect::from_origin_and_size(
+            point2(0, window_size.height - test_size.height),
+            test_size,
+        );
+
+        // Nothing is known about a document the renderer has no frame for.
+        assert_eq!(self.wrench.renderer.effective_clear_color(DocumentId::INVALID), None);
+
+        // An empty display list leaves the framebuffer at the clear color.
+        let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+        builder.begin();
+        self.submit_dl(&mut Epoch(0), builder, Transaction::new());
+        self.render_and_get_pixels(window_rect);
+
+        // Without any overrides, the color from the options is used.
+        let options_color = ColorF::WHITE;
+        assert_eq!(self.wrench.renderer.effective_clear_color(document_id), Some(options_color));
+
+        // `set_clear_color` replaces the color from the options.
+        let renderer_color = ColorF::new(1.0, 0.0, 0.0, 1.0);
+        self.wrench.renderer.set_clear_color(renderer_color);
+        assert_eq!(self.wrench.renderer.effective_clear_color(document_id), Some(renderer_color));
+
+        // A per-document color takes precedence over both.
+        let document_color = ColorF::new(0.0, 0.0, 1.0, 1.0);
+        self.wrench.renderer.set_document_clear_color(document_id, Some(document_color));
+        assert_eq!(self.wrench.renderer.effective_clear_color(document_id), Some(document_color));
+
+        // The resolved color is the one the framebuffer is cleared to.
+        self.wrench.renderer.render(window_size, 0).unwrap();
+        let pixels = self.wrench.renderer.read_pixels_rgba8(window_rect);
+        assert_eq!(&pixels[0 .. 4], &[0, 0, 255, 255]);
+
+        // Removing the per-document color falls back to `set_clear_color`.
+        self.wrench.renderer.set_document_clear_color(document_id, None);
+        assert_eq!(self.wrench.renderer.effective_clear_color(document_id), Some(renderer_color));
+
+        self.wrench.renderer.set_clear_color(options_color);
+    }
+
+    fn test_output_alpha_mode(&mut self) {
+        println!("\toutput alpha mode...");
+        let window_size = self.window.get_inner_size();
+
+        let test_size = FramebufferIntSize::new(64, 64);
+        let window_rect = FramebufferIntRect::from_origin_and_size(
+            point2(0, window_size.height - test_size.height),
+            test_size,
+        );
+
+        // A half transparent red rect over a transparent clear color.
+        self.wrench.renderer.set_clear_color(ColorF::TRANSPARENT);
+        let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+        builder.begin();
+        let info = self.make_common_properties(rect(0., 0., 64., 64.).to_box2d());
+        builder.push_rect(&info, info.clip_rect, ColorF::new(1.0, 0.0, 0.0, 0.5));
+        self.submit_dl(&mut Epoch(0), builder, Transaction::new());
+
+        let assert_near = |actual: &[u8], expected: [u8; 4]| {
+            for (a, e) in actual.iter().zip(expected.iter()) {
+                assert!((*a as i32 - *e as i32).abs() <= 1, "{:?} != {:?}", actual, expected);
+            }
+        };
+
+        // By default the red channel is scaled by the alpha.
+        let pixels = self.render_and_get_pixels(window_rect);
+        assert_near(&pixels[0 .. 4], [128, 0, 0, 128]);
+
+        // With straight alpha the color is written unscaled.
+        self.wrench.renderer.set_output_alpha_mode(AlphaMode::Straight);
+        self.wrench.renderer.render(window_size, 0).unwrap();
+        let pixels = self.wrench.renderer.read_pixels_rgba8(window_rect);
+        assert_near(&pixels[0 .. 4], [255, 0, 0, 128]);
+
+        self.wrench.renderer.set_output_alpha_mode(AlphaMode::Premultiplied);
+        self.wrench.renderer.set_clear_color(ColorF::WHITE);
+    }
+
+    fn test_output_linear_depth(&mut self) {
+        println!("\toutput linear depth...");
+        let window_size = self.window.get_inner_size();
+        let pipeline_id = self.wrench.root_pipeline_id;
+        let root_space = SpaceAndClipInfo::root_scroll(pipeline_id);
+
+        let mut builder = DisplayListBuilder::new(pipeline_id);
+        builder.begin();
+
+        // Two overlapping rects, each in its own scrollable frame so that they
+        // end up in different picture cache slices. The second one is in front.
+        for (i, x) in [0., 50.].iter().enumerate() {
+            let space = builder.define_scroll_frame(
+                root_space.spatial_id,
+                ExternalScrollId(i as u64 + 1, pipeline_id),
+                LayoutRect::from_origin_and_size(LayoutPoint::new(*x, 0.), LayoutSize::new(100., 1000.)),
+                LayoutRect::from_origin_and_size(LayoutPoint::new(*x, 0.), LayoutSize::new(100., 100.)),
+                LayoutVector2D::zero(),
+                APZScrollGeneration::default(),
+                HasScrollLinkedEffect::No,
+                SpatialTreeItemKey::new(0, i as u64),
+            );
+            let rect = LayoutRect::from_origin_and_size(LayoutPoint::new(*x, 0.), LayoutSize::new(100., 100.));
+            let info = CommonItemProperties {
+                clip_rect: rect,
+                clip_chain_id: ClipChainId::INVALID,
+                spatial_id: space,
+                flags: PrimitiveFlags::default(),
+            };
+            builder.push_rect(&info, rect, ColorF::new(0.0, 1.0, 0.0, 1.0));
+        }
+
+        self.wrench.renderer.set_output_linear_depth(true);
+        self.submit_dl(&mut Epoch(0), builder, Transaction::new());
+        self.rx.recv().unwrap();
+        self.wrench.render();
+
+        let mut depth_at = |x: i32, y: i32| -> Option<f32> {
+            let rect = FramebufferIntRect::from_origin_and_size(
+                point2(x, window_size.height - y - 1),
+                size2(1, 1),
+            );
+            self.wrench.renderer.read_depth_pixels(rect).map(|depth| depth[0])
+        };
+
+        // Depth can't be read back on GLES.
+        if let (Some(back), Some(overlap), Some(front), Some(empty)) =
+            (depth_at(25, 50), depth_at(75, 50), depth_at(125, 50), depth_at(300, 300))
+        {
+            // The nearer rect has the smaller depth, and wins where they overlap.
+            assert!(front < back, "front {} should be nearer than back {}", front, back);
+            assert_eq!(overlap, front);
+            assert!(back < 1.0);
+            assert_eq!(empty, 1.0);
+        }
+
+        self.wrench.renderer.set_output_linear_depth(false);
+    }
+
+    #[cfg(feature = "gl_trace")]
+    fn test_gl_trace(&mut self) {
+        println!("\tgl trace...");
+        let trace_path = "gl-trace.txt";
+        let _ = std::fs::remove_file(trace_path);
+
+        let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+        builder.begin();
+        let info = self.make_common_properties(rect(0., 0., 100., 100.).to_box2d());
+        builder.push_rect(&info, info.clip_rect, ColorF::new(0.0, 1.0, 0.0, 1.0));
+
+        let mut epoch = Epoch(0);
+        self.submit_dl(&mut epoch, builder, Transaction::new());
+
+        let flags = self.wrench.renderer.get_debug_flags();
+        self.wrench.renderer.set_debug_flags(flags | DebugFlags::TRACE_GL_CALLS);
+        self.rx.recv().unwrap();
+        self.wrench.render();
+        self.wrench.renderer.set_debug_flags(flags);
+
+        let trace = std::fs::read_to_string(trace_path).expect("GL trace was not written");
+        assert!(trace.starts_with("# frame"));
+        assert!(trace.lines().any(|call| call == "draw_elements"));
+
+        std::fs::remove_file(trace_path).unwrap();
+    }
+
+    #[cfg(feature = "gl_trace")]
+    fn test_early_gl_flush(&mut self) {
+        println!("\tearly gl flush...");
+        let trace_path = "gl-trace.txt";
+
+        // Render a frame with GL tracing enabled and count its flushes.
+        let mut epoch = Epoch(0);
+        let mut count_flushes = |early_gl_flush: bool| {
+            let _ = std::fs::remove_file(trace_path);
+            self.wrench.renderer.set_early_gl_flush(early_gl_flush);
+
+            let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+            builder.begin();
+            let info = self.make_common_properties(rect(0., 0., 100., 100.).to_box2d());
+            builder.push_rect(&info, info.clip_rect, ColorF::new(0.0, 1.0, 0.0, 1.0));
+            self.submit_dl(&mut epoch, builder, Transaction::new());
+
+            let flags = self.wrench.renderer.get_debug_flags();
+            self.wrench.renderer.set_debug_flags(flags | DebugFlags::TRACE_GL_CALLS);
+            self.rx.recv().unwrap();
+            self.wrench.render();
+            self.wrench.renderer.set_debug_flags(flags);
+
+            let trace = std::fs::read_to_string(trace_path).expect("GL trace was not written");
+            std::fs::remove_file(trace_path).unwrap();
+            trace.lines().filter(|call| *call == "flush").count()
+        };
+
+        // The first frame may render content the others don't, so only
+        // compare frames with identical content.
+        count_flushes(true);
+        let with_early_flush = count_flushes(true);
+        let without_early_flush = count_flushes(false);
+        assert_eq!(without_early_flush + 1, with_early_flush);
+
+        self.wrench.renderer.set_early_gl_flush(true);
+    }
+
+    fn test_capture(&mut self) {
+        println!("\tcapture...");
+        let path = "../captures/test";
+        let layout_size = LayoutSize::new(400., 400.);
+        let dim = self.window.get_inner_size();
+        let window_rect = FramebufferIntRect::from_origin_and_size(
+            point2(0, dim.height - layout_size.height as i32),
+            size2(layout_size.width as i32, layout_size.height as i32),
+        );
+
+        // 1. render some scene
+
+        let mut txn = Transaction::new();
+        let image = self.wrench.api.generate_image_key();
+        txn.add_image(
+            image,
+            ImageDescriptor::new(1, 1, ImageFormat::BGRA8, ImageDescriptorFlags::IS_OPAQUE),
+            ImageData::new(vec![0xFF, 0, 0, 0xFF]),
+            None,
+        );
+
+        let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+        builder.begin();
+
+        let info = self.make_common_properties(rect(300.0, 70.0, 150.0, 50.0).to_box2d());
+        builder.push_image(
+            &info,
+            info.clip_rect,
+            ImageRendering::Auto,
+            AlphaType::PremultipliedAlpha,
+            image,
+            ColorF::WHITE,
+        );
+
+        let mut txn = Transaction::new();
+
+        txn.set_display_list(
+            Epoch(0),
+            builder.end(),
+        );
+        txn.generate_frame(0, RenderReasons::TESTING);
+
+        self.wrench.api.send_transaction(self.wrench.document_id, txn);
+
+        let pixels0 = self.render_and_get_pixels(window_rect);
+
+        // 2. capture it
+        self.wrench.api.save_capture(path.into(), CaptureBits::all());
+
+        // 3. set a different scene
+
+        builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+        builder.begin();
+
+        let mut txn = Transaction::new();
+        txn.set_display_list(
+            Epoch(1),
+            builder.end(),
+        );
+        self.wrench.api.send_transaction(self.wrench.document_id, txn);
+
+        // 4. load the first one
+
+        let mut documents = self.wrench.api.load_capture(path.into(), None);
+        let captured = documents.swap_remove(0);
+
+        // 5. render the built frame and compare
+        let pixels1 = self.render_and_get_pixels(window_rect);
+        self.compare_pixels(pixels0.clone(), pixels1, window_rect.size());
+
+        // 6. rebuild the scene and compare again
+        let mut txn = Transaction::new();
+        txn.set_root_pipeline(captured.root_pipeline_id.unwrap());
+        txn.generate_frame(0, RenderReasons::TESTING);
+        self.wrench.api.send_transaction(captured.document_id, txn);
+        let pixels2 = self.render_and_get_pixels(window_rect);
+        self.compare_pixels(pixels0, pixels2, window_rect.size());
+    }
+
+    fn test_display_list_metadata(&mut self) {
+        println!("\tdisplay list metadata...");
+        let path = "../captures/metadata";
+
+        let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+        builder.begin();
+        let info = self.make_common_properties(rect(0., 0., 100., 100.).to_box2d());
+        builder.push_rect(&info, info.clip_rect, ColorF::new(0.0, 1.0, 0.0, 1.0));
+        builder.set_metadata("url", "https://example.com/".to_string());
+        builder.set_metadata("build", "1234".to_string());
+        builder.set_metadata("build", "5678".to_string());
+        let (pipeline_id, display_list) = builder.end();
+
+        let check = |metadata: &BTreeMap<String, String>| {
+            assert_eq!(metadata.len(), 2);
+            assert_eq!(metadata.get("url").map(String::as_str), Some("https://example.com/"));
+            assert_eq!(metadata.get("build").map(String::as_str), Some("5678"));
+        };
+        check(&display_list.metadata());
+
+        // The metadata is part of the payload, as sent by embedders that
+        // serialize display lists.
+        let (payload, descriptor) = display_list.into_data();
+        let display_list = BuiltDisplayList::from_data(payload, descriptor);
+        check(&display_list.metadata());
+
+        // The builder starts the next display list without metadata.
+        builder.begin();
+        assert!(builder.end().1.metadata().is_empty());
+
+        // The metadata is saved in captures.
+        let mut txn = Transaction::new();
+        txn.set_display_list(Epoch(0), (pipeline_id, display_list));
+        txn.generate_frame(0, RenderReasons::TESTING);
+        self.wrench.api.send_transaction(self.wrench.document_id, txn);
+        self.rx.recv().unwrap();
+        self.wrench.render();
+
+        self.wrench.api.save_capture(path.into(), CaptureBits::all());
+        let mut documents = self.wrench.api.load_capture(path.into(), None);
+        let captured = documents.swap_remove(0);
+        let (_, metadata) = captured.display_list_metadata
+            .iter()
+            .find(|(id, _)| *id == pipeline_id)
+            .unwrap();
+        check(metadata);
+
+        self.rx.recv().unwrap();
+        self.wrench.render();
+    }
+
+    fn test_zero_height_window(&mut self) {
+        println!("\tzero height test...");
+
+        let layout_size = LayoutSize::new(120.0, 0.0);
+        let window_size = DeviceIntSize::new(layout_size.width as i32, layout_size.height as i32);
+        let doc_id = self.wrench.api.add_document(window_size);
+
+        let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+        builder.begin();
+        let info = self.make_common_properties(
+            LayoutRect::from_size(LayoutSize::new(100.0, 100.0))
+        );
+        builder.push_rect(
+            &info,
+            info.clip_rect,
+            ColorF::new(0.0, 1.0, 0.0, 1.0),
+        );
+
+        let mut txn = Transaction::new();
+        txn.set_root_pipeline(self.wrench.root_pipeline_id);
+        txn.set_display_list(
+            Epoch(1),
+            builder.end(),
+        );
+        txn.generate_frame(0, RenderReasons::TESTING);
+        self.wrench.api.send_transaction(doc_id, txn);
+
+        // Ensure we get a notification from rendering the above, even though
+        // there are zero visible pixels
+        assert!(self.rx.recv().unwrap() == NotifierEvent::WakeUp { composite_needed: true });
+    }
+
+
+    fn test_hit_testing(&mut self) {
+        println!("\thit testing test...");
+
+        let layout_size = LayoutSize::new(400., 400.);
+        let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+        builder.begin();
+
+        // Add a rectangle that covers the entire scene.
+        let space_and_clip = SpaceAndClipInfo::root_scroll(self.wrench.root_pipeline_id);
+        builder.push_hit_test(
+            LayoutRect::from_size(layout_size),
+            ClipChainId::INVALID,
+            space_and_clip.spatial_id,
+            PrimitiveFlags::default(),
+            (0, 1),
+        );
+
+        // Add a simple 100x100 rectangle at 100,0.
+        builder.push_hit_test(
+            LayoutRect::from_origin_and_size(
+                LayoutPoint::new(100., 0.),
+                LayoutSize::new(100., 100.)
+            ),
+            ClipChainId::INVALID,
+            space_and_clip.spatial_id,
+            PrimitiveFlags::default(),
+            (0, 2),
+        );
+
+        let make_rounded_complex_clip = |rect: &LayoutRect, radius: f32| -> ComplexClipRegion {
+            ComplexClipRegion::new(
+                *rect,
+                BorderRadius::uniform_size(LayoutSize::new(radius, radius)),
+                ClipMode::Clip
+            )
+        };
+
+        // Add a rectangle that is clipped by a rounded rect clip item.
+        let rect = LayoutRect::from_origin_and_size(LayoutPoint::new(100., 100.), LayoutSize::new(100., 100.));
+        let temp_clip_id = builder.define_clip_rounded_rect(
+            space_and_clip.spatial_id,
+            make_rounded_complex_clip(&rect, 20.),
+        );
+        let clip_chain_id = builder.define_clip_chain(None, vec![temp_clip_id]);
+        builder.push_hit_test(
+            rect,
+            clip_chain_id,
+            space_and_clip.spatial_id,
+            PrimitiveFlags::default(),
+            (0, 4),
+        );
+
+        // Add a rectangle that is clipped by a ClipChain containing a rounded rect.
+        let rect = LayoutRect::from_origin_and_size(LayoutPoint::new(200., 100.), LayoutSize::new(100., 100.));
+        let clip_id = builder.define_clip_rounded_rect(
+            space_and_clip.spatial_id,
+            make_rounded_complex_clip(&rect, 20.),
+        );
+        let clip_chain_id = builder.define_clip_chain(None, vec![clip_id]);
+        builder.push_hit_test(
+            rect,
+            clip_chain_id,
+            space_and_clip.spatial_id,
+            PrimitiveFlags::default(),
+            (0, 5),
+        );
+
+        let mut epoch = Epoch(0);
+        let txn = Transaction::new();
+        self.submit_dl(&mut epoch, builder, txn);
+
+        // We render to ensure that the hit tester is up to date with the current scene.
+        self.rx.recv().unwrap();
+        self.wrench.render();
+
+        let hit_test = |point: WorldPoint| -> HitTestResult {
+            self.wrench.api.hit_test(
+                self.wrench.document_id,
+                point,
+            )
+        };
+
+        let assert_hit_test = |point: WorldPoint, tags: Vec<ItemTag>| {
+            let result = hit_test(point);
+            assert_eq!(result.items.len(), tags.len());
+
+            for (hit_test_item, item_b) in result.items.iter().zip(tags.iter()) {
+                assert_eq!(hit_test_item.tag, *item_b);
+            }
+        };
+
+        // We should not have any hits outside the boundaries of the scene.
+        assert_hit_test(WorldPoint::new(-10., -10.), Vec::new());
+        assert_hit_test(WorldPoint::new(-10., 10.), Vec::new());
+        assert_hit_test(WorldPoint::new(450., 450.), Vec::new());
+        assert_hit_test(WorldPoint::new(100., 450.), Vec::new());
+
+        // The top left corner of the scene should only contain the background.
+        assert_hit_test(WorldPoint::new(50., 50.), vec![(0, 1)]);
+
+        // The middle of the normal rectangle should be hit.
+        assert_hit_test(WorldPoint::new(150., 50.), vec![(0, 2), (0, 1)]);
+
+        let test_rounded_rectangle = |point: WorldPoint, size: WorldSize, tag: ItemTag| {
+            // The cut out corners of the rounded rectangle should not be hit.
+            let top_left = point + WorldVector2D::new(5., 5.);
+            let bottom_right = point + size.to_vector() - WorldVector2D::new(5., 5.);
+
+            assert_hit_test(
+                WorldPoint::new(point.x + (size.width / 2.), point.y + (size.height / 2.)),
+                vec![tag, (0, 1)]
+            );
+
+            assert_hit_test(top_left, vec![(0, 1)]);
+            assert_hit_test(WorldPoint::new(bottom_right.x, top_left.y), vec![(0, 1)]);
+            assert_hit_test(WorldPoint::new(top_left.x, bottom_right.y), vec![(0, 1)]);
+            assert_hit_test(bottom_right, vec![(0, 1)]);
+        };
+
+        test_rounded_rectangle(WorldPoint::new(100., 100.), WorldSize::new(100., 100.), (0, 4));
+        test_rounded_rectangle(WorldPoint::new(200., 100.), WorldSize::new(100., 100.), (0, 5));
+    }
+
+    fn test_hit_testing_scroll_id(&mut self) {
+        println!("\thit testing scroll id test...");
+
+        let pipeline_id = self.wrench.root_pipeline_id;
+        let layout_size = LayoutSize::new(400., 400.);
+        let mut builder = DisplayListBuilder::new(pipeline_id);
+        builder.begin();
+
+        // A background in the root scroll frame of the pipeline.
+        let root_space = SpaceAndClipInfo::root_scroll(pipeline_id);
+        builder.push_hit_test(
+            LayoutRect::from_size(layout_size),
+            ClipChainId::INVALID,
+            root_space.spatial_id,
+            PrimitiveFlags::default(),
+            (0, 1),
+        );
+
+        // An item inside a scroll frame nested in another scroll frame.
+        let outer_scroll_id = ExternalScrollId(1, pipeline_id);
+        let outer_space = builder.define_scroll_frame(
+            root_space.spatial_id,
+            outer_scroll_id,
+            LayoutRect::from_size(LayoutSize::new(400., 1000.)),
+            LayoutRect::from_size(LayoutSize::new(300., 300.)),
+            LayoutVector2D::zero(),
+            APZScrollGeneration::default(),
+            HasScrollLinkedEffect::No,
+            SpatialTreeItemKey::new(0, 0),
+        );
+        let inner_scroll_id = ExternalScrollId(2, pipeline_id);
+        let inner_space = builder.define_scroll_frame(
+            outer_space,
+            inner_scroll_id,
+            LayoutRect::from_origin_and_size(LayoutPoint::new(100., 100.), LayoutSize::new(100., 500.)),
+            LayoutRect::from_origin_and_size(LayoutPoint::new(100., 100.), LayoutSize::new(100., 100.)),
+            LayoutVector2D::zero(),
+            APZScrollGeneration::default(),
+            HasScrollLinkedEffect::No,
+            SpatialTreeItemKey::new(0, 1),
+        );
+        builder.push_hit_test(
+            LayoutRect::from_origin_and_size(LayoutPoint::new(100., 100.), LayoutSize::new(100., 100.)),
+            ClipChainId::INVALID,
+            inner_space,
+            PrimitiveFlags::default(),
+            (0, 2),
+        );
+
+        // An item attached to the root reference frame, outside of any scroll frame.
+        builder.push_hit_test(
+            LayoutRect::from_origin_and_size(LayoutPoint::new(300., 300.), LayoutSize::new(50., 50.)),
+            ClipChainId::INVALID,
+            SpatialId::root_reference_frame(pipeline_id),
+            PrimitiveFlags::default(),
+            (0, 3),
+        );
+
+        let mut epoch = Epoch(0);
+        let txn = Transaction::new();
+        self.submit_dl(&mut epoch, builder, txn);
+
+        self.rx.recv().unwrap();
+        self.wrench.render();
+
+        let assert_hit_test = |point: WorldPoint, expected: Vec<(ItemTag, Option<ExternalScrollId>)>| {
+            let result = self.wrench.api.hit_test(self.wrench.document_id, point);
+            let items: Vec<_> = result.items.iter().map(|item| (item.tag, item.scroll_id)).collect();
+            assert_eq!(items, expected);
+        };
+
+        let root_scroll_id = Some(ExternalScrollId(0, pipeline_id));
+        assert_hit_test(
+            WorldPoint::new(50., 50.),
+            vec![((0, 1), root_scroll_id)],
+        );
+        assert_hit_test(
+            WorldPoint::new(150., 150.),
+            vec![((0, 2), Some(inner_scroll_id)), ((0, 1), root_scroll_id)],
+        );
+        assert_hit_test(
+            WorldPoint::new(325., 325.),
+            vec![((0, 3), None), ((0, 1), root_scroll_id)],
+        );
+    }
+
+    fn test_clear_cache(&mut self) {
+        println!("\tclear cache test...");
+
+        self.wrench.api.send_message(ApiMsg::DebugCommand(DebugCommand::ClearCaches(ClearCache::all())));
+
+        let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+        builder.begin();
+
+        let txn = Transaction::new();
+        let mut epoch = Epoch(0);
+        self.submit_dl(&mut epoch, builder, txn);
+
+        self.rx.recv().unwrap();
+        self.wrench.render();
+    }
+
+    fn test_texture_allocator_hook(&mut self) {
+        println!("\ttexture allocator hook...");
+
+        struct TrackingHook {
+            requests: Arc<Mutex<Vec<TextureAllocationRequest>>>,
+        }
+
+        impl TextureAllocatorHook for TrackingHook {
+            fn allocate(&mut self, request: &TextureAllocationRequest) -> Option<gl::GLuint> {
+                self.requests.lock().unwrap().push(*request);
+                None
+            }
+
+            fn release(&mut self, _texture_id: gl::GLuint) {
+                panic!("No texture was supplied by the hook");
+            }
+        }
+
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        self.wrench.renderer.set_texture_allocator_hook(Some(Box::new(TrackingHook {
+            requests: Arc::clone(&requests),
+        })));
+
+        // Free the shared textures in a frame of their own, so that they can't
+        // be reused for the allocation below.
+        self.wrench.api.send_message(ApiMsg::DebugCommand(DebugCommand::ClearCaches(ClearCache::all())));
+        let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+        builder.begin();
+        let mut epoch = Epoch(0);
+        self.submit_dl(&mut epoch, builder, Transaction::new());
+        self.rx.recv().unwrap();
+        self.wrench.render();
+        assert!(requests.lock().unwrap().is_empty());
+
+        let mut txn = Transaction::new();
+        let img = self.wrench.api.generate_image_key();
+        txn.add_image(
+            img,
+            ImageDescriptor::new(64, 64, ImageFormat::BGRA8, ImageDescriptorFlags::IS_OPAQUE),
+            ImageData::new(vec![255; 64 * 64 * 4]),
+            None,
+        );
+
+        let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+        builder.begin();
+        let info = self.make_common_properties(rect(0.0, 0.0, 64.0, 64.0).to_box2d());
+        builder.push_image(
+            &info,
+            info.clip_rect,
+            ImageRendering::Auto,
+            AlphaType::PremultipliedAlpha,
+            img,
+            ColorF::WHITE,
+        );
+        self.submit_dl(&mut epoch, builder, txn);
+        self.rx.recv().unwrap();
+        self.wrench.render();
+
+        // The shared cache texture holding the image was requested from the hook.
+        assert!(requests.lock().unwrap().iter().any(|request| {
+            request.format == ImageFormat::BGRA8 &&
+                request.size.width >= 64 &&
+                request.size.height >= 64
+        }));
+
+        self.wrench.renderer.set_texture_allocator_hook(None);
+
+        let mut txn = Transaction::new();
+        txn.delete_image(img);
+        self.wrench.api.send_transaction(self.wrench.document_id, txn);
+    }
+
+    fn test_explicit_native_present(&mut self) {
+        println!("\texplicit native present...");
+
+        let compositor = MockCompositor::new();
+        let calls = Arc::clone(&compositor.calls);
+        let opts = webrender::WebRenderOptions {
+            compositor_config: compositor.into_config(),
+            explicit_native_present: true,
+            ..Default::default()
+        };
+        let mut test = TestRenderer::new(self.window, DeviceIntSize::new(256, 256), opts);
+        let frame_calls = || -> Vec<CompositorCall> {
+            calls.lock().unwrap().iter()
+                .filter(|call| matches!(call, CompositorCall::BeginFrame | CompositorCall::EndFrame))
+                .cloned()
+                .collect()
+        };
+
+        // Drawing the frame doesn't present it.
+        test.render_rect(Epoch(0), ColorF::new(0.0, 0.0, 1.0, 1.0));
+        assert_eq!(frame_calls(), vec![CompositorCall::BeginFrame]);
+
+        assert!(test.renderer.present(test.document_id));
+        assert_eq!(frame_calls(), vec![CompositorCall::BeginFrame, CompositorCall::EndFrame]);
+
+        // There is nothing left to present.
+        assert!(!test.renderer.present(test.document_id));
+
+        // A frame that wasn't presented is presented before the next one is drawn.
+        test.renderer.render(test.size, 0).unwrap();
+        test.renderer.render(test.size, 0).unwrap();
+        assert_eq!(
+            frame_calls(),
+            vec![
+                CompositorCall::BeginFrame,
+                CompositorCall::EndFrame,
+                CompositorCall::BeginFrame,
+                CompositorCall::EndFrame,
+                CompositorCall::BeginFrame,
+            ],
+        );
+        assert!(test.renderer.present(test.document_id));
+
+        test.deinit();
+    }
+
+    fn test_compositor_capabilities(&mut self) {
+        println!("\tcompositor capabilities...");
+
+        let capabilities = CompositorCapabilities {
+            virtual_surface_size: 1024 * 1024,
+            max_update_rects: 4,
+            supports_surface_clip_radius: true,
+            ..Default::default()
+        };
+
+        let mut compositor = MockCompositor::new();
+        compositor.capabilities = capabilities;
+        let opts = webrender::WebRenderOptions {
+            compositor_config: compositor.into_config(),
+            ..Default::default()
+        };
+        let mut test = TestRenderer::new(self.window, DeviceIntSize::new(256, 256), opts);
+
+        // The draw compositor has no capabilities to report.
+        assert_eq!(self.wrench.renderer.compositor_capabilities(), None);
+        assert_eq!(test.renderer.compositor_capabilities(), Some(capabilities));
+
+        test.render_rect(Epoch(0), ColorF::new(0.0, 0.0, 1.0, 1.0));
+        assert_eq!(test.renderer.compositor_capabilities(), Some(capabilities));
+
+        // The override applies from the next frame onwards.
+        test.api.send_debug_cmd(DebugCommand::SetCompositorMaxUpdateRects(0));
+        test.render(Transaction::new());
+        assert_eq!(
+            test.renderer.compositor_capabilities(),
+            Some(CompositorCapabilities { max_update_rects: 0, ..capabilities }),
+        );
+
+        test.deinit();
+    }
+
+    fn test_frame_summary_sink(&mut self) {
+        println!("\tframe summary sink...");
+
+        let summaries = Arc::new(Mutex::new(Vec::new()));
+        let sink_summaries = Arc::clone(&summaries);
+
+        let opts = webrender::WebRenderOptions {
+            frame_summary_sink: Some(Arc::new(move |summary: FrameSummary| {
+                sink_summaries.lock().unwrap().push(summary);
+            })),
+            ..Default::default()
+        };
+        let mut test = TestRenderer::new(self.window, DeviceIntSize::new(256, 256), opts);
+
+        for i in 0 .. 3 {
+            test.render_rect(Epoch(i), ColorF::new(0.0, 0.0, i as f32 / 2.0, 1.0));
+        }
+
+        let summaries = summaries.lock().unwrap();
+        assert_eq!(summaries.len(), 3);
+        for pair in summaries.windows(2) {
+            assert!(pair[0].frame_id < pair[1].frame_id);
+        }
+        for summary in summaries.iter() {
+            assert_eq!(summary.document_id, test.document_id);
+        }
+
+        test.deinit();
+    }
+
+    fn test_frame_ids(&mut self) {
+        println!("\tframe ids...");
+
+        let opts = webrender::WebRenderOptions {
+            max_recorded_profiles: 4,
+            ..Default::default()
+        };
+        let mut test = TestRenderer::new(self.window, DeviceIntSize::new(256, 256), opts);
+
+        let mut last_frame_id = None;
+        for i in 0 .. 4 {
+            let results = test.render_rect(Epoch(i), ColorF::new(0.0, i as f32 / 3.0, 0.0, 1.0));
+
+            if let Some(last_frame_id) = last_frame_id {
+                assert_eq!(results.frame_id, last_frame_id + 1);
+            }
+            last_frame_id = Some(results.frame_id);
+
+            let (cpu_profiles, gpu_profiles) = test.renderer.get_frame_profiles();
+            assert_eq!(cpu_profiles.last().unwrap().frame_id, results.frame_id);
+            // GPU timings resolve later, so they can only refer to earlier frames.
+            for profile in &gpu_profiles {
+                assert!(profile.frame_id < results.frame_id);
+            }
+        }
+
+        test.deinit();
+    }
+
+    fn test_list_documents(&mut self) {
+        println!("\tlist documents...");
+
+        let size_a = DeviceIntSize::new(120, 80);
+        let size_b = DeviceIntSize::new(300, 200);
+        let document_a = self.wrench.api.add_document(size_a);
+        let document_b = self.wrench.api.add_document(size_b);
+
+        let documents = self.wrench.api.list_documents();
+        let find = |id| documents.iter().find(|doc| doc.id == id).cloned().unwrap();
+        assert_eq!(find(document_a).size, size_a);
+        assert_eq!(find(document_b).size, size_b);
+        assert!(!find(document_a).has_pending_frame);
+        assert!(!find(document_b).has_pending_frame);
+
+        // A published frame is pending until the renderer renders it.
+        let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+        builder.begin();
+        self.submit_dl(&mut Epoch(0), builder, Transaction::new());
+        self.rx.recv().unwrap();
+        let is_pending = |api: &RenderApi, id| {
+            api.list_documents().iter().find(|doc| doc.id == id).unwrap().has_pending_frame
+        };
+        assert!(is_pending(&self.wrench.api, self.wrench.document_id));
+        self.wrench.render();
+        assert!(!is_pending(&self.wrench.api, self.wrench.document_id));
+
+        // Rendering a frame that was superseded before it was rendered must
+        // leave the newer frame pending.
+        let mut epoch = Epoch(1);
+        let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+        builder.begin();
+        self.submit_dl(&mut epoch, builder, Transaction::new());
+        self.rx.recv().unwrap();
+        self.wrench.renderer.update();
+        let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+        builder.begin();
+        self.submit_dl(&mut epoch, builder, Transaction::new());
+        self.rx.recv().unwrap();
+        self.wrench.renderer.render(self.wrench.window_size, 0).unwrap();
+        assert!(is_pending(&self.wrench.api, self.wrench.document_id));
+        self.wrench.render();
+        assert!(!is_pending(&self.wrench.api, self.wrench.document_id));
+
+        self.wrench.api.delete_document(document_a);
+        self.wrench.api.delete_document(document_b);
+    }
+
+    fn test_gradient_templates(&mut self) {
+        println!("\tgradient templates...");
+
+        let template = self.wrench.api.create_gradient_template(
+            vec![
+                GradientStop { offset: 0.0, color: ColorF::new(1.0, 0.0, 0.0, 1.0) },
+                GradientStop { offset: 0.5, color: ColorF::new(0.0, 1.0, 0.0, 1.0) },
+                GradientStop { offset: 1.0, color: ColorF::new(0.0, 0.0, 1.0, 1.0) },
+            ],
+            GradientTemplateKind::Linear {
+                start_point: LayoutPoint::new(0.0, 0.0),
+                end_point: LayoutPoint::new(1.0, 1.0),
+                extend_mode: ExtendMode::Clamp,
+            },
+        );
+
+        let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+        builder.begin();
+
+        // Two items of the same size and a larger one referencing the same
+        // template.
+        for bounds in &[rect(0., 0., 100., 50.), rect(150., 0., 100., 50.), rect(0., 100., 200., 300.)] {
+            let bounds = bounds.to_box2d();
+            let info = self.make_common_properties(bounds);
+            builder.push_gradient_ref(&info, bounds, template);
+        }
+
+        self.submit_dl(&mut Epoch(0), builder, Transaction::new());
+        self.rx.recv().unwrap();
+        let stats = self.wrench.render().stats;
+
+        // The template was rendered once per item size rather than once per
+        // item, so the larger item isn't a stretched copy of the smaller ones.
+        assert_eq!(stats.render_task_cache_misses, 2);
+
+        self.wrench.api.delete_gradient_template(template);
+    }
+
+    fn test_last_frame_compositor_kind(&mut self) {
+        println!("\tlast frame compositor kind...");
+
+        // The wrench renderer always draws.
+        assert!(matches!(
+            self.wrench.renderer.last_frame_compositor_kind(),
+            CompositorKind::Draw { .. }
+        ));
+
+        let opts = webrender::WebRenderOptions {
+            compositor_config: MockCompositor::new().into_config(),
+            ..Default::default()
+        };
+        let mut test = TestRenderer::new(self.window, DeviceIntSize::new(256, 256), opts);
+
+        test.render_rect(Epoch(0), ColorF::new(0.0, 0.0, 1.0, 1.0));
+        assert!(matches!(test.renderer.last_frame_compositor_kind(), CompositorKind::Native { .. }));
+
+        // Switch to the draw compositor and back, checking the kind reported
+        // after each frame.
+        for &native in &[false, true] {
+            test.api.send_debug_cmd(DebugCommand::EnableNativeCompositor(native));
+            test.render(Transaction::new());
+            let kind = test.renderer.last_frame_compositor_kind();
+            assert_eq!(matches!(kind, CompositorKind::Native { .. }), native, "{:?}", kind);
+        }
+
+        test.deinit();
+    }
+
+    fn test_display_list_validation(&mut self) {
+        println!("\tdisplay list validation...");
+
+        let pipeline_id = self.wrench.root_pipeline_id;
+        let space_and_clip = SpaceAndClipInfo::root_scroll(pipeline_id);
+
+        // A list using only the clips and clip chains it defines is valid.
+        let mut builder = DisplayListBuilder::new(pipeline_id);
+        builder.begin();
+        let clip_id = builder.define_clip_rect(space_and_clip.spatial_id, rect(0., 0., 50., 50.).to_box2d());
+        let clip_chain_id = builder.define_clip_chain(None, [clip_id]);
+        let mut info = self.make_common_properties(rect(0., 0., 100., 100.).to_box2d());
+        info.clip_chain_id = clip_chain_id;
+        builder.push_rect(&info, info.clip_rect, ColorF::BLACK);
+        assert_eq!(builder.validate(), Ok(()));
+        builder.end();
+
+        // A clip chain referring to a clip that was never defined.
+        let mut builder = DisplayListBuilder::new(pipeline_id);
+        builder.begin();
+        let dangling = ClipId(1000, pipeline_id);
+        builder.define_clip_chain(None, [dangling]);
+        assert_eq!(
+            builder.validate(),
+            Err(vec![DisplayListError::DanglingClipId { index: 0, id: dangling }]),
+        );
+        builder.end();
+
+        // A rect with NaN coordinates.
+        let mut builder = DisplayListBuilder::new(pipeline_id);
+        builder.begin();
+        let info = self.make_common_properties(rect(0., 0., 100., 100.).to_box2d());
+        let bounds = LayoutRect::new(point2(0., 0.), point2(f32::NAN, 100.));
+        builder.push_rect(&info, bounds, ColorF::BLACK);
+        // NaN doesn't compare equal to itself, so match on the error kind.
+        match builder.validate() {
+            Err(ref errors) => match errors[..] {
+                [DisplayListError::NonFiniteRect { index: 0, .. }] => {}
+                _ => panic!("unexpected errors {:?}", errors),
+            },
+            Ok(()) => panic!("NaN bounds passed validation"),
+        }
+        builder.end();
+    }
+
+    fn test_export_glyph_atlas(&mut self) {
+        println!("\texport glyph atlas...");
+        let path = "../captures/glyph-atlas";
+
+        let font_bytes = std::fs::read("reftests/text/FreeSans.ttf").unwrap();
+        let font_key = self.wrench.font_key_from_bytes(font_bytes, 0);
+        let instance_key = self.wrench.add_font_instance(
+            font_key,
+            32.0,
+            FontInstanceFlags::empty(),
+            Some(FontRenderMode::Alpha),
+            SyntheticItalics::disabled(),
+            None,
+            None,
+            None,
+        );
+
+        let indices: Vec<u32> = self.wrench.api
+            .get_glyph_indices(font_key, "ABC")
+            .iter()
+            .filter_map(|idx| *idx)
+            .collect();
+        assert_eq!(indices.len(), 3);
+
+        let glyphs: Vec<GlyphInstance> = indices
+            .iter()
+            .enumerate()
+            .map(|(i, &index)| GlyphInstance {
+                index,
+                point: LayoutPoint::new(20.0 + 40.0 * i as f32, 60.0),
+            })
+            .collect();
+
+        let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+        builder.begin();
+        let info = self.make_common_properties(rect(0.0, 0.0, 200.0, 100.0).to_box2d());
+        builder.push_text(
+            &info,
+            info.clip_rect,
+            &glyphs,
+            instance_key,
+            ColorF::BLACK,
+            None,
+        );
+
+        let mut epoch = Epoch(0);
+        self.submit_dl(&mut epoch, builder, Transaction::new());
+        self.rx.recv().unwrap();
+        self.wrench.render();
+
+        // The listing is written once the renderer receives the glyph data
+        // back from the render backend.
+        self.wrench.renderer.export_glyph_atlas(path.into());
+        self.rx.recv().unwrap();
+        self.wrench.render();
+
+        let listing = std::fs::read_to_string(format!("{}/glyphs.txt", path)).unwrap();
+        let instance = format!("{:?}", instance_key);
+        let count = listing.lines().filter(|line| line.contains(&instance)).count();
+        assert_eq!(count, indices.len());
+
+        self.wrench.delete_font_instance(instance_key);
+    }
+
+    fn test_deterministic_glyph_positioning(&mut self) {
+        println!("\tdeterministic glyph positioning...");
+
+        let font_bytes = std::fs::read("reftests/text/FreeSans.ttf").unwrap();
+        let font_key = self.wrench.font_key_from_bytes(font_bytes, 0);
+
+        // Render modes that are normally hinted and measured differently.
+        let instance_keys: Vec<FontInstanceKey> = [FontRenderMode::Alpha, FontRenderMode::Mono]
+            .iter()
+            .map(|&render_mode| self.wrench.add_font_instance(
+                font_key,
+                17.0,
+                FontInstanceFlags::SUBPIXEL_POSITION | FontInstanceFlags::DETERMINISTIC_POSITIONING,
+                Some(render_mode),
+                SyntheticItalics::disabled(),
+                None,
+                None,
+                None,
+            ))
+            .collect();
+
+        let indices: Vec<u32> = self.wrench.api
+            .get_glyph_indices(font_key, "Deterministic")
+            .iter()
+            .filter_map(|idx| *idx)
+            .collect();
+
+        let advances: Vec<Vec<f32>> = instance_keys
+            .iter()
+            .map(|&key| {
+                self.wrench.api
+                    .get_glyph_dimensions(key, indices.clone())
+                    .iter()
+                    .map(|dims| dims.map_or(0.0, |dims| dims.advance))
+                    .collect()
+            })
+            .collect();
+
+        assert!(advances[0].iter().all(|advance| advance.fract() == 0.0));
+        assert_eq!(advances[0], advances[1]);
+
+        for key in instance_keys {
+            self.wrench.delete_font_instance(key);
+        }
+    }
+
+    fn test_reupload_texture_cache(&mut self) {
+        println!("\treupload texture cache...");
+
+        // Supplies the shared BGRA8 cache textures, so that the test knows
+        // their names and can write to them behind WebRender's back.
+        struct OwningHook {
+            gl: Rc<dyn gl::Gl>,
+            textures: Rc<std::cell::RefCell<Vec<gl::GLuint>>>,
+        }
+
+        impl TextureAllocatorHook for OwningHook {
+            fn allocate(&mut self, request: &TextureAllocationRequest) -> Option<gl::GLuint> {
+                if request.format != ImageFormat::BGRA8 || request.target != ImageBufferKind::Texture2D {
+                    return None;
+                }
+                let (internal_format, format) = match self.gl.get_type() {
+                    gl::GlType::Gl => (gl::RGBA8, gl::BGRA),
+                    gl::GlType::Gles => (gl::BGRA_EXT, gl::BGRA_EXT),
+                };
+                let mut bound = [0];
+                unsafe {
+                    self.gl.get_integer_v(gl::TEXTURE_BINDING_2D, &mut bound);
+                }
+                let id = self.gl.gen_textures(1)[0];
+                self.gl.bind_texture(gl::TEXTURE_2D, id);
+                self.gl.tex_image_2d(
+                    gl::TEXTURE_2D,
+                    0,
+                    internal_format as gl::GLint,
+                    request.size.width,
+                    request.size.height,
+                    0,
+                    format,
+                    gl::UNSIGNED_BYTE,
+                    None,
+                );
+                self.gl.bind_texture(gl::TEXTURE_2D, bound[0] as gl::GLuint);
+                self.textures.borrow_mut().push(id);
+                Some(id)
+            }
+
+            fn release(&mut self, texture_id: gl::GLuint) {
+                self.textures.borrow_mut().retain(|&id| id != texture_id);
+                self.gl.delete_textures(&[texture_id]);
+            }
+        }
+
+        let textures = Rc::new(std::cell::RefCell::new(Vec::new()));
+        self.wrench.renderer.set_texture_allocator_hook(Some(Box::new(OwningHook {
+            gl: self.window.clone_gl(),
+            textures: Rc::clone(&textures),
+        })));
+
+        // Tiles have to be redrawn every frame for corrupted cache contents to
+        // show up in the output.
+        let flags = self.wrench.renderer.get_debug_flags();
+        self.wrench.renderer.set_debug_flags(flags | DebugFlags::FORCE_PICTURE_INVALIDATION);
+
+        // Free the shared textures in a frame of their own, so that the image
+        // below is placed in a texture supplied by the hook.
+        self.wrench.api.send_message(ApiMsg::DebugCommand(DebugCommand::ClearCaches(ClearCache::all())));
+        let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+        builder.begin();
+        let mut epoch = Epoch(0);
+        self.submit_dl(&mut epoch, builder, Transaction::new());
+        self.rx.recv().unwrap();
+        self.wrench.render();
+
+        let window_size = self.window.get_inner_size();
+        let test_size = FramebufferIntSize::new(64, 64);
+        let window_rect = FramebufferIntRect::from_origin_and_size(
+            FramebufferIntPoint::new(0, window_size.height - test_size.height),
+            test_size,
+        );
+
+        let mut txn = Transaction::new();
+        let img = self.wrench.api.generate_image_key();
+        txn.add_image(
+            img,
+            ImageDescriptor::new(64, 64, ImageFormat::BGRA8, ImageDescriptorFlags::IS_OPAQUE),
+            ImageData::new([0, 255, 0, 255].repeat(64 * 64)),
+            None,
+        );
+
+        let mut draw_image = |harness: &mut Self, txn: Transaction| {
+            let mut builder = DisplayListBuilder::new(harness.wrench.root_pipeline_id);
+            builder.begin();
+            let info = harness.make_common_properties(rect(0.0, 0.0, 64.0, 64.0).to_box2d());
+            builder.push_image(
+                &info,
+                info.clip_rect,
+                ImageRendering::Auto,
+                AlphaType::PremultipliedAlpha,
+                img,
+                ColorF::WHITE,
+            );
+            harness.submit_dl(&mut epoch, builder, txn);
+            harness.render_and_get_pixels(window_rect)
+        };
+
+        let pixels = draw_image(self, txn);
+        assert!(!textures.borrow().is_empty());
+        assert_eq!(&pixels[0..4], &[0, 255, 0, 255]);
+
+        // Overwrite the contents of the cache textures with magenta. The
+        // device restores its bindings when the next frame begins.
+        {
+            let gl = self.wrench.renderer.device.gl();
+            let scissor = gl.is_enabled(gl::SCISSOR_TEST);
+            let fbo = gl.gen_framebuffers(1)[0];
+            gl.bind_framebuffer(gl::DRAW_FRAMEBUFFER, fbo);
+            gl.disable(gl::SCISSOR_TEST);
+            gl.clear_color(1.0, 0.0, 1.0, 1.0);
+            for &texture in textures.borrow().iter() {
+                gl.framebuffer_texture_2d(gl::DRAW_FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D, texture, 0);
+                gl.clear(gl::COLOR_BUFFER_BIT);
+            }
+            gl.bind_framebuffer(gl::DRAW_FRAMEBUFFER, 0);
+            gl.delete_framebuffers(&[fbo]);
+            if scissor != 0 {
+                gl.enable(gl::SCISSOR_TEST);
+            }
+        }
+
+        let pixels = draw_image(self, Transaction::new());
+        assert_eq!(&pixels[0..4], &[255, 0, 255, 255]);
+
+        // Reuploading restores the image from its template.
+        self.wrench.api.send_message(ApiMsg::DebugCommand(DebugCommand::ReuploadTextureCache));
+        let pixels = draw_image(self, Transaction::new());
+        assert_eq!(&pixels[0..4], &[0, 255, 0, 255]);
+
+        self.wrench.renderer.set_debug_flags(flags);
+        self.wrench.renderer.set_texture_allocator_hook(None);
+
+        let mut txn = Transaction::new();
+        txn.delete_image(img);
+        self.wrench.api.send_transaction(self.wrench.document_id, txn);
+    }
+
+    fn test_window_backdrop_blur(&mut self) {
+        println!("\twindow backdrop blur...");
+
+        struct BackdropHandler {
+            texture: gl::GLuint,
+        }
+
+        impl ExternalImageHandler for BackdropHandler {
+            fn lock(&mut self, _key: ExternalImageId, _channel_index: u8) -> ExternalImage {
+                ExternalImage {
+                    uv: TexelRect::new(0.0, 0.0, 1.0, 1.0),
+                    source: ExternalImageSource::NativeTexture(self.texture),
+                }
+            }
+            fn unlock(&mut self, _key: ExternalImageId, _channel_index: u8) {}
+        }
+
+        // The backdrop image handler replaces the renderer's, so use a renderer
+        // of our own rather than the shared wrench one.
+        let window_size = self.window.get_inner_size();
+        let mut test = TestRenderer::new(self.window, window_size, webrender::WebRenderOptions::default());
+
+        // A backdrop that is white on the left half of the window and black
+        // on the right half, with a sharp edge in the middle.
+        let texture = {
+            let device = &test.renderer.device;
+            let gl = device.gl();
+            let format_desc = device.gl_describe_format(ImageFormat::RGBA8);
+            let texture = gl.gen_textures(1)[0];
+            gl.bind_texture(gl::TEXTURE_2D, texture);
+            gl.tex_parameter_i(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as gl::GLint);
+            gl.tex_parameter_i(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as gl::GLint);
+            gl.tex_parameter_i(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as gl::GLint);
+            gl.tex_parameter_i(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as gl::GLint);
+            gl.tex_image_2d(
+                gl::TEXTURE_2D,
+                0,
+                format_desc.internal as gl::GLint,
+                2,
+                1,
+                0,
+                format_desc.external,
+                format_desc.pixel_type,
+                Some(&[255, 255, 255, 255, 0, 0, 0, 255]),
+            );
+            gl.bind_texture(gl::TEXTURE_2D, 0);
+            texture
+        };
+
+        test.renderer.set_external_image_handler(Box::new(BackdropHandler { texture }));
+        test.renderer.set_window_backdrop_image(Some(ExternalImageData {
+            id: ExternalImageId(0),
+            channel_index: 0,
+            image_type: ExternalImageType::TextureHandle(ImageBufferKind::Texture2D),
+            normalized_uvs: true,
+        }));
+
+        let row_rect = FramebufferIntRect::from_origin_and_size(
+            FramebufferIntPoint::new(0, 0),
+            FramebufferIntSize::new(window_size.width, 1),
+        );
+        let mid = (window_size.width / 2) as usize;
+
+        // The document is transparent, except for an opaque square at the top left.
+        let mut epoch = Epoch(0);
+        let mut render_row = |test: &mut TestRenderer| {
+            let mut builder = test.builder();
+            let info = test.common_properties(rect(0.0, 0.0, 64.0, 64.0).to_box2d());
+            builder.push_rect(&info, info.clip_rect, ColorF::new(0.0, 1.0, 0.0, 1.0));
+            test.render_display_list(epoch, builder, Transaction::new());
+            epoch.0 += 1;
+            test.renderer.read_pixels_rgba8(row_rect)
+        };
+
+        // Without a blur the edge of the backdrop stays sharp.
+        test.renderer.set_window_backdrop_blur(Some(0.0));
+        let pixels = render_row(&mut test);
+        assert_eq!(&pixels[(mid - 1) * 4 .. mid * 4], &[255, 255, 255, 255]);
+        assert_eq!(&pixels[mid * 4 .. (mid + 1) * 4], &[0, 0, 0, 255]);
+
+        test.renderer.set_window_backdrop_blur(Some(8.0));
+        let pixels = render_row(&mut test);
+        let red = |x: usize| pixels[x * 4];
+        assert!(red(0) > 250);
+        assert!(red(window_size.width as usize - 1) < 5);
+        assert!(red(mid - 1) > 16 && red(mid - 1) < 240);
+        assert!(red(mid) > 16 && red(mid) < 240);
+        assert!(red(mid - 8) > red(mid + 8));
+
+        // The document is composited over the backdrop.
+        let pixels = test.renderer.read_pixels_rgba8(FramebufferIntRect::from_origin_and_size(
+            FramebufferIntPoint::new(0, window_size.height - 1),
+            FramebufferIntSize::new(1, 1),
+        ));
+        assert_eq!(pixels, vec![0, 255, 0, 255]);
+
+        test.renderer.device.gl().delete_textures(&[texture]);
+        test.deinit();
+    }
+
+    fn test_gpu_cache_resized(&mut self) {
+        println!("\tgpu cache resized...");
+
+        let font_bytes = std::fs::read("reftests/text/FreeSans.ttf").unwrap();
+        let font_key = self.wrench.font_key_from_bytes(font_bytes, 0);
+        let instance_key = self.wrench.add_font_instance(
+            font_key,
+            12.0,
+            FontInstanceFlags::empty(),
+            Some(FontRenderMode::Alpha),
+            SyntheticItalics::disabled(),
+            None,
+            None,
+            None,
+        );
+        let index = self.wrench.api.get_glyph_indices(font_key, "x")[0].unwrap();
+
+        let mut epoch = Epoch(0);
+        let mut render = |harness: &mut Self, text_runs: usize| {
+            let mut builder = DisplayListBuilder::new(harness.wrench.root_pipeline_id);
+            builder.begin();
+            let info = harness.make_common_properties(rect(0.0, 0.0, 400.0, 200.0).to_box2d());
+            for run in 0 .. text_runs {
+                // Each run has enough glyphs to take up a whole row of the
+                // GPU cache, and is offset so that it isn't deduplicated.
+                let glyphs: Vec<GlyphInstance> = (0 .. 1500)
+                    .map(|i| GlyphInstance {
+                        index,
+                        point: LayoutPoint::new(10.0 + (i % 50) as f32 * 7.0, 20.0 + run as f32 * 2.0),
+                    })
+                    .collect();
+                builder.push_text(&info, info.clip_rect, &glyphs, instance_key, ColorF::BLACK, None);
+            }
+            harness.submit_dl(&mut epoch, builder, Transaction::new());
+            harness.rx.recv().unwrap();
+            harness.wrench.render().stats.gpu_cache_resized
+        };
+
+        assert!(!render(self, 0));
+        assert!(!render(self, 0));
+
+        // Many more rows than the GPU cache texture starts out with.
+        assert!(render(self, 64));
+        assert!(!render(self, 64));
+
+        self.wrench.delete_font_instance(instance_key);
+    }
+
+    fn test_render_scale(&mut self) {
+        println!("\trender scale...");
+
+        // A 100x80 document, green on the left and red on the right, rendered
+        // at half resolution into a framebuffer of the same size.
+        let size = DeviceIntSize::new(100, 80);
+
+        self.wrench.renderer.set_render_scale(0.5);
+
+        let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+        builder.begin();
+        let info = self.make_common_properties(rect(0., 0., 50., 80.).to_box2d());
+        builder.push_rect(&info, info.clip_rect, ColorF::new(0.0, 1.0, 0.0, 1.0));
+        let info = self.make_common_properties(rect(50., 0., 50., 80.).to_box2d());
+        builder.push_rect(&info, info.clip_rect, ColorF::new(1.0, 0.0, 0.0, 1.0));
+
+        let mut epoch = Epoch(0);
+        let mut txn = Transaction::new();
+        txn.set_document_view(DeviceIntRect::from_size(size));
+        self.submit_dl(&mut epoch, builder, txn);
+        self.rx.recv().unwrap();
+
+        self.wrench.renderer.update();
+        self.wrench.renderer.render(size, 0).unwrap();
+        let pixels = self.wrench.renderer.read_pixels_rgba8(
+            FramebufferIntRect::from_size(FramebufferIntSize::new(size.width, size.height))
+        );
+
+        assert_eq!(
+            self.wrench.renderer.render_scale_target_size(),
+            Some(DeviceIntSize::new(50, 40)),
+        );
+
+        let pixel_at = |x: usize, y: usize| {
+            let p = (y * size.width as usize + x) * 4;
+            &pixels[p .. p + 4]
+        };
+
+        // The document is upsampled to cover the whole framebuffer.
+        for &y in &[0, 40, 79] {
+            assert_eq!(pixel_at(0, y), &[0, 255, 0, 255]);
+            assert_eq!(pixel_at(40, y), &[0, 255, 0, 255]);
+            assert_eq!(pixel_at(60, y), &[255, 0, 0, 255]);
+            assert_eq!(pixel_at(99, y), &[255, 0, 0, 255]);
+        }
+
+        // The edge between the halves is filtered.
+        let edge = pixel_at(49, 40);
+        assert!(edge[0] > 0 && edge[0] < 255, "{:?}", edge);
+        assert!(edge[1] > 0 && edge[1] < 255, "{:?}", edge);
+
+        // Rebuild the scene at full resolution.
+        self.wrench.renderer.set_render_scale(1.0);
+
+        let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+        builder.begin();
+        let mut txn = Transaction::new();
+        txn.set_document_view(DeviceIntRect::from_size(self.window.get_inner_size()));
+        self.submit_dl(&mut epoch, builder, txn);
+        self.rx.recv().unwrap();
+        self.wrench.render();
+
+        assert_eq!(self.wrench.renderer.render_scale_target_size(), None);
+    }
+
+    fn test_max_clip_chain_depth(&mut self) {
+        println!("\tmax clip chain depth...");
+
+        let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+        builder.begin();
+
+        let root_space = SpatialId::root_scroll_node(self.wrench.root_pipeline_id);
+
+        // A shallow chain with a single clip.
+        let clip_id = builder.define_clip_rect(root_space, rect(0., 0., 300., 300.).to_box2d());
+        let shallow_chain_id = builder.define_clip_chain(None, [clip_id]);
+
+        // A chain of five nested clips, each with its own parent chain.
+        let mut deep_chain_id = None;
+        for i in 0 .. 5 {
+            let inset = i as f32 * 10.0;
+            let clip_id = builder.define_clip_rect(
+                root_space,
+                rect(inset, inset, 200. - inset, 200. - inset).to_box2d(),
+            );
+            deep_chain_id = Some(builder.define_clip_chain(deep_chain_id, [clip_id]));
+        }
+
+        let info = self.make_common_properties_with_clip_and_spatial(
+            rect(0., 0., 300., 300.).to_box2d(),
+            shallow_chain_id,
+            root_space,
+        );
+        builder.push_rect(&info, info.clip_rect, ColorF::new(0.0, 0.0, 1.0, 1.0));
+        let info = self.make_common_properties_with_clip_and_spatial(
+            rect(0., 0., 200., 200.).to_box2d(),
+            deep_chain_id.unwrap(),
+            root_space,
+        );
+        builder.push_rect(&info, info.clip_rect, ColorF::new(0.0, 1.0, 0.0, 1.0));
+
+        self.submit_dl(&mut Epoch(0), builder, Transaction::new());
+        self.rx.recv().unwrap();
+        self.wrench.render();
+
+        assert_eq!(self.wrench.api.max_clip_chain_depth(self.wrench.document_id), 5);
+    }
+
+    fn test_dirty_rect_hook(&mut self) {
+        println!("\tdirty rect hook...");
+
+        // The embedder damages the area below the changing rect, reaching past
+        // the edge of the framebuffer.
+        let embedder_damage = DeviceIntRect::new(point2(0, 100), point2(100, 400));
+        let opts = webrender::WebRenderOptions {
+            compositor_config: CompositorConfig::Draw {
+                max_partial_present_rects: 1,
+                draw_previous_partial_present_regions: false,
+                partial_present: None,
+            },
+            dirty_rect_hook: Some(Arc::new(move |rect: DeviceIntRect| {
+                rect.union(&embedder_damage)
+            })),
+            ..Default::default()
+        };
+        let size = DeviceIntSize::new(256, 256);
+        let mut test = TestRenderer::new(self.window, size, opts);
+
+        test.render_rect(Epoch(0), ColorF::new(1.0, 0.0, 0.0, 1.0));
+
+        // Fill the framebuffer with magenta, so that it is visible which parts
+        // the next frame redraws.
+        let gl = self.window.clone_gl();
+        gl.bind_framebuffer(gl::FRAMEBUFFER, 0);
+        gl.disable(gl::SCISSOR_TEST);
+        gl.clear_color(1.0, 0.0, 1.0, 1.0);
+        gl.clear(gl::COLOR_BUFFER_BIT);
+
+        // The reported rect is clamped to the framebuffer.
+        let results = test.render_rect(Epoch(0), ColorF::new(0.0, 1.0, 0.0, 1.0));
+        assert_eq!(
+            results.dirty_rects,
+            vec![DeviceIntRect::new(point2(0, 0), point2(100, 256))],
+        );
+
+        let pixels = test.renderer.read_pixels_rgba8(
+            FramebufferIntRect::from_size(FramebufferIntSize::new(size.width, size.height))
+        );
+        // Pixels are read back bottom-up, this takes a top-left origin.
+        let pixel_at = |x: usize, y: usize| {
+            let p = ((size.height as usize - 1 - y) * size.width as usize + x) * 4;
+            &pixels[p .. p + 4]
+        };
+
+        // The changed rect and the embedder's damage are redrawn, the rest of
+        // the framebuffer is left alone.
+        assert_eq!(pixel_at(50, 50), &[0, 255, 0, 255]);
+        assert_eq!(pixel_at(50, 150), &[255, 255, 255, 255]);
+        assert_eq!(pixel_at(200, 150), &[255, 0, 255, 255]);
+
+        test.deinit();
+    }
+
+    fn test_opaque_only(&mut self) {
+        println!("\topaque only...");
+
+        let window_size = self.window.get_inner_size();
+        let test_size = FramebufferIntSize::new(200, 100);
+        let window_rect = FramebufferIntRect::from_origin_and_size(
+            point2(0, window_size.height - test_size.height),
+            test_size,
+        );
+        let flags = self.wrench.renderer.get_debug_flags();
+
+        let mut epoch = Epoch(0);
+        let mut render = |harness: &mut Self, opaque_only: bool, extra_flags: DebugFlags| {
+            // Tiles are invalidated every frame so that the change of settings
+            // takes effect.
+            harness.wrench.renderer.set_opaque_only(opaque_only);
+            harness.wrench.api.send_debug_cmd(DebugCommand::SetFlags(
+                flags | extra_flags | DebugFlags::FORCE_PICTURE_INVALIDATION,
+            ));
+
+            let mut builder = DisplayListBuilder::new(harness.wrench.root_pipeline_id);
+            builder.begin();
+            let info = harness.make_common_properties(rect(0., 0., 100., 100.).to_box2d());
+            builder.push_rect(&info, info.clip_rect, ColorF::new(0.0, 1.0, 0.0, 1.0));
+            let info = harness.make_common_properties(rect(100., 0., 100., 100.).to_box2d());
+            builder.push_rect(&info, info.clip_rect, ColorF::new(1.0, 0.0, 0.0, 0.5));
+
+            harness.submit_dl(&mut epoch, builder, Transaction::new());
+            harness.render_and_get_pixels(window_rect)
+        };
+
+        // Pixels are read back bottom-up, this takes a top-left origin.
+        let pixel_at = |pixels: &[u8], x: usize, y: usize| {
+            let p = ((test_size.height as usize - 1 - y) * test_size.width as usize + x) * 4;
+            pixels[p .. p + 4].to_vec()
+        };
+
+        let pixels = render(self, false, DebugFlags::empty());
+        assert_eq!(pixel_at(&pixels, 50, 50), vec![0, 255, 0, 255]);
+        let blended = pixel_at(&pixels, 150, 50);
+        assert!(blended[1] > 0 && blended[1] < 255, "{:?}", blended);
+
+        // The translucent rect is only drawn by the alpha pass, so it goes
+        // missing, even if the opaque pass is disabled too.
+        for &extra_flags in &[DebugFlags::empty(), DebugFlags::DISABLE_OPAQUE_PASS] {
+            let pixels = render(self, true, extra_flags);
+            assert_eq!(pixel_at(&pixels, 50, 50), vec![0, 255, 0, 255]);
+            assert_eq!(pixel_at(&pixels, 150, 50), vec![255, 255, 255, 255]);
+        }
+
+        self.wrench.renderer.set_opaque_only(false);
+        self.wrench.api.send_debug_cmd(DebugCommand::SetFlags(flags));
+    }
+
+    fn test_backend_wait_time(&mut self) {
+        println!("\tbackend wait time...");
+
+        let mut epoch = Epoch(0);
+        let mut render = |harness: &mut Self, update_first: bool| {
+            // Updating before the frame is published makes the renderer wait
+            // on the backend from then on.
+            if update_first {
+                harness.wrench.renderer.update();
+            }
+            let mut builder = DisplayListBuilder::new(harness.wrench.root_pipeline_id);
+            builder.begin();
+            let info = harness.make_common_properties(rect(0., 0., 100., 100.).to_box2d());
+            builder.push_rect(&info, info.clip_rect, ColorF::new(0.0, 1.0, 0.0, 1.0));
+            harness.submit_dl(&mut epoch, builder, Transaction::new());
+            harness.rx.recv().unwrap();
+            harness.wrench.render().stats.backend_wait_time
+        };
+
+        render(self, false);
+
+        self.wrench.api.send_debug_cmd(DebugCommand::SimulateLongSceneBuild(100));
+
+        // A slow backend doesn't count as waiting if the frame is already
+        // published by the time the renderer updates.
+        assert_eq!(render(self, false), 0.0);
+
+        // The frame is published at least this long after it is requested,
+        // which is after the first update.
+        let wait_time = render(self, true);
+        assert!(wait_time >= 100.0, "{}", wait_time);
+
+        self.wrench.api.send_debug_cmd(DebugCommand::SimulateLongSceneBuild(0));
+
+        // Updates while no frame is requested are the embedder being idle,
+        // not waiting on the backend.
+        self.wrench.renderer.update();
+        std::thread::sleep(Duration::from_millis(200));
+        let wait_time = render(self, false);
+        assert!(wait_time < 200.0, "{}", wait_time);
+
+        // Rendering the same frame again doesn't wait on the backend.
+        assert_eq!(self.wrench.render().stats.backend_wait_time, 0.0);
+    }
+
+    fn test_async_shader_compile(&mut self) {
+        println!("\tasync shader compile...");
+
+        let opts = webrender::WebRenderOptions {
+            async_shader_compile: true,
+            clear_color: ColorF::new(0.0, 0.0, 1.0, 1.0),
+            ..Default::default()
+        };
+        let size = DeviceIntSize::new(256, 256);
+        let mut test = TestRenderer::new(self.window, size, opts);
+        assert!(!test.renderer.shaders_are_ready());
+
+        let mut builder = test.builder();
+        let info = test.common_properties(rect(0., 0., 100., 100.).to_box2d());
+        builder.push_rect(&info, info.clip_rect, ColorF::new(1.0, 0.0, 0.0, 1.0));
+        let mut txn = Transaction::new();
+        txn.set_root_pipeline(test.pipeline_id);
+        txn.set_display_list(Epoch(0), builder.end());
+        test.send_frame(txn);
+
+        // Pixels are read back bottom-up, this reads the top-left corner.
+        let window_rect = FramebufferIntRect::from_origin_and_size(
+            point2(0, size.height - 1),
+            size2(1, 1),
+        );
+
+        // The renderer can be used straight away, drawing whatever the
+        // shaders that are ready allow.
+        let mut frames = 0;
+        while !test.renderer.shaders_are_ready() {
+            test.renderer.update();
+            test.renderer.render(size, 0).unwrap();
+            frames += 1;
+            assert!(frames < 1000, "shaders never became ready");
+        }
+
+        // Once they are all linked, the document is redrawn in full.
+        test.rx.recv().unwrap();
+        test.renderer.update();
+        test.renderer.render(size, 0).unwrap();
+        let pixels = test.renderer.read_pixels_rgba8(window_rect);
+        assert_eq!(pixels, vec![255, 0, 0, 255]);
+
+        test.deinit();
+    }
+
+    fn test_primitive_isolation(&mut self) {
+        println!("\tprimitive isolation...");
+
+        let window_size = self.window.get_inner_size();
+        let test_size = FramebufferIntSize::new(200, 100);
+        let window_rect = FramebufferIntRect::from_origin_and_size(
+            point2(0, window_size.height - test_size.height),
+            test_size,
+        );
+        let flags = self.wrench.renderer.get_debug_flags();
+
+        let font_bytes = std::fs::read("reftests/text/FreeSans.ttf").unwrap();
+        let font_key = self.wrench.font_key_from_bytes(font_bytes, 0);
+        let instance_key = self.wrench.add_font_instance(
+            font_key,
+            32.0,
+            FontInstanceFlags::empty(),
+            Some(FontRenderMode::Alpha),
+            SyntheticItalics::disabled(),
+            None,
+            None,
+            None,
+        );
+        let index = self.wrench.api.get_glyph_indices(font_key, "x")[0].unwrap();
+
+        let mut epoch = Epoch(0);
+        let mut render = |harness: &mut Self, isolation: Option<IsolatedBatchKind>| {
+            harness.wrench.renderer.set_primitive_isolation(isolation);
+            // Tiles are invalidated every frame so that the isolation takes
+            // effect.
+            harness.wrench.api.send_debug_cmd(DebugCommand::SetFlags(
+                flags | DebugFlags::FORCE_PICTURE_INVALIDATION,
+            ));
+
+            let mut builder = DisplayListBuilder::new(harness.wrench.root_pipeline_id);
+            builder.begin();
+            let info = harness.make_common_properties(rect(0., 0., 100., 100.).to_box2d());
+            builder.push_rect(&info, info.clip_rect, ColorF::new(0.0, 1.0, 0.0, 1.0));
+            let info = harness.make_common_properties(rect(100., 0., 100., 100.).to_box2d());
+            let glyphs = [GlyphInstance { index, point: LayoutPoint::new(130.0, 60.0) }];
+            builder.push_text(&info, info.clip_rect, &glyphs, instance_key, ColorF::BLACK, None);
+
+            harness.submit_dl(&mut epoch, builder, Transaction::new());
+            let pixels = harness.render_and_get_pixels(window_rect);
+
+            // Pixels are read back bottom-up, this takes a top-left origin.
+            let rect_pixel = {
+                let p = ((test_size.height as usize - 1 - 50) * test_size.width as usize + 50) * 4;
+                pixels[p .. p + 4].to_vec()
+            };
+            let text_pixels = (0 .. test_size.height as usize)
+                .flat_map(|y| (100 .. test_size.width as usize).map(move |x| (x, y)))
+                .filter(|&(x, y)| pixels[(y * test_size.width as usize + x) * 4] < 128)
+                .count();
+            (rect_pixel, text_pixels)
+        };
+
+        let (rect_pixel, text_pixels) = render(self, None);
+        assert_eq!(rect_pixel, vec![0, 255, 0, 255]);
+        assert!(text_pixels > 0);
+
+        // Only the text is drawn.
+        let (rect_pixel, text_pixels) = render(self, Some(IsolatedBatchKind::Text));
+        assert_eq!(rect_pixel, vec![255, 255, 255, 255]);
+        assert!(text_pixels > 0);
+
+        // Neither is drawn.
+        let (rect_pixel, text_pixels) = render(self, Some(IsolatedBatchKind::ConicGradient));
+        assert_eq!(rect_pixel, vec![255, 255, 255, 255]);
+        assert_eq!(text_pixels, 0);
+
+        self.wrench.renderer.set_primitive_isolation(None);
+        self.wrench.api.send_debug_cmd(DebugCommand::SetFlags(flags));
+        self.wrench.delete_font_instance(instance_key);
+    }
+
+    fn test_render_document(&mut self) {
+        println!("\trender document...");
+
+        struct DamageRecorder(Arc<Mutex<Vec<DeviceIntRect>>>);
+
+        impl PartialPresentCompositor for DamageRecorder {
+            fn set_buffer_damage_region(&mut self, rects: &[DeviceIntRect]) {
+                self.0.lock().unwrap().extend_from_slice(rects);
+            }
+        }
+
+        let damage = Arc::new(Mutex::new(Vec::new()));
+        let opts = webrender::WebRenderOptions {
+            compositor_config: CompositorConfig::Draw {
+                max_partial_present_rects: 1,
+                draw_previous_partial_present_regions: true,
+                partial_present: Some(Box::new(DamageRecorder(damage.clone()))),
+            },
+            ..Default::default()
+        };
+        let size = DeviceIntSize::new(256, 256);
+        let mut test = TestRenderer::new(self.window, size, opts);
+
+        let rect_a = rect(0, 0, 100, 100).to_box2d();
+        let rect_b = rect(150, 150, 100, 100).to_box2d();
+        let documents = [
+            (test.document_id, PipelineId(0, 0), rect_a),
+            (test.api.add_document(size), PipelineId(1, 0), rect_b),
+        ];
+
+        let mut render = |index: usize, color: ColorF, buffer_age: usize| {
+            let (document_id, pipeline_id, bounds) = documents[index];
+            let mut builder = DisplayListBuilder::new(pipeline_id);
+            builder.begin();
+            let space_and_clip = SpaceAndClipInfo::root_scroll(pipeline_id);
+            let bounds = bounds.to_f32().cast_unit();
+            builder.push_rect(
+                &CommonItemProperties::new(bounds, space_and_clip),
+                bounds,
+                color,
+            );
+
+            let mut txn = Transaction::new();
+            txn.set_root_pipeline(pipeline_id);
+            txn.set_display_list(Epoch(0), builder.end());
+            txn.generate_frame(0, RenderReasons::TESTING);
+            test.api.send_transaction(document_id, txn);
+            test.rx.recv().unwrap();
+            test.renderer.update();
+            test.renderer.render_document(document_id, size, buffer_age).unwrap();
+            damage.lock().unwrap().pop()
+        };
+
+        render(0, ColorF::new(1.0, 0.0, 0.0, 1.0), 0);
+        render(1, ColorF::new(1.0, 0.0, 0.0, 1.0), 0);
+
+        assert_eq!(render(0, ColorF::new(0.0, 1.0, 0.0, 1.0), 1), Some(rect_a));
+        assert_eq!(render(1, ColorF::new(0.0, 1.0, 0.0, 1.0), 1), Some(rect_b));
+
+        // Each document's surface only carries its own damage from the
+        // previous frame, not the other document's.
+        assert_eq!(render(0, ColorF::new(0.0, 0.0, 1.0, 1.0), 2), Some(rect_a));
+        assert_eq!(render(1, ColorF::new(0.0, 0.0, 1.0, 1.0), 2), Some(rect_b));
+
+        test.deinit();
+    }
+
+    fn test_reserve_gpu_cache_height(&mut self) {
+        println!("\treserve gpu cache height...");
+
+        // Use a fresh renderer, so that the GPU cache isn't already taller
+        // than the reserved height.
+        let size = DeviceIntSize::new(400, 200);
+        let mut test = TestRenderer::new(self.window, size, Default::default());
+        let initial_height = test.renderer.gpu_cache_height();
+        assert!(initial_height < 1024);
+
+        let max_texture_size = test.renderer.get_max_texture_size();
+        assert!(matches!(
+            test.renderer.reserve_gpu_cache_height(max_texture_size + 1),
+            Err(RendererError::MaxTextureSize),
+        ));
+        assert_eq!(test.renderer.gpu_cache_height(), initial_height);
+
+        test.renderer.reserve_gpu_cache_height(1024).unwrap();
+        assert_eq!(test.renderer.gpu_cache_height(), 1024);
+        let reallocation_count = test.renderer.gpu_cache_reallocation_count();
+
+        // Already tall enough.
+        test.renderer.reserve_gpu_cache_height(512).unwrap();
+        assert_eq!(test.renderer.gpu_cache_height(), 1024);
+        assert_eq!(test.renderer.gpu_cache_reallocation_count(), reallocation_count);
+
+        let font_key = test.api.generate_font_key();
+        let instance_key = test.api.generate_font_instance_key();
+        let mut txn = Transaction::new();
+        txn.add_raw_font(font_key, std::fs::read("reftests/text/FreeSans.ttf").unwrap(), 0);
+        let mut options: FontInstanceOptions = Default::default();
+        options.render_mode = FontRenderMode::Alpha;
+        txn.add_font_instance(instance_key, font_key, 12.0, Some(options), None, Vec::new());
+        test.api.send_transaction(test.document_id, txn);
+        let index = test.api.get_glyph_indices(font_key, "x")[0].unwrap();
+
+        let mut builder = test.builder();
+        let info = test.common_properties(rect(0.0, 0.0, 400.0, 200.0).to_box2d());
+        for run in 0 .. 64 {
+            // Enough glyphs to take up a whole row of the GPU cache each.
+            let glyphs: Vec<GlyphInstance> = (0 .. 1500)
+                .map(|i| GlyphInstance {
+                    index,
+                    point: LayoutPoint::new(10.0 + (i % 50) as f32 * 7.0, 21.0 + run as f32 * 2.0),
+                })
+                .collect();
+            builder.push_text(&info, info.clip_rect, &glyphs, instance_key, ColorF::BLACK, None);
+        }
+        let mut txn = Transaction::new();
+        txn.set_root_pipeline(test.pipeline_id);
+        txn.set_display_list(Epoch(0), builder.end());
+        let results = test.render(txn);
+
+        // The updates fit in the reserved rows.
+        assert!(!results.stats.gpu_cache_resized);
+        assert_eq!(test.renderer.gpu_cache_height(), 1024);
+        assert_eq!(test.renderer.gpu_cache_reallocation_count(), reallocation_count);
+
+        test.deinit();
+    }
+
+    fn test_update_spatial_nodes(&mut self) {
+        println!("\tupdate spatial nodes...");
+
+        let window_size = self.window.get_inner_size();
+        let test_size = FramebufferIntSize::new(200, 100);
+        let window_rect = FramebufferIntRect::from_origin_and_size(
+            point2(0, window_size.height - test_size.height),
+            test_size,
+        );
+        // Pixels are read back bottom-up, this takes a top-left origin.
+        let pixel_at = |pixels: &[u8], x: usize, y: usize| {
+            let p = ((test_size.height as usize - 1 - y) * test_size.width as usize + x) * 4;
+            pixels[p .. p + 4].to_vec()
+        };
+
+        let space_and_clip = SpaceAndClipInfo::root_scroll(self.wrench.root_pipeline_id);
+        let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+        builder.begin();
+        let reference_frame = builder.push_reference_frame(
+            LayoutPoint::zero(),
+            space_and_clip.spatial_id,
+            TransformStyle::Flat,
+            PropertyBinding::Value(LayoutTransform::identity()),
+            ReferenceFrameKind::Transform {
+                is_2d_scale_translation: false,
+                should_snap: false,
+                paired_with_perspective: false,
+            },
+            SpatialTreeItemKey::new(0, 0),
+        );
+        let info = CommonItemProperties {
+            clip_rect: rect(0., 0., 50., 50.).to_box2d(),
+            clip_chain_id: space_and_clip.clip_chain_id,
+            spatial_id: reference_frame,
+            flags: PrimitiveFlags::default(),
+        };
+        builder.push_rect(&info, info.clip_rect, ColorF::new(0.0, 1.0, 0.0, 1.0));
+        builder.pop_reference_frame();
+
+        let mut epoch = Epoch(0);
+        self.submit_dl(&mut epoch, builder, Transaction::new());
+        let pixels = self.render_and_get_pixels(window_rect);
+        assert_eq!(pixel_at(&pixels, 25, 25), vec![0, 255, 0, 255]);
+
+        let update = |harness: &mut Self, transform: LayoutTransform| {
+            let mut txn = Transaction::new();
+            txn.update_spatial_nodes(vec![(reference_frame, transform)]);
+            txn.generate_frame(0, RenderReasons::TESTING);
+            harness.wrench.api.send_transaction(harness.wrench.document_id, txn);
+            harness.rx.recv().unwrap();
+            let results = harness.wrench.render();
+            // The frame is built from the existing scene.
+            assert!(!results.stats.full_paint);
+            harness.wrench.renderer.read_pixels_rgba8(window_rect)
+        };
+
+        let pixels = update(self, LayoutTransform::translation(100.0, 0.0, 0.0));
+        assert_eq!(pixel_at(&pixels, 25, 25), vec![255, 255, 255, 255]);
+        assert_eq!(pixel_at(&pixels, 125, 25), vec![0, 255, 0, 255]);
+
+        // The reference frame was axis-aligned in the display list, so it
+        // can't be rotated.
+        let pixels = update(self, LayoutTransform::rotation(0.0, 0.0, 1.0, Angle::degrees(45.0)));
+        assert_eq!(pixel_at(&pixels, 125, 25), vec![0, 255, 0, 255]);
+    }
+
+    fn test_read_picture_cache_tile(&mut self) {
+        println!("\tread picture cache tile...");
+
+        let window_size = self.window.get_inner_size();
+        let test_size = FramebufferIntSize::new(100, 100);
+        let window_rect = FramebufferIntRect::from_origin_and_size(
+            point2(0, window_size.height - test_size.height),
+            test_size,
+        );
+
+        let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+        builder.begin();
+        for &(bounds, color) in &[
+            (rect(0., 0., 100., 100.), ColorF::WHITE),
+            (rect(10., 10., 30., 30.), ColorF::new(1.0, 0.0, 0.0, 1.0)),
+            (rect(50., 50., 40., 40.), ColorF::new(0.0, 0.0, 1.0, 0.5)),
+        ] {
+            let info = self.make_common_properties(bounds.to_box2d());
+            builder.push_rect(&info, info.clip_rect, color);
+        }
+        self.submit_dl(&mut Epoch(0), builder, Transaction::new());
+        self.rx.recv().unwrap();
+        let results = self.wrench.render();
+        let pixels = self.wrench.renderer.read_pixels_rgba8(window_rect);
+
+        // The content is all in the first tile.
+        let surface = results.composite_surfaces
+            .iter()
+            .find(|surface| surface.content == CompositeSurfaceContent::Tile)
+            .unwrap();
+        assert_eq!(surface.device_rect, rect(0., 0., 100., 100.).to_box2d());
+        let document_id = self.wrench.document_id;
+        let tile = self.wrench.renderer
+            .read_picture_cache_tile(document_id, surface.tile_id.unwrap())
+            .unwrap();
+        assert_eq!(tile.len(), pixels.len());
+
+        // Tile rows start at the top, while pixels are read back bottom-up.
+        let row_size = test_size.width as usize * 4;
+        for y in 0 .. test_size.height as usize {
+            let row = (test_size.height as usize - 1 - y) * row_size;
+            assert_eq!(&tile[y * row_size .. (y + 1) * row_size], &pixels[row .. row + row_size]);
+        }
+
+        let unknown_tile = TileId(usize::MAX);
+        assert!(self.wrench.renderer.read_picture_cache_tile(document_id, unknown_tile).is_none());
+    }
+
+    fn test_read_pixels_format(&mut self) {
+        println!("\tread pixels format...");
+
+        let window_size = self.window.get_inner_size();
+        let test_size = FramebufferIntSize::new(10, 10);
+        let window_rect = FramebufferIntRect::from_origin_and_size(
+            point2(0, window_size.height - test_size.height),
+            test_size,
+        );
+
+        let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+        builder.begin();
+        let info = self.make_common_properties(rect(0., 0., 10., 10.).to_box2d());
+        builder.push_rect(&info, info.clip_rect, ColorF::new(0.0, 0.0, 1.0, 1.0));
+        self.submit_dl(&mut Epoch(0), builder, Transaction::new());
+
+        let pixels = self.render_and_get_pixels(window_rect);
+        assert!(pixels.chunks(4).all(|p| p == [0, 0, 255, 255]));
+
+        let mut pixels = vec![0; 10 * 10 * 4];
+        self.wrench.renderer.read_pixels_into(window_rect, ImageFormat::BGRA8, &mut pixels).unwrap();
+        assert!(pixels.chunks(4).all(|p| p == [255, 0, 0, 255]));
+
+        let mut pixels = vec![0; 10 * 10 * 4 - 1];
+        assert!(matches!(
+            self.wrench.renderer.read_pixels_into(window_rect, ImageFormat::RGBA8, &mut pixels),
+            Err(RendererError::ReadPixelsSizeMismatch),
+        ));
+    }
+
+    fn test_max_render_targets_per_frame(&mut self) {
+        println!("\tmax render targets per frame...");
+
+        let mut do_test = |max_render_targets_per_frame: Option<usize>| {
+            let opts = webrender::WebRenderOptions {
+                max_render_targets_per_frame,
+                clear_color: ColorF::WHITE,
+                ..Default::default()
+            };
+            let mut test = TestRenderer::new(self.window, DeviceIntSize::new(300, 100), opts);
+
+            // Nested blur chains of different depths.
+            let mut builder = test.builder();
+            let space_and_clip = SpaceAndClipInfo::root_scroll(test.pipeline_id);
+            for depth in 1 .. 4 {
+                for _ in 0 .. depth {
+                    builder.push_simple_stacking_context_with_filters(
+                        LayoutPoint::zero(),
+                        space_and_clip.spatial_id,
+                        PrimitiveFlags::IS_BACKFACE_VISIBLE,
+                        &[FilterOp::Blur(2.0, 2.0)],
+                        &[],
+                        &[],
+                    );
+                }
+                let x = 25.0 + (depth - 1) as f32 * 100.0;
+                let info = test.common_properties(rect(x, 25., 50., 50.).to_box2d());
+                builder.push_rect(&info, info.clip_rect, ColorF::new(0.0, 0.0, 1.0, 1.0));
+                for _ in 0 .. depth {
+                    builder.pop_stacking_context();
+                }
+            }
+
+            let results = test.render_display_list(Epoch(0), builder, Transaction::new());
+            let pixels = test.renderer.read_pixels_rgba8(
+                FramebufferIntRect::from_size(size2(test.size.width, test.size.height)),
+            );
+
+            test.deinit();
+
+            (results.stats.render_target_texture_count, pixels)
+        };
+
+        let (_, unlimited_pixels) = do_test(None);
+        let (limited_count, limited_pixels) = do_test(Some(2));
+
+        assert!(limited_count > 0);
+        assert!(limited_count <= 2);
+        assert_eq!(limited_pixels, unlimited_pixels);
+    }
+
+    fn test_show_clip_bounds(&mut self) {
+        println!("\tshow clip bounds...");
+
+        let window_size = self.window.get_inner_size();
+        let test_size = FramebufferIntSize::new(200, 200);
+        let window_rect = FramebufferIntRect::from_origin_and_size(
+            point2(0, window_size.height - test_size.height),
+            test_size,
+        );
+        let mut epoch = Epoch(0);
+        let mut render = |harness: &mut Self, show_clip_bounds: bool| {
+            harness.wrench.api.send_debug_cmd(DebugCommand::SetShowClipBounds(show_clip_bounds));
+
+            let mut builder = DisplayListBuilder::new(harness.wrench.root_pipeline_id);
+            builder.begin();
+            let space_and_clip = SpaceAndClipInfo::root_scroll(harness.wrench.root_pipeline_id);
+            let outer_clip = builder.define_clip_rect(
+                space_and_clip.spatial_id,
+                rect(20., 20., 160., 160.).to_box2d(),
+            );
+            let outer_chain = builder.define_clip_chain(None, [outer_clip]);
+            let inner_clip = builder.define_clip_rounded_rect(
+                space_and_clip.spatial_id,
+                ComplexClipRegion::new(
+                    rect(60., 60., 80., 80.).to_box2d(),
+                    BorderRadius::uniform(10.0),
+                    ClipMode::Clip,
+                ),
+            );
+            let inner_chain = builder.define_clip_chain(Some(outer_chain), [inner_clip]);
+            builder.push_rect(
+                &CommonItemProperties {
+                    clip_rect: rect(0., 0., 200., 200.).to_box2d(),
+                    clip_chain_id: inner_chain,
+                    spatial_id: space_and_clip.spatial_id,
+                    flags: PrimitiveFlags::default(),
+                },
+                rect(0., 0., 200., 200.).to_box2d(),
+                ColorF::new(1.0, 0.0, 0.0, 1.0),
+            );
+
+            // An offscreen primitive whose clip extends into the visible
+            // area. It is culled, so its clip isn't used this frame.
+            let offscreen_clip = builder.define_clip_rect(
+                space_and_clip.spatial_id,
+                rect(-480., 10., 670., 170.).to_box2d(),
+            );
+            let offscreen_chain = builder.define_clip_chain(None, [offscreen_clip]);
+            builder.push_rect(
+                &CommonItemProperties {
+                    clip_rect: rect(-500., 0., 100., 100.).to_box2d(),
+                    clip_chain_id: offscreen_chain,
+                    spatial_id: space_and_clip.spatial_id,
+                    flags: PrimitiveFlags::default(),
+                },
+                rect(-500., 0., 100., 100.).to_box2d(),
+                ColorF::new(1.0, 0.0, 0.0, 1.0),
+            );
+
+            harness.submit_dl(&mut epoch, builder, Transaction::new());
+            harness.render_and_get_pixels(window_rect)
+        };
+
+        // Pixels are read back bottom-up, this takes a top-left origin.
+        let pixel_at = |pixels: &[u8], x: usize, y: usize| {
+            let p = ((test_size.height as usize - 1 - y) * test_size.width as usize + x) * 4;
+            pixels[p .. p + 4].to_vec()
+        };
+
+        let pixels = render(self, false);
+        assert_eq!(pixel_at(&pixels, 100, 20), [255, 255, 255, 255]);
+        assert_eq!(pixel_at(&pixels, 100, 60), [255, 0, 0, 255]);
+
+        // The outer rect clip is outlined in green, the nested rounded rect
+        // clip in blue.
+        let pixels = render(self, true);
+        assert_eq!(pixel_at(&pixels, 100, 20), [0, 255, 0, 255]);
+        assert_eq!(pixel_at(&pixels, 100, 60), [0, 0, 255, 255]);
+        assert_eq!(pixel_at(&pixels, 100, 100), [255, 0, 0, 255]);
+        // The right edge of the offscreen primitive's clip isn't outlined.
+        assert_eq!(pixel_at(&pixels, 189, 100), [255, 255, 255, 255]);
+
+        self.wrench.api.send_debug_cmd(DebugCommand::SetShowClipBounds(false));
+    }
+
+    fn test_native_surface_dirty_rects(&mut self) {
+        println!("\tnative surface dirty rects...");
+
+        let compositor = MockCompositor::new();
+        let calls = Arc::clone(&compositor.calls);
+        let opts = webrender::WebRenderOptions {
+            compositor_config: compositor.into_config(),
+            ..Default::default()
+        };
+        let mut test = TestRenderer::new(self.window, DeviceIntSize::new(256, 256), opts);
+
+        let img = test.api.generate_image_key();
+        let descriptor = ImageDescriptor::new(64, 64, ImageFormat::BGRA8, ImageDescriptorFlags::IS_OPAQUE);
+        let mut txn = Transaction::new();
+        txn.add_image(img, descriptor, ImageData::new(vec![255; 64 * 64 * 4]), None);
+
+        let mut builder = test.builder();
+        let rect_info = test.common_properties(rect(150., 150., 50., 50.).to_box2d());
+        builder.push_rect(&rect_info, rect_info.clip_rect, ColorF::new(1.0, 0.0, 0.0, 1.0));
+        let mut info = test.common_properties(rect(20., 20., 100., 100.).to_box2d());
+        info.flags |= PrimitiveFlags::PREFER_COMPOSITOR_SURFACE;
+        builder.push_image(
+            &info,
+            info.clip_rect,
+            ImageRendering::Auto,
+            AlphaType::PremultipliedAlpha,
+            img,
+            ColorF::WHITE,
+        );
+
+        // Renders a frame and returns the reported dirty rects, after
+        // checking that they match the tiles the compositor was asked to bind.
+        let render = |test: &mut TestRenderer, txn: Transaction| {
+            calls.lock().unwrap().clear();
+            let results = test.render(txn);
+            let binds: Vec<_> = calls.lock().unwrap().iter().filter_map(|call| match *call {
+                CompositorCall::Bind(id, dirty_rect) => Some((id, dirty_rect)),
+                _ => None,
+            }).collect();
+            assert_eq!(results.native_surface_dirty_rects, binds);
+            results.native_surface_dirty_rects
+        };
+
+        // The picture cache tile with the rect and the native surface the
+        // promoted image is rasterized into are both reported, along with
+        // the rect that was drawn in each.
+        txn.set_root_pipeline(test.pipeline_id);
+        txn.set_display_list(Epoch(0), builder.end());
+        let dirty_rects = render(&mut test, txn);
+        let surface_id = calls.lock().unwrap().iter().find_map(|call| match *call {
+            CompositorCall::CreateExternalSurface(id, _) => Some(id),
+            _ => None,
+        }).unwrap();
+        let tile_id = NativeTileId { surface_id, x: 0, y: 0 };
+        let dirty_rect = DeviceIntRect::from_size(DeviceIntSize::new(100, 100));
+        assert!(dirty_rects.contains(&(tile_id, dirty_rect)));
+        assert!(dirty_rects.iter().any(|(id, _)| id.surface_id != surface_id));
+
+        // Nothing is drawn while the content is unchanged.
+        let dirty_rects = render(&mut test, Transaction::new());
+        assert!(dirty_rects.is_empty());
+
+        // Updating the image updates the same surface.
+        let mut txn = Transaction::new();
+        txn.update_image(img, descriptor, ImageData::new(vec![0; 64 * 64 * 4]), &DirtyRect::All);
+        let dirty_rects = render(&mut test, txn);
+        assert!(dirty_rects.contains(&(tile_id, dirty_rect)));
+
+        // Nothing is reported when compositing with WR.
+        test.api.send_debug_cmd(DebugCommand::EnableNativeCompositor(false));
+        let mut txn = Transaction::new();
+        txn.update_image(img, descriptor, ImageData::new(vec![255; 64 * 64 * 4]), &DirtyRect::All);
+        let dirty_rects = render(&mut test, txn);
+        assert!(dirty_rects.is_empty());
+
+        test.deinit();
+    }
+
+    fn test_native_surface_clip_radius(&mut self) {
+        println!("\tnative surface clip radius...");
+
+        for &supports_surface_clip_radius in &[false, true] {
+            let mut compositor = MockCompositor::new();
+            compositor.capabilities.supports_surface_clip_radius = supports_surface_clip_radius;
+            let calls = Arc::clone(&compositor.calls);
+            let opts = webrender::WebRenderOptions {
+                compositor_config: compositor.into_config(),
+                ..Default::default()
+            };
+            let mut test = TestRenderer::new(self.window, DeviceIntSize::new(256, 256), opts);
+
+            let img = test.api.generate_image_key();
+            let descriptor = ImageDescriptor::new(64, 64, ImageFormat::BGRA8, ImageDescriptorFlags::IS_OPAQUE);
+            let mut txn = Transaction::new();
+            txn.add_image(img, descriptor, ImageData::new(vec![255; 64 * 64 * 4]), None);
+
+            // An image that prefers a compositor surface, with its corners
+            // rounded by a clip that exactly covers it.
+            let bounds = rect(20., 20., 100., 100.).to_box2d();
+            let mut builder = test.builder();
+            let root_space_and_clip = SpaceAndClipInfo::root_scroll(test.pipeline_id);
+            let clip_id = builder.define_clip_rounded_rect(
+                root_space_and_clip.spatial_id,
+                ComplexClipRegion::new(bounds, BorderRadius::uniform(16.), ClipMode::Clip),
+            );
+            let clip_chain_id = builder.define_clip_chain(None, [clip_id]);
+            let info = CommonItemProperties {
+                clip_rect: bounds,
+                clip_chain_id,
+                spatial_id: root_space_and_clip.spatial_id,
+                flags: PrimitiveFlags::PREFER_COMPOSITOR_SURFACE,
+            };
+            builder.push_image(
+                &info,
+                bounds,
+                ImageRendering::Auto,
+                AlphaType::PremultipliedAlpha,
+                img,
+                ColorF::WHITE,
+            );
+
+            test.render_display_list(Epoch(0), builder, txn);
+
+            let radii: Vec<ClipRadius> = calls.lock().unwrap().iter().filter_map(|call| {
+                match *call {
+                    CompositorCall::AddSurface(_, _, clip_radius) => Some(clip_radius),
+                    _ => None,
+                }
+            }).collect();
+
+            if supports_surface_clip_radius {
+                // The compositor rounds the corners of the image's own surface.
+                let clip_radius = ClipRadius {
+                    top_left: 16,
+                    top_right: 16,
+                    bottom_left: 16,
+                    bottom_right: 16,
+                };
+                assert!(radii.contains(&clip_radius), "{:?}", radii);
+            } else {
+                // The image is drawn into the picture cache tiles instead.
+                assert!(radii.iter().all(ClipRadius::is_empty), "{:?}", radii);
+            }
+
+            test.deinit();
+        }
+    }
+}
+
+/// Signals a channel whenever a frame is ready. Used by the renderers that
+/// individual tests create with their own options.
+struct FrameNotifier(std::sync::mpsc::Sender<()>);
+
+impl RenderNotifier for FrameNotifier {
+    fn clone(&self) -> Box<dyn RenderNotifier> {
+        Box::new(FrameNotifier(self.0.clone()))
+    }
+    fn wake_up(&self, _: bool) {}
+    fn new_frame_ready(&self, _: DocumentId, _: bool, _: bool, _: FramePublishId) {
+        let _ = self.0.send(());
+    }
+}
+
+/// A renderer with its own API and document, for tests that need
+/// `WebRenderOptions` that differ from wrench's.
+struct TestRenderer {
+    renderer: webrender::Renderer,
+    api: RenderApi,
+    document_id: DocumentId,
+    pipeline_id: PipelineId,
+    size: DeviceIntSize,
+    rx: std::sync::mpsc::Receiver<()>,
+}
+
+impl TestRenderer {
+    fn new(window: &WindowWrapper, size: DeviceIntSize, opts: webrender::WebRenderOptions) -> Self {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let (renderer, sender) = webrender::create_webrender_instance(
+            window.clone_gl(),
+            Box::new(FrameNotifier(tx)),
+            opts,
+            None,
+        ).unwrap();
+        let mut api = sender.create_api();
+        let document_id = api.add_document(size);
+
+        TestRenderer {
+            renderer,
+            api,
+            document_id,
+            pipeline_id: PipelineId(0, 0),
+            size,
+            rx,
+        }
+    }
+
+    /// Returns a display list builder for the document's root pipeline.
+    fn builder(&self) -> DisplayListBuilder {
+        let mut builder = DisplayListBuilder::new(self.pipeline_id);
+        builder.begin();
+        builder
+    }
+
+    /// Returns the properties of an item with the given bounds in the root
+    /// scroll frame.
+    fn common_properties(&self, bounds: LayoutRect) -> CommonItemProperties {
+        CommonItemProperties::new(bounds, SpaceAndClipInfo::root_scroll(self.pipeline_id))
+    }
+
+    /// Sends the transaction with a request to generate a frame, and waits
+    /// for the frame to be published to the renderer.
+    fn send_frame(&mut self, mut txn: Transaction) {
+        txn.generate_frame(0, RenderReasons::TESTING);
+        self.api.send_transaction(self.document_id, txn);
+        self.rx.recv().unwrap();
+        self.renderer.update();
+    }
+
+    /// Sends the transaction with a request to generate a frame, then renders
+    /// the frame.
+    fn render(&mut self, txn: Transaction) -> RenderResults {
+        self.send_frame(txn);
+        self.renderer.render(self.size, 0).unwrap()
+    }
+
+    /// Sets the document's display list and renders it.
+    fn render_display_list(&mut self, epoch: Epoch, mut builder: DisplayListBuilder, mut txn: Transaction) -> RenderResults {
+        txn.set_root_pipeline(self.pipeline_id);
+        txn.set_display_list(epoch, builder.end());
+        self.render(txn)
+    }
+
+    /// Renders a display list holding a single 100x100 rect of the given
+    /// color in the top-left corner.
+    fn render_rect(&mut self, epoch: Epoch, color: ColorF) -> RenderResults {
+        let mut builder = self.builder();
+        let info = self.common_properties(rect(0., 0., 100., 100.).to_box2d());
+        builder.push_rect(&info, info.clip_rect, color);
+        self.render_display_list(epoch, builder, Transaction::new())
+    }
+
+    fn deinit(self) {
+        self.api.shut_down(true);
+        self.renderer.deinit();
+    }
+}
+
+/// A call made to a `MockCompositor`.
+#[derive(Clone, Debug, PartialEq)]
+enum CompositorCall {
+    BeginFrame,
+    EndFrame,
+    Bind(NativeTileId, DeviceIntRect),
+    AddSurface(NativeSurfaceId, DeviceIntRect, ClipRadius),
+    CreateExternalSurface(NativeSurfaceId, CompositeSurfaceFormat),
+}
+
+/// A native compositor that draws nothing and records the calls made to it.
+struct MockCompositor {
+    capabilities: CompositorCapabilities,
+    calls: Arc<Mutex<Vec<CompositorCall>>>,
+}
+
+impl MockCompositor {
+    fn new() -> Self {
+        MockCompositor {
+            capabilities: CompositorCapabilities {
+                virtual_surface_size: 1024 * 1024,
+                ..Default::default()
+            },
+            calls: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Returns a compositor config that composites through this compositor.
+    fn into_config(self) -> CompositorConfig {
+        CompositorConfig::Native {
+            compositor: Box::new(self),
+            max_tiles_per_frame: None,
+        }
+    }
+
+    fn record(&self, call: CompositorCall) {
+        self.calls.lock().unwrap().push(call);
+    }
+}
+
+impl Compositor for MockCompositor {
+    fn create_surface(&mut self, _: &mut Device, _: NativeSurfaceId, _: DeviceIntPoint, _: DeviceIntSize, _: bool) {}
+    fn create_external_surface(&mut self, _: &mut Device, id: NativeSurfaceId, format: CompositeSurfaceFormat) {
+        self.record(CompositorCall::CreateExternalSurface(id, format));
+    }
+    fn create_backdrop_surface(&mut self, _: &mut Device, _: NativeSurfaceId, _: ColorF) {}
+    fn destroy_surface(&mut self, _: &mut Device, _: NativeSurfaceId) {}
+    fn create_tile(&mut self, _: &mut Device, _: NativeTileId) {}
+    fn destroy_tile(&mut self, _: &mut Device, _: NativeTileId) {}
+    fn attach_external_image(&mut self, _: &mut Device, _: NativeSurfaceId, _: ExternalImageId) {}
+    fn bind(&mut self, _: &mut Device, id: NativeTileId, dirty_rect: DeviceIntRect, _: DeviceIntRect) -> NativeSurfaceInfo {
+        self.record(CompositorCall::Bind(id, dirty_rect));
+        NativeSurfaceInfo {
+            origin: DeviceIntPoint::zero(),
+            fbo_id: 0,
+        }
+    }
+    fn unbind(&mut self, _: &mut Device) {}
+    fn begin_frame(&mut self, _: &mut Device) {
+        self.record(CompositorCall::BeginFrame);
+    }
+    fn add_surface(
+        &mut self,
+        _: &mut Device,
+        id: NativeSurfaceId,
+        _: CompositorSurfaceTransform,
+        clip_rect: DeviceIntRect,
+        clip_radius: ClipRadius,
+        _: ImageRendering,
+    ) {
+        self.record(CompositorCall::AddSurface(id, clip_rect, clip_radius));
+    }
+    fn end_frame(&mut self, _: &mut Device) {
+        self.record(CompositorCall::EndFrame);
+    }
+    fn enable_native_compositor(&mut self, _: &mut Device, _: bool) {}
+    fn deinit(&mut self, _: &mut Device) {}
+    fn get_capabilities(&self, _: &mut Device) -> CompositorCapabilities {
+        self.capabilities
+    }
+    fn get_window_visibility(&self, _: &mut Device) -> WindowVisibility {
+        WindowVisibility::default()
     }
 }
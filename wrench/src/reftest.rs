@@ -103,7 +103,14 @@ pub struct Reftest {
     extra_checks: Vec<ExtraCheck>,
     allow_mipmaps: bool,
     force_subpixel_aa_where_possible: Option<bool>,
+    clip_aa_quality: Option<ClipAaQuality>,
     max_surface_override: Option<usize>,
+    global_saturation: Option<f32>,
+    edge_aa_feather: Option<f32>,
+    srgb_render_targets: bool,
+    clip_mask_msaa_samples: Option<u8>,
+    max_blur_raster_size: Option<DeviceIntSize>,
+    integer_scaling: Option<u32>,
 }
 
 impl Reftest {
@@ -360,7 +367,14 @@ impl ReftestManifest {
             let mut extra_checks = vec![];
             let mut allow_mipmaps = false;
             let mut force_subpixel_aa_where_possible = None;
+            let mut clip_aa_quality = None;
             let mut max_surface_override = None;
+            let mut global_saturation = None;
+            let mut edge_aa_feather = None;
+            let mut srgb_render_targets = false;
+            let mut clip_mask_msaa_samples = None;
+            let mut max_blur_raster_size = None;
+            let mut integer_scaling = None;
 
             let mut parse_command = |token: &str| -> bool {
                 match token {
@@ -368,6 +382,14 @@ impl ReftestManifest {
                         let (_, args, _) = parse_function(function);
                         force_subpixel_aa_where_possible = Some(args[0].parse().unwrap());
                     }
+                    function if function.starts_with("clip_aa_quality(") => {
+                        let (_, args, _) = parse_function(function);
+                        clip_aa_quality = Some(match args[0] {
+                            "standard" => ClipAaQuality::Standard,
+                            "high" => ClipAaQuality::High,
+                            quality => panic!("unknown clip AA quality {}", quality),
+                        });
+                    }
                     function if function.starts_with("fuzzy-range(") ||
                                 function.starts_with("fuzzy-range-if(") => {
                         let (_, mut args, _) = parse_function(function);
@@ -422,6 +444,33 @@ impl ReftestManifest {
                         let (_, args, _) = parse_function(function);
                         max_surface_override = Some(args[0].parse().unwrap());
                     }
+                    function if function.starts_with("global_saturation(") => {
+                        let (_, args, _) = parse_function(function);
+                        global_saturation = Some(args[0].parse().unwrap());
+                    }
+                    function if function.starts_with("edge_aa_feather(") => {
+                        let (_, args, _) = parse_function(function);
+                        edge_aa_feather = Some(args[0].parse().unwrap());
+                    }
+                    function if function.starts_with("clip_mask_msaa(") => {
+                        let (_, args, _) = parse_function(function);
+                        clip_mask_msaa_samples = Some(args[0].parse().unwrap());
+                    }
+                    function if function.starts_with("max_blur_raster_size(") => {
+                        let (_, args, _) = parse_function(function);
+                        max_blur_raster_size = Some(DeviceIntSize::new(
+                            args[0].parse().unwrap(),
+                            args[1].parse().unwrap(),
+                        ));
+                    }
+                    function if function.starts_with("integer_scaling(") => {
+                        let (_, args, _) = parse_function(function);
+                        integer_scaling = Some(args[0].parse().unwrap());
+                    }
+                    function if function.starts_with("srgb_render_targets(") => {
+                        let (_, args, _) = parse_function(function);
+                        srgb_render_targets = args[0].parse().unwrap();
+                    }
                     options if options.starts_with("options(") => {
                         let (_, args, _) = parse_function(options);
                         if args.iter().any(|arg| arg == &OPTION_DISABLE_SUBPX) {
@@ -540,7 +589,14 @@ impl ReftestManifest {
                 extra_checks,
                 allow_mipmaps,
                 force_subpixel_aa_where_possible,
+                clip_aa_quality,
                 max_surface_override,
+                global_saturation,
+                edge_aa_feather,
+                srgb_render_targets,
+                clip_mask_msaa_samples,
+                max_blur_raster_size,
+                integer_scaling,
             });
         }
 
@@ -735,6 +791,7 @@ impl<'a> ReftestHarness<'a> {
 
         let quality_settings = QualitySettings {
             force_subpixel_aa_where_possible: t.force_subpixel_aa_where_possible.unwrap_or_default(),
+            clip_aa_quality: t.clip_aa_quality.unwrap_or(ClipAaQuality::Standard),
         };
 
         self.wrench.set_quality_settings(quality_settings);
@@ -765,6 +822,47 @@ impl<'a> ReftestHarness<'a> {
         let mut images = vec![];
         let mut results = vec![];
 
+        // The global saturation only applies to the test images.
+        if let Some(saturation) = t.global_saturation {
+            self.wrench.renderer.set_global_saturation(saturation);
+        }
+
+        // As does the edge AA feather.
+        if let Some(feather) = t.edge_aa_feather {
+            self.wrench.renderer.set_edge_aa_feather(feather);
+        }
+
+        // As do sRGB render targets.
+        if t.srgb_render_targets {
+            self.wrench
+                .api
+                .send_debug_cmd(
+                    DebugCommand::SetSrgbRenderTargets(true)
+                );
+        }
+
+        // And multisampled clip masks.
+        if let Some(samples) = t.clip_mask_msaa_samples {
+            self.wrench.renderer.set_clip_mask_msaa_samples(samples);
+        }
+
+        // And the blur raster size cap.
+        if t.max_blur_raster_size.is_some() {
+            self.wrench
+                .api
+                .send_debug_cmd(
+                    DebugCommand::SetMaxBlurRasterSize(t.max_blur_raster_size)
+                );
+        }
+
+        // And integer scaling. The document is sized so that it fits the
+        // window once scaled. With a factor of 2 that leaves less than a
+        // pixel to center it in, so it stays in the top left corner.
+        if let Some(factor) = t.integer_scaling {
+            self.wrench.renderer.set_integer_scaling(Some(factor));
+            self.set_document_size(window_size / factor as i32);
+        }
+
         match t.op {
             ReftestOp::Equal | ReftestOp::NotEqual => {
                 // For equality tests, render each test image and store result
@@ -813,6 +911,39 @@ impl<'a> ReftestHarness<'a> {
             }
         }
 
+        if t.global_saturation.is_some() {
+            self.wrench.renderer.set_global_saturation(1.0);
+        }
+
+        if t.edge_aa_feather.is_some() {
+            self.wrench.renderer.set_edge_aa_feather(1.0);
+        }
+
+        if t.srgb_render_targets {
+            self.wrench
+                .api
+                .send_debug_cmd(
+                    DebugCommand::SetSrgbRenderTargets(false)
+                );
+        }
+
+        if t.clip_mask_msaa_samples.is_some() {
+            self.wrench.renderer.set_clip_mask_msaa_samples(0);
+        }
+
+        if t.max_blur_raster_size.is_some() {
+            self.wrench
+                .api
+                .send_debug_cmd(
+                    DebugCommand::SetMaxBlurRasterSize(None)
+                );
+        }
+
+        if t.integer_scaling.is_some() {
+            self.wrench.renderer.set_integer_scaling(None);
+            self.set_document_size(window_size);
+        }
+
         let reference = if let Some(image) = reference_image {
             let save_all_png = false; // flip to true to update all the tests!
             if save_all_png {
@@ -921,6 +1052,12 @@ impl<'a> ReftestHarness<'a> {
         }
     }
 
+    fn set_document_size(&mut self, size: DeviceIntSize) {
+        let mut txn = Transaction::new();
+        txn.set_document_view(DeviceIntRect::from_size(size));
+        self.wrench.api.send_transaction(self.wrench.document_id, txn);
+    }
+
     fn render_yaml(
         &mut self,
         filename: &Path,
@@ -51,6 +51,7 @@ impl<'a> TestHarness<'a> {
         self.test_basic();
         self.test_composite_nop();
         self.test_scroll_subpic();
+        self.test_composite_opacity();
     }
 
     /// Simple validation / proof of concept of invalidation testing
@@ -129,6 +130,31 @@ impl<'a> TestHarness<'a> {
         );
     }
 
+    /// Ensure that fading a cached surface is applied at composite time,
+    /// without rasterizing the tiles again
+    fn test_composite_opacity(
+        &mut self,
+    ) {
+        // First frame, fully opaque
+        let results = self.render_yaml("composite_opacity_1");
+
+        // Ensure we actually rendered something
+        assert!(
+            matches!(results.pc_debug.slice(0).tile(0, 0), TileDebugInfo::Dirty(..)),
+            "Ensure the first test frame actually rendered something",
+        );
+
+        // Second frame only changes the animated opacity
+        let results = self.render_yaml("composite_opacity_2");
+
+        // Ensure the cache tile was not invalidated, but a composite is still required
+        assert!(
+            results.pc_debug.slice(0).tile(0, 0).is_valid(),
+            "Ensure the cache tile was not invalidated by the opacity change",
+        );
+        assert!(results.composite_needed);
+    }
+
     /// Render a YAML file, and return the picture cache debug info
     fn render_yaml(
         &mut self,
@@ -258,6 +258,8 @@ impl Wrench {
             // SWGL doesn't support the GL_ALWAYS depth comparison function used by
             // `clear_caches_with_quads`, but scissored clears work well.
             clear_caches_with_quads: !window.is_software(),
+            // Needed for rawtest::test_low_priority_scene_build.
+            support_low_priority_transactions: true,
             ..Default::default()
         };
 
@@ -511,6 +513,9 @@ impl Wrench {
         flags: FontInstanceFlags,
         render_mode: Option<FontRenderMode>,
         synthetic_italics: SyntheticItalics,
+        stroke: Option<FontStroke>,
+        gamma: Option<u16>,
+        contrast: Option<u8>,
     ) -> FontInstanceKey {
         let key = self.api.generate_font_instance_key();
         let mut txn = Transaction::new();
@@ -520,6 +525,9 @@ impl Wrench {
             options.render_mode = render_mode;
         }
         options.synthetic_italics = synthetic_italics;
+        options.stroke = stroke.unwrap_or(FontStroke::NONE);
+        options.gamma = gamma.unwrap_or(FontInstanceOptions::DEFAULT_GAMMA);
+        options.contrast = contrast.unwrap_or(FontInstanceOptions::DEFAULT_CONTRAST);
         txn.add_font_instance(key, font_key, size, Some(options), None, Vec::new());
         self.api.send_transaction(self.document_id, txn);
         key
@@ -603,6 +611,13 @@ impl Wrench {
             "C - Save a capture to captures/wrench/",
             "X - Do a hit test at the current cursor position",
             "Y - Clear all caches",
+            "K - Halve the depth id budget (wraps to the device max)",
+            "A - Toggle the experimental alpha depth prepass",
+            "U - Toggle drawing only opaque batches (transparent content goes missing)",
+            "E - Cycle drawing only the batches of a single kind",
+            "N - Toggle the layout debug grid",
+            "J - Double the layout debug grid spacing (wraps to 4px)",
+            "F - Toggle showing clip bounds",
         ];
 
         let color_and_offset = [(ColorF::BLACK, 2.0), (ColorF::WHITE, 0.0)];
@@ -330,7 +330,7 @@ pub struct YamlFrameReader {
     image_map: HashMap<(PathBuf, Option<i64>), (ImageKey, LayoutSize)>,
 
     fonts: HashMap<FontDescriptor, FontKey>,
-    font_instances: HashMap<(FontKey, FontSize, FontInstanceFlags, SyntheticItalics), FontInstanceKey>,
+    font_instances: HashMap<(FontKey, FontSize, FontInstanceFlags, SyntheticItalics, Option<FontStroke>, Option<u16>, Option<u8>), FontInstanceKey>,
     font_render_mode: Option<FontRenderMode>,
     allow_mipmaps: bool,
 
@@ -563,7 +563,7 @@ impl YamlFrameReader {
         }
 
         if self.list_resources { println!("{}", file.to_string_lossy()); }
-        let (descriptor, image_data) = match image::open(file) {
+        let (mut descriptor, image_data) = match image::open(file) {
             Ok(image) => {
                 let (image_width, image_height) = image.dimensions();
                 let (format, bytes) = match image {
@@ -594,9 +594,6 @@ impl YamlFrameReader {
                 if is_image_opaque(format, &bytes[..]) {
                     flags |= ImageDescriptorFlags::IS_OPAQUE;
                 }
-                if self.allow_mipmaps {
-                    flags |= ImageDescriptorFlags::ALLOW_MIPMAPS;
-                }
                 let descriptor = ImageDescriptor::new(
                     image_width as i32,
                     image_height as i32,
@@ -672,6 +669,14 @@ impl YamlFrameReader {
                 }
             }
         };
+
+        if self.allow_mipmaps {
+            descriptor.flags |= ImageDescriptorFlags::ALLOW_MIPMAPS;
+        }
+        if item["anisotropic-filtering"].as_bool().unwrap_or(false) {
+            descriptor.flags |= ImageDescriptorFlags::ANISOTROPIC_FILTERING;
+        }
+
         let tiling = tiling.map(|tile_size| tile_size as u16);
         let image_key = wrench.api.generate_image_key();
         let mut txn = Transaction::new();
@@ -748,12 +753,15 @@ impl YamlFrameReader {
         size: f32,
         flags: FontInstanceFlags,
         synthetic_italics: SyntheticItalics,
+        stroke: Option<FontStroke>,
+        gamma: Option<u16>,
+        contrast: Option<u8>,
         wrench: &mut Wrench,
     ) -> FontInstanceKey {
         let font_render_mode = self.font_render_mode;
 
         *self.font_instances
-            .entry((font_key, size.into(), flags, synthetic_italics))
+            .entry((font_key, size.into(), flags, synthetic_italics, stroke, gamma, contrast))
             .or_insert_with(|| {
                 wrench.add_font_instance(
                     font_key,
@@ -761,6 +769,9 @@ impl YamlFrameReader {
                     flags,
                     font_render_mode,
                     synthetic_italics,
+                    stroke,
+                    gamma,
+                    contrast,
                 )
             })
     }
@@ -806,7 +817,29 @@ impl YamlFrameReader {
 
         let bounds = self.resolve_rect(&item[bounds_key]);
         let color = self.resolve_colorf(&item["color"]).unwrap_or(ColorF::BLACK);
-        dl.push_rect(info, bounds, color);
+        match item["opacity"].as_f32() {
+            Some(opacity) => dl.push_rect_with_opacity(info, bounds, color, opacity),
+            None => dl.push_rect(info, bounds, color),
+        }
+    }
+
+    fn handle_highlight(
+        &self,
+        dl: &mut DisplayListBuilder,
+        item: &Yaml,
+        info: &CommonItemProperties,
+    ) {
+        let rects: Vec<LayoutRect> = item["rects"]
+            .as_vec()
+            .expect("highlight type must have rects")
+            .iter()
+            .map(|rect| self.resolve_rect(rect))
+            .collect();
+        let color = self.resolve_colorf(&item["color"]).unwrap_or(ColorF::BLACK);
+        let blend_mode = item["mix-blend-mode"]
+            .as_mix_blend_mode()
+            .unwrap_or(MixBlendMode::Normal);
+        dl.push_highlight(info, &rects, color, blend_mode);
     }
 
     fn handle_clear_rect(
@@ -1143,7 +1176,14 @@ impl YamlFrameReader {
             None
         };
         if let Some(details) = border_details {
-            dl.push_border(info, bounds, widths, details);
+            let dash_phase = item["dash-phase"].as_f32().unwrap_or(0.0);
+            dl.push_border_with_dash_phase(
+                info,
+                bounds,
+                widths,
+                details,
+                PropertyBinding::Value(dash_phase),
+            );
         }
     }
 
@@ -1324,19 +1364,65 @@ impl YamlFrameReader {
         let color = item["color"]
             .as_colorf()
             .unwrap_or_else(|| ColorF::WHITE);
+        let wrap_mode = match item["wrap-mode"].as_str() {
+            Some("clamp") | None => ImageWrapMode::Clamp,
+            Some("repeat") => ImageWrapMode::Repeat,
+            Some("mirror") => ImageWrapMode::Mirror,
+            Some(_) => panic!(
+                "ImageWrapMode can be clamp, repeat or mirror -- got {:?}",
+                item
+            ),
+        };
+        let repeat_mode = match item["repeat"].as_str() {
+            Some("both") | None => ImageRepeatMode::Both,
+            Some("x") => ImageRepeatMode::X,
+            Some("y") => ImageRepeatMode::Y,
+            Some("none") => ImageRepeatMode::None,
+            Some(_) => panic!(
+                "ImageRepeatMode can be both, x, y or none -- got {:?}",
+                item
+            ),
+        };
+        let opacity = item["opacity"].as_f32();
         let stretch_size = item["stretch-size"].as_size();
         let tile_spacing = item["tile-spacing"].as_size();
-        if stretch_size.is_none() && tile_spacing.is_none() {
-            dl.push_image(
+        if stretch_size.is_none() && tile_spacing.is_none() && repeat_mode == ImageRepeatMode::Both {
+            match opacity {
+                Some(opacity) => dl.push_image_with_opacity(
+                    info,
+                    bounds,
+                    rendering,
+                    alpha_type,
+                    image_key,
+                    color,
+                    opacity,
+                ),
+                None => dl.push_image(
+                    info,
+                    bounds,
+                    rendering,
+                    alpha_type,
+                    image_key,
+                    color,
+                ),
+            }
+        } else if opacity.is_some() {
+            panic!("opacity is only supported on non-repeating images -- got {:?}", item);
+        } else if repeat_mode != ImageRepeatMode::Both {
+            assert_eq!(wrap_mode, ImageWrapMode::Clamp, "repeat can't be combined with wrap-mode");
+            dl.push_repeating_image_with_repeat_mode(
                 info,
                 bounds,
+                stretch_size.unwrap_or(image_dims),
+                tile_spacing.unwrap_or_else(LayoutSize::zero),
                 rendering,
                 alpha_type,
                 image_key,
                 color,
+                repeat_mode,
            );
         } else {
-            dl.push_repeating_image(
+            dl.push_repeating_image_with_wrap_mode(
                 info,
                 bounds,
                 stretch_size.unwrap_or(image_dims),
@@ -1345,6 +1431,7 @@ impl YamlFrameReader {
                 alpha_type,
                 image_key,
                 color,
+                wrap_mode,
            );
         }
     }
@@ -1365,6 +1452,12 @@ impl YamlFrameReader {
         } else {
             SyntheticItalics::disabled()
         };
+        let stroke = item["stroke-width"].as_f32().map(|width| {
+            let stroke_color = item["stroke-color"].as_colorf().unwrap_or(ColorF::BLACK);
+            FontStroke::new(width, stroke_color)
+        });
+        let gamma = item["gamma"].as_f32().map(|gamma| (gamma * 100.0).round() as u16);
+        let contrast = item["contrast"].as_f32().map(|contrast| (contrast * 100.0).round() as u8);
 
         let mut flags = FontInstanceFlags::empty();
         if item["synthetic-bold"].as_bool().unwrap_or(false) {
@@ -1382,6 +1475,9 @@ impl YamlFrameReader {
         if item["flip-y"].as_bool().unwrap_or(false) {
             flags |= FontInstanceFlags::FLIP_Y;
         }
+        if item["no-subpixel-over-transparent"].as_bool().unwrap_or(false) {
+            flags |= FontInstanceFlags::NO_SUBPIXEL_OVER_TRANSPARENT;
+        }
 
         assert!(
             item["blur-radius"].is_badvalue(),
@@ -1394,6 +1490,9 @@ impl YamlFrameReader {
                                                                  size,
                                                                  flags,
                                                                  synthetic_italics,
+                                                                 stroke,
+                                                                 gamma,
+                                                                 contrast,
                                                                  wrench);
 
         assert!(
@@ -1458,6 +1557,30 @@ impl YamlFrameReader {
             (glyphs, bounds)
         };
 
+        if let Some(path) = item["path"].as_vec() {
+            // Glyph positions are interpreted as distances along the path,
+            // so the origin should normally be left at zero.
+            let path: Vec<LayoutPoint> = path
+                .iter()
+                .map(|point| point.as_point().expect("path points must be points"))
+                .collect();
+            let glyph_bounds = item["glyph-bounds"].as_rect().unwrap_or_else(|| {
+                LayoutRect::new(LayoutPoint::new(-size, -size), LayoutPoint::new(size, size))
+            });
+            let key = self.next_spatial_key();
+            dl.push_text_on_path(
+                info,
+                glyph_bounds,
+                &glyphs,
+                &path,
+                font_instance_key,
+                color,
+                None,
+                key,
+            );
+            return;
+        }
+
         dl.push_text(
             info,
             rect,
@@ -1540,6 +1663,7 @@ impl YamlFrameReader {
                 ("backface-visible", PrimitiveFlags::IS_BACKFACE_VISIBLE),
                 ("scrollbar-container", PrimitiveFlags::IS_SCROLLBAR_CONTAINER),
                 ("prefer-compositor-surface", PrimitiveFlags::PREFER_COMPOSITOR_SURFACE),
+                ("snap-to-device-pixels", PrimitiveFlags::SNAP_TO_DEVICE_PIXELS),
             ] {
                 if let Some(value) = item[key].as_bool() {
                     flags.set(flag, value);
@@ -1558,6 +1682,7 @@ impl YamlFrameReader {
                 "rect" => self.handle_rect(dl, item, &info),
                 "hit-test" => self.handle_hit_test(dl, item, &mut info),
                 "clear-rect" => self.handle_clear_rect(dl, item, &info),
+                "highlight" => self.handle_highlight(dl, item, &info),
                 "line" => self.handle_line(dl, item, &mut info),
                 "image" => self.handle_image(dl, wrench, item, &mut info),
                 "yuv-image" => self.handle_yuv_image(dl, wrench, item, &mut info),
@@ -1874,6 +1999,15 @@ impl YamlFrameReader {
             }
         };
 
+        // Either compose the transform origin into the transform here, or
+        // let WebRender apply it.
+        let (transform_origin, builder_transform_origin) =
+            if yaml["webrender-transform-origin"].as_bool().unwrap_or(false) {
+                (LayoutPoint::zero(), transform_origin)
+            } else {
+                (transform_origin, LayoutPoint::zero())
+            };
+
         let transform = yaml["transform"]
             .as_transform(&transform_origin);
 
@@ -1885,13 +2019,14 @@ impl YamlFrameReader {
             _ => yaml["perspective"].as_matrix4d(),
         };
 
-        let reference_frame_id = dl.push_reference_frame(
+        let reference_frame_id = dl.push_reference_frame_with_transform_origin(
             bounds.min,
             *self.spatial_id_stack.last().unwrap(),
             transform_style,
             transform.or(perspective).unwrap_or_default().into(),
             reference_frame_kind,
             self.next_spatial_key(),
+            builder_transform_origin,
         );
 
         let numeric_id = yaml["id"].as_i64();
@@ -868,6 +868,9 @@ impl YamlHelper for Yaml {
                 ("blur", ref args, _) if args.len() == 2 => {
                     Some(FilterOp::Blur(args[0].parse().unwrap(), args[1].parse().unwrap()))
                 }
+                ("cheap-blur", ref args, _) if args.len() == 1 => {
+                    Some(FilterOp::CheapBlur(args[0].parse().unwrap()))
+                }
                 ("brightness", ref args, _) if args.len() == 1 => {
                     Some(FilterOp::Brightness(args[0].parse().unwrap()))
                 }
@@ -887,6 +890,12 @@ impl YamlHelper for Yaml {
                     let amount: f32 = args[0].parse().unwrap();
                     Some(FilterOp::Opacity(amount.into(), amount))
                 }
+                ("opacity", ref args, _) if args.len() == 2 => {
+                    // An animated opacity, with a default value and binding id
+                    let amount: f32 = args[0].parse().unwrap();
+                    let key = PropertyBindingKey::new(args[1].parse().unwrap());
+                    Some(FilterOp::Opacity(PropertyBinding::Binding(key, amount), amount))
+                }
                 ("saturate", ref args, _) if args.len() == 1 => {
                     Some(FilterOp::Saturate(args[0].parse().unwrap()))
                 }
@@ -905,6 +914,19 @@ impl YamlHelper for Yaml {
                         color: yaml["color"].as_colorf().unwrap()
                     }))
                 }
+                ("drop-shadows", ref args, _) if args.len() % 3 == 0 => {
+                    let shadows: Vec<Shadow> = args.chunks(3).map(|shadow| {
+                        let str = format!("---\noffset: {}\nblur-radius: {}\ncolor: {}\n", shadow[0], shadow[1], shadow[2]);
+                        let mut yaml_doc = YamlLoader::load_from_str(&str).expect("Failed to parse drop-shadows");
+                        let yaml = yaml_doc.pop().unwrap();
+                        Shadow {
+                            offset: yaml["offset"].as_vector().unwrap(),
+                            blur_radius: yaml["blur-radius"].as_f32().unwrap(),
+                            color: yaml["color"].as_colorf().unwrap()
+                        }
+                    }).collect();
+                    Some(FilterOp::drop_shadows(&shadows))
+                }
                 ("color-matrix", ref args, _) if args.len() == 20 => {
                     let m: Vec<f32> = args.iter().map(|f| f.parse().unwrap()).collect();
                     let mut matrix: [f32; 20] = [0.0; 20];